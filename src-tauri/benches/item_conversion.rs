@@ -0,0 +1,80 @@
+//! Serving an item: the JSON fast path against the typed conversion
+//!
+//! Run with `cargo bench --bench item_conversion`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use serde_json::{json, Map, Value};
+use zenstac_lib::config::Config;
+use zenstac_lib::database::models::DbItem;
+use zenstac_lib::server::utils::ServerConfig;
+
+/// A stored item with `asset_count` assets, shaped like a Sentinel-2 scene
+fn sample_item(asset_count: usize) -> DbItem {
+    let assets: Map<String, Value> = (0..asset_count)
+        .map(|i| {
+            (
+                format!("B{:02}", i),
+                json!({
+                    "href": format!("https://example.com/S2A_T32TQM/B{:02}.tif", i),
+                    "title": format!("Band {}", i),
+                    "type": "image/tiff; application=geotiff; profile=cloud-optimized",
+                    "roles": ["data"],
+                    "eo:bands": [{ "name": format!("B{:02}", i), "center_wavelength": 0.665 }],
+                    "raster:bands": [{ "nodata": 0, "data_type": "uint16", "scale": 0.0001 }]
+                }),
+            )
+        })
+        .collect();
+    serde_json::from_value(json!({
+        "id": "S2A_T32TQM_20240501",
+        "collection_id": "sentinel-2-l2a",
+        "type": "Feature",
+        "stac_version": "1.0.0",
+        "stac_extensions": [
+            "https://stac-extensions.github.io/eo/v1.1.0/schema.json",
+            "https://stac-extensions.github.io/raster/v1.1.0/schema.json"
+        ],
+        "geometry": {
+            "type": "Polygon",
+            "coordinates": [[[10.0, 45.0], [11.4, 45.0], [11.4, 46.0], [10.0, 46.0], [10.0, 45.0]]]
+        },
+        "bbox": [10.0, 45.0, 11.4, 46.0],
+        "properties": {
+            "datetime": "2024-05-01T10:20:31Z",
+            "platform": "sentinel-2a",
+            "instruments": ["msi"],
+            "eo:cloud_cover": 12.5,
+            "proj:epsg": 32632
+        },
+        "links": [],
+        "assets": assets,
+        "created_at": "2024-05-02T00:00:00Z",
+        "updated_at": "2024-05-02T00:00:00Z"
+    }))
+    .expect("sample item")
+}
+
+fn item_conversion(c: &mut Criterion) {
+    let server_config = ServerConfig::from_config(&Config::default());
+    let item = sample_item(20);
+
+    let mut group = c.benchmark_group("serve_item_20_assets");
+    group.bench_function("typed", |b| {
+        b.iter_batched(
+            || item.clone(),
+            |item| serde_json::to_string(&item.to_stac_item_with_relations(&server_config, &[])),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("json", |b| {
+        b.iter_batched(
+            || item.clone(),
+            |item| serde_json::to_string(&item.into_stac_item_json(&server_config, &[])),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, item_conversion);
+criterion_main!(benches);
@@ -0,0 +1,442 @@
+use crate::cli::{db_collection_from_json, db_item_from_json};
+use crate::database::migrations::has_stable_hashes;
+use crate::database::sync_revisions::content_hash;
+use crate::database::{DatabaseService, DbCollection, DbItem};
+use rusqlite::{OpenFlags, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Catalog name for the database the app is running on
+pub const LIVE_CATALOG: &str = "live";
+
+/// Content hashes of one catalog's collections and items, keyed by id, and
+/// where to read the records that differ
+pub struct CatalogSnapshot {
+    collections: BTreeMap<String, String>,
+    items: BTreeMap<(String, String), String>,
+    records: Records,
+}
+
+/// Where a snapshot's records are read from when changes are applied
+enum Records {
+    Live(DatabaseService),
+    Database(Mutex<rusqlite::Connection>),
+    /// An export file has no stored hashes, so it is read in full
+    Export {
+        collections: BTreeMap<String, DbCollection>,
+        items: BTreeMap<(String, String), DbItem>,
+    },
+}
+
+impl CatalogSnapshot {
+    /// Loads `live`, another ZenSTAC database file or a `zenstac export` file
+    pub async fn load(catalog: &str, live: &DatabaseService) -> Result<Self, String> {
+        if catalog == LIVE_CATALOG {
+            return Self::from_service(live).await;
+        }
+        let path = Path::new(catalog);
+        if !path.is_file() {
+            return Err(format!("Catalog '{}' not found", catalog));
+        }
+        if is_sqlite_file(path) {
+            Self::from_database_file(path)
+        } else {
+            Self::from_export_file(path)
+        }
+    }
+
+    /// Reads the stored hashes only; records are read when applied
+    async fn from_service(db_service: &DatabaseService) -> Result<Self, String> {
+        let mut snapshot = Self {
+            collections: db_service
+                .collections
+                .content_hashes()
+                .await
+                .map_err(|e| e.to_string())?,
+            items: BTreeMap::new(),
+            records: Records::Live(db_service.clone()),
+        };
+        for collection_id in snapshot.collections.keys() {
+            let items = db_service
+                .items
+                .content_hashes(collection_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            for (item_id, hash) in items {
+                snapshot
+                    .items
+                    .insert((collection_id.clone(), item_id), hash);
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Reads another database without modifying it. Records without a stored
+    /// hash, such as all of those of a database from an older release, are
+    /// hashed from their data.
+    fn from_database_file(path: &Path) -> Result<Self, String> {
+        let conn = rusqlite::Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut collections = BTreeMap::new();
+        let mut items = BTreeMap::new();
+        read_hashes(&conn, &mut collections, &mut items)?;
+        Ok(Self {
+            collections,
+            items,
+            records: Records::Database(Mutex::new(conn)),
+        })
+    }
+
+    fn from_export_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let document: Value = serde_json::from_str(&content)
+            .map_err(|e| format!("{} is not a database or STAC export: {}", path.display(), e))?;
+
+        let mut collections = BTreeMap::new();
+        let mut items = BTreeMap::new();
+        for value in document
+            .get("collections")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let collection = db_collection_from_json(value)?;
+            collections.insert(collection.id.clone(), collection);
+        }
+        for value in document
+            .get("features")
+            .and_then(|f| f.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let item = db_item_from_json(value, None)?;
+            items.insert((item.collection_id.clone(), item.id.clone()), item);
+        }
+        Ok(Self {
+            collections: collections
+                .iter()
+                .map(|(id, collection)| (id.clone(), content_hash(collection)))
+                .collect(),
+            items: items
+                .iter()
+                .map(|(key, item)| (key.clone(), content_hash(item)))
+                .collect(),
+            records: Records::Export { collections, items },
+        })
+    }
+
+    /// Reads a collection of the catalog
+    async fn collection(&self, id: &str) -> Result<Option<DbCollection>, String> {
+        match &self.records {
+            Records::Live(db_service) => db_service
+                .collections
+                .get_by_id(id)
+                .await
+                .map_err(|e| e.to_string()),
+            Records::Database(conn) => {
+                let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+                let data: Option<String> = conn
+                    .query_row("SELECT data FROM collections WHERE id = ?", [id], |row| {
+                        row.get(0)
+                    })
+                    .optional()
+                    .map_err(|e| e.to_string())?;
+                data.map(|data| parse_collection(Some(&data))).transpose()
+            }
+            Records::Export { collections, .. } => Ok(collections.get(id).cloned()),
+        }
+    }
+
+    /// Reads an item of the catalog
+    async fn item(&self, collection_id: &str, item_id: &str) -> Result<Option<DbItem>, String> {
+        match &self.records {
+            Records::Live(db_service) => db_service
+                .items
+                .get_by_id(collection_id, item_id)
+                .await
+                .map_err(|e| e.to_string()),
+            Records::Database(conn) => {
+                let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+                let data: Option<String> = conn
+                    .query_row(
+                        "SELECT data FROM items WHERE collection_id = ? AND id = ?",
+                        [collection_id, item_id],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .map_err(|e| e.to_string())?;
+                data.map(|data| parse_item(collection_id, item_id, Some(&data)))
+                    .transpose()
+            }
+            Records::Export { items, .. } => Ok(items
+                .get(&(collection_id.to_string(), item_id.to_string()))
+                .cloned()),
+        }
+    }
+}
+
+fn parse_collection(data: Option<&str>) -> Result<DbCollection, String> {
+    serde_json::from_str(data.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+fn parse_item(collection_id: &str, item_id: &str, data: Option<&str>) -> Result<DbItem, String> {
+    let mut item: DbItem =
+        serde_json::from_str(data.unwrap_or_default()).map_err(|e| e.to_string())?;
+    item.id = item_id.to_string();
+    item.collection_id = collection_id.to_string();
+    Ok(item)
+}
+
+/// Database that changes are applied to: the live one or another database
+/// file. Export files are read-only.
+pub async fn open_target(catalog: &str, live: &DatabaseService) -> Result<DatabaseService, String> {
+    if catalog == LIVE_CATALOG {
+        return Ok(live.clone());
+    }
+    if !is_sqlite_file(Path::new(catalog)) {
+        return Err("Changes can only be applied to a database, not an export file".to_string());
+    }
+    DatabaseService::new(catalog).await.map_err(|e| e.to_string())
+}
+
+fn is_sqlite_file(path: &Path) -> bool {
+    use std::io::Read;
+    let mut header = [0u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .map(|_| &header == b"SQLite format 3\0")
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Only in the source
+    Added,
+    /// In both with different content
+    Changed,
+    /// Only in the target
+    Removed,
+}
+
+/// A collection or item that differs between the catalogs
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordChange {
+    pub change: ChangeKind,
+    pub collection_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+}
+
+/// What it takes to turn the target catalog into the source
+#[derive(Debug, Default, Serialize)]
+pub struct CatalogDiff {
+    pub collections: Vec<RecordChange>,
+    pub items: Vec<RecordChange>,
+    /// Records present in both with the same content
+    pub unchanged: usize,
+    /// Whether the changes were written to the target
+    pub applied: bool,
+}
+
+/// Compares two catalogs by id and content hash
+pub fn diff(source: &CatalogSnapshot, target: &CatalogSnapshot) -> CatalogDiff {
+    let mut result = CatalogDiff::default();
+
+    for (id, hash) in &source.collections {
+        let change = match target.collections.get(id) {
+            None => Some(ChangeKind::Added),
+            Some(other) if hash != other => Some(ChangeKind::Changed),
+            Some(_) => None,
+        };
+        match change {
+            Some(change) => result.collections.push(RecordChange {
+                change,
+                collection_id: id.clone(),
+                item_id: None,
+            }),
+            None => result.unchanged += 1,
+        }
+    }
+    for id in target.collections.keys() {
+        if !source.collections.contains_key(id) {
+            result.collections.push(RecordChange {
+                change: ChangeKind::Removed,
+                collection_id: id.clone(),
+                item_id: None,
+            });
+        }
+    }
+
+    for (key, hash) in &source.items {
+        let change = match target.items.get(key) {
+            None => Some(ChangeKind::Added),
+            Some(other) if hash != other => Some(ChangeKind::Changed),
+            Some(_) => None,
+        };
+        match change {
+            Some(change) => result.items.push(RecordChange {
+                change,
+                collection_id: key.0.clone(),
+                item_id: Some(key.1.clone()),
+            }),
+            None => result.unchanged += 1,
+        }
+    }
+    for key in target.items.keys() {
+        if !source.items.contains_key(key) {
+            result.items.push(RecordChange {
+                change: ChangeKind::Removed,
+                collection_id: key.0.clone(),
+                item_id: Some(key.1.clone()),
+            });
+        }
+    }
+
+    result
+}
+
+/// Writes a diff into the target database: added and changed records are
+/// copied from the source, removed ones are deleted only when `delete_removed`
+/// is set so a merge never loses records by default
+pub async fn apply(
+    diff: &CatalogDiff,
+    source: &CatalogSnapshot,
+    target: &DatabaseService,
+    delete_removed: bool,
+) -> Result<(), String> {
+    for change in &diff.collections {
+        if change.change == ChangeKind::Removed {
+            continue;
+        }
+        let fail = |e: String| format!("Collection {}: {}", change.collection_id, e);
+        let collection = source.collection(&change.collection_id).await.map_err(fail)?;
+        match (change.change, collection) {
+            (ChangeKind::Added, Some(collection)) => target.collections.create(&collection).await,
+            (ChangeKind::Changed, Some(collection)) => target.collections.update(&collection).await,
+            _ => Ok(()),
+        }
+        .map_err(|e| fail(e.to_string()))?;
+    }
+
+    for change in &diff.items {
+        let item_id = change.item_id.clone().unwrap_or_default();
+        let fail = |e: String| format!("Item {}/{}: {}", change.collection_id, item_id, e);
+        let item = match change.change {
+            ChangeKind::Removed => None,
+            _ => source.item(&change.collection_id, &item_id).await.map_err(fail)?,
+        };
+        match (change.change, item) {
+            (ChangeKind::Added, Some(item)) => target.items.create(&item).await,
+            (ChangeKind::Changed, Some(item)) => target.items.update(&item).await,
+            (ChangeKind::Removed, _) if delete_removed => {
+                target.items.delete(&change.collection_id, &item_id).await
+            }
+            _ => Ok(()),
+        }
+        .map_err(|e| fail(e.to_string()))?;
+    }
+
+    // Collections go last so their removed items are already gone
+    if delete_removed {
+        for change in &diff.collections {
+            if change.change == ChangeKind::Removed {
+                target
+                    .collections
+                    .delete(&change.collection_id)
+                    .await
+                    .map_err(|e| format!("Collection {}: {}", change.collection_id, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Hashes of the live collections and items of a ZenSTAC database file
+fn read_hashes(
+    conn: &rusqlite::Connection,
+    collections: &mut BTreeMap<String, String>,
+    items: &mut BTreeMap<(String, String), String>,
+) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, {} FROM collections{}",
+            hash_columns(conn, "collections"),
+            live_condition(conn, "collections")
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (id, hash, data) = row.map_err(|e| e.to_string())?;
+        let hash = match hash {
+            Some(hash) => hash,
+            None => content_hash(&parse_collection(data.as_deref())?),
+        };
+        collections.insert(id, hash);
+    }
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, collection_id, {} FROM items{}",
+            hash_columns(conn, "items"),
+            live_condition(conn, "items")
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (id, collection_id, hash, data) = row.map_err(|e| e.to_string())?;
+        let hash = match hash {
+            Some(hash) => hash,
+            None => content_hash(&parse_item(&collection_id, &id, data.as_deref())?),
+        };
+        items.insert((collection_id, id), hash);
+    }
+    Ok(())
+}
+
+/// Columns with a record's stored hash and, when there is none or it was
+/// made before hashes were stable, its data
+fn hash_columns(conn: &rusqlite::Connection, table: &str) -> &'static str {
+    let has_hashes = conn
+        .prepare(&format!("SELECT content_hash FROM {} LIMIT 0", table))
+        .is_ok();
+    if has_hashes && has_stable_hashes(conn) {
+        "content_hash, CASE WHEN content_hash IS NULL THEN data END"
+    } else {
+        "NULL, data"
+    }
+}
+
+/// WHERE clause leaving out records in the trash, for databases old enough
+/// not to have one
+fn live_condition(conn: &rusqlite::Connection, table: &str) -> &'static str {
+    let has_trash = conn
+        .prepare(&format!("SELECT deleted_at FROM {} LIMIT 0", table))
+        .is_ok();
+    if has_trash {
+        " WHERE deleted_at IS NULL"
+    } else {
+        ""
+    }
+}
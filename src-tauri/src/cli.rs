@@ -0,0 +1,673 @@
+use crate::config::{close_memory_database, copy_dir, Config, DatabaseLocation};
+use crate::database::{spatial_index, DatabaseService, DbCollection, DbItem};
+use crate::models::{Collection, Item};
+use crate::server::demo::apply_demo_preset;
+use crate::server::helpers::calculate_bbox_for_geometry;
+use crate::server::ingest::prepare_item;
+use crate::server::ingest_hooks::IngestEvent;
+use crate::server::item_schema::describe_violations;
+use crate::server::processing::{record_lineage, stamp_processing, ProcessingRecord};
+use crate::server::stac_validation::validate_stac_collection;
+use crate::server::utils::ServerConfig;
+use crate::service_install;
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Link relations generated by the server; they are dropped on import so
+/// re-importing an export does not duplicate them
+const GENERATED_LINK_RELS: [&str; 6] =
+    ["self", "root", "parent", "collection", "items", "alternate"];
+
+/// ZenSTAC command line interface.
+///
+/// Running without a subcommand launches the desktop application.
+#[derive(Debug, Parser)]
+#[command(name = "zenstac", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Catalog database file, or `sqlite://:memory:` for a throwaway catalog
+    /// that leaves the app data untouched
+    #[arg(long, global = true)]
+    pub database: Option<String>,
+    /// Write the in-memory catalog to this file on exit
+    #[arg(long, global = true, requires = "database")]
+    pub persist: Option<PathBuf>,
+    /// Serve a public demo: a throwaway in-memory catalog with the sample
+    /// data, read-only, rate limited and cached
+    #[arg(long, global = true, conflicts_with_all = ["database", "persist"])]
+    pub demo: bool,
+}
+
+/// Selects the database given with `--database` for the whole process, or
+/// the in-memory catalog of `--demo`
+pub fn select_database(cli: &Cli) -> Result<(), String> {
+    if cli.demo {
+        return crate::config::select_database(DatabaseLocation::Memory { persist_to: None });
+    }
+    let Some(database) = &cli.database else {
+        return Ok(());
+    };
+    let location = match (DatabaseLocation::parse(database), &cli.persist) {
+        (DatabaseLocation::Memory { .. }, persist_to) => DatabaseLocation::Memory {
+            persist_to: persist_to.clone(),
+        },
+        (_, Some(_)) => return Err("--persist only applies to sqlite://:memory:".to_string()),
+        (location, None) => location,
+    };
+    crate::config::select_database(location)
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the STAC server without the desktop window
+    Serve {
+        /// Address to bind to (defaults to the saved internal address)
+        #[arg(long)]
+        host: Option<String>,
+        /// Port to listen on (defaults to the saved port)
+        #[arg(long)]
+        port: Option<u16>,
+    },
+    /// Import a STAC Collection, Item, FeatureCollection or export file
+    Import {
+        /// JSON file to import
+        file: PathBuf,
+        /// Collection to add items to when they don't name one
+        #[arg(long)]
+        collection: Option<String>,
+        /// Skip the STAC checks, like `validate=false` on the API. Strict
+        /// ingest mode and collection item schemas still apply.
+        #[arg(long)]
+        no_validate: bool,
+    },
+    /// Export collections and their items as STAC JSON
+    Export {
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Only export this collection
+        #[arg(long)]
+        collection: Option<String>,
+    },
+    /// Check that a file contains valid STAC JSON without importing it
+    Validate {
+        /// JSON file to validate
+        file: PathBuf,
+    },
+    /// Write a consistent copy of the catalog database
+    Backup {
+        /// Destination database file
+        output: PathBuf,
+        /// Also copy the assets directory to this location
+        #[arg(long)]
+        assets: Option<PathBuf>,
+    },
+    /// Run `zenstac serve` for this catalog as a systemd user unit (Linux)
+    /// or a logon task (Windows), without the desktop window
+    InstallService,
+    /// Stop and remove the service installed by `install-service`
+    UninstallService,
+}
+
+/// Runs a CLI subcommand against the same catalog the desktop app uses, or
+/// the demo catalog with `demo`
+pub async fn run(command: Command, demo: bool) -> Result<(), Box<dyn std::error::Error>> {
+    // The demo catalog is thrown away on exit: an import into it, or an
+    // export or backup of it, would only appear to work
+    if demo && !matches!(command, Command::Serve { .. }) {
+        return Err("--demo only applies to `zenstac serve` and the desktop app".into());
+    }
+    let mut config = Config::with_server_settings();
+    fs::create_dir_all(config.assets_dir())?;
+    let db_service = DatabaseService::new(&config.database.path).await?;
+    if demo {
+        apply_demo_preset(&db_service, &mut config).await?;
+    }
+
+    let result = match command {
+        Command::Serve { host, port } => serve(db_service, config, host, port).await,
+        Command::Import {
+            file,
+            collection,
+            no_validate,
+        } => import(&db_service, &file, collection.as_deref(), !no_validate).await,
+        Command::Export { output, collection } => {
+            export(&db_service, &config, output.as_deref(), collection.as_deref()).await
+        }
+        Command::Validate { file } => validate(&file),
+        Command::Backup { output, assets } => backup(&config, &output, assets.as_deref()),
+        Command::InstallService => {
+            println!("{}", service_install::install(&config.database.path)?);
+            Ok(())
+        }
+        Command::UninstallService => {
+            println!("{}", service_install::uninstall()?);
+            Ok(())
+        }
+    };
+    close_memory_database()?;
+    result
+}
+
+async fn serve(
+    db_service: DatabaseService,
+    mut config: Config,
+    host: Option<String>,
+    port: Option<u16>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(host) = host {
+        config.server.internal_address = host;
+    }
+    if let Some(port) = port {
+        config.server.port = port;
+    }
+
+    db_service.initialize_with_sample_data().await?;
+
+    let host = config.server.internal_address.clone();
+    let port = config.server.port;
+    println!("Serving STAC API on http://{}:{}{}", host, port, config.server.api_version);
+    // Return on Ctrl-C so an in-memory catalog can be persisted
+    tokio::select! {
+        result = crate::server::server::start_stac_server(&host, port, db_service, config) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
+
+/// Imports collections and items through the same checks as the API: STAC
+/// validation unless `validate` is off, and for items the ingest hook, the
+/// ingest mode and the collection's item schema
+async fn import(
+    db_service: &DatabaseService,
+    file: &Path,
+    collection: Option<&str>,
+    validate: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let document: Value = serde_json::from_str(&fs::read_to_string(file)?)?;
+
+    let mut collections = Vec::new();
+    let mut items = Vec::new();
+    match document.get("type").and_then(|t| t.as_str()) {
+        Some("Collection") => collections.push(document.clone()),
+        Some("Feature") => items.push(document.clone()),
+        _ => {
+            if let Some(values) = document.get("collections").and_then(|c| c.as_array()) {
+                collections.extend(values.iter().cloned());
+            }
+            if let Some(values) = document.get("features").and_then(|f| f.as_array()) {
+                items.extend(values.iter().cloned());
+            }
+        }
+    }
+
+    if collections.is_empty() && items.is_empty() {
+        return Err("File does not contain any STAC Collections or Items".into());
+    }
+
+    for value in &collections {
+        if validate {
+            let violations = validate_stac_collection(value);
+            if !violations.is_empty() {
+                let id = value.get("id").and_then(|v| v.as_str()).unwrap_or("?");
+                return Err(format!(
+                    "Collection {} is not valid STAC: {}",
+                    id,
+                    describe_violations(&violations)
+                )
+                .into());
+            }
+        }
+        let db_collection = db_collection_from_json(value)?;
+        if db_service.collections.get_by_id(&db_collection.id).await?.is_some() {
+            db_service.collections.update(&db_collection).await?;
+        } else {
+            db_service.collections.create(&db_collection).await?;
+        }
+        println!("Imported collection {}", db_collection.id);
+    }
+
+    let file_name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file.display().to_string());
+
+    for value in &items {
+        let id = required_str(value, "id", "Item")?;
+        let collection_id = item_collection_id(value, &id, collection)?;
+        if db_service.collections.get_by_id(&collection_id).await?.is_none() {
+            return Err(format!(
+                "Item {} references unknown collection {}",
+                id, collection_id
+            )
+            .into());
+        }
+        let exists = db_service.items.get_by_id(&collection_id, &id).await?.is_some();
+        let event = if exists {
+            IngestEvent::Update
+        } else {
+            IngestEvent::Create
+        };
+
+        let (value, warnings) =
+            prepare_item(db_service, &collection_id, event, value.clone(), validate)
+                .await
+                .map_err(|rejection| format!("Item {} {}", id, rejection))?;
+        for warning in warnings {
+            println!("Warning: item {}: {}", id, warning);
+        }
+        let mut db_item = db_item_from_json(&value, Some(&collection_id))?;
+
+        // Lineage: where the item came from and when it was ingested
+        let record = ProcessingRecord {
+            job: "import".to_string(),
+            lineage: Some(format!("Imported from {}", file_name)),
+            derived_from_hrefs: self_href(&value).into_iter().collect(),
+            ..Default::default()
+        };
+        stamp_processing(&mut db_item, &record);
+        if exists {
+            db_service.items.update(&db_item).await?;
+        } else {
+            db_service.items.create(&db_item).await?;
+        }
+        record_lineage(db_service, &db_item, &record).await?;
+    }
+    if !items.is_empty() {
+        println!("Imported {} items", items.len());
+    }
+
+    Ok(())
+}
+
+async fn export(
+    db_service: &DatabaseService,
+    config: &Config,
+    output: Option<&Path>,
+    collection: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let server_config = ServerConfig::from_config(config);
+
+    let collection_hashes = db_service.collections.content_hashes().await?;
+    let collection_ids: Vec<String> = match collection {
+        Some(id) if collection_hashes.contains_key(id) => vec![id.to_string()],
+        Some(id) => return Err(format!("Collection {} not found", id).into()),
+        None => collection_hashes.keys().cloned().collect(),
+    };
+    let manifest_path = output.map(export_manifest_path);
+    let mut previous = match (output, &manifest_path) {
+        (Some(output), Some(manifest_path)) => {
+            PreviousExport::load(output, manifest_path, &server_config.base_url)
+        }
+        _ => None,
+    };
+
+    let mut manifest = ExportManifest {
+        base_url: server_config.base_url.clone(),
+        ..Default::default()
+    };
+    let mut collections = Vec::new();
+    let mut features = Vec::new();
+    let mut reused = 0;
+    for collection_id in &collection_ids {
+        let hash = &collection_hashes[collection_id];
+        match previous.as_mut().and_then(|p| p.take_collection(collection_id, hash)) {
+            Some(document) => {
+                reused += 1;
+                collections.push(document);
+            }
+            None => {
+                let db_collection = db_service
+                    .collections
+                    .get_by_id(collection_id)
+                    .await?
+                    .ok_or_else(|| format!("Collection {} not found", collection_id))?;
+                collections.push(serde_json::to_value(
+                    db_collection.to_stac_collection(&server_config),
+                )?);
+            }
+        }
+        manifest.collections.insert(collection_id.clone(), hash.clone());
+
+        let item_hashes = db_service.items.content_hashes(collection_id).await?;
+        match previous.as_mut() {
+            // Unchanged items are copied from the previous export; only the
+            // others are read
+            Some(previous) => {
+                for (item_id, hash) in &item_hashes {
+                    if let Some(document) = previous.take_item(collection_id, item_id, hash) {
+                        reused += 1;
+                        features.push(document);
+                    } else if let Some(db_item) =
+                        db_service.items.get_by_id(collection_id, item_id).await?
+                    {
+                        features.push(serde_json::to_value(db_item.to_stac_item(&server_config))?);
+                    }
+                }
+            }
+            None => {
+                let mut offset = 0;
+                loop {
+                    let page = db_service
+                        .items
+                        .get_by_collection(collection_id, Some(EXPORT_PAGE_SIZE), Some(offset))
+                        .await?;
+                    for db_item in &page {
+                        features.push(serde_json::to_value(db_item.to_stac_item(&server_config))?);
+                    }
+                    if (page.len() as i64) < EXPORT_PAGE_SIZE {
+                        break;
+                    }
+                    offset += EXPORT_PAGE_SIZE;
+                }
+            }
+        }
+        manifest.items.insert(collection_id.clone(), item_hashes);
+    }
+
+    let document = json!({
+        "collections": collections,
+        "features": features,
+    });
+    let content = serde_json::to_string_pretty(&document)?;
+
+    match (output, manifest_path) {
+        (Some(path), Some(manifest_path)) => {
+            fs::write(path, content)?;
+            fs::write(&manifest_path, serde_json::to_string(&manifest)?)?;
+            println!(
+                "Exported {} collections and {} items to {} ({} unchanged)",
+                collections.len(),
+                features.len(),
+                path.display(),
+                reused
+            );
+        }
+        _ => println!("{}", content),
+    }
+    Ok(())
+}
+
+/// Items read from the database at a time by a full export
+const EXPORT_PAGE_SIZE: i64 = 1000;
+
+/// Content hashes of the records in an export file, written beside it so the
+/// next export to the same file can copy the records that did not change
+#[derive(Default, Serialize, Deserialize)]
+struct ExportManifest {
+    /// Server URL the links were written with; a change invalidates them all
+    base_url: String,
+    collections: BTreeMap<String, String>,
+    /// Item hashes by collection and item id
+    items: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// `catalog.json.hashes` for `catalog.json`
+fn export_manifest_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".hashes");
+    PathBuf::from(name)
+}
+
+/// The records of an earlier export file, with the hashes they had
+struct PreviousExport {
+    manifest: ExportManifest,
+    collections: HashMap<String, Value>,
+    features: HashMap<(String, String), Value>,
+}
+
+impl PreviousExport {
+    /// Reads an earlier export and its manifest. `None` when either is
+    /// missing or unreadable, or it was written for another server URL, in
+    /// which case every record is exported afresh.
+    fn load(output: &Path, manifest_path: &Path, base_url: &str) -> Option<Self> {
+        let manifest: ExportManifest =
+            serde_json::from_str(&fs::read_to_string(manifest_path).ok()?).ok()?;
+        if manifest.base_url != base_url {
+            return None;
+        }
+        let mut document: Value = serde_json::from_str(&fs::read_to_string(output).ok()?).ok()?;
+        let id_of = |value: &Value, field: &str| {
+            value.get(field).and_then(|v| v.as_str()).map(str::to_string)
+        };
+        let collections = match document.get_mut("collections")?.take() {
+            Value::Array(values) => values
+                .into_iter()
+                .filter_map(|value| Some((id_of(&value, "id")?, value)))
+                .collect(),
+            _ => return None,
+        };
+        let features = match document.get_mut("features")?.take() {
+            Value::Array(values) => values
+                .into_iter()
+                .filter_map(|value| {
+                    Some(((id_of(&value, "collection")?, id_of(&value, "id")?), value))
+                })
+                .collect(),
+            _ => return None,
+        };
+        Some(Self {
+            manifest,
+            collections,
+            features,
+        })
+    }
+
+    /// The earlier document of a collection, if its content is unchanged
+    fn take_collection(&mut self, id: &str, hash: &str) -> Option<Value> {
+        if self.manifest.collections.get(id).map(String::as_str) != Some(hash) {
+            return None;
+        }
+        self.collections.remove(id)
+    }
+
+    /// The earlier document of an item, if its content is unchanged
+    fn take_item(&mut self, collection_id: &str, item_id: &str, hash: &str) -> Option<Value> {
+        let previous_hash = self.manifest.items.get(collection_id)?.get(item_id)?;
+        if previous_hash != hash {
+            return None;
+        }
+        self.features
+            .remove(&(collection_id.to_string(), item_id.to_string()))
+    }
+}
+
+fn validate(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let document: Value = serde_json::from_str(&fs::read_to_string(file)?)?;
+
+    let mut errors = Vec::new();
+    let mut checked = 0;
+    let mut check = |value: &Value| {
+        checked += 1;
+        let id = value.get("id").and_then(|v| v.as_str()).unwrap_or("<no id>").to_string();
+        match value.get("type").and_then(|t| t.as_str()) {
+            Some("Collection") => match serde_json::from_value::<Collection>(value.clone()) {
+                Ok(c) if c.is_valid() => {}
+                Ok(_) => errors.push(format!("Collection {}: missing required fields", id)),
+                Err(e) => errors.push(format!("Collection {}: {}", id, e)),
+            },
+            Some("Feature") => match serde_json::from_value::<Item>(value.clone()) {
+                Ok(i) if i.has_valid_bbox() && i.properties.is_valid() => {}
+                Ok(_) => errors.push(format!("Item {}: missing bbox or datetime", id)),
+                Err(e) => errors.push(format!("Item {}: {}", id, e)),
+            },
+            other => errors.push(format!("{}: unsupported type {:?}", id, other)),
+        }
+    };
+
+    match document.get("type").and_then(|t| t.as_str()) {
+        Some("Collection") | Some("Feature") => check(&document),
+        _ => {
+            for key in ["collections", "features"] {
+                if let Some(values) = document.get(key).and_then(|v| v.as_array()) {
+                    values.iter().for_each(&mut check);
+                }
+            }
+        }
+    }
+
+    if checked == 0 {
+        return Err("File does not contain any STAC Collections or Items".into());
+    }
+    if !errors.is_empty() {
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        return Err(format!("{} of {} objects are invalid", errors.len(), checked).into());
+    }
+
+    println!("{} objects are valid", checked);
+    Ok(())
+}
+
+fn backup(
+    config: &Config,
+    output: &Path,
+    assets: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if output.exists() {
+        return Err(format!("{} already exists", output.display()).into());
+    }
+
+    // VACUUM INTO produces a consistent snapshot even while the app is running
+    let conn = rusqlite::Connection::open(&config.database.path)?;
+    conn.execute("VACUUM INTO ?1", [output.to_string_lossy()])?;
+    spatial_index::rebuild_copy(output)?;
+    println!("Database backed up to {}", output.display());
+
+    if let Some(assets) = assets {
+        copy_dir(Path::new(&config.assets_dir()), assets)?;
+        println!("Assets copied to {}", assets.display());
+    }
+    Ok(())
+}
+
+/// Builds a database collection from a STAC Collection document
+pub(crate) fn db_collection_from_json(value: &Value) -> Result<DbCollection, String> {
+    let id = required_str(value, "id", "Collection")?;
+    let description = required_str(value, "description", "Collection")?;
+    let license = required_str(value, "license", "Collection")?;
+    let extent = value.get("extent");
+    let now = chrono::Utc::now().to_rfc3339();
+
+    Ok(DbCollection {
+        id,
+        r#type: "Collection".to_string(),
+        stac_version: value
+            .get("stac_version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("1.0.0")
+            .to_string(),
+        stac_extensions: value.get("stac_extensions").cloned(),
+        title: value.get("title").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        description,
+        keywords: value.get("keywords").cloned(),
+        license,
+        providers: value.get("providers").cloned(),
+        extent_spatial_bbox: extent
+            .and_then(|e| e.get("spatial"))
+            .cloned()
+            .unwrap_or_else(|| json!({ "bbox": [[-180.0, -90.0, 180.0, 90.0]] })),
+        extent_temporal_interval: extent
+            .and_then(|e| e.get("temporal"))
+            .cloned()
+            .unwrap_or_else(|| json!({ "interval": [[null, null]] })),
+        summaries: value.get("summaries").cloned(),
+        assets: value.get("assets").cloned(),
+        conforms_to: value.get("conformsTo").cloned().unwrap_or_else(|| {
+            json!([
+                "https://api.stacspec.org/v1.0.0/core",
+                "https://api.stacspec.org/v1.0.0/collections",
+                "https://api.stacspec.org/v1.0.0/item-search",
+                "https://api.stacspec.org/v1.0.0/ogcapi-features"
+            ])
+        }),
+        created_at: now.clone(),
+        updated_at: now,
+    })
+}
+
+/// Builds a database item from a STAC Item document
+pub(crate) fn db_item_from_json(value: &Value, collection: Option<&str>) -> Result<DbItem, String> {
+    let id = required_str(value, "id", "Item")?;
+    let collection_id = item_collection_id(value, &id, collection)?;
+    let properties = value
+        .get("properties")
+        .cloned()
+        .ok_or_else(|| format!("Item {} is missing required field: properties", id))?;
+    let geometry = value.get("geometry").filter(|g| !g.is_null()).cloned();
+
+    let bbox = value.get("bbox").cloned().or_else(|| {
+        geometry
+            .as_ref()
+            .and_then(|g| serde_json::from_value(g.clone()).ok())
+            .map(|g| json!(calculate_bbox_for_geometry(&g)))
+    });
+
+    let links = value.get("links").and_then(|l| l.as_array()).map(|links| {
+        Value::Array(
+            links
+                .iter()
+                .filter(|link| {
+                    let rel = link.get("rel").and_then(|r| r.as_str()).unwrap_or("");
+                    !GENERATED_LINK_RELS.contains(&rel)
+                })
+                .cloned()
+                .collect(),
+        )
+    });
+
+    let now = chrono::Utc::now().to_rfc3339();
+    Ok(DbItem {
+        id,
+        collection_id,
+        r#type: "Feature".to_string(),
+        stac_version: value
+            .get("stac_version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("1.0.0")
+            .to_string(),
+        stac_extensions: value.get("stac_extensions").cloned(),
+        geometry,
+        bbox,
+        properties,
+        links,
+        assets: Some(value.get("assets").cloned().unwrap_or_else(|| json!({}))),
+        created_at: now.clone(),
+        updated_at: now,
+    })
+}
+
+/// Collection an item document names, or `collection` when it names none
+fn item_collection_id(value: &Value, id: &str, collection: Option<&str>) -> Result<String, String> {
+    value
+        .get("collection")
+        .and_then(|v| v.as_str())
+        .or(collection)
+        .map(|s| s.to_string())
+        .ok_or_else(|| format!("Item {} has no collection; pass --collection", id))
+}
+
+/// Absolute `self` link of a document, i.e. where it was published before import
+fn self_href(value: &Value) -> Option<String> {
+    value
+        .get("links")?
+        .as_array()?
+        .iter()
+        .find(|link| link.get("rel").and_then(|r| r.as_str()) == Some("self"))
+        .and_then(|link| link.get("href").and_then(|h| h.as_str()))
+        .filter(|href| href.starts_with("http://") || href.starts_with("https://"))
+        .map(|href| href.to_string())
+}
+
+fn required_str(value: &Value, field: &str, kind: &str) -> Result<String, String> {
+    value
+        .get(field)
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| format!("{} is missing required field: {}", kind, field))
+}
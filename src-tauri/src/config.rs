@@ -1,6 +1,12 @@
+use crate::database::spatial_index;
+use crate::database::settings::{
+    self, EnableSearch, EnableTransactions, EnableUploads, MaxJsonBodyBytes, MaxUploadBodyBytes,
+    Setting, ServerExternalAddress, ServerInternalAddress, ServerPort,
+};
 use serde::{Deserialize, Serialize};
 use rusqlite;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 /// Configuration for the STAC server
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,8 +34,6 @@ pub struct CatalogConfig {
     pub license: String,
     /// List of STAC extensions that this catalog supports
     pub stac_extensions: Vec<String>,
-    /// List of conformance classes (conformsTo)
-    pub conforms_to: Vec<String>,
 }
 
 /// Server configuration
@@ -43,6 +47,21 @@ pub struct ServerConfig {
     pub port: u16,
     /// API version path (e.g., "/v1")
     pub api_version: String,
+    /// Maximum request body size for JSON endpoints, in bytes
+    #[serde(default = "MaxJsonBodyBytes::default_value")]
+    pub max_json_body_bytes: u64,
+    /// Maximum request body size for asset uploads, in bytes
+    #[serde(default = "MaxUploadBodyBytes::default_value")]
+    pub max_upload_body_bytes: u64,
+    /// Whether collection and item transactions are served
+    #[serde(default = "EnableTransactions::default_value")]
+    pub enable_transactions: bool,
+    /// Whether asset uploads are accepted
+    #[serde(default = "EnableUploads::default_value")]
+    pub enable_uploads: bool,
+    /// Whether item search and sortables are served
+    #[serde(default = "EnableSearch::default_value")]
+    pub enable_search: bool,
 }
 
 /// Database configuration
@@ -57,7 +76,11 @@ impl Default for Config {
         // Get the app data directory for storing user data
         let app_data_dir = get_app_data_dir();
         
-        let db_path = app_data_dir.join("zenstac.db").to_string_lossy().to_string();
+        let db_path = match database_location() {
+            DatabaseLocation::AppData => app_data_dir.join("zenstac.db").to_string_lossy().to_string(),
+            DatabaseLocation::File(path) => path.to_string_lossy().to_string(),
+            DatabaseLocation::Memory { .. } => memory_database_uri(),
+        };
         
         Self {
             catalog: CatalogConfig {
@@ -70,21 +93,17 @@ impl Default for Config {
                 stac_extensions: vec![
                     "https://stac-extensions.github.io/eo/v1.0.0/schema.json".to_string()
                 ],
-                conforms_to: vec![
-                    "https://api.stacspec.org/v1.0.0/core".to_string(),
-                    "https://api.stacspec.org/v1.0.0/collections".to_string(),
-                    "https://api.stacspec.org/v1.0.0/item-search".to_string(),
-                    "https://api.stacspec.org/v1.0.0/ogcapi-features".to_string(),
-                    "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/core".to_string(),
-                    "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/oas30".to_string(),
-                    "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/geojson".to_string(),
-                ],
             },
             server: ServerConfig {
                 internal_address: "127.0.0.1".to_string(),
                 external_address: "127.0.0.1".to_string(),
                 port: 3000,
                 api_version: "/v1".to_string(),
+                max_json_body_bytes: MaxJsonBodyBytes::default_value(),
+                max_upload_body_bytes: MaxUploadBodyBytes::default_value(),
+                enable_transactions: EnableTransactions::default_value(),
+                enable_uploads: EnableUploads::default_value(),
+                enable_search: EnableSearch::default_value(),
             },
             database: DatabaseConfig {
                 path: db_path,
@@ -93,8 +112,178 @@ impl Default for Config {
     }
 }
 
-/// Get the app data directory for storing user data
+/// Where the catalog database lives, selected once at startup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DatabaseLocation {
+    /// `zenstac.db` in the app data directory
+    AppData,
+    /// Another database file; assets and other data are kept in a
+    /// `<file>.data` directory beside it
+    File(PathBuf),
+    /// A throwaway catalog in memory, with its data in a temporary directory.
+    /// Written to `persist_to` on exit when set.
+    Memory { persist_to: Option<PathBuf> },
+}
+
+impl DatabaseLocation {
+    /// Parses a `--database` value: `sqlite://:memory:` (or `:memory:`), or a
+    /// database file as a path or `sqlite://` URL
+    pub fn parse(value: &str) -> Self {
+        let path = value.strip_prefix("sqlite://").unwrap_or(value);
+        if path == ":memory:" {
+            DatabaseLocation::Memory { persist_to: None }
+        } else {
+            DatabaseLocation::File(PathBuf::from(path))
+        }
+    }
+}
+
+static DATABASE_LOCATION: OnceLock<DatabaseLocation> = OnceLock::new();
+
+/// Connection keeping the shared in-memory database alive while the process
+/// runs; SQLite drops it when its last connection closes
+static MEMORY_DATABASE: OnceLock<Mutex<rusqlite::Connection>> = OnceLock::new();
+
+/// Selects the database every `Config` of this process uses. Must be called
+/// before the first configuration is loaded.
+pub fn select_database(location: DatabaseLocation) -> Result<(), String> {
+    if let DatabaseLocation::Memory { .. } = location {
+        let conn = rusqlite::Connection::open(memory_database_uri())
+            .map_err(|e| format!("Failed to open in-memory database: {}", e))?;
+        let _ = MEMORY_DATABASE.set(Mutex::new(conn));
+    }
+    DATABASE_LOCATION
+        .set(location)
+        .map_err(|_| "The database was already selected".to_string())
+}
+
+/// The database selected at startup
+pub fn database_location() -> &'static DatabaseLocation {
+    DATABASE_LOCATION.get_or_init(|| DatabaseLocation::AppData)
+}
+
+/// Whether `path` names the in-memory database
+pub fn is_memory_database(path: &str) -> bool {
+    path == memory_database_uri()
+}
+
+/// Shared-cache URI, so every connection of the process sees the same
+/// in-memory catalog
+fn memory_database_uri() -> String {
+    format!("file:zenstac-memory-{}?mode=memory&cache=shared", std::process::id())
+}
+
+/// Ends an in-memory catalog on exit: writes it to its `persist_to` file and
+/// its assets and other files to the file's `<file>.data` directory,
+/// replacing both, then removes its temporary data directory. Does nothing
+/// for file databases.
+pub fn close_memory_database() -> Result<(), String> {
+    let DatabaseLocation::Memory { persist_to } = database_location() else {
+        return Ok(());
+    };
+    if let (Some(path), Some(conn)) = (persist_to, MEMORY_DATABASE.get()) {
+        let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+        persist_memory_database(&conn, path)?;
+    }
+    let _ = std::fs::remove_dir_all(get_app_data_dir());
+    Ok(())
+}
+
+/// Writes the in-memory catalog and a copy of its data directory beside
+/// `path` first and only then moves them over the previous ones, so a failed
+/// write leaves the previous catalog as it was
+fn persist_memory_database(conn: &rusqlite::Connection, path: &Path) -> Result<(), String> {
+    let partial = with_suffix(path, ".partial");
+    let data = data_dir(&DatabaseLocation::File(path.to_path_buf()));
+    let partial_data = with_suffix(&data, ".partial");
+    let _ = std::fs::remove_file(&partial);
+    let _ = std::fs::remove_dir_all(&partial_data);
+
+    let written = conn
+        .execute("VACUUM INTO ?1", [partial.to_string_lossy()])
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+        .and_then(|_| {
+            spatial_index::rebuild_copy(&partial)
+                .map_err(|e| format!("Failed to index {}: {}", path.display(), e))
+        })
+        .and_then(|_| {
+            copy_dir(&get_app_data_dir(), &partial_data)
+                .map_err(|e| format!("Failed to copy assets to {}: {}", data.display(), e))
+        });
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        let _ = std::fs::remove_dir_all(&partial_data);
+        return Err(e);
+    }
+
+    std::fs::rename(&partial, path)
+        .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))?;
+    if data.exists() {
+        std::fs::remove_dir_all(&data)
+            .map_err(|e| format!("Failed to replace {}: {}", data.display(), e))?;
+    }
+    std::fs::rename(&partial_data, &data)
+        .map_err(|e| format!("Failed to replace {}: {}", data.display(), e))
+}
+
+/// `path` with `suffix` appended to its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Copies a directory with everything in it
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            copy_dir(&path, &to.join(entry.file_name()))?;
+        } else {
+            std::fs::copy(&path, to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the directory for storing user data of the database selected at
+/// startup
 fn get_app_data_dir() -> PathBuf {
+    data_dir(database_location())
+}
+
+/// Directory for the user data of a catalog: the app data directory, a
+/// `<file>.data` directory beside a database file, so catalogs sharing a
+/// folder never share assets, or a temporary directory for an in-memory
+/// catalog
+fn data_dir(location: &DatabaseLocation) -> PathBuf {
+    match location {
+        DatabaseLocation::AppData => default_app_data_dir(),
+        DatabaseLocation::File(path) => with_suffix(path, ".data"),
+        DatabaseLocation::Memory { .. } => {
+            std::env::temp_dir().join(format!("zenstac-memory-{}", std::process::id()))
+        }
+    }
+}
+
+/// Data directory of the catalog whose database is at `db_path`, the one
+/// `Config` uses when that catalog is the selected one. Lets a repository
+/// keep files with its own catalog rather than the selected one.
+pub fn catalog_data_dir(db_path: &str) -> PathBuf {
+    let location = if is_memory_database(db_path) {
+        DatabaseLocation::Memory { persist_to: None }
+    } else if Path::new(db_path) == default_app_data_dir().join("zenstac.db") {
+        DatabaseLocation::AppData
+    } else {
+        DatabaseLocation::File(PathBuf::from(db_path))
+    };
+    data_dir(&location)
+}
+
+/// Get the app data directory for storing user data
+fn default_app_data_dir() -> PathBuf {
     #[cfg(target_os = "windows")]
     {
         let app_data = std::env::var("APPDATA").unwrap_or_else(|_| "data".to_string());
@@ -126,43 +315,89 @@ impl Config {
         
         // Try to load server settings from database
         if let Ok(conn) = rusqlite::Connection::open(&config.database.path) {
-            // Load internal address
-            if let Ok(mut stmt) = conn.prepare("SELECT value FROM application_settings WHERE key = 'server_internal_address'") {
-                if let Ok(mut rows) = stmt.query([]) {
-                    if let Ok(Some(row)) = rows.next() {
-                        if let Ok(value) = row.get::<_, String>(0) {
-                            config.server.internal_address = value;
-                        }
-                    }
+            if let Ok(value) = settings::load::<ServerInternalAddress>(&conn) {
+                config.server.internal_address = value;
+            }
+            if let Ok(value) = settings::load::<ServerExternalAddress>(&conn) {
+                config.server.external_address = value;
+            }
+            if let Ok(value) = settings::load::<ServerPort>(&conn) {
+                config.server.port = value;
+            }
+            if let Ok(value) = settings::load::<MaxJsonBodyBytes>(&conn) {
+                config.server.max_json_body_bytes = value;
+            }
+            if let Ok(value) = settings::load::<MaxUploadBodyBytes>(&conn) {
+                config.server.max_upload_body_bytes = value;
+            }
+            if let Ok(value) = settings::load::<EnableTransactions>(&conn) {
+                config.server.enable_transactions = value;
+            }
+            if let Ok(value) = settings::load::<EnableUploads>(&conn) {
+                config.server.enable_uploads = value;
+            }
+            if let Ok(value) = settings::load::<EnableSearch>(&conn) {
+                config.server.enable_search = value;
+            }
+        }
+        
+        config
+    }
+
+    /// Apply a changed setting to the in-memory configuration
+    pub fn apply_setting(&mut self, key: &str, raw: &str) {
+        match key {
+            ServerInternalAddress::KEY => {
+                if let Some(value) = settings::parse::<ServerInternalAddress>(raw) {
+                    self.server.internal_address = value;
+                }
+            }
+            ServerExternalAddress::KEY => {
+                if let Some(value) = settings::parse::<ServerExternalAddress>(raw) {
+                    self.server.external_address = value;
+                }
+            }
+            ServerPort::KEY => {
+                if let Some(value) = settings::parse::<ServerPort>(raw) {
+                    self.server.port = value;
+                }
+            }
+            MaxJsonBodyBytes::KEY => {
+                if let Some(value) = settings::parse::<MaxJsonBodyBytes>(raw) {
+                    self.server.max_json_body_bytes = value;
+                }
+            }
+            MaxUploadBodyBytes::KEY => {
+                if let Some(value) = settings::parse::<MaxUploadBodyBytes>(raw) {
+                    self.server.max_upload_body_bytes = value;
                 }
             }
-            
-            // Load external address
-            if let Ok(mut stmt) = conn.prepare("SELECT value FROM application_settings WHERE key = 'server_external_address'") {
-                if let Ok(mut rows) = stmt.query([]) {
-                    if let Ok(Some(row)) = rows.next() {
-                        if let Ok(value) = row.get::<_, String>(0) {
-                            config.server.external_address = value;
-                        }
-                    }
+            EnableTransactions::KEY => {
+                if let Some(value) = settings::parse::<EnableTransactions>(raw) {
+                    self.server.enable_transactions = value;
                 }
             }
-            
-            // Load port
-            if let Ok(mut stmt) = conn.prepare("SELECT value FROM application_settings WHERE key = 'server_port'") {
-                if let Ok(mut rows) = stmt.query([]) {
-                    if let Ok(Some(row)) = rows.next() {
-                        if let Ok(value) = row.get::<_, String>(0) {
-                            if let Ok(port) = value.parse::<u16>() {
-                                config.server.port = port;
-                            }
-                        }
-                    }
+            EnableUploads::KEY => {
+                if let Some(value) = settings::parse::<EnableUploads>(raw) {
+                    self.server.enable_uploads = value;
                 }
             }
+            EnableSearch::KEY => {
+                if let Some(value) = settings::parse::<EnableSearch>(raw) {
+                    self.server.enable_search = value;
+                }
+            }
+            _ => {}
         }
-        
-        config
+    }
+
+    /// Copy of this configuration with every feature group enabled.
+    /// The desktop UI always needs full catalog access.
+    pub fn with_all_features(mut self) -> Self {
+        self.server.enable_transactions = true;
+        self.server.enable_uploads = true;
+        self.server.enable_search = true;
+        self
     }
 
     /// Get the full internal server URL
@@ -206,6 +441,54 @@ impl Config {
         app_data_dir.join("assets").to_string_lossy().to_string()
     }
 
+    /// Get the directory deleted asset folders are moved to until purged
+    pub fn trash_dir(&self) -> String {
+        let app_data_dir = get_app_data_dir();
+        app_data_dir.join("trash").to_string_lossy().to_string()
+    }
+
+    /// Get the directory uploads rejected by the upload scanner are moved to
+    pub fn quarantine_dir(&self) -> String {
+        let app_data_dir = get_app_data_dir();
+        app_data_dir.join("quarantine").to_string_lossy().to_string()
+    }
+
+    /// Get the directory chunked uploads are written to until complete
+    pub fn uploads_dir(&self) -> String {
+        let app_data_dir = get_app_data_dir();
+        app_data_dir.join("uploads").to_string_lossy().to_string()
+    }
+
+    /// Get the basemap tile cache directory path
+    pub fn basemap_cache_dir(&self) -> String {
+        let app_data_dir = get_app_data_dir();
+        app_data_dir.join("basemap_cache").to_string_lossy().to_string()
+    }
+
+    /// Get the directory holding the ACME account and certificates
+    pub fn acme_dir(&self) -> String {
+        let app_data_dir = get_app_data_dir();
+        app_data_dir.join("acme").to_string_lossy().to_string()
+    }
+
+    /// Get the catalog branding (logo, favicon, about document) directory path
+    pub fn branding_dir(&self) -> String {
+        let app_data_dir = get_app_data_dir();
+        app_data_dir.join("branding").to_string_lossy().to_string()
+    }
+
+    /// Get the directory search exports are written to
+    pub fn exports_dir(&self) -> String {
+        let app_data_dir = get_app_data_dir();
+        app_data_dir.join("exports").to_string_lossy().to_string()
+    }
+
+    /// Get the directory scanned for WASM property extractor plugins
+    pub fn plugins_dir(&self) -> String {
+        let app_data_dir = get_app_data_dir();
+        app_data_dir.join("plugins").to_string_lossy().to_string()
+    }
+
     /// Load configuration from a file (optional - for future use)
     pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let content = std::fs::read_to_string(path)?;
@@ -220,3 +503,44 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_file_gets_its_own_data_dir() {
+        let location = DatabaseLocation::parse("/srv/stac/catalog.db");
+        assert_eq!(data_dir(&location), PathBuf::from("/srv/stac/catalog.db.data"));
+
+        let location = DatabaseLocation::parse("sqlite://catalog.db");
+        assert_eq!(data_dir(&location), PathBuf::from("catalog.db.data"));
+    }
+
+    #[test]
+    fn database_files_in_one_folder_keep_data_apart() {
+        let first = data_dir(&DatabaseLocation::parse("/srv/stac/first.db"));
+        let second = data_dir(&DatabaseLocation::parse("/srv/stac/second.db"));
+        assert_ne!(first, second);
+        assert!(!first.starts_with(&second) && !second.starts_with(&first));
+        // Nor with the folder holding the database files
+        assert_ne!(first, PathBuf::from("/srv/stac"));
+    }
+
+    #[test]
+    fn catalog_data_dir_follows_the_database() {
+        assert_eq!(
+            catalog_data_dir("/srv/stac/catalog.db"),
+            PathBuf::from("/srv/stac/catalog.db.data")
+        );
+        let app_data_db = default_app_data_dir().join("zenstac.db");
+        assert_eq!(
+            catalog_data_dir(&app_data_db.to_string_lossy()),
+            default_app_data_dir()
+        );
+        assert_eq!(
+            catalog_data_dir(&memory_database_uri()),
+            data_dir(&DatabaseLocation::Memory { persist_to: None })
+        );
+    }
+}
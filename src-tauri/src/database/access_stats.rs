@@ -0,0 +1,234 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Level at which access statistics are aggregated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Collection,
+    Item,
+    Asset,
+}
+
+impl AccessKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "collection" | "collections" => Some(AccessKind::Collection),
+            "item" | "items" => Some(AccessKind::Item),
+            "asset" | "assets" => Some(AccessKind::Asset),
+            _ => None,
+        }
+    }
+}
+
+/// A single served request, recorded by the access log middleware
+#[derive(Debug, Clone)]
+pub struct AccessRecord {
+    /// UTC day of the request (YYYY-MM-DD)
+    pub day: String,
+    pub collection_id: String,
+    pub item_id: Option<String>,
+    pub asset_key: Option<String>,
+    /// Anonymised client identifier
+    pub client: String,
+    pub bytes: u64,
+}
+
+/// Aggregated statistics for one collection, item or asset
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessStat {
+    pub collection_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_key: Option<String>,
+    pub hits: u64,
+    pub unique_clients: u64,
+    pub bytes_served: u64,
+}
+
+/// All-time totals of one item
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ItemAccessTotals {
+    /// Requests for the item document itself
+    pub views: u64,
+    /// Requests for any of its assets
+    pub downloads: u64,
+    pub bytes_served: u64,
+}
+
+/// Daily per collection/item/asset hit counters.
+///
+/// Item and asset ids are stored as empty strings for collection-level and
+/// item-level requests so they can be part of the primary key.
+#[derive(Clone)]
+pub struct AccessStatsRepository {
+    db: DatabaseConnection,
+}
+
+impl AccessStatsRepository {
+    /// Creates a new access statistics repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Adds a served request to the daily counters
+    pub async fn record(&self, record: &AccessRecord) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let item_id = record.item_id.as_deref().unwrap_or("");
+            let asset_key = record.asset_key.as_deref().unwrap_or("");
+
+            conn.execute(
+                r#"
+                INSERT INTO access_stats (day, collection_id, item_id, asset_key, hits, bytes)
+                VALUES (?1, ?2, ?3, ?4, 1, ?5)
+                ON CONFLICT(day, collection_id, item_id, asset_key)
+                DO UPDATE SET hits = hits + 1, bytes = bytes + excluded.bytes
+                "#,
+                rusqlite::params![record.day, record.collection_id, item_id, asset_key, record.bytes as i64],
+            )?;
+            conn.execute(
+                r#"
+                INSERT OR IGNORE INTO access_clients (day, collection_id, item_id, asset_key, client)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                "#,
+                rusqlite::params![record.day, record.collection_id, item_id, asset_key, record.client],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Most requested collections, items or assets since `since_day` (inclusive).
+    ///
+    /// Collection and item counts include the requests for everything below them.
+    pub async fn top(&self, kind: AccessKind, since_day: &str, limit: usize) -> Result<Vec<AccessStat>> {
+        let (group, filter, client_match) = match kind {
+            AccessKind::Collection => ("s.collection_id", "", ""),
+            AccessKind::Item => (
+                "s.collection_id, s.item_id",
+                "AND s.item_id != ''",
+                "AND c.item_id = s.item_id",
+            ),
+            AccessKind::Asset => (
+                "s.collection_id, s.item_id, s.asset_key",
+                "AND s.asset_key != ''",
+                "AND c.item_id = s.item_id AND c.asset_key = s.asset_key",
+            ),
+        };
+        let sql = format!(
+            r#"
+            SELECT s.collection_id, s.item_id, s.asset_key, SUM(s.hits), SUM(s.bytes),
+                (SELECT COUNT(DISTINCT c.client) FROM access_clients c
+                 WHERE c.day >= ?1 AND c.collection_id = s.collection_id {client_match})
+            FROM access_stats s
+            WHERE s.day >= ?1 {filter}
+            GROUP BY {group}
+            ORDER BY SUM(s.hits) DESC
+            LIMIT ?2
+            "#
+        );
+
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(rusqlite::params![since_day, limit as i64], |row| {
+                let item_id: String = row.get(1)?;
+                let asset_key: String = row.get(2)?;
+                Ok(AccessStat {
+                    collection_id: row.get(0)?,
+                    item_id: (kind != AccessKind::Collection).then_some(item_id),
+                    asset_key: (kind == AccessKind::Asset).then_some(asset_key),
+                    hits: row.get::<_, i64>(3)? as u64,
+                    bytes_served: row.get::<_, i64>(4)? as u64,
+                    unique_clients: row.get::<_, i64>(5)? as u64,
+                })
+            })?;
+
+            let mut stats = Vec::new();
+            for row in rows {
+                stats.push(row?);
+            }
+            Ok(stats)
+        })
+    }
+
+    /// All-time totals of the given (collection, item) pairs. Items never
+    /// requested are left out.
+    pub async fn item_totals(
+        &self,
+        items: &[(String, String)],
+    ) -> Result<HashMap<(String, String), ItemAccessTotals>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut totals = HashMap::new();
+            // Stays well below SQLite's bound parameter limit
+            for chunk in items.chunks(400) {
+                let values = vec!["(?, ?)"; chunk.len()].join(", ");
+                let sql = format!(
+                    r#"
+                    SELECT collection_id, item_id,
+                        SUM(CASE WHEN asset_key = '' THEN hits ELSE 0 END),
+                        SUM(CASE WHEN asset_key != '' THEN hits ELSE 0 END),
+                        SUM(bytes)
+                    FROM access_stats
+                    WHERE item_id != '' AND (collection_id, item_id) IN (VALUES {values})
+                    GROUP BY collection_id, item_id
+                    "#
+                );
+                let params: Vec<&str> = chunk
+                    .iter()
+                    .flat_map(|(collection_id, item_id)| [collection_id.as_str(), item_id.as_str()])
+                    .collect();
+                let mut stmt = conn.prepare_cached(&sql)?;
+                let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                    Ok((
+                        (row.get::<_, String>(0)?, row.get::<_, String>(1)?),
+                        ItemAccessTotals {
+                            views: row.get::<_, i64>(2)? as u64,
+                            downloads: row.get::<_, i64>(3)? as u64,
+                            bytes_served: row.get::<_, i64>(4)? as u64,
+                        },
+                    ))
+                })?;
+                for row in rows {
+                    let (key, item_totals) = row?;
+                    totals.insert(key, item_totals);
+                }
+            }
+            Ok(totals)
+        })
+    }
+
+    /// All-time statistics of each requested asset of an item
+    pub async fn item_assets(&self, collection_id: &str, item_id: &str) -> Result<Vec<AccessStat>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                r#"
+                SELECT s.asset_key, SUM(s.hits), SUM(s.bytes),
+                    (SELECT COUNT(DISTINCT c.client) FROM access_clients c
+                     WHERE c.collection_id = s.collection_id AND c.item_id = s.item_id
+                       AND c.asset_key = s.asset_key)
+                FROM access_stats s
+                WHERE s.collection_id = ?1 AND s.item_id = ?2 AND s.asset_key != ''
+                GROUP BY s.asset_key
+                ORDER BY SUM(s.hits) DESC
+                "#,
+            )?;
+            let rows = stmt.query_map([collection_id, item_id], |row| {
+                Ok(AccessStat {
+                    collection_id: collection_id.to_string(),
+                    item_id: Some(item_id.to_string()),
+                    asset_key: Some(row.get(0)?),
+                    hits: row.get::<_, i64>(1)? as u64,
+                    bytes_served: row.get::<_, i64>(2)? as u64,
+                    unique_clients: row.get::<_, i64>(3)? as u64,
+                })
+            })?;
+            rows.collect()
+        })
+    }
+}
@@ -0,0 +1,160 @@
+use crate::database::DbItem;
+use rusqlite::{Connection, Result};
+use serde_json::Value;
+
+/// Prefix of the search properties answered from an item's assets, e.g.
+/// `assets.type`; the rest names the asset field
+pub const ASSET_PROPERTY_PREFIX: &str = "assets.";
+
+/// Asset fields searches can filter on, each with the `item_assets` column
+/// holding it. Roles are a JSON array, matched per role.
+const ASSET_FIELDS: [(&str, &str); 4] = [
+    ("key", "asset_key"),
+    ("type", "media_type"),
+    ("roles", "roles"),
+    ("file:size", "file_size"),
+];
+
+/// Column of the `item_assets` table holding an `assets.<field>` search
+/// property; `None` for other properties
+pub fn asset_column(property: &str) -> Option<&'static str> {
+    let field = property.strip_prefix(ASSET_PROPERTY_PREFIX)?;
+    ASSET_FIELDS
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map(|(_, column)| *column)
+}
+
+/// SQL condition on the `item_assets` rows of the outer `items` row: some
+/// asset satisfies `condition`, or with `negate` none does. `condition`
+/// refers to roles as `role.value`.
+pub(crate) fn exists_sql(column: &str, condition: &str, negate: bool) -> String {
+    let roles = if column == "roles" {
+        ", json_each(item_assets.roles) AS role"
+    } else {
+        ""
+    };
+    format!(
+        "{}EXISTS (SELECT 1 FROM item_assets{} WHERE item_assets.collection_id = items.collection_id \
+         AND item_assets.item_id = items.id AND {})",
+        if negate { "NOT " } else { "" },
+        roles,
+        condition
+    )
+}
+
+/// Expression of an asset column inside [`exists_sql`]
+pub(crate) fn column_expression(column: &str) -> String {
+    if column == "roles" {
+        "role.value".to_string()
+    } else {
+        format!("item_assets.{}", column)
+    }
+}
+
+/// Creates the index of item assets. Returns whether it was created, in
+/// which case it still has to be filled.
+pub(crate) fn create_index(conn: &Connection) -> Result<bool> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'item_assets')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute(
+            r#"
+            CREATE TABLE item_assets (
+                collection_id TEXT NOT NULL,
+                item_id TEXT NOT NULL,
+                asset_key TEXT NOT NULL,
+                media_type TEXT,
+                roles TEXT NOT NULL DEFAULT '[]',
+                file_size INTEGER,
+                PRIMARY KEY (collection_id, item_id, asset_key)
+            )
+            "#,
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX idx_item_assets_media_type ON item_assets(media_type)",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX idx_item_assets_file_size ON item_assets(file_size)",
+            [],
+        )?;
+    }
+    Ok(!exists)
+}
+
+/// Indexes the assets of every item outside the trash, replacing what was
+/// indexed
+pub(crate) fn rebuild_index(conn: &Connection) -> Result<()> {
+    let items: Vec<DbItem> = {
+        let mut stmt =
+            conn.prepare("SELECT id, collection_id, data FROM items WHERE deleted_at IS NULL")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        rows.filter_map(|row| row.ok())
+            .filter_map(|(id, collection_id, data)| {
+                let mut item = serde_json::from_str::<DbItem>(&data).ok()?;
+                item.id = id;
+                item.collection_id = collection_id;
+                Some(item)
+            })
+            .collect()
+    };
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM item_assets", [])?;
+    for item in &items {
+        index_item(&tx, item)?;
+    }
+    tx.commit()
+}
+
+/// Indexes the media type, roles and `file:size` of each asset of an item,
+/// replacing its previous entries
+pub(crate) fn index_item(conn: &Connection, item: &DbItem) -> Result<()> {
+    remove(conn, &item.collection_id, Some(&item.id))?;
+    let Some(assets) = item.assets.as_ref().and_then(|assets| assets.as_object()) else {
+        return Ok(());
+    };
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO item_assets (collection_id, item_id, asset_key, media_type, roles, file_size)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )?;
+    for (key, asset) in assets {
+        let roles: Vec<&str> = asset
+            .get("roles")
+            .and_then(|roles| roles.as_array())
+            .map(|roles| roles.iter().filter_map(|role| role.as_str()).collect())
+            .unwrap_or_default();
+        stmt.execute(rusqlite::params![
+            item.collection_id,
+            item.id,
+            key,
+            asset.get("type").and_then(|v| v.as_str()),
+            Value::from(roles).to_string(),
+            asset.get("file:size").and_then(|v| v.as_i64()),
+        ])?;
+    }
+    Ok(())
+}
+
+/// Removes the assets of an item, or with `None` of all items of a
+/// collection, from the index
+pub(crate) fn remove(conn: &Connection, collection_id: &str, item_id: Option<&str>) -> Result<()> {
+    match item_id {
+        Some(item_id) => conn.execute(
+            "DELETE FROM item_assets WHERE collection_id = ? AND item_id = ?",
+            [collection_id, item_id],
+        )?,
+        None => conn.execute("DELETE FROM item_assets WHERE collection_id = ?", [collection_id])?,
+    };
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::{OptionalExtension, Result};
+use serde::Serialize;
+
+/// Size on disk of one item's asset folder
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetSize {
+    pub collection_id: String,
+    pub item_id: String,
+    pub bytes: u64,
+    pub file_count: u64,
+}
+
+/// Summed asset sizes of a collection, or of the whole store when
+/// `collection_id` is `None`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AssetSizeTotals {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection_id: Option<String>,
+    pub bytes: u64,
+    pub file_count: u64,
+}
+
+/// Cached asset folder sizes, so the UI can show storage use without walking
+/// the assets directory
+#[derive(Clone)]
+pub struct AssetSizeRepository {
+    db: DatabaseConnection,
+}
+
+impl AssetSizeRepository {
+    /// Creates a new asset size repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Records the size of an item's assets; an empty folder removes the entry
+    pub async fn set_item(&self, size: &AssetSize) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            if size.file_count == 0 {
+                conn.execute(
+                    "DELETE FROM asset_sizes WHERE collection_id = ? AND item_id = ?",
+                    [&size.collection_id, &size.item_id],
+                )?;
+                return Ok(());
+            }
+            conn.execute(
+                "INSERT OR REPLACE INTO asset_sizes (collection_id, item_id, bytes, file_count, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![
+                    size.collection_id,
+                    size.item_id,
+                    size.bytes as i64,
+                    size.file_count as i64,
+                    chrono::Utc::now().to_rfc3339()
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Replaces the entries of a collection, or of every collection when
+    /// `collection_id` is `None`, with the result of a recount
+    pub async fn replace(&self, collection_id: Option<&str>, sizes: &[AssetSize]) -> Result<()> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let tx = conn.transaction()?;
+            match collection_id {
+                Some(collection_id) => tx.execute(
+                    "DELETE FROM asset_sizes WHERE collection_id = ?",
+                    [collection_id],
+                )?,
+                None => tx.execute("DELETE FROM asset_sizes", [])?,
+            };
+            let now = chrono::Utc::now().to_rfc3339();
+            for size in sizes.iter().filter(|s| s.file_count > 0) {
+                tx.execute(
+                    "INSERT OR REPLACE INTO asset_sizes (collection_id, item_id, bytes, file_count, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    rusqlite::params![
+                        size.collection_id,
+                        size.item_id,
+                        size.bytes as i64,
+                        size.file_count as i64,
+                        now
+                    ],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+
+    /// Forgets the entry of a deleted item
+    pub async fn delete_for_item(&self, collection_id: &str, item_id: &str) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute(
+                "DELETE FROM asset_sizes WHERE collection_id = ? AND item_id = ?",
+                [collection_id, item_id],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Forgets the entries of a deleted collection
+    pub async fn delete_for_collection(&self, collection_id: &str) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute(
+                "DELETE FROM asset_sizes WHERE collection_id = ?",
+                [collection_id],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Total over the whole store
+    pub async fn total(&self) -> Result<AssetSizeTotals> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            conn.query_row(
+                "SELECT COALESCE(SUM(bytes), 0), COALESCE(SUM(file_count), 0) FROM asset_sizes",
+                [],
+                |row| {
+                    Ok(AssetSizeTotals {
+                        collection_id: None,
+                        bytes: row.get::<_, i64>(0)? as u64,
+                        file_count: row.get::<_, i64>(1)? as u64,
+                    })
+                },
+            )
+        })
+    }
+
+    /// Totals per collection, largest first
+    pub async fn collection_totals(&self) -> Result<Vec<AssetSizeTotals>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT collection_id, SUM(bytes) AS bytes, SUM(file_count) FROM asset_sizes GROUP BY collection_id ORDER BY bytes DESC",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok(AssetSizeTotals {
+                    collection_id: Some(row.get(0)?),
+                    bytes: row.get::<_, i64>(1)? as u64,
+                    file_count: row.get::<_, i64>(2)? as u64,
+                })
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// When the cache was last written, `None` if it has never been filled
+    pub async fn last_updated(&self) -> Result<Option<String>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            conn.query_row("SELECT MAX(updated_at) FROM asset_sizes", [], |row| {
+                row.get::<_, Option<String>>(0)
+            })
+            .optional()
+            .map(Option::flatten)
+        })
+    }
+}
@@ -0,0 +1,148 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::{OptionalExtension, Result};
+use serde::Serialize;
+
+/// Something the catalog check found wrong: an external href that did not
+/// answer with success, or a STAC rule a stored record breaks
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogIssue {
+    pub collection_id: String,
+    /// Empty for the collection itself
+    pub item_id: String,
+    /// `asset`, `link` or `stac`
+    pub kind: String,
+    /// Asset key, link relation, or JSON pointer of a STAC violation
+    pub name: String,
+    /// The href checked; empty for STAC violations
+    pub href: String,
+    /// HTTP status answered, when the request got that far
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Transport error or violation message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of the last catalog check
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CatalogCheckSummary {
+    pub started_at: String,
+    pub finished_at: String,
+    /// Distinct external URLs requested
+    pub checked_urls: u64,
+    /// Asset hrefs and links that failed, counted per reference
+    pub broken_links: u64,
+    pub stac_violations: u64,
+}
+
+/// Results of the scheduled link-rot and conformance check. Each run
+/// replaces those of the previous one.
+#[derive(Clone)]
+pub struct CatalogCheckRepository {
+    db: DatabaseConnection,
+}
+
+impl CatalogCheckRepository {
+    /// Creates a new catalog check repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Stores the outcome of a run in place of the previous one
+    pub async fn replace(
+        &self,
+        summary: &CatalogCheckSummary,
+        issues: &[CatalogIssue],
+    ) -> Result<()> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM catalog_check_issues", [])?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT INTO catalog_check_issues
+                        (collection_id, item_id, kind, name, href, status, error)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)",
+                )?;
+                for issue in issues {
+                    stmt.execute(rusqlite::params![
+                        issue.collection_id,
+                        issue.item_id,
+                        issue.kind,
+                        issue.name,
+                        issue.href,
+                        issue.status,
+                        issue.error
+                    ])?;
+                }
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO catalog_check_runs
+                    (id, started_at, finished_at, checked_urls, broken_links, stac_violations)
+                 VALUES (1, ?, ?, ?, ?, ?)",
+                rusqlite::params![
+                    summary.started_at,
+                    summary.finished_at,
+                    summary.checked_urls as i64,
+                    summary.broken_links as i64,
+                    summary.stac_violations as i64
+                ],
+            )?;
+            tx.commit()
+        })
+    }
+
+    /// Summary of the last run; `None` before the first one finished
+    pub async fn summary(&self) -> Result<Option<CatalogCheckSummary>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            conn.query_row(
+                "SELECT started_at, finished_at, checked_urls, broken_links, stac_violations
+                 FROM catalog_check_runs WHERE id = 1",
+                [],
+                |row| {
+                    Ok(CatalogCheckSummary {
+                        started_at: row.get(0)?,
+                        finished_at: row.get(1)?,
+                        checked_urls: row.get::<_, i64>(2)? as u64,
+                        broken_links: row.get::<_, i64>(3)? as u64,
+                        stac_violations: row.get::<_, i64>(4)? as u64,
+                    })
+                },
+            )
+            .optional()
+        })
+    }
+
+    /// Issues of the last run, optionally of one collection, ordered by
+    /// record
+    pub async fn issues(
+        &self,
+        collection_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<CatalogIssue>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT collection_id, item_id, kind, name, href, status, error
+                 FROM catalog_check_issues
+                 WHERE ?1 IS NULL OR collection_id = ?1
+                 ORDER BY collection_id, item_id, kind, name
+                 LIMIT ?2",
+            )?;
+            let rows = stmt.query_map(rusqlite::params![collection_id, limit], |row| {
+                Ok(CatalogIssue {
+                    collection_id: row.get(0)?,
+                    item_id: row.get(1)?,
+                    kind: row.get(2)?,
+                    name: row.get(3)?,
+                    href: row.get(4)?,
+                    status: row.get(5)?,
+                    error: row.get(6)?,
+                })
+            })?;
+            rows.collect()
+        })
+    }
+}
@@ -0,0 +1,316 @@
+use crate::database::connection::blocking;
+use crate::database::indexed_properties::check_property_name;
+use crate::database::{DatabaseConnection, DbItem};
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Mean Earth radius in km, as used for spherical areas and distances
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// A property a collection computes for its items from a formula, e.g.
+/// `{"property": "area_km2", "formula": "area_km2(geometry)"}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComputedProperty {
+    pub property: String,
+    pub formula: String,
+}
+
+impl ComputedProperty {
+    /// Checks the property name and formula, for 400 responses
+    pub fn check(&self) -> std::result::Result<(), String> {
+        check_property_name(&self.property)?;
+        if matches!(self.property.as_str(), "datetime" | "start_datetime" | "end_datetime") {
+            return Err(format!("'{}' cannot be computed", self.property));
+        }
+        Formula::parse(&self.formula).map(|_| ())
+    }
+}
+
+/// What a computed property is derived from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Formula {
+    /// `area_km2(geometry)`: area of the polygons, on a spherical Earth
+    AreaKm2,
+    /// `length_km(geometry)`: great-circle length of the lines, or the
+    /// perimeter of the polygons
+    LengthKm,
+    /// `age_days(<property>)`: whole days from a date-time property to now
+    AgeDays(String),
+    /// `duration_days(<start>, <end>)`: days between two date-time properties
+    DurationDays(String, String),
+}
+
+impl Formula {
+    pub fn parse(text: &str) -> std::result::Result<Self, String> {
+        let text = text.trim();
+        let (name, args) = text
+            .strip_suffix(')')
+            .and_then(|t| t.split_once('('))
+            .ok_or_else(|| format!("Formula '{}' is not of the form name(arguments)", text))?;
+        let args: Vec<&str> = args
+            .split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .collect();
+        for arg in &args {
+            check_property_name(arg)?;
+        }
+        match (name.trim(), args.as_slice()) {
+            ("area_km2", ["geometry"]) => Ok(Formula::AreaKm2),
+            ("length_km", ["geometry"]) => Ok(Formula::LengthKm),
+            ("age_days", [property]) => Ok(Formula::AgeDays(property.to_string())),
+            ("duration_days", [start, end]) => {
+                Ok(Formula::DurationDays(start.to_string(), end.to_string()))
+            }
+            ("area_km2" | "length_km", _) => Err(format!("{} takes the argument 'geometry'", name)),
+            ("age_days", _) => Err("age_days takes one date-time property".to_string()),
+            ("duration_days", _) => {
+                Err("duration_days takes a start and an end date-time property".to_string())
+            }
+            _ => Err(format!(
+                "Unknown formula '{}'; use area_km2, length_km, age_days or duration_days",
+                name.trim()
+            )),
+        }
+    }
+
+    /// Whether the value changes with the time it is evaluated at
+    pub fn is_time_relative(&self) -> bool {
+        matches!(self, Formula::AgeDays(_))
+    }
+
+    /// Value of the formula for an item, or `None` when its inputs are
+    /// missing
+    pub fn evaluate(&self, item: &DbItem, now: DateTime<Utc>) -> Option<Value> {
+        let datetime = |property: &str| {
+            item.properties
+                .get(property)
+                .and_then(|v| v.as_str())
+                .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+        };
+        match self {
+            Formula::AreaKm2 => item
+                .geometry
+                .as_ref()
+                .map(|g| Value::from(round3(geometry_measure(g, true)))),
+            Formula::LengthKm => item
+                .geometry
+                .as_ref()
+                .map(|g| Value::from(round3(geometry_measure(g, false)))),
+            Formula::AgeDays(property) => {
+                datetime(property).map(|dt| Value::from((now - dt).num_days()))
+            }
+            Formula::DurationDays(start, end) => {
+                let seconds = (datetime(end)? - datetime(start)?).num_seconds();
+                Some(Value::from(round3(seconds as f64 / 86_400.0)))
+            }
+        }
+    }
+}
+
+fn round3(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+/// Area in km² (`area`) or length in km of a GeoJSON geometry. Lines have no
+/// area; the length of a polygon is its perimeter, holes included.
+fn geometry_measure(geometry: &Value, area: bool) -> f64 {
+    let coordinates = geometry.get("coordinates");
+    let ring_value = |ring: &Value| {
+        let positions = positions(ring);
+        if area {
+            ring_area(&positions)
+        } else {
+            line_length(&positions)
+        }
+    };
+    let polygon_value = |polygon: &Value| -> f64 {
+        let rings = polygon.as_array().map(Vec::as_slice).unwrap_or(&[]);
+        match (area, rings.split_first()) {
+            (true, Some((outer, holes))) => {
+                (ring_value(outer) - holes.iter().map(ring_value).sum::<f64>()).max(0.0)
+            }
+            (true, None) => 0.0,
+            (false, _) => rings.iter().map(ring_value).sum(),
+        }
+    };
+    let list = |value: Option<&Value>| value.and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    match geometry.get("type").and_then(|t| t.as_str()) {
+        Some("LineString") if !area => coordinates.map(ring_value).unwrap_or(0.0),
+        Some("MultiLineString") if !area => list(coordinates).iter().map(ring_value).sum(),
+        Some("Polygon") => coordinates.map(polygon_value).unwrap_or(0.0),
+        Some("MultiPolygon") => list(coordinates).iter().map(polygon_value).sum(),
+        Some("GeometryCollection") => list(geometry.get("geometries"))
+            .iter()
+            .map(|g| geometry_measure(g, area))
+            .sum(),
+        _ => 0.0,
+    }
+}
+
+/// (longitude, latitude) pairs of a GeoJSON position list
+fn positions(value: &Value) -> Vec<(f64, f64)> {
+    value
+        .as_array()
+        .map(|positions| {
+            positions
+                .iter()
+                .filter_map(|p| Some((p.get(0)?.as_f64()?, p.get(1)?.as_f64()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Area of a ring on the sphere in km² (Chamberlain and Duquette, 2007)
+fn ring_area(ring: &[(f64, f64)]) -> f64 {
+    if ring.len() < 3 {
+        return 0.0;
+    }
+    let mut sum = 0.0;
+    for i in 0..ring.len() {
+        let (lon1, lat1) = ring[i];
+        let (lon2, lat2) = ring[(i + 1) % ring.len()];
+        sum += (lon2 - lon1).to_radians() * (2.0 + lat1.to_radians().sin() + lat2.to_radians().sin());
+    }
+    (sum * EARTH_RADIUS_KM * EARTH_RADIUS_KM / 2.0).abs()
+}
+
+/// Great-circle length of a line in km (haversine)
+fn line_length(line: &[(f64, f64)]) -> f64 {
+    line.windows(2)
+        .map(|pair| {
+            let ((lon1, lat1), (lon2, lat2)) = (pair[0], pair[1]);
+            let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+            let h = ((phi2 - phi1) / 2.0).sin().powi(2)
+                + phi1.cos() * phi2.cos() * ((lon2 - lon1).to_radians() / 2.0).sin().powi(2);
+            2.0 * EARTH_RADIUS_KM * h.sqrt().min(1.0).asin()
+        })
+        .sum()
+}
+
+/// Sets the computed properties of an item from its current geometry and
+/// properties, removing those whose inputs are missing
+pub fn apply(definitions: &[ComputedProperty], item: &mut DbItem, now: DateTime<Utc>) {
+    for definition in definitions {
+        let Ok(formula) = Formula::parse(&definition.formula) else {
+            continue;
+        };
+        let value = formula.evaluate(item, now);
+        if let Some(properties) = item.properties.as_object_mut() {
+            match value {
+                Some(value) => {
+                    properties.insert(definition.property.clone(), value);
+                }
+                None => {
+                    properties.remove(&definition.property);
+                }
+            }
+        }
+    }
+}
+
+/// Whether any of the definitions changes with the time items are served,
+/// so a served item cannot be revalidated by its stored version
+pub fn any_time_relative(definitions: &[ComputedProperty]) -> bool {
+    definitions.iter().any(|definition| {
+        Formula::parse(&definition.formula).is_ok_and(|formula| formula.is_time_relative())
+    })
+}
+
+/// Computed properties of a collection, read on the caller's connection
+pub(crate) fn definitions(conn: &Connection, collection_id: &str) -> Result<Vec<ComputedProperty>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT property, formula FROM computed_properties WHERE collection_id = ? ORDER BY property",
+    )?;
+    let rows = stmt.query_map([collection_id], |row| {
+        Ok(ComputedProperty {
+            property: row.get(0)?,
+            formula: row.get(1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[derive(Clone)]
+pub struct ComputedPropertyRepository {
+    db: DatabaseConnection,
+}
+
+impl ComputedPropertyRepository {
+    /// Creates a new computed property repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Computed properties of a collection
+    pub async fn for_collection(&self, collection_id: &str) -> Result<Vec<ComputedProperty>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            definitions(&conn, collection_id)
+        })
+    }
+
+    /// Computed properties of every collection that has any
+    pub async fn by_collection(&self) -> Result<HashMap<String, Vec<ComputedProperty>>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT collection_id, property, formula FROM computed_properties ORDER BY collection_id, property",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    ComputedProperty {
+                        property: row.get(1)?,
+                        formula: row.get(2)?,
+                    },
+                ))
+            })?;
+            let mut definitions: HashMap<String, Vec<ComputedProperty>> = HashMap::new();
+            for row in rows {
+                let (collection_id, definition) = row?;
+                definitions.entry(collection_id).or_default().push(definition);
+            }
+            Ok(definitions)
+        })
+    }
+
+    /// Replaces the computed properties of a collection
+    pub async fn set_for_collection(
+        &self,
+        collection_id: &str,
+        properties: &[ComputedProperty],
+    ) -> Result<()> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM computed_properties WHERE collection_id = ?",
+                [collection_id],
+            )?;
+            for property in properties {
+                tx.execute(
+                    "INSERT OR REPLACE INTO computed_properties (collection_id, property, formula) VALUES (?, ?, ?)",
+                    [collection_id, &property.property, &property.formula],
+                )?;
+            }
+            tx.commit()
+        })
+    }
+
+    /// Forgets the computed properties of a deleted collection
+    pub async fn delete_for_collection(&self, collection_id: &str) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute(
+                "DELETE FROM computed_properties WHERE collection_id = ?",
+                [collection_id],
+            )?;
+            Ok(())
+        })
+    }
+}
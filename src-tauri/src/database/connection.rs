@@ -1,25 +1,103 @@
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, OpenFlags, Result};
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::{Handle, RuntimeFlavor};
 use tokio::sync::Mutex;
 
+/// Read-only connections opened beside the writer
+const READ_CONNECTIONS: usize = 4;
+
+/// Prepared statements kept per connection. Repositories use
+/// `prepare_cached`, so the queries they run often are parsed once.
+const STATEMENT_CACHE: usize = 64;
+
+/// How long a statement waits for a lock held by another connection before
+/// failing with "database is locked"
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The database's connections: one that writes, and a small pool of
+/// read-only ones so searches and listings do not queue behind each other or
+/// behind writes. The file is in WAL mode, where readers see the last
+/// committed state while a write is in progress.
 #[derive(Clone)]
 pub struct DatabaseConnection {
     conn: Arc<Mutex<Connection>>,
+    readers: Arc<Vec<Mutex<Connection>>>,
+    next_reader: Arc<AtomicUsize>,
 }
 
 impl DatabaseConnection {
     /// Creates a new database connection
     pub async fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
         let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE);
+
+        // An in-memory catalog is shared between connections through the
+        // shared cache, which has no WAL and fails lock waits immediately, so
+        // it is served by the writer alone
+        let in_memory = crate::config::is_memory_database(&path.to_string_lossy());
+        let mut readers = Vec::new();
+        if !in_memory {
+            // journal_mode answers with the mode now in effect
+            let _: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+            conn.execute_batch("PRAGMA synchronous = NORMAL")?;
+            for _ in 0..READ_CONNECTIONS {
+                let reader = Connection::open_with_flags(
+                    path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY
+                        | OpenFlags::SQLITE_OPEN_URI
+                        | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?;
+                reader.busy_timeout(BUSY_TIMEOUT)?;
+                reader.set_prepared_statement_cache_capacity(STATEMENT_CACHE);
+                readers.push(Mutex::new(reader));
+            }
+        }
+
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            readers: Arc::new(readers),
+            next_reader: Arc::new(AtomicUsize::new(0)),
         })
     }
 
+    /// The writing connection. Everything that writes, or reads inside a
+    /// transaction, goes through it.
     pub async fn get_connection(&self) -> tokio::sync::MutexGuard<Connection> {
         self.conn.lock().await
     }
 
+    /// A read-only connection for queries: an idle one from the pool, or the
+    /// next in turn when all are busy. Falls back to the writer when there
+    /// is no pool.
+    pub async fn read_connection(&self) -> tokio::sync::MutexGuard<Connection> {
+        if self.readers.is_empty() {
+            return self.get_connection().await;
+        }
+        let start = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        for offset in 0..self.readers.len() {
+            let reader = &self.readers[(start + offset) % self.readers.len()];
+            if let Ok(guard) = reader.try_lock() {
+                return guard;
+            }
+        }
+        self.readers[start % self.readers.len()].lock().await
+    }
+}
 
+/// Runs SQLite calls, which block, without stalling the runtime: on a
+/// multi-threaded runtime the worker's other tasks are handed to other
+/// threads first, so a large result set does not hold up other requests. A
+/// single-threaded runtime cannot do that and runs them directly.
+pub fn blocking<R>(f: impl FnOnce() -> R) -> R {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
 }
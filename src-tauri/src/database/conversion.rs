@@ -1,9 +1,10 @@
+use crate::database::item_links::ItemLink;
 use crate::database::{DbCollection, DbItem};
 use crate::models::{
     collection::SummaryValue, link::Link, range::Range, Asset, Collection, Item, Properties,
 };
 use crate::server::utils::ServerConfig;
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 impl DbCollection {
     pub fn to_stac_collection(&self, server_config: &ServerConfig) -> Collection {
@@ -26,6 +27,24 @@ impl DbCollection {
             headers: None,
             body: None,
         });
+        links.push(Link {
+            href: format!("{}/queryables", server_config.collection_href(&self.id)),
+            rel: "http://www.opengis.net/def/rel/ogc/1.0/queryables".to_string(),
+            r#type: Some("application/schema+json".to_string()),
+            title: Some("Queryables".to_string()),
+            method: None,
+            headers: None,
+            body: None,
+        });
+        links.push(Link {
+            href: server_config.feed_href(Some(&self.id)),
+            rel: "alternate".to_string(),
+            r#type: Some("application/atom+xml".to_string()),
+            title: Some("Recently added items".to_string()),
+            method: None,
+            headers: None,
+            body: None,
+        });
         links.push(Link {
             href: server_config.root_href(),
             rel: "root".to_string(),
@@ -150,89 +169,12 @@ impl DbItem {
         let properties: Properties = serde_json::from_value(self.properties.clone())
             .unwrap_or_else(|_| Properties::new(None));
 
-        let links = if let Some(links_json) = &self.links {
-            let mut links: Vec<Link> =
-                serde_json::from_value(links_json.clone()).unwrap_or_else(|_| Vec::new());
-
-            // Add standard STAC links
-            links.push(Link {
-                href: server_config.root_href(),
-                rel: "root".to_string(),
-                r#type: Some("application/json".to_string()),
-                title: Some("Root Catalog".to_string()),
-                method: None,
-                headers: None,
-                body: None,
-            });
-            links.push(Link {
-                href: server_config.item_href(&self.collection_id, &self.id),
-                rel: "self".to_string(),
-                r#type: Some("application/geo+json".to_string()),
-                title: Some("This Item".to_string()),
-                method: None,
-                headers: None,
-                body: None,
-            });
-            links.push(Link {
-                href: server_config.collection_href(&self.collection_id),
-                rel: "collection".to_string(),
-                r#type: Some("application/json".to_string()),
-                title: Some("Collection".to_string()),
-                method: None,
-                headers: None,
-                body: None,
-            });
-            links.push(Link {
-                href: server_config.collection_href(&self.collection_id),
-                rel: "parent".to_string(),
-                r#type: Some("application/json".to_string()),
-                title: Some("Parent Collection".to_string()),
-                method: None,
-                headers: None,
-                body: None,
-            });
-
-            links
-        } else {
-            vec![
-                Link {
-                    href: server_config.root_href(),
-                    rel: "root".to_string(),
-                    r#type: Some("application/json".to_string()),
-                    title: Some("Root Catalog".to_string()),
-                    method: None,
-                    headers: None,
-                    body: None,
-                },
-                Link {
-                    href: server_config.item_href(&self.collection_id, &self.id),
-                    rel: "self".to_string(),
-                    r#type: Some("application/geo+json".to_string()),
-                    title: Some("This Item".to_string()),
-                    method: None,
-                    headers: None,
-                    body: None,
-                },
-                Link {
-                    href: server_config.collection_href(&self.collection_id),
-                    rel: "collection".to_string(),
-                    r#type: Some("application/json".to_string()),
-                    title: Some("Collection".to_string()),
-                    method: None,
-                    headers: None,
-                    body: None,
-                },
-                Link {
-                    href: server_config.collection_href(&self.collection_id),
-                    rel: "parent".to_string(),
-                    r#type: Some("application/json".to_string()),
-                    title: Some("Parent Collection".to_string()),
-                    method: None,
-                    headers: None,
-                    body: None,
-                },
-            ]
-        };
+        let mut links: Vec<Link> = self
+            .links
+            .as_ref()
+            .and_then(|links_json| serde_json::from_value(links_json.clone()).ok())
+            .unwrap_or_default();
+        links.extend(item_links(server_config, &self.collection_id, &self.id));
 
         let assets = if let Some(assets_json) = &self.assets {
             serde_json::from_value(assets_json.clone())
@@ -260,4 +202,100 @@ impl DbItem {
             collection: Some(self.collection_id.clone()),
         }
     }
+
+    /// Converts to STAC item JSON without the typed round trip: the stored
+    /// geometry, properties, assets and links are moved into the document as
+    /// they are and only the server's links are added. Serves the same
+    /// document as `to_stac_item_with_relations` for well-formed items, and
+    /// keeps fields the typed models do not know, such as extension fields on
+    /// assets.
+    pub fn into_stac_item_json(self, server_config: &ServerConfig, relations: &[ItemLink]) -> Value {
+        let mut links = match self.links {
+            Some(Value::Array(links)) => links,
+            _ => Vec::new(),
+        };
+        let generated = item_links(server_config, &self.collection_id, &self.id)
+            .into_iter()
+            .chain(relations.iter().map(|relation| relation_link(server_config, relation)));
+        links.extend(generated.map(|link| serde_json::to_value(link).unwrap_or(Value::Null)));
+
+        let mut properties = match self.properties {
+            Value::Object(properties) => properties,
+            _ => Map::new(),
+        };
+        properties.entry("datetime").or_insert(Value::Null);
+
+        let mut item = Map::new();
+        item.insert("type".to_string(), Value::String(self.r#type));
+        item.insert("stac_version".to_string(), Value::String(self.stac_version));
+        if let Some(extensions @ Value::Array(_)) = self.stac_extensions {
+            item.insert("stac_extensions".to_string(), extensions);
+        }
+        item.insert("id".to_string(), Value::String(self.id));
+        item.insert("geometry".to_string(), self.geometry.unwrap_or(Value::Null));
+        if let Some(bbox @ Value::Array(_)) = self.bbox {
+            item.insert("bbox".to_string(), bbox);
+        }
+        item.insert("properties".to_string(), Value::Object(properties));
+        item.insert("links".to_string(), Value::Array(links));
+        let assets = match self.assets {
+            Some(assets @ Value::Object(_)) => assets,
+            _ => Value::Object(Map::new()),
+        };
+        item.insert("assets".to_string(), assets);
+        item.insert("collection".to_string(), Value::String(self.collection_id));
+        Value::Object(item)
+    }
+
+    /// Converts to a STAC item and appends a link for each declared relationship
+    /// (`derived_from`, `source`, `sibling`)
+    pub fn to_stac_item_with_relations(
+        &self,
+        server_config: &ServerConfig,
+        relations: &[ItemLink],
+    ) -> Item {
+        let mut item = self.to_stac_item(server_config);
+        item.links.extend(
+            relations
+                .iter()
+                .map(|relation| relation_link(server_config, relation)),
+        );
+        item
+    }
+}
+
+/// Links the server adds to every item
+fn item_links(server_config: &ServerConfig, collection_id: &str, item_id: &str) -> Vec<Link> {
+    let link = |href: String, rel: &str, media_type: &str, title: &str| Link {
+        href,
+        rel: rel.to_string(),
+        r#type: Some(media_type.to_string()),
+        title: Some(title.to_string()),
+        method: None,
+        headers: None,
+        body: None,
+    };
+    let item_href = server_config.item_href(collection_id, item_id);
+    let collection_href = server_config.collection_href(collection_id);
+    vec![
+        link(server_config.root_href(), "root", "application/json", "Root Catalog"),
+        link(item_href.clone(), "self", "application/geo+json", "This Item"),
+        link(collection_href.clone(), "collection", "application/json", "Collection"),
+        link(collection_href, "parent", "application/json", "Parent Collection"),
+        // Plain JSON alternate for clients that don't accept application/geo+json
+        link(format!("{}?f=json", item_href), "alternate", "application/json", "This Item as JSON"),
+    ]
+}
+
+/// Link for a declared relationship (`derived_from`, `source`, `sibling`)
+fn relation_link(server_config: &ServerConfig, relation: &ItemLink) -> Link {
+    Link {
+        href: server_config.item_href(&relation.target_collection, &relation.target_item),
+        rel: relation.rel.rel().to_string(),
+        r#type: Some("application/geo+json".to_string()),
+        title: None,
+        method: None,
+        headers: None,
+        body: None,
+    }
 }
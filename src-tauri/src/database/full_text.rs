@@ -0,0 +1,204 @@
+use crate::database::{DbCollection, DbItem};
+use rusqlite::{Connection, Result};
+use serde_json::Value;
+
+/// Creates the full-text index over collection and item metadata. Returns
+/// whether it was created, in which case it still has to be filled.
+pub(crate) fn create_index(conn: &Connection) -> Result<bool> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'search_text')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        // Collections are indexed with an empty item_id
+        conn.execute(
+            r#"
+            CREATE VIRTUAL TABLE search_text USING fts5(
+                collection_id UNINDEXED,
+                item_id UNINDEXED,
+                title,
+                description,
+                keywords,
+                properties,
+                tokenize = 'unicode61 remove_diacritics 2'
+            )
+            "#,
+            [],
+        )?;
+    }
+    Ok(!exists)
+}
+
+/// Indexes every collection and item outside the trash, replacing what was
+/// indexed
+pub(crate) fn rebuild_index(conn: &Connection) -> Result<()> {
+    let collections: Vec<DbCollection> = {
+        let mut stmt = conn.prepare("SELECT data FROM collections WHERE deleted_at IS NULL")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.filter_map(|row| row.ok())
+            .filter_map(|data| serde_json::from_str(&data).ok())
+            .collect()
+    };
+    let items: Vec<DbItem> = {
+        let mut stmt =
+            conn.prepare("SELECT id, collection_id, data FROM items WHERE deleted_at IS NULL")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        rows.filter_map(|row| row.ok())
+            .filter_map(|(id, collection_id, data)| {
+                let mut item = serde_json::from_str::<DbItem>(&data).ok()?;
+                item.id = id;
+                item.collection_id = collection_id;
+                Some(item)
+            })
+            .collect()
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM search_text", [])?;
+    for collection in &collections {
+        index_collection(&tx, collection)?;
+    }
+    for item in &items {
+        index_item(&tx, item)?;
+    }
+    tx.commit()
+}
+
+/// Indexes the title, description, keywords and provider names of a
+/// collection, replacing its previous entry
+pub(crate) fn index_collection(conn: &Connection, collection: &DbCollection) -> Result<()> {
+    let mut other = Vec::new();
+    if let Some(providers) = &collection.providers {
+        collect_text(providers, &mut other);
+    }
+    if let Some(summaries) = &collection.summaries {
+        collect_text(summaries, &mut other);
+    }
+    replace(
+        conn,
+        &collection.id,
+        "",
+        collection.title.as_deref().unwrap_or(""),
+        &collection.description,
+        &keywords_text(collection.keywords.as_ref()),
+        &other.join(" "),
+    )
+}
+
+/// Indexes the title, description, keywords and other text properties of an
+/// item, replacing its previous entry
+pub(crate) fn index_item(conn: &Connection, item: &DbItem) -> Result<()> {
+    let text = |key: &str| {
+        item.properties
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let mut other = vec![item.id.clone()];
+    if let Some(properties) = item.properties.as_object() {
+        for (key, value) in properties {
+            if !matches!(key.as_str(), "title" | "description" | "keywords") {
+                collect_text(value, &mut other);
+            }
+        }
+    }
+    replace(
+        conn,
+        &item.collection_id,
+        &item.id,
+        &text("title"),
+        &text("description"),
+        &keywords_text(item.properties.get("keywords")),
+        &other.join(" "),
+    )
+}
+
+/// Removes an item, or with `None` a collection and all of its items, from
+/// the index
+pub(crate) fn remove(conn: &Connection, collection_id: &str, item_id: Option<&str>) -> Result<()> {
+    match item_id {
+        Some(item_id) => conn.execute(
+            "DELETE FROM search_text WHERE collection_id = ? AND item_id = ?",
+            [collection_id, item_id],
+        )?,
+        None => conn.execute("DELETE FROM search_text WHERE collection_id = ?", [collection_id])?,
+    };
+    Ok(())
+}
+
+fn replace(
+    conn: &Connection,
+    collection_id: &str,
+    item_id: &str,
+    title: &str,
+    description: &str,
+    keywords: &str,
+    properties: &str,
+) -> Result<()> {
+    conn.execute(
+        "DELETE FROM search_text WHERE collection_id = ? AND item_id = ?",
+        [collection_id, item_id],
+    )?;
+    conn.execute(
+        "INSERT INTO search_text (collection_id, item_id, title, description, keywords, properties)
+         VALUES (?, ?, ?, ?, ?, ?)",
+        [collection_id, item_id, title, description, keywords, properties],
+    )?;
+    Ok(())
+}
+
+fn keywords_text(keywords: Option<&Value>) -> String {
+    let mut words = Vec::new();
+    if let Some(keywords) = keywords {
+        collect_text(keywords, &mut words);
+    }
+    words.join(" ")
+}
+
+/// Strings found anywhere in a JSON value; numbers and booleans are not
+/// worth a free-text match
+fn collect_text(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(text) => out.push(text.clone()),
+        Value::Array(values) => values.iter().for_each(|v| collect_text(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_text(v, out)),
+        _ => {}
+    }
+}
+
+/// FTS5 `MATCH` expression of a free-text `q` parameter, or `None` when it
+/// has no words. Comma-separated terms match if any does; the words of a
+/// term must all match, and a trailing `*` matches a word prefix. Every word
+/// is quoted, so FTS5 operators in the input are searched as text.
+pub fn match_expression(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split(',')
+        .filter_map(|term| {
+            let words: Vec<String> = term
+                .split_whitespace()
+                .filter_map(|word| {
+                    let (word, prefix) = match word.strip_suffix('*') {
+                        Some(word) => (word, true),
+                        None => (word, false),
+                    };
+                    let word = word.trim_matches('"');
+                    if word.is_empty() {
+                        return None;
+                    }
+                    let quoted = format!("\"{}\"", word.replace('"', "\"\""));
+                    Some(if prefix { format!("{}*", quoted) } else { quoted })
+                })
+                .collect();
+            (!words.is_empty()).then(|| format!("({})", words.join(" AND ")))
+        })
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" OR "))
+}
@@ -0,0 +1,215 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::{Connection, Result};
+use serde::{Deserialize, Serialize};
+
+/// Value type of an indexed property, which decides how it is compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexedPropertyKind {
+    Number,
+    String,
+}
+
+impl IndexedPropertyKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            IndexedPropertyKind::Number => "number",
+            IndexedPropertyKind::String => "string",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "number" => Some(IndexedPropertyKind::Number),
+            "string" => Some(IndexedPropertyKind::String),
+            _ => None,
+        }
+    }
+
+    fn column_type(&self) -> &'static str {
+        match self {
+            IndexedPropertyKind::Number => "REAL",
+            IndexedPropertyKind::String => "TEXT",
+        }
+    }
+}
+
+/// An item property a collection searches on often enough to index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedProperty {
+    pub property: String,
+    #[serde(rename = "type")]
+    pub kind: IndexedPropertyKind,
+}
+
+impl IndexedProperty {
+    /// Generated column on the items table holding the property. The name is
+    /// derived from the property so collections indexing the same property
+    /// share the column.
+    pub fn column(&self) -> String {
+        let hex: String = self.property.bytes().map(|b| format!("{:02x}", b)).collect();
+        format!("prop_{}_{}", self.kind.as_str(), hex)
+    }
+}
+
+/// `json_extract` expression reading a property from an item document
+pub fn property_expression(property: &str) -> String {
+    format!("json_extract(data, '$.properties.\"{}\"')", property)
+}
+
+/// Checks that a property name can be used in a JSON path
+pub fn check_property_name(property: &str) -> std::result::Result<(), String> {
+    if property.is_empty() {
+        return Err("Property name is empty".to_string());
+    }
+    if property.contains(['"', '\'', '\\']) || property.chars().any(char::is_control) {
+        return Err(format!("Property name '{}' contains unsupported characters", property));
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct IndexedPropertyRepository {
+    db: DatabaseConnection,
+}
+
+impl IndexedPropertyRepository {
+    /// Creates a new indexed property repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Indexed properties of a collection
+    pub async fn for_collection(&self, collection_id: &str) -> Result<Vec<IndexedProperty>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT property, kind FROM indexed_properties WHERE collection_id = ? ORDER BY property",
+            )?;
+            let rows = stmt.query_map([collection_id], |row| {
+                let property: String = row.get(0)?;
+                let kind: String = row.get(1)?;
+                Ok((property, kind))
+            })?;
+
+            let mut properties = Vec::new();
+            for row in rows {
+                let (property, kind) = row?;
+                if let Some(kind) = IndexedPropertyKind::parse(&kind) {
+                    properties.push(IndexedProperty { property, kind });
+                }
+            }
+            Ok(properties)
+        })
+    }
+
+    /// Replaces the indexed properties of a collection, adding generated
+    /// columns and indexes for new ones and dropping those no collection uses
+    /// anymore
+    pub async fn set_for_collection(
+        &self,
+        collection_id: &str,
+        properties: &[IndexedProperty],
+    ) -> Result<()> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM indexed_properties WHERE collection_id = ?",
+                [collection_id],
+            )?;
+            for property in properties {
+                tx.execute(
+                    "INSERT OR REPLACE INTO indexed_properties (collection_id, property, kind) VALUES (?, ?, ?)",
+                    [collection_id, &property.property, property.kind.as_str()],
+                )?;
+                ensure_column(&tx, property)?;
+            }
+            drop_unused_columns(&tx)?;
+            tx.commit()
+        })
+    }
+
+    /// Forgets the indexed properties of a deleted collection
+    pub async fn delete_for_collection(&self, collection_id: &str) -> Result<()> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "DELETE FROM indexed_properties WHERE collection_id = ?",
+                [collection_id],
+            )?;
+            drop_unused_columns(&tx)?;
+            tx.commit()
+        })
+    }
+}
+
+/// Distinct (property, type) pairs indexed by any collection. Their generated
+/// columns cover all items, so a search may use them whichever collections it
+/// spans.
+pub(crate) fn indexed_columns(conn: &Connection) -> Result<Vec<IndexedProperty>> {
+    let mut stmt = conn.prepare_cached("SELECT DISTINCT property, kind FROM indexed_properties")?;
+    let rows = stmt.query_map([], |row| {
+        let property: String = row.get(0)?;
+        let kind: String = row.get(1)?;
+        Ok((property, kind))
+    })?;
+
+    let mut columns = Vec::new();
+    for row in rows {
+        let (property, kind) = row?;
+        if let Some(kind) = IndexedPropertyKind::parse(&kind) {
+            columns.push(IndexedProperty { property, kind });
+        }
+    }
+    Ok(columns)
+}
+
+fn item_columns(conn: &Connection) -> Result<Vec<String>> {
+    // table_xinfo also lists generated columns
+    let mut stmt = conn.prepare("PRAGMA table_xinfo(items)")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    rows.collect()
+}
+
+/// Adds the generated column and index of a property if missing. Virtual
+/// columns take no space; SQLite keeps the index current on every write.
+fn ensure_column(conn: &Connection, property: &IndexedProperty) -> Result<()> {
+    let column = property.column();
+    if !item_columns(conn)?.contains(&column) {
+        conn.execute(
+            &format!(
+                "ALTER TABLE items ADD COLUMN {} {} GENERATED ALWAYS AS ({}) VIRTUAL",
+                column,
+                property.kind.column_type(),
+                property_expression(&property.property)
+            ),
+            [],
+        )?;
+    }
+    conn.execute(
+        &format!(
+            "CREATE INDEX IF NOT EXISTS idx_items_{} ON items(collection_id, {})",
+            column, column
+        ),
+        [],
+    )?;
+    Ok(())
+}
+
+fn drop_unused_columns(conn: &Connection) -> Result<()> {
+    let in_use: Vec<String> = indexed_columns(conn)?
+        .iter()
+        .map(IndexedProperty::column)
+        .collect();
+
+    for column in item_columns(conn)? {
+        if column.starts_with("prop_") && !in_use.contains(&column) {
+            conn.execute(&format!("DROP INDEX IF EXISTS idx_items_{}", column), [])?;
+            conn.execute(&format!("ALTER TABLE items DROP COLUMN {}", column), [])?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Typed relationship from one item to another, possibly in another collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemRelation {
+    /// The item was produced from the target, e.g. a COG derived from a raw scene
+    DerivedFrom,
+    /// The target is the original acquisition the item belongs to
+    Source,
+    /// Items of the same acquisition or product family; stored in both directions
+    Sibling,
+}
+
+impl ItemRelation {
+    /// Link relation type emitted in STAC items
+    pub fn rel(&self) -> &'static str {
+        match self {
+            ItemRelation::DerivedFrom => "derived_from",
+            ItemRelation::Source => "source",
+            ItemRelation::Sibling => "sibling",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "derived_from" => Some(ItemRelation::DerivedFrom),
+            "source" => Some(ItemRelation::Source),
+            "sibling" => Some(ItemRelation::Sibling),
+            _ => None,
+        }
+    }
+}
+
+/// A relationship stored in the `item_links` table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemLink {
+    pub source_collection: String,
+    pub source_item: String,
+    pub rel: ItemRelation,
+    pub target_collection: String,
+    pub target_item: String,
+}
+
+impl ItemLink {
+    /// The same relationship seen from the target item
+    fn reversed(&self) -> Self {
+        ItemLink {
+            source_collection: self.target_collection.clone(),
+            source_item: self.target_item.clone(),
+            rel: self.rel,
+            target_collection: self.source_collection.clone(),
+            target_item: self.source_item.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ItemLinkRepository {
+    db: DatabaseConnection,
+}
+
+impl ItemLinkRepository {
+    /// Creates a new item link repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Adds a relationship; sibling relationships are stored in both directions
+    pub async fn add(&self, link: &ItemLink) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let mut links = vec![link.clone()];
+            if link.rel == ItemRelation::Sibling {
+                links.push(link.reversed());
+            }
+            for link in links {
+                conn.execute(
+                    r#"
+                    INSERT OR IGNORE INTO item_links
+                        (source_collection, source_item, rel, target_collection, target_item)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
+                    "#,
+                    rusqlite::params![
+                        link.source_collection,
+                        link.source_item,
+                        link.rel.rel(),
+                        link.target_collection,
+                        link.target_item
+                    ],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes a relationship, returning whether it existed
+    pub async fn remove(&self, link: &ItemLink) -> Result<bool> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let mut links = vec![link.clone()];
+            if link.rel == ItemRelation::Sibling {
+                links.push(link.reversed());
+            }
+            let mut removed = 0;
+            for link in links {
+                removed += conn.execute(
+                    r#"
+                    DELETE FROM item_links
+                    WHERE source_collection = ?1 AND source_item = ?2 AND rel = ?3
+                        AND target_collection = ?4 AND target_item = ?5
+                    "#,
+                    rusqlite::params![
+                        link.source_collection,
+                        link.source_item,
+                        link.rel.rel(),
+                        link.target_collection,
+                        link.target_item
+                    ],
+                )?;
+            }
+            Ok(removed > 0)
+        })
+    }
+
+    /// Relationships declared by an item
+    pub async fn outgoing(&self, collection_id: &str, item_id: &str) -> Result<Vec<ItemLink>> {
+        self.query(
+            "WHERE source_collection = ?1 AND source_item = ?2",
+            vec![collection_id.to_string(), item_id.to_string()],
+        )
+        .await
+    }
+
+    /// Relationships pointing at an item, e.g. the products derived from it
+    pub async fn incoming(&self, collection_id: &str, item_id: &str) -> Result<Vec<ItemLink>> {
+        self.query(
+            "WHERE target_collection = ?1 AND target_item = ?2 AND rel != 'sibling'",
+            vec![collection_id.to_string(), item_id.to_string()],
+        )
+        .await
+    }
+
+    /// Relationships declared by the items of a collection, keyed by item id
+    pub async fn outgoing_for_collection(
+        &self,
+        collection_id: &str,
+    ) -> Result<HashMap<String, Vec<ItemLink>>> {
+        let links = self
+            .query("WHERE source_collection = ?1", vec![collection_id.to_string()])
+            .await?;
+        let mut by_item: HashMap<String, Vec<ItemLink>> = HashMap::new();
+        for link in links {
+            by_item.entry(link.source_item.clone()).or_default().push(link);
+        }
+        Ok(by_item)
+    }
+
+    /// Relationships declared by the items of several collections, keyed by
+    /// collection and item id, in one query
+    pub async fn outgoing_for_collections(
+        &self,
+        collection_ids: &[String],
+    ) -> Result<HashMap<String, HashMap<String, Vec<ItemLink>>>> {
+        if collection_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let placeholders = vec!["?"; collection_ids.len()].join(", ");
+        let links = self
+            .query(
+                &format!("WHERE source_collection IN ({})", placeholders),
+                collection_ids.to_vec(),
+            )
+            .await?;
+        let mut by_collection: HashMap<String, HashMap<String, Vec<ItemLink>>> = HashMap::new();
+        for link in links {
+            by_collection
+                .entry(link.source_collection.clone())
+                .or_default()
+                .entry(link.source_item.clone())
+                .or_default()
+                .push(link);
+        }
+        Ok(by_collection)
+    }
+
+    /// Removes every relationship from or to an item
+    pub async fn delete_for_item(&self, collection_id: &str, item_id: &str) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute(
+                r#"
+                DELETE FROM item_links
+                WHERE (source_collection = ?1 AND source_item = ?2)
+                    OR (target_collection = ?1 AND target_item = ?2)
+                "#,
+                rusqlite::params![collection_id, item_id],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Removes every relationship from or to the items of a collection
+    pub async fn delete_for_collection(&self, collection_id: &str) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute(
+                "DELETE FROM item_links WHERE source_collection = ?1 OR target_collection = ?1",
+                [collection_id],
+            )?;
+            Ok(())
+        })
+    }
+
+    async fn query(&self, filter: &str, params: Vec<String>) -> Result<Vec<ItemLink>> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(&format!(
+                r#"
+                SELECT source_collection, source_item, rel, target_collection, target_item
+                FROM item_links
+                {}
+                ORDER BY rel, target_collection, target_item
+                "#,
+                filter
+            ))?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                let rel: String = row.get(2)?;
+                Ok(ItemLink {
+                    source_collection: row.get(0)?,
+                    source_item: row.get(1)?,
+                    rel: ItemRelation::parse(&rel).unwrap_or(ItemRelation::Sibling),
+                    target_collection: row.get(3)?,
+                    target_item: row.get(4)?,
+                })
+            })?;
+
+            let mut links = Vec::new();
+            for row in rows {
+                links.push(row?);
+            }
+            Ok(links)
+        })
+    }
+}
@@ -0,0 +1,98 @@
+use crate::database::connection::blocking;
+use crate::database::spatial_index;
+use crate::database::DatabaseConnection;
+use rusqlite::{Connection, Result};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Problems reported by `integrity_check` beyond this many are not listed
+const MAX_INTEGRITY_ERRORS: i64 = 100;
+
+/// Outcome of `MaintenanceRepository::run`
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceReport {
+    /// Whether `PRAGMA integrity_check` found the file sound
+    pub integrity_ok: bool,
+    /// What the integrity check found wrong; empty when it passed
+    pub integrity_errors: Vec<String>,
+    /// Whether the file was rewritten. A damaged file is left as it is, so
+    /// it can still be backed up or repaired.
+    pub vacuumed: bool,
+    /// Size in bytes of the database and its write-ahead log before and
+    /// after
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+/// Checks and compacts the database file
+#[derive(Clone)]
+pub struct MaintenanceRepository {
+    db: DatabaseConnection,
+    /// `None` for an in-memory catalog, whose size is counted in pages
+    path: Option<PathBuf>,
+}
+
+impl MaintenanceRepository {
+    /// Creates a new maintenance repository
+    pub fn new(db: DatabaseConnection, db_path: &str) -> Self {
+        let path = (!crate::config::is_memory_database(db_path)).then(|| PathBuf::from(db_path));
+        Self { db, path }
+    }
+
+    /// Runs `PRAGMA integrity_check`, then, if it passed, `VACUUM` to give
+    /// the space of deleted records back to the file system and `ANALYZE` to
+    /// refresh the query planner's statistics. Holds the writing connection
+    /// throughout, so writes wait until it is done.
+    pub async fn run(&self) -> Result<MaintenanceReport> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let size_before = self.size(&conn)?;
+            let integrity_errors = integrity_errors(&conn)?;
+            let integrity_ok = integrity_errors.is_empty();
+            if integrity_ok {
+                conn.execute_batch("VACUUM")?;
+                // VACUUM may renumber item rows, which the R*Tree is keyed by
+                spatial_index::rebuild_index(&conn)?;
+                conn.execute_batch("ANALYZE")?;
+                // In WAL mode VACUUM writes the whole file through the log;
+                // truncating it afterwards is what frees the space
+                if self.path.is_some() {
+                    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+                }
+            }
+            Ok(MaintenanceReport {
+                integrity_ok,
+                integrity_errors,
+                vacuumed: integrity_ok,
+                size_before,
+                size_after: self.size(&conn)?,
+            })
+        })
+    }
+
+    /// Bytes taken by the database file and its write-ahead log
+    fn size(&self, conn: &Connection) -> Result<u64> {
+        match &self.path {
+            Some(path) => {
+                let len = |path: PathBuf| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+                let mut wal = path.clone().into_os_string();
+                wal.push("-wal");
+                Ok(len(path.clone()) + len(PathBuf::from(wal)))
+            }
+            None => conn.query_row(
+                "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+                [],
+                |row| row.get::<_, i64>(0).map(|size| size as u64),
+            ),
+        }
+    }
+}
+
+/// Problems `PRAGMA integrity_check` reports; it answers a single `ok` when
+/// there are none
+fn integrity_errors(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA integrity_check({})", MAX_INTEGRITY_ERRORS))?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let messages = rows.collect::<Result<Vec<_>>>()?;
+    Ok(messages.into_iter().filter(|message| message != "ok").collect())
+}
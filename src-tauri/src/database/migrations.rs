@@ -0,0 +1,255 @@
+use crate::database::sync_revisions::content_hash;
+use crate::database::{schema, spatial_index};
+use crate::database::{DatabaseConnection, DbCollection, DbItem};
+use rusqlite::Connection;
+
+/// A change to the schema, applied once per database in version order
+struct Migration {
+    version: u32,
+    name: &'static str,
+    apply: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+/// Every migration, in order. A new schema change is appended here with the
+/// next version and never edited once released. Migrations are not wrapped
+/// in a transaction (some rebuild indexes in their own), so each must be
+/// safe to run again: one interrupted before its version was recorded runs
+/// again on the next start.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        apply: schema::baseline,
+    },
+    Migration {
+        version: 2,
+        name: "content_hashes",
+        apply: content_hashes,
+    },
+    Migration {
+        version: 3,
+        name: "antimeridian_index",
+        apply: spatial_index::rebuild_index,
+    },
+    Migration {
+        version: STABLE_HASHES_VERSION,
+        name: "stable_content_hashes",
+        apply: stable_content_hashes,
+    },
+];
+
+/// Version from which stored content hashes are SHA-256; a database below
+/// it holds hashes no other build can reproduce
+pub const STABLE_HASHES_VERSION: u32 = 4;
+
+/// Rows hashed per transaction by `content_hashes`
+const HASH_BATCH: i64 = 1000;
+
+/// Schema version this build creates and understands
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Database(rusqlite::Error),
+    /// The database was written by a newer release, whose schema this one
+    /// cannot safely use
+    TooNew {
+        found: u32,
+        supported: u32,
+    },
+    Failed {
+        version: u32,
+        name: &'static str,
+        error: rusqlite::Error,
+    },
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::Database(e) => write!(f, "Schema migration database error: {}", e),
+            MigrationError::TooNew { found, supported } => write!(
+                f,
+                "The database has schema version {}, but this version of ZenSTAC supports up to {}. Upgrade ZenSTAC to open it.",
+                found, supported
+            ),
+            MigrationError::Failed { version, name, error } => {
+                write!(f, "Schema migration {} ({}) failed: {}", version, name, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Database(e)
+    }
+}
+
+/// Version of the database's schema: the highest migration recorded, or 0
+/// for a new database or one from before versions were recorded
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )
+        "#,
+        [],
+    )?;
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Brings the database to the latest schema version on startup, applying
+/// the missing migrations in order and recording each. Refuses a database
+/// with a newer version than this build knows, before changing anything.
+pub async fn migrate(db: &DatabaseConnection) -> Result<(), MigrationError> {
+    let conn = db.get_connection().await;
+    let current = schema_version(&conn)?;
+    let supported = latest_version();
+    if current > supported {
+        return Err(MigrationError::TooNew {
+            found: current,
+            supported,
+        });
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        (migration.apply)(&conn).map_err(|error| MigrationError::Failed {
+            version: migration.version,
+            name: migration.name,
+            error,
+        })?;
+        conn.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?, ?, ?)",
+            rusqlite::params![
+                migration.version,
+                migration.name,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Whether the stored content hashes of a database opened without migrating
+/// it can be compared with this build's
+pub fn has_stable_hashes(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get::<_, u32>(0),
+    )
+    .is_ok_and(|version| version >= STABLE_HASHES_VERSION)
+}
+
+/// Hashes every collection and item again. The hashes stored before were
+/// made with `DefaultHasher`, whose output may change with the Rust release.
+/// Sync revisions that matched their record keep matching it, so peer sync
+/// does not count the new hashes as local changes.
+fn stable_content_hashes(conn: &Connection) -> rusqlite::Result<()> {
+    // Marked first: the old hashes are gone once the records are hashed again
+    conn.execute(
+        &format!(
+            "UPDATE sync_revisions SET hash = '{}' WHERE deleted = 0 AND hash = COALESCE({}, '')",
+            UNCHANGED_REVISION, REVISION_RECORD_HASH
+        ),
+        [],
+    )?;
+    conn.execute("UPDATE collections SET content_hash = NULL", [])?;
+    conn.execute("UPDATE items SET content_hash = NULL", [])?;
+    content_hashes(conn)?;
+    conn.execute(
+        &format!(
+            "UPDATE sync_revisions SET hash = COALESCE({}, '') WHERE hash = '{}'",
+            REVISION_RECORD_HASH, UNCHANGED_REVISION
+        ),
+        [],
+    )?;
+    Ok(())
+}
+
+/// Placeholder hash of a sync revision whose record did not change since it
+/// was last bumped, while `stable_content_hashes` runs
+const UNCHANGED_REVISION: &str = "unchanged";
+
+/// Stored content hash of the record a `sync_revisions` row describes
+const REVISION_RECORD_HASH: &str = "CASE WHEN sync_revisions.item_id = ''
+    THEN (SELECT content_hash FROM collections
+          WHERE collections.id = sync_revisions.collection_id AND collections.deleted_at IS NULL)
+    ELSE (SELECT content_hash FROM items
+          WHERE items.collection_id = sync_revisions.collection_id
+            AND items.id = sync_revisions.item_id AND items.deleted_at IS NULL)
+    END";
+
+/// Keeps the content hash of each collection and item in a column, so sync,
+/// catalog diffs and exports can find unchanged records without reading
+/// them, and hashes the records stored before
+fn content_hashes(conn: &Connection) -> rusqlite::Result<()> {
+    schema::add_column_if_missing(conn, "collections", "content_hash", "TEXT")?;
+    schema::add_column_if_missing(conn, "items", "content_hash", "TEXT")?;
+
+    let collections = {
+        let mut stmt =
+            conn.prepare("SELECT id, data FROM collections WHERE content_hash IS NULL")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    for (id, data) in collections {
+        if let Ok(collection) = serde_json::from_str::<DbCollection>(&data) {
+            conn.execute(
+                "UPDATE collections SET content_hash = ? WHERE id = ?",
+                [content_hash(&collection), id],
+            )?;
+        }
+    }
+
+    // In batches by rowid, so a large catalog is not held in memory and rows
+    // that fail to parse are passed over rather than read again
+    let mut after = 0i64;
+    loop {
+        let rows = {
+            let mut stmt = conn.prepare(
+                "SELECT rowid, id, collection_id, data FROM items
+                 WHERE rowid > ? AND content_hash IS NULL ORDER BY rowid LIMIT ?",
+            )?;
+            let rows = stmt.query_map([after, HASH_BATCH], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        let Some((last, ..)) = rows.last() else {
+            return Ok(());
+        };
+        after = *last;
+
+        let tx = conn.unchecked_transaction()?;
+        for (rowid, id, collection_id, data) in rows {
+            let Ok(mut item) = serde_json::from_str::<DbItem>(&data) else {
+                continue;
+            };
+            item.id = id;
+            item.collection_id = collection_id;
+            tx.execute(
+                "UPDATE items SET content_hash = ? WHERE rowid = ?",
+                rusqlite::params![content_hash(&item), rowid],
+            )?;
+        }
+        tx.commit()?;
+    }
+}
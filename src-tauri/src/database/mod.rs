@@ -1,12 +1,48 @@
+pub mod access_stats;
+pub mod asset_index;
+pub mod asset_sizes;
+pub mod catalog_checks;
+pub mod computed_properties;
 pub mod connection;
 pub mod conversion;
+pub mod full_text;
+pub mod indexed_properties;
+pub mod item_links;
+pub mod maintenance;
+pub mod migrations;
 pub mod models;
 pub mod repository;
+pub mod retention;
 pub mod schema;
+pub mod secrets;
 pub mod service;
+pub mod settings;
+pub mod spatial_index;
+pub mod sync_revisions;
+pub mod translations;
+pub mod upload_sessions;
+pub mod virtual_collections;
 
+pub use access_stats::AccessStatsRepository;
+pub use asset_sizes::AssetSizeRepository;
+pub use catalog_checks::CatalogCheckRepository;
+pub use computed_properties::ComputedPropertyRepository;
 pub use connection::DatabaseConnection;
+pub use indexed_properties::IndexedPropertyRepository;
+pub use item_links::ItemLinkRepository;
+pub use maintenance::MaintenanceRepository;
 pub use models::{DbCollection, DbItem};
-pub use repository::{CollectionRepository, ItemRepository};
-pub use schema::create_tables;
+pub use repository::{
+    is_unique_violation, AggregateKey, CollectionBounds, CollectionRepository, ItemBounds,
+    ItemChange, ItemCursor, ItemRepository, ItemStatistics, PredicateOp, PropertyPredicate,
+    SortBy, SortKey, TrashedRecord,
+};
+pub use retention::RetentionPolicyRepository;
+pub use migrations::migrate;
+pub use secrets::SecretRepository;
 pub use service::DatabaseService;
+pub use settings::SettingsRepository;
+pub use sync_revisions::SyncRevisionRepository;
+pub use translations::TranslationRepository;
+pub use upload_sessions::{UploadSession, UploadSessionRepository};
+pub use virtual_collections::VirtualCollectionRepository;
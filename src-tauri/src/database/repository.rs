@@ -1,16 +1,792 @@
+use crate::database::asset_index;
+use crate::database::computed_properties;
+use crate::database::connection::blocking;
+use crate::database::full_text;
+use crate::database::indexed_properties::{
+    indexed_columns, property_expression, IndexedProperty, IndexedPropertyKind,
+};
+use crate::database::spatial_index;
+use crate::database::sync_revisions::content_hash;
 use crate::database::{DatabaseConnection, DbCollection, DbItem};
-use rusqlite::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{OptionalExtension, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Clone)]
 pub struct CollectionRepository {
     db: DatabaseConnection,
 }
 
+/// Comparison of a STAC Query extension predicate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PredicateOp {
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    StartsWith,
+    EndsWith,
+    Contains,
+    In,
+}
+
+/// A condition on an item property, evaluated in SQL
+#[derive(Debug, Clone)]
+pub struct PropertyPredicate {
+    pub property: String,
+    pub op: PredicateOp,
+    /// One value, or the candidates of `In`; numbers, strings or booleans
+    pub values: Vec<serde_json::Value>,
+}
+
+impl PropertyPredicate {
+    /// SQL condition and its parameters. Indexed properties of the matching
+    /// type are read from their generated column, which lets SQLite use the
+    /// index; anything else falls back to `json_extract`.
+    fn to_sql(&self, indexed: &[IndexedProperty]) -> (String, Vec<SqlValue>) {
+        if let Some(column) = asset_index::asset_column(&self.property) {
+            return self.asset_sql(column);
+        }
+        let expr = self
+            .indexed_column(indexed)
+            .map(IndexedProperty::column)
+            .unwrap_or_else(|| property_expression(&self.property));
+        compare_sql(self.op, &expr, &self.values)
+    }
+
+    /// Condition on the assets of an item: some asset satisfies the
+    /// predicate, except for `neq`, which asks that no asset has the value,
+    /// e.g. `{"assets.roles": {"neq": "thumbnail"}}` for items lacking one
+    fn asset_sql(&self, column: &str) -> (String, Vec<SqlValue>) {
+        let negate = self.op == PredicateOp::Neq;
+        let op = if negate { PredicateOp::Eq } else { self.op };
+        let (condition, params) =
+            compare_sql(op, &asset_index::column_expression(column), &self.values);
+        (asset_index::exists_sql(column, &condition, negate), params)
+    }
+
+    /// The indexed property whose generated column can answer the predicate:
+    /// same property, and same type as the compared value
+    fn indexed_column<'a>(&self, indexed: &'a [IndexedProperty]) -> Option<&'a IndexedProperty> {
+        let kind = match self.values.first() {
+            Some(serde_json::Value::Number(_)) => Some(IndexedPropertyKind::Number),
+            Some(serde_json::Value::String(_)) => Some(IndexedPropertyKind::String),
+            _ => None,
+        };
+        indexed
+            .iter()
+            .find(|p| p.property == self.property && Some(p.kind) == kind)
+    }
+}
+
+/// Comparison of a SQL expression with a predicate's values
+fn compare_sql(
+    op: PredicateOp,
+    expr: &str,
+    values: &[serde_json::Value],
+) -> (String, Vec<SqlValue>) {
+    let params: Vec<SqlValue> = values.iter().map(json_to_sql).collect();
+    let sql = match op {
+        PredicateOp::Eq => format!("{} = ?", expr),
+        PredicateOp::Neq => format!("{} != ?", expr),
+        PredicateOp::Lt => format!("{} < ?", expr),
+        PredicateOp::Lte => format!("{} <= ?", expr),
+        PredicateOp::Gt => format!("{} > ?", expr),
+        PredicateOp::Gte => format!("{} >= ?", expr),
+        PredicateOp::StartsWith => format!("instr({}, ?) = 1", expr),
+        PredicateOp::Contains => format!("instr({}, ?) > 0", expr),
+        PredicateOp::EndsWith => {
+            // The value is bound twice: once for its length
+            let params = vec![params[0].clone(), params[0].clone()];
+            return (format!("substr({}, -length(?)) = ?", expr), params);
+        }
+        PredicateOp::In => format!(
+            "{} IN ({})",
+            expr,
+            vec!["?"; params.len()].join(", ")
+        ),
+    };
+    (sql, params)
+}
+
+/// A search condition evaluated in SQL, as reported by search explain
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SqlCondition {
+    pub filter: String,
+    pub sql: String,
+    /// Whether the condition reads a generated column SQLite can index
+    pub indexed: bool,
+}
+
+/// What a sorted search orders items by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortKey {
+    Id,
+    Collection,
+    /// An item property, e.g. `datetime` or `eo:cloud_cover`
+    Property(String),
+}
+
+impl SortKey {
+    /// Key of a `sortby` field: `id`, `collection`, or a property named bare
+    /// or as `properties.<name>`
+    pub fn from_field(field: &str) -> Self {
+        match field {
+            "id" => SortKey::Id,
+            "collection" => SortKey::Collection,
+            _ => SortKey::Property(
+                field.strip_prefix("properties.").unwrap_or(field).to_string(),
+            ),
+        }
+    }
+
+    fn to_sql(&self) -> String {
+        match self {
+            SortKey::Id => "id".to_string(),
+            SortKey::Collection => "collection_id".to_string(),
+            SortKey::Property(property) => property_expression(property),
+        }
+    }
+}
+
+/// One field of a sorted search and its direction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortBy {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+/// What an aggregation counts matching items by
+#[derive(Debug, Clone, PartialEq)]
+pub enum AggregateKey {
+    Collection,
+    /// Leading characters of the item's UTC start datetime: 4 for the year,
+    /// 7 for the month, 10 for the day, 13 for the hour
+    DatetimePrefix(usize),
+    /// Values of an item property
+    Property(String),
+    /// Numeric values of an item property in buckets of `interval`, keyed by
+    /// the start of the bucket
+    PropertyBucket { property: String, interval: f64 },
+}
+
+/// Start and end of a datetime query; `None` ends are open
+pub type DateTimeInterval = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// Item ids, spatial and temporal bounds and free text of a search, matched
+/// in SQL against the key and extent columns stored with each item and the
+/// full-text index
+#[derive(Debug, Clone, Default)]
+pub struct ItemBounds {
+    /// Ids the item's id must be among, in any of the searched collections
+    pub ids: Option<Vec<String>>,
+    /// `[min_x, min_y, max_x, max_y]` the item's bbox must intersect
+    pub bbox: Option<[f64; 4]>,
+    /// Interval the item's datetime or range must overlap
+    pub datetime: Option<DateTimeInterval>,
+    /// Full-text `MATCH` expression the item's metadata must satisfy, from
+    /// [`full_text::match_expression`]
+    pub text: Option<String>,
+}
+
+impl ItemBounds {
+    /// SQL condition and parameters per bound, named like the search parameter
+    fn to_sql(&self) -> Vec<(&'static str, String, Vec<SqlValue>)> {
+        let mut conditions = Vec::new();
+        if let Some(ids) = &self.ids {
+            conditions.push((
+                "ids",
+                format!("id IN ({})", vec!["?"; ids.len()].join(", ")),
+                ids.iter().map(|id| SqlValue::Text(id.clone())).collect(),
+            ));
+        }
+        if let Some([min_x, min_y, max_x, max_y]) = self.bbox {
+            // A box crossing the antimeridian, searched or stored, has its
+            // west edge above its east edge and covers both ends of the x
+            // range. Two such boxes always overlap in x.
+            let (index_x, exact_x, index_params, exact_params) = if min_x <= max_x {
+                (
+                    "min_x <= ? AND max_x >= ?",
+                    "CASE WHEN bbox_min_x <= bbox_max_x \
+                     THEN bbox_min_x <= ? AND bbox_max_x >= ? \
+                     ELSE bbox_min_x <= ? OR bbox_max_x >= ? END",
+                    vec![max_x, min_x],
+                    vec![max_x, min_x, max_x, min_x],
+                )
+            } else {
+                (
+                    "(max_x >= ? OR min_x <= ?)",
+                    "(bbox_min_x > bbox_max_x OR bbox_max_x >= ? OR bbox_min_x <= ?)",
+                    vec![min_x, max_x],
+                    vec![min_x, max_x],
+                )
+            };
+            // The R*Tree narrows the candidates; its boxes are rounded
+            // outwards to 32-bit floats, so the columns decide exactly
+            let params = index_params
+                .into_iter()
+                .chain([max_y, min_y])
+                .chain(exact_params)
+                .chain([max_y, min_y])
+                .map(SqlValue::Real)
+                .collect();
+            conditions.push((
+                "bbox",
+                format!(
+                    "rowid IN (SELECT id FROM item_rtree WHERE {} AND min_y <= ? AND max_y >= ?) \
+                     AND ({}) AND bbox_min_y <= ? AND bbox_max_y >= ?",
+                    index_x, exact_x
+                ),
+                params,
+            ));
+        }
+        if let Some((start, end)) = &self.datetime {
+            let mut sql = vec!["datetime_start IS NOT NULL".to_string()];
+            let mut params = Vec::new();
+            if let Some(end) = end {
+                sql.push("datetime_start <= ?".to_string());
+                params.push(SqlValue::Text(extent_datetime(end)));
+            }
+            if let Some(start) = start {
+                sql.push("datetime_end >= ?".to_string());
+                params.push(SqlValue::Text(extent_datetime(start)));
+            }
+            conditions.push(("datetime", sql.join(" AND "), params));
+        }
+        if let Some(text) = &self.text {
+            conditions.push((
+                "q",
+                "(collection_id, id) IN (SELECT collection_id, item_id FROM search_text \
+                 WHERE search_text MATCH ? AND item_id != '')"
+                    .to_string(),
+                vec![SqlValue::Text(text.clone())],
+            ));
+        }
+        conditions
+    }
+}
+
+/// Spatial and temporal bounds and free text of a collection search, matched
+/// in SQL against the first (overall) extent stored with each collection and
+/// the full-text index
+#[derive(Debug, Clone, Default)]
+pub struct CollectionBounds {
+    /// `[min_x, min_y, max_x, max_y]` the collection's spatial extent must
+    /// intersect
+    pub bbox: Option<[f64; 4]>,
+    /// Interval the collection's temporal extent must overlap; open ends of
+    /// the extent overlap everything on their side
+    pub datetime: Option<DateTimeInterval>,
+    /// Full-text `MATCH` expression the collection's metadata must satisfy,
+    /// from [`full_text::match_expression`]
+    pub text: Option<String>,
+}
+
+impl CollectionBounds {
+    /// Whether any bound is set
+    pub fn is_empty(&self) -> bool {
+        self.bbox.is_none() && self.datetime.is_none() && self.text.is_none()
+    }
+
+    /// WHERE clause and parameters, over collections not in the trash
+    fn to_sql(&self) -> (String, Vec<SqlValue>) {
+        let mut conditions = vec!["deleted_at IS NULL".to_string()];
+        let mut params = Vec::new();
+        if let Some([min_x, min_y, max_x, max_y]) = self.bbox {
+            // 3D extents keep the maxima at indexes 3 and 4
+            conditions.push(
+                "json_extract(data, '$.extent_spatial_bbox.bbox[0][0]') <= ? \
+                 AND json_extract(data, CASE json_array_length(data, '$.extent_spatial_bbox.bbox[0]') \
+                     WHEN 6 THEN '$.extent_spatial_bbox.bbox[0][3]' ELSE '$.extent_spatial_bbox.bbox[0][2]' END) >= ? \
+                 AND json_extract(data, '$.extent_spatial_bbox.bbox[0][1]') <= ? \
+                 AND json_extract(data, CASE json_array_length(data, '$.extent_spatial_bbox.bbox[0]') \
+                     WHEN 6 THEN '$.extent_spatial_bbox.bbox[0][4]' ELSE '$.extent_spatial_bbox.bbox[0][3]' END) >= ?"
+                    .to_string(),
+            );
+            params.extend([max_x, min_x, max_y, min_y].map(SqlValue::Real));
+        }
+        if let Some((start, end)) = &self.datetime {
+            // julianday() compares date-times written with any offset
+            if let Some(end) = end {
+                conditions.push(
+                    "(json_extract(data, '$.extent_temporal_interval.interval[0][0]') IS NULL \
+                     OR julianday(json_extract(data, '$.extent_temporal_interval.interval[0][0]')) <= julianday(?))"
+                        .to_string(),
+                );
+                params.push(SqlValue::Text(extent_datetime(end)));
+            }
+            if let Some(start) = start {
+                conditions.push(
+                    "(json_extract(data, '$.extent_temporal_interval.interval[0][1]') IS NULL \
+                     OR julianday(json_extract(data, '$.extent_temporal_interval.interval[0][1]')) >= julianday(?))"
+                        .to_string(),
+                );
+                params.push(SqlValue::Text(extent_datetime(start)));
+            }
+        }
+        if let Some(text) = &self.text {
+            conditions.push(
+                "id IN (SELECT collection_id FROM search_text WHERE search_text MATCH ? AND item_id = '')"
+                    .to_string(),
+            );
+            params.push(SqlValue::Text(text.clone()));
+        }
+        (conditions.join(" AND "), params)
+    }
+}
+
+/// Stored start of an item without one; sorts before every date-time
+const OPEN_START: &str = "";
+/// Stored end of an item without one; sorts after every date-time
+const OPEN_END: &str = "~";
+
+/// Fixed-width UTC form of a date-time, so the extent columns compare as text
+fn extent_datetime(value: &DateTime<Utc>) -> String {
+    value.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Values of the extent columns of an item: its bbox (the 2D part of a 3D
+/// one) and its `datetime`, or `start_datetime`/`end_datetime` range, in UTC.
+/// Both datetime columns are NULL when the item has no usable datetime.
+pub(crate) fn item_extent(item: &DbItem) -> [SqlValue; 6] {
+    let numbers: Vec<f64> = item
+        .bbox
+        .as_ref()
+        .and_then(|bbox| bbox.as_array())
+        .map(|bbox| bbox.iter().filter_map(|n| n.as_f64()).collect())
+        .unwrap_or_default();
+    let bbox = match numbers.as_slice() {
+        [min_x, min_y, max_x, max_y] | [min_x, min_y, _, max_x, max_y, _] => {
+            [*min_x, *min_y, *max_x, *max_y].map(SqlValue::Real)
+        }
+        _ => [SqlValue::Null, SqlValue::Null, SqlValue::Null, SqlValue::Null],
+    };
+
+    let parse = |key: &str| -> Option<Option<String>> {
+        match item.properties.get(key) {
+            Some(serde_json::Value::String(text)) => DateTime::parse_from_rfc3339(text)
+                .ok()
+                .map(|dt| Some(extent_datetime(&dt.with_timezone(&Utc)))),
+            Some(serde_json::Value::Null) | None => Some(None),
+            Some(_) => None,
+        }
+    };
+    let interval = match parse("datetime") {
+        Some(Some(instant)) => Some((instant.clone(), instant)),
+        Some(None) => match (parse("start_datetime"), parse("end_datetime")) {
+            (Some(None), Some(None)) => None,
+            (Some(start), Some(end)) => Some((
+                start.unwrap_or_else(|| OPEN_START.to_string()),
+                end.unwrap_or_else(|| OPEN_END.to_string()),
+            )),
+            _ => None,
+        },
+        None => None,
+    };
+    let (start, end) = match interval {
+        Some((start, end)) => (SqlValue::Text(start), SqlValue::Text(end)),
+        None => (SqlValue::Null, SqlValue::Null),
+    };
+
+    let [min_x, min_y, max_x, max_y] = bbox;
+    [min_x, min_y, max_x, max_y, start, end]
+}
+
+/// WHERE clause restricting items outside the trash to collections,
+/// predicates and bounds
+fn item_filter_sql(
+    conn: &rusqlite::Connection,
+    collection_ids: &[String],
+    predicates: &[PropertyPredicate],
+    bounds: &ItemBounds,
+) -> Result<(String, Vec<SqlValue>)> {
+    let indexed = indexed_columns(conn)?;
+    let mut conditions = format!(
+        "deleted_at IS NULL AND collection_id IN ({})",
+        vec!["?"; collection_ids.len()].join(", ")
+    );
+    let mut params: Vec<SqlValue> = collection_ids
+        .iter()
+        .map(|id| SqlValue::Text(id.clone()))
+        .collect();
+    for predicate in predicates {
+        let (condition, values) = predicate.to_sql(&indexed);
+        conditions.push_str(" AND ");
+        conditions.push_str(&condition);
+        params.extend(values);
+    }
+    for (_, condition, values) in bounds.to_sql() {
+        conditions.push_str(" AND ");
+        conditions.push_str(&condition);
+        params.extend(values);
+    }
+    Ok((conditions, params))
+}
+
+fn json_to_sql(value: &serde_json::Value) -> SqlValue {
+    match value {
+        serde_json::Value::Bool(b) => SqlValue::Integer(*b as i64),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => SqlValue::Text(s.clone()),
+        _ => SqlValue::Null,
+    }
+}
+
 #[derive(Clone)]
 pub struct ItemRepository {
     db: DatabaseConnection,
 }
 
+/// Position in the (datetime, collection_id, id) order used for keyset
+/// pagination. Items inserted while a client pages never shift the position,
+/// unlike offsets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemCursor {
+    /// `properties.datetime`, else `properties.start_datetime`, else empty
+    pub datetime: String,
+    pub collection_id: String,
+    pub id: String,
+}
+
+impl ItemCursor {
+    /// Opaque, URL-safe token for `next` links
+    pub fn encode(&self) -> String {
+        let json = serde_json::json!([self.datetime, self.collection_id, self.id]).to_string();
+        json.bytes().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        if !token.len().is_multiple_of(2) {
+            return None;
+        }
+        let bytes: Vec<u8> = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(token.get(i..i + 2)?, 16).ok())
+            .collect::<Option<_>>()?;
+        let [datetime, collection_id, id]: [String; 3] = serde_json::from_slice(&bytes).ok()?;
+        Some(Self {
+            datetime,
+            collection_id,
+            id,
+        })
+    }
+}
+
+/// Last change to the items of a collection: when it happened, and a
+/// revision bumped by every create, update and delete
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemChange {
+    pub last_modified: DateTime<Utc>,
+    pub revision: i64,
+}
+
+/// A collection or item in the trash
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashedRecord {
+    pub collection_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub deleted_at: String,
+    /// Items deleted together with a collection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<i64>,
+}
+
+/// Item counts and extent of the live items of a collection
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemStatistics {
+    pub collection_id: String,
+    pub item_count: u64,
+    pub items_with_bbox: u64,
+    pub items_with_datetime: u64,
+    /// Union of the item bboxes, `[min_x, min_y, max_x, max_y]`
+    pub bbox: Option<[f64; 4]>,
+    /// Earliest start and latest end of the items; `None` where an item
+    /// leaves it open
+    pub datetime_start: Option<String>,
+    pub datetime_end: Option<String>,
+}
+
+/// Whether an insert failed because a row with the same key exists: a
+/// collection id, or an item id within its collection. Records in the trash
+/// keep their ids taken.
+pub fn is_unique_violation(error: &rusqlite::Error) -> bool {
+    matches!(
+        error,
+        rusqlite::Error::SqliteFailure(failure, _)
+            if failure.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY
+                || failure.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+    )
+}
+
+/// Records a change to the items of a collection
+fn touch_item_changes(conn: &rusqlite::Connection, collection_id: &str) -> Result<()> {
+    conn.execute(
+        r#"
+        INSERT INTO item_changes (collection_id, last_modified, revision) VALUES (?1, ?2, 1)
+        ON CONFLICT(collection_id) DO UPDATE SET
+            last_modified = excluded.last_modified,
+            revision = revision + 1
+        "#,
+        rusqlite::params![collection_id, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Spatial and temporal extent of the items of a collection: the union of
+/// their 2D bboxes, and the earliest start and latest end, `None` where open
+#[derive(Debug, Clone, PartialEq)]
+struct ItemsExtent {
+    bbox: Option<[f64; 4]>,
+    interval: Option<(Option<String>, Option<String>)>,
+}
+
+impl ItemsExtent {
+    /// Extent of one item from its extent column values
+    fn of_item(item: &DbItem) -> Self {
+        let [min_x, min_y, max_x, max_y, start, end] = item_extent(item);
+        let real = |value: SqlValue| match value {
+            SqlValue::Real(value) => Some(value),
+            _ => None,
+        };
+        let text = |value: SqlValue| match value {
+            SqlValue::Text(value) => Some(value),
+            _ => None,
+        };
+        let bbox = match (real(min_x), real(min_y), real(max_x), real(max_y)) {
+            (Some(min_x), Some(min_y), Some(max_x), Some(max_y)) => {
+                Some([min_x, min_y, max_x, max_y])
+            }
+            _ => None,
+        };
+        let interval = text(start).zip(text(end)).map(|(start, end)| {
+            (
+                Some(start).filter(|start| start != OPEN_START),
+                Some(end).filter(|end| end != OPEN_END),
+            )
+        });
+        Self { bbox, interval }
+    }
+
+    /// Extent currently stored with a collection, as written by
+    /// `write_collection_extent`
+    fn stored(collection: &serde_json::Value) -> Self {
+        let bbox = collection
+            .pointer("/extent_spatial_bbox/bbox/0")
+            .and_then(|bbox| bbox.as_array())
+            .map(|bbox| bbox.iter().filter_map(|n| n.as_f64()).collect::<Vec<_>>())
+            .and_then(|numbers| match numbers.as_slice() {
+                [min_x, min_y, max_x, max_y] | [min_x, min_y, _, max_x, max_y, _] => {
+                    Some([*min_x, *min_y, *max_x, *max_y])
+                }
+                _ => None,
+            });
+        let bound = |index: usize| {
+            collection
+                .pointer(&format!("/extent_temporal_interval/interval/0/{}", index))
+                .and_then(|value| value.as_str())
+                .and_then(|text| DateTime::parse_from_rfc3339(text).ok())
+                .map(|dt| extent_datetime(&dt.with_timezone(&Utc)))
+        };
+        Self {
+            bbox,
+            interval: Some((bound(0), bound(1))),
+        }
+    }
+
+    /// Smallest extent covering both
+    fn union(self, other: Self) -> Self {
+        let bbox = match (self.bbox, other.bbox) {
+            (Some(a), Some(b)) => Some([
+                a[0].min(b[0]),
+                a[1].min(b[1]),
+                a[2].max(b[2]),
+                a[3].max(b[3]),
+            ]),
+            (a, b) => a.or(b),
+        };
+        let interval = match (self.interval, other.interval) {
+            (Some((a_start, a_end)), Some((b_start, b_end))) => Some((
+                a_start.zip(b_start).map(|(a, b)| a.min(b)),
+                a_end.zip(b_end).map(|(a, b)| a.max(b)),
+            )),
+            (a, b) => a.or(b),
+        };
+        Self { bbox, interval }
+    }
+}
+
+/// Extent of all items of a collection; `None` when it has no items
+fn collection_items_extent(
+    conn: &rusqlite::Connection,
+    collection_id: &str,
+) -> Result<Option<ItemsExtent>> {
+    conn.query_row(
+        "SELECT COUNT(*), MIN(bbox_min_x), MIN(bbox_min_y), MAX(bbox_max_x), MAX(bbox_max_y),
+            MIN(datetime_start), MAX(datetime_end)
+         FROM items WHERE collection_id = ? AND deleted_at IS NULL",
+        [collection_id],
+        |row| {
+            let count: i64 = row.get(0)?;
+            if count == 0 {
+                return Ok(None);
+            }
+            let corner = |index: usize| row.get::<_, Option<f64>>(index);
+            let bbox = match (corner(1)?, corner(2)?, corner(3)?, corner(4)?) {
+                (Some(min_x), Some(min_y), Some(max_x), Some(max_y)) => {
+                    Some([min_x, min_y, max_x, max_y])
+                }
+                _ => None,
+            };
+            let start: Option<String> = row.get(5)?;
+            let end: Option<String> = row.get(6)?;
+            let interval = (start.is_some() || end.is_some()).then(|| {
+                (
+                    start.filter(|start| start != OPEN_START),
+                    end.filter(|end| end != OPEN_END),
+                )
+            });
+            Ok(Some(ItemsExtent { bbox, interval }))
+        },
+    )
+}
+
+/// Stores the extent of a collection's items as its `extent`. Without items,
+/// or parts the items leave undefined, the whole-earth and open-interval
+/// defaults of a new collection are written.
+fn write_collection_extent(
+    conn: &rusqlite::Connection,
+    collection_id: &str,
+    extent: Option<&ItemsExtent>,
+) -> Result<()> {
+    let bbox = extent
+        .and_then(|extent| extent.bbox)
+        .unwrap_or([-180.0, -90.0, 180.0, 90.0]);
+    let (start, end) = extent
+        .and_then(|extent| extent.interval.clone())
+        .unwrap_or((None, None));
+    let spatial = serde_json::json!({ "bbox": [bbox] });
+    let temporal = serde_json::json!({ "interval": [[start, end]] });
+    conn.execute(
+        "UPDATE collections SET data = json_set(data,
+            '$.extent_spatial_bbox', json(?), '$.extent_temporal_interval', json(?)),
+            content_hash = NULL
+         WHERE id = ?",
+        rusqlite::params![spatial.to_string(), temporal.to_string(), collection_id],
+    )?;
+    Ok(())
+}
+
+/// Recomputes a collection's extent from all of its items, after an item
+/// was changed or removed
+fn refresh_collection_extent(conn: &rusqlite::Connection, collection_id: &str) -> Result<()> {
+    let extent = collection_items_extent(conn, collection_id)?;
+    write_collection_extent(conn, collection_id, extent.as_ref())
+}
+
+/// Grows a collection's extent to cover a newly inserted item. The first
+/// item replaces the extent the collection was created with; later ones are
+/// merged into the stored extent, so bulk imports do not rescan the
+/// collection for every item.
+fn extend_collection_extent(conn: &rusqlite::Connection, item: &DbItem) -> Result<()> {
+    let has_other_items: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM items WHERE collection_id = ? AND id != ? AND deleted_at IS NULL)",
+        [&item.collection_id, &item.id],
+        |row| row.get(0),
+    )?;
+    if !has_other_items {
+        return refresh_collection_extent(conn, &item.collection_id);
+    }
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT data FROM collections WHERE id = ?",
+            [&item.collection_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(stored) =
+        stored.and_then(|data| serde_json::from_str::<serde_json::Value>(&data).ok())
+    else {
+        return Ok(());
+    };
+    let extent = ItemsExtent::stored(&stored).union(ItemsExtent::of_item(item));
+    write_collection_extent(conn, &item.collection_id, Some(&extent))
+}
+
+/// Items of a collection outside the trash, or only the one with `item_id`
+fn live_items(
+    conn: &rusqlite::Connection,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> Result<Vec<DbItem>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, collection_id, data FROM items
+         WHERE collection_id = ?1 AND (?2 IS NULL OR id = ?2) AND deleted_at IS NULL",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![collection_id, item_id], |row| {
+        let id: String = row.get(0)?;
+        let collection_id: String = row.get(1)?;
+        let data: String = row.get(2)?;
+        let mut item: DbItem = serde_json::from_str(&data)
+            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+        item.id = id;
+        item.collection_id = collection_id;
+        Ok(item)
+    })?;
+    rows.collect()
+}
+
+/// Copy of an item with the computed properties of its collection set, so
+/// they are stored, indexed and searchable like any other property
+fn with_computed_properties(conn: &rusqlite::Connection, item: &DbItem) -> Result<DbItem> {
+    let definitions = computed_properties::definitions(conn, &item.collection_id)?;
+    let mut item = item.clone();
+    computed_properties::apply(&definitions, &mut item, Utc::now());
+    Ok(item)
+}
+
+/// Writes an item's data, extent columns and full-text entry, with its
+/// computed properties. Returns the number of rows updated.
+fn update_item_row(conn: &rusqlite::Connection, item: &DbItem) -> Result<usize> {
+    let item = &with_computed_properties(conn, item)?;
+    let data = serde_json::to_string(item)
+        .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+    let [min_x, min_y, max_x, max_y, start, end] = item_extent(item);
+    // Bulk updates run this per item, so the statement is kept prepared
+    let updated = conn.prepare_cached(
+        "UPDATE items SET data = ?, bbox_min_x = ?, bbox_min_y = ?, bbox_max_x = ?, bbox_max_y = ?,
+            datetime_start = ?, datetime_end = ?, content_hash = ?
+         WHERE collection_id = ? AND id = ? AND deleted_at IS NULL",
+    )?
+    .execute(rusqlite::params![
+        data,
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+        start,
+        end,
+        content_hash(item),
+        item.collection_id,
+        item.id
+    ])?;
+    if updated > 0 {
+        full_text::index_item(conn, item)?;
+        asset_index::index_item(conn, item)?;
+        spatial_index::index_items(conn, &item.collection_id, Some(&item.id))?;
+    }
+    Ok(updated)
+}
+
 impl CollectionRepository {
     /// Creates a new collection repository
     pub fn new(db: DatabaseConnection) -> Self {
@@ -24,72 +800,369 @@ impl CollectionRepository {
 
     /// Gets all collections
     pub async fn get_all(&self) -> Result<Vec<DbCollection>> {
-        let conn = self.db.get_connection().await;
-        let mut stmt = conn.prepare("SELECT id, data FROM collections")?;
-        let rows = stmt.query_map([], |row| {
-            let _id: String = row.get(0)?;
-            let data: String = row.get(1)?;
-            let collection: DbCollection = serde_json::from_str(&data)
-                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
-            Ok(collection)
-        })?;
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached("SELECT id, data FROM collections WHERE deleted_at IS NULL")?;
+            let rows = stmt.query_map([], |row| {
+                let _id: String = row.get(0)?;
+                let data: String = row.get(1)?;
+                let collection: DbCollection = serde_json::from_str(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                Ok(collection)
+            })?;
 
-        let mut collections = Vec::new();
-        for row in rows {
-            collections.push(row?);
-        }
-        Ok(collections)
+            let mut collections = Vec::new();
+            for row in rows {
+                collections.push(row?);
+            }
+            Ok(collections)
+        })
     }
 
     /// Gets a collection by ID
     pub async fn get_by_id(&self, id: &str) -> Result<Option<DbCollection>> {
-        let conn = self.db.get_connection().await;
-        let mut stmt = conn.prepare("SELECT data FROM collections WHERE id = ?")?;
-        let mut rows = stmt.query_map([id], |row| {
-            let data: String = row.get(0)?;
-            let collection: DbCollection = serde_json::from_str(&data)
-                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
-            Ok(collection)
-        })?;
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt =
+                conn.prepare_cached("SELECT data FROM collections WHERE id = ? AND deleted_at IS NULL")?;
+            let mut rows = stmt.query_map([id], |row| {
+                let data: String = row.get(0)?;
+                let collection: DbCollection = serde_json::from_str(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                Ok(collection)
+            })?;
 
-        if let Some(row) = rows.next() {
-            Ok(Some(row?))
-        } else {
-            Ok(None)
-        }
+            if let Some(row) = rows.next() {
+                Ok(Some(row?))
+            } else {
+                Ok(None)
+            }
+        })
     }
 
     /// Creates a new collection
     pub async fn create(&self, collection: &DbCollection) -> Result<()> {
         let conn = self.db.get_connection().await;
-        let data = serde_json::to_string(collection)
-            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
-        conn.execute(
-            "INSERT INTO collections (id, data) VALUES (?, ?)",
-            [&collection.id, &data],
-        )?;
-        Ok(())
+        blocking(|| {
+            let data = serde_json::to_string(collection)
+                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+            conn.execute(
+                "INSERT INTO collections (id, data, content_hash) VALUES (?, ?, ?)",
+                [&collection.id, &data, &content_hash(collection)],
+            )?;
+            full_text::index_collection(&conn, collection)
+        })
     }
 
     /// Updates an existing collection
     #[allow(dead_code)]
     pub async fn update(&self, collection: &DbCollection) -> Result<()> {
         let conn = self.db.get_connection().await;
-        let data = serde_json::to_string(collection)
-            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
-        conn.execute(
-            "UPDATE collections SET data = ? WHERE id = ?",
-            [&data, &collection.id],
-        )?;
-        Ok(())
+        blocking(|| {
+            let data = serde_json::to_string(collection)
+                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+            let updated = conn.execute(
+                "UPDATE collections SET data = ?, content_hash = ? WHERE id = ? AND deleted_at IS NULL",
+                [&data, &content_hash(collection), &collection.id],
+            )?;
+            if updated > 0 {
+                full_text::index_collection(&conn, collection)?;
+            }
+            Ok(())
+        })
     }
 
-    /// Deletes a collection by ID
-    #[allow(dead_code)]
-    pub async fn delete(&self, id: &str) -> Result<()> {
+    /// Gets the JSON Schema that items of a collection must satisfy, if any
+    pub async fn get_item_schema(&self, id: &str) -> Result<Option<serde_json::Value>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let raw: Option<Option<String>> = conn
+                .query_row("SELECT item_schema FROM collections WHERE id = ?", [id], |row| row.get(0))
+                .optional()?;
+            match raw.flatten() {
+                Some(raw) => serde_json::from_str(&raw)
+                    .map(Some)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string())),
+                None => Ok(None),
+            }
+        })
+    }
+
+    /// Sets or clears (`None`) the item JSON Schema of a collection.
+    /// Returns false when the collection does not exist.
+    pub async fn set_item_schema(&self, id: &str, schema: Option<&serde_json::Value>) -> Result<bool> {
         let conn = self.db.get_connection().await;
-        conn.execute("DELETE FROM collections WHERE id = ?", [id])?;
-        Ok(())
+        blocking(|| {
+            let raw = schema.map(|s| s.to_string());
+            let updated = conn.execute(
+                "UPDATE collections SET item_schema = ? WHERE id = ?",
+                rusqlite::params![raw, id],
+            )?;
+            Ok(updated > 0)
+        })
+    }
+
+    /// Gets the item pinned as the collection's thumbnail, if any
+    pub async fn get_thumbnail_item(&self, id: &str) -> Result<Option<String>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let item: Option<Option<String>> = conn
+                .query_row("SELECT thumbnail_item FROM collections WHERE id = ?", [id], |row| row.get(0))
+                .optional()?;
+            Ok(item.flatten())
+        })
+    }
+
+    /// Pins an item as the collection's thumbnail, or clears the pin (`None`).
+    /// Returns false when the collection does not exist.
+    pub async fn set_thumbnail_item(&self, id: &str, item_id: Option<&str>) -> Result<bool> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let updated = conn.execute(
+                "UPDATE collections SET thumbnail_item = ? WHERE id = ?",
+                rusqlite::params![item_id, id],
+            )?;
+            Ok(updated > 0)
+        })
+    }
+
+    /// Collections within the bounds in id order, starting after the
+    /// collection id `after`, or with `backward` the ones before it, closest
+    /// first
+    pub async fn page(
+        &self,
+        bounds: &CollectionBounds,
+        after: Option<&str>,
+        backward: bool,
+        limit: i64,
+    ) -> Result<Vec<DbCollection>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let (conditions, mut params) = bounds.to_sql();
+            let sql = match (after, backward) {
+                (Some(_), true) => format!(
+                    "SELECT data FROM collections WHERE {} AND id < ? ORDER BY id DESC LIMIT ?",
+                    conditions
+                ),
+                (Some(_), false) => format!(
+                    "SELECT data FROM collections WHERE {} AND id > ? ORDER BY id ASC LIMIT ?",
+                    conditions
+                ),
+                (None, _) => format!(
+                    "SELECT data FROM collections WHERE {} ORDER BY id ASC LIMIT ?",
+                    conditions
+                ),
+            };
+            if let Some(after) = after {
+                params.push(SqlValue::Text(after.to_string()));
+            }
+            params.push(SqlValue::Integer(limit));
+
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                let data: String = row.get(0)?;
+                serde_json::from_str::<DbCollection>(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// Content hashes of the live collections, by id. Collections whose
+    /// extent changed since they were written are hashed from their data.
+    pub async fn content_hashes(&self) -> Result<BTreeMap<String, String>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT id, content_hash, CASE WHEN content_hash IS NULL THEN data END
+                 FROM collections WHERE deleted_at IS NULL",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                let id: String = row.get(0)?;
+                let hash = match row.get::<_, Option<String>>(1)? {
+                    Some(hash) => hash,
+                    None => {
+                        let data: String = row.get(2)?;
+                        let collection: DbCollection = serde_json::from_str(&data)
+                            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                        content_hash(&collection)
+                    }
+                };
+                Ok((id, hash))
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// Ids of every stored collection, those in the trash included, whose
+    /// asset folders are kept until they are purged
+    pub async fn stored_ids(&self) -> Result<BTreeSet<String>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached("SELECT id FROM collections")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect()
+        })
+    }
+
+    /// Number of live collections
+    pub async fn count(&self) -> Result<i64> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            conn.query_row(
+                "SELECT COUNT(*) FROM collections WHERE deleted_at IS NULL",
+                [],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Number of collections within the bounds
+    pub async fn count_matching(&self, bounds: &CollectionBounds) -> Result<i64> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let (conditions, params) = bounds.to_sql();
+            conn.query_row(
+                &format!("SELECT COUNT(*) FROM collections WHERE {}", conditions),
+                rusqlite::params_from_iter(params),
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Moves a collection and its items to the trash. They are hidden from
+    /// every other query until restored, and only removed by a purge.
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let tx = conn.transaction()?;
+            let deleted_at = Utc::now().to_rfc3339();
+            let deleted = tx.execute(
+                "UPDATE collections SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+                rusqlite::params![deleted_at, id],
+            )?;
+            if deleted > 0 {
+                // Items share the collection's time, so a restore brings back
+                // exactly these
+                tx.execute(
+                    "UPDATE items SET deleted_at = ? WHERE collection_id = ? AND deleted_at IS NULL",
+                    rusqlite::params![deleted_at, id],
+                )?;
+                tx.execute("DELETE FROM item_changes WHERE collection_id = ?", [id])?;
+                asset_index::remove(&tx, id, None)?;
+                full_text::remove(&tx, id, None)?;
+                spatial_index::remove(&tx, id, None)?;
+            }
+            tx.commit()
+        })
+    }
+
+    /// Takes a collection out of the trash together with the items deleted
+    /// with it. Returns false when it is not in the trash.
+    pub async fn restore(&self, id: &str) -> Result<bool> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let tx = conn.transaction()?;
+            let trashed: Option<(String, String)> = tx
+                .query_row(
+                    "SELECT data, deleted_at FROM collections WHERE id = ? AND deleted_at IS NOT NULL",
+                    [id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            let Some((data, deleted_at)) = trashed else {
+                return Ok(false);
+            };
+            tx.execute("UPDATE collections SET deleted_at = NULL WHERE id = ?", [id])?;
+            tx.execute(
+                "UPDATE items SET deleted_at = NULL WHERE collection_id = ? AND deleted_at = ?",
+                rusqlite::params![id, deleted_at],
+            )?;
+            let collection: DbCollection = serde_json::from_str(&data)
+                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+            full_text::index_collection(&tx, &collection)?;
+            for item in live_items(&tx, id, None)? {
+                full_text::index_item(&tx, &item)?;
+                asset_index::index_item(&tx, &item)?;
+            }
+            spatial_index::index_items(&tx, id, None)?;
+            refresh_collection_extent(&tx, id)?;
+            touch_item_changes(&tx, id)?;
+            tx.commit()?;
+            Ok(true)
+        })
+    }
+
+    /// Permanently deletes a collection in the trash and all of its items.
+    /// Returns false when it is not in the trash.
+    pub async fn purge(&self, id: &str) -> Result<bool> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let tx = conn.transaction()?;
+            let purged = tx.execute(
+                "DELETE FROM collections WHERE id = ? AND deleted_at IS NOT NULL",
+                [id],
+            )?;
+            if purged > 0 {
+                tx.execute("DELETE FROM items WHERE collection_id = ?", [id])?;
+            }
+            tx.commit()?;
+            Ok(purged > 0)
+        })
+    }
+
+    /// Whether a collection, or with `item_id` one of its items, is in the
+    /// trash
+    pub async fn in_trash(&self, collection_id: &str, item_id: Option<&str>) -> Result<bool> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            match item_id {
+                Some(item_id) => conn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM items WHERE collection_id = ? AND id = ? AND deleted_at IS NOT NULL)",
+                    [collection_id, item_id],
+                    |row| row.get(0),
+                ),
+                None => conn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM collections WHERE id = ? AND deleted_at IS NOT NULL)",
+                    [collection_id],
+                    |row| row.get(0),
+                ),
+            }
+        })
+    }
+
+    /// Collections and items in the trash, most recently deleted first.
+    /// Items deleted together with their collection are counted with it
+    /// rather than listed.
+    pub async fn trashed(&self) -> Result<Vec<TrashedRecord>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                r#"
+                SELECT id, NULL, json_extract(data, '$.title'), deleted_at,
+                    (SELECT COUNT(*) FROM items
+                     WHERE items.collection_id = collections.id AND items.deleted_at = collections.deleted_at)
+                FROM collections WHERE deleted_at IS NOT NULL
+                UNION ALL
+                SELECT items.collection_id, items.id, json_extract(items.data, '$.properties.title'),
+                    items.deleted_at, NULL
+                FROM items LEFT JOIN collections ON collections.id = items.collection_id
+                WHERE items.deleted_at IS NOT NULL
+                    AND (collections.deleted_at IS NULL OR collections.deleted_at != items.deleted_at)
+                ORDER BY 4 DESC, 1, 2
+                "#,
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok(TrashedRecord {
+                    collection_id: row.get(0)?,
+                    item_id: row.get(1)?,
+                    title: row.get::<_, Option<String>>(2).ok().flatten(),
+                    deleted_at: row.get(3)?,
+                    items: row.get(4)?,
+                })
+            })?;
+            rows.collect()
+        })
     }
 }
 
@@ -98,22 +1171,88 @@ impl ItemRepository {
         Self { db }
     }
 
+    /// Most recently created or updated items, newest first, across the
+    /// catalog or within one collection
+    pub async fn recent(&self, collection_id: Option<&str>, limit: i64) -> Result<Vec<DbItem>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                r#"
+                SELECT id, collection_id, data FROM items
+                WHERE deleted_at IS NULL AND (?1 IS NULL OR collection_id = ?1)
+                ORDER BY json_extract(data, '$.updated_at') DESC, collection_id, id
+                LIMIT ?2
+                "#,
+            )?;
+            let rows = stmt.query_map(rusqlite::params![collection_id, limit], |row| {
+                let id: String = row.get(0)?;
+                let collection_id: String = row.get(1)?;
+                let data: String = row.get(2)?;
+                let mut item: DbItem = serde_json::from_str(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                item.id = id;
+                item.collection_id = collection_id;
+                Ok(item)
+            })?;
+            rows.collect()
+        })
+    }
+
     pub async fn get_by_collection(
         &self,
         collection_id: &str,
         limit: Option<i64>,
         offset: Option<i64>,
     ) -> Result<Vec<DbItem>> {
-        let conn = self.db.get_connection().await;
-        let limit = limit.unwrap_or(10);
-        let offset = offset.unwrap_or(0);
-
-        let mut stmt = conn.prepare(
-            "SELECT id, collection_id, data FROM items WHERE collection_id = ? ORDER BY id LIMIT ? OFFSET ?"
-        )?;
-        let rows = stmt.query_map(
-            [collection_id, &limit.to_string(), &offset.to_string()],
-            |row| {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let limit = limit.unwrap_or(10);
+            let offset = offset.unwrap_or(0);
+
+            let mut stmt = conn.prepare_cached(
+                "SELECT id, collection_id, data FROM items WHERE collection_id = ? AND deleted_at IS NULL ORDER BY id LIMIT ? OFFSET ?"
+            )?;
+            let rows = stmt.query_map(
+                [collection_id, &limit.to_string(), &offset.to_string()],
+                |row| {
+                    let id: String = row.get(0)?;
+                    let collection_id: String = row.get(1)?;
+                    let data: String = row.get(2)?;
+                    let mut item: DbItem = serde_json::from_str(&data)
+                        .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                    // Ensure the id and collection_id match what's in the database
+                    item.id = id;
+                    item.collection_id = collection_id;
+                    Ok(item)
+                },
+            )?;
+
+            let mut items = Vec::new();
+            for row in rows {
+                items.push(row?);
+            }
+            Ok(items)
+        })
+    }
+
+    /// Whether an item is stored and not in the trash
+    pub async fn exists(&self, collection_id: &str, item_id: &str) -> Result<bool> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            conn.prepare_cached(
+                "SELECT EXISTS(SELECT 1 FROM items WHERE collection_id = ? AND id = ? AND deleted_at IS NULL)",
+            )?
+            .query_row([collection_id, item_id], |row| row.get(0))
+        })
+    }
+
+    pub async fn get_by_id(&self, collection_id: &str, item_id: &str) -> Result<Option<DbItem>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT id, collection_id, data FROM items WHERE collection_id = ? AND id = ? AND deleted_at IS NULL",
+            )?;
+            let mut rows = stmt.query_map([collection_id, item_id], |row| {
                 let id: String = row.get(0)?;
                 let collection_id: String = row.get(1)?;
                 let data: String = row.get(2)?;
@@ -123,77 +1262,760 @@ impl ItemRepository {
                 item.id = id;
                 item.collection_id = collection_id;
                 Ok(item)
-            },
-        )?;
+            })?;
 
-        let mut items = Vec::new();
-        for row in rows {
-            items.push(row?);
+            if let Some(row) = rows.next() {
+                Ok(Some(row?))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    /// Live items of several collections ordered by collection and id, read
+    /// a page at a time in one query rather than one per collection
+    pub async fn get_by_collections(
+        &self,
+        collection_ids: &[String],
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DbItem>> {
+        if collection_ids.is_empty() {
+            return Ok(Vec::new());
         }
-        Ok(items)
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let sql = format!(
+                "SELECT id, collection_id, data FROM items
+                 WHERE collection_id IN ({}) AND deleted_at IS NULL
+                 ORDER BY collection_id, id LIMIT ? OFFSET ?",
+                vec!["?"; collection_ids.len()].join(", ")
+            );
+            let mut params: Vec<SqlValue> = collection_ids
+                .iter()
+                .map(|id| SqlValue::Text(id.clone()))
+                .collect();
+            params.extend([SqlValue::Integer(limit), SqlValue::Integer(offset)]);
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                let id: String = row.get(0)?;
+                let collection_id: String = row.get(1)?;
+                let data: String = row.get(2)?;
+                let mut item: DbItem = serde_json::from_str(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                item.id = id;
+                item.collection_id = collection_id;
+                Ok(item)
+            })?;
+            rows.collect()
+        })
     }
 
-    pub async fn get_by_id(&self, collection_id: &str, item_id: &str) -> Result<Option<DbItem>> {
-        let conn = self.db.get_connection().await;
-        let mut stmt = conn.prepare(
-            "SELECT id, collection_id, data FROM items WHERE collection_id = ? AND id = ?",
-        )?;
-        let mut rows = stmt.query_map([collection_id, item_id], |row| {
-            let id: String = row.get(0)?;
-            let collection_id: String = row.get(1)?;
-            let data: String = row.get(2)?;
-            let mut item: DbItem = serde_json::from_str(&data)
-                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
-            // Ensure the id and collection_id match what's in the database
-            item.id = id;
-            item.collection_id = collection_id;
-            Ok(item)
-        })?;
+    /// Item counts and extent of each live collection, computed from the
+    /// extent columns without reading the items
+    pub async fn statistics(&self) -> Result<Vec<ItemStatistics>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT c.id, COUNT(i.id), COUNT(i.bbox_min_x), COUNT(i.datetime_start),
+                    MIN(i.bbox_min_x), MIN(i.bbox_min_y), MAX(i.bbox_max_x), MAX(i.bbox_max_y),
+                    MIN(i.datetime_start), MAX(i.datetime_end)
+                 FROM collections c
+                 LEFT JOIN items i ON i.collection_id = c.id AND i.deleted_at IS NULL
+                 WHERE c.deleted_at IS NULL
+                 GROUP BY c.id
+                 ORDER BY c.id",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                let corner = |index: usize| row.get::<_, Option<f64>>(index);
+                let bbox = match (corner(4)?, corner(5)?, corner(6)?, corner(7)?) {
+                    (Some(min_x), Some(min_y), Some(max_x), Some(max_y)) => {
+                        Some([min_x, min_y, max_x, max_y])
+                    }
+                    _ => None,
+                };
+                let start: Option<String> = row.get(8)?;
+                let end: Option<String> = row.get(9)?;
+                Ok(ItemStatistics {
+                    collection_id: row.get(0)?,
+                    item_count: row.get::<_, i64>(1)? as u64,
+                    items_with_bbox: row.get::<_, i64>(2)? as u64,
+                    items_with_datetime: row.get::<_, i64>(3)? as u64,
+                    bbox,
+                    datetime_start: start.filter(|start| start != OPEN_START),
+                    datetime_end: end.filter(|end| end != OPEN_END),
+                })
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// Number of live items, of one collection or of all
+    pub async fn count(&self, collection_id: Option<&str>) -> Result<i64> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            conn.query_row(
+                "SELECT COUNT(*) FROM items WHERE (?1 IS NULL OR collection_id = ?1) AND deleted_at IS NULL",
+                [collection_id],
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Content hashes of the live items of a collection, by item id, read
+    /// without loading the items
+    pub async fn content_hashes(&self, collection_id: &str) -> Result<BTreeMap<String, String>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT id, content_hash, CASE WHEN content_hash IS NULL THEN data END
+                 FROM items WHERE collection_id = ? AND deleted_at IS NULL",
+            )?;
+            let rows = stmt.query_map([collection_id], |row| {
+                let id: String = row.get(0)?;
+                let hash = match row.get::<_, Option<String>>(1)? {
+                    Some(hash) => hash,
+                    None => {
+                        let data: String = row.get(2)?;
+                        let mut item: DbItem = serde_json::from_str(&data)
+                            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                        item.id = id.clone();
+                        item.collection_id = collection_id.to_string();
+                        content_hash(&item)
+                    }
+                };
+                Ok((id, hash))
+            })?;
+            rows.collect()
+        })
+    }
+
+
 
-        if let Some(row) = rows.next() {
-            Ok(Some(row?))
-        } else {
-            Ok(None)
+    /// Key and href of every asset of the items of a collection, those in
+    /// the trash included, as `(item_id, asset_key, href)`
+    pub async fn asset_references(
+        &self,
+        collection_id: &str,
+    ) -> Result<Vec<(String, String, Option<String>)>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT items.id, asset.key,
+                    CASE WHEN json_type(asset.value, '$.href') = 'text'
+                        THEN json_extract(asset.value, '$.href') END
+                 FROM items, json_each(items.data, '$.assets') AS asset
+                 WHERE items.collection_id = ? AND json_type(items.data, '$.assets') = 'object'",
+            )?;
+            let rows = stmt.query_map([collection_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// Most recently created or updated item of a collection with a
+    /// thumbnail asset: keyed `thumbnail` or with the `thumbnail` role
+    pub async fn latest_with_thumbnail(&self, collection_id: &str) -> Result<Option<DbItem>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            conn.query_row(
+                r#"
+                SELECT id, collection_id, data FROM items
+                WHERE collection_id = ?1 AND deleted_at IS NULL AND (
+                    json_extract(data, '$.assets.thumbnail') IS NOT NULL
+                    OR EXISTS (
+                        SELECT 1 FROM json_each(items.data, '$.assets') AS asset,
+                            json_each(asset.value, '$.roles') AS role
+                        WHERE role.value = 'thumbnail'
+                    )
+                )
+                ORDER BY json_extract(data, '$.updated_at') DESC, id
+                LIMIT 1
+                "#,
+                [collection_id],
+                |row| {
+                    let id: String = row.get(0)?;
+                    let collection_id: String = row.get(1)?;
+                    let data: String = row.get(2)?;
+                    let mut item: DbItem = serde_json::from_str(&data)
+                        .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                    item.id = id;
+                    item.collection_id = collection_id;
+                    Ok(item)
+                },
+            )
+            .optional()
+        })
+    }
+
+    /// Items of the given collections matching every predicate and bound, in
+    /// (datetime, collection_id, id) order starting after `after`, with the
+    /// cursor of each item
+    pub async fn page_after(
+        &self,
+        collection_ids: &[String],
+        predicates: &[PropertyPredicate],
+        bounds: &ItemBounds,
+        after: Option<&ItemCursor>,
+        limit: i64,
+    ) -> Result<Vec<(ItemCursor, DbItem)>> {
+        self.page(collection_ids, predicates, bounds, after, false, limit)
+            .await
+    }
+
+    /// Like [`Self::page_after`] in reverse: the items before `before`,
+    /// closest first
+    pub async fn page_before(
+        &self,
+        collection_ids: &[String],
+        predicates: &[PropertyPredicate],
+        bounds: &ItemBounds,
+        before: &ItemCursor,
+        limit: i64,
+    ) -> Result<Vec<(ItemCursor, DbItem)>> {
+        self.page(collection_ids, predicates, bounds, Some(before), true, limit)
+            .await
+    }
+
+    async fn page(
+        &self,
+        collection_ids: &[String],
+        predicates: &[PropertyPredicate],
+        bounds: &ItemBounds,
+        cursor: Option<&ItemCursor>,
+        backward: bool,
+        limit: i64,
+    ) -> Result<Vec<(ItemCursor, DbItem)>> {
+        if collection_ids.is_empty() {
+            return Ok(Vec::new());
         }
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let (conditions, mut params) =
+                item_filter_sql(&conn, collection_ids, predicates, bounds)?;
+            let sql = format!(
+                r#"
+                SELECT id, collection_id, data, sort_datetime FROM (
+                    SELECT id, collection_id, data,
+                        COALESCE(
+                            json_extract(data, '$.properties.datetime'),
+                            json_extract(data, '$.properties.start_datetime'),
+                            ''
+                        ) AS sort_datetime
+                    FROM items
+                    WHERE {}
+                )
+                WHERE (sort_datetime, collection_id, id) {} (?, ?, ?)
+                ORDER BY sort_datetime {2}, collection_id {2}, id {2}
+                LIMIT ?
+                "#,
+                conditions,
+                if backward { "<" } else { ">" },
+                if backward { "DESC" } else { "ASC" },
+            );
+
+            // The empty cursor sorts before every item
+            let (datetime, collection_id, id) = match cursor {
+                Some(cursor) => (cursor.datetime.as_str(), cursor.collection_id.as_str(), cursor.id.as_str()),
+                None => ("", "", ""),
+            };
+            params.extend([
+                SqlValue::Text(datetime.to_string()),
+                SqlValue::Text(collection_id.to_string()),
+                SqlValue::Text(id.to_string()),
+                SqlValue::Integer(limit),
+            ]);
+
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                let id: String = row.get(0)?;
+                let collection_id: String = row.get(1)?;
+                let data: String = row.get(2)?;
+                let sort_datetime: String = row.get(3)?;
+                let mut item: DbItem = serde_json::from_str(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                item.id = id.clone();
+                item.collection_id = collection_id.clone();
+                Ok((
+                    ItemCursor {
+                        datetime: sort_datetime,
+                        collection_id,
+                        id,
+                    },
+                    item,
+                ))
+            })?;
+
+            let mut items = Vec::new();
+            for row in rows {
+                items.push(row?);
+            }
+            Ok(items)
+        })
     }
 
+    /// Items of the given collections matching every predicate and bound,
+    /// ordered by `sortby` in SQL and then by collection and id, skipping
+    /// `offset`. SQLite orders nulls and missing properties first, then
+    /// numbers (booleans as 0 and 1), then text.
+    pub async fn page_sorted(
+        &self,
+        collection_ids: &[String],
+        predicates: &[PropertyPredicate],
+        bounds: &ItemBounds,
+        sortby: &[SortBy],
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<DbItem>> {
+        if collection_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let (conditions, mut params) =
+                item_filter_sql(&conn, collection_ids, predicates, bounds)?;
+            let mut order: Vec<String> = sortby
+                .iter()
+                .map(|s| format!("{} {}", s.key.to_sql(), if s.descending { "DESC" } else { "ASC" }))
+                .collect();
+            order.extend(["collection_id ASC".to_string(), "id ASC".to_string()]);
+            let sql = format!(
+                "SELECT id, collection_id, data FROM items WHERE {} ORDER BY {} LIMIT ? OFFSET ?",
+                conditions,
+                order.join(", ")
+            );
+            params.extend([SqlValue::Integer(limit), SqlValue::Integer(offset)]);
 
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                let id: String = row.get(0)?;
+                let collection_id: String = row.get(1)?;
+                let data: String = row.get(2)?;
+                let mut item: DbItem = serde_json::from_str(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+                item.id = id;
+                item.collection_id = collection_id;
+                Ok(item)
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// Number of items of the given collections matching every predicate and
+    /// bound
+    pub async fn count_matching(
+        &self,
+        collection_ids: &[String],
+        predicates: &[PropertyPredicate],
+        bounds: &ItemBounds,
+    ) -> Result<i64> {
+        if collection_ids.is_empty() {
+            return Ok(0);
+        }
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let (conditions, params) = item_filter_sql(&conn, collection_ids, predicates, bounds)?;
+            conn.query_row(
+                &format!("SELECT COUNT(*) FROM items WHERE {}", conditions),
+                rusqlite::params_from_iter(params),
+                |row| row.get(0),
+            )
+        })
+    }
+
+    /// Number of items of the given collections matching every predicate and
+    /// bound per value of `key`. Items without the value are left out.
+    /// Histogram keys come in key order, property values most frequent first.
+    pub async fn group_counts(
+        &self,
+        collection_ids: &[String],
+        predicates: &[PropertyPredicate],
+        bounds: &ItemBounds,
+        key: &AggregateKey,
+    ) -> Result<Vec<(serde_json::Value, i64)>> {
+        if collection_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let (conditions, filter_params) =
+                item_filter_sql(&conn, collection_ids, predicates, bounds)?;
+            let mut params = Vec::new();
+            let sql = match key {
+                AggregateKey::Collection => format!(
+                    "SELECT collection_id, COUNT(*) AS n FROM items WHERE {} \
+                     GROUP BY collection_id ORDER BY n DESC, collection_id",
+                    conditions
+                ),
+                AggregateKey::DatetimePrefix(length) => format!(
+                    "SELECT substr(datetime_start, 1, {1}) AS k, COUNT(*) FROM items \
+                     WHERE {0} AND length(datetime_start) >= {1} GROUP BY k ORDER BY k",
+                    conditions, length
+                ),
+                AggregateKey::Property(property) => {
+                    let expr = property_expression(property);
+                    format!(
+                        "SELECT {0} AS k, COUNT(*) AS n FROM items WHERE {1} AND {0} IS NOT NULL \
+                         AND json_type(data, '$.properties.\"{2}\"') NOT IN ('array', 'object') \
+                         GROUP BY k ORDER BY n DESC, k",
+                        expr, conditions, property
+                    )
+                }
+                AggregateKey::PropertyBucket { property, interval } => {
+                    // Floor of the scaled value; CAST truncates towards zero
+                    params.extend([SqlValue::Real(*interval), SqlValue::Real(*interval)]);
+                    let expr = property_expression(property);
+                    format!(
+                        "SELECT k, COUNT(*) FROM ( \
+                            SELECT (CASE WHEN v >= 0 OR v = CAST(v AS INTEGER) \
+                                THEN CAST(v AS INTEGER) ELSE CAST(v AS INTEGER) - 1 END) * ? AS k \
+                            FROM (SELECT {0} / ? AS v FROM items \
+                                WHERE {1} AND typeof({0}) IN ('integer', 'real')) \
+                         ) GROUP BY k ORDER BY k",
+                        expr, conditions
+                    )
+                }
+            };
+            params.extend(filter_params);
+
+            let mut stmt = conn.prepare_cached(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                let key = match row.get::<_, SqlValue>(0)? {
+                    SqlValue::Integer(i) => serde_json::json!(i),
+                    SqlValue::Real(f) => serde_json::json!(f),
+                    SqlValue::Text(text) => serde_json::Value::String(text),
+                    _ => serde_json::Value::Null,
+                };
+                Ok((key, row.get(1)?))
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// Earliest start and latest end of the datetimes of the items of the
+    /// given collections matching every predicate and bound, as stored UTC
+    /// date-times; open ends are ignored
+    pub async fn datetime_range(
+        &self,
+        collection_ids: &[String],
+        predicates: &[PropertyPredicate],
+        bounds: &ItemBounds,
+    ) -> Result<(Option<String>, Option<String>)> {
+        if collection_ids.is_empty() {
+            return Ok((None, None));
+        }
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let (conditions, mut params) = item_filter_sql(&conn, collection_ids, predicates, bounds)?;
+            params.splice(
+                0..0,
+                [
+                    SqlValue::Text(OPEN_START.to_string()),
+                    SqlValue::Text(OPEN_END.to_string()),
+                ],
+            );
+            conn.query_row(
+                &format!(
+                    "SELECT MIN(NULLIF(datetime_start, ?)), MAX(NULLIF(datetime_end, ?)) FROM items WHERE {}",
+                    conditions
+                ),
+                rusqlite::params_from_iter(params),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+        })
+    }
+
+    /// Property names used by the items of the given collections, each with
+    /// the SQLite JSON types (`integer`, `real`, `text`, `true`, `false`,
+    /// `array`, `object`) its values take; nulls are left out
+    pub async fn property_types(
+        &self,
+        collection_ids: &[String],
+    ) -> Result<BTreeMap<String, BTreeSet<String>>> {
+        let mut types: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        if collection_ids.is_empty() {
+            return Ok(types);
+        }
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT DISTINCT p.key, p.type FROM items, json_each(items.data, '$.properties') AS p \
+                 WHERE items.collection_id IN ({}) AND items.deleted_at IS NULL AND p.type != 'null'",
+                vec!["?"; collection_ids.len()].join(", ")
+            ))?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(collection_ids), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for row in rows {
+                let (key, json_type) = row?;
+                types.entry(key).or_default().insert(json_type);
+            }
+            Ok(types)
+        })
+    }
+
+    /// How [`Self::page_after`] and [`Self::count_matching`] filter items: the
+    /// SQL condition of each predicate, and SQLite's plan for the query
+    pub async fn filter_plan(
+        &self,
+        collection_ids: &[String],
+        predicates: &[PropertyPredicate],
+        bounds: &ItemBounds,
+    ) -> Result<(Vec<SqlCondition>, Vec<String>)> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let indexed = indexed_columns(&conn)?;
+            let mut conditions = vec![SqlCondition {
+                filter: "collections".to_string(),
+                sql: "deleted_at IS NULL AND collection_id IN (...)".to_string(),
+                indexed: true,
+            }];
+            for predicate in predicates {
+                conditions.push(SqlCondition {
+                    filter: format!("query.{}", predicate.property),
+                    sql: predicate.to_sql(&indexed).0,
+                    indexed: asset_index::asset_column(&predicate.property).is_some()
+                        || predicate.indexed_column(&indexed).is_some(),
+                });
+            }
+            for (filter, sql, _) in bounds.to_sql() {
+                conditions.push(SqlCondition {
+                    filter: filter.to_string(),
+                    sql,
+                    indexed: matches!(filter, "bbox" | "datetime" | "q"),
+                });
+            }
+
+            if collection_ids.is_empty() {
+                return Ok((conditions, Vec::new()));
+            }
+            let (where_sql, params) = item_filter_sql(&conn, collection_ids, predicates, bounds)?;
+            let mut stmt = conn.prepare_cached(&format!(
+                "EXPLAIN QUERY PLAN SELECT COUNT(*) FROM items WHERE {}",
+                where_sql
+            ))?;
+            let plan = stmt
+                .query_map(rusqlite::params_from_iter(params), |row| {
+                    row.get::<_, String>(3)
+                })?
+                .collect::<Result<Vec<_>>>()?;
+            Ok((conditions, plan))
+        })
+    }
+
+    /// Last change to the items of a collection; `None` when none was
+    /// recorded, as for a collection that never had items
+    pub async fn last_change(&self, collection_id: &str) -> Result<Option<ItemChange>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let row: Option<(String, i64)> = conn
+                .query_row(
+                    "SELECT last_modified, revision FROM item_changes WHERE collection_id = ?",
+                    [collection_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            row.map(|(last_modified, revision)| {
+                let last_modified = DateTime::parse_from_rfc3339(&last_modified)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?
+                    .with_timezone(&Utc);
+                Ok(ItemChange {
+                    last_modified,
+                    revision,
+                })
+            })
+            .transpose()
+        })
+    }
 
     /// Creates a new item
     pub async fn create(&self, item: &DbItem) -> Result<()> {
         let conn = self.db.get_connection().await;
-        let data = serde_json::to_string(item)
-            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
-        conn.execute(
-            "INSERT INTO items (id, collection_id, data) VALUES (?, ?, ?)",
-            [&item.id, &item.collection_id, &data],
-        )?;
-        Ok(())
+        blocking(|| {
+            let item = &with_computed_properties(&conn, item)?;
+            let data = serde_json::to_string(item)
+                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+            let [min_x, min_y, max_x, max_y, start, end] = item_extent(item);
+            // Imports run this per item, so the statement is kept prepared
+            conn.prepare_cached(
+                "INSERT INTO items (id, collection_id, data, bbox_min_x, bbox_min_y, bbox_max_x, bbox_max_y, datetime_start, datetime_end, content_hash)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )?
+            .execute(rusqlite::params![
+                item.id,
+                item.collection_id,
+                data,
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                start,
+                end,
+                content_hash(item)
+            ])?;
+            full_text::index_item(&conn, item)?;
+            asset_index::index_item(&conn, item)?;
+            spatial_index::index_items(&conn, &item.collection_id, Some(&item.id))?;
+            extend_collection_extent(&conn, item)?;
+            touch_item_changes(&conn, &item.collection_id)
+        })
     }
 
     /// Updates an existing item
     #[allow(dead_code)]
     pub async fn update(&self, item: &DbItem) -> Result<()> {
         let conn = self.db.get_connection().await;
-        let data = serde_json::to_string(item)
-            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
-        conn.execute(
-            "UPDATE items SET data = ? WHERE collection_id = ? AND id = ?",
-            [&data, &item.collection_id, &item.id],
-        )?;
-        Ok(())
+        blocking(|| {
+            update_item_row(&conn, item)?;
+            refresh_collection_extent(&conn, &item.collection_id)?;
+            touch_item_changes(&conn, &item.collection_id)
+        })
     }
 
-    /// Deletes an item by collection ID and item ID
-    #[allow(dead_code)]
+    /// Updates items of a collection in one transaction: all of them are
+    /// stored or, on an error, none. Returns the number of rows updated.
+    pub async fn update_many(&self, collection_id: &str, items: &[DbItem]) -> Result<usize> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let tx = conn.transaction()?;
+            let mut updated = 0;
+            for item in items {
+                updated += update_item_row(&tx, item)?;
+            }
+            if updated > 0 {
+                refresh_collection_extent(&tx, collection_id)?;
+                touch_item_changes(&tx, collection_id)?;
+            }
+            tx.commit()?;
+            Ok(updated)
+        })
+    }
+
+    /// Moves an item to the trash, hiding it from every other query until it
+    /// is restored or purged
     pub async fn delete(&self, collection_id: &str, item_id: &str) -> Result<()> {
         let conn = self.db.get_connection().await;
-        conn.execute(
-            "DELETE FROM items WHERE collection_id = ? AND id = ?",
-            [collection_id, item_id],
-        )?;
-        Ok(())
+        blocking(|| {
+            let deleted = conn.execute(
+                "UPDATE items SET deleted_at = ? WHERE collection_id = ? AND id = ? AND deleted_at IS NULL",
+                rusqlite::params![Utc::now().to_rfc3339(), collection_id, item_id],
+            )?;
+            if deleted > 0 {
+                full_text::remove(&conn, collection_id, Some(item_id))?;
+                asset_index::remove(&conn, collection_id, Some(item_id))?;
+                spatial_index::remove(&conn, collection_id, Some(item_id))?;
+                refresh_collection_extent(&conn, collection_id)?;
+                touch_item_changes(&conn, collection_id)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Takes an item out of the trash. Returns false when it is not in the
+    /// trash, or its collection is.
+    pub async fn restore(&self, collection_id: &str, item_id: &str) -> Result<bool> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let restored = conn.execute(
+                "UPDATE items SET deleted_at = NULL
+                 WHERE collection_id = ?1 AND id = ?2 AND deleted_at IS NOT NULL
+                    AND EXISTS (SELECT 1 FROM collections WHERE id = ?1 AND deleted_at IS NULL)",
+                [collection_id, item_id],
+            )?;
+            if restored > 0 {
+                for item in live_items(&conn, collection_id, Some(item_id))? {
+                    full_text::index_item(&conn, &item)?;
+                    asset_index::index_item(&conn, &item)?;
+                }
+                spatial_index::index_items(&conn, collection_id, Some(item_id))?;
+                refresh_collection_extent(&conn, collection_id)?;
+                touch_item_changes(&conn, collection_id)?;
+            }
+            Ok(restored > 0)
+        })
     }
 
+    /// Permanently deletes an item in the trash. Returns false when it is
+    /// not in the trash.
+    pub async fn purge(&self, collection_id: &str, item_id: &str) -> Result<bool> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let purged = conn.execute(
+                "DELETE FROM items WHERE collection_id = ? AND id = ? AND deleted_at IS NOT NULL",
+                [collection_id, item_id],
+            )?;
+            Ok(purged > 0)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
 
+    /// Items with the given `[min_x, min_y, max_x, max_y]` boxes, indexed
+    fn items_with_boxes(boxes: &[(&str, [f64; 4])]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (collection_id TEXT, id TEXT, deleted_at TEXT,
+                bbox_min_x REAL, bbox_min_y REAL, bbox_max_x REAL, bbox_max_y REAL)",
+        )
+        .unwrap();
+        for (id, [min_x, min_y, max_x, max_y]) in boxes {
+            conn.execute(
+                "INSERT INTO items (collection_id, id, bbox_min_x, bbox_min_y, bbox_max_x, bbox_max_y)
+                 VALUES ('c', ?, ?, ?, ?, ?)",
+                rusqlite::params![id, min_x, min_y, max_x, max_y],
+            )
+            .unwrap();
+        }
+        spatial_index::create_index(&conn).unwrap();
+        spatial_index::rebuild_index(&conn).unwrap();
+        conn
+    }
+
+    fn matching(conn: &Connection, bbox: [f64; 4]) -> Vec<String> {
+        let bounds = ItemBounds {
+            bbox: Some(bbox),
+            ..Default::default()
+        };
+        let (_, sql, params) = bounds.to_sql().remove(0);
+        let mut stmt = conn
+            .prepare(&format!("SELECT id FROM items WHERE {} ORDER BY id", sql))
+            .unwrap();
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(params), |row| row.get(0))
+            .unwrap();
+        rows.collect::<Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn bbox_matches_items_crossing_the_antimeridian() {
+        let conn = items_with_boxes(&[
+            // Fiji, from 177°E across the antimeridian to 178°W
+            ("fiji", [177.0, -19.0, -178.0, -16.0]),
+            ("europe", [5.0, 45.0, 15.0, 55.0]),
+        ]);
+        assert_eq!(matching(&conn, [178.0, -20.0, 179.0, -15.0]), ["fiji"]);
+        assert_eq!(matching(&conn, [-179.5, -20.0, -178.5, -15.0]), ["fiji"]);
+        assert!(matching(&conn, [0.0, -20.0, 10.0, -15.0]).is_empty());
+        assert_eq!(matching(&conn, [0.0, 40.0, 10.0, 50.0]), ["europe"]);
+    }
+
+    #[test]
+    fn bbox_crossing_the_antimeridian_matches_either_side() {
+        let conn = items_with_boxes(&[
+            ("fiji", [177.0, -19.0, -178.0, -16.0]),
+            ("samoa", [-172.8, -14.1, -171.4, -13.4]),
+            ("tonga", [-175.5, -21.5, -173.9, -15.5]),
+            ("europe", [5.0, 45.0, 15.0, 55.0]),
+        ]);
+        assert_eq!(
+            matching(&conn, [170.0, -25.0, -174.0, -10.0]),
+            ["fiji", "tonga"]
+        );
+        assert!(matching(&conn, [170.0, 40.0, -170.0, 60.0]).is_empty());
+    }
 }
@@ -0,0 +1,121 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::{OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+
+/// Timestamp an age rule measures items by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionAge {
+    /// Acquisition time: `datetime`, or `end_datetime` for intervals
+    #[default]
+    Acquired,
+    /// When the item was added to the catalog
+    Created,
+}
+
+/// A condition under which items of a collection expire
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RetentionRule {
+    /// Items older than `days` expire
+    MaxAge {
+        days: u32,
+        #[serde(default)]
+        age: RetentionAge,
+    },
+    /// Only the newest `count` items are kept, per distinct value of the
+    /// `group_by` property (e.g. `platform`) when given
+    KeepLatest {
+        count: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group_by: Option<String>,
+    },
+}
+
+/// Retention rules of a collection. An item expires when any rule says so.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub collection_id: String,
+    pub rules: Vec<RetentionRule>,
+    /// Whether the scheduler applies the policy; disabled policies can still
+    /// be previewed
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Clone)]
+pub struct RetentionPolicyRepository {
+    db: DatabaseConnection,
+}
+
+impl RetentionPolicyRepository {
+    /// Creates a new retention policy repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Gets the policies of all collections
+    pub async fn get_all(&self) -> Result<Vec<RetentionPolicy>> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let mut stmt =
+                conn.prepare_cached("SELECT data FROM retention_policies ORDER BY collection_id")?;
+            let rows = stmt.query_map([], |row| {
+                let data: String = row.get(0)?;
+                serde_json::from_str::<RetentionPolicy>(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// Gets the policy of a collection
+    pub async fn get(&self, collection_id: &str) -> Result<Option<RetentionPolicy>> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.query_row(
+                "SELECT data FROM retention_policies WHERE collection_id = ?",
+                [collection_id],
+                |row| {
+                    let data: String = row.get(0)?;
+                    serde_json::from_str::<RetentionPolicy>(&data)
+                        .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))
+                },
+            )
+            .optional()
+        })
+    }
+
+    /// Creates or replaces the policy of a collection
+    pub async fn upsert(&self, policy: &RetentionPolicy) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let data = serde_json::to_string(policy)
+                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+            conn.execute(
+                "INSERT OR REPLACE INTO retention_policies (collection_id, data) VALUES (?1, ?2)",
+                rusqlite::params![policy.collection_id, data],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Deletes the policy of a collection, returning whether it existed
+    pub async fn delete(&self, collection_id: &str) -> Result<bool> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let deleted = conn.execute(
+                "DELETE FROM retention_policies WHERE collection_id = ?",
+                [collection_id],
+            )?;
+            Ok(deleted > 0)
+        })
+    }
+}
@@ -1,10 +1,15 @@
-use crate::database::DatabaseConnection;
-use rusqlite::Result;
+use crate::database::{asset_index, full_text, spatial_index};
+use crate::database::repository::item_extent;
+use crate::database::DbItem;
+use rusqlite::{Connection, Result};
 
-pub async fn create_tables(db: &DatabaseConnection) -> Result<()> {
-    let conn = db.get_connection().await;
-
-    // Create collections table if it doesn't exist
+/// Schema version 1: every table, column and index of databases created
+/// before schema versions were recorded. Each step checks what is already
+/// there, so it brings a database of any earlier layout up to date.
+pub(crate) fn baseline(conn: &Connection) -> Result<()> {
+    // Create collections table if it doesn't exist. The primary keys of this
+    // and the items table are the unique constraints behind the 409 answered
+    // to a create with a taken id.
     conn.execute(
         r#"
         CREATE TABLE IF NOT EXISTS collections (
@@ -35,17 +40,394 @@ pub async fn create_tables(db: &DatabaseConnection) -> Result<()> {
         [],
     )?;
 
+    // Recency index for the Atom feeds of recently added items
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_items_updated_at ON items(json_extract(data, '$.updated_at'))",
+        [],
+    )?;
+
     // Create application_settings table if it doesn't exist
     conn.execute(
         r#"
         CREATE TABLE IF NOT EXISTS application_settings (
             key TEXT PRIMARY KEY,
             value TEXT NOT NULL,
+            version INTEGER NOT NULL DEFAULT 1,
             updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
         )
         "#,
         [],
     )?;
 
+    // Daily request counters per collection, item and asset
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS access_stats (
+            day TEXT NOT NULL,
+            collection_id TEXT NOT NULL,
+            item_id TEXT NOT NULL DEFAULT '',
+            asset_key TEXT NOT NULL DEFAULT '',
+            hits INTEGER NOT NULL DEFAULT 0,
+            bytes INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (day, collection_id, item_id, asset_key)
+        )
+        "#,
+        [],
+    )?;
+
+    // Anonymised clients seen per day, for unique client counts
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS access_clients (
+            day TEXT NOT NULL,
+            collection_id TEXT NOT NULL,
+            item_id TEXT NOT NULL DEFAULT '',
+            asset_key TEXT NOT NULL DEFAULT '',
+            client TEXT NOT NULL,
+            PRIMARY KEY (day, collection_id, item_id, asset_key, client)
+        )
+        "#,
+        [],
+    )?;
+
+    // Stored searches exposed as read-only collections
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS virtual_collections (
+            id TEXT PRIMARY KEY,
+            data TEXT NOT NULL
+        )
+        "#,
+        [],
+    )?;
+
+    // Typed relationships between items, possibly across collections
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS item_links (
+            source_collection TEXT NOT NULL,
+            source_item TEXT NOT NULL,
+            rel TEXT NOT NULL,
+            target_collection TEXT NOT NULL,
+            target_item TEXT NOT NULL,
+            PRIMARY KEY (source_collection, source_item, rel, target_collection, target_item)
+        )
+        "#,
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_item_links_target ON item_links(target_collection, target_item)",
+        [],
+    )?;
+
+    // Item properties a collection indexes through generated columns on items
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS indexed_properties (
+            collection_id TEXT NOT NULL,
+            property TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            PRIMARY KEY (collection_id, property)
+        )
+        "#,
+        [],
+    )?;
+
+    // Item properties a collection computes from formulas, e.g. area_km2
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS computed_properties (
+            collection_id TEXT NOT NULL,
+            property TEXT NOT NULL,
+            formula TEXT NOT NULL,
+            PRIMARY KEY (collection_id, property)
+        )
+        "#,
+        [],
+    )?;
+
+    // Revision vectors of collections and items for peer sync, including
+    // tombstones of deleted records
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS sync_revisions (
+            collection_id TEXT NOT NULL,
+            item_id TEXT NOT NULL DEFAULT '',
+            vector TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            deleted INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (collection_id, item_id)
+        )
+        "#,
+        [],
+    )?;
+
+    // Per-collection retention rules applied by the scheduler
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS retention_policies (
+            collection_id TEXT PRIMARY KEY,
+            data TEXT NOT NULL
+        )
+        "#,
+        [],
+    )?;
+
+    // Secrets known to the app; values live in the OS keyring or the fallback
+    // file, never in this table
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS secrets (
+            id TEXT PRIMARY KEY,
+            backend TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+        "#,
+        [],
+    )?;
+
+    // Cached size of each item's asset folder, kept current on upload and
+    // delete
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS asset_sizes (
+            collection_id TEXT NOT NULL,
+            item_id TEXT NOT NULL,
+            bytes INTEGER NOT NULL,
+            file_count INTEGER NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (collection_id, item_id)
+        )
+        "#,
+        [],
+    )?;
+
+    // Language-tagged titles and descriptions of collections and items, as
+    // JSON keyed by language tag
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS translations (
+            collection_id TEXT NOT NULL,
+            item_id TEXT NOT NULL DEFAULT '',
+            data TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (collection_id, item_id)
+        )
+        "#,
+        [],
+    )?;
+
+    // Time and revision of the last item change per collection, so list
+    // endpoints answer conditional requests without reading the items
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS item_changes (
+            collection_id TEXT PRIMARY KEY,
+            last_modified TEXT NOT NULL,
+            revision INTEGER NOT NULL DEFAULT 0
+        )
+        "#,
+        [],
+    )?;
+
+    // Collections whose items were stored before changes were tracked start
+    // from their newest item
+    conn.execute(
+        r#"
+        INSERT OR IGNORE INTO item_changes (collection_id, last_modified, revision)
+        SELECT collection_id,
+            COALESCE(MAX(json_extract(data, '$.updated_at')), strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+            0
+        FROM items GROUP BY collection_id
+        "#,
+        [],
+    )?;
+
+    // Chunked uploads in progress: how much of the file has been written to
+    // its temporary file and the running CRC-32 of those bytes, so an upload
+    // resumes where it stopped after a restart
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS upload_sessions (
+            id TEXT PRIMARY KEY,
+            collection_id TEXT NOT NULL,
+            item_id TEXT NOT NULL,
+            asset_key TEXT NOT NULL,
+            file_name TEXT NOT NULL,
+            content_type TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            received INTEGER NOT NULL DEFAULT 0,
+            crc32 INTEGER NOT NULL DEFAULT 0,
+            checksum TEXT,
+            temp_path TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+        "#,
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_upload_sessions_asset ON upload_sessions(collection_id, item_id, asset_key)",
+        [],
+    )?;
+
+    // Broken external links and STAC violations found by the last catalog
+    // check, and the summary of that run in a single row
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS catalog_check_issues (
+            collection_id TEXT NOT NULL,
+            item_id TEXT NOT NULL DEFAULT '',
+            kind TEXT NOT NULL,
+            name TEXT NOT NULL,
+            href TEXT NOT NULL DEFAULT '',
+            status INTEGER,
+            error TEXT
+        )
+        "#,
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_catalog_check_issues_collection ON catalog_check_issues(collection_id, item_id)",
+        [],
+    )?;
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS catalog_check_runs (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            started_at TEXT NOT NULL,
+            finished_at TEXT NOT NULL,
+            checked_urls INTEGER NOT NULL,
+            broken_links INTEGER NOT NULL,
+            stac_violations INTEGER NOT NULL
+        )
+        "#,
+        [],
+    )?;
+
+    // When a collection or item was moved to the trash; NULL while live.
+    // Added before the indexes below are filled, which skip trashed rows.
+    add_column_if_missing(conn, "collections", "deleted_at", "TEXT")?;
+    add_column_if_missing(conn, "items", "deleted_at", "TEXT")?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_items_deleted_at ON items(deleted_at) WHERE deleted_at IS NOT NULL",
+        [],
+    )?;
+
+    // Free-text index over collection and item metadata, filled from the
+    // stored records when first created
+    if full_text::create_index(conn)? {
+        full_text::rebuild_index(conn)?;
+    }
+
+    // Index of item asset media types, roles and sizes for asset searches
+    if asset_index::create_index(conn)? {
+        asset_index::rebuild_index(conn)?;
+    }
+
+    // Optional JSON Schema that items of a collection must satisfy
+    add_column_if_missing(conn, "collections", "item_schema", "TEXT")?;
+
+    // Item whose thumbnail a user pinned as the collection's thumbnail
+    add_column_if_missing(conn, "collections", "thumbnail_item", "TEXT")?;
+
+    // Databases created before settings were versioned lack this column
+    add_column_if_missing(
+        conn,
+        "application_settings",
+        "version",
+        "INTEGER NOT NULL DEFAULT 1",
+    )?;
+
+    // Item extents, so search filters bbox and datetime in SQL
+    let mut added = false;
+    for column in ["bbox_min_x", "bbox_min_y", "bbox_max_x", "bbox_max_y"] {
+        added |= add_column_if_missing(conn, "items", column, "REAL")?;
+    }
+    for column in ["datetime_start", "datetime_end"] {
+        added |= add_column_if_missing(conn, "items", column, "TEXT")?;
+    }
+    if added {
+        backfill_item_extents(conn)?;
+    }
+
+    // R*Tree of item boxes for bbox searches, filled from the extent columns
+    // when first created or when those were just backfilled
+    if spatial_index::create_index(conn)? || added {
+        spatial_index::rebuild_index(conn)?;
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_items_datetime ON items(collection_id, datetime_start, datetime_end)",
+        [],
+    )?;
+
     Ok(())
 }
+
+/// Fills the extent columns of items stored before they existed
+fn backfill_item_extents(conn: &Connection) -> Result<()> {
+    let items = {
+        let mut stmt = conn.prepare("SELECT id, collection_id, data FROM items")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        rows.filter_map(|row| row.ok())
+            .filter_map(|(id, collection_id, data)| {
+                let mut item = serde_json::from_str::<DbItem>(&data).ok()?;
+                item.id = id;
+                item.collection_id = collection_id;
+                Some(item)
+            })
+            .collect::<Vec<_>>()
+    };
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE items SET bbox_min_x = ?, bbox_min_y = ?, bbox_max_x = ?, bbox_max_y = ?,
+                datetime_start = ?, datetime_end = ?
+             WHERE collection_id = ? AND id = ?",
+        )?;
+        for item in &items {
+            let [min_x, min_y, max_x, max_y, start, end] = item_extent(item);
+            stmt.execute(rusqlite::params![
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                start,
+                end,
+                item.collection_id,
+                item.id
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Adds a column to an existing table unless it is already present. Returns
+/// whether it was added.
+pub(crate) fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|name| name.ok())
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(!exists)
+}
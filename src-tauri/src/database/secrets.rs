@@ -0,0 +1,316 @@
+use crate::database::connection::blocking;
+use crate::database::settings::{self, SecretsKeyringId, Setting};
+use crate::database::DatabaseConnection;
+use rusqlite::{OptionalExtension, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Prefix of the keyring service names secrets are stored under; each
+/// catalog appends its own id
+const KEYRING_SERVICE: &str = "zenstac";
+
+/// Where a secret's value is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackend {
+    /// macOS Keychain, Windows Credential Manager or the Secret Service
+    Keyring,
+    /// Owner-only file in the catalog's data directory, for systems without a
+    /// keyring
+    File,
+}
+
+impl SecretBackend {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SecretBackend::Keyring => "keyring",
+            SecretBackend::File => "file",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "keyring" => SecretBackend::Keyring,
+            _ => SecretBackend::File,
+        }
+    }
+}
+
+/// A stored secret, without its value
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretInfo {
+    pub id: String,
+    pub backend: SecretBackend,
+    pub updated_at: String,
+}
+
+/// Checks that a secret id can be referenced from settings and environment
+/// variable names
+pub fn check_secret_id(id: &str) -> std::result::Result<(), String> {
+    if id.is_empty() || id.len() > 64 {
+        return Err("Secret id must be 1 to 64 characters long".to_string());
+    }
+    if !id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        return Err(format!(
+            "Secret id '{}' may only contain letters, digits, '_', '-' and '.'",
+            id
+        ));
+    }
+    Ok(())
+}
+
+/// Environment variable that overrides a secret, e.g. `ZENSTAC_SECRET_TILE_KEY`
+/// for `tile-key`
+pub fn secret_env_var(id: &str) -> String {
+    let name: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("ZENSTAC_SECRET_{}", name)
+}
+
+/// Credentials for outbound integrations. Values live in the OS keyring, or in
+/// an owner-only fallback file when no keyring is available; the database only
+/// records which secrets exist so they can be listed and referenced by id.
+#[derive(Clone)]
+pub struct SecretRepository {
+    db: DatabaseConnection,
+    fallback_file: PathBuf,
+    /// Off for an in-memory catalog, whose secrets must not outlive it in
+    /// the user's keyring
+    use_keyring: bool,
+}
+
+impl SecretRepository {
+    /// Creates a new secret repository keeping its fallback file in the data
+    /// directory of the catalog at `db_path`
+    pub fn new(db: DatabaseConnection, db_path: &str) -> Self {
+        Self {
+            db,
+            fallback_file: crate::config::catalog_data_dir(db_path).join("secrets.json"),
+            use_keyring: !crate::config::is_memory_database(db_path),
+        }
+    }
+
+    /// Keyring service this catalog's secrets are stored under,
+    /// `zenstac:<id>` with an id generated for the catalog on first use, so
+    /// catalogs sharing a user account never read or overwrite each other's
+    /// secrets
+    async fn keyring_service(&self) -> std::result::Result<String, String> {
+        let mut conn = self.db.get_connection().await;
+        blocking(|| {
+            let mut id = settings::load::<SecretsKeyringId>(&conn).map_err(|e| e.to_string())?;
+            if id.is_empty() {
+                id = uuid::Uuid::new_v4().to_string();
+                settings::store_raw(
+                    &mut conn,
+                    &[(SecretsKeyringId::KEY.to_string(), id.clone())],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            Ok(format!("{}:{}", KEYRING_SERVICE, id))
+        })
+    }
+
+    /// Stores a secret, preferring the OS keyring
+    pub async fn set(&self, id: &str, value: &str) -> std::result::Result<SecretBackend, String> {
+        check_secret_id(id)?;
+
+        let keyring_id = id.to_string();
+        let keyring_value = value.to_string();
+        let in_keyring = if self.use_keyring {
+            let service = self.keyring_service().await?;
+            let stored = tokio::task::spawn_blocking(move || {
+                keyring::Entry::new(&service, &keyring_id)
+                    .and_then(|entry| entry.set_password(&keyring_value))
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+            Some(stored)
+        } else {
+            None
+        };
+
+        let backend = match in_keyring {
+            Some(Ok(())) => {
+                // A value stored in the file earlier must not linger in plain text
+                self.update_file(|secrets| {
+                    secrets.remove(id);
+                })?;
+                SecretBackend::Keyring
+            }
+            failed => {
+                if let Some(Err(e)) = failed {
+                    eprintln!("Keyring unavailable, storing secret '{}' in file: {}", id, e);
+                }
+                self.update_file(|secrets| {
+                    secrets.insert(id.to_string(), value.to_string());
+                })?;
+                SecretBackend::File
+            }
+        };
+
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute(
+                "INSERT OR REPLACE INTO secrets (id, backend, updated_at) VALUES (?, ?, ?)",
+                [id, backend.as_str(), &chrono::Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(backend)
+        })
+    }
+
+    /// Value of a secret. An environment variable named by [`secret_env_var`]
+    /// takes precedence, so headless deployments can inject credentials.
+    pub async fn get(&self, id: &str) -> std::result::Result<Option<String>, String> {
+        check_secret_id(id)?;
+        if let Ok(value) = std::env::var(secret_env_var(id)) {
+            return Ok(Some(value));
+        }
+
+        let backend: Option<String> = {
+            let conn = self.db.get_connection().await;
+            blocking(|| {
+                conn.query_row("SELECT backend FROM secrets WHERE id = ?", [id], |row| row.get(0))
+                    .optional()
+            })
+            .map_err(|e| e.to_string())?
+        };
+
+        match backend.as_deref().map(SecretBackend::parse) {
+            None => Ok(None),
+            Some(SecretBackend::File) => Ok(self.read_file()?.remove(id)),
+            Some(SecretBackend::Keyring) => {
+                let service = self.keyring_service().await?;
+                let keyring_id = id.to_string();
+                let value = tokio::task::spawn_blocking(move || {
+                    keyring::Entry::new(&service, &keyring_id)
+                        .and_then(|entry| entry.get_password())
+                })
+                .await
+                .map_err(|e| e.to_string())?;
+                match value {
+                    Ok(value) => Ok(Some(value)),
+                    Err(keyring::Error::NoEntry) => Ok(None),
+                    Err(e) => Err(format!("Failed to read secret '{}' from keyring: {}", id, e)),
+                }
+            }
+        }
+    }
+
+    /// Removes a secret from wherever it is stored
+    pub async fn delete(&self, id: &str) -> std::result::Result<(), String> {
+        check_secret_id(id)?;
+
+        if self.use_keyring {
+            let service = self.keyring_service().await?;
+            let keyring_id = id.to_string();
+            let removed = tokio::task::spawn_blocking(move || {
+                keyring::Entry::new(&service, &keyring_id)
+                    .and_then(|entry| entry.delete_credential())
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+            if let Err(e) = removed {
+                if !matches!(e, keyring::Error::NoEntry) {
+                    eprintln!("Failed to remove secret '{}' from keyring: {}", id, e);
+                }
+            }
+        }
+        self.update_file(|secrets| {
+            secrets.remove(id);
+        })?;
+
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute("DELETE FROM secrets WHERE id = ?", [id])
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        })
+    }
+
+    /// Stored secrets, without their values
+    pub async fn list(&self) -> Result<Vec<SecretInfo>> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached("SELECT id, backend, updated_at FROM secrets ORDER BY id")?;
+            let rows = stmt.query_map([], |row| {
+                let backend: String = row.get(1)?;
+                Ok(SecretInfo {
+                    id: row.get(0)?,
+                    backend: SecretBackend::parse(&backend),
+                    updated_at: row.get(2)?,
+                })
+            })?;
+            rows.collect()
+        })
+    }
+
+    /// Replaces `${secret:<id>}` references in a configured value, e.g. an API
+    /// key in a tile URL template
+    pub async fn expand(&self, text: &str) -> std::result::Result<String, String> {
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${secret:") {
+            expanded.push_str(&rest[..start]);
+            let reference = &rest[start + "${secret:".len()..];
+            let end = reference
+                .find('}')
+                .ok_or_else(|| "Unterminated secret reference".to_string())?;
+            let id = &reference[..end];
+            let value = self
+                .get(id)
+                .await?
+                .ok_or_else(|| format!("Secret '{}' is not set", id))?;
+            expanded.push_str(&value);
+            rest = &reference[end + 1..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    fn read_file(&self) -> std::result::Result<BTreeMap<String, String>, String> {
+        match std::fs::read_to_string(&self.fallback_file) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| {
+                format!("Failed to parse {}: {}", self.fallback_file.display(), e)
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(format!("Failed to read {}: {}", self.fallback_file.display(), e)),
+        }
+    }
+
+    fn update_file(
+        &self,
+        change: impl FnOnce(&mut BTreeMap<String, String>),
+    ) -> std::result::Result<(), String> {
+        let existed = self.fallback_file.exists();
+        let mut secrets = self.read_file()?;
+        change(&mut secrets);
+        if !existed && secrets.is_empty() {
+            return Ok(());
+        }
+
+        let content = serde_json::to_string_pretty(&secrets).map_err(|e| e.to_string())?;
+        if let Some(dir) = self.fallback_file.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        use std::io::Write;
+        options
+            .open(&self.fallback_file)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(|e| format!("Failed to write {}: {}", self.fallback_file.display(), e))
+    }
+}
@@ -1,5 +1,15 @@
-use crate::database::{create_tables, CollectionRepository, DatabaseConnection, ItemRepository};
+use crate::database::settings::FirstInstallationComplete;
+use crate::database::{
+    migrate, AccessStatsRepository, AssetSizeRepository, CatalogCheckRepository,
+    CollectionRepository, ComputedPropertyRepository, DatabaseConnection,
+    IndexedPropertyRepository, ItemLinkRepository, ItemRepository, ItemStatistics,
+    MaintenanceRepository, RetentionPolicyRepository, SecretRepository, SettingsRepository,
+    SyncRevisionRepository, TranslationRepository, UploadSessionRepository,
+    VirtualCollectionRepository,
+};
 use rusqlite::Result;
+use serde::Serialize;
+use std::collections::HashMap;
 
 /// Status information about the database
 #[derive(Debug)]
@@ -9,10 +19,51 @@ pub struct DatabaseStatus {
     pub is_empty: bool,
 }
 
+/// Item counts, extent and asset storage of a collection
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionStatistics {
+    #[serde(flatten)]
+    pub items: ItemStatistics,
+    /// From the asset size counters
+    pub asset_bytes: u64,
+    pub asset_files: u64,
+}
+
+/// Totals over the live collections, with the figures of each
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogStatistics {
+    pub collection_count: u64,
+    pub item_count: u64,
+    pub items_with_bbox: u64,
+    pub asset_bytes: u64,
+    pub asset_files: u64,
+    /// Union of the collections' item bboxes
+    pub bbox: Option<[f64; 4]>,
+    /// Earliest start and latest end over all items; `None` where an item
+    /// leaves it open or no item has a datetime
+    pub datetime_start: Option<String>,
+    pub datetime_end: Option<String>,
+    pub collections: Vec<CollectionStatistics>,
+}
+
 #[derive(Clone)]
 pub struct DatabaseService {
     pub collections: CollectionRepository,
     pub items: ItemRepository,
+    pub settings: SettingsRepository,
+    pub access_stats: AccessStatsRepository,
+    pub virtual_collections: VirtualCollectionRepository,
+    pub item_links: ItemLinkRepository,
+    pub indexed_properties: IndexedPropertyRepository,
+    pub computed_properties: ComputedPropertyRepository,
+    pub sync_revisions: SyncRevisionRepository,
+    pub secrets: SecretRepository,
+    pub retention_policies: RetentionPolicyRepository,
+    pub asset_sizes: AssetSizeRepository,
+    pub translations: TranslationRepository,
+    pub upload_sessions: UploadSessionRepository,
+    pub catalog_checks: CatalogCheckRepository,
+    pub maintenance: MaintenanceRepository,
 }
 
 impl DatabaseService {
@@ -21,62 +72,134 @@ impl DatabaseService {
 
         let db_conn = DatabaseConnection::new(db_path).await?;
 
-        // Create tables if they don't exist (preserves existing data)
-        create_tables(&db_conn).await?;
+        // Apply pending schema migrations; a database from a newer release
+        // is refused untouched
+        migrate(&db_conn).await?;
 
 
         let collections = CollectionRepository::new(db_conn.clone());
-        let items = ItemRepository::new(db_conn);
+        let items = ItemRepository::new(db_conn.clone());
+        let settings = SettingsRepository::new(db_conn.clone());
+        let access_stats = AccessStatsRepository::new(db_conn.clone());
+        let virtual_collections = VirtualCollectionRepository::new(db_conn.clone());
+        let item_links = ItemLinkRepository::new(db_conn.clone());
+        let indexed_properties = IndexedPropertyRepository::new(db_conn.clone());
+        let computed_properties = ComputedPropertyRepository::new(db_conn.clone());
+        let sync_revisions = SyncRevisionRepository::new(db_conn.clone());
+        let secrets = SecretRepository::new(db_conn.clone(), db_path);
+        let retention_policies = RetentionPolicyRepository::new(db_conn.clone());
+        let asset_sizes = AssetSizeRepository::new(db_conn.clone());
+        let translations = TranslationRepository::new(db_conn.clone());
+        let upload_sessions = UploadSessionRepository::new(db_conn.clone());
+        let catalog_checks = CatalogCheckRepository::new(db_conn.clone());
+        let maintenance = MaintenanceRepository::new(db_conn, db_path);
 
-        Ok(Self { collections, items })
+        Ok(Self {
+            collections,
+            items,
+            settings,
+            access_stats,
+            virtual_collections,
+            item_links,
+            indexed_properties,
+            computed_properties,
+            sync_revisions,
+            secrets,
+            retention_policies,
+            asset_sizes,
+            translations,
+            upload_sessions,
+            catalog_checks,
+            maintenance,
+        })
     }
 
     /// Check if the database is empty (no collections exist)
     pub async fn is_empty(&self) -> Result<bool, Box<dyn std::error::Error>> {
-        let existing_collections = self.collections.get_all().await?;
-        Ok(existing_collections.is_empty())
+        Ok(self.collections.count().await? == 0)
     }
 
     /// Check if this is the first installation by looking for a specific setting
     pub async fn is_first_installation(&self) -> Result<bool, Box<dyn std::error::Error>> {
-        let conn = self.collections.get_connection().await;
-        let mut stmt = conn.prepare("SELECT value FROM application_settings WHERE key = 'first_installation_complete'")?;
-        let mut rows = stmt.query_map([], |row| {
-            let value: String = row.get(0)?;
-            Ok(value)
-        })?;
-
-        // If no setting exists, this is the first installation
-        Ok(rows.next().is_none())
+        let complete = self.settings.get::<FirstInstallationComplete>().await?;
+        Ok(!complete)
     }
 
     /// Mark the first installation as complete
     pub async fn mark_first_installation_complete(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let conn = self.collections.get_connection().await;
-        conn.execute(
-            "INSERT OR REPLACE INTO application_settings (key, value, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
-            ("first_installation_complete", "true"),
-        )?;
+        self.settings.set::<FirstInstallationComplete>(&true).await?;
         Ok(())
     }
 
     /// Get database status information
     pub async fn get_status(&self) -> Result<DatabaseStatus, Box<dyn std::error::Error>> {
-        let collections = self.collections.get_all().await?;
-        let mut total_items = 0;
-
-        for collection in &collections {
-            let items = self
-                .items
-                .get_by_collection(&collection.id, None, None)
-                .await?;
-            total_items += items.len();
-        }
+        let collections = self.collections.count().await?;
+        let total_items = self.items.count(None).await?;
 
         Ok(DatabaseStatus {
-            collections_count: collections.len(),
-            total_items_count: total_items,
-            is_empty: collections.is_empty(),
+            collections_count: collections as usize,
+            total_items_count: total_items as usize,
+            is_empty: collections == 0,
+        })
+    }
+
+    /// Item counts, extents and asset storage per collection and in total,
+    /// from aggregate queries and the asset size counters
+    pub async fn get_statistics(&self) -> Result<CatalogStatistics, Box<dyn std::error::Error>> {
+        let items = self.items.statistics().await?;
+        let sizes: HashMap<String, (u64, u64)> = self
+            .asset_sizes
+            .collection_totals()
+            .await?
+            .into_iter()
+            .filter_map(|totals| Some((totals.collection_id?, (totals.bytes, totals.file_count))))
+            .collect();
+
+        let collections: Vec<CollectionStatistics> = items
+            .into_iter()
+            .map(|items| {
+                let (asset_bytes, asset_files) =
+                    sizes.get(&items.collection_id).copied().unwrap_or_default();
+                CollectionStatistics {
+                    items,
+                    asset_bytes,
+                    asset_files,
+                }
+            })
+            .collect();
+
+        let bbox = collections
+            .iter()
+            .filter_map(|c| c.items.bbox)
+            .reduce(|a, b| [a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])]);
+        // A collection with dated items but an open bound leaves the
+        // catalog's bound open too
+        let dated: Vec<&ItemStatistics> = collections
+            .iter()
+            .map(|c| &c.items)
+            .filter(|items| items.items_with_datetime > 0)
+            .collect();
+        let datetime_start = dated
+            .iter()
+            .map(|items| items.datetime_start.clone())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|starts| starts.into_iter().min());
+        let datetime_end = dated
+            .iter()
+            .map(|items| items.datetime_end.clone())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|ends| ends.into_iter().max());
+
+        Ok(CatalogStatistics {
+            collection_count: collections.len() as u64,
+            item_count: collections.iter().map(|c| c.items.item_count).sum(),
+            items_with_bbox: collections.iter().map(|c| c.items.items_with_bbox).sum(),
+            asset_bytes: collections.iter().map(|c| c.asset_bytes).sum(),
+            asset_files: collections.iter().map(|c| c.asset_files).sum(),
+            bbox,
+            datetime_start,
+            datetime_end,
+            collections,
         })
     }
 
@@ -0,0 +1,1181 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::{Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::sync::broadcast;
+
+/// A typed application setting stored in the `application_settings` table.
+///
+/// Values are stored as text: strings verbatim, everything else as JSON, so
+/// preferences written by older versions (and by the frontend) stay readable.
+pub trait Setting {
+    /// The Rust type of the setting value
+    type Value: Serialize + DeserializeOwned + Clone;
+    /// Key of the setting in the database
+    const KEY: &'static str;
+    /// Schema version of the stored value, bumped when its format changes
+    const VERSION: u32 = 1;
+
+    /// Value used when the setting has never been written
+    fn default_value() -> Self::Value;
+
+    /// Checks that a value is acceptable before it is persisted
+    fn validate(_value: &Self::Value) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Upgrades a value stored with an older schema version
+    fn migrate(_from_version: u32, raw: &str) -> Option<Self::Value> {
+        decode_value(raw)
+    }
+}
+
+/// Host/IP the STAC server binds to
+pub struct ServerInternalAddress;
+
+impl Setting for ServerInternalAddress {
+    type Value = String;
+    const KEY: &'static str = "server_internal_address";
+
+    fn default_value() -> String {
+        "127.0.0.1".to_string()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if value.trim().is_empty() {
+            return Err("Internal address cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Host or full URL advertised to clients in generated links
+pub struct ServerExternalAddress;
+
+impl Setting for ServerExternalAddress {
+    type Value = String;
+    const KEY: &'static str = "server_external_address";
+
+    fn default_value() -> String {
+        "127.0.0.1".to_string()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if value.trim().is_empty() {
+            return Err("External address cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Port the STAC server listens on
+pub struct ServerPort;
+
+impl Setting for ServerPort {
+    type Value = u16;
+    const KEY: &'static str = "server_port";
+
+    fn default_value() -> u16 {
+        3000
+    }
+
+    fn validate(value: &u16) -> Result<(), String> {
+        if *value < 1024 {
+            return Err("Port must be between 1024 and 65535".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Public domain to obtain a certificate for over ACME and serve HTTPS on;
+/// empty serves plain HTTP only
+pub struct AcmeDomain;
+
+impl Setting for AcmeDomain {
+    type Value = String;
+    const KEY: &'static str = "acme_domain";
+
+    fn default_value() -> String {
+        String::new()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if value.is_empty() {
+            return Ok(());
+        }
+        let valid_label = |label: &str| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
+        if !value.contains('.') || !value.split('.').all(valid_label) {
+            return Err(
+                "ACME domain must be a host name such as stac.example.com, without scheme or port"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Contact address given to the ACME certificate authority for expiry notices
+pub struct AcmeEmail;
+
+impl Setting for AcmeEmail {
+    type Value = String;
+    const KEY: &'static str = "acme_email";
+
+    fn default_value() -> String {
+        String::new()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if !value.is_empty() && !value.contains('@') {
+            return Err("ACME contact must be an email address".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// ACME directory of the certificate authority; the Let's Encrypt staging
+/// directory is useful while testing
+pub struct AcmeDirectoryUrl;
+
+impl Setting for AcmeDirectoryUrl {
+    type Value = String;
+    const KEY: &'static str = "acme_directory_url";
+
+    fn default_value() -> String {
+        "https://acme-v02.api.letsencrypt.org/directory".to_string()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if !value.starts_with("https://") {
+            return Err("ACME directory must be an https:// URL".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Port answering ACME HTTP-01 challenges; the certificate authority
+/// connects to port 80 of the domain, which may be forwarded here
+pub struct AcmeChallengePort;
+
+impl Setting for AcmeChallengePort {
+    type Value = u16;
+    const KEY: &'static str = "acme_challenge_port";
+
+    fn default_value() -> u16 {
+        80
+    }
+}
+
+/// Port the HTTPS listener binds to when an ACME domain is set
+pub struct HttpsPort;
+
+impl Setting for HttpsPort {
+    type Value = u16;
+    const KEY: &'static str = "https_port";
+
+    fn default_value() -> u16 {
+        443
+    }
+}
+
+/// Set once the sample data has been created on first launch
+pub struct FirstInstallationComplete;
+
+impl Setting for FirstInstallationComplete {
+    type Value = bool;
+    const KEY: &'static str = "first_installation_complete";
+
+    fn default_value() -> bool {
+        false
+    }
+}
+
+/// Maximum number of per-collection `child` links on the landing page.
+/// Catalogs with more collections are browsed through `/collections` instead.
+pub struct LandingChildLinkLimit;
+
+impl Setting for LandingChildLinkLimit {
+    type Value = usize;
+    const KEY: &'static str = "landing_child_link_limit";
+
+    fn default_value() -> usize {
+        100
+    }
+}
+
+/// Maximum request body size, in bytes, for JSON endpoints
+pub struct MaxJsonBodyBytes;
+
+impl Setting for MaxJsonBodyBytes {
+    type Value = u64;
+    const KEY: &'static str = "max_json_body_bytes";
+
+    fn default_value() -> u64 {
+        10 * 1024 * 1024
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if *value < 1024 {
+            return Err("JSON body limit must be at least 1024 bytes".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Maximum request body size, in bytes, for asset uploads
+pub struct MaxUploadBodyBytes;
+
+impl Setting for MaxUploadBodyBytes {
+    type Value = u64;
+    const KEY: &'static str = "max_upload_body_bytes";
+
+    fn default_value() -> u64 {
+        4 * 1024 * 1024 * 1024
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if *value < 1024 {
+            return Err("Upload body limit must be at least 1024 bytes".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// XYZ tile URL template the basemap cache fetches from
+pub struct BasemapUpstreamUrl;
+
+impl Setting for BasemapUpstreamUrl {
+    type Value = String;
+    const KEY: &'static str = "basemap_upstream_url";
+
+    fn default_value() -> String {
+        "https://tile.openstreetmap.org/{z}/{x}/{y}.png".to_string()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if !value.starts_with("http://") && !value.starts_with("https://") {
+            return Err("Basemap URL must start with http:// or https://".to_string());
+        }
+        if !["{z}", "{x}", "{y}"].iter().all(|p| value.contains(p)) {
+            return Err("Basemap URL must contain {z}, {x} and {y} placeholders".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Maximum size of the on-disk basemap tile cache, in bytes
+pub struct BasemapCacheMaxBytes;
+
+impl Setting for BasemapCacheMaxBytes {
+    type Value = u64;
+    const KEY: &'static str = "basemap_cache_max_bytes";
+
+    fn default_value() -> u64 {
+        512 * 1024 * 1024
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if *value < 1024 * 1024 {
+            return Err("Basemap cache must be at least 1 MB".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Download rate each client may use for assets, in bytes per second; 0 is unlimited
+pub struct AssetBandwidthLimit;
+
+impl Setting for AssetBandwidthLimit {
+    type Value = u64;
+    const KEY: &'static str = "asset_bandwidth_limit";
+
+    fn default_value() -> u64 {
+        0
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if *value != 0 && *value < 64 * 1024 {
+            return Err("Asset bandwidth limit must be 0 (unlimited) or at least 64 KB/s".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Requests per minute each network client may make; 0 disables the limit
+pub struct RequestRateLimit;
+
+impl Setting for RequestRateLimit {
+    type Value = u32;
+    const KEY: &'static str = "request_rate_limit";
+
+    fn default_value() -> u32 {
+        0
+    }
+}
+
+/// Seconds GET responses are served from the in-memory response cache; 0
+/// disables the cache
+pub struct ResponseCacheSeconds;
+
+impl Setting for ResponseCacheSeconds {
+    type Value = u64;
+    const KEY: &'static str = "response_cache_seconds";
+
+    fn default_value() -> u64 {
+        0
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if *value > 3600 {
+            return Err("Response cache lifetime must be at most 3600 seconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Serves the transaction endpoints (create, update and delete of collections and items)
+pub struct EnableTransactions;
+
+impl Setting for EnableTransactions {
+    type Value = bool;
+    const KEY: &'static str = "enable_transactions";
+
+    fn default_value() -> bool {
+        true
+    }
+}
+
+/// Serves the asset upload endpoint
+pub struct EnableUploads;
+
+impl Setting for EnableUploads {
+    type Value = bool;
+    const KEY: &'static str = "enable_uploads";
+
+    fn default_value() -> bool {
+        true
+    }
+}
+
+/// Serves item search and the sortables endpoints
+pub struct EnableSearch;
+
+impl Setting for EnableSearch {
+    type Value = bool;
+    const KEY: &'static str = "enable_search";
+
+    fn default_value() -> bool {
+        true
+    }
+}
+
+/// STUN server (`host:port`) used to discover the public address suggested
+/// for `server_external_address`. Empty disables the lookup.
+pub struct StunServer;
+
+impl Setting for StunServer {
+    type Value = String;
+    const KEY: &'static str = "stun_server";
+
+    fn default_value() -> String {
+        "stun.l.google.com:19302".to_string()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if !value.is_empty() && !value.contains(':') {
+            return Err("STUN server must be given as host:port".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// How ids are assigned to collections and items created without one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdStrategy {
+    /// Reject the request with 400
+    Reject,
+    /// Time-ordered UUID (RFC 9562 version 7)
+    UuidV7,
+    /// Lexicographically sortable ULID
+    Ulid,
+    /// Slug of the title followed by the datetime, e.g. `harbour-survey-20240501t120000z`
+    Slug,
+}
+
+/// Strategy used to generate ids when clients omit them on create
+pub struct IdGeneration;
+
+impl Setting for IdGeneration {
+    type Value = IdStrategy;
+    const KEY: &'static str = "id_strategy";
+
+    fn default_value() -> IdStrategy {
+        IdStrategy::UuidV7
+    }
+}
+
+/// Executable run with the item JSON on stdin when items are created or
+/// updated; it may print properties to add. Empty disables the hook.
+pub struct IngestHookCommand;
+
+impl Setting for IngestHookCommand {
+    type Value = String;
+    const KEY: &'static str = "ingest_hook_command";
+
+    fn default_value() -> String {
+        String::new()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if !value.is_empty() && !std::path::Path::new(value).is_file() {
+            return Err(format!("Ingest hook '{}' does not exist", value));
+        }
+        Ok(())
+    }
+}
+
+/// Seconds an ingest hook may run before it is killed
+pub struct IngestHookTimeoutSecs;
+
+impl Setting for IngestHookTimeoutSecs {
+    type Value = u64;
+    const KEY: &'static str = "ingest_hook_timeout_secs";
+
+    fn default_value() -> u64 {
+        10
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if !(1..=300).contains(value) {
+            return Err("Ingest hook timeout must be between 1 and 300 seconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Executable run with the path of every uploaded file; a non-zero exit
+/// quarantines the file. Empty disables scanning.
+pub struct UploadScanCommand;
+
+impl Setting for UploadScanCommand {
+    type Value = String;
+    const KEY: &'static str = "upload_scan_command";
+
+    fn default_value() -> String {
+        String::new()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if !value.is_empty() && !std::path::Path::new(value).is_file() {
+            return Err(format!("Upload scanner '{}' does not exist", value));
+        }
+        Ok(())
+    }
+}
+
+/// Seconds an upload scan may run before the file is quarantined unscanned
+pub struct UploadScanTimeoutSecs;
+
+impl Setting for UploadScanTimeoutSecs {
+    type Value = u64;
+    const KEY: &'static str = "upload_scan_timeout_secs";
+
+    fn default_value() -> u64 {
+        120
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if !(1..=3600).contains(value) {
+            return Err("Upload scan timeout must be between 1 and 3600 seconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Most items a search may materialize. Larger unsorted searches are served
+/// in pages of this size; larger sorted searches are refused.
+pub struct SearchMaxResults;
+
+impl Setting for SearchMaxResults {
+    type Value = u64;
+    const KEY: &'static str = "search_max_results";
+
+    fn default_value() -> u64 {
+        10_000
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if !(100..=1_000_000).contains(value) {
+            return Err("Search result limit must be between 100 and 1000000".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Persistent identifier of this installation in peer sync revision vectors;
+/// generated on first sync
+pub struct SyncReplicaId;
+
+impl Setting for SyncReplicaId {
+    type Value = String;
+    const KEY: &'static str = "sync_replica_id";
+
+    fn default_value() -> String {
+        String::new()
+    }
+}
+
+/// Identifier of this catalog in the OS keyring: its secrets are stored under
+/// the service `zenstac:<id>`. Generated when the first secret is stored.
+pub struct SecretsKeyringId;
+
+impl Setting for SecretsKeyringId {
+    type Value = String;
+    const KEY: &'static str = "secrets_keyring_id";
+
+    fn default_value() -> String {
+        String::new()
+    }
+}
+
+/// Deleting a collection or item that would remove more asset data than this
+/// many bytes needs `confirm=true`
+pub struct DeleteConfirmThresholdBytes;
+
+impl Setting for DeleteConfirmThresholdBytes {
+    type Value = u64;
+    const KEY: &'static str = "delete_confirm_threshold_bytes";
+
+    fn default_value() -> u64 {
+        1024 * 1024 * 1024
+    }
+}
+
+/// Minutes between scheduled runs of the collection retention policies
+pub struct RetentionIntervalMinutes;
+
+impl Setting for RetentionIntervalMinutes {
+    type Value = u64;
+    const KEY: &'static str = "retention_interval_minutes";
+
+    fn default_value() -> u64 {
+        60
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if !(5..=10_080).contains(value) {
+            return Err("Retention interval must be between 5 minutes and 7 days".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Days deleted collections, items and asset folders stay in the trash
+/// before the retention scheduler purges them
+pub struct TrashRetentionDays;
+
+impl Setting for TrashRetentionDays {
+    type Value = u32;
+    const KEY: &'static str = "trash_retention_days";
+
+    fn default_value() -> u32 {
+        30
+    }
+
+    fn validate(value: &u32) -> Result<(), String> {
+        if *value > 3650 {
+            return Err("Trash retention must be at most 3650 days".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Hours a chunked upload session may go without receiving a chunk before
+/// the retention scheduler discards it and its partial file
+pub struct UploadSessionExpiryHours;
+
+impl Setting for UploadSessionExpiryHours {
+    type Value = u32;
+    const KEY: &'static str = "upload_session_expiry_hours";
+
+    fn default_value() -> u32 {
+        168
+    }
+
+    fn validate(value: &u32) -> Result<(), String> {
+        if !(1..=8760).contains(value) {
+            return Err("Upload session expiry must be between 1 and 8760 hours".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Hours between runs of the catalog check, which requests every external
+/// asset href and link and validates stored records; 0 disables it
+pub struct CatalogCheckIntervalHours;
+
+impl Setting for CatalogCheckIntervalHours {
+    type Value = u32;
+    const KEY: &'static str = "catalog_check_interval_hours";
+
+    fn default_value() -> u32 {
+        24
+    }
+
+    fn validate(value: &u32) -> Result<(), String> {
+        if *value > 720 {
+            return Err("Catalog check interval must be 0 (disabled) or up to 720 hours".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// How per-item access statistics appear in STAC item output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatsOutput {
+    /// Not exposed
+    Off,
+    /// `zenstac:downloads` property with the item's asset download count
+    Property,
+    /// `stats` link to the item's access statistics
+    Link,
+}
+
+/// Whether items expose their access statistics to API clients (opt-in)
+pub struct ItemAccessStats;
+
+impl Setting for ItemAccessStats {
+    type Value = ItemStatsOutput;
+    const KEY: &'static str = "item_access_stats";
+
+    fn default_value() -> ItemStatsOutput {
+        ItemStatsOutput::Off
+    }
+}
+
+/// How incoming items that are not quite conformant are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestMode {
+    /// Rejected with a list of the problems
+    Strict,
+    /// Coerced where the intent is clear, with each fix-up reported as a
+    /// warning
+    Lenient,
+}
+
+/// Whether item create and update reject or repair non-conformant items
+pub struct ItemIngestMode;
+
+impl Setting for ItemIngestMode {
+    type Value = IngestMode;
+    const KEY: &'static str = "ingest_mode";
+
+    fn default_value() -> IngestMode {
+        IngestMode::Lenient
+    }
+}
+
+/// Keeps the values of date-time properties rewritten to UTC on write under
+/// `zenstac:original_datetimes`
+pub struct PreserveOriginalDatetimes;
+
+impl Setting for PreserveOriginalDatetimes {
+    type Value = bool;
+    const KEY: &'static str = "preserve_original_datetimes";
+
+    fn default_value() -> bool {
+        false
+    }
+}
+
+/// Language the titles and descriptions of collections and items are written
+/// in; translations into other languages are negotiated with `Accept-Language`
+pub struct MetadataLanguage;
+
+impl Setting for MetadataLanguage {
+    type Value = String;
+    const KEY: &'static str = "metadata_language";
+
+    fn default_value() -> String {
+        "en".to_string()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if !crate::database::translations::is_language_tag(value) {
+            return Err(format!(
+                "'{}' is not a language tag such as en or pt-BR",
+                value
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Background jobs a queue runs at once, by queue name (`export`,
+/// `trash_purge`); queues not listed run two
+pub struct JobConcurrency;
+
+impl Setting for JobConcurrency {
+    type Value = BTreeMap<String, usize>;
+    const KEY: &'static str = "job_concurrency";
+
+    fn default_value() -> BTreeMap<String, usize> {
+        BTreeMap::new()
+    }
+
+    fn validate(value: &BTreeMap<String, usize>) -> Result<(), String> {
+        if let Some((queue, _)) = value.iter().find(|(_, limit)| **limit == 0) {
+            return Err(format!("Queue '{}' must run at least one job", queue));
+        }
+        Ok(())
+    }
+}
+
+/// Jobs of one collection a queue runs at once, so a bulk operation on one
+/// collection leaves slots for the others
+pub struct JobCollectionConcurrency;
+
+impl Setting for JobCollectionConcurrency {
+    type Value = usize;
+    const KEY: &'static str = "job_collection_concurrency";
+
+    fn default_value() -> usize {
+        1
+    }
+
+    fn validate(value: &usize) -> Result<(), String> {
+        if *value == 0 {
+            return Err("At least one job per collection must be allowed".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Where browsers landing on the catalog root are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RootRedirectTarget {
+    /// No redirect; browsers get the catalog JSON like any client
+    Catalog,
+    /// The STAC browser at `browser_url`
+    Browser,
+    /// The HTML API documentation page
+    Landing,
+}
+
+/// Whether HTML requests for `/` and the API root are redirected
+pub struct RootRedirect;
+
+impl Setting for RootRedirect {
+    type Value = RootRedirectTarget;
+    const KEY: &'static str = "root_redirect";
+
+    fn default_value() -> RootRedirectTarget {
+        RootRedirectTarget::Catalog
+    }
+}
+
+/// Location of the STAC browser used by the `browser` root redirect, a path
+/// on this server or an absolute URL
+pub struct BrowserUrl;
+
+impl Setting for BrowserUrl {
+    type Value = String;
+    const KEY: &'static str = "browser_url";
+
+    fn default_value() -> String {
+        "/browser".to_string()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        let valid = value.starts_with('/')
+            || value.starts_with("http://")
+            || value.starts_with("https://");
+        if !valid || value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(format!(
+                "'{}' is not a path starting with / or an http(s) URL",
+                value
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Media types for asset file extensions, added to or replacing the built-in
+/// ones, e.g. `{"nc": "application/x-netcdf"}`. Keys are lowercase
+/// extensions without the leading dot and may span several suffixes
+/// (`cog.tif`).
+pub struct MediaTypeOverrides;
+
+impl Setting for MediaTypeOverrides {
+    type Value = BTreeMap<String, String>;
+    const KEY: &'static str = "media_types";
+
+    fn default_value() -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    fn validate(value: &BTreeMap<String, String>) -> Result<(), String> {
+        for (extension, media_type) in value {
+            if extension.is_empty()
+                || extension.starts_with('.')
+                || extension.ends_with('.')
+                || !extension.chars().all(|c| {
+                    c.is_ascii_lowercase() || c.is_ascii_digit() || c == '.' || c == '-' || c == '_'
+                })
+            {
+                return Err(format!(
+                    "'{}' is not a lowercase file extension without the leading dot",
+                    extension
+                ));
+            }
+            let essence = media_type.split(';').next().unwrap_or("").trim();
+            if !essence.contains('/')
+                || !media_type.chars().all(|c| c.is_ascii_graphic() || c == ' ')
+            {
+                return Err(format!("'{}' is not a media type", media_type));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Remote STAC APIs searched alongside the local catalog on `/search`, by
+/// name, e.g. `{"earth-search": "https://earth-search.aws.element84.com/v1"}`.
+/// Names tag the results they return; URLs are API roots and may reference
+/// secrets, e.g. for an API key in the query string.
+pub struct FederatedCatalogs;
+
+impl Setting for FederatedCatalogs {
+    type Value = BTreeMap<String, String>;
+    const KEY: &'static str = "federated_catalogs";
+
+    fn default_value() -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    fn validate(value: &BTreeMap<String, String>) -> Result<(), String> {
+        for (name, url) in value {
+            if name.is_empty()
+                || name == "local"
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+            {
+                return Err(format!(
+                    "'{}' is not a catalog name of lowercase letters, digits, - and _ other than 'local'",
+                    name
+                ));
+            }
+            if !(url.starts_with("http://") || url.starts_with("https://"))
+                || url.chars().any(|c| c.is_whitespace() || c.is_control())
+            {
+                return Err(format!("'{}' is not an http(s) URL", url));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Seconds a federated search waits for each remote catalog before leaving
+/// its results out
+pub struct FederationTimeoutSecs;
+
+impl Setting for FederationTimeoutSecs {
+    type Value = u64;
+    const KEY: &'static str = "federation_timeout_secs";
+
+    fn default_value() -> u64 {
+        10
+    }
+
+    fn validate(value: &u64) -> Result<(), String> {
+        if !(1..=120).contains(value) {
+            return Err("Federation timeout must be between 1 and 120 seconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Proxy for outbound HTTP requests (basemap tiles, peer sync, federated
+/// search). Empty uses the system proxy from the
+/// `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY` environment variables, `none`
+/// connects directly, anything else is a proxy URL which may reference a
+/// secret for its credentials, e.g.
+/// `http://user:${secret:proxy-password}@proxy.example.com:3128`.
+pub struct OutboundProxy;
+
+impl Setting for OutboundProxy {
+    type Value = String;
+    const KEY: &'static str = "outbound_proxy";
+
+    fn default_value() -> String {
+        String::new()
+    }
+
+    fn validate(value: &String) -> Result<(), String> {
+        if value.is_empty() || value == "none" {
+            return Ok(());
+        }
+        if !value.starts_with("http://") && !value.starts_with("https://") {
+            return Err("Proxy must be empty, \"none\" or an http:// or https:// URL".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Comma-separated hosts, domains (`.example.com`) and CIDR ranges reached
+/// without the outbound proxy
+pub struct OutboundNoProxy;
+
+impl Setting for OutboundNoProxy {
+    type Value = String;
+    const KEY: &'static str = "outbound_no_proxy";
+
+    fn default_value() -> String {
+        "localhost,127.0.0.1,::1".to_string()
+    }
+}
+
+/// Validates a raw value for `key` if it belongs to a typed setting.
+/// Unknown keys are free-form user preferences and are accepted as-is.
+fn validate_raw(key: &str, raw: &str) -> Result<u32, SettingsError> {
+    match key {
+        ServerInternalAddress::KEY => check_raw::<ServerInternalAddress>(raw),
+        ServerExternalAddress::KEY => check_raw::<ServerExternalAddress>(raw),
+        ServerPort::KEY => check_raw::<ServerPort>(raw),
+        AcmeDomain::KEY => check_raw::<AcmeDomain>(raw),
+        AcmeEmail::KEY => check_raw::<AcmeEmail>(raw),
+        AcmeDirectoryUrl::KEY => check_raw::<AcmeDirectoryUrl>(raw),
+        AcmeChallengePort::KEY => check_raw::<AcmeChallengePort>(raw),
+        HttpsPort::KEY => check_raw::<HttpsPort>(raw),
+        FirstInstallationComplete::KEY => check_raw::<FirstInstallationComplete>(raw),
+        LandingChildLinkLimit::KEY => check_raw::<LandingChildLinkLimit>(raw),
+        MaxJsonBodyBytes::KEY => check_raw::<MaxJsonBodyBytes>(raw),
+        MaxUploadBodyBytes::KEY => check_raw::<MaxUploadBodyBytes>(raw),
+        BasemapUpstreamUrl::KEY => check_raw::<BasemapUpstreamUrl>(raw),
+        BasemapCacheMaxBytes::KEY => check_raw::<BasemapCacheMaxBytes>(raw),
+        AssetBandwidthLimit::KEY => check_raw::<AssetBandwidthLimit>(raw),
+        RequestRateLimit::KEY => check_raw::<RequestRateLimit>(raw),
+        ResponseCacheSeconds::KEY => check_raw::<ResponseCacheSeconds>(raw),
+        EnableTransactions::KEY => check_raw::<EnableTransactions>(raw),
+        EnableUploads::KEY => check_raw::<EnableUploads>(raw),
+        EnableSearch::KEY => check_raw::<EnableSearch>(raw),
+        StunServer::KEY => check_raw::<StunServer>(raw),
+        IdGeneration::KEY => check_raw::<IdGeneration>(raw),
+        IngestHookCommand::KEY => check_raw::<IngestHookCommand>(raw),
+        IngestHookTimeoutSecs::KEY => check_raw::<IngestHookTimeoutSecs>(raw),
+        UploadScanCommand::KEY => check_raw::<UploadScanCommand>(raw),
+        UploadScanTimeoutSecs::KEY => check_raw::<UploadScanTimeoutSecs>(raw),
+        SearchMaxResults::KEY => check_raw::<SearchMaxResults>(raw),
+        SyncReplicaId::KEY => check_raw::<SyncReplicaId>(raw),
+        SecretsKeyringId::KEY => check_raw::<SecretsKeyringId>(raw),
+        DeleteConfirmThresholdBytes::KEY => check_raw::<DeleteConfirmThresholdBytes>(raw),
+        RetentionIntervalMinutes::KEY => check_raw::<RetentionIntervalMinutes>(raw),
+        TrashRetentionDays::KEY => check_raw::<TrashRetentionDays>(raw),
+        UploadSessionExpiryHours::KEY => check_raw::<UploadSessionExpiryHours>(raw),
+        CatalogCheckIntervalHours::KEY => check_raw::<CatalogCheckIntervalHours>(raw),
+        ItemAccessStats::KEY => check_raw::<ItemAccessStats>(raw),
+        ItemIngestMode::KEY => check_raw::<ItemIngestMode>(raw),
+        PreserveOriginalDatetimes::KEY => check_raw::<PreserveOriginalDatetimes>(raw),
+        MediaTypeOverrides::KEY => check_raw::<MediaTypeOverrides>(raw),
+        MetadataLanguage::KEY => check_raw::<MetadataLanguage>(raw),
+        OutboundProxy::KEY => check_raw::<OutboundProxy>(raw),
+        OutboundNoProxy::KEY => check_raw::<OutboundNoProxy>(raw),
+        FederatedCatalogs::KEY => check_raw::<FederatedCatalogs>(raw),
+        FederationTimeoutSecs::KEY => check_raw::<FederationTimeoutSecs>(raw),
+        RootRedirect::KEY => check_raw::<RootRedirect>(raw),
+        JobConcurrency::KEY => check_raw::<JobConcurrency>(raw),
+        JobCollectionConcurrency::KEY => check_raw::<JobCollectionConcurrency>(raw),
+        BrowserUrl::KEY => check_raw::<BrowserUrl>(raw),
+        _ => Ok(1),
+    }
+}
+
+fn check_raw<S: Setting>(raw: &str) -> Result<u32, SettingsError> {
+    let value: S::Value = decode_value(raw).ok_or_else(|| SettingsError::Invalid {
+        key: S::KEY.to_string(),
+        reason: format!("'{}' is not a valid value", raw),
+    })?;
+    S::validate(&value).map_err(|reason| SettingsError::Invalid {
+        key: S::KEY.to_string(),
+        reason,
+    })?;
+    Ok(S::VERSION)
+}
+
+fn encode_value<T: Serialize>(value: &T) -> Result<String, SettingsError> {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => Ok(s),
+        Ok(other) => Ok(other.to_string()),
+        Err(e) => Err(SettingsError::Serialization(e.to_string())),
+    }
+}
+
+fn decode_value<T: DeserializeOwned>(raw: &str) -> Option<T> {
+    serde_json::from_str(raw)
+        .ok()
+        .or_else(|| serde_json::from_value(serde_json::Value::String(raw.to_string())).ok())
+}
+
+/// Errors returned when reading or writing settings
+#[derive(Debug)]
+pub enum SettingsError {
+    Database(rusqlite::Error),
+    Invalid { key: String, reason: String },
+    Serialization(String),
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Database(e) => write!(f, "Settings database error: {}", e),
+            SettingsError::Invalid { key, reason } => {
+                write!(f, "Invalid value for setting '{}': {}", key, reason)
+            }
+            SettingsError::Serialization(e) => write!(f, "Failed to encode setting: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<rusqlite::Error> for SettingsError {
+    fn from(e: rusqlite::Error) -> Self {
+        SettingsError::Database(e)
+    }
+}
+
+/// Broadcast whenever a setting is written
+#[derive(Debug, Clone)]
+pub struct SettingChange {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Clone)]
+pub struct SettingsRepository {
+    db: DatabaseConnection,
+    changes: broadcast::Sender<SettingChange>,
+}
+
+impl SettingsRepository {
+    /// Creates a new settings repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        let (changes, _) = broadcast::channel(64);
+        Self { db, changes }
+    }
+
+    /// Subscribes to setting change events
+    pub fn subscribe(&self) -> broadcast::Receiver<SettingChange> {
+        self.changes.subscribe()
+    }
+
+    /// Gets a typed setting, falling back to its default
+    pub async fn get<S: Setting>(&self) -> Result<S::Value, SettingsError> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            load::<S>(&conn)
+        })
+    }
+
+    /// Validates and stores a typed setting
+    pub async fn set<S: Setting>(&self, value: &S::Value) -> Result<(), SettingsError> {
+        S::validate(value).map_err(|reason| SettingsError::Invalid {
+            key: S::KEY.to_string(),
+            reason,
+        })?;
+        let raw = encode_value(value)?;
+        self.write(vec![(S::KEY.to_string(), raw)]).await
+    }
+
+    /// Gets the raw stored text of any setting or user preference
+    pub async fn get_raw(&self, key: &str) -> Result<Option<String>, SettingsError> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            read_raw(&conn, key)
+        })
+    }
+
+    /// Stores a raw value, validating it if the key is a typed setting
+    pub async fn set_raw(&self, key: &str, value: &str) -> Result<(), SettingsError> {
+        self.write(vec![(key.to_string(), value.to_string())]).await
+    }
+
+    /// Validates every entry and writes them in a single transaction, so a
+    /// crash or a rejected value never leaves a half-applied configuration
+    pub async fn set_many(&self, entries: Vec<(String, String)>) -> Result<(), SettingsError> {
+        self.write(entries).await
+    }
+
+    async fn write(&self, entries: Vec<(String, String)>) -> Result<(), SettingsError> {
+        {
+            let mut conn = self.db.get_connection().await;
+            blocking(|| store_raw(&mut conn, &entries))?;
+        }
+
+        for (key, value) in entries {
+            // No receivers is not an error
+            let _ = self.changes.send(SettingChange { key, value });
+        }
+        Ok(())
+    }
+}
+
+/// Validates and writes raw setting values in one immediate transaction
+pub fn store_raw(conn: &mut Connection, entries: &[(String, String)]) -> Result<(), SettingsError> {
+    let mut versions = Vec::with_capacity(entries.len());
+    for (key, value) in entries {
+        versions.push(validate_raw(key, value)?);
+    }
+
+    let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+    for ((key, value), version) in entries.iter().zip(versions) {
+        tx.execute(
+            "INSERT OR REPLACE INTO application_settings (key, value, version, updated_at) VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)",
+            (key, value, version),
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Parses a raw stored value as the type of setting `S`
+pub fn parse<S: Setting>(raw: &str) -> Option<S::Value> {
+    decode_value(raw)
+}
+
+/// Reads the raw stored text of a setting from an open connection
+pub fn read_raw(conn: &Connection, key: &str) -> Result<Option<String>, SettingsError> {
+    Ok(read_row(conn, key)?.map(|(value, _)| value))
+}
+
+/// Reads a typed setting from an open connection.
+/// Used by synchronous callers such as configuration loading.
+pub fn load<S: Setting>(conn: &Connection) -> Result<S::Value, SettingsError> {
+    let value = match read_row(conn, S::KEY)? {
+        Some((raw, version)) if version < S::VERSION => S::migrate(version, &raw),
+        Some((raw, _)) => decode_value(&raw),
+        None => None,
+    };
+    Ok(value.unwrap_or_else(S::default_value))
+}
+
+fn read_row(conn: &Connection, key: &str) -> Result<Option<(String, u32)>, rusqlite::Error> {
+    conn.query_row(
+        "SELECT value, version FROM application_settings WHERE key = ?1",
+        [key],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?)),
+    )
+    .optional()
+}
@@ -0,0 +1,91 @@
+use rusqlite::{Connection, Result};
+
+/// Creates the R*Tree of item bounding boxes, keyed by the rowid of the
+/// item. Returns whether it was created, in which case it still has to be
+/// filled.
+pub(crate) fn create_index(conn: &Connection) -> Result<bool> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'item_rtree')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute(
+            "CREATE VIRTUAL TABLE item_rtree USING rtree(id, min_x, max_x, min_y, max_y)",
+            [],
+        )?;
+    }
+    Ok(!exists)
+}
+
+/// Boxes of the live items selected by `condition`. R*Tree rejects a
+/// minimum above its maximum, so a box crossing the antimeridian (west edge
+/// above east edge) is indexed at the full width of -180 to 180; the box
+/// stays a superset of the stored columns, which searches check exactly
+/// afterwards.
+fn insert_sql(condition: &str) -> String {
+    format!(
+        "INSERT INTO item_rtree (id, min_x, max_x, min_y, max_y)
+         SELECT rowid,
+            CASE WHEN bbox_min_x > bbox_max_x THEN -180 ELSE bbox_min_x END,
+            CASE WHEN bbox_min_x > bbox_max_x THEN 180 ELSE bbox_max_x END,
+            min(bbox_min_y, bbox_max_y), max(bbox_min_y, bbox_max_y)
+         FROM items
+         WHERE deleted_at IS NULL AND bbox_min_x IS NOT NULL AND bbox_min_y IS NOT NULL
+            AND bbox_max_x IS NOT NULL AND bbox_max_y IS NOT NULL{}",
+        condition
+    )
+}
+
+/// Indexes the box of every item outside the trash, replacing what was
+/// indexed. Needed whenever item rowids may have changed, as after
+/// `VACUUM INTO`.
+pub(crate) fn rebuild_index(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM item_rtree", [])?;
+    tx.execute(&insert_sql(""), [])?;
+    tx.commit()
+}
+
+/// Indexes the box of an item, or with `None` of all items of a collection,
+/// replacing their previous entries
+pub(crate) fn index_items(
+    conn: &Connection,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> Result<()> {
+    remove(conn, collection_id, item_id)?;
+    match item_id {
+        Some(item_id) => conn.execute(
+            &insert_sql(" AND collection_id = ? AND id = ?"),
+            [collection_id, item_id],
+        )?,
+        None => conn.execute(&insert_sql(" AND collection_id = ?"), [collection_id])?,
+    };
+    Ok(())
+}
+
+/// Removes the box of an item, or with `None` of all items of a collection,
+/// from the index
+pub(crate) fn remove(conn: &Connection, collection_id: &str, item_id: Option<&str>) -> Result<()> {
+    match item_id {
+        Some(item_id) => conn.execute(
+            "DELETE FROM item_rtree WHERE id IN
+             (SELECT rowid FROM items WHERE collection_id = ? AND id = ?)",
+            [collection_id, item_id],
+        )?,
+        None => conn.execute(
+            "DELETE FROM item_rtree WHERE id IN (SELECT rowid FROM items WHERE collection_id = ?)",
+            [collection_id],
+        )?,
+    };
+    Ok(())
+}
+
+/// Opens a database file written by `VACUUM INTO` and rebuilds its index,
+/// since the copy may number item rows differently
+pub(crate) fn rebuild_copy(path: &std::path::Path) -> Result<()> {
+    let conn = Connection::open(path)?;
+    create_index(&conn)?;
+    rebuild_index(&conn)
+}
@@ -0,0 +1,220 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::{OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// Record a revision belongs to: a collection (`item_id` empty) or an item
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RecordKey {
+    pub collection_id: String,
+    #[serde(default)]
+    pub item_id: String,
+}
+
+impl RecordKey {
+    pub fn collection(collection_id: &str) -> Self {
+        Self {
+            collection_id: collection_id.to_string(),
+            item_id: String::new(),
+        }
+    }
+
+    pub fn item(collection_id: &str, item_id: &str) -> Self {
+        Self {
+            collection_id: collection_id.to_string(),
+            item_id: item_id.to_string(),
+        }
+    }
+
+    pub fn is_collection(&self) -> bool {
+        self.item_id.is_empty()
+    }
+}
+
+/// Revision vector: number of changes each replica made to a record
+pub type RevisionVector = BTreeMap<String, u64>;
+
+/// How two revision vectors of a record relate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Causality {
+    Equal,
+    /// The first has seen every change of the second and more
+    Newer,
+    /// The second has seen every change of the first and more
+    Older,
+    /// Both have changes the other hasn't seen
+    Concurrent,
+}
+
+pub fn compare_vectors(a: &RevisionVector, b: &RevisionVector) -> Causality {
+    let mut a_ahead = false;
+    let mut b_ahead = false;
+    for replica in a.keys().chain(b.keys()) {
+        let x = a.get(replica).copied().unwrap_or(0);
+        let y = b.get(replica).copied().unwrap_or(0);
+        match x.cmp(&y) {
+            Ordering::Greater => a_ahead = true,
+            Ordering::Less => b_ahead = true,
+            Ordering::Equal => {}
+        }
+    }
+    match (a_ahead, b_ahead) {
+        (false, false) => Causality::Equal,
+        (true, false) => Causality::Newer,
+        (false, true) => Causality::Older,
+        (true, true) => Causality::Concurrent,
+    }
+}
+
+/// Entry-wise maximum of two vectors
+pub fn merge_vectors(a: &RevisionVector, b: &RevisionVector) -> RevisionVector {
+    let mut merged = a.clone();
+    for (replica, count) in b {
+        let entry = merged.entry(replica.clone()).or_insert(0);
+        *entry = (*entry).max(*count);
+    }
+    merged
+}
+
+/// Hash of a record's STAC content: SHA-256 of its canonical JSON, in hex.
+/// Timestamps are left out so a record copied between catalogs compares
+/// equal. Hashes are stored and compared across builds and peers, so the
+/// algorithm must never depend on the toolchain.
+pub fn content_hash<T: Serialize>(record: &T) -> String {
+    let mut value = serde_json::to_value(record).unwrap_or(serde_json::Value::Null);
+    if let Some(object) = value.as_object_mut() {
+        object.remove("created_at");
+        object.remove("updated_at");
+    }
+    let digest = Sha256::digest(sorted_keys(value).to_string().as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether `hash` was made by [`content_hash`]. Peers from before content
+/// hashes were SHA-256 send hashes no other build can reproduce.
+pub fn is_content_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Same value with object keys in order, so key order never affects the hash
+fn sorted_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => {
+            let mut entries: Vec<(String, serde_json::Value)> = object.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sorted_keys(value)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(values) => {
+            serde_json::Value::Array(values.into_iter().map(sorted_keys).collect())
+        }
+        other => other,
+    }
+}
+
+/// Sync state of one record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    #[serde(flatten)]
+    pub key: RecordKey,
+    pub vector: RevisionVector,
+    /// Content hash of the record when the vector was last bumped
+    pub hash: String,
+    /// The record was deleted; kept as a tombstone so the deletion syncs
+    pub deleted: bool,
+}
+
+#[derive(Clone)]
+pub struct SyncRevisionRepository {
+    db: DatabaseConnection,
+}
+
+impl SyncRevisionRepository {
+    /// Creates a new sync revision repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    pub async fn get_all(&self) -> Result<Vec<Revision>> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT collection_id, item_id, vector, hash, deleted FROM sync_revisions ORDER BY collection_id, item_id",
+            )?;
+            let rows = stmt.query_map([], row_to_revision)?;
+            rows.collect()
+        })
+    }
+
+    pub async fn get(&self, key: &RecordKey) -> Result<Option<Revision>> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.query_row(
+                "SELECT collection_id, item_id, vector, hash, deleted FROM sync_revisions WHERE collection_id = ? AND item_id = ?",
+                [&key.collection_id, &key.item_id],
+                row_to_revision,
+            )
+            .optional()
+        })
+    }
+
+    pub async fn upsert(&self, revision: &Revision) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let vector = serde_json::to_string(&revision.vector)
+                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+            conn.execute(
+                "INSERT OR REPLACE INTO sync_revisions (collection_id, item_id, vector, hash, deleted) VALUES (?, ?, ?, ?, ?)",
+                rusqlite::params![
+                    revision.key.collection_id,
+                    revision.key.item_id,
+                    vector,
+                    revision.hash,
+                    revision.deleted
+                ],
+            )?;
+            Ok(())
+        })
+    }
+}
+
+fn row_to_revision(row: &rusqlite::Row) -> Result<Revision> {
+    let vector: String = row.get(2)?;
+    Ok(Revision {
+        key: RecordKey {
+            collection_id: row.get(0)?,
+            item_id: row.get(1)?,
+        },
+        vector: serde_json::from_str(&vector)
+            .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?,
+        hash: row.get(3)?,
+        deleted: row.get(4)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_hash_is_sha256_of_canonical_json() {
+        let record = serde_json::json!({
+            "b": [1, { "d": null, "c": true }],
+            "a": 2,
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-06-01T00:00:00Z"
+        });
+        // SHA-256 of {"a":2,"b":[1,{"c":true,"d":null}]}
+        assert_eq!(
+            content_hash(&record),
+            "f4edd77a6fe12972c2a508f8b1df32c82e1706fde16bc0689254d26604ef23f3"
+        );
+    }
+}
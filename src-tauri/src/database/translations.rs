@@ -0,0 +1,158 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::{OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Title and description of a collection or item in one language
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Translation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Translations of a collection or item, keyed by language tag (`fr`, `de-CH`)
+pub type Translations = BTreeMap<String, Translation>;
+
+/// Whether `tag` looks like a BCP 47 language tag: a 2-3 letter language
+/// followed by subtags of 1-8 letters or digits (`en`, `pt-BR`, `zh-Hant-TW`)
+pub fn is_language_tag(tag: &str) -> bool {
+    let mut parts = tag.split('-');
+    let language = parts.next().unwrap_or("");
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|part| {
+            (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Language-tagged titles and descriptions, stored apart from the STAC
+/// documents so every representation can be served from one record. Rows
+/// for collections have an empty `item_id`.
+#[derive(Clone)]
+pub struct TranslationRepository {
+    db: DatabaseConnection,
+}
+
+impl TranslationRepository {
+    /// Creates a new translation repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Translations of a collection, or of an item when `item_id` is given
+    pub async fn get(&self, collection_id: &str, item_id: Option<&str>) -> Result<Translations> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let data: Option<String> = conn
+                .query_row(
+                    "SELECT data FROM translations WHERE collection_id = ? AND item_id = ?",
+                    [collection_id, item_id.unwrap_or("")],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(data
+                .and_then(|data| serde_json::from_str(&data).ok())
+                .unwrap_or_default())
+        })
+    }
+
+    /// Replaces the translations of a collection or item; an empty set
+    /// removes them
+    pub async fn set(
+        &self,
+        collection_id: &str,
+        item_id: Option<&str>,
+        translations: &Translations,
+    ) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            if translations.is_empty() {
+                conn.execute(
+                    "DELETE FROM translations WHERE collection_id = ? AND item_id = ?",
+                    [collection_id, item_id.unwrap_or("")],
+                )?;
+                return Ok(());
+            }
+            let data = serde_json::to_string(translations)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            conn.execute(
+                "INSERT OR REPLACE INTO translations (collection_id, item_id, data, updated_at) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![
+                    collection_id,
+                    item_id.unwrap_or(""),
+                    data,
+                    chrono::Utc::now().to_rfc3339()
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Translations of every collection that has any, by collection id
+    pub async fn for_collections(&self) -> Result<HashMap<String, Translations>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt =
+                conn.prepare_cached("SELECT collection_id, data FROM translations WHERE item_id = ''")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut translations = HashMap::new();
+            for row in rows {
+                let (collection_id, data) = row?;
+                if let Ok(parsed) = serde_json::from_str(&data) {
+                    translations.insert(collection_id, parsed);
+                }
+            }
+            Ok(translations)
+        })
+    }
+
+    /// Translations of the items of a collection, by item id
+    pub async fn for_items(&self, collection_id: &str) -> Result<HashMap<String, Translations>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT item_id, data FROM translations WHERE collection_id = ? AND item_id != ''",
+            )?;
+            let rows = stmt.query_map([collection_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            let mut translations = HashMap::new();
+            for row in rows {
+                let (item_id, data) = row?;
+                if let Ok(parsed) = serde_json::from_str(&data) {
+                    translations.insert(item_id, parsed);
+                }
+            }
+            Ok(translations)
+        })
+    }
+
+    /// Forgets the translations of a deleted item
+    pub async fn delete_for_item(&self, collection_id: &str, item_id: &str) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute(
+                "DELETE FROM translations WHERE collection_id = ? AND item_id = ?",
+                [collection_id, item_id],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Forgets the translations of a deleted collection and its items
+    pub async fn delete_for_collection(&self, collection_id: &str) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute(
+                "DELETE FROM translations WHERE collection_id = ?",
+                [collection_id],
+            )?;
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,172 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use rusqlite::{OptionalExtension, Result, Row};
+use serde::Serialize;
+
+/// A chunked upload in progress: the bytes received so far are in
+/// `temp_path`, and `crc32` is the running checksum over them
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadSession {
+    pub id: String,
+    pub collection_id: String,
+    pub item_id: String,
+    pub asset_key: String,
+    pub file_name: String,
+    pub content_type: String,
+    /// Size of the whole file in bytes
+    pub size: u64,
+    /// Bytes received so far; the next chunk starts here
+    pub offset: u64,
+    #[serde(skip)]
+    pub crc32: u32,
+    /// Expected CRC-32 of the whole file as 8 hex digits, checked on
+    /// completion when given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    #[serde(skip)]
+    pub temp_path: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const COLUMNS: &str = "id, collection_id, item_id, asset_key, file_name, content_type, size, received, crc32, checksum, temp_path, created_at, updated_at";
+
+fn from_row(row: &Row) -> Result<UploadSession> {
+    Ok(UploadSession {
+        id: row.get(0)?,
+        collection_id: row.get(1)?,
+        item_id: row.get(2)?,
+        asset_key: row.get(3)?,
+        file_name: row.get(4)?,
+        content_type: row.get(5)?,
+        size: row.get::<_, i64>(6)? as u64,
+        offset: row.get::<_, i64>(7)? as u64,
+        crc32: row.get::<_, i64>(8)? as u32,
+        checksum: row.get(9)?,
+        temp_path: row.get(10)?,
+        created_at: row.get(11)?,
+        updated_at: row.get(12)?,
+    })
+}
+
+/// Chunked upload sessions, kept in the database so an upload survives a
+/// restart of the app or the server
+#[derive(Clone)]
+pub struct UploadSessionRepository {
+    db: DatabaseConnection,
+}
+
+impl UploadSessionRepository {
+    /// Creates a new upload session repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Stores a new session
+    pub async fn create(&self, session: &UploadSession) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO upload_sessions ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                    COLUMNS
+                ),
+                rusqlite::params![
+                    session.id,
+                    session.collection_id,
+                    session.item_id,
+                    session.asset_key,
+                    session.file_name,
+                    session.content_type,
+                    session.size as i64,
+                    session.offset as i64,
+                    session.crc32 as i64,
+                    session.checksum,
+                    session.temp_path,
+                    session.created_at,
+                    session.updated_at
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Gets a session by id
+    pub async fn get(&self, id: &str) -> Result<Option<UploadSession>> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            conn.query_row(
+                &format!("SELECT {} FROM upload_sessions WHERE id = ?", COLUMNS),
+                [id],
+                from_row,
+            )
+            .optional()
+        })
+    }
+
+    /// Sessions uploading to an asset, oldest first
+    pub async fn list_for_asset(
+        &self,
+        collection_id: &str,
+        item_id: &str,
+        asset_key: &str,
+    ) -> Result<Vec<UploadSession>> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {} FROM upload_sessions WHERE collection_id = ? AND item_id = ? AND asset_key = ? ORDER BY created_at",
+                COLUMNS
+            ))?;
+            let rows = stmt.query_map([collection_id, item_id, asset_key], from_row)?;
+            rows.collect()
+        })
+    }
+
+    /// Records that the session now holds `offset` bytes with checksum
+    /// `crc32`, provided it still held `expected_offset`. Returns false when
+    /// another writer moved it meanwhile or it is gone.
+    pub async fn advance(
+        &self,
+        id: &str,
+        expected_offset: u64,
+        offset: u64,
+        crc32: u32,
+    ) -> Result<bool> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let updated = conn.execute(
+                "UPDATE upload_sessions SET received = ?1, crc32 = ?2, updated_at = ?3 WHERE id = ?4 AND received = ?5",
+                rusqlite::params![
+                    offset as i64,
+                    crc32 as i64,
+                    chrono::Utc::now().to_rfc3339(),
+                    id,
+                    expected_offset as i64
+                ],
+            )?;
+            Ok(updated > 0)
+        })
+    }
+
+    /// Deletes a session, returning whether it existed
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let deleted = conn.execute("DELETE FROM upload_sessions WHERE id = ?", [id])?;
+            Ok(deleted > 0)
+        })
+    }
+
+    /// Sessions that received nothing since `cutoff` (RFC 3339)
+    pub async fn expired(&self, cutoff: &str) -> Result<Vec<UploadSession>> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {} FROM upload_sessions WHERE updated_at < ?",
+                COLUMNS
+            ))?;
+            let rows = stmt.query_map([cutoff], from_row)?;
+            rows.collect()
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use crate::database::connection::blocking;
+use crate::database::DatabaseConnection;
+use crate::models::search::SearchBody;
+use rusqlite::Result;
+use serde::{Deserialize, Serialize};
+
+/// A read-only collection whose items are the result of a stored search,
+/// e.g. "cloud-free 2024 scenes" drawn from one or more real collections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VirtualCollection {
+    pub id: String,
+    pub title: Option<String>,
+    pub description: String,
+    pub keywords: Option<Vec<String>>,
+    pub license: Option<String>,
+    /// Search executed for the items (collections, bbox, datetime, ids, sortby, limit)
+    pub search: SearchBody,
+    #[serde(default)]
+    pub created_at: String,
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+#[derive(Clone)]
+pub struct VirtualCollectionRepository {
+    db: DatabaseConnection,
+}
+
+impl VirtualCollectionRepository {
+    /// Creates a new virtual collection repository
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Gets all virtual collections
+    pub async fn get_all(&self) -> Result<Vec<VirtualCollection>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached("SELECT data FROM virtual_collections ORDER BY id")?;
+            let rows = stmt.query_map([], |row| {
+                let data: String = row.get(0)?;
+                serde_json::from_str::<VirtualCollection>(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))
+            })?;
+
+            let mut collections = Vec::new();
+            for row in rows {
+                collections.push(row?);
+            }
+            Ok(collections)
+        })
+    }
+
+    /// Gets a virtual collection by ID
+    pub async fn get_by_id(&self, id: &str) -> Result<Option<VirtualCollection>> {
+        let conn = self.db.read_connection().await;
+        blocking(|| {
+            let mut stmt = conn.prepare_cached("SELECT data FROM virtual_collections WHERE id = ?")?;
+            let mut rows = stmt.query_map([id], |row| {
+                let data: String = row.get(0)?;
+                serde_json::from_str::<VirtualCollection>(&data)
+                    .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))
+            })?;
+
+            if let Some(row) = rows.next() {
+                Ok(Some(row?))
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    /// Creates or replaces a virtual collection
+    pub async fn upsert(&self, collection: &VirtualCollection) -> Result<()> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let data = serde_json::to_string(collection)
+                .map_err(|e| rusqlite::Error::InvalidParameterName(e.to_string()))?;
+            conn.execute(
+                "INSERT OR REPLACE INTO virtual_collections (id, data) VALUES (?1, ?2)",
+                rusqlite::params![collection.id, data],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Deletes a virtual collection, returning whether it existed
+    pub async fn delete(&self, id: &str) -> Result<bool> {
+        let conn = self.db.get_connection().await;
+        blocking(|| {
+            let deleted = conn.execute("DELETE FROM virtual_collections WHERE id = ?", [id])?;
+            Ok(deleted > 0)
+        })
+    }
+}
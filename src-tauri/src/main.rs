@@ -2,16 +2,21 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 #![allow(non_snake_case)]
 
+mod catalog_diff;
+mod cli;
 mod config;
 mod database;
 mod models;
 mod server;
+mod service_install;
 
 use config::Config;
+use database::settings::{
+    EnableSearch, EnableTransactions, EnableUploads, ServerExternalAddress, ServerInternalAddress,
+    ServerPort, Setting, StunServer,
+};
 use database::DatabaseService;
-use std::fs;
 use std::path::Path;
-use rusqlite;
 use std::sync::{Arc, Mutex};
 use tokio::task::JoinHandle;
 
@@ -37,14 +42,27 @@ impl ServerState {
 
 #[tokio::main]
 async fn main() {
+    // Run a CLI subcommand instead of the desktop app when one is given
+    let cli = <cli::Cli as clap::Parser>::parse();
+    if let Err(e) = cli::select_database(&cli) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    if let Some(command) = cli.command {
+        if let Err(e) = cli::run(command, cli.demo).await {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Load configuration with server settings from database
-    let config = Config::with_server_settings();
+    let mut config = Config::with_server_settings();
 
 
 
     // Create data directory if it doesn't exist
-    let config = Config::default();
-    if let Err(e) = std::fs::create_dir_all(&config.assets_dir()) {
+    if let Err(e) = tokio::fs::create_dir_all(&config.assets_dir()).await {
         eprintln!("Failed to create data directory: {}", e);
         std::process::exit(1);
     }
@@ -79,10 +97,61 @@ async fn main() {
         std::process::exit(1);
     }
 
+    // The demo catalog is served read-only, rate limited and cached
+    if cli.demo {
+        if let Err(e) = server::demo::apply_demo_preset(&db_service, &mut config).await {
+            eprintln!("Failed to apply the demo preset: {}", e);
+            std::process::exit(1);
+        }
+    }
+
     // Create server state
     let server_state = ServerState::new(config.clone(), db_service.clone());
     let server_state_for_tauri = server_state.clone();
 
+    // Keep the in-memory configuration in sync with persisted settings
+    let mut setting_changes = db_service.settings.subscribe();
+    let settings_config = server_state.config.clone();
+    tokio::spawn(async move {
+        loop {
+            match setting_changes.recv().await {
+                Ok(change) => {
+                    if let Ok(mut config) = settings_config.lock() {
+                        config.apply_setting(&change.key, &change.value);
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    // Fill the asset size counters on first run; uploads and deletes keep
+    // them current afterwards
+    if matches!(db_service.asset_sizes.last_updated().await, Ok(None)) {
+        let recount_db_service = db_service.clone();
+        let recount_config = config.clone();
+        tokio::spawn(async move {
+            if let Err(e) =
+                server::asset_sizes::recount_all(&recount_db_service, &recount_config).await
+            {
+                eprintln!("Failed to count asset sizes: {}", e);
+            }
+        });
+    }
+
+    // Expire items of collections with retention policies
+    tokio::spawn(server::retention::run_retention_scheduler(
+        db_service.clone(),
+        config.clone(),
+    ));
+
+    // Request external hrefs and validate stored records periodically
+    tokio::spawn(server::catalog_checks::run_catalog_check_scheduler(
+        db_service.clone(),
+        config.clone(),
+    ));
+
     // Start the STAC server initially
     let server_config = config.clone();
     let server_db_service = db_service.clone();
@@ -121,20 +190,80 @@ async fn main() {
         .invoke_handler(tauri::generate_handler![
             greet,
             get_database_file_size,
+            maintain_database,
             get_assets_directory_size,
+            get_asset_sizes,
+            get_catalog_statistics,
+            get_media_types,
+            get_capabilities,
+            execute_api_request,
+            recount_asset_sizes,
+            get_catalog_check,
+            get_broken_link_count,
+            run_catalog_check,
+            install_service,
+            uninstall_service,
             get_user_pref,
             set_user_pref,
             cleanup_item_assets,
             cleanup_orphaned_collection_directories,
             copy_asset_file,
+            verify_asset_checksums,
             get_server_config,
             update_server_config,
+            set_feature_toggles,
+            get_access_stats,
+            set_collection_schema,
+            pin_collection_thumbnail,
+            set_indexed_properties,
+            set_computed_properties,
+            bulk_update_items,
+            get_translations,
+            set_translations,
+            list_virtual_collections,
+            save_virtual_collection,
+            delete_virtual_collection,
+            diff_catalogs,
+            sync_with_peer,
+            set_retention_policy,
+            run_retention_policy,
+            list_secrets,
+            set_secret,
+            delete_secret,
             stop_server,
             start_server,
-            restart_server
+            restart_server,
+            list_collections,
+            get_collection,
+            create_collection,
+            update_collection,
+            delete_collection,
+            list_items,
+            get_item,
+            get_item_footprint,
+            create_item,
+            update_item,
+            delete_item,
+            preview_asset_cleanup,
+            list_trash,
+            restore_collection,
+            restore_item,
+            purge_trash,
+            rehost_collection_assets,
+            search_items,
+            explain_search,
+            geometry_tool,
+            set_catalog_branding
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                if let Err(e) = config::close_memory_database() {
+                    eprintln!("{}", e);
+                }
+            }
+        });
 }
 
 #[tauri::command]
@@ -143,139 +272,226 @@ fn greet(name: &str) -> String {
 }
 
 #[tauri::command]
-fn get_database_file_size() -> Result<u64, String> {
+async fn get_database_file_size() -> Result<u64, String> {
     let config = Config::default();
-    let db_path = &config.database.path;
-
-    if !Path::new(db_path).exists() {
-        return Err("Database file not found".to_string());
-    }
-
-    match fs::metadata(db_path) {
-        Ok(metadata) => Ok(metadata.len()),
+    match tokio::fs::metadata(&config.database.path).await {
+        // Recent writes sit in the write-ahead log until a checkpoint
+        Ok(metadata) => {
+            let wal = format!("{}-wal", config.database.path);
+            let wal_size = tokio::fs::metadata(&wal).await.map(|m| m.len()).unwrap_or(0);
+            Ok(metadata.len() + wal_size)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err("Database file not found".to_string())
+        }
         Err(e) => Err(format!("Failed to get database file size: {}", e)),
     }
 }
 
+/// Checks the database's integrity and compacts it, e.g. to reclaim the
+/// space of deleted collections, reporting the file size before and after
 #[tauri::command]
-fn get_assets_directory_size() -> Result<u64, String> {
-    let config = Config::default();
-    let assets_dir = config.assets_dir();
-    let assets_path = Path::new(&assets_dir);
+async fn maintain_database(
+    state: tauri::State<'_, ServerState>,
+) -> Result<database::maintenance::MaintenanceReport, String> {
+    state
+        .db_service
+        .maintenance
+        .run()
+        .await
+        .map_err(|e| format!("Database maintenance failed: {}", e))
+}
 
-    if !assets_path.exists() {
-        return Ok(0); // Return 0 if directory doesn't exist
-    }
+/// Runs the headless server for the current catalog as a service, so it
+/// keeps serving when the app is closed
+#[tauri::command]
+async fn install_service() -> Result<String, String> {
+    service_install::install(&Config::default().database.path)
+}
 
-    fn calculate_directory_size(dir_path: &Path) -> Result<u64, std::io::Error> {
-        let mut total_size = 0u64;
-        
-        if dir_path.is_dir() {
-            for entry in fs::read_dir(dir_path)? {
-                let entry = entry?;
-                let path = entry.path();
-                
-                if path.is_file() {
-                    // Add file size
-                    total_size += fs::metadata(&path)?.len();
-                } else if path.is_dir() {
-                    // Recursively calculate directory size
-                    total_size += calculate_directory_size(&path)?;
-                }
-            }
-        }
-        
-        Ok(total_size)
-    }
+#[tauri::command]
+async fn uninstall_service() -> Result<String, String> {
+    service_install::uninstall()
+}
 
-    match calculate_directory_size(assets_path) {
-        Ok(size) => Ok(size),
-        Err(e) => Err(format!("Failed to calculate assets directory size: {}", e)),
-    }
+/// Total size of the assets directory, from the cached counters
+#[tauri::command]
+async fn get_assets_directory_size(state: tauri::State<'_, ServerState>) -> Result<u64, String> {
+    state
+        .db_service
+        .asset_sizes
+        .total()
+        .await
+        .map(|total| total.bytes)
+        .map_err(|e| format!("Failed to read assets directory size: {}", e))
 }
 
+/// Cached asset storage use, in total and per collection
 #[tauri::command]
-fn get_user_pref(key: String) -> Result<Option<String>, String> {
-    let config = Config::default();
-    let conn = match rusqlite::Connection::open(&config.database.path) {
-        Ok(c) => c,
-        Err(e) => return Err(format!("Failed to open DB: {}", e)),
-    };
-    let mut stmt = match conn.prepare("SELECT value FROM application_settings WHERE key = ?1") {
-        Ok(s) => s,
-        Err(e) => return Err(format!("Failed to prepare statement: {}", e)),
-    };
-    let mut rows = match stmt.query([key]) {
-        Ok(r) => r,
-        Err(e) => return Err(format!("Failed to query: {}", e)),
-    };
-    match rows.next() {
-        Ok(Some(row)) => {
-            let value: String = row.get(0).map_err(|e| e.to_string())?;
-            Ok(Some(value))
-        }
-        Ok(None) => Ok(None),
-        Err(e) => Err(e.to_string()),
-    }
+async fn get_asset_sizes(
+    state: tauri::State<'_, ServerState>,
+) -> Result<server::asset_sizes::AssetSizeSummary, String> {
+    server::asset_sizes::summary(&state.db_service).await
 }
 
+/// Item counts, extents and asset storage per collection, for the dashboard
 #[tauri::command]
-fn set_user_pref(key: String, value: String) -> Result<(), String> {
-    let config = Config::default();
-    let conn = match rusqlite::Connection::open(&config.database.path) {
-        Ok(c) => c,
-        Err(e) => return Err(format!("Failed to open DB: {}", e)),
-    };
-    conn.execute(
-        "INSERT OR REPLACE INTO application_settings (key, value, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
-        (&key, &value),
-    ).map_err(|e| format!("Failed to insert: {}", e))?;
-    Ok(())
+async fn get_catalog_statistics(
+    state: tauri::State<'_, ServerState>,
+) -> Result<database::service::CatalogStatistics, String> {
+    state
+        .db_service
+        .get_statistics()
+        .await
+        .map_err(|e| format!("Failed to read catalog statistics: {}", e))
 }
 
-/// Asynchronously cleans up all asset files for a given item
-/// This runs in the background so the UI remains responsive
+/// Media types assigned to asset files by extension, including the user's
+/// `media_types` overrides
 #[tauri::command]
-async fn cleanup_item_assets(collection_id: String, item_id: String) -> Result<String, String> {
-    // Clone the values for the async block
-    let collection_id_clone = collection_id.clone();
-    let item_id_clone = item_id.clone();
+async fn get_media_types(
+    state: tauri::State<'_, ServerState>,
+) -> Result<std::collections::BTreeMap<String, String>, String> {
+    Ok(server::media_types::MediaTypeRegistry::load(&state.db_service)
+        .await
+        .entries()
+        .clone())
+}
 
-    // Spawn the cleanup task in the background
-    tokio::spawn(async move {
-        let config = Config::default();
-        let assets_dir = format!("{}/{}/{}", config.assets_dir(), collection_id_clone, item_id_clone);
-        let assets_path = Path::new(&assets_dir);
+/// Optional subsystems this build has and which are turned on, for showing
+/// or hiding their controls
+#[tauri::command]
+async fn get_capabilities(
+    state: tauri::State<'_, ServerState>,
+) -> Result<server::capabilities::Capabilities, String> {
+    let config = state.config.lock().map_err(|e| e.to_string())?.clone();
+    Ok(server::capabilities::capabilities(&state.db_service, &config).await)
+}
 
-        if assets_path.exists() {
+/// Runs a request from the API playground through the router in-process and
+/// returns its status, headers and body, so the API can be tried even when
+/// loopback connections are blocked
+#[tauri::command]
+async fn execute_api_request(
+    method: String,
+    path: String,
+    body: Option<String>,
+    headers: Option<std::collections::HashMap<String, String>>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<server::internal::ApiExchange, String> {
+    let config = state.config.lock().map_err(|e| e.to_string())?.clone();
+    server::internal::execute(
+        state.db_service.clone(),
+        config,
+        &method,
+        &path,
+        &headers.unwrap_or_default(),
+        body,
+    )
+    .await
+}
 
+/// Rebuilds the asset size counters from disk in the background, e.g. after
+/// files were changed outside the app
+#[tauri::command]
+async fn recount_asset_sizes(state: tauri::State<'_, ServerState>) -> Result<String, String> {
+    let db_service = state.db_service.clone();
+    let config = state.config.lock().map_err(|e| e.to_string())?.clone();
+    tokio::spawn(async move {
+        if let Err(e) = server::asset_sizes::recount_all(&db_service, &config).await {
+            eprintln!("Failed to count asset sizes: {}", e);
+        }
+    });
+    Ok("Asset size recount started in background".to_string())
+}
 
-            // Remove all files in the assets directory
-            if let Err(e) = fs::remove_dir_all(&assets_dir) {
-                eprintln!(
-                    "Cleanup: Failed to remove assets directory {}: {}",
-                    assets_dir, e
-                );
-            } else {
+/// Last catalog check: its summary, `None` before the first run, and the
+/// broken links and STAC violations it found, optionally of one collection
+#[tauri::command]
+async fn get_catalog_check(
+    collection_id: Option<String>,
+    limit: Option<i64>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let summary = state
+        .db_service
+        .catalog_checks
+        .summary()
+        .await
+        .map_err(|e| e.to_string())?;
+    let issues = state
+        .db_service
+        .catalog_checks
+        .issues(collection_id.as_deref(), limit.unwrap_or(1000))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({ "summary": summary, "issues": issues }))
+}
 
-            }
+/// Number of broken links found by the last catalog check, for the badge in
+/// the desktop UI
+#[tauri::command]
+async fn get_broken_link_count(state: tauri::State<'_, ServerState>) -> Result<u64, String> {
+    let summary = state
+        .db_service
+        .catalog_checks
+        .summary()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(summary.map(|s| s.broken_links).unwrap_or(0))
+}
 
-            // Try to remove the parent directory if it's empty
-            let parent_dir = format!("{}/{}", config.assets_dir(), collection_id_clone);
-            if let Ok(entries) = fs::read_dir(&parent_dir) {
-                if entries.count() == 0 {
-                    if let Err(e) = fs::remove_dir(&parent_dir) {
-                        eprintln!(
-                            "Cleanup: Failed to remove empty parent directory {}: {}",
-                            parent_dir, e
-                        );
-                    } else {
+/// Runs the catalog check now in the background instead of waiting for the
+/// scheduler
+#[tauri::command]
+async fn run_catalog_check(state: tauri::State<'_, ServerState>) -> Result<String, String> {
+    let db_service = state.db_service.clone();
+    let config = state.config.lock().map_err(|e| e.to_string())?.clone();
+    tokio::spawn(async move {
+        if let Err(e) = server::catalog_checks::run_check(&db_service, &config).await {
+            eprintln!("Catalog check: {}", e);
+        }
+    });
+    Ok("Catalog check started in background".to_string())
+}
 
-                    }
-                }
-            }
-        } else {
+#[tauri::command]
+async fn get_user_pref(
+    key: String,
+    state: tauri::State<'_, ServerState>,
+) -> Result<Option<String>, String> {
+    state
+        .db_service
+        .settings
+        .get_raw(&key)
+        .await
+        .map_err(|e| e.to_string())
+}
 
+#[tauri::command]
+async fn set_user_pref(
+    key: String,
+    value: String,
+    state: tauri::State<'_, ServerState>,
+) -> Result<(), String> {
+    state
+        .db_service
+        .settings
+        .set_raw(&key, &value)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Moves all asset files of a given item to the trash
+/// This runs in the background so the UI remains responsive
+#[tauri::command]
+async fn cleanup_item_assets(collection_id: String, item_id: String) -> Result<String, String> {
+    let cleanup = server::asset_cleanup::AssetCleanup::new(&Config::default());
+    let collection_id_clone = collection_id.clone();
+    let item_id_clone = item_id.clone();
+    tokio::spawn(async move {
+        if let Err(e) = cleanup.trash(&collection_id_clone, Some(&item_id_clone)).await {
+            eprintln!("Cleanup: {}", e);
         }
     });
 
@@ -285,58 +501,15 @@ async fn cleanup_item_assets(collection_id: String, item_id: String) -> Result<S
     ))
 }
 
-/// Manually clean up orphaned collection directories
+/// Lists the asset files no collection or item refers to; with `confirm`
+/// they are deleted
 #[tauri::command]
-async fn cleanup_orphaned_collection_directories() -> Result<String, String> {
-    tokio::spawn(async move {
-        let config = Config::default();
-        let assets_base_dir = config.assets_dir();
-        let assets_path = Path::new(&assets_base_dir);
-
-        if !assets_path.exists() {
-        
-            return;
-        }
-
-    
-
-        if let Ok(entries) = fs::read_dir(&assets_base_dir) {
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        let _collection_id = path.file_name().unwrap_or_default().to_string_lossy();
-
-
-                        // Check if this collection exists in the database
-                        // For now, we'll just try to remove empty directories
-                        if let Ok(sub_entries) = fs::read_dir(&path) {
-                            let mut has_contents = false;
-                            for _ in sub_entries {
-                                has_contents = true;
-                                break;
-                            }
-
-                            if !has_contents {
-
-                                if let Err(e) = fs::remove_dir(&path) {
-                                    eprintln!("Cleanup orphaned: Failed to remove empty directory {:?}: {}", path, e);
-                                } else {
-                        
-                                }
-                            } else {
-                    
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-    
-    });
-
-    Ok("Orphaned directory cleanup started in background".to_string())
+async fn cleanup_orphaned_collection_directories(
+    confirm: Option<bool>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<server::assets::AssetGcReport, String> {
+    let config = state.config.lock().map_err(|e| e.to_string())?.clone();
+    server::assets::collect_garbage(&state.db_service, &config, confirm != Some(true)).await
 }
 
 #[tauri::command]
@@ -354,11 +527,11 @@ async fn copy_asset_file(
     // Copy the file using the filename as the destination
     let config = Config::default();
     let dest_dir = format!("{}/{}/{}", config.assets_dir(), collection_id, item_id);
-    if let Err(e) = fs::create_dir_all(&dest_dir) {
+    if let Err(e) = tokio::fs::create_dir_all(&dest_dir).await {
         return Err(format!("Failed to create destination directory: {}", e));
     }
     let dest_path = Path::new(&dest_dir).join(filename);
-    if let Err(e) = fs::copy(&src_path, &dest_path) {
+    if let Err(e) = tokio::fs::copy(&src_path, &dest_path).await {
         return Err(format!("Failed to copy file: {}", e));
     }
 
@@ -372,36 +545,11 @@ async fn copy_asset_file(
         .map_err(|e| format!("Failed to get item: {}", e))?
         .ok_or_else(|| format!("Item '{}' not found in collection '{}'", item_id, collection_id))?;
 
-    // Determine content type based on file extension
-    let content_type = if let Some(ext) = Path::new(&src_path).extension() {
-        match ext.to_str().unwrap_or("").to_lowercase().as_str() {
-            "jpg" | "jpeg" => "image/jpeg",
-            "png" => "image/png",
-            "gif" => "image/gif",
-            "webp" => "image/webp",
-            "svg" => "image/svg+xml",
-            "tif" | "tiff" => "image/tiff",
-            "pdf" => "application/pdf",
-            "json" => "application/json",
-            "xml" => "application/xml",
-            "txt" => "text/plain",
-            "csv" => "text/csv",
-            _ => "application/octet-stream",
-        }
-    } else {
-        "application/octet-stream"
-    };
-
-    // Determine asset roles based on key and content type
-    let roles = if asset_key == "thumbnail" {
-        vec!["thumbnail"]
-    } else if content_type.starts_with("image/") {
-        vec!["overview"]
-    } else if content_type.contains("geotiff") || content_type.contains("tiff") {
-        vec!["data"]
-    } else {
-        vec!["data"]
-    };
+    let content_type = server::media_types::MediaTypeRegistry::load(&db_service)
+        .await
+        .for_path(&src_path)
+        .to_string();
+    let roles = server::media_types::asset_roles(&asset_key, &content_type);
 
     // Build the asset URL using the filename as the endpoint
     // Use the external_url from config and append the asset path
@@ -418,39 +566,640 @@ async fn copy_asset_file(
     };
 
     // Add or update the asset with proper STAC structure
-    let asset_data = serde_json::json!({
+    let mut asset_data = serde_json::json!({
         "href": asset_href,
         "type": content_type,
         "title": filename,
         "description": format!("Uploaded asset: {}", filename),
         "roles": roles
     });
-    
+    match server::checksums::file_info(&dest_path).await {
+        Ok(info) => {
+            server::checksums::set_file_info(&mut asset_data, &info);
+            server::checksums::add_file_extension(&mut db_item);
+        }
+        Err(e) => eprintln!("Failed to hash {}: {}", dest_path.display(), e),
+    }
+
     assets.insert(asset_key.clone(), asset_data);
+
+    // Plugins may read sensor metadata from the file into properties
+    let extracted = server::plugins::extract_file_properties(
+        std::path::PathBuf::from(config.plugins_dir()),
+        filename.to_string(),
+        &dest_path,
+    )
+    .await;
+    server::plugins::apply_properties(&mut db_item.properties, extracted);
+
     db_item.assets = Some(serde_json::to_value(assets).unwrap());
     db_item.updated_at = chrono::Utc::now().to_rfc3339();
 
     // Save updated item
     db_service.items.update(&db_item).await
         .map_err(|e| format!("Failed to update item with new asset: {}", e))?;
+    if let Err(e) =
+        server::asset_sizes::refresh(&db_service, &config, &collection_id, Some(&item_id)).await
+    {
+        eprintln!("Failed to update asset size of {}/{}: {}", collection_id, item_id, e);
+    }
 
     Ok(())
 }
 
-/// Get current server configuration
+/// Hashes every locally stored asset and compares it with its
+/// `file:checksum`, reporting files that changed or went missing
 #[tauri::command]
-fn get_server_config() -> Result<serde_json::Value, String> {
+async fn verify_asset_checksums(
+    state: tauri::State<'_, ServerState>,
+) -> Result<server::checksums::ChecksumReport, String> {
+    let config = state.config.lock().map_err(|e| e.to_string())?.clone();
+    server::checksums::verify_all(&state.db_service, &config).await
+}
+
+/// Sends a catalog request to the STAC router in-process, bypassing the public server
+async fn internal_request(
+    state: &ServerState,
+    method: axum::http::Method,
+    path: String,
+    body: Option<serde_json::Value>,
+) -> Result<serde_json::Value, String> {
+    let config = state
+        .config
+        .lock()
+        .map_err(|e| format!("Failed to acquire lock: {}", e))?
+        .clone();
+    server::internal::dispatch(state.db_service.clone(), config, method, &path, body).await
+}
+
+/// Percent-encodes a path segment supplied by the UI
+fn segment(value: &str) -> String {
+    urlencoding::encode(value).into_owned()
+}
+
+#[tauri::command]
+async fn list_collections(state: tauri::State<'_, ServerState>) -> Result<serde_json::Value, String> {
+    internal_request(&state, axum::http::Method::GET, "/collections".to_string(), None).await
+}
+
+#[tauri::command]
+async fn get_collection(
+    collection_id: String,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = format!("/collections/{}", segment(&collection_id));
+    internal_request(&state, axum::http::Method::GET, path, None).await
+}
+
+#[tauri::command]
+async fn create_collection(
+    collection: serde_json::Value,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    internal_request(&state, axum::http::Method::POST, "/collections".to_string(), Some(collection)).await
+}
+
+#[tauri::command]
+async fn update_collection(
+    collection_id: String,
+    collection: serde_json::Value,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = format!("/collections/{}", segment(&collection_id));
+    internal_request(&state, axum::http::Method::PUT, path, Some(collection)).await
+}
+
+#[tauri::command]
+async fn delete_collection(
+    collection_id: String,
+    confirm: Option<bool>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let mut path = format!("/collections/{}", segment(&collection_id));
+    if confirm == Some(true) {
+        path.push_str("?confirm=true");
+    }
+    internal_request(&state, axum::http::Method::DELETE, path, None).await
+}
+
+#[tauri::command]
+async fn list_items(
+    collection_id: String,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    bbox: Option<Vec<f64>>,
+    datetime: Option<String>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let mut params = Vec::new();
+    if let Some(limit) = limit {
+        params.push(format!("limit={}", limit));
+    }
+    if let Some(offset) = offset {
+        params.push(format!("offset={}", offset));
+    }
+    if let Some(bbox) = bbox {
+        let bbox: Vec<String> = bbox.iter().map(|v| v.to_string()).collect();
+        params.push(format!("bbox={}", bbox.join(",")));
+    }
+    if let Some(datetime) = datetime {
+        params.push(format!("datetime={}", segment(&datetime)));
+    }
+
+    let mut path = format!("/collections/{}/items", segment(&collection_id));
+    if !params.is_empty() {
+        path = format!("{}?{}", path, params.join("&"));
+    }
+    internal_request(&state, axum::http::Method::GET, path, None).await
+}
+
+#[tauri::command]
+async fn get_item(
+    collection_id: String,
+    item_id: String,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = format!("/collections/{}/items/{}", segment(&collection_id), segment(&item_id));
+    internal_request(&state, axum::http::Method::GET, path, None).await
+}
+
+/// Item geometry simplified for map overlays, optionally in web mercator
+#[tauri::command]
+async fn get_item_footprint(
+    collection_id: String,
+    item_id: String,
+    tolerance: Option<f64>,
+    crs: Option<String>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let mut params = Vec::new();
+    if let Some(tolerance) = tolerance {
+        params.push(format!("tolerance={}", tolerance));
+    }
+    if let Some(crs) = crs {
+        params.push(format!("crs={}", segment(&crs)));
+    }
+
+    let mut path = format!(
+        "/collections/{}/items/{}/footprint",
+        segment(&collection_id),
+        segment(&item_id)
+    );
+    if !params.is_empty() {
+        path = format!("{}?{}", path, params.join("&"));
+    }
+    internal_request(&state, axum::http::Method::GET, path, None).await
+}
+
+#[tauri::command]
+async fn create_item(
+    collection_id: String,
+    item: serde_json::Value,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = format!("/collections/{}/items", segment(&collection_id));
+    internal_request(&state, axum::http::Method::POST, path, Some(item)).await
+}
+
+#[tauri::command]
+async fn update_item(
+    collection_id: String,
+    item_id: String,
+    item: serde_json::Value,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = format!("/collections/{}/items/{}", segment(&collection_id), segment(&item_id));
+    internal_request(&state, axum::http::Method::PUT, path, Some(item)).await
+}
+
+#[tauri::command]
+async fn delete_item(
+    collection_id: String,
+    item_id: String,
+    confirm: Option<bool>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let mut path = format!("/collections/{}/items/{}", segment(&collection_id), segment(&item_id));
+    if confirm == Some(true) {
+        path.push_str("?confirm=true");
+    }
+    internal_request(&state, axum::http::Method::DELETE, path, None).await
+}
+
+/// Files and bytes deleting a collection, or one of its items, would remove.
+/// Deletes above `delete_confirm_threshold_bytes` need `confirm`.
+#[tauri::command]
+async fn preview_asset_cleanup(
+    collection_id: String,
+    item_id: Option<String>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = match item_id {
+        Some(item_id) => format!(
+            "/collections/{}/items/{}/cleanup-preview",
+            segment(&collection_id),
+            segment(&item_id)
+        ),
+        None => format!("/collections/{}/cleanup-preview", segment(&collection_id)),
+    };
+    internal_request(&state, axum::http::Method::GET, path, None).await
+}
+
+/// Collections and items in the trash
+#[tauri::command]
+async fn list_trash(state: tauri::State<'_, ServerState>) -> Result<serde_json::Value, String> {
+    internal_request(&state, axum::http::Method::GET, "/trash".to_string(), None).await
+}
+
+/// Takes a collection, with the items deleted together with it, out of the
+/// trash
+#[tauri::command]
+async fn restore_collection(
+    collection_id: String,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = format!("/trash/collections/{}/restore", segment(&collection_id));
+    internal_request(&state, axum::http::Method::POST, path, None).await
+}
+
+/// Takes an item out of the trash
+#[tauri::command]
+async fn restore_item(
+    collection_id: String,
+    item_id: String,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = format!(
+        "/trash/collections/{}/items/{}/restore",
+        segment(&collection_id),
+        segment(&item_id)
+    );
+    internal_request(&state, axum::http::Method::POST, path, None).await
+}
+
+/// Permanently deletes a trashed collection or item with its assets, or
+/// without a collection everything in the trash. Answers the purge job.
+#[tauri::command]
+async fn purge_trash(
+    collection_id: Option<String>,
+    item_id: Option<String>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = match (collection_id, item_id) {
+        (Some(collection_id), Some(item_id)) => format!(
+            "/trash/collections/{}/items/{}",
+            segment(&collection_id),
+            segment(&item_id)
+        ),
+        (Some(collection_id), None) => format!("/trash/collections/{}", segment(&collection_id)),
+        (None, _) => "/trash".to_string(),
+    };
+    internal_request(&state, axum::http::Method::DELETE, path, None).await
+}
+
+/// Starts a job downloading the remote asset hrefs of a collection's items
+/// into local storage; the answer links to the job's status
+#[tauri::command]
+async fn rehost_collection_assets(
+    collection_id: String,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let path = format!("/collections/{}/rehost", segment(&collection_id));
+    internal_request(&state, axum::http::Method::POST, path, None).await
+}
+
+#[tauri::command]
+async fn search_items(
+    search: serde_json::Value,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    internal_request(&state, axum::http::Method::POST, "/search".to_string(), Some(search)).await
+}
+
+/// How a search runs: filters pushed to SQL or applied in memory, row counts
+/// and timings per stage
+#[tauri::command]
+async fn explain_search(
+    search: serde_json::Value,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    internal_request(
+        &state,
+        axum::http::Method::POST,
+        "/search/explain".to_string(),
+        Some(search),
+    )
+    .await
+}
+
+/// Geometry utilities for the item geometry editor (WKT import, buffering, bbox)
+#[tauri::command]
+fn geometry_tool(
+    request: server::geometry::GeometryToolRequest,
+) -> Result<server::geometry::GeometryToolResponse, String> {
+    server::geometry::run_geometry_tool(request)
+}
+
+/// Most requested collections, items or assets ("top downloaded assets this week")
+#[tauri::command]
+async fn get_access_stats(
+    kind: Option<String>,
+    days: Option<u32>,
+    limit: Option<usize>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
+    let kind_name = kind.unwrap_or_else(|| "asset".to_string());
+    let kind = database::access_stats::AccessKind::parse(&kind_name)
+        .ok_or_else(|| format!("Unknown kind '{}'. Use collection, item or asset", kind_name))?;
+    server::access_log::top_accessed(&state.db_service, kind, days.unwrap_or(7), limit.unwrap_or(10))
+        .await
+        .map_err(|e| format!("Failed to read access statistics: {}", e))
+}
+
+/// Attaches a JSON Schema that new and updated items of the collection must
+/// satisfy; `null` removes it
+#[tauri::command]
+async fn set_collection_schema(
+    collection_id: String,
+    schema: serde_json::Value,
+    state: tauri::State<'_, ServerState>,
+) -> Result<Option<serde_json::Value>, String> {
+    server::item_schema::set_collection_schema(&state.db_service, &collection_id, schema)
+        .await
+        .map_err(|(_, e)| e)
+}
+
+/// Pins an item's thumbnail as its collection's thumbnail; `null` goes back to
+/// the most recent item with a thumbnail
+#[tauri::command]
+async fn pin_collection_thumbnail(
+    collection_id: String,
+    item_id: Option<String>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<Option<String>, String> {
+    server::thumbnails::pin_collection_thumbnail(&state.db_service, &collection_id, item_id.as_deref())
+        .await
+        .map_err(|(_, e)| e)
+}
+
+/// Titles and descriptions of a collection, or of one of its items, in
+/// languages other than the metadata language
+#[tauri::command]
+async fn get_translations(
+    collection_id: String,
+    item_id: Option<String>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<database::translations::Translations, String> {
+    server::i18n::get_translations(&state.db_service, &collection_id, item_id.as_deref())
+        .await
+        .map_err(|(_, e)| e)
+}
+
+/// Replaces the translations of a collection or item, e.g.
+/// `{"fr": {"title": "Relevé du port", "description": "..."}}`
+#[tauri::command]
+async fn set_translations(
+    collection_id: String,
+    item_id: Option<String>,
+    translations: database::translations::Translations,
+    state: tauri::State<'_, ServerState>,
+) -> Result<database::translations::Translations, String> {
+    server::i18n::set_translations(
+        &state.db_service,
+        &collection_id,
+        item_id.as_deref(),
+        translations,
+    )
+    .await
+    .map_err(|(_, e)| e)
+}
+
+/// Replaces the item properties a collection indexes for fast query
+/// predicates, e.g. `[{"property": "eo:cloud_cover", "type": "number"}]`
+#[tauri::command]
+async fn set_indexed_properties(
+    collection_id: String,
+    properties: Vec<database::indexed_properties::IndexedProperty>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<Vec<database::indexed_properties::IndexedProperty>, String> {
+    server::indexed_properties::set_indexed_properties(&state.db_service, &collection_id, properties)
+        .await
+        .map_err(|(_, e)| e)
+}
+
+/// Replaces the properties a collection computes for its items
+#[tauri::command]
+async fn set_computed_properties(
+    collection_id: String,
+    properties: Vec<database::computed_properties::ComputedProperty>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<Vec<database::computed_properties::ComputedProperty>, String> {
+    server::computed_properties::set_computed_properties(&state.db_service, &collection_id, properties)
+        .await
+        .map_err(|(_, e)| e)
+}
+
+/// Applies a merge patch to every item of a collection matching a filter
+#[tauri::command]
+async fn bulk_update_items(
+    collection_id: String,
+    request: server::bulk_update::BulkUpdateRequest,
+    state: tauri::State<'_, ServerState>,
+) -> Result<server::bulk_update::BulkUpdateReport, String> {
+    let config = state.config.lock().map_err(|e| e.to_string())?.clone();
+    server::bulk_update::bulk_update(&state.db_service, &config, &collection_id, &request)
+        .await
+        .map_err(|(_, e)| e)
+}
+
+/// Lists the stored searches published as virtual collections
+#[tauri::command]
+async fn list_virtual_collections(
+    state: tauri::State<'_, ServerState>,
+) -> Result<Vec<database::virtual_collections::VirtualCollection>, String> {
+    state
+        .db_service
+        .virtual_collections
+        .get_all()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Creates or replaces a virtual collection
+#[tauri::command]
+async fn save_virtual_collection(
+    virtual_collection: database::virtual_collections::VirtualCollection,
+    state: tauri::State<'_, ServerState>,
+) -> Result<database::virtual_collections::VirtualCollection, String> {
+    server::virtual_collections::save_virtual_collection(&state.db_service, virtual_collection)
+        .await
+        .map_err(|(_, e)| e)
+}
+
+/// Removes a virtual collection; the items of its source collections are untouched
+#[tauri::command]
+async fn delete_virtual_collection(
+    collection_id: String,
+    state: tauri::State<'_, ServerState>,
+) -> Result<bool, String> {
+    state
+        .db_service
+        .virtual_collections
+        .delete(&collection_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Compares two catalogs, each "live" (this app's database), another ZenSTAC
+/// database file or a `zenstac export` file, reporting the collections and
+/// items added, changed or removed in `source` relative to `target`. With
+/// `apply`, the changes are written to `target`, which must be "live" or a
+/// database file; removed records are only deleted with `delete_removed`.
+#[tauri::command]
+async fn diff_catalogs(
+    source: String,
+    target: String,
+    apply: Option<bool>,
+    delete_removed: Option<bool>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<catalog_diff::CatalogDiff, String> {
+    let source_snapshot = catalog_diff::CatalogSnapshot::load(&source, &state.db_service).await?;
+    let target_snapshot = catalog_diff::CatalogSnapshot::load(&target, &state.db_service).await?;
+    let mut diff = catalog_diff::diff(&source_snapshot, &target_snapshot);
+
+    if apply.unwrap_or(false) {
+        let target_db = catalog_diff::open_target(&target, &state.db_service).await?;
+        catalog_diff::apply(&diff, &source_snapshot, &target_db, delete_removed.unwrap_or(false))
+            .await?;
+        diff.applied = true;
+    }
+    Ok(diff)
+}
+
+/// Exchanges changes with another ZenSTAC instance, given its API root such as
+/// `http://192.168.1.20:3000/v1`. Records both sides changed are reported as
+/// conflicts unless `prefer` is "local" or "remote". `credential` is the id of
+/// a stored secret holding the peer's API key.
+#[tauri::command]
+async fn sync_with_peer(
+    peer_url: String,
+    prefer: Option<server::sync::Resolution>,
+    credential: Option<String>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<server::sync::SyncReport, String> {
+    server::sync::sync_with_peer(&state.db_service, &peer_url, prefer, credential.as_deref()).await
+}
+
+/// Replaces the retention policy of a collection; an empty rule list removes it
+#[tauri::command]
+async fn set_retention_policy(
+    collection_id: String,
+    rules: Vec<database::retention::RetentionRule>,
+    enabled: Option<bool>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<Option<database::retention::RetentionPolicy>, String> {
+    server::retention::save_policy(
+        &state.db_service,
+        &collection_id,
+        rules,
+        enabled.unwrap_or(true),
+    )
+    .await
+    .map_err(|(_, e)| e)
+}
+
+/// Evaluates a collection's retention policy. With `preview` the expired items
+/// are only listed; otherwise they are moved to the trash right away.
+#[tauri::command]
+async fn run_retention_policy(
+    collection_id: String,
+    preview: bool,
+    state: tauri::State<'_, ServerState>,
+) -> Result<server::retention::RetentionReport, String> {
+    let policy = state
+        .db_service
+        .retention_policies
+        .get(&collection_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Collection '{}' has no retention policy", collection_id))?;
+    server::retention::run_policy(&state.db_service, &policy, preview).await
+}
+
+/// Lists stored secrets by id and backend; values are never returned
+#[tauri::command]
+async fn list_secrets(
+    state: tauri::State<'_, ServerState>,
+) -> Result<Vec<database::secrets::SecretInfo>, String> {
+    state.db_service.secrets.list().await.map_err(|e| e.to_string())
+}
+
+/// Stores a credential in the OS keyring (or the fallback file) under `id`,
+/// for settings to reference as `${secret:<id>}`
+#[tauri::command]
+async fn set_secret(
+    id: String,
+    value: String,
+    state: tauri::State<'_, ServerState>,
+) -> Result<database::secrets::SecretBackend, String> {
+    state.db_service.secrets.set(&id, &value).await
+}
+
+#[tauri::command]
+async fn delete_secret(id: String, state: tauri::State<'_, ServerState>) -> Result<(), String> {
+    state.db_service.secrets.delete(&id).await
+}
+
+/// Sets or clears the catalog logo, favicon or about document.
+/// `kind` is one of "logo", "favicon" or "about"; a missing `src_path` removes the file.
+#[tauri::command]
+fn set_catalog_branding(kind: String, src_path: Option<String>) -> Result<(), String> {
+    use server::branding::{install_branding_file, remove_branding_file, BrandingKind};
+
+    let kind = BrandingKind::parse(&kind)
+        .ok_or_else(|| format!("Unknown branding kind '{}'. Use logo, favicon or about", kind))?;
+    let config = Config::default();
+    match src_path {
+        Some(path) => install_branding_file(&config, kind, &path),
+        None => remove_branding_file(&config, kind),
+    }
+}
+
+/// Get current server configuration.
+/// `suggested_external_addresses` lists LAN addresses, plus the public address
+/// from the configured STUN server when `detect_public_address` is set.
+#[tauri::command]
+async fn get_server_config(
+    detect_public_address: Option<bool>,
+    state: tauri::State<'_, ServerState>,
+) -> Result<serde_json::Value, String> {
     // Load current configuration with database settings
     let config = Config::with_server_settings();
-    
+
+    let stun_server = if detect_public_address.unwrap_or(false) {
+        state
+            .db_service
+            .settings
+            .get::<StunServer>()
+            .await
+            .unwrap_or_else(|_| StunServer::default_value())
+    } else {
+        String::new()
+    };
+    let suggestions = server::network::suggest_external_addresses(&stun_server).await;
+
     // Return server configuration as JSON
     let server_config = serde_json::json!({
         "internal_address": config.server.internal_address,
         "external_address": config.server.external_address,
         "port": config.server.port,
-        "external_url": config.external_url()
+        "external_url": config.external_url(),
+        "enable_transactions": config.server.enable_transactions,
+        "enable_uploads": config.server.enable_uploads,
+        "enable_search": config.server.enable_search,
+        "suggested_external_addresses": suggestions
     });
-    
+
     Ok(server_config)
 }
 
@@ -536,53 +1285,105 @@ async fn restart_server(state: tauri::State<'_, ServerState>) -> Result<String,
     Ok("Server restarted successfully".to_string())
 }
 
+/// Saves the server addresses and port and restarts the server.
+/// With `verify_external_url`, the new configuration is applied first and only
+/// persisted once the advertised external URL answers from this server;
+/// otherwise the previous configuration is restored.
 #[tauri::command]
 async fn update_server_config(
     internal_address: String,
     external_address: String,
     port: u16,
+    verify_external_url: Option<bool>,
     state: tauri::State<'_, ServerState>,
 ) -> Result<String, String> {
-
-    if port < 1024 || port > 65535 {
-        return Err("Port must be between 1024 and 65535".to_string());
+    let verify = verify_external_url.unwrap_or(false);
+    let settings = vec![
+        (ServerInternalAddress::KEY.to_string(), internal_address.clone()),
+        (ServerExternalAddress::KEY.to_string(), external_address.clone()),
+        (ServerPort::KEY.to_string(), port.to_string()),
+    ];
+
+    // Validate and persist all three values atomically
+    if !verify {
+        state
+            .db_service
+            .settings
+            .set_many(settings.clone())
+            .await
+            .map_err(|e| format!("Failed to save server configuration: {}", e))?;
     }
-    if internal_address.is_empty() || external_address.is_empty() {
-        return Err("Addresses cannot be empty".to_string());
-    }
-    let config = Config::default();
-    let conn = match rusqlite::Connection::open(&config.database.path) {
-        Ok(c) => c,
-        Err(e) => return Err(format!("Failed to open DB: {}", e)),
-    };
-    conn.execute(
-        "INSERT OR REPLACE INTO application_settings (key, value, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
-        ("server_internal_address", &internal_address),
-    ).map_err(|e| format!("Failed to save internal address: {}", e))?;
-    conn.execute(
-        "INSERT OR REPLACE INTO application_settings (key, value, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
-        ("server_external_address", &external_address),
-    ).map_err(|e| format!("Failed to save external address: {}", e))?;
-    conn.execute(
-        "INSERT OR REPLACE INTO application_settings (key, value, updated_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
-        ("server_port", &port.to_string()),
-    ).map_err(|e| format!("Failed to save port: {}", e))?;
 
     // Update the config in memory
-    {
+    let (previous, external_url) = {
         let mut config_guard = state.config.lock().map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        let previous = config_guard.clone();
         config_guard.server.internal_address = internal_address.clone();
         config_guard.server.external_address = external_address.clone();
         config_guard.server.port = port;
-    }
+        (previous, config_guard.external_url())
+    };
     // Now drop the lock before await
 
-    let restart_result = restart_server(state).await;
+    let restart_result = restart_server(state.clone()).await;
     if let Err(e) = restart_result {
         return Err(format!("Failed to restart server: {}", e));
     }
+
+    if verify {
+        let check = match server::network::self_check(&external_url).await {
+            Ok(()) => state
+                .db_service
+                .settings
+                .set_many(settings)
+                .await
+                .map_err(|e| format!("Failed to save server configuration: {}", e)),
+            Err(e) => Err(format!("External URL check failed, configuration not saved: {}", e)),
+        };
+        if let Err(e) = check {
+            if let Ok(mut config_guard) = state.config.lock() {
+                *config_guard = previous;
+            }
+            let _ = restart_server(state.clone()).await;
+            return Err(e);
+        }
+    }
+
     Ok(format!(
         "Server configuration updated and restarted successfully on {}:{}",
         internal_address, port
     ))
 }
+
+/// Enables or disables the transaction, upload and search feature groups and
+/// restarts the server so the router, conformance classes and OpenAPI document follow
+#[tauri::command]
+async fn set_feature_toggles(
+    enable_transactions: bool,
+    enable_uploads: bool,
+    enable_search: bool,
+    state: tauri::State<'_, ServerState>,
+) -> Result<String, String> {
+    state
+        .db_service
+        .settings
+        .set_many(vec![
+            (EnableTransactions::KEY.to_string(), enable_transactions.to_string()),
+            (EnableUploads::KEY.to_string(), enable_uploads.to_string()),
+            (EnableSearch::KEY.to_string(), enable_search.to_string()),
+        ])
+        .await
+        .map_err(|e| format!("Failed to save feature settings: {}", e))?;
+
+    {
+        let mut config_guard = state.config.lock().map_err(|e| format!("Failed to acquire lock: {}", e))?;
+        config_guard.server.enable_transactions = enable_transactions;
+        config_guard.server.enable_uploads = enable_uploads;
+        config_guard.server.enable_search = enable_search;
+    }
+
+    restart_server(state)
+        .await
+        .map_err(|e| format!("Failed to restart server: {}", e))?;
+    Ok("Feature settings updated and server restarted".to_string())
+}
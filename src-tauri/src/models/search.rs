@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 /// Query parameters for STAC Item Search endpoint
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct SearchQuery {
     /// Maximum number of results to return
     pub limit: Option<i32>,
@@ -20,6 +20,38 @@ pub struct SearchQuery {
     pub collections: Option<String>,
     /// Sort parameters in format "field:direction,field:direction"
     pub sortby: Option<String>,
+    /// Opaque keyset pagination token from a previous page's `next` link
+    pub token: Option<String>,
+    /// Query extension object as JSON, e.g. `{"eo:cloud_cover":{"lt":10}}`
+    pub query: Option<String>,
+    /// Free-text search; comma-separated terms match if any does
+    pub q: Option<String>,
+    /// Minimal items for low-bandwidth clients: no links, assets as href and type
+    pub compact: Option<bool>,
+    /// CQL2 filter expression
+    pub filter: Option<String>,
+    /// Language of `filter`: "cql2-text" (default) or "cql2-json"
+    #[serde(rename = "filter-lang")]
+    pub filter_lang: Option<String>,
+    /// Whether the remote catalogs of `federated_catalogs` are searched too;
+    /// they are unless this is false
+    pub federated: Option<bool>,
+}
+
+/// Query parameters for GET /collections (STAC Collection Search)
+#[derive(Debug, Default, Deserialize)]
+pub struct CollectionsQuery {
+    /// Maximum number of collections to return; all of them when absent
+    pub limit: Option<i32>,
+    /// Opaque pagination token from a previous page's `next` or `prev` link
+    pub token: Option<String>,
+    /// Bounding box the collection's spatial extent must intersect, in format
+    /// "min_lon,min_lat,max_lon,max_lat"
+    pub bbox: Option<String>,
+    /// Date/time or interval the collection's temporal extent must overlap
+    pub datetime: Option<String>,
+    /// Free-text search over collection titles, descriptions and keywords
+    pub q: Option<String>,
 }
 
 /// Query parameters for OGC API - Features endpoints
@@ -30,16 +62,22 @@ pub struct OGCFeaturesQuery {
     /// Bounding box filter in format "min_lon,min_lat,max_lon,max_lat"
     pub bbox: Option<String>,
     /// Date/time filter in ISO 8601 format
-    #[allow(dead_code)]
     pub datetime: Option<String>,
     /// Number of results to skip for pagination
     pub offset: Option<i32>,
     /// Sort parameters in format "field:direction,field:direction"
     pub sortby: Option<String>,
+    /// Minimal items for low-bandwidth clients: no links, assets as href and type
+    pub compact: Option<bool>,
+    /// CQL2 filter expression
+    pub filter: Option<String>,
+    /// Language of `filter`: "cql2-text" (default) or "cql2-json"
+    #[serde(rename = "filter-lang")]
+    pub filter_lang: Option<String>,
 }
 
 /// Request body for POST /search endpoint
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SearchBody {
     /// Maximum number of results to return
     pub limit: Option<i32>,
@@ -55,13 +93,81 @@ pub struct SearchBody {
     pub collections: Option<Vec<String>>,
     /// Array of sort fields and directions
     pub sortby: Option<Vec<SortByField>>,
+    /// Opaque keyset pagination token from a previous page's `next` link
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Query extension predicates on item properties
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<serde_json::Value>,
+    /// Free-text search; comma-separated terms match if any does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    /// Minimal items for low-bandwidth clients: no links, assets as href and type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compact: Option<bool>,
+    /// CQL2 filter, a CQL2-JSON object or a CQL2-text string
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<serde_json::Value>,
+    /// Language of `filter`; defaults to cql2-json for objects and cql2-text for strings
+    #[serde(
+        default,
+        rename = "filter-lang",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub filter_lang: Option<String>,
+    /// Whether the remote catalogs of `federated_catalogs` are searched too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub federated: Option<bool>,
 }
 
 /// Sort field specification for search results
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SortByField {
     /// Field name to sort by
     pub field: String,
     /// Sort direction: "asc" or "desc"
     pub direction: String,
 }
+
+impl From<SearchBody> for SearchQuery {
+    fn from(body: SearchBody) -> Self {
+        SearchQuery {
+            limit: body.limit,
+            bbox: body
+                .bbox
+                .map(|bbox| format!("{},{},{},{}", bbox[0], bbox[1], bbox[2], bbox[3])),
+            datetime: body.datetime,
+            intersects: body
+                .intersects
+                .map(|geom| serde_json::to_string(&geom).unwrap_or_default()),
+            ids: body.ids.map(|ids| ids.join(",")),
+            collections: body.collections.map(|cols| cols.join(",")),
+            sortby: body.sortby.map(|sortby| {
+                sortby
+                    .iter()
+                    .map(|s| format!("{}:{}", s.field, s.direction))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }),
+            token: body.token,
+            query: body.query.map(|query| query.to_string()),
+            q: body.q,
+            compact: body.compact,
+            federated: body.federated,
+            filter_lang: body.filter_lang.or_else(|| {
+                body.filter.as_ref().map(|filter| {
+                    if filter.is_string() {
+                        "cql2-text"
+                    } else {
+                        "cql2-json"
+                    }
+                    .to_string()
+                })
+            }),
+            filter: body.filter.map(|filter| match filter {
+                serde_json::Value::String(text) => text,
+                other => other.to_string(),
+            }),
+        }
+    }
+}
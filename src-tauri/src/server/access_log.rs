@@ -0,0 +1,326 @@
+use crate::database::access_stats::{AccessKind, AccessRecord};
+use crate::database::settings::{ItemAccessStats, ItemStatsOutput, Setting};
+use crate::database::DatabaseService;
+use crate::models::link::Link;
+use crate::models::Item;
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    body::HttpBody,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, HeaderValue, Method},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+
+/// Middleware recording successful GET requests for collections, items and
+/// assets into the access statistics tables.
+///
+/// Only requests arriving over the network are counted; in-process requests
+/// from the desktop UI carry no connection info and are skipped.
+pub async fn access_log(
+    State(state): State<AppState>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let client = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| client_id(req.headers(), addr));
+    let target = if req.method() == Method::GET {
+        parse_target(req.uri().path(), &state.config.server.api_version)
+    } else {
+        None
+    };
+
+    let response = next.run(req).await;
+
+    if let (Some(client), Some((collection_id, item_id, asset_key))) = (client, target) {
+        if response.status().is_success() {
+            let bytes = response
+                .headers()
+                .get("Content-Length")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .or_else(|| response.body().size_hint().exact())
+                .unwrap_or(0);
+            let record = AccessRecord {
+                day: chrono::Utc::now().format("%Y-%m-%d").to_string(),
+                collection_id,
+                item_id,
+                asset_key,
+                client,
+                bytes,
+            };
+            let access_stats = state.db_service.access_stats.clone();
+            tokio::spawn(async move {
+                if let Err(e) = access_stats.record(&record).await {
+                    eprintln!("Failed to record access statistics: {}", e);
+                }
+            });
+        }
+    }
+
+    response
+}
+
+/// Anonymised client identifier: a hash of the forwarded or peer address
+pub(crate) fn client_id(headers: &HeaderMap, addr: &SocketAddr) -> String {
+    let forwarded = headers
+        .get("X-Forwarded-For")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .map(|v| v.trim().to_string());
+    let address = forwarded.unwrap_or_else(|| addr.ip().to_string());
+
+    let mut hasher = DefaultHasher::new();
+    address.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Extracts (collection, item, asset) from a collection, item or asset URL
+fn parse_target(path: &str, api_path: &str) -> Option<(String, Option<String>, Option<String>)> {
+    let rest = path.strip_prefix(api_path)?.strip_prefix("/collections/")?;
+    let segments: Vec<String> = rest
+        .split('/')
+        .map(|s| urlencoding::decode(s).map(|s| s.into_owned()).unwrap_or_else(|_| s.to_string()))
+        .collect();
+
+    match segments.as_slice() {
+        [collection] if collection != "sortables" => Some((collection.clone(), None, None)),
+        [collection, items, item] if items == "items" => {
+            Some((collection.clone(), Some(item.clone()), None))
+        }
+        [collection, items, item, asset]
+            if items == "items" && !["related", "stats", "cleanup-preview"].contains(&asset.as_str()) =>
+        {
+            Some((collection.clone(), Some(item.clone()), Some(asset.clone())))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccessStatsQuery {
+    /// "collection", "item" or "asset" (default)
+    pub kind: Option<String>,
+    /// Number of days to look back, including today (default 7)
+    pub days: Option<u32>,
+    /// Maximum number of entries (default 10)
+    pub limit: Option<usize>,
+}
+
+/// Handler for the most requested collections, items or assets (GET /stats/access)
+pub async fn access_stats(
+    Query(query): Query<AccessStatsQuery>,
+    State(state): State<AppState>,
+) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+
+    let kind_name = query.kind.unwrap_or_else(|| "asset".to_string());
+    let Some(kind) = AccessKind::parse(&kind_name) else {
+        return ApiError::invalid_parameter(format!(
+            "Unknown kind '{}'. Use collection, item or asset",
+            kind_name
+        ))
+        .into_response();
+    };
+
+    match top_accessed(&state.db_service, kind, query.days.unwrap_or(7), query.limit.unwrap_or(10)).await {
+        Ok(result) => (headers, serde_json::to_string(&result).unwrap()).into_response(),
+        Err(e) => {
+            ApiError::internal(format!("Failed to read access statistics: {}", e)).into_response()
+        }
+    }
+}
+
+/// Top entries of `kind` over the last `days` days. Shared by the HTTP endpoint
+/// and the Tauri command.
+pub async fn top_accessed(
+    db_service: &DatabaseService,
+    kind: AccessKind,
+    days: u32,
+    limit: usize,
+) -> Result<serde_json::Value, rusqlite::Error> {
+    let days = days.clamp(1, 366);
+    let since = (chrono::Utc::now() - chrono::Duration::days(i64::from(days) - 1))
+        .format("%Y-%m-%d")
+        .to_string();
+    let stats = db_service
+        .access_stats
+        .top(kind, &since, limit.clamp(1, 1000))
+        .await?;
+
+    Ok(serde_json::json!({
+        "since": since,
+        "days": days,
+        "stats": stats
+    }))
+}
+
+/// An item about to be served, typed or already converted to JSON, that
+/// access statistics can be added to
+pub trait ServedItem {
+    /// Collection and item id
+    fn key(&self) -> Option<(String, String)>;
+    fn push_link(&mut self, link: Link);
+    fn set_downloads(&mut self, downloads: u64);
+}
+
+impl ServedItem for Item {
+    fn key(&self) -> Option<(String, String)> {
+        Some((self.collection.clone()?, self.id.clone()))
+    }
+
+    fn push_link(&mut self, link: Link) {
+        self.links.push(link);
+    }
+
+    fn set_downloads(&mut self, downloads: u64) {
+        self.properties
+            .additional_fields
+            .insert("zenstac:downloads".to_string(), serde_json::json!(downloads));
+    }
+}
+
+impl ServedItem for Value {
+    fn key(&self) -> Option<(String, String)> {
+        Some((
+            self.get("collection")?.as_str()?.to_string(),
+            self.get("id")?.as_str()?.to_string(),
+        ))
+    }
+
+    fn push_link(&mut self, link: Link) {
+        if let Some(links) = self.get_mut("links").and_then(Value::as_array_mut) {
+            links.push(serde_json::to_value(link).unwrap_or(Value::Null));
+        }
+    }
+
+    fn set_downloads(&mut self, downloads: u64) {
+        if let Some(properties) = self.get_mut("properties").and_then(Value::as_object_mut) {
+            properties.insert("zenstac:downloads".to_string(), serde_json::json!(downloads));
+        }
+    }
+}
+
+/// Adds access statistics to items about to be served, as configured by the
+/// `item_access_stats` setting. Off by default, so publishers opt in to
+/// showing usage.
+pub async fn add_item_access_stats<T: ServedItem>(
+    db_service: &DatabaseService,
+    server_config: &ServerConfig,
+    items: &mut [T],
+) {
+    let output = db_service
+        .settings
+        .get::<ItemAccessStats>()
+        .await
+        .unwrap_or_else(|_| ItemAccessStats::default_value());
+
+    match output {
+        ItemStatsOutput::Off => {}
+        ItemStatsOutput::Link => {
+            for item in items.iter_mut() {
+                let Some((collection_id, item_id)) = item.key() else {
+                    continue;
+                };
+                item.push_link(stats_link(server_config, &collection_id, &item_id));
+            }
+        }
+        ItemStatsOutput::Property => {
+            let keys: Vec<(String, String)> = items.iter().filter_map(T::key).collect();
+            let totals = match db_service.access_stats.item_totals(&keys).await {
+                Ok(totals) => totals,
+                Err(e) => {
+                    eprintln!("Failed to read item access statistics: {}", e);
+                    return;
+                }
+            };
+            for item in items.iter_mut() {
+                let Some(key) = item.key() else {
+                    continue;
+                };
+                item.set_downloads(totals.get(&key).map(|t| t.downloads).unwrap_or(0));
+            }
+        }
+    }
+}
+
+fn stats_link(server_config: &ServerConfig, collection_id: &str, item_id: &str) -> Link {
+    Link {
+        href: format!("{}/stats", server_config.item_href(collection_id, item_id)),
+        rel: "stats".to_string(),
+        r#type: Some("application/json".to_string()),
+        title: Some("Access statistics".to_string()),
+        method: None,
+        headers: None,
+        body: None,
+    }
+}
+
+/// Handler for the all-time access statistics of an item and its assets
+/// (GET /collections/:collection_id/items/:item_id/stats). Answers 404 unless
+/// `item_access_stats` is enabled.
+pub async fn item_access_stats(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+
+    let output = state
+        .db_service
+        .settings
+        .get::<ItemAccessStats>()
+        .await
+        .unwrap_or_else(|_| ItemAccessStats::default_value());
+    let exists = matches!(
+        state.db_service.items.get_by_id(&collection_id, &item_id).await,
+        Ok(Some(_))
+    );
+    if output == ItemStatsOutput::Off || !exists {
+        return ApiError::not_found(format!(
+            "No statistics for item '{}' in collection '{}'",
+            item_id, collection_id
+        ))
+        .into_response();
+    }
+
+    let key = (collection_id.clone(), item_id.clone());
+    let totals = state.db_service.access_stats.item_totals(std::slice::from_ref(&key)).await;
+    let assets = state
+        .db_service
+        .access_stats
+        .item_assets(&collection_id, &item_id)
+        .await;
+    match (totals, assets) {
+        (Ok(mut totals), Ok(assets)) => {
+            let result = serde_json::json!({
+                "collection_id": collection_id,
+                "item_id": item_id,
+                "totals": totals.remove(&key).unwrap_or_default(),
+                "assets": assets
+            });
+            (headers, serde_json::to_string(&result).unwrap()).into_response()
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            ApiError::internal(format!("Failed to read access statistics: {}", e)).into_response()
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use crate::config::Config;
+use crate::database::settings::{
+    AcmeChallengePort, AcmeDirectoryUrl, AcmeDomain, AcmeEmail, HttpsPort, Setting,
+};
+use crate::database::DatabaseService;
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, NewAccount,
+    NewOrder, Order, OrderStatus,
+};
+use rustls::pki_types::CertificateDer;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+/// Certificates are renewed once this old; Let's Encrypt issues them for 90 days
+const RENEW_AFTER: Duration = Duration::from_secs(60 * 24 * 60 * 60);
+
+/// How often the certificate's age is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Wait before retrying a failed order
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Polling of an order's status while the authority validates or issues it
+const ORDER_POLL: Duration = Duration::from_secs(2);
+const ORDER_POLL_ATTEMPTS: u32 = 30;
+
+/// Key authorizations of pending HTTP-01 challenges, by token
+type Challenges = Arc<Mutex<HashMap<String, String>>>;
+
+/// ACME settings of an instance serving HTTPS
+#[derive(Debug, Clone)]
+pub struct AcmeSettings {
+    pub domain: String,
+    pub email: String,
+    pub directory_url: String,
+    pub challenge_port: u16,
+    pub https_port: u16,
+}
+
+impl AcmeSettings {
+    /// The ACME settings, or `None` when no domain is set and the server
+    /// speaks plain HTTP only
+    pub async fn load(db_service: &DatabaseService) -> Option<Self> {
+        let settings = &db_service.settings;
+        let domain = settings
+            .get::<AcmeDomain>()
+            .await
+            .unwrap_or_else(|_| AcmeDomain::default_value());
+        if domain.is_empty() {
+            return None;
+        }
+        Some(Self {
+            domain,
+            email: settings
+                .get::<AcmeEmail>()
+                .await
+                .unwrap_or_else(|_| AcmeEmail::default_value()),
+            directory_url: settings
+                .get::<AcmeDirectoryUrl>()
+                .await
+                .unwrap_or_else(|_| AcmeDirectoryUrl::default_value()),
+            challenge_port: settings
+                .get::<AcmeChallengePort>()
+                .await
+                .unwrap_or_else(|_| AcmeChallengePort::default_value()),
+            https_port: settings
+                .get::<HttpsPort>()
+                .await
+                .unwrap_or_else(|_| HttpsPort::default_value()),
+        })
+    }
+}
+
+/// Certificate presented by the HTTPS listener. Renewals replace it in place,
+/// so new connections get the new certificate without a restart.
+#[derive(Debug, Default)]
+pub struct CertificateResolver {
+    current: RwLock<Option<Arc<CertifiedKey>>>,
+}
+
+impl CertificateResolver {
+    fn set(&self, key: Arc<CertifiedKey>) {
+        *self.current.write().unwrap() = Some(key);
+    }
+
+    fn has_certificate(&self) -> bool {
+        self.current.read().unwrap().is_some()
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+/// ACME account as stored in the app data, with the directory it belongs to
+#[derive(Serialize, Deserialize)]
+struct StoredAccount {
+    directory_url: String,
+    credentials: AccountCredentials,
+}
+
+/// Serves `app` over HTTPS for the ACME domain, next to the plain HTTP
+/// listener. The challenge port answers HTTP-01 challenges and redirects
+/// everything else to HTTPS. A stored certificate is served right away; a
+/// missing or ageing one is ordered in the background, and until the first
+/// one arrives TLS handshakes fail.
+pub async fn serve_https(
+    host: &str,
+    app: Router,
+    settings: AcmeSettings,
+    config: &Config,
+) -> Result<(), String> {
+    if settings.challenge_port == settings.https_port {
+        return Err("The ACME challenge port and the HTTPS port must differ".to_string());
+    }
+    let acme_dir = PathBuf::from(config.acme_dir());
+    let cert_dir = acme_dir.join(&settings.domain);
+
+    let resolver = Arc::new(CertificateResolver::default());
+    match load_certificate(&cert_dir) {
+        Ok(key) => resolver.set(key),
+        Err(e) if cert_dir.exists() => {
+            eprintln!("Stored certificate for {} is unusable: {}", settings.domain, e)
+        }
+        Err(_) => {}
+    }
+    let challenges = Challenges::default();
+
+    let challenge_listener = TcpListener::bind(format!("{}:{}", host, settings.challenge_port))
+        .await
+        .map_err(|e| {
+            format!(
+                "Failed to bind ACME challenge port {}: {}",
+                settings.challenge_port, e
+            )
+        })?;
+    let https_listener = TcpListener::bind(format!("{}:{}", host, settings.https_port))
+        .await
+        .map_err(|e| format!("Failed to bind HTTPS port {}: {}", settings.https_port, e))?;
+
+    tokio::select! {
+        result = serve_challenges(challenge_listener, challenges.clone(), &settings) => result,
+        result = serve_tls(https_listener, app, resolver.clone()) => result,
+        result = keep_certificate(&settings, &acme_dir, &cert_dir, &resolver, &challenges) => result,
+    }
+}
+
+/// HTTP listener answering HTTP-01 challenges and redirecting other requests
+/// to HTTPS
+async fn serve_challenges(
+    listener: TcpListener,
+    challenges: Challenges,
+    settings: &AcmeSettings,
+) -> Result<(), String> {
+    let origin = match settings.https_port {
+        443 => format!("https://{}", settings.domain),
+        port => format!("https://{}:{}", settings.domain, port),
+    };
+    let router = Router::new()
+        .route("/.well-known/acme-challenge/:token", get(challenge_response))
+        .fallback(move |uri: Uri| async move {
+            let path = uri.path_and_query().map_or("/", |p| p.as_str());
+            Redirect::permanent(&format!("{}{}", origin, path))
+        })
+        .with_state(challenges);
+    axum::serve(listener, router)
+        .await
+        .map_err(|e| format!("ACME challenge listener failed: {}", e))
+}
+
+/// Key authorization of a pending challenge (GET /.well-known/acme-challenge/:token)
+async fn challenge_response(
+    Path(token): Path<String>,
+    State(challenges): State<Challenges>,
+) -> Response {
+    match challenges.lock().unwrap().get(&token) {
+        Some(key_authorization) => (StatusCode::OK, key_authorization.clone()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// TLS listener serving the router with the resolver's current certificate.
+/// Peer addresses are passed on like on the plain listener, for the access
+/// statistics and bandwidth limits.
+async fn serve_tls(
+    listener: TcpListener,
+    app: Router,
+    resolver: Arc<CertificateResolver>,
+) -> Result<(), String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut tls_config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to configure TLS: {}", e))?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    tls_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(tls_config));
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Failed to accept HTTPS connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            // Handshakes fail while no certificate has been issued yet
+            let Ok(stream) = acceptor.accept(stream).await else {
+                return;
+            };
+            let service = hyper::service::service_fn(
+                move |mut request: hyper::Request<hyper::body::Incoming>| {
+                    request.extensions_mut().insert(ConnectInfo(peer));
+                    tower::ServiceExt::oneshot(app.clone(), request)
+                },
+            );
+            // Errors here are clients going away mid-request
+            let _ = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+/// Orders a certificate whenever there is none or it is due for renewal
+async fn keep_certificate(
+    settings: &AcmeSettings,
+    acme_dir: &std::path::Path,
+    cert_dir: &std::path::Path,
+    resolver: &CertificateResolver,
+    challenges: &Challenges,
+) -> Result<(), String> {
+    loop {
+        let age = std::fs::metadata(cert_dir.join("cert.pem"))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        let wait = match age.filter(|_| resolver.has_certificate()) {
+            Some(age) if age < RENEW_AFTER => (RENEW_AFTER - age).min(CHECK_INTERVAL),
+            _ => match obtain_certificate(settings, acme_dir, cert_dir, challenges).await {
+                Ok(key) => {
+                    resolver.set(key);
+                    println!("Obtained a certificate for {}", settings.domain);
+                    CHECK_INTERVAL
+                }
+                Err(e) => {
+                    eprintln!("Failed to obtain a certificate for {}: {}", settings.domain, e);
+                    RETRY_INTERVAL
+                }
+            },
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Runs an ACME order for the domain over HTTP-01 and stores the issued
+/// certificate and its key under the domain's directory
+async fn obtain_certificate(
+    settings: &AcmeSettings,
+    acme_dir: &std::path::Path,
+    cert_dir: &std::path::Path,
+    challenges: &Challenges,
+) -> Result<Arc<CertifiedKey>, String> {
+    let account = load_account(settings, acme_dir).await?;
+    let identifiers = [Identifier::Dns(settings.domain.clone())];
+    let mut order = account
+        .new_order(&NewOrder {
+            identifiers: &identifiers,
+        })
+        .await
+        .map_err(|e| format!("Failed to create order: {}", e))?;
+    let authorizations = order
+        .authorizations()
+        .await
+        .map_err(|e| format!("Failed to read authorizations: {}", e))?;
+
+    let mut tokens = Vec::new();
+    let mut ready = Ok(());
+    for authorization in &authorizations {
+        match &authorization.status {
+            AuthorizationStatus::Pending => {}
+            AuthorizationStatus::Valid => continue,
+            status => {
+                ready = Err(format!("Authorization is {:?}", status));
+                break;
+            }
+        }
+        let Some(challenge) = authorization
+            .challenges
+            .iter()
+            .find(|c| c.r#type == ChallengeType::Http01)
+        else {
+            ready = Err("The authority offered no HTTP-01 challenge".to_string());
+            break;
+        };
+        let key_authorization = order.key_authorization(challenge);
+        challenges
+            .lock()
+            .unwrap()
+            .insert(challenge.token.clone(), key_authorization.as_str().to_string());
+        tokens.push(challenge.token.clone());
+        if let Err(e) = order.set_challenge_ready(&challenge.url).await {
+            ready = Err(format!("Failed to start validation: {}", e));
+            break;
+        }
+    }
+    let issued = match ready {
+        Ok(()) => finish_order(&mut order, &settings.domain).await,
+        Err(e) => Err(e),
+    };
+    {
+        let mut pending = challenges.lock().unwrap();
+        for token in &tokens {
+            pending.remove(token);
+        }
+    }
+    let (cert_pem, key_pem) = issued?;
+
+    let key = certified_key(cert_pem.as_bytes(), key_pem.as_bytes())?;
+    std::fs::create_dir_all(cert_dir)
+        .map_err(|e| format!("Failed to create {}: {}", cert_dir.display(), e))?;
+    std::fs::write(cert_dir.join("key.pem"), key_pem)
+        .and_then(|_| std::fs::write(cert_dir.join("cert.pem"), cert_pem))
+        .map_err(|e| format!("Failed to store certificate: {}", e))?;
+    Ok(key)
+}
+
+/// Waits for the authority to validate the order, then finalizes it with a
+/// new key and returns the certificate chain and key as PEM
+async fn finish_order(order: &mut Order, domain: &str) -> Result<(String, String), String> {
+    let mut status = OrderStatus::Pending;
+    for _ in 0..ORDER_POLL_ATTEMPTS {
+        tokio::time::sleep(ORDER_POLL).await;
+        status = order
+            .refresh()
+            .await
+            .map_err(|e| format!("Failed to read order: {}", e))?
+            .status;
+        if matches!(status, OrderStatus::Ready | OrderStatus::Invalid) {
+            break;
+        }
+    }
+    if !matches!(status, OrderStatus::Ready) {
+        return Err(format!(
+            "Order is {:?}; check that http://{}/.well-known/acme-challenge/ reaches the challenge port",
+            status, domain
+        ));
+    }
+
+    let mut params = rcgen::CertificateParams::new(vec![domain.to_string()])
+        .map_err(|e| format!("Invalid domain: {}", e))?;
+    params.distinguished_name = rcgen::DistinguishedName::new();
+    let key_pair = rcgen::KeyPair::generate().map_err(|e| format!("Failed to create key: {}", e))?;
+    let csr = params
+        .serialize_request(&key_pair)
+        .map_err(|e| format!("Failed to create certificate request: {}", e))?;
+    order
+        .finalize(csr.der())
+        .await
+        .map_err(|e| format!("Failed to finalize order: {}", e))?;
+
+    for _ in 0..ORDER_POLL_ATTEMPTS {
+        match order.certificate().await {
+            Ok(Some(cert_pem)) => return Ok((cert_pem, key_pair.serialize_pem())),
+            Ok(None) => tokio::time::sleep(ORDER_POLL).await,
+            Err(e) => return Err(format!("Failed to download certificate: {}", e)),
+        }
+    }
+    Err("The certificate was not issued in time".to_string())
+}
+
+/// The stored ACME account for the directory, registering one on first use
+async fn load_account(
+    settings: &AcmeSettings,
+    acme_dir: &std::path::Path,
+) -> Result<Account, String> {
+    let account_file = acme_dir.join("account.json");
+    let stored = std::fs::read_to_string(&account_file)
+        .ok()
+        .and_then(|json| serde_json::from_str::<StoredAccount>(&json).ok())
+        .filter(|stored| stored.directory_url == settings.directory_url);
+    if let Some(stored) = stored {
+        return Account::from_credentials(stored.credentials)
+            .await
+            .map_err(|e| format!("Failed to load ACME account: {}", e));
+    }
+
+    let contact = format!("mailto:{}", settings.email);
+    let contacts: Vec<&str> = if settings.email.is_empty() {
+        Vec::new()
+    } else {
+        vec![contact.as_str()]
+    };
+    let (account, credentials) = Account::create(
+        &NewAccount {
+            contact: &contacts,
+            terms_of_service_agreed: true,
+            only_return_existing: false,
+        },
+        &settings.directory_url,
+        None,
+    )
+    .await
+    .map_err(|e| format!("Failed to register ACME account: {}", e))?;
+
+    let stored = StoredAccount {
+        directory_url: settings.directory_url.clone(),
+        credentials,
+    };
+    std::fs::create_dir_all(acme_dir)
+        .and_then(|_| {
+            std::fs::write(
+                &account_file,
+                serde_json::to_string_pretty(&stored).unwrap_or_default(),
+            )
+        })
+        .map_err(|e| format!("Failed to store ACME account: {}", e))?;
+    Ok(account)
+}
+
+fn load_certificate(cert_dir: &std::path::Path) -> Result<Arc<CertifiedKey>, String> {
+    let cert_pem = std::fs::read(cert_dir.join("cert.pem")).map_err(|e| e.to_string())?;
+    let key_pem = std::fs::read(cert_dir.join("key.pem")).map_err(|e| e.to_string())?;
+    certified_key(&cert_pem, &key_pem)
+}
+
+/// Certificate chain and signing key for rustls from PEM
+fn certified_key(cert_pem: &[u8], key_pem: &[u8]) -> Result<Arc<CertifiedKey>, String> {
+    let chain: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut &cert_pem[..])
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Invalid certificate: {}", e))?;
+    if chain.is_empty() {
+        return Err("No certificate found".to_string());
+    }
+    let key = rustls_pemfile::private_key(&mut &key_pem[..])
+        .map_err(|e| format!("Invalid key: {}", e))?
+        .ok_or_else(|| "No private key found".to_string())?;
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(&key)
+        .map_err(|e| format!("Unsupported key: {}", e))?;
+    Ok(Arc::new(CertifiedKey::new(chain, signing_key)))
+}
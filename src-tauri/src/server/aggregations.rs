@@ -0,0 +1,354 @@
+use crate::database::full_text;
+use crate::database::indexed_properties::check_property_name;
+use crate::database::{AggregateKey, ItemBounds, PropertyPredicate};
+use crate::server::error::ApiError;
+use crate::server::helpers::{parse_datetime_interval, parse_query};
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Aggregations computed when a request names none
+const DEFAULT_AGGREGATIONS: &[&str] = &[
+    "total_count",
+    "datetime_min",
+    "datetime_max",
+    "datetime_frequency",
+    "collection_frequency",
+];
+
+/// Most buckets returned per property term frequency; the items of the
+/// remaining values are counted as `overflow`
+const TERM_BUCKET_LIMIT: usize = 100;
+
+/// Query of GET /aggregations and GET /collections/:collection_id/aggregations
+#[derive(Debug, Default, Deserialize)]
+pub struct AggregationQuery {
+    /// Comma-separated aggregation names, e.g. `total_count,platform_frequency`
+    pub aggregations: Option<String>,
+    /// Comma-separated collection IDs; all collections when absent
+    pub collections: Option<String>,
+    /// Bounding box filter in format "min_lon,min_lat,max_lon,max_lat"
+    pub bbox: Option<String>,
+    /// Date/time filter in ISO 8601 format
+    pub datetime: Option<String>,
+    /// Comma-separated list of item IDs to filter by
+    pub ids: Option<String>,
+    /// Query extension object as JSON, e.g. `{"eo:cloud_cover":{"lt":10}}`
+    pub query: Option<String>,
+    /// Free-text search; comma-separated terms match if any does
+    pub q: Option<String>,
+    /// Bucket size of `datetime_frequency`: year, month (default), day or hour
+    pub datetime_frequency_interval: Option<String>,
+    /// Bucket width of `cloud_cover_frequency` in percent; 10 by default
+    pub cloud_cover_frequency_interval: Option<f64>,
+}
+
+/// One requested aggregation
+enum Aggregation {
+    TotalCount,
+    DatetimeMin,
+    DatetimeMax,
+    Frequency(AggregateKey),
+}
+
+/// Aggregation of a name: the fixed ones, `cloud_cover_frequency` over
+/// `eo:cloud_cover` buckets, or `<property>_frequency` for the values of any
+/// other property
+fn parse_aggregation(name: &str, query: &AggregationQuery) -> Result<Aggregation, String> {
+    Ok(match name {
+        "total_count" => Aggregation::TotalCount,
+        "datetime_min" => Aggregation::DatetimeMin,
+        "datetime_max" => Aggregation::DatetimeMax,
+        "collection_frequency" => Aggregation::Frequency(AggregateKey::Collection),
+        "datetime_frequency" => {
+            let length = match query.datetime_frequency_interval.as_deref().unwrap_or("month") {
+                "year" => 4,
+                "month" => 7,
+                "day" => 10,
+                "hour" => 13,
+                other => {
+                    return Err(format!(
+                        "Unknown datetime_frequency_interval '{}'; use year, month, day or hour",
+                        other
+                    ))
+                }
+            };
+            Aggregation::Frequency(AggregateKey::DatetimePrefix(length))
+        }
+        "cloud_cover_frequency" => {
+            let interval = query.cloud_cover_frequency_interval.unwrap_or(10.0);
+            if !interval.is_finite() || interval <= 0.0 {
+                return Err("cloud_cover_frequency_interval must be a positive number".to_string());
+            }
+            Aggregation::Frequency(AggregateKey::PropertyBucket {
+                property: "eo:cloud_cover".to_string(),
+                interval,
+            })
+        }
+        _ => match name.strip_suffix("_frequency").filter(|p| !p.is_empty()) {
+            Some(property) => {
+                check_property_name(property)?;
+                Aggregation::Frequency(AggregateKey::Property(property.to_string()))
+            }
+            None => return Err(format!("Unknown aggregation '{}'", name)),
+        },
+    })
+}
+
+/// Predicates and bounds of the items to aggregate
+fn parse_filters(query: &AggregationQuery) -> Result<(Vec<PropertyPredicate>, ItemBounds), String> {
+    let mut bounds = ItemBounds::default();
+    if let Some(datetime) = &query.datetime {
+        bounds.datetime =
+            Some(parse_datetime_interval(datetime).map_err(|e| format!("Invalid datetime: {}", e))?);
+    }
+    if let Some(bbox) = &query.bbox {
+        let numbers: Vec<f64> = bbox
+            .split(',')
+            .map(|s| s.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "Invalid bbox: expected four comma-separated numbers".to_string())?;
+        match numbers[..] {
+            [min_x, min_y, max_x, max_y] => bounds.bbox = Some([min_x, min_y, max_x, max_y]),
+            _ => return Err("Invalid bbox: expected four comma-separated numbers".to_string()),
+        }
+    }
+    if let Some(ids) = &query.ids {
+        let ids: Vec<String> = ids
+            .split(',')
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect();
+        if !ids.is_empty() {
+            bounds.ids = Some(ids);
+        }
+    }
+    if let Some(q) = &query.q {
+        bounds.text = full_text::match_expression(q);
+    }
+    let predicates = match &query.query {
+        Some(query) => serde_json::from_str(query)
+            .map_err(|e| format!("query is not JSON: {}", e))
+            .and_then(|value| parse_query(&value))
+            .map_err(|e| format!("Invalid query: {}", e))?,
+        None => Vec::new(),
+    };
+    Ok((predicates, bounds))
+}
+
+/// Full date-time of a stored datetime prefix, e.g. "2024-03" to
+/// "2024-03-01T00:00:00Z"
+fn bucket_datetime(prefix: &str) -> String {
+    const START: &str = "0000-01-01T00:00:00Z";
+    let prefix = prefix.get(..prefix.len().min(START.len())).unwrap_or(prefix);
+    format!("{}{}", prefix, &START[prefix.len()..])
+}
+
+/// Stored UTC date-time in the RFC 3339 form of STAC datetimes
+fn stored_datetime(value: Option<String>) -> Value {
+    value
+        .and_then(|v| chrono::DateTime::parse_from_rfc3339(&v).ok())
+        .map(|dt| {
+            json!(dt
+                .with_timezone(&chrono::Utc)
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        })
+        .unwrap_or(Value::Null)
+}
+
+fn bucket(key: String, frequency: i64, from: Value, to: Value) -> Value {
+    json!({
+        "key": key,
+        "data_type": "frequency_distribution",
+        "frequency": frequency,
+        "from": from,
+        "to": to
+    })
+}
+
+/// Computes the requested aggregations over the matching items of
+/// `collection_ids`
+async fn aggregate(
+    state: &AppState,
+    collection_ids: &[String],
+    query: &AggregationQuery,
+    self_href: String,
+) -> Result<Response, ApiError> {
+    let names: Vec<&str> = match query.aggregations.as_deref() {
+        Some(names) => names.split(',').map(str::trim).filter(|n| !n.is_empty()).collect(),
+        None => DEFAULT_AGGREGATIONS.to_vec(),
+    };
+    let aggregations = names
+        .iter()
+        .map(|name| parse_aggregation(name, query).map(|a| (*name, a)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ApiError::bad_request)?;
+    let (predicates, bounds) = parse_filters(query).map_err(ApiError::bad_request)?;
+
+    let items = &state.db_service.items;
+    let mut results = Vec::new();
+    let mut range = None;
+    for (name, aggregation) in aggregations {
+        let result = match aggregation {
+            Aggregation::TotalCount => items
+                .count_matching(collection_ids, &predicates, &bounds)
+                .await
+                .map(|count| json!({ "name": name, "data_type": "integer", "value": count })),
+            Aggregation::DatetimeMin | Aggregation::DatetimeMax => {
+                if range.is_none() {
+                    range = Some(
+                        items
+                            .datetime_range(collection_ids, &predicates, &bounds)
+                            .await
+                            .map_err(database_error)?,
+                    );
+                }
+                let (min, max) = range.clone().unwrap_or_default();
+                let value = match aggregation {
+                    Aggregation::DatetimeMin => min,
+                    _ => max,
+                };
+                Ok(json!({ "name": name, "data_type": "datetime", "value": stored_datetime(value) }))
+            }
+            Aggregation::Frequency(key) => items
+                .group_counts(collection_ids, &predicates, &bounds, &key)
+                .await
+                .map(|groups| frequency_result(name, &key, groups)),
+        };
+        results.push(result.map_err(database_error)?);
+    }
+
+    let server_config = ServerConfig::from_config(&state.config);
+    let body = json!({
+        "type": "AggregationCollection",
+        "aggregations": results,
+        "links": [
+            {
+                "rel": "self",
+                "type": "application/json",
+                "href": self_href
+            },
+            {
+                "rel": "root",
+                "type": "application/json",
+                "href": server_config.root_href()
+            }
+        ]
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    Ok((headers, serde_json::to_string(&body).unwrap()).into_response())
+}
+
+/// Frequency distribution of grouped counts. Term frequencies keep the
+/// [`TERM_BUCKET_LIMIT`] most frequent values and count the rest as overflow.
+fn frequency_result(name: &str, key: &AggregateKey, groups: Vec<(Value, i64)>) -> Value {
+    let value_text = |value: &Value| match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    let mut overflow = 0;
+    let buckets: Vec<Value> = match key {
+        AggregateKey::DatetimePrefix(_) => groups
+            .iter()
+            .map(|(k, n)| bucket(bucket_datetime(&value_text(k)), *n, Value::Null, Value::Null))
+            .collect(),
+        AggregateKey::PropertyBucket { interval, .. } => groups
+            .iter()
+            .map(|(k, n)| {
+                let from = k.as_f64().unwrap_or_default();
+                bucket(value_text(k), *n, json!(from), json!(from + interval))
+            })
+            .collect(),
+        AggregateKey::Collection | AggregateKey::Property(_) => {
+            overflow = groups.iter().skip(TERM_BUCKET_LIMIT).map(|(_, n)| n).sum();
+            groups
+                .iter()
+                .take(TERM_BUCKET_LIMIT)
+                .map(|(k, n)| bucket(value_text(k), *n, Value::Null, Value::Null))
+                .collect()
+        }
+    };
+    json!({
+        "name": name,
+        "data_type": "frequency_distribution",
+        "overflow": overflow,
+        "buckets": buckets
+    })
+}
+
+fn database_error(e: rusqlite::Error) -> ApiError {
+    eprintln!("Failed to compute aggregations: {}", e);
+    ApiError::internal("Failed to compute aggregations")
+}
+
+/// Handler for counts, datetime histograms and property term frequencies of
+/// the items of all or the listed collections (GET /aggregations)
+pub async fn aggregations(
+    Query(query): Query<AggregationQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let listed: Vec<String> = query
+        .collections
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    let collection_ids = if listed.is_empty() {
+        let collections = state
+            .db_service
+            .collections
+            .get_all()
+            .await
+            .map_err(database_error)?;
+        collections.into_iter().map(|c| c.id).collect()
+    } else {
+        listed
+    };
+    let server_config = ServerConfig::from_config(&state.config);
+    aggregate(
+        &state,
+        &collection_ids,
+        &query,
+        server_config.href("/aggregations"),
+    )
+    .await
+}
+
+/// Handler for the aggregations of one collection's items
+/// (GET /collections/:collection_id/aggregations)
+pub async fn collection_aggregations(
+    Path(collection_id): Path<String>,
+    Query(query): Query<AggregationQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    state
+        .db_service
+        .collections
+        .get_by_id(&collection_id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(|| ApiError::not_found(format!("Collection '{}' not found", collection_id)))?;
+    let server_config = ServerConfig::from_config(&state.config);
+    aggregate(
+        &state,
+        std::slice::from_ref(&collection_id),
+        &query,
+        format!("{}/aggregations", server_config.collection_href(&collection_id)),
+    )
+    .await
+}
@@ -0,0 +1,393 @@
+use crate::config::Config;
+use crate::database::settings::{DeleteConfirmThresholdBytes, Setting};
+use crate::database::DatabaseService;
+use crate::server::error::ApiError;
+use crate::server::files;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path as FsPath, PathBuf};
+
+/// Most files listed in a cleanup report; totals always cover every file
+pub(crate) const MAX_LISTED_FILES: usize = 1000;
+
+/// Moving a folder fails while another process (an indexer, a virus scanner,
+/// a viewer on Windows) holds one of its files open, so moves are retried
+const MOVE_ATTEMPTS: u32 = 5;
+const MOVE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Metadata file kept beside the assets of a trash entry
+const TRASH_ENTRY_FILE: &str = "entry.json";
+/// Folder of a trash entry holding the moved assets
+const TRASH_ASSETS_DIR: &str = "assets";
+/// Folder of the trash directory holding asset folders while they are purged
+const PURGING_DIR: &str = "purging";
+
+/// A file a deletion would remove, relative to the assets directory
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupFile {
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// What deleting a collection or item would remove from disk
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupReport {
+    pub collection_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    pub files: Vec<CleanupFile>,
+    /// More files exist than are listed
+    pub files_truncated: bool,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub confirm_threshold_bytes: u64,
+    /// The deletion only goes ahead with `confirm=true`
+    pub requires_confirmation: bool,
+}
+
+/// `confirm` flag accepted by collection and item deletes
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteQuery {
+    pub confirm: Option<bool>,
+}
+
+/// Asset directory of a collection, or of one of its items
+pub fn assets_path(config: &Config, collection_id: &str, item_id: Option<&str>) -> PathBuf {
+    let collection_dir = FsPath::new(&config.assets_dir()).join(collection_id);
+    match item_id {
+        Some(item_id) => collection_dir.join(item_id),
+        None => collection_dir,
+    }
+}
+
+/// Lists the files a delete of the collection or item would remove, without
+/// touching them
+pub async fn cleanup_report(
+    db_service: &DatabaseService,
+    config: &Config,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> CleanupReport {
+    let threshold = db_service
+        .settings
+        .get::<DeleteConfirmThresholdBytes>()
+        .await
+        .unwrap_or_else(|_| DeleteConfirmThresholdBytes::default_value());
+
+    let root = PathBuf::from(config.assets_dir());
+    let dir = assets_path(config, collection_id, item_id);
+    let mut files: Vec<CleanupFile> = files::list_files(dir)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|file| CleanupFile {
+            path: file
+                .path
+                .strip_prefix(&root)
+                .unwrap_or(&file.path)
+                .to_string_lossy()
+                .replace('\\', "/"),
+            bytes: file.bytes,
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let file_count = files.len();
+    let total_bytes = files.iter().map(|f| f.bytes).sum();
+    let files_truncated = file_count > MAX_LISTED_FILES;
+    files.truncate(MAX_LISTED_FILES);
+
+    CleanupReport {
+        collection_id: collection_id.to_string(),
+        item_id: item_id.map(str::to_string),
+        files,
+        files_truncated,
+        file_count,
+        total_bytes,
+        confirm_threshold_bytes: threshold,
+        requires_confirmation: file_count > 0 && total_bytes > threshold,
+    }
+}
+
+/// 409 answer to a delete that would remove more than the configured amount of
+/// asset data without `confirm=true`. The report tells the client what is at
+/// stake.
+pub fn confirmation_required_response(report: &CleanupReport) -> Response {
+    ApiError::new(
+        StatusCode::CONFLICT,
+        "ConfirmationRequired",
+        format!(
+            "Deleting removes {} files ({} bytes), more than the {} bytes that can be removed without confirmation. Repeat the request with confirm=true.",
+            report.file_count, report.total_bytes, report.confirm_threshold_bytes
+        ),
+    )
+    .with_extension("cleanup", serde_json::json!(report))
+    .into_response()
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+fn not_found(description: String) -> Response {
+    ApiError::not_found(description).into_response()
+}
+
+/// Handler previewing the asset files deleting a collection would remove
+/// (GET /collections/:collection_id/cleanup-preview)
+pub async fn collection_cleanup_preview(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    if !matches!(
+        state.db_service.collections.get_by_id(&collection_id).await,
+        Ok(Some(_))
+    ) {
+        return not_found(format!("Collection '{}' not found", collection_id));
+    }
+    let report = cleanup_report(&state.db_service, &state.config, &collection_id, None).await;
+    json_response(StatusCode::OK, serde_json::json!(report))
+}
+
+/// Handler previewing the asset files deleting an item would remove
+/// (GET /collections/:collection_id/items/:item_id/cleanup-preview)
+pub async fn item_cleanup_preview(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Response {
+    if !matches!(
+        state
+            .db_service
+            .items
+            .get_by_id(&collection_id, &item_id)
+            .await,
+        Ok(Some(_))
+    ) {
+        return not_found(format!(
+            "Item '{}' not found in collection '{}'",
+            item_id, collection_id
+        ));
+    }
+    let report = cleanup_report(
+        &state.db_service,
+        &state.config,
+        &collection_id,
+        Some(&item_id),
+    )
+    .await;
+    json_response(StatusCode::OK, serde_json::json!(report))
+}
+
+/// Asset folder moved to the trash directory on its own, without its record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// `<yyyymmdd>-<hhmmss>-<suffix>`; the date picks the trash folder
+    pub id: String,
+    pub collection_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    pub deleted_at: String,
+}
+
+/// Removes asset folders of collections and items. Folders cleaned up on
+/// their own are moved into a dated trash directory that the retention
+/// scheduler purges; those of records purged from the trash are deleted.
+#[derive(Debug, Clone)]
+pub struct AssetCleanup {
+    assets_dir: PathBuf,
+    trash_dir: PathBuf,
+}
+
+impl AssetCleanup {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            assets_dir: PathBuf::from(config.assets_dir()),
+            trash_dir: PathBuf::from(config.trash_dir()),
+        }
+    }
+
+    /// Moves the asset folder of a collection, or of one of its items, to the
+    /// trash. Returns `None` when there was nothing to move.
+    pub async fn trash(
+        &self,
+        collection_id: &str,
+        item_id: Option<&str>,
+    ) -> Result<Option<TrashEntry>, String> {
+        let mut source = self.assets_dir.join(collection_id);
+        if let Some(item_id) = item_id {
+            source = source.join(item_id);
+        }
+        if tokio::fs::metadata(&source).await.is_err() {
+            return Ok(None);
+        }
+
+        let now = Utc::now();
+        let id = format!(
+            "{}-{}",
+            now.format("%Y%m%d-%H%M%S"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let entry = TrashEntry {
+            id,
+            collection_id: collection_id.to_string(),
+            item_id: item_id.map(str::to_string),
+            deleted_at: now.to_rfc3339(),
+        };
+        let entry_dir = self
+            .entry_dir(&entry.id)
+            .ok_or_else(|| format!("Invalid trash entry id '{}'", entry.id))?;
+        tokio::fs::create_dir_all(&entry_dir)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", entry_dir.display(), e))?;
+        let metadata = serde_json::to_vec_pretty(&entry).map_err(|e| e.to_string())?;
+        tokio::fs::write(entry_dir.join(TRASH_ENTRY_FILE), metadata)
+            .await
+            .map_err(|e| format!("Failed to write trash entry {}: {}", entry.id, e))?;
+
+        if let Err(e) = move_dir(&source, &entry_dir.join(TRASH_ASSETS_DIR)).await {
+            let _ = tokio::fs::remove_dir_all(&entry_dir).await;
+            return Err(e);
+        }
+
+        // An item's collection folder is not needed once it is empty
+        if item_id.is_some() {
+            let _ = tokio::fs::remove_dir(self.assets_dir.join(collection_id)).await;
+        }
+        Ok(Some(entry))
+    }
+
+    /// Moves the asset folder of a collection, or of one of its items, out of
+    /// the assets directory ahead of a purge, so a record created with the
+    /// same id meanwhile starts with an empty folder. Returns `None` when
+    /// there was nothing to move.
+    pub async fn detach(
+        &self,
+        collection_id: &str,
+        item_id: Option<&str>,
+    ) -> Result<Option<PathBuf>, String> {
+        let mut source = self.assets_dir.join(collection_id);
+        if let Some(item_id) = item_id {
+            source = source.join(item_id);
+        }
+        if tokio::fs::metadata(&source).await.is_err() {
+            return Ok(None);
+        }
+        let purging = self.trash_dir.join(PURGING_DIR);
+        tokio::fs::create_dir_all(&purging)
+            .await
+            .map_err(|e| format!("Failed to create {}: {}", purging.display(), e))?;
+        let target = purging.join(uuid::Uuid::new_v4().simple().to_string());
+        move_dir(&source, &target).await?;
+        if item_id.is_some() {
+            let _ = tokio::fs::remove_dir(self.assets_dir.join(collection_id)).await;
+        }
+        Ok(Some(target))
+    }
+
+    /// Moves a folder taken by [`Self::detach`] back, when the purge it was
+    /// taken for did not happen
+    pub async fn reattach(
+        &self,
+        detached: &FsPath,
+        collection_id: &str,
+        item_id: Option<&str>,
+    ) -> Result<(), String> {
+        let mut target = self.assets_dir.join(collection_id);
+        if let Some(item_id) = item_id {
+            target = target.join(item_id);
+        }
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        move_dir(detached, &target).await
+    }
+
+    /// Permanently deletes a folder taken by [`Self::detach`]
+    pub async fn remove(&self, detached: &FsPath) -> Result<(), String> {
+        tokio::fs::remove_dir_all(detached)
+            .await
+            .map_err(|e| format!("Failed to remove {}: {}", detached.display(), e))
+    }
+
+    /// Permanently deletes trash folders dated more than `days` days ago,
+    /// returning how many were removed
+    pub async fn purge(&self, days: u32) -> Result<usize, String> {
+        let cutoff = Utc::now().date_naive() - chrono::Duration::days(days as i64);
+        let mut purged = 0;
+        let Ok(mut day_dirs) = tokio::fs::read_dir(&self.trash_dir).await else {
+            return Ok(0);
+        };
+        while let Ok(Some(day_dir)) = day_dirs.next_entry().await {
+            let name = day_dir.file_name().to_string_lossy().to_string();
+            let Ok(date) = NaiveDate::parse_from_str(&name, "%Y-%m-%d") else {
+                continue;
+            };
+            if date >= cutoff {
+                continue;
+            }
+            tokio::fs::remove_dir_all(day_dir.path())
+                .await
+                .map_err(|e| format!("Failed to purge trash folder {}: {}", name, e))?;
+            purged += 1;
+        }
+        Ok(purged)
+    }
+
+    /// Folder of a trash entry; `None` for ids that are not ours
+    fn entry_dir(&self, entry_id: &str) -> Option<PathBuf> {
+        let valid =
+            entry_id.len() == 24 && entry_id.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+        if !valid {
+            return None;
+        }
+        let date = NaiveDate::parse_from_str(&entry_id[..8], "%Y%m%d").ok()?;
+        Some(
+            self.trash_dir
+                .join(date.format("%Y-%m-%d").to_string())
+                .join(entry_id),
+        )
+    }
+}
+
+/// Renames a folder, retrying while files in it are held open
+async fn move_dir(source: &FsPath, target: &FsPath) -> Result<(), String> {
+    let mut attempt = 1;
+    loop {
+        match tokio::fs::rename(source, target).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < MOVE_ATTEMPTS => {
+                eprintln!(
+                    "Attempt {} to move {} failed: {}",
+                    attempt,
+                    source.display(),
+                    e
+                );
+                attempt += 1;
+                tokio::time::sleep(MOVE_RETRY_DELAY).await;
+            }
+            Err(e) => {
+                return Err(format!(
+                    "Failed to move {} to {}: {}",
+                    source.display(),
+                    target.display(),
+                    e
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::config::Config;
+use crate::database::asset_sizes::{AssetSize, AssetSizeTotals};
+use crate::database::DatabaseService;
+use crate::server::files::{self, FileEntry};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Cached storage use, as shown in the desktop UI
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetSizeSummary {
+    pub bytes: u64,
+    pub file_count: u64,
+    pub collections: Vec<AssetSizeTotals>,
+    /// When the counters last changed; `None` until the first recount
+    pub updated_at: Option<String>,
+}
+
+/// Recounts the asset folder of an item, or of every item in a collection,
+/// and stores the result. Called after uploads and restores.
+pub async fn refresh(
+    db_service: &DatabaseService,
+    config: &Config,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> Result<(), String> {
+    let collection_dir = PathBuf::from(config.assets_dir()).join(collection_id);
+    match item_id {
+        Some(item_id) => {
+            let files = files::list_files(collection_dir.join(item_id))
+                .await
+                .map_err(|e| e.to_string())?;
+            let size = AssetSize {
+                collection_id: collection_id.to_string(),
+                item_id: item_id.to_string(),
+                bytes: files.iter().map(|f| f.bytes).sum(),
+                file_count: files.len() as u64,
+            };
+            db_service
+                .asset_sizes
+                .set_item(&size)
+                .await
+                .map_err(|e| e.to_string())
+        }
+        None => {
+            let files = files::list_files(&collection_dir)
+                .await
+                .map_err(|e| e.to_string())?;
+            let sizes: Vec<AssetSize> = group_by_item(&collection_dir, &files)
+                .into_iter()
+                .map(|(item_id, (bytes, file_count))| AssetSize {
+                    collection_id: collection_id.to_string(),
+                    item_id,
+                    bytes,
+                    file_count,
+                })
+                .collect();
+            db_service
+                .asset_sizes
+                .replace(Some(collection_id), &sizes)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Walks the whole assets directory and rebuilds the counters. Picks up
+/// files added or removed outside the app.
+pub async fn recount_all(
+    db_service: &DatabaseService,
+    config: &Config,
+) -> Result<AssetSizeTotals, String> {
+    let root = PathBuf::from(config.assets_dir());
+    let files = files::list_files(&root).await.map_err(|e| e.to_string())?;
+
+    // Collection folders, then item folders within them
+    let mut by_collection: BTreeMap<String, Vec<FileEntry>> = BTreeMap::new();
+    for file in files {
+        let mut components = file
+            .path
+            .strip_prefix(&root)
+            .unwrap_or(&file.path)
+            .components();
+        if let (Some(collection), Some(_)) = (components.next(), components.next()) {
+            let collection = collection.as_os_str().to_string_lossy().to_string();
+            by_collection.entry(collection).or_default().push(file);
+        }
+    }
+    let mut sizes = Vec::new();
+    for (collection_id, files) in by_collection {
+        let collection_dir = root.join(&collection_id);
+        for (item_id, (bytes, file_count)) in group_by_item(&collection_dir, &files) {
+            sizes.push(AssetSize {
+                collection_id: collection_id.clone(),
+                item_id,
+                bytes,
+                file_count,
+            });
+        }
+    }
+
+    db_service
+        .asset_sizes
+        .replace(None, &sizes)
+        .await
+        .map_err(|e| e.to_string())?;
+    db_service
+        .asset_sizes
+        .total()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Cached totals for the whole store and per collection
+pub async fn summary(db_service: &DatabaseService) -> Result<AssetSizeSummary, String> {
+    let total = db_service
+        .asset_sizes
+        .total()
+        .await
+        .map_err(|e| e.to_string())?;
+    let collections = db_service
+        .asset_sizes
+        .collection_totals()
+        .await
+        .map_err(|e| e.to_string())?;
+    let updated_at = db_service
+        .asset_sizes
+        .last_updated()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(AssetSizeSummary {
+        bytes: total.bytes,
+        file_count: total.file_count,
+        collections,
+        updated_at,
+    })
+}
+
+/// Bytes and file count per item folder below a collection folder. Files
+/// lying directly in the collection folder belong to no item and are skipped.
+fn group_by_item(collection_dir: &Path, files: &[FileEntry]) -> BTreeMap<String, (u64, u64)> {
+    let mut sizes: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    for file in files {
+        let Ok(relative) = file.path.strip_prefix(collection_dir) else {
+            continue;
+        };
+        let mut components = relative.components();
+        if let (Some(item), Some(_)) = (components.next(), components.next()) {
+            let item_id = item.as_os_str().to_string_lossy().to_string();
+            let entry = sizes.entry(item_id).or_default();
+            entry.0 += file.bytes;
+            entry.1 += 1;
+        }
+    }
+    sizes
+}
@@ -0,0 +1,199 @@
+use crate::config::Config;
+use crate::database::DatabaseService;
+use crate::server::asset_cleanup::{CleanupFile, MAX_LISTED_FILES};
+use crate::server::{asset_sizes, files};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Files changed more recently than this are left alone: an upload or copy
+/// writes its file before the item records it
+const MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Outcome of a garbage collection of the assets directory
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AssetGcReport {
+    /// Nothing was deleted; `orphaned_files` is what a real run would remove
+    pub dry_run: bool,
+    pub scanned_files: usize,
+    /// Orphaned files, relative to the assets directory (at most 1000)
+    pub orphaned_files: Vec<CleanupFile>,
+    /// More orphaned files exist than are listed
+    pub orphaned_truncated: bool,
+    pub orphaned_count: usize,
+    pub orphaned_bytes: u64,
+    /// Unreferenced files left alone because they changed within the last
+    /// hour
+    pub skipped_recent: usize,
+    /// Files left alone because they are not at `<collection>/<item>/<file>`
+    /// under the folder of a stored collection
+    pub skipped_unknown: usize,
+    pub removed_files: usize,
+    pub removed_bytes: u64,
+    pub removed_directories: usize,
+    /// Files that could not be removed, with the reason
+    pub errors: Vec<String>,
+}
+
+/// File names the assets of one item may be stored under: each asset's key,
+/// which uploads and re-hosting use, and the last segment of its href, which
+/// copied files use
+#[derive(Default)]
+struct ItemReferences(HashSet<String>);
+
+impl ItemReferences {
+    fn add(&mut self, key: String, href: Option<String>) {
+        if let Some(name) = href.as_deref().and_then(href_file_name) {
+            self.0.insert(name);
+        }
+        self.0.insert(key);
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+}
+
+/// Decoded last path segment of an asset href, without query or fragment
+pub(crate) fn href_file_name(href: &str) -> Option<String> {
+    let path = href.split(['?', '#']).next()?;
+    let segment = path.rsplit('/').next().filter(|s| !s.is_empty())?;
+    Some(
+        urlencoding::decode(segment)
+            .map(|s| s.into_owned())
+            .unwrap_or_else(|_| segment.to_string()),
+    )
+}
+
+/// Finds the asset files no record refers to and deletes them, or with
+/// `dry_run` only reports them. Only files at `<collection>/<item>/<file>`
+/// in the folder of a stored collection are considered; such a file is kept
+/// while its item is stored, in the trash too, and one of the item's assets
+/// names it. Emptied item folders are removed and the asset size counters
+/// recounted afterwards.
+pub async fn collect_garbage(
+    db_service: &DatabaseService,
+    config: &Config,
+    dry_run: bool,
+) -> Result<AssetGcReport, String> {
+    let root = PathBuf::from(config.assets_dir());
+    let mut all_files = files::list_files(&root)
+        .await
+        .map_err(|e| format!("Failed to read assets directory: {}", e))?;
+    all_files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let collections = db_service
+        .collections
+        .stored_ids()
+        .await
+        .map_err(|e| format!("Failed to read collections: {}", e))?;
+    let mut references: HashMap<String, BTreeMap<String, ItemReferences>> = HashMap::new();
+
+    let mut report = AssetGcReport {
+        dry_run,
+        scanned_files: all_files.len(),
+        ..Default::default()
+    };
+    let now = SystemTime::now();
+    for file in all_files {
+        let relative = file.path.strip_prefix(&root).unwrap_or(&file.path);
+        let parts: Vec<String> = relative
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect();
+
+        // Only files stored as <collection>/<item>/<asset file> under the
+        // folder of a stored collection are candidates; anything else in the
+        // assets directory was not put there by the catalog and is kept
+        let (collection_id, item_id, name) = match parts.as_slice() {
+            [collection_id, item_id, name] if collections.contains(collection_id) => {
+                (collection_id, item_id, name)
+            }
+            _ => {
+                report.skipped_unknown += 1;
+                continue;
+            }
+        };
+        if !references.contains_key(collection_id) {
+            let items = item_references(db_service, collection_id).await?;
+            references.insert(collection_id.clone(), items);
+        }
+        let referenced = references[collection_id]
+            .get(item_id)
+            .is_some_and(|item| item.contains(name));
+        if referenced {
+            continue;
+        }
+
+        let changed = tokio::fs::metadata(&file.path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or(now);
+        if now.duration_since(changed).unwrap_or_default() < MIN_AGE {
+            report.skipped_recent += 1;
+            continue;
+        }
+
+        report.orphaned_count += 1;
+        report.orphaned_bytes += file.bytes;
+        if report.orphaned_files.len() < MAX_LISTED_FILES {
+            report.orphaned_files.push(CleanupFile {
+                path: relative.to_string_lossy().replace('\\', "/"),
+                bytes: file.bytes,
+            });
+        } else {
+            report.orphaned_truncated = true;
+        }
+
+        if !dry_run {
+            match tokio::fs::remove_file(&file.path).await {
+                Ok(()) => {
+                    report.removed_files += 1;
+                    report.removed_bytes += file.bytes;
+                }
+                Err(e) => report.errors.push(format!("{}: {}", file.path.display(), e)),
+            }
+        }
+    }
+
+    if !dry_run {
+        report.removed_directories = remove_empty_dirs(&root, &collections).await;
+        if report.removed_files > 0 {
+            asset_sizes::recount_all(db_service, config).await?;
+        }
+    }
+    Ok(report)
+}
+
+/// Asset file names of each item of a collection, by item id
+async fn item_references(
+    db_service: &DatabaseService,
+    collection_id: &str,
+) -> Result<BTreeMap<String, ItemReferences>, String> {
+    let rows = db_service
+        .items
+        .asset_references(collection_id)
+        .await
+        .map_err(|e| format!("Failed to read assets of collection '{}': {}", collection_id, e))?;
+    let mut items: BTreeMap<String, ItemReferences> = BTreeMap::new();
+    for (item_id, key, href) in rows {
+        items.entry(item_id).or_default().add(key, href);
+    }
+    Ok(items)
+}
+
+/// Removes the empty item folders of the stored collections, returning how
+/// many were removed
+async fn remove_empty_dirs(root: &Path, collections: &BTreeSet<String>) -> usize {
+    let mut removed = 0;
+    for collection_id in collections {
+        removed += files::remove_empty_subdirs(root.join(collection_id))
+            .await
+            .unwrap_or(0);
+    }
+    removed
+}
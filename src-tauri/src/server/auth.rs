@@ -0,0 +1,180 @@
+use crate::config::Config;
+use crate::server::error::ApiError;
+use crate::server::server::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Secret holding the key that unlocks administrative endpoints such as
+/// search explain. Set it with the `set_secret` Tauri command or the
+/// `ZENSTAC_SECRET_ADMIN_API_KEY` environment variable.
+pub const ADMIN_KEY_SECRET: &str = "admin_api_key";
+
+/// Secret holding the key that unlocks writes. While it is set, write
+/// requests need it (or the admin key), and callers without it are served a
+/// read-only landing page, conformance list and OpenAPI document.
+pub const WRITE_KEY_SECRET: &str = "write_api_key";
+
+/// POST endpoints that only read the catalog, relative to the API root
+const READ_ONLY_POSTS: &[&str] = &[
+    "/search",
+    "/search/explain",
+    "/search/export",
+    "/geometry/tools",
+    "/geometry/reproject",
+    "/sync/records",
+];
+
+/// Marks requests the desktop UI dispatches in-process; they never pass
+/// through the network listener, so they are trusted
+#[derive(Debug, Clone, Copy)]
+pub struct InternalRequest;
+
+/// Checks that a request may use an administrative endpoint: it comes from
+/// the desktop UI, or carries the admin key as a bearer token or in
+/// `X-API-Key`. Without a configured key the endpoints are closed to HTTP
+/// clients.
+pub async fn require_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+    internal: Option<InternalRequest>,
+) -> Result<(), Response> {
+    if internal.is_some() {
+        return Ok(());
+    }
+
+    let key = match state.db_service.secrets.get(ADMIN_KEY_SECRET).await {
+        Ok(Some(key)) if !key.is_empty() => key,
+        Ok(_) => {
+            return Err(auth_error(
+                StatusCode::FORBIDDEN,
+                "Forbidden",
+                &format!(
+                    "This endpoint is disabled until the '{}' secret is set",
+                    ADMIN_KEY_SECRET
+                ),
+            ))
+        }
+        Err(e) => {
+            return Err(auth_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "InternalServerError",
+                &format!("Failed to read the admin key: {}", e),
+            ))
+        }
+    };
+
+    match presented_key(headers) {
+        Some(presented) if constant_time_eq(presented.trim().as_bytes(), key.as_bytes()) => Ok(()),
+        _ => Err(auth_error(
+            StatusCode::UNAUTHORIZED,
+            "Unauthorized",
+            "A valid admin key is required",
+        )),
+    }
+}
+
+/// Whether a request may write: it comes from the desktop UI, no write key
+/// is set, or it carries the write or the admin key
+pub async fn can_write(
+    state: &AppState,
+    headers: &HeaderMap,
+    internal: Option<InternalRequest>,
+) -> bool {
+    if internal.is_some() {
+        return true;
+    }
+    let write_key = match state.db_service.secrets.get(WRITE_KEY_SECRET).await {
+        Ok(Some(key)) if !key.is_empty() => key,
+        Ok(_) => return true,
+        Err(_) => return false,
+    };
+    let Some(presented) = presented_key(headers).map(str::trim) else {
+        return false;
+    };
+    if constant_time_eq(presented.as_bytes(), write_key.as_bytes()) {
+        return true;
+    }
+    matches!(
+        state.db_service.secrets.get(ADMIN_KEY_SECRET).await,
+        Ok(Some(admin_key))
+            if !admin_key.is_empty() && constant_time_eq(presented.as_bytes(), admin_key.as_bytes())
+    )
+}
+
+/// Configuration as a caller sees it: without write permission the
+/// transactions and uploads groups are off, so what the landing page,
+/// `/conformance` and `/api` describe is what the caller can do
+pub async fn caller_config(
+    state: &AppState,
+    headers: &HeaderMap,
+    internal: Option<InternalRequest>,
+) -> Config {
+    let mut config = state.config.clone();
+    if !can_write(state, headers, internal).await {
+        config.server.enable_transactions = false;
+        config.server.enable_uploads = false;
+    }
+    config
+}
+
+/// Middleware turning away write requests from callers without write
+/// permission
+pub async fn write_access(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let internal = request.extensions().get::<InternalRequest>().copied();
+    if is_write(request.method(), request.uri().path(), &state.config.server.api_version)
+        && !can_write(&state, request.headers(), internal).await
+    {
+        return auth_error(
+            StatusCode::UNAUTHORIZED,
+            "Unauthorized",
+            "A valid write key is required",
+        );
+    }
+    next.run(request).await
+}
+
+/// Whether a request changes the catalog: every PUT, DELETE and PATCH, and
+/// POSTs other than searches and geometry tools
+fn is_write(method: &Method, path: &str, api_path: &str) -> bool {
+    match *method {
+        Method::PUT | Method::DELETE | Method::PATCH => true,
+        Method::POST => {
+            let relative = match api_path {
+                "/" => path,
+                api_path => path.strip_prefix(api_path).unwrap_or(path),
+            };
+            !READ_ONLY_POSTS.contains(&relative.trim_end_matches('/'))
+        }
+        _ => false,
+    }
+}
+
+/// Key presented as a bearer token or in `X-API-Key`
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("X-API-Key").and_then(|v| v.to_str().ok()))
+}
+
+/// Compares without stopping at the first difference, so response times do
+/// not reveal how much of a guessed key is right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn auth_error(status: StatusCode, code: &'static str, description: &str) -> Response {
+    let mut error = ApiError::new(status, code, description);
+    if status == StatusCode::UNAUTHORIZED {
+        error = error.with_header(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    }
+    error.into_response()
+}
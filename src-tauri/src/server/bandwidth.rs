@@ -0,0 +1,103 @@
+use axum::body::{Body, Bytes};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+
+/// Bytes read from disk per streamed chunk
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Buckets idle this long are dropped by the next download
+const IDLE_BUCKET: Duration = Duration::from_secs(300);
+
+/// Token bucket of one client: refills at `rate` bytes per second up to one
+/// second's worth, so short requests pass at full speed while long
+/// downloads settle at the rate
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Takes `bytes` tokens and returns how long to wait before sending them
+    fn take(&mut self, rate: u64, bytes: usize) -> Duration {
+        let now = Instant::now();
+        self.rate = rate as f64;
+        self.tokens = (self.tokens + now.duration_since(self.updated).as_secs_f64() * self.rate)
+            .min(self.rate);
+        self.updated = now;
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Token buckets by client, shared by all of a client's downloads so
+/// parallel requests split its limit instead of multiplying it
+#[derive(Debug, Default)]
+pub struct BandwidthLimiter {
+    buckets: Mutex<HashMap<String, Arc<Mutex<TokenBucket>>>>,
+}
+
+impl BandwidthLimiter {
+    fn bucket(&self, client: &str, rate: u64) -> Arc<Mutex<TokenBucket>> {
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.retain(|_, bucket| {
+            Arc::strong_count(bucket) > 1
+                || bucket.lock().unwrap().updated.elapsed() < IDLE_BUCKET
+        });
+        buckets
+            .entry(client.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(TokenBucket::new(rate))))
+            .clone()
+    }
+}
+
+/// The application's bandwidth limiter
+pub fn limiter() -> &'static BandwidthLimiter {
+    static LIMITER: OnceLock<BandwidthLimiter> = OnceLock::new();
+    LIMITER.get_or_init(BandwidthLimiter::default)
+}
+
+/// Response body streaming up to `length` bytes of a file in chunks, from
+/// its current position. With a client and a non-zero `rate` (bytes per
+/// second) the chunks are paced by the client's token bucket; otherwise the
+/// file is sent as fast as the connection allows.
+pub fn file_body(file: tokio::fs::File, length: u64, client: Option<&str>, rate: u64) -> Body {
+    let bucket = match client {
+        Some(client) if rate > 0 => Some(limiter().bucket(client, rate)),
+        _ => None,
+    };
+    let stream = futures_util::stream::unfold(
+        (file.take(length), bucket, vec![0u8; CHUNK_BYTES]),
+        move |(mut file, bucket, mut buffer)| async move {
+            let read = match file.read(&mut buffer).await {
+                Ok(0) => return None,
+                Ok(read) => read,
+                Err(e) => return Some((Err(e), (file, bucket, buffer))),
+            };
+            if let Some(bucket) = &bucket {
+                let wait = bucket.lock().unwrap().take(rate, read);
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+            let chunk = Bytes::copy_from_slice(&buffer[..read]);
+            Some((Ok(chunk), (file, bucket, buffer)))
+        },
+    );
+    Body::from_stream(stream)
+}
@@ -0,0 +1,173 @@
+use crate::database::settings::{BasemapCacheMaxBytes, BasemapUpstreamUrl, Setting};
+use crate::server::error::ApiError;
+use crate::server::outbound::http_client;
+use crate::server::server::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path as FsPath, PathBuf};
+use std::time::Duration;
+
+/// Handler for cached basemap tiles (GET /basemap/:z/:x/:y)
+///
+/// Tiles are served from the disk cache when present, otherwise fetched from
+/// the configured upstream and stored, so maps keep working offline for any
+/// area that has been viewed before.
+pub async fn basemap_tile(
+    Path((z, x, y)): Path<(u32, u32, String)>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    // Leaflet-style URLs may carry an extension on the last segment
+    let y = y
+        .split('.')
+        .next()
+        .and_then(|v| v.parse::<u32>().ok())
+        .ok_or_else(|| ApiError::bad_request("Invalid tile row"))?;
+    if z > 24 || x >= (1 << z) || y >= (1 << z) {
+        return Err(ApiError::bad_request("Tile coordinates are out of range"));
+    }
+
+    let upstream = state
+        .db_service
+        .settings
+        .get::<BasemapUpstreamUrl>()
+        .await
+        .unwrap_or_else(|_| BasemapUpstreamUrl::default_value());
+    let max_bytes = state
+        .db_service
+        .settings
+        .get::<BasemapCacheMaxBytes>()
+        .await
+        .unwrap_or_else(|_| BasemapCacheMaxBytes::default_value());
+
+    let cache_root = PathBuf::from(state.config.basemap_cache_dir());
+    let tile_path = cache_root
+        .join(upstream_key(&upstream))
+        .join(z.to_string())
+        .join(x.to_string())
+        .join(format!("{}.tile", y));
+
+    if let Ok(data) = tokio::fs::read(&tile_path).await {
+        return Ok(tile_response(data));
+    }
+
+    // Secret references are resolved only here, so API keys never end up in
+    // cache paths
+    let bad_gateway = |e: String| ApiError::from_status(StatusCode::BAD_GATEWAY, e);
+    let url = state
+        .db_service
+        .secrets
+        .expand(&upstream)
+        .await
+        .map_err(bad_gateway)?;
+    let url = url
+        .replace("{z}", &z.to_string())
+        .replace("{x}", &x.to_string())
+        .replace("{y}", &y.to_string());
+
+    let client = http_client(&state.db_service).await.map_err(bad_gateway)?;
+    let data = fetch_tile(&client, &url).await.map_err(|e| {
+        bad_gateway(format!(
+            "Tile is not cached and the upstream is unavailable: {}",
+            e
+        ))
+    })?;
+
+    if let Some(parent) = tile_path.parent() {
+        if tokio::fs::create_dir_all(parent).await.is_ok()
+            && tokio::fs::write(&tile_path, &data).await.is_ok()
+        {
+            tokio::task::spawn_blocking(move || enforce_cache_limit(&cache_root, max_bytes));
+        }
+    }
+
+    Ok(tile_response(data))
+}
+
+async fn fetch_tile(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, String> {
+    let response = client
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("upstream returned {}", response.status()));
+    }
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    Ok(bytes.to_vec())
+}
+
+/// Cache subdirectory for an upstream, so switching basemaps never mixes tiles
+fn upstream_key(upstream: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    upstream.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Deletes the oldest cached tiles until the cache is below 90% of its limit
+fn enforce_cache_limit(root: &FsPath, max_bytes: u64) {
+    fn collect(dir: &FsPath, files: &mut Vec<(PathBuf, u64, std::time::SystemTime)>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                collect(&path, files);
+            } else if let Ok(metadata) = entry.metadata() {
+                let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+                files.push((path, metadata.len(), modified));
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    collect(root, &mut files);
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    if total <= max_bytes {
+        return;
+    }
+
+    files.sort_by_key(|(_, _, modified)| *modified);
+    let target = max_bytes / 10 * 9;
+    for (path, size, _) in files {
+        if total <= target {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            total -= size;
+        }
+    }
+}
+
+/// Detects the image format from its leading bytes
+fn sniff_content_type(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG") {
+        "image/png"
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        "image/jpeg"
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        "image/webp"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+fn tile_response(data: Vec<u8>) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static(sniff_content_type(&data)),
+    );
+    headers.insert("Access-Control-Allow-Origin", HeaderValue::from_static("*"));
+    headers.insert(
+        "Cache-Control",
+        HeaderValue::from_static("public, max-age=86400"),
+    );
+    (headers, data).into_response()
+}
@@ -0,0 +1,163 @@
+use crate::config::Config;
+use crate::models::link::Link;
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::Path,
+    http::{HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use std::path::{Path as FsPath, PathBuf};
+
+/// Catalog-level branding files, stored under the app data `branding` directory
+/// as `<kind>.<ext>` and served from /branding/:kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrandingKind {
+    Logo,
+    Favicon,
+    About,
+}
+
+impl BrandingKind {
+    pub const ALL: [BrandingKind; 3] = [BrandingKind::Logo, BrandingKind::Favicon, BrandingKind::About];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BrandingKind::Logo => "logo",
+            BrandingKind::Favicon => "favicon",
+            BrandingKind::About => "about",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// File extensions accepted for this kind
+    fn allowed_extensions(&self) -> &'static [&'static str] {
+        match self {
+            BrandingKind::Logo => &["png", "jpg", "jpeg", "svg", "webp", "gif"],
+            BrandingKind::Favicon => &["ico", "png", "svg"],
+            BrandingKind::About => &["html", "htm", "md", "txt", "pdf"],
+        }
+    }
+
+    /// Link relation used on the landing page
+    fn rel(&self) -> &'static str {
+        match self {
+            BrandingKind::Logo => "logo",
+            BrandingKind::Favicon => "icon",
+            BrandingKind::About => "about",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            BrandingKind::Logo => "Catalog Logo",
+            BrandingKind::Favicon => "Catalog Icon",
+            BrandingKind::About => "About this Catalog",
+        }
+    }
+}
+
+/// Finds the stored file for a branding kind, whatever its extension
+pub fn find_branding_file(config: &Config, kind: BrandingKind) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(config.branding_dir()).ok()?;
+    entries.flatten().map(|entry| entry.path()).find(|path| {
+        path.is_file() && path.file_stem().and_then(|s| s.to_str()) == Some(kind.name())
+    })
+}
+
+/// Copies `src_path` into the branding directory, replacing any previous file
+pub fn install_branding_file(config: &Config, kind: BrandingKind, src_path: &str) -> Result<(), String> {
+    let extension = FsPath::new(src_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .unwrap_or_default();
+    if !kind.allowed_extensions().contains(&extension.as_str()) {
+        return Err(format!(
+            "Unsupported {} file type '{}'. Supported types: {}",
+            kind.name(),
+            extension,
+            kind.allowed_extensions().join(", ")
+        ));
+    }
+
+    let dir = PathBuf::from(config.branding_dir());
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create branding directory: {}", e))?;
+    remove_branding_file(config, kind)?;
+    std::fs::copy(src_path, dir.join(format!("{}.{}", kind.name(), extension)))
+        .map_err(|e| format!("Failed to copy {} file: {}", kind.name(), e))?;
+    Ok(())
+}
+
+/// Removes the stored file for a branding kind, if any
+pub fn remove_branding_file(config: &Config, kind: BrandingKind) -> Result<(), String> {
+    if let Some(existing) = find_branding_file(config, kind) {
+        std::fs::remove_file(&existing).map_err(|e| format!("Failed to remove {} file: {}", kind.name(), e))?;
+    }
+    Ok(())
+}
+
+/// Landing page links for every configured branding file
+pub fn branding_links(config: &Config, server_config: &ServerConfig) -> Vec<Link> {
+    BrandingKind::ALL
+        .into_iter()
+        .filter_map(|kind| {
+            let path = find_branding_file(config, kind)?;
+            Some(Link {
+                href: server_config.branding_href(kind.name()),
+                rel: kind.rel().to_string(),
+                r#type: Some(content_type_for(&path).to_string()),
+                title: Some(kind.title().to_string()),
+                method: None,
+                headers: None,
+                body: None,
+            })
+        })
+        .collect()
+}
+
+fn content_type_for(path: &FsPath) -> &'static str {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+        .as_str()
+    {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "ico" => "image/vnd.microsoft.icon",
+        "html" | "htm" => "text/html; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Handler serving catalog branding files (GET /branding/:kind)
+pub async fn branding_file(Path(name): Path<String>) -> Response {
+    let config = Config::default();
+    let file = BrandingKind::parse(&name).and_then(|kind| find_branding_file(&config, kind));
+
+    if let Some(path) = file {
+        if let Ok(data) = tokio::fs::read(&path).await {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                "Content-Type",
+                HeaderValue::from_static(content_type_for(&path)),
+            );
+            headers = add_cors_headers(headers);
+            return (headers, data).into_response();
+        }
+    }
+
+    ApiError::not_found(format!("No catalog {} has been configured", name)).into_response()
+}
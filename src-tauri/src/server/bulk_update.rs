@@ -0,0 +1,282 @@
+use crate::config::Config;
+use crate::database::{DatabaseService, DbItem};
+use crate::models::item::{Geometry, Item};
+use crate::server::cql2::{self, Expr};
+use crate::server::error::ApiError;
+use crate::server::helpers::calculate_bbox_for_geometry;
+use crate::server::ingest_mode::{normalize_datetimes_to_utc, preserve_original_datetimes};
+use crate::server::item_schema::validate_item;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Items read per batch while looking for matches
+const SCAN_BATCH: i64 = 500;
+
+/// Top-level item members a patch may change; the id, collection and type
+/// identify the item and stay as they are
+const PATCHABLE: &[&str] = &[
+    "stac_version",
+    "stac_extensions",
+    "geometry",
+    "bbox",
+    "properties",
+    "assets",
+    "links",
+];
+
+/// Body of POST /collections/:collection_id/items/update
+#[derive(Debug, Clone, Deserialize)]
+pub struct BulkUpdateRequest {
+    /// CQL2 filter selecting the items, a CQL2-JSON object or a CQL2-text string
+    pub filter: Value,
+    /// Language of `filter`; defaults to cql2-json for objects and cql2-text for strings
+    #[serde(default, rename = "filter-lang")]
+    pub filter_lang: Option<String>,
+    /// JSON Merge Patch (RFC 7396) applied to every matching item
+    pub patch: Value,
+    /// Count and check the matching items without storing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Outcome of a bulk update
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkUpdateReport {
+    /// Items matching the filter
+    pub matched: usize,
+    /// Items stored; 0 in a dry run
+    pub updated: usize,
+    pub dry_run: bool,
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Handler patching every item of a collection that matches a filter
+/// (POST /collections/:collection_id/items/update)
+pub async fn update_items(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+    Json(request): Json<BulkUpdateRequest>,
+) -> Result<Response, ApiError> {
+    let report = bulk_update(&state.db_service, &state.config, &collection_id, &request)
+        .await
+        .map_err(|(status, description)| ApiError::from_status(status, description))?;
+    Ok(json_response(StatusCode::OK, serde_json::json!(report)))
+}
+
+/// Applies a merge patch to every item of a collection matching a CQL2
+/// filter. Every patched item is checked (STAC shape and the collection's
+/// item schema) before any is stored, and all are stored in one transaction,
+/// so the update applies to all matches or to none. A dry run stops after
+/// the checks and reports how many items would change. Shared by the HTTP
+/// endpoint and the Tauri command.
+pub async fn bulk_update(
+    db_service: &DatabaseService,
+    config: &Config,
+    collection_id: &str,
+    request: &BulkUpdateRequest,
+) -> Result<BulkUpdateReport, (StatusCode, String)> {
+    let filter = parse_filter(request)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid filter: {}", e)))?;
+    check_patch(&request.patch).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    match db_service.collections.get_by_id(collection_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Collection '{}' not found", collection_id),
+            ))
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to get collection: {}", e),
+            ))
+        }
+    }
+
+    // Filters are evaluated on the STAC form of each item, as in item pages
+    let server_config = ServerConfig::from_config(config);
+    let preserve_datetimes = preserve_original_datetimes(db_service).await;
+    let mut patched = Vec::new();
+    let mut offset = 0;
+    loop {
+        let items = db_service
+            .items
+            .get_by_collection(collection_id, Some(SCAN_BATCH), Some(offset))
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to read items: {}", e),
+                )
+            })?;
+        for item in &items {
+            if !filter.matches(&item.clone().into_stac_item_json(&server_config, &[])) {
+                continue;
+            }
+            let mut item = apply_patch(item, &request.patch).map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Patched item '{}' is invalid: {}", item.id, e),
+                )
+            })?;
+            if let Value::Object(properties) = &mut item.properties {
+                normalize_datetimes_to_utc(properties, preserve_datetimes);
+            }
+            let stac_item = item.clone().into_stac_item_json(&server_config, &[]);
+            if let Err(e) = serde_json::from_value::<Item>(stac_item.clone()) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Patched item '{}' is not a valid STAC item: {}", item.id, e),
+                ));
+            }
+            if let Err(violations) = validate_item(db_service, collection_id, &stac_item).await {
+                let reasons: Vec<String> = violations
+                    .iter()
+                    .map(|v| {
+                        if v.path.is_empty() {
+                            v.message.clone()
+                        } else {
+                            format!("{}: {}", v.path, v.message)
+                        }
+                    })
+                    .collect();
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "Patched item '{}' does not satisfy the schema of collection '{}': {}",
+                        item.id,
+                        collection_id,
+                        reasons.join("; ")
+                    ),
+                ));
+            }
+            patched.push(item);
+        }
+        if (items.len() as i64) < SCAN_BATCH {
+            break;
+        }
+        offset += SCAN_BATCH;
+    }
+
+    let matched = patched.len();
+    if request.dry_run || patched.is_empty() {
+        return Ok(BulkUpdateReport {
+            matched,
+            updated: 0,
+            dry_run: request.dry_run,
+        });
+    }
+    let updated = db_service
+        .items
+        .update_many(collection_id, &patched)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to update items, none were changed: {}", e),
+            )
+        })?;
+    Ok(BulkUpdateReport {
+        matched,
+        updated,
+        dry_run: false,
+    })
+}
+
+fn parse_filter(request: &BulkUpdateRequest) -> Result<Expr, String> {
+    match &request.filter {
+        Value::String(text) => cql2::parse_filter(text, request.filter_lang.as_deref()),
+        Value::Null => Err("a filter is required; use true to select every item".to_string()),
+        value => cql2::parse_filter(
+            &value.to_string(),
+            Some(request.filter_lang.as_deref().unwrap_or("cql2-json")),
+        ),
+    }
+}
+
+/// Checks that a patch is an object of patchable members
+fn check_patch(patch: &Value) -> Result<(), String> {
+    let members = patch
+        .as_object()
+        .ok_or_else(|| "The patch must be a JSON object".to_string())?;
+    for (key, value) in members {
+        match key.as_str() {
+            "id" | "collection" | "type" => {
+                return Err(format!("The patch cannot change '{}'", key))
+            }
+            "properties" if !value.is_object() => {
+                return Err("The patch of 'properties' must be an object".to_string())
+            }
+            key if !PATCHABLE.contains(&key) => {
+                return Err(format!(
+                    "Unknown item member '{}'; a patch may change {}",
+                    key,
+                    PATCHABLE.join(", ")
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Copy of an item with the merge patch applied. A new geometry without a
+/// bbox in the patch gets its bbox recomputed.
+fn apply_patch(item: &DbItem, patch: &Value) -> Result<DbItem, String> {
+    let mut document = serde_json::to_value(item).map_err(|e| e.to_string())?;
+    merge_patch(&mut document, patch);
+    let mut patched: DbItem = serde_json::from_value(document).map_err(|e| e.to_string())?;
+    if patch.get("geometry").is_some() && patch.get("bbox").is_none() {
+        patched.bbox = match &patched.geometry {
+            Some(geometry) => {
+                let geometry: Geometry =
+                    serde_json::from_value(geometry.clone()).map_err(|e| e.to_string())?;
+                Some(serde_json::json!(calculate_bbox_for_geometry(&geometry)))
+            }
+            None => None,
+        };
+    }
+    patched.updated_at = chrono::Utc::now().to_rfc3339();
+    Ok(patched)
+}
+
+/// RFC 7396 JSON Merge Patch: objects merge member by member, `null` removes
+/// a member and anything else replaces the target
+pub(crate) fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(members) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in members {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::config::{database_location, Config, DatabaseLocation};
+use crate::database::settings::{
+    AssetBandwidthLimit, FederatedCatalogs, IngestHookCommand, RequestRateLimit,
+    ResponseCacheSeconds, Setting, UploadScanCommand,
+};
+use crate::database::DatabaseService;
+use crate::server::acme::AcmeSettings;
+use crate::server::auth::{ADMIN_KEY_SECRET, WRITE_KEY_SECRET};
+use crate::server::demo;
+use crate::server::plugins::list_plugins;
+use serde::Serialize;
+
+/// Which optional subsystems this build has and which are turned on, so the
+/// desktop frontend can show or hide their controls instead of probing
+/// endpoints
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    /// Application version
+    pub version: &'static str,
+    pub features: FeatureGroups,
+    pub storage: StorageCapabilities,
+    pub auth: AuthCapabilities,
+    /// Subsystems not built into this version; always false for now and
+    /// listed so the frontend can rely on the keys
+    pub gdal: bool,
+    pub tiler: bool,
+    /// HTTPS with ACME certificates is configured
+    pub https: bool,
+    /// Number of WASM property extractor plugins installed
+    pub wasm_plugins: usize,
+    /// An ingest hook command is configured
+    pub ingest_hook: bool,
+    /// An upload scan command is configured
+    pub upload_scan: bool,
+    /// Number of remote STAC APIs searched alongside the local catalog
+    pub federated_catalogs: usize,
+    pub limits: LimitCapabilities,
+    /// The catalog was started with `--demo`
+    pub demo: bool,
+}
+
+/// Feature groups served to network clients; the desktop UI always has all
+#[derive(Debug, Serialize)]
+pub struct FeatureGroups {
+    pub transactions: bool,
+    pub uploads: bool,
+    pub search: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageCapabilities {
+    /// Where assets are kept: `local`, the only backend so far
+    pub backend: &'static str,
+    pub s3: bool,
+    /// `app_data`, `file` or `memory`
+    pub database: &'static str,
+}
+
+/// Keys network clients must present
+#[derive(Debug, Serialize)]
+pub struct AuthCapabilities {
+    /// Admin endpoints are open to clients presenting the admin key
+    pub admin_key: bool,
+    /// Writes need the write or the admin key
+    pub write_key: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LimitCapabilities {
+    pub request_rate_limit: bool,
+    pub response_cache: bool,
+    pub asset_bandwidth_limit: bool,
+}
+
+/// Collects the capabilities of the running app
+pub async fn capabilities(db_service: &DatabaseService, config: &Config) -> Capabilities {
+    let settings = &db_service.settings;
+    let secret_set = |key: &'static str| async move {
+        matches!(db_service.secrets.get(key).await, Ok(Some(value)) if !value.is_empty())
+    };
+
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        features: FeatureGroups {
+            transactions: config.server.enable_transactions,
+            uploads: config.server.enable_uploads,
+            search: config.server.enable_search,
+        },
+        storage: StorageCapabilities {
+            backend: "local",
+            s3: false,
+            database: match database_location() {
+                DatabaseLocation::AppData => "app_data",
+                DatabaseLocation::File(_) => "file",
+                DatabaseLocation::Memory { .. } => "memory",
+            },
+        },
+        auth: AuthCapabilities {
+            admin_key: secret_set(ADMIN_KEY_SECRET).await,
+            write_key: secret_set(WRITE_KEY_SECRET).await,
+        },
+        gdal: false,
+        tiler: false,
+        https: AcmeSettings::load(db_service).await.is_some(),
+        wasm_plugins: list_plugins(std::path::Path::new(&config.plugins_dir())).len(),
+        ingest_hook: !settings
+            .get::<IngestHookCommand>()
+            .await
+            .unwrap_or_else(|_| IngestHookCommand::default_value())
+            .is_empty(),
+        upload_scan: !settings
+            .get::<UploadScanCommand>()
+            .await
+            .unwrap_or_else(|_| UploadScanCommand::default_value())
+            .is_empty(),
+        federated_catalogs: settings
+            .get::<FederatedCatalogs>()
+            .await
+            .unwrap_or_else(|_| FederatedCatalogs::default_value())
+            .len(),
+        limits: LimitCapabilities {
+            request_rate_limit: settings
+                .get::<RequestRateLimit>()
+                .await
+                .unwrap_or_else(|_| RequestRateLimit::default_value())
+                > 0,
+            response_cache: settings
+                .get::<ResponseCacheSeconds>()
+                .await
+                .unwrap_or_else(|_| ResponseCacheSeconds::default_value())
+                > 0,
+            asset_bandwidth_limit: settings
+                .get::<AssetBandwidthLimit>()
+                .await
+                .unwrap_or_else(|_| AssetBandwidthLimit::default_value())
+                > 0,
+        },
+        demo: demo::is_demo(),
+    }
+}
@@ -0,0 +1,305 @@
+use crate::config::Config;
+use crate::database::catalog_checks::{CatalogCheckSummary, CatalogIssue};
+use crate::database::settings::{CatalogCheckIntervalHours, Setting};
+use crate::database::DatabaseService;
+use crate::server::outbound;
+use crate::server::stac_validation::{validate_stac_collection, validate_stac_item};
+use crate::server::utils::ServerConfig;
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Items read from the database at a time
+const PAGE_SIZE: i64 = 500;
+/// External URLs requested at the same time
+const CONCURRENT_REQUESTS: usize = 8;
+/// Time each URL has to answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Set while a check runs, so scheduled and manual runs do not overlap
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// A record referencing an external href
+struct Reference {
+    collection_id: String,
+    item_id: String,
+    kind: &'static str,
+    name: String,
+}
+
+/// Answer to a request for an href: the status, or the transport error
+type Outcome = Result<u16, String>;
+
+/// Requests every external asset href and link of the catalog and validates
+/// every stored collection and item, then stores what failed in place of
+/// the previous report. Hrefs under this server's base URL, relative hrefs
+/// and other schemes are not requested.
+pub async fn run_check(
+    db_service: &DatabaseService,
+    config: &Config,
+) -> Result<CatalogCheckSummary, String> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("A catalog check is already running".to_string());
+    }
+    let result = check_catalog(db_service, config).await;
+    RUNNING.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn check_catalog(
+    db_service: &DatabaseService,
+    config: &Config,
+) -> Result<CatalogCheckSummary, String> {
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let server_config = ServerConfig::from_config(config);
+    let own_prefix = format!("{}/", server_config.base_url.trim_end_matches('/'));
+
+    let mut references: BTreeMap<String, Vec<Reference>> = BTreeMap::new();
+    let mut issues = Vec::new();
+    let mut add_reference = |href: &str, reference: Reference| {
+        if (href.starts_with("http://") || href.starts_with("https://"))
+            && !href.starts_with(&own_prefix)
+        {
+            references
+                .entry(href.to_string())
+                .or_default()
+                .push(reference);
+        }
+    };
+
+    let collections = db_service
+        .collections
+        .get_all()
+        .await
+        .map_err(|e| e.to_string())?;
+    for collection in &collections {
+        for (key, href) in asset_hrefs(collection.assets.as_ref()) {
+            add_reference(
+                &href,
+                Reference {
+                    collection_id: collection.id.clone(),
+                    item_id: String::new(),
+                    kind: "asset",
+                    name: key,
+                },
+            );
+        }
+        let document = serde_json::to_value(collection.to_stac_collection(&server_config))
+            .map_err(|e| e.to_string())?;
+        for violation in validate_stac_collection(&document) {
+            issues.push(stac_issue(
+                &collection.id,
+                "",
+                violation.path,
+                violation.message,
+            ));
+        }
+    }
+
+    let collection_ids: Vec<String> = collections.iter().map(|c| c.id.clone()).collect();
+    let mut offset = 0;
+    loop {
+        let items = db_service
+            .items
+            .get_by_collections(&collection_ids, PAGE_SIZE, offset)
+            .await
+            .map_err(|e| e.to_string())?;
+        for item in &items {
+            for (key, href) in asset_hrefs(item.assets.as_ref()) {
+                add_reference(
+                    &href,
+                    Reference {
+                        collection_id: item.collection_id.clone(),
+                        item_id: item.id.clone(),
+                        kind: "asset",
+                        name: key,
+                    },
+                );
+            }
+            for (rel, href) in link_hrefs(item.links.as_ref()) {
+                add_reference(
+                    &href,
+                    Reference {
+                        collection_id: item.collection_id.clone(),
+                        item_id: item.id.clone(),
+                        kind: "link",
+                        name: rel,
+                    },
+                );
+            }
+            let document = serde_json::to_value(item.to_stac_item(&server_config))
+                .map_err(|e| e.to_string())?;
+            for violation in validate_stac_item(&document) {
+                issues.push(stac_issue(
+                    &item.collection_id,
+                    &item.id,
+                    violation.path,
+                    violation.message,
+                ));
+            }
+        }
+        if (items.len() as i64) < PAGE_SIZE {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    let stac_violations = issues.len() as u64;
+
+    let client = outbound::http_client(db_service).await?;
+    let outcomes: Vec<(String, Outcome)> = futures_util::stream::iter(references.keys().cloned())
+        .map(|href| {
+            let client = client.clone();
+            async move {
+                let outcome = request(&client, &href).await;
+                (href, outcome)
+            }
+        })
+        .buffer_unordered(CONCURRENT_REQUESTS)
+        .collect()
+        .await;
+
+    let checked_urls = outcomes.len() as u64;
+    let mut broken_links = 0;
+    for (href, outcome) in outcomes {
+        let (status, error) = match outcome {
+            Ok(status) if status < 400 => continue,
+            Ok(status) => (Some(status), None),
+            Err(e) => (None, Some(e)),
+        };
+        for reference in references.remove(&href).unwrap_or_default() {
+            broken_links += 1;
+            issues.push(CatalogIssue {
+                collection_id: reference.collection_id,
+                item_id: reference.item_id,
+                kind: reference.kind.to_string(),
+                name: reference.name,
+                href: href.clone(),
+                status,
+                error: error.clone(),
+            });
+        }
+    }
+
+    let summary = CatalogCheckSummary {
+        started_at,
+        finished_at: chrono::Utc::now().to_rfc3339(),
+        checked_urls,
+        broken_links,
+        stac_violations,
+    };
+    db_service
+        .catalog_checks
+        .replace(&summary, &issues)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+/// HEADs an href, falling back to a one-byte GET for servers that do not
+/// answer HEAD. Redirects are followed.
+async fn request(client: &reqwest::Client, href: &str) -> Outcome {
+    let response = client
+        .head(href)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status().as_u16();
+    if status != 405 && status != 501 {
+        return Ok(status);
+    }
+    let response = client
+        .get(href)
+        .header(reqwest::header::RANGE, "bytes=0-0")
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(response.status().as_u16())
+}
+
+/// Key and href of each asset
+fn asset_hrefs(assets: Option<&Value>) -> Vec<(String, String)> {
+    assets
+        .and_then(|assets| assets.as_object())
+        .map(|assets| {
+            assets
+                .iter()
+                .filter_map(|(key, asset)| {
+                    let href = asset.get("href")?.as_str()?;
+                    Some((key.clone(), href.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Relation and href of each stored link
+fn link_hrefs(links: Option<&Value>) -> Vec<(String, String)> {
+    links
+        .and_then(|links| links.as_array())
+        .map(|links| {
+            links
+                .iter()
+                .filter_map(|link| {
+                    let href = link.get("href")?.as_str()?;
+                    let rel = link.get("rel").and_then(|r| r.as_str()).unwrap_or_default();
+                    Some((rel.to_string(), href.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn stac_issue(collection_id: &str, item_id: &str, path: String, message: String) -> CatalogIssue {
+    CatalogIssue {
+        collection_id: collection_id.to_string(),
+        item_id: item_id.to_string(),
+        kind: "stac".to_string(),
+        name: path,
+        href: String::new(),
+        status: None,
+        error: Some(message),
+    }
+}
+
+/// Runs the catalog check whenever the configured interval has passed since
+/// the last run finished. Runs for the lifetime of the application.
+pub async fn run_catalog_check_scheduler(db_service: DatabaseService, config: Config) {
+    loop {
+        // Wakes up regularly so a changed interval applies without a restart
+        tokio::time::sleep(Duration::from_secs(15 * 60)).await;
+
+        let hours = db_service
+            .settings
+            .get::<CatalogCheckIntervalHours>()
+            .await
+            .unwrap_or_else(|_| CatalogCheckIntervalHours::default_value());
+        let last_run = db_service
+            .catalog_checks
+            .summary()
+            .await
+            .ok()
+            .flatten()
+            .and_then(|summary| chrono::DateTime::parse_from_rfc3339(&summary.finished_at).ok());
+        let due = match last_run {
+            _ if hours == 0 => false,
+            Some(finished_at) => {
+                chrono::Utc::now() - finished_at.with_timezone(&chrono::Utc)
+                    >= chrono::Duration::hours(hours as i64)
+            }
+            None => true,
+        };
+        if due {
+            match run_check(&db_service, &config).await {
+                Ok(summary) if summary.broken_links > 0 => {
+                    eprintln!("Catalog check: {} broken links", summary.broken_links)
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Catalog check: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::config::Config;
+use crate::database::{DatabaseService, DbItem};
+use crate::server::assets::href_file_name;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncReadExt;
+
+/// STAC file extension schema, for the `file:size` and `file:checksum` of
+/// stored assets
+pub const FILE_EXTENSION: &str = "https://stac-extensions.github.io/file/v2.1.0/schema.json";
+
+/// Multihash prefix of a SHA2-256 digest: function code 0x12, 32 bytes
+pub const SHA2_256_MULTIHASH: &str = "1220";
+
+/// Bytes read from disk per hashed chunk
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Items read from the database at a time
+const PAGE_SIZE: i64 = 200;
+
+/// Size and checksum of a stored asset file
+#[derive(Debug, Clone)]
+pub struct FileInfo {
+    pub size: u64,
+    /// SHA2-256 multihash in hex, as in `file:checksum`
+    pub checksum: String,
+}
+
+/// Reads a file to its end, returning its size and checksum
+pub async fn file_info(path: impl AsRef<Path>) -> std::io::Result<FileInfo> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut buffer = vec![0u8; CHUNK_BYTES];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok(FileInfo {
+        size,
+        checksum: sha2_256_multihash(&hasher.finalize()),
+    })
+}
+
+/// Sets an asset's `file:size` and `file:checksum` to those of its file
+pub fn set_file_info(asset: &mut Value, info: &FileInfo) {
+    asset["file:size"] = json!(info.size);
+    asset["file:checksum"] = json!(info.checksum);
+}
+
+/// Declares the file extension on an item whose assets carry its fields
+pub fn add_file_extension(item: &mut DbItem) {
+    let mut extensions: Vec<Value> = item
+        .stac_extensions
+        .as_ref()
+        .and_then(|e| e.as_array().cloned())
+        .unwrap_or_default();
+    if !extensions
+        .iter()
+        .any(|e| e.as_str() == Some(FILE_EXTENSION))
+    {
+        extensions.push(json!(FILE_EXTENSION));
+    }
+    item.stac_extensions = Some(Value::Array(extensions));
+}
+
+/// A stored asset whose file is gone or no longer matches its checksum
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecksumIssue {
+    pub collection_id: String,
+    pub item_id: String,
+    pub asset_key: String,
+    /// `file:checksum` of the asset
+    pub expected: String,
+    /// Checksum of the file on disk; `None` when it could not be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of `verify_all`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChecksumReport {
+    /// Local asset files hashed
+    pub checked: usize,
+    pub bytes_read: u64,
+    /// Files matching their SHA2-256 `file:checksum`
+    pub verified: usize,
+    /// Local files without a SHA2-256 `file:checksum` to compare with
+    pub without_checksum: usize,
+    /// Files that differ from their checksum, or that are gone
+    pub failed: Vec<ChecksumIssue>,
+}
+
+/// File of an asset in the item's asset folder: stored under its key by
+/// uploads and re-hosting, or under the last segment of its href by copied
+/// files. `None` for assets hosted elsewhere.
+async fn local_file(config: &Config, item: &DbItem, key: &str, asset: &Value) -> Option<PathBuf> {
+    let dir = PathBuf::from(config.assets_dir())
+        .join(&item.collection_id)
+        .join(&item.id);
+    let href = asset
+        .get("href")
+        .and_then(|h| h.as_str())
+        .unwrap_or_default();
+    let served_here = href.contains(&format!(
+        "/collections/{}/items/{}/",
+        item.collection_id, item.id
+    ));
+    for name in href_file_name(href).into_iter().chain([key.to_string()]) {
+        let path = dir.join(name);
+        if tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+            return Some(path);
+        }
+    }
+    served_here.then(|| dir.join(key))
+}
+
+/// Hashes the file of every locally stored asset of the live items and
+/// compares it with the asset's `file:checksum`. Only SHA2-256 multihashes
+/// are compared; files with another kind of checksum, or none, are counted
+/// but not judged.
+pub async fn verify_all(
+    db_service: &DatabaseService,
+    config: &Config,
+) -> Result<ChecksumReport, String> {
+    let collection_ids: Vec<String> = db_service
+        .collections
+        .get_all()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|c| c.id)
+        .collect();
+
+    let mut report = ChecksumReport::default();
+    let mut offset = 0;
+    loop {
+        let items = db_service
+            .items
+            .get_by_collections(&collection_ids, PAGE_SIZE, offset)
+            .await
+            .map_err(|e| e.to_string())?;
+        for item in &items {
+            let Some(assets) = item.assets.as_ref().and_then(|a| a.as_object()) else {
+                continue;
+            };
+            for (key, asset) in assets {
+                let Some(path) = local_file(config, item, key, asset).await else {
+                    continue;
+                };
+                let expected = asset
+                    .get("file:checksum")
+                    .and_then(|c| c.as_str())
+                    .map(str::to_ascii_lowercase)
+                    .filter(|c| c.starts_with(SHA2_256_MULTIHASH));
+                let issue = |actual: Option<String>, error: Option<String>| ChecksumIssue {
+                    collection_id: item.collection_id.clone(),
+                    item_id: item.id.clone(),
+                    asset_key: key.clone(),
+                    expected: expected.clone().unwrap_or_default(),
+                    actual,
+                    error,
+                };
+
+                match file_info(&path).await {
+                    Ok(info) => {
+                        report.checked += 1;
+                        report.bytes_read += info.size;
+                        match &expected {
+                            None => report.without_checksum += 1,
+                            Some(expected) if *expected == info.checksum => report.verified += 1,
+                            Some(_) => report.failed.push(issue(Some(info.checksum), None)),
+                        }
+                    }
+                    // A remote-looking asset without a file has nothing to
+                    // verify; one served from here has lost its file
+                    Err(e) if expected.is_some() => {
+                        report.failed.push(issue(None, Some(e.to_string())))
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+        if (items.len() as i64) < PAGE_SIZE {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    Ok(report)
+}
+
+/// A SHA2-256 digest as a multihash in hex, as in `file:checksum`
+pub fn sha2_256_multihash(digest: &[u8]) -> String {
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", SHA2_256_MULTIHASH, hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FIPS 180-2 examples: "abc" and the two-block message
+    #[test]
+    fn multihash_of_nist_test_vectors() {
+        assert_eq!(
+            sha2_256_multihash(&Sha256::digest(b"abc")),
+            "1220ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha2_256_multihash(&Sha256::digest(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "1220248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[tokio::test]
+    async fn file_info_reads_size_and_checksum() {
+        let path = std::env::temp_dir().join(format!("zenstac-checksum-{}", std::process::id()));
+        // Longer than one chunk, so the digest spans several reads
+        let content = vec![b'a'; CHUNK_BYTES + 1000];
+        std::fs::write(&path, &content).unwrap();
+        let info = file_info(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(info.size, content.len() as u64);
+        assert_eq!(info.checksum, sha2_256_multihash(&Sha256::digest(&content)));
+    }
+}
@@ -0,0 +1,46 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Query parameter of single-item requests selecting the compact form
+#[derive(Debug, Default, Deserialize)]
+pub struct CompactQuery {
+    pub compact: Option<bool>,
+}
+
+/// Link relations a compact result page keeps so clients can still page
+const PAGING_RELS: &[&str] = &["next", "prev", "previous"];
+
+/// Strips an item down for clients on slow links: its links are dropped and
+/// each asset is reduced to `href` and `type`
+pub fn compact_item(item: &mut Value) {
+    let Some(object) = item.as_object_mut() else {
+        return;
+    };
+    object.remove("links");
+    if let Some(Value::Object(assets)) = object.get_mut("assets") {
+        for asset in assets.values_mut() {
+            let Some(fields) = asset.as_object() else {
+                continue;
+            };
+            let compact: Map<String, Value> = ["href", "type"]
+                .iter()
+                .filter_map(|key| fields.get(*key).map(|v| (key.to_string(), v.clone())))
+                .collect();
+            *asset = Value::Object(compact);
+        }
+    }
+}
+
+/// Compacts every feature of an item page and keeps only its paging links
+pub fn compact_feature_collection(page: &mut Value) {
+    if let Some(Value::Array(features)) = page.get_mut("features") {
+        features.iter_mut().for_each(compact_item);
+    }
+    if let Some(Value::Array(links)) = page.get_mut("links") {
+        links.retain(|link| {
+            link.get("rel")
+                .and_then(|rel| rel.as_str())
+                .is_some_and(|rel| PAGING_RELS.contains(&rel))
+        });
+    }
+}
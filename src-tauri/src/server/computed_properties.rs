@@ -0,0 +1,163 @@
+use crate::database::computed_properties::ComputedProperty;
+use crate::database::DatabaseService;
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::collections::HashSet;
+
+/// Items rewritten per batch when a collection's computed properties change
+const REWRITE_BATCH: i64 = 500;
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Handler listing the computed properties of a collection
+/// (GET /collections/:collection_id/computed-properties)
+pub async fn get_computed_properties(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    state
+        .db_service
+        .collections
+        .get_by_id(&collection_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to get collection: {}", e)))?
+        .ok_or_else(|| ApiError::not_found(format!("Collection '{}' not found", collection_id)))?;
+
+    let properties = state
+        .db_service
+        .computed_properties
+        .for_collection(&collection_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read computed properties: {}", e)))?;
+    Ok(json_response(StatusCode::OK, serde_json::json!(properties)))
+}
+
+/// Handler replacing the computed properties of a collection
+/// (PUT /collections/:collection_id/computed-properties)
+pub async fn put_computed_properties(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+    Json(properties): Json<Vec<ComputedProperty>>,
+) -> Result<Response, ApiError> {
+    let properties = set_computed_properties(&state.db_service, &collection_id, properties)
+        .await
+        .map_err(|(status, description)| ApiError::from_status(status, description))?;
+    Ok(json_response(StatusCode::OK, serde_json::json!(properties)))
+}
+
+/// Replaces the properties a collection computes for its items and rewrites
+/// its items, so stored values, indexed columns and search match the new
+/// formulas. Properties no longer computed are removed from the items.
+/// Shared by the HTTP endpoint and the Tauri command.
+pub async fn set_computed_properties(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    properties: Vec<ComputedProperty>,
+) -> Result<Vec<ComputedProperty>, (StatusCode, String)> {
+    let mut names = HashSet::new();
+    for property in &properties {
+        property.check().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        if !names.insert(property.property.as_str()) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Property '{}' is computed twice", property.property),
+            ));
+        }
+    }
+
+    match db_service.collections.get_by_id(collection_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Collection '{}' not found", collection_id),
+            ))
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to get collection: {}", e),
+            ))
+        }
+    }
+
+    let read_error = |e: rusqlite::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read computed properties: {}", e),
+        )
+    };
+    let previous = db_service
+        .computed_properties
+        .for_collection(collection_id)
+        .await
+        .map_err(read_error)?;
+    db_service
+        .computed_properties
+        .set_for_collection(collection_id, &properties)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to save computed properties: {}", e),
+            )
+        })?;
+
+    // Stored items get the new values on update; dropped ones are removed
+    let dropped: Vec<&str> = previous
+        .iter()
+        .map(|p| p.property.as_str())
+        .filter(|name| !names.contains(name))
+        .collect();
+    let mut offset = 0;
+    loop {
+        let items = db_service
+            .items
+            .get_by_collection(collection_id, Some(REWRITE_BATCH), Some(offset))
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to read items: {}", e),
+                )
+            })?;
+        for mut item in items.iter().cloned() {
+            if let Some(stored) = item.properties.as_object_mut() {
+                for name in &dropped {
+                    stored.remove(*name);
+                }
+            }
+            db_service.items.update(&item).await.map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to update item '{}': {}", item.id, e),
+                )
+            })?;
+        }
+        if (items.len() as i64) < REWRITE_BATCH {
+            break;
+        }
+        offset += REWRITE_BATCH;
+    }
+
+    db_service
+        .computed_properties
+        .for_collection(collection_id)
+        .await
+        .map_err(read_error)
+}
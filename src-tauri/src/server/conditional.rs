@@ -0,0 +1,217 @@
+use crate::database::translations::Translations;
+use crate::database::ItemChange;
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Validators of an item list page: the time of the collection's last item
+/// change, and a weak entity tag of its revision and the requested page
+#[derive(Debug, Clone)]
+pub struct ListValidators {
+    pub last_modified: DateTime<Utc>,
+    pub etag: String,
+}
+
+impl ListValidators {
+    /// Validators of the page `uri` asks for. The query and `Accept-Language`
+    /// select the representation, so they are part of the entity tag.
+    pub fn new(change: &ItemChange, uri: &Uri, request_headers: &HeaderMap) -> Self {
+        let mut hasher = DefaultHasher::new();
+        uri.query().unwrap_or("").hash(&mut hasher);
+        request_headers
+            .get("Accept-Language")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .hash(&mut hasher);
+        Self {
+            // HTTP dates have whole seconds
+            last_modified: DateTime::from_timestamp(change.last_modified.timestamp(), 0)
+                .unwrap_or(change.last_modified),
+            etag: format!("W/\"{}-{:016x}\"", change.revision, hasher.finish()),
+        }
+    }
+
+    /// Whether the client's copy is current
+    pub fn matches(&self, request_headers: &HeaderMap) -> bool {
+        is_fresh(request_headers, &self.etag, Some(&self.last_modified))
+    }
+
+    /// Adds `ETag` and `Last-Modified`, and lets clients cache the page as
+    /// long as they revalidate it
+    pub fn add_headers(&self, headers: &mut HeaderMap) {
+        set_validator_headers(headers, &self.etag, Some(&self.last_modified));
+    }
+
+    /// Empty `304 Not Modified` answer carrying the validators
+    pub fn not_modified(&self) -> Response {
+        let mut headers = add_cors_headers(HeaderMap::new());
+        self.add_headers(&mut headers);
+        (StatusCode::NOT_MODIFIED, headers).into_response()
+    }
+}
+
+/// Validators of a single collection or item: a strong entity tag hashing
+/// the stored record, its translations and the language it is served in,
+/// and the record's `updated_at`. The tag names the stored version; access
+/// counts derived when serving can change under it, and items with
+/// computed properties relative to now are served without validators.
+#[derive(Debug, Clone)]
+pub struct RecordValidators {
+    pub last_modified: Option<DateTime<Utc>>,
+    pub etag: String,
+}
+
+impl RecordValidators {
+    pub fn new<T: Serialize>(
+        record: &T,
+        translations: &Translations,
+        updated_at: &str,
+        language: &str,
+    ) -> Self {
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_string(record).unwrap_or_default().hash(&mut hasher);
+        serde_json::to_string(translations).unwrap_or_default().hash(&mut hasher);
+        language.hash(&mut hasher);
+        Self {
+            last_modified: DateTime::parse_from_rfc3339(updated_at)
+                .ok()
+                .and_then(|updated| DateTime::from_timestamp(updated.timestamp(), 0)),
+            etag: format!("\"{:016x}\"", hasher.finish()),
+        }
+    }
+
+    /// Whether the client's copy is current
+    pub fn matches(&self, request_headers: &HeaderMap) -> bool {
+        is_fresh(request_headers, &self.etag, self.last_modified.as_ref())
+    }
+
+    /// Adds `ETag` and `Last-Modified`, and lets clients cache the record as
+    /// long as they revalidate it
+    pub fn add_headers(&self, headers: &mut HeaderMap) {
+        set_validator_headers(headers, &self.etag, self.last_modified.as_ref());
+    }
+
+    /// Empty `304 Not Modified` answer carrying the validators
+    pub fn not_modified(&self) -> Response {
+        let mut headers = add_cors_headers(HeaderMap::new());
+        self.add_headers(&mut headers);
+        (StatusCode::NOT_MODIFIED, headers).into_response()
+    }
+
+    /// Checks the `If-Match` precondition of a write against the stored
+    /// record, so a client cannot overwrite changes it has not seen. Without
+    /// the header the write is refused with `428` when `required`; a tag
+    /// other than the current one (or `*`) is refused with `412`.
+    pub fn check_if_match(
+        &self,
+        request_headers: &HeaderMap,
+        required: bool,
+    ) -> Result<(), Response> {
+        let Some(if_match) = request_headers
+            .get("If-Match")
+            .and_then(|v| v.to_str().ok())
+        else {
+            if !required {
+                return Ok(());
+            }
+            return Err(precondition_response(
+                StatusCode::PRECONDITION_REQUIRED,
+                "PreconditionRequired",
+                "Send the ETag of the version you are replacing in If-Match",
+                self,
+            ));
+        };
+        // If-Match compares strongly, so weak tags never match
+        if if_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag == self.etag)
+        {
+            return Ok(());
+        }
+        Err(precondition_response(
+            StatusCode::PRECONDITION_FAILED,
+            "PreconditionFailed",
+            "The resource has changed since it was read; fetch it again and reapply the change",
+            self,
+        ))
+    }
+}
+
+fn precondition_response(
+    status: StatusCode,
+    code: &'static str,
+    description: &str,
+    validators: &RecordValidators,
+) -> Response {
+    let mut headers = HeaderMap::new();
+    validators.add_headers(&mut headers);
+    ApiError::new(status, code, description)
+        .with_headers(headers)
+        .into_response()
+}
+
+/// Whether a GET's conditional headers say the client's copy is current.
+/// `If-None-Match` takes precedence over `If-Modified-Since`, as in RFC 9110.
+fn is_fresh(
+    request_headers: &HeaderMap,
+    etag: &str,
+    last_modified: Option<&DateTime<Utc>>,
+) -> bool {
+    if let Some(if_none_match) = request_headers
+        .get("If-None-Match")
+        .and_then(|v| v.to_str().ok())
+    {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || weak_tag(tag) == weak_tag(etag));
+    }
+    let Some(last_modified) = last_modified else {
+        return false;
+    };
+    request_headers
+        .get("If-Modified-Since")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+        .is_some_and(|since| *last_modified <= since.with_timezone(&Utc))
+}
+
+fn set_validator_headers(
+    headers: &mut HeaderMap,
+    etag: &str,
+    last_modified: Option<&DateTime<Utc>>,
+) {
+    if let Ok(etag) = HeaderValue::from_str(etag) {
+        headers.insert("ETag", etag);
+    }
+    if let Some(date) = last_modified.and_then(|t| HeaderValue::from_str(&http_date(t)).ok()) {
+        headers.insert("Last-Modified", date);
+    }
+    headers.insert(
+        "Access-Control-Expose-Headers",
+        HeaderValue::from_static("ETag, Last-Modified"),
+    );
+    // The tags differ per language, so caches keep one copy per language
+    headers.insert("Vary", HeaderValue::from_static("Accept-Language"));
+    headers.insert("Cache-Control", HeaderValue::from_static("no-cache"));
+    headers.remove("Pragma");
+    headers.remove("Expires");
+}
+
+/// Entity tag without its weakness prefix; `If-None-Match` compares weakly
+fn weak_tag(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+/// IMF-fixdate form of a time, as used by `Last-Modified`
+pub(crate) fn http_date(time: &DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
@@ -0,0 +1,394 @@
+use crate::config::{self, Config};
+use crate::models::link::Link;
+use crate::server::utils::ServerConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Query parameters of GET /conformance
+#[derive(Debug, Default, Deserialize)]
+pub struct ConformanceQuery {
+    /// List the endpoints and example requests of each class
+    pub verbose: Option<bool>,
+}
+
+/// Feature group a conformance class or landing link depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureGroup {
+    /// Always served
+    Core,
+    /// `enable_search`
+    Search,
+    /// `enable_transactions`
+    Transactions,
+}
+
+impl FeatureGroup {
+    fn enabled(self, server: &config::ServerConfig) -> bool {
+        match self {
+            FeatureGroup::Core => true,
+            FeatureGroup::Search => server.enable_search,
+            FeatureGroup::Transactions => server.enable_transactions,
+        }
+    }
+}
+
+/// A link a feature adds to the landing page
+#[derive(Debug, Clone, Copy)]
+pub struct LandingLink {
+    pub rel: &'static str,
+    /// Path relative to the API root
+    pub path: &'static str,
+    pub media_type: &'static str,
+    pub title: &'static str,
+}
+
+/// Conformance classes and landing page links of the implemented features.
+/// Each feature module registers what it implements, so `/conformance` and
+/// the landing page declare exactly what the router serves under the
+/// current feature toggles.
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceRegistry {
+    classes: Vec<(FeatureGroup, &'static str)>,
+    links: Vec<(FeatureGroup, LandingLink)>,
+}
+
+impl ConformanceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry of every feature module of the server
+    pub fn standard() -> Self {
+        let mut registry = Self::new();
+        crate::server::handlers::register_conformance(&mut registry);
+        crate::server::cql2::register_conformance(&mut registry);
+        crate::server::queryables::register_conformance(&mut registry);
+        crate::server::feeds::register_conformance(&mut registry);
+        registry
+    }
+
+    /// Declares conformance classes implemented while `group` is enabled
+    pub fn register(&mut self, group: FeatureGroup, classes: &[&'static str]) {
+        for class in classes {
+            if !self.classes.iter().any(|(_, c)| c == class) {
+                self.classes.push((group, class));
+            }
+        }
+    }
+
+    /// Adds a landing page link served while `group` is enabled
+    pub fn register_link(&mut self, group: FeatureGroup, link: LandingLink) {
+        self.links.push((group, link));
+    }
+
+    /// Classes of the enabled feature groups, in registration order
+    pub fn classes(&self, server: &config::ServerConfig) -> Vec<String> {
+        self.classes
+            .iter()
+            .filter(|(group, _)| group.enabled(server))
+            .map(|(_, class)| class.to_string())
+            .collect()
+    }
+
+    /// Landing page links of the enabled feature groups
+    pub fn landing_links(
+        &self,
+        server: &config::ServerConfig,
+        server_config: &ServerConfig,
+    ) -> Vec<Link> {
+        self.links
+            .iter()
+            .filter(|(group, _)| group.enabled(server))
+            .map(|(_, link)| Link {
+                href: server_config.href(link.path),
+                rel: link.rel.to_string(),
+                r#type: Some(link.media_type.to_string()),
+                title: Some(link.title.to_string()),
+                method: None,
+                headers: None,
+                body: None,
+            })
+            .collect()
+    }
+}
+
+/// An endpoint, relative to the API root
+#[derive(Debug, Clone, Serialize)]
+pub struct Endpoint {
+    pub method: &'static str,
+    pub path: &'static str,
+}
+
+/// What a conformance class covers on this server
+struct ClassDetails {
+    /// Matches the class URI by suffix, so versions of a class share details
+    suffix: &'static str,
+    title: &'static str,
+    endpoints: &'static [(&'static str, &'static str)],
+    /// Example requests, relative to the API root
+    examples: &'static [&'static str],
+}
+
+const ITEMS: &str = "/collections/{collectionId}/items";
+const ITEM: &str = "/collections/{collectionId}/items/{itemId}";
+
+const FILTER_ENDPOINTS: &[(&str, &str)] =
+    &[("GET", "/search"), ("POST", "/search"), ("GET", ITEMS)];
+const FILTER_EXAMPLES: &[&str] = &[
+    "GET /search?filter=eo:cloud_cover < 10 AND datetime > TIMESTAMP('2024-01-01T00:00:00Z')",
+    "POST /search {\"filter\": {\"op\": \"<\", \"args\": [{\"property\": \"eo:cloud_cover\"}, 10]}}",
+];
+
+const CLASS_DETAILS: &[ClassDetails] = &[
+    ClassDetails {
+        suffix: "/core",
+        title: "Core",
+        endpoints: &[("GET", "/"), ("GET", "/conformance"), ("GET", "/api")],
+        examples: &["GET /", "GET /conformance?verbose=true"],
+    },
+    ClassDetails {
+        suffix: "/collections",
+        title: "Collections",
+        endpoints: &[("GET", "/collections"), ("GET", "/collections/{collectionId}")],
+        examples: &["GET /collections"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features",
+        title: "OGC API - Features",
+        endpoints: &[("GET", ITEMS), ("GET", ITEM)],
+        examples: &["GET /collections/{collectionId}/items?limit=10&offset=10"],
+    },
+    ClassDetails {
+        suffix: "/item-search",
+        title: "Item Search",
+        endpoints: &[("GET", "/search"), ("POST", "/search")],
+        examples: &[
+            "GET /search?collections={collectionId}&bbox=-10,35,5,45&datetime=2024-01-01/..&limit=10",
+            "POST /search {\"collections\": [\"{collectionId}\"], \"limit\": 10}",
+        ],
+    },
+    ClassDetails {
+        suffix: "/item-search#query",
+        title: "Item Search - Query",
+        endpoints: &[("GET", "/search"), ("POST", "/search")],
+        examples: &[
+            "GET /search?query={\"eo:cloud_cover\":{\"lt\":10}}",
+            "POST /search {\"query\": {\"platform\": {\"in\": [\"sentinel-2a\", \"sentinel-2b\"]}}}",
+        ],
+    },
+    ClassDetails {
+        suffix: "/item-search#sort",
+        title: "Item Search - Sort",
+        endpoints: &[("GET", "/search"), ("POST", "/search")],
+        examples: &[
+            "GET /search?sortby=-datetime,id",
+            "POST /search {\"sortby\": [{\"field\": \"datetime\", \"direction\": \"desc\"}]}",
+        ],
+    },
+    ClassDetails {
+        suffix: "/item-search#filter",
+        title: "Item Search - Filter",
+        endpoints: FILTER_ENDPOINTS,
+        examples: FILTER_EXAMPLES,
+    },
+    ClassDetails {
+        suffix: "/item-search#context",
+        title: "Item Search - Context",
+        endpoints: &[("GET", "/search"), ("POST", "/search")],
+        examples: &["GET /search?limit=10"],
+    },
+    ClassDetails {
+        suffix: "/item-search#free-text",
+        title: "Item Search - Free Text",
+        endpoints: &[("GET", "/search"), ("POST", "/search")],
+        examples: &["GET /search?q=sentinel,landsat"],
+    },
+    ClassDetails {
+        suffix: "/collection-search",
+        title: "Collection Search",
+        endpoints: &[("GET", "/collections")],
+        examples: &[
+            "GET /collections?limit=10",
+            "GET /collections?bbox=-10,35,5,45&datetime=2024-01-01/..",
+        ],
+    },
+    ClassDetails {
+        suffix: "/collection-search#free-text",
+        title: "Collection Search - Free Text",
+        endpoints: &[("GET", "/collections")],
+        examples: &["GET /collections?q=land cover"],
+    },
+    ClassDetails {
+        suffix: "/collections/extensions/transaction",
+        title: "Collection Transactions",
+        endpoints: &[
+            ("POST", "/collections"),
+            ("PUT", "/collections/{collectionId}"),
+            ("PATCH", "/collections/{collectionId}"),
+            ("DELETE", "/collections/{collectionId}"),
+        ],
+        examples: &["POST /collections {\"id\": \"{collectionId}\", ...}"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features/extensions/transaction",
+        title: "Item Transactions",
+        endpoints: &[("POST", ITEMS), ("PUT", ITEM), ("PATCH", ITEM), ("DELETE", ITEM)],
+        examples: &["POST /collections/{collectionId}/items {\"type\": \"Feature\", ...}"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-1/1.0/conf/core",
+        title: "OGC API - Features Part 1 Core",
+        endpoints: &[
+            ("GET", "/"),
+            ("GET", "/conformance"),
+            ("GET", "/collections"),
+            ("GET", ITEMS),
+            ("GET", ITEM),
+        ],
+        examples: &["GET /collections/{collectionId}/items?limit=10"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-1/1.0/conf/oas30",
+        title: "OpenAPI 3.0",
+        endpoints: &[("GET", "/api"), ("GET", "/api.html")],
+        examples: &["GET /api", "GET /api.html"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-1/1.0/conf/geojson",
+        title: "GeoJSON",
+        endpoints: &[("GET", ITEMS), ("GET", ITEM)],
+        examples: &["GET /collections/{collectionId}/items/{itemId}?f=geojson"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-4/1.0/conf/create-replace-delete",
+        title: "OGC API - Features Part 4 Create/Replace/Delete",
+        endpoints: &[("POST", ITEMS), ("PUT", ITEM), ("DELETE", ITEM)],
+        examples: &["DELETE /collections/{collectionId}/items/{itemId}"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-4/1.0/conf/update",
+        title: "OGC API - Features Part 4 Update",
+        endpoints: &[("PATCH", ITEM)],
+        examples: &["PATCH /collections/{collectionId}/items/{itemId} {\"properties\": {...}}"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-4/1.0/conf/optimistic-locking-etags",
+        title: "OGC API - Features Part 4 Optimistic Locking with ETags",
+        endpoints: &[("GET", ITEM), ("PUT", ITEM), ("PATCH", ITEM), ("DELETE", ITEM)],
+        examples: &["PUT /collections/{collectionId}/items/{itemId} (If-Match: \"{etag}\")"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-4/1.0/conf/features",
+        title: "OGC API - Features Part 4 Features",
+        endpoints: &[("POST", ITEMS), ("PUT", ITEM), ("PATCH", ITEM), ("DELETE", ITEM)],
+        examples: &["POST /collections/{collectionId}/items {\"type\": \"Feature\", ...}"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-3/1.0/conf/filter",
+        title: "OGC API - Features Part 3 Filter",
+        endpoints: FILTER_ENDPOINTS,
+        examples: FILTER_EXAMPLES,
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-3/1.0/conf/features-filter",
+        title: "OGC API - Features Part 3 Features Filter",
+        endpoints: &[("GET", ITEMS)],
+        examples: &["GET /collections/{collectionId}/items?filter=platform LIKE 'sentinel%'"],
+    },
+    ClassDetails {
+        suffix: "/ogcapi-features-3/1.0/conf/queryables",
+        title: "OGC API - Features Part 3 Queryables",
+        endpoints: &[("GET", "/queryables"), ("GET", "/collections/{collectionId}/queryables")],
+        examples: &["GET /collections/{collectionId}/queryables"],
+    },
+    ClassDetails {
+        suffix: "/cql2/1.0/conf/cql2-text",
+        title: "CQL2 Text",
+        endpoints: FILTER_ENDPOINTS,
+        examples: &["GET /search?filter=platform IN ('sentinel-2a', 'sentinel-2b')"],
+    },
+    ClassDetails {
+        suffix: "/cql2/1.0/conf/cql2-json",
+        title: "CQL2 JSON",
+        endpoints: FILTER_ENDPOINTS,
+        examples: &["GET /search?filter-lang=cql2-json&filter={\"op\":\"isNull\",\"args\":[{\"property\":\"gsd\"}]}"],
+    },
+    ClassDetails {
+        suffix: "/cql2/1.0/conf/basic-cql2",
+        title: "Basic CQL2",
+        endpoints: FILTER_ENDPOINTS,
+        examples: &["GET /search?filter=eo:cloud_cover <= 20 AND NOT (platform = 'landsat-8')"],
+    },
+    ClassDetails {
+        suffix: "/cql2/1.0/conf/advanced-comparison-operators",
+        title: "CQL2 Advanced Comparison Operators",
+        endpoints: FILTER_ENDPOINTS,
+        examples: &["GET /search?filter=gsd BETWEEN 10 AND 30 AND id LIKE 'S2%'"],
+    },
+    ClassDetails {
+        suffix: "/cql2/1.0/conf/basic-spatial-functions",
+        title: "CQL2 Basic Spatial Functions",
+        endpoints: FILTER_ENDPOINTS,
+        examples: &["GET /search?filter=S_INTERSECTS(geometry, BBOX(-10, 35, 5, 45))"],
+    },
+    ClassDetails {
+        suffix: "/cql2/1.0/conf/temporal-functions",
+        title: "CQL2 Temporal Functions",
+        endpoints: FILTER_ENDPOINTS,
+        examples: &["GET /search?filter=T_DURING(datetime, INTERVAL('2024-01-01', '2024-06-30'))"],
+    },
+];
+
+/// Body of GET /conformance: the declared classes and, in verbose mode, the
+/// endpoints and example requests of each
+pub fn conformance_body(config: &Config, verbose: bool) -> Value {
+    let classes = ConformanceRegistry::standard().classes(&config.server);
+    if !verbose {
+        return json!({ "conformsTo": classes });
+    }
+
+    let server_config = ServerConfig::from_config(config);
+    let details: Vec<Value> = classes
+        .iter()
+        .map(|uri| {
+            let Some(details) = CLASS_DETAILS
+                .iter()
+                .filter(|d| uri.ends_with(d.suffix))
+                .max_by_key(|d| d.suffix.len())
+            else {
+                return json!({ "uri": uri, "endpoints": [], "examples": [] });
+            };
+            // Filter classes outlive a disabled search on the items endpoint
+            let enabled = |path: &str| config.server.enable_search || !path.starts_with("/search");
+            let endpoints: Vec<Endpoint> = details
+                .endpoints
+                .iter()
+                .filter(|(_, path)| enabled(path))
+                .map(|&(method, path)| Endpoint { method, path })
+                .collect();
+            let examples: Vec<String> = details
+                .examples
+                .iter()
+                .filter(|example| {
+                    example
+                        .split_once(' ')
+                        .is_none_or(|(_, rest)| enabled(rest))
+                })
+                .map(|example| match example.split_once(' ') {
+                    Some((method, rest)) => format!("{} {}", method, server_config.href(rest)),
+                    None => example.to_string(),
+                })
+                .collect();
+            json!({
+                "uri": uri,
+                "title": details.title,
+                "endpoints": endpoints,
+                "examples": examples
+            })
+        })
+        .collect();
+    json!({
+        "conformsTo": classes,
+        "classes": details
+    })
+}
@@ -0,0 +1,1104 @@
+//! CQL2 filters (OGC API - Features Part 3, STAC Filter extension).
+//!
+//! Filters arrive as CQL2-text or CQL2-JSON, are parsed into an [`Expr`] and
+//! evaluated against each candidate item. Comparisons follow SQL's
+//! three-valued logic: a predicate on a property the item lacks is unknown,
+//! and only items for which the whole filter is true match. Spatial
+//! functions compare bounding boxes.
+
+use crate::database::asset_index;
+use crate::models::item::Geometry;
+use crate::server::conformance::{ConformanceRegistry, FeatureGroup};
+use crate::server::geometry::wkt_to_geometry;
+use crate::server::helpers::calculate_bbox_for_geometry;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::Value;
+
+/// Filter classes: the items endpoint filters whatever the feature toggles,
+/// search only with search enabled
+pub fn register_conformance(registry: &mut ConformanceRegistry) {
+    registry.register(
+        FeatureGroup::Core,
+        &[
+            "http://www.opengis.net/spec/ogcapi-features-3/1.0/conf/filter",
+            "http://www.opengis.net/spec/ogcapi-features-3/1.0/conf/features-filter",
+            "http://www.opengis.net/spec/cql2/1.0/conf/cql2-text",
+            "http://www.opengis.net/spec/cql2/1.0/conf/cql2-json",
+            "http://www.opengis.net/spec/cql2/1.0/conf/basic-cql2",
+            "http://www.opengis.net/spec/cql2/1.0/conf/advanced-comparison-operators",
+            "http://www.opengis.net/spec/cql2/1.0/conf/basic-spatial-functions",
+            "http://www.opengis.net/spec/cql2/1.0/conf/temporal-functions",
+        ],
+    );
+    registry.register(
+        FeatureGroup::Search,
+        &["https://api.stacspec.org/v1.0.0-rc.2/item-search#filter"],
+    );
+}
+
+/// A parsed filter
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Bool(bool),
+    Compare(CompareOp, Operand, Operand),
+    Like(Operand, String),
+    Between(Operand, Operand, Operand),
+    In(Operand, Vec<Operand>),
+    IsNull(Operand),
+    Spatial(SpatialOp, Operand, Operand),
+    Temporal(TemporalOp, Operand, Operand),
+}
+
+/// A value a predicate works on
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Property(String),
+    Literal(Value),
+    Timestamp(DateTime<Utc>),
+    /// Closed or half-open interval; `None` is an open end (`..`)
+    Interval(Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+    /// Literal geometry, reduced to its bounding box
+    Bbox([f64; 4]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpatialOp {
+    Intersects,
+    Disjoint,
+    Within,
+    Contains,
+    Equals,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemporalOp {
+    After,
+    Before,
+    Contains,
+    Disjoint,
+    During,
+    Equals,
+    FinishedBy,
+    Finishes,
+    Intersects,
+    Meets,
+    MetBy,
+    OverlappedBy,
+    Overlaps,
+    StartedBy,
+    Starts,
+}
+
+impl CompareOp {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        Some(match symbol {
+            "=" => CompareOp::Eq,
+            "<>" | "!=" => CompareOp::Ne,
+            "<" => CompareOp::Lt,
+            "<=" => CompareOp::Le,
+            ">" => CompareOp::Gt,
+            ">=" => CompareOp::Ge,
+            _ => return None,
+        })
+    }
+
+    fn holds(self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            CompareOp::Eq => ordering == Equal,
+            CompareOp::Ne => ordering != Equal,
+            CompareOp::Lt => ordering == Less,
+            CompareOp::Le => ordering != Greater,
+            CompareOp::Gt => ordering == Greater,
+            CompareOp::Ge => ordering != Less,
+        }
+    }
+}
+
+impl SpatialOp {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "s_intersects" => SpatialOp::Intersects,
+            "s_disjoint" => SpatialOp::Disjoint,
+            "s_within" => SpatialOp::Within,
+            "s_contains" => SpatialOp::Contains,
+            "s_equals" => SpatialOp::Equals,
+            _ => return None,
+        })
+    }
+}
+
+impl TemporalOp {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "t_after" => TemporalOp::After,
+            "t_before" => TemporalOp::Before,
+            "t_contains" => TemporalOp::Contains,
+            "t_disjoint" => TemporalOp::Disjoint,
+            "t_during" => TemporalOp::During,
+            "t_equals" => TemporalOp::Equals,
+            "t_finishedby" => TemporalOp::FinishedBy,
+            "t_finishes" => TemporalOp::Finishes,
+            "t_intersects" => TemporalOp::Intersects,
+            "t_meets" => TemporalOp::Meets,
+            "t_metby" => TemporalOp::MetBy,
+            "t_overlappedby" => TemporalOp::OverlappedBy,
+            "t_overlaps" => TemporalOp::Overlaps,
+            "t_startedby" => TemporalOp::StartedBy,
+            "t_starts" => TemporalOp::Starts,
+            _ => return None,
+        })
+    }
+}
+
+/// Parses a filter in the given language (`cql2-text` or `cql2-json`). Without
+/// a language, a filter that starts with `{` is read as JSON.
+pub fn parse_filter(filter: &str, lang: Option<&str>) -> Result<Expr, String> {
+    let is_json = match lang.map(|l| l.trim().to_ascii_lowercase()) {
+        Some(lang) if lang == "cql2-json" => true,
+        Some(lang) if lang == "cql2-text" => false,
+        Some(lang) => {
+            return Err(format!(
+                "Unsupported filter-lang '{}', expected cql2-text or cql2-json",
+                lang
+            ))
+        }
+        None => filter.trim_start().starts_with('{'),
+    };
+    if is_json {
+        let value: Value =
+            serde_json::from_str(filter).map_err(|e| format!("filter is not JSON: {}", e))?;
+        parse_json(&value)
+    } else {
+        TextParser::new(filter)?.parse()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// CQL2-JSON
+
+/// Parses a CQL2-JSON expression
+pub fn parse_json(value: &Value) -> Result<Expr, String> {
+    if let Some(b) = value.as_bool() {
+        return Ok(Expr::Bool(b));
+    }
+    let op = value
+        .get("op")
+        .and_then(|op| op.as_str())
+        .ok_or_else(|| format!("Expected an object with \"op\", found {}", value))?;
+    let args = value
+        .get("args")
+        .and_then(|args| args.as_array())
+        .ok_or_else(|| format!("Operator '{}' needs an \"args\" array", op))?;
+    let arity = |n: usize| -> Result<(), String> {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(format!(
+                "Operator '{}' takes {} arguments, found {}",
+                op,
+                n,
+                args.len()
+            ))
+        }
+    };
+
+    let lower = op.to_ascii_lowercase();
+    match lower.as_str() {
+        "and" | "or" => {
+            if args.len() < 2 {
+                return Err(format!("Operator '{}' needs at least two arguments", op));
+            }
+            let exprs = args.iter().map(parse_json).collect::<Result<Vec<_>, _>>()?;
+            Ok(if lower == "and" {
+                Expr::And(exprs)
+            } else {
+                Expr::Or(exprs)
+            })
+        }
+        "not" => {
+            arity(1)?;
+            Ok(Expr::Not(Box::new(parse_json(&args[0])?)))
+        }
+        "like" => {
+            arity(2)?;
+            let pattern = args[1]
+                .as_str()
+                .ok_or("The pattern of 'like' must be a string")?;
+            Ok(Expr::Like(json_operand(&args[0])?, pattern.to_string()))
+        }
+        "between" => {
+            arity(3)?;
+            Ok(Expr::Between(
+                json_operand(&args[0])?,
+                json_operand(&args[1])?,
+                json_operand(&args[2])?,
+            ))
+        }
+        "in" => {
+            arity(2)?;
+            let list = args[1]
+                .as_array()
+                .ok_or("The second argument of 'in' must be an array")?;
+            Ok(Expr::In(
+                json_operand(&args[0])?,
+                list.iter().map(json_operand).collect::<Result<_, _>>()?,
+            ))
+        }
+        "isnull" => {
+            arity(1)?;
+            Ok(Expr::IsNull(json_operand(&args[0])?))
+        }
+        _ => {
+            if let Some(compare) = CompareOp::from_symbol(op) {
+                arity(2)?;
+                return Ok(Expr::Compare(
+                    compare,
+                    json_operand(&args[0])?,
+                    json_operand(&args[1])?,
+                ));
+            }
+            if let Some(spatial) = SpatialOp::from_name(op) {
+                arity(2)?;
+                return Ok(Expr::Spatial(
+                    spatial,
+                    json_operand(&args[0])?,
+                    json_operand(&args[1])?,
+                ));
+            }
+            if let Some(temporal) = TemporalOp::from_name(op) {
+                arity(2)?;
+                return Ok(Expr::Temporal(
+                    temporal,
+                    json_operand(&args[0])?,
+                    json_operand(&args[1])?,
+                ));
+            }
+            Err(format!("Unsupported operator '{}'", op))
+        }
+    }
+}
+
+fn json_operand(value: &Value) -> Result<Operand, String> {
+    let Some(object) = value.as_object() else {
+        return Ok(Operand::Literal(value.clone()));
+    };
+    if let Some(name) = object.get("property").and_then(|p| p.as_str()) {
+        return Ok(Operand::Property(name.to_string()));
+    }
+    if let Some(text) = object.get("timestamp").and_then(|t| t.as_str()) {
+        return Ok(Operand::Timestamp(parse_timestamp(text)?));
+    }
+    if let Some(text) = object.get("date").and_then(|d| d.as_str()) {
+        return Ok(Operand::Timestamp(parse_date(text)?));
+    }
+    if let Some(bounds) = object.get("interval").and_then(|i| i.as_array()) {
+        if bounds.len() != 2 {
+            return Err("An interval has a start and an end".to_string());
+        }
+        let bound = |b: &Value| -> Result<Option<DateTime<Utc>>, String> {
+            match b {
+                Value::String(s) => parse_interval_bound(s),
+                Value::Object(o) => match (o.get("timestamp"), o.get("date")) {
+                    (Some(Value::String(t)), _) => parse_timestamp(t).map(Some),
+                    (_, Some(Value::String(d))) => parse_date(d).map(Some),
+                    _ => Err("Interval bounds must be timestamps, dates or \"..\"".to_string()),
+                },
+                _ => Err("Interval bounds must be timestamps, dates or \"..\"".to_string()),
+            }
+        };
+        return Ok(Operand::Interval(bound(&bounds[0])?, bound(&bounds[1])?));
+    }
+    if let Some(bbox) = object.get("bbox").and_then(|b| b.as_array()) {
+        let numbers: Vec<f64> = bbox.iter().filter_map(|n| n.as_f64()).collect();
+        return bbox_operand(&numbers);
+    }
+    if object.contains_key("type") && object.contains_key("coordinates")
+        || object.contains_key("geometries")
+    {
+        let geometry: Geometry = serde_json::from_value(value.clone())
+            .map_err(|e| format!("Invalid GeoJSON geometry: {}", e))?;
+        return geometry_operand(&geometry);
+    }
+    Err(format!("Unsupported operand {}", value))
+}
+
+// ---------------------------------------------------------------------------
+// CQL2-text
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    /// Double-quoted identifier, never a keyword
+    Quoted(String),
+    Str(String),
+    Num(f64),
+    Wkt(String),
+    Symbol(&'static str),
+}
+
+const WKT_TYPES: &[&str] = &[
+    "POINT",
+    "LINESTRING",
+    "POLYGON",
+    "MULTIPOINT",
+    "MULTILINESTRING",
+    "MULTIPOLYGON",
+    "GEOMETRYCOLLECTION",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                        text.push('\'');
+                        i += 2;
+                    }
+                    Some('\'') => {
+                        i += 1;
+                        break;
+                    }
+                    Some(ch) => {
+                        text.push(*ch);
+                        i += 1;
+                    }
+                    None => return Err("Unterminated string literal".to_string()),
+                }
+            }
+            tokens.push(Token::Str(text));
+        } else if c == '"' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|ch| *ch == '"')
+                .ok_or("Unterminated quoted identifier")?;
+            tokens.push(Token::Quoted(chars[i + 1..i + 1 + end].iter().collect()));
+            i += end + 2;
+        } else if c.is_ascii_digit()
+            || (c == '-' || c == '+' || c == '.')
+                && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())
+                && !matches!(
+                    tokens.last(),
+                    Some(Token::Num(_) | Token::Str(_) | Token::Ident(_) | Token::Quoted(_))
+                )
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_ascii_digit()
+                    || chars[i] == '.'
+                    || chars[i] == 'e'
+                    || chars[i] == 'E'
+                    || (chars[i] == '-' || chars[i] == '+') && matches!(chars[i - 1], 'e' | 'E'))
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid number '{}'", text))?;
+            tokens.push(Token::Num(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | ':' | '.'))
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if WKT_TYPES.contains(&word.to_ascii_uppercase().as_str()) {
+                // Geometry literals are passed whole to the WKT parser
+                let mut j = i;
+                while j < chars.len() && chars[j] != '(' && !chars[j].is_ascii_punctuation() {
+                    j += 1;
+                }
+                if chars.get(j) == Some(&'(') {
+                    let mut depth = 0;
+                    while j < chars.len() {
+                        match chars[j] {
+                            '(' => depth += 1,
+                            ')' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                        j += 1;
+                    }
+                    if depth != 0 {
+                        return Err(format!("Unbalanced parentheses in {} literal", word));
+                    }
+                    tokens.push(Token::Wkt(chars[start..=j].iter().collect()));
+                    i = j + 1;
+                    continue;
+                }
+            }
+            tokens.push(Token::Ident(word));
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let symbol = match two.as_str() {
+                "<=" => "<=",
+                ">=" => ">=",
+                "<>" => "<>",
+                "!=" => "!=",
+                _ => match c {
+                    '=' => "=",
+                    '<' => "<",
+                    '>' => ">",
+                    '(' => "(",
+                    ')' => ")",
+                    ',' => ",",
+                    _ => return Err(format!("Unexpected character '{}'", c)),
+                },
+            };
+            i += symbol.len();
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+    Ok(tokens)
+}
+
+struct TextParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl TextParser {
+    fn new(input: &str) -> Result<Self, String> {
+        Ok(Self {
+            tokens: tokenize(input)?,
+            pos: 0,
+        })
+    }
+
+    fn parse(mut self) -> Result<Expr, String> {
+        if self.tokens.is_empty() {
+            return Err("Empty filter".to_string());
+        }
+        let expr = self.or_expr()?;
+        match self.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {} after the filter", describe(token))),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            Some(token) => Err(format!("Expected '{}', found {}", symbol, describe(&token))),
+            None => Err(format!("Expected '{}' at the end of the filter", symbol)),
+        }
+    }
+
+    fn or_expr(&mut self) -> Result<Expr, String> {
+        let mut exprs = vec![self.and_expr()?];
+        while self.eat_keyword("OR") {
+            exprs.push(self.and_expr()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, String> {
+        let mut exprs = vec![self.not_expr()?];
+        while self.eat_keyword("AND") {
+            exprs.push(self.not_expr()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    fn not_expr(&mut self) -> Result<Expr, String> {
+        if self.eat_keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not_expr()?)));
+        }
+        self.predicate()
+    }
+
+    fn predicate(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::Symbol("(")) {
+            self.pos += 1;
+            let expr = self.or_expr()?;
+            self.expect_symbol(")")?;
+            return Ok(expr);
+        }
+
+        if let Some(Token::Ident(name)) = self.peek().cloned() {
+            if let Some(op) = SpatialOp::from_name(&name) {
+                self.pos += 1;
+                let (a, b) = self.function_args()?;
+                return Ok(Expr::Spatial(op, a, b));
+            }
+            if let Some(op) = TemporalOp::from_name(&name) {
+                self.pos += 1;
+                let (a, b) = self.function_args()?;
+                return Ok(Expr::Temporal(op, a, b));
+            }
+            let is_bool = name.eq_ignore_ascii_case("TRUE") || name.eq_ignore_ascii_case("FALSE");
+            if is_bool && !matches!(self.tokens.get(self.pos + 1), Some(Token::Symbol(_))) {
+                self.pos += 1;
+                return Ok(Expr::Bool(name.eq_ignore_ascii_case("TRUE")));
+            }
+        }
+
+        let left = self.operand()?;
+        let negated = self.eat_keyword("NOT");
+        let expr = if self.eat_keyword("LIKE") {
+            match self.operand()? {
+                Operand::Literal(Value::String(pattern)) => Expr::Like(left, pattern),
+                _ => return Err("The pattern of LIKE must be a string".to_string()),
+            }
+        } else if self.eat_keyword("BETWEEN") {
+            let low = self.operand()?;
+            if !self.eat_keyword("AND") {
+                return Err("Expected AND in BETWEEN".to_string());
+            }
+            Expr::Between(left, low, self.operand()?)
+        } else if self.eat_keyword("IN") {
+            self.expect_symbol("(")?;
+            let mut list = vec![self.operand()?];
+            while self.peek() == Some(&Token::Symbol(",")) {
+                self.pos += 1;
+                list.push(self.operand()?);
+            }
+            self.expect_symbol(")")?;
+            Expr::In(left, list)
+        } else if !negated && self.eat_keyword("IS") {
+            let not_null = self.eat_keyword("NOT");
+            if !self.eat_keyword("NULL") {
+                return Err("Expected NULL after IS".to_string());
+            }
+            let expr = Expr::IsNull(left);
+            return Ok(if not_null {
+                Expr::Not(Box::new(expr))
+            } else {
+                expr
+            });
+        } else if negated {
+            return Err("Expected LIKE, BETWEEN or IN after NOT".to_string());
+        } else {
+            let op = match self.next() {
+                Some(Token::Symbol(symbol)) => CompareOp::from_symbol(symbol)
+                    .ok_or_else(|| format!("Expected a comparison, found '{}'", symbol))?,
+                Some(token) => {
+                    return Err(format!("Expected a comparison, found {}", describe(&token)))
+                }
+                None => return Err("Expected a comparison at the end of the filter".to_string()),
+            };
+            Expr::Compare(op, left, self.operand()?)
+        };
+        Ok(if negated {
+            Expr::Not(Box::new(expr))
+        } else {
+            expr
+        })
+    }
+
+    fn function_args(&mut self) -> Result<(Operand, Operand), String> {
+        self.expect_symbol("(")?;
+        let a = self.operand()?;
+        self.expect_symbol(",")?;
+        let b = self.operand()?;
+        self.expect_symbol(")")?;
+        Ok((a, b))
+    }
+
+    fn string_arg(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Str(text)) => Ok(text),
+            Some(token) => Err(format!("Expected a string, found {}", describe(&token))),
+            None => Err("Expected a string at the end of the filter".to_string()),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.next() {
+            Some(Token::Str(text)) => Ok(Operand::Literal(Value::String(text))),
+            Some(Token::Num(number)) => Ok(Operand::Literal(Value::from(number))),
+            Some(Token::Quoted(name)) => Ok(Operand::Property(name)),
+            Some(Token::Wkt(wkt)) => geometry_operand(&wkt_to_geometry(&wkt)?),
+            Some(Token::Ident(word)) => match word.to_ascii_uppercase().as_str() {
+                "TRUE" => Ok(Operand::Literal(Value::Bool(true))),
+                "FALSE" => Ok(Operand::Literal(Value::Bool(false))),
+                "NULL" => Ok(Operand::Literal(Value::Null)),
+                "TIMESTAMP" => {
+                    self.expect_symbol("(")?;
+                    let text = self.string_arg()?;
+                    self.expect_symbol(")")?;
+                    Ok(Operand::Timestamp(parse_timestamp(&text)?))
+                }
+                "DATE" => {
+                    self.expect_symbol("(")?;
+                    let text = self.string_arg()?;
+                    self.expect_symbol(")")?;
+                    Ok(Operand::Timestamp(parse_date(&text)?))
+                }
+                "INTERVAL" => {
+                    self.expect_symbol("(")?;
+                    let start = self.interval_bound()?;
+                    self.expect_symbol(",")?;
+                    let end = self.interval_bound()?;
+                    self.expect_symbol(")")?;
+                    Ok(Operand::Interval(start, end))
+                }
+                "BBOX" => {
+                    self.expect_symbol("(")?;
+                    let mut numbers = Vec::new();
+                    loop {
+                        match self.next() {
+                            Some(Token::Num(n)) => numbers.push(n),
+                            _ => return Err("BBOX takes numbers".to_string()),
+                        }
+                        match self.next() {
+                            Some(Token::Symbol(",")) => continue,
+                            Some(Token::Symbol(")")) => break,
+                            _ => return Err("Expected ',' or ')' in BBOX".to_string()),
+                        }
+                    }
+                    bbox_operand(&numbers)
+                }
+                _ => Ok(Operand::Property(word)),
+            },
+            Some(token) => Err(format!("Expected a value, found {}", describe(&token))),
+            None => Err("Expected a value at the end of the filter".to_string()),
+        }
+    }
+
+    fn interval_bound(&mut self) -> Result<Option<DateTime<Utc>>, String> {
+        match self.next() {
+            Some(Token::Str(text)) => parse_interval_bound(&text),
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case("TIMESTAMP") => {
+                self.expect_symbol("(")?;
+                let text = self.string_arg()?;
+                self.expect_symbol(")")?;
+                parse_timestamp(&text).map(Some)
+            }
+            Some(Token::Ident(word)) if word.eq_ignore_ascii_case("DATE") => {
+                self.expect_symbol("(")?;
+                let text = self.string_arg()?;
+                self.expect_symbol(")")?;
+                parse_date(&text).map(Some)
+            }
+            _ => Err("Interval bounds must be timestamps, dates or '..'".to_string()),
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(word) | Token::Quoted(word) => format!("'{}'", word),
+        Token::Str(text) => format!("string '{}'", text),
+        Token::Num(number) => format!("number {}", number),
+        Token::Wkt(_) => "a geometry".to_string(),
+        Token::Symbol(symbol) => format!("'{}'", symbol),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Literals
+
+fn parse_timestamp(text: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(text.trim())
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|_| format!("'{}' is not an RFC 3339 timestamp", text))
+}
+
+fn parse_date(text: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc())
+        .map_err(|_| format!("'{}' is not a YYYY-MM-DD date", text))
+}
+
+fn parse_interval_bound(text: &str) -> Result<Option<DateTime<Utc>>, String> {
+    let text = text.trim();
+    if text == ".." || text.is_empty() {
+        return Ok(None);
+    }
+    parse_timestamp(text)
+        .or_else(|_| parse_date(text))
+        .map(Some)
+}
+
+fn bbox_operand(numbers: &[f64]) -> Result<Operand, String> {
+    match numbers {
+        [min_x, min_y, max_x, max_y] | [min_x, min_y, _, max_x, max_y, _] => {
+            Ok(Operand::Bbox([*min_x, *min_y, *max_x, *max_y]))
+        }
+        _ => Err("A bbox has 4 or 6 numbers".to_string()),
+    }
+}
+
+fn geometry_operand(geometry: &Geometry) -> Result<Operand, String> {
+    bbox_operand(&calculate_bbox_for_geometry(geometry))
+}
+
+// ---------------------------------------------------------------------------
+// Evaluation
+
+impl Expr {
+    /// Whether an item, as STAC JSON, satisfies the filter
+    pub fn matches(&self, item: &Value) -> bool {
+        self.eval(item) == Some(true)
+    }
+
+    /// `None` is SQL's unknown, e.g. a comparison on a missing property
+    fn eval(&self, item: &Value) -> Option<bool> {
+        if let Some(name) = self.asset_property() {
+            return Some(self.eval_assets(item, name));
+        }
+        match self {
+            Expr::And(exprs) => {
+                let mut result = Some(true);
+                for expr in exprs {
+                    match expr.eval(item) {
+                        Some(false) => return Some(false),
+                        None => result = None,
+                        Some(true) => {}
+                    }
+                }
+                result
+            }
+            Expr::Or(exprs) => {
+                let mut result = Some(false);
+                for expr in exprs {
+                    match expr.eval(item) {
+                        Some(true) => return Some(true),
+                        None => result = None,
+                        Some(false) => {}
+                    }
+                }
+                result
+            }
+            Expr::Not(expr) => expr.eval(item).map(|b| !b),
+            Expr::Bool(b) => Some(*b),
+            Expr::Compare(op, a, b) => {
+                let ordering = compare(&resolve(item, a)?, &resolve(item, b)?)?;
+                Some(op.holds(ordering))
+            }
+            Expr::Like(a, pattern) => match resolve(item, a)? {
+                Resolved::Value(Value::String(text)) => Some(like(&text, pattern)),
+                _ => None,
+            },
+            Expr::Between(a, low, high) => {
+                let value = resolve(item, a)?;
+                let above = compare(&value, &resolve(item, low)?)? != std::cmp::Ordering::Less;
+                let below = compare(&value, &resolve(item, high)?)? != std::cmp::Ordering::Greater;
+                Some(above && below)
+            }
+            Expr::In(a, list) => {
+                let value = resolve(item, a)?;
+                let mut result = Some(false);
+                for candidate in list {
+                    match resolve(item, candidate).and_then(|c| compare(&value, &c)) {
+                        Some(std::cmp::Ordering::Equal) => return Some(true),
+                        None => result = None,
+                        Some(_) => {}
+                    }
+                }
+                result
+            }
+            Expr::IsNull(a) => Some(resolve(item, a).is_none()),
+            Expr::Spatial(op, a, b) => {
+                let a = spatial_bbox(item, a)?;
+                let b = spatial_bbox(item, b)?;
+                Some(match op {
+                    SpatialOp::Intersects => bbox_intersects(&a, &b),
+                    SpatialOp::Disjoint => !bbox_intersects(&a, &b),
+                    SpatialOp::Within => bbox_within(&a, &b),
+                    SpatialOp::Contains => bbox_within(&b, &a),
+                    SpatialOp::Equals => a == b,
+                })
+            }
+            Expr::Temporal(op, a, b) => {
+                let (a1, a2) = temporal_range(item, a)?;
+                let (b1, b2) = temporal_range(item, b)?;
+                Some(match op {
+                    TemporalOp::After => a1 > b2,
+                    TemporalOp::Before => a2 < b1,
+                    TemporalOp::Contains => a1 < b1 && a2 > b2,
+                    TemporalOp::Disjoint => a2 < b1 || a1 > b2,
+                    TemporalOp::During => a1 > b1 && a2 < b2,
+                    TemporalOp::Equals => a1 == b1 && a2 == b2,
+                    TemporalOp::FinishedBy => a1 < b1 && a2 == b2,
+                    TemporalOp::Finishes => a1 > b1 && a2 == b2,
+                    TemporalOp::Intersects => a1 <= b2 && a2 >= b1,
+                    TemporalOp::Meets => a2 == b1,
+                    TemporalOp::MetBy => a1 == b2,
+                    TemporalOp::OverlappedBy => a1 > b1 && a1 < b2 && a2 > b2,
+                    TemporalOp::Overlaps => a1 < b1 && a2 > b1 && a2 < b2,
+                    TemporalOp::StartedBy => a1 == b1 && a2 > b2,
+                    TemporalOp::Starts => a1 == b1 && a2 < b2,
+                })
+            }
+        }
+    }
+
+    /// The `assets.<field>` property a comparison predicate works on, if any
+    fn asset_property(&self) -> Option<&str> {
+        let operands: Vec<&Operand> = match self {
+            Expr::Compare(_, a, b) => vec![a, b],
+            Expr::Like(a, _) | Expr::IsNull(a) => vec![a],
+            Expr::Between(a, low, high) => vec![a, low, high],
+            Expr::In(a, list) => std::iter::once(a).chain(list).collect(),
+            _ => return None,
+        };
+        operands.into_iter().find_map(|operand| match operand {
+            Operand::Property(name) if asset_index::asset_column(name).is_some() => {
+                Some(name.as_str())
+            }
+            _ => None,
+        })
+    }
+
+    /// Whether some asset of the item satisfies a predicate on an asset
+    /// property, each role of an asset counting on its own. Never unknown,
+    /// so `NOT` finds the items without such an asset.
+    fn eval_assets(&self, item: &Value, name: &str) -> bool {
+        asset_values(item, name).into_iter().any(|value| {
+            let expr = self.with_property(name, &value);
+            expr.asset_property().is_none() && expr.eval(item) == Some(true)
+        })
+    }
+
+    /// The predicate with a property replaced by a literal value
+    fn with_property(&self, name: &str, value: &Value) -> Expr {
+        let replace = |operand: &Operand| match operand {
+            Operand::Property(property) if property == name => Operand::Literal(value.clone()),
+            other => other.clone(),
+        };
+        match self {
+            Expr::Compare(op, a, b) => Expr::Compare(*op, replace(a), replace(b)),
+            Expr::Like(a, pattern) => Expr::Like(replace(a), pattern.clone()),
+            Expr::IsNull(a) => Expr::IsNull(replace(a)),
+            Expr::Between(a, low, high) => Expr::Between(replace(a), replace(low), replace(high)),
+            Expr::In(a, list) => Expr::In(replace(a), list.iter().map(replace).collect()),
+            other => other.clone(),
+        }
+    }
+}
+
+/// Values of an `assets.<field>` property, one per asset (`null` where the
+/// asset lacks the field) and one per role for `assets.roles`
+fn asset_values(item: &Value, name: &str) -> Vec<Value> {
+    let field = name
+        .strip_prefix(asset_index::ASSET_PROPERTY_PREFIX)
+        .unwrap_or(name);
+    let Some(assets) = item.get("assets").and_then(|assets| assets.as_object()) else {
+        return Vec::new();
+    };
+    assets
+        .iter()
+        .flat_map(|(key, asset)| match field {
+            "key" => vec![Value::String(key.clone())],
+            "roles" => asset
+                .get("roles")
+                .and_then(|roles| roles.as_array())
+                .cloned()
+                .unwrap_or_default(),
+            _ => vec![asset.get(field).cloned().unwrap_or(Value::Null)],
+        })
+        .collect()
+}
+
+/// An operand evaluated for one item
+#[derive(Debug, Clone)]
+enum Resolved {
+    Value(Value),
+    Time(DateTime<Utc>),
+}
+
+/// Looks a property up on an item: `id`, `collection`, `geometry` and `bbox`
+/// are top-level members, anything else is read from `properties`
+fn property<'a>(item: &'a Value, name: &str) -> Option<&'a Value> {
+    let value = match name {
+        "id" | "collection" | "geometry" | "bbox" => item.get(name),
+        _ => {
+            let name = name.strip_prefix("properties.").unwrap_or(name);
+            item.get("properties").and_then(|p| p.get(name))
+        }
+    };
+    value.filter(|v| !v.is_null())
+}
+
+fn resolve(item: &Value, operand: &Operand) -> Option<Resolved> {
+    match operand {
+        Operand::Property(name) => property(item, name).cloned().map(Resolved::Value),
+        Operand::Literal(Value::Null) => None,
+        Operand::Literal(value) => Some(Resolved::Value(value.clone())),
+        Operand::Timestamp(time) => Some(Resolved::Time(*time)),
+        Operand::Interval(..) | Operand::Bbox(_) => None,
+    }
+}
+
+fn compare(a: &Resolved, b: &Resolved) -> Option<std::cmp::Ordering> {
+    match (a, b) {
+        (Resolved::Time(a), Resolved::Time(b)) => Some(a.cmp(b)),
+        (Resolved::Time(a), Resolved::Value(Value::String(b))) => {
+            Some(a.cmp(&parse_timestamp(b).ok()?))
+        }
+        (Resolved::Value(Value::String(a)), Resolved::Time(b)) => {
+            Some(parse_timestamp(a).ok()?.cmp(b))
+        }
+        (Resolved::Value(a), Resolved::Value(b)) => match (a, b) {
+            (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// SQL LIKE: `%` matches any run of characters, `_` exactly one and `\`
+/// escapes the next character
+fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    // Positions in the text reachable after each pattern element
+    let mut reachable = vec![false; text.len() + 1];
+    reachable[0] = true;
+    let mut p = 0;
+    while p < pattern.len() {
+        let (element, literal) = match pattern[p] {
+            '\\' if p + 1 < pattern.len() => {
+                p += 1;
+                (pattern[p], true)
+            }
+            c => (c, false),
+        };
+        let mut next = vec![false; text.len() + 1];
+        if element == '%' && !literal {
+            let mut any = false;
+            for i in 0..=text.len() {
+                any |= reachable[i];
+                next[i] = any;
+            }
+        } else {
+            for i in 0..text.len() {
+                if reachable[i] && ((element == '_' && !literal) || text[i] == element) {
+                    next[i + 1] = true;
+                }
+            }
+        }
+        reachable = next;
+        p += 1;
+    }
+    reachable[text.len()]
+}
+
+fn spatial_bbox(item: &Value, operand: &Operand) -> Option<[f64; 4]> {
+    match operand {
+        Operand::Bbox(bbox) => Some(*bbox),
+        Operand::Property(_) => {
+            if let Some(bbox) = item.get("bbox").and_then(|b| b.as_array()) {
+                let numbers: Vec<f64> = bbox.iter().filter_map(|n| n.as_f64()).collect();
+                if let Ok(Operand::Bbox(bbox)) = bbox_operand(&numbers) {
+                    return Some(bbox);
+                }
+            }
+            let geometry: Geometry = serde_json::from_value(item.get("geometry")?.clone()).ok()?;
+            match geometry_operand(&geometry) {
+                Ok(Operand::Bbox(bbox)) => Some(bbox),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn bbox_intersects(a: &[f64; 4], b: &[f64; 4]) -> bool {
+    a[0] <= b[2] && a[2] >= b[0] && a[1] <= b[3] && a[3] >= b[1]
+}
+
+fn bbox_within(inner: &[f64; 4], outer: &[f64; 4]) -> bool {
+    inner[0] >= outer[0] && inner[1] >= outer[1] && inner[2] <= outer[2] && inner[3] <= outer[3]
+}
+
+/// Start and end of an operand in microseconds, open ends at the extremes.
+/// The `datetime` property of an item without one is its
+/// `start_datetime`/`end_datetime` range.
+fn temporal_range(item: &Value, operand: &Operand) -> Option<(i64, i64)> {
+    let micros =
+        |time: Option<DateTime<Utc>>, open: i64| time.map_or(open, |t| t.timestamp_micros());
+    match operand {
+        Operand::Timestamp(time) => Some((time.timestamp_micros(), time.timestamp_micros())),
+        Operand::Interval(start, end) => Some((micros(*start, i64::MIN), micros(*end, i64::MAX))),
+        Operand::Literal(Value::String(text)) => {
+            let time = parse_timestamp(text).ok()?.timestamp_micros();
+            Some((time, time))
+        }
+        Operand::Property(name) => {
+            if let Some(text) = property(item, name).and_then(|v| v.as_str()) {
+                let time = parse_timestamp(text).ok()?.timestamp_micros();
+                return Some((time, time));
+            }
+            if name != "datetime" {
+                return None;
+            }
+            let bound = |key: &str| {
+                property(item, key)
+                    .and_then(|v| v.as_str())
+                    .and_then(|text| parse_timestamp(text).ok())
+            };
+            let (start, end) = (bound("start_datetime"), bound("end_datetime"));
+            if start.is_none() && end.is_none() {
+                return None;
+            }
+            Some((micros(start, i64::MIN), micros(end, i64::MAX)))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,49 @@
+use crate::config::Config;
+use crate::database::settings::{
+    AssetBandwidthLimit, EnableTransactions, EnableUploads, RequestRateLimit,
+    ResponseCacheSeconds, SettingsError,
+};
+use crate::database::DatabaseService;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Requests per minute each client may make in demo mode
+const DEMO_REQUEST_RATE_LIMIT: u32 = 60;
+
+/// Seconds demo responses are served from the response cache
+const DEMO_RESPONSE_CACHE_SECONDS: u64 = 60;
+
+/// Bytes per second each client may download assets at in demo mode
+const DEMO_ASSET_BANDWIDTH_LIMIT: u64 = 256 * 1024;
+
+/// Set once the demo preset was applied
+static DEMO: AtomicBool = AtomicBool::new(false);
+
+/// Whether this process serves the `--demo` catalog
+pub fn is_demo() -> bool {
+    DEMO.load(Ordering::Relaxed)
+}
+
+/// Turns the catalog into a public demo: transactions and uploads off, a
+/// per-client request limit, cached responses and throttled downloads. Only
+/// applied to the throwaway in-memory catalog `--demo` selects, which starts
+/// with the sample data, so the saved catalog and its settings are never
+/// touched.
+pub async fn apply_demo_preset(
+    db_service: &DatabaseService,
+    config: &mut Config,
+) -> Result<(), SettingsError> {
+    let settings = &db_service.settings;
+    settings.set::<EnableTransactions>(&false).await?;
+    settings.set::<EnableUploads>(&false).await?;
+    settings.set::<RequestRateLimit>(&DEMO_REQUEST_RATE_LIMIT).await?;
+    settings
+        .set::<ResponseCacheSeconds>(&DEMO_RESPONSE_CACHE_SECONDS)
+        .await?;
+    settings
+        .set::<AssetBandwidthLimit>(&DEMO_ASSET_BANDWIDTH_LIMIT)
+        .await?;
+    config.server.enable_transactions = false;
+    config.server.enable_uploads = false;
+    DEMO.store(true, Ordering::Relaxed);
+    Ok(())
+}
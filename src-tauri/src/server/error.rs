@@ -0,0 +1,141 @@
+use crate::server::middleware::add_cors_headers;
+use axum::{
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+/// Media type of error responses, from RFC 7807
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Error answer of the API, sent as an RFC 7807 problem document:
+///
+/// ```json
+/// {
+///   "type": "about:blank",
+///   "title": "Not Found",
+///   "status": 404,
+///   "detail": "Collection 'harbour' not found",
+///   "code": "NotFound",
+///   "description": "Collection 'harbour' not found"
+/// }
+/// ```
+///
+/// `code` and `description` repeat the OGC API exception fields so clients
+/// of the earlier error format keep working. Server errors carry a
+/// `trace_id` that is also logged, so a report can be matched to its cause.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    status: StatusCode,
+    code: Cow<'static, str>,
+    detail: String,
+    extensions: Map<String, Value>,
+    headers: HeaderMap,
+}
+
+impl ApiError {
+    pub fn new(
+        status: StatusCode,
+        code: impl Into<Cow<'static, str>>,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            status,
+            code: code.into(),
+            detail: detail.into(),
+            extensions: Map::new(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Error whose code follows from the status, e.g. `NotFound` for 404
+    pub fn from_status(status: StatusCode, detail: impl Into<String>) -> Self {
+        Self::new(status, default_code(status), detail)
+    }
+
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "BadRequest", detail)
+    }
+
+    pub fn invalid_parameter(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "InvalidParameterValue", detail)
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "NotFound", detail)
+    }
+
+    pub fn internal(detail: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "InternalServerError",
+            detail,
+        )
+    }
+
+    /// Adds an extension member to the problem document, e.g. the list of
+    /// violations of a rejected item
+    pub fn with_extension(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.extensions.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Adds a response header, e.g. `Retry-After`
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Adds all of `headers` to the response, e.g. a record's validators
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut body = Map::new();
+        body.insert("type".to_string(), Value::from("about:blank"));
+        body.insert(
+            "title".to_string(),
+            Value::from(self.status.canonical_reason().unwrap_or("Error")),
+        );
+        body.insert("status".to_string(), Value::from(self.status.as_u16()));
+        body.insert("detail".to_string(), Value::from(self.detail.clone()));
+        if self.status.is_server_error() {
+            let trace_id = uuid::Uuid::now_v7().to_string();
+            eprintln!(
+                "{} [{}] {}: {}",
+                self.status, trace_id, self.code, self.detail
+            );
+            body.insert("trace_id".to_string(), Value::from(trace_id));
+        }
+        body.insert("code".to_string(), Value::from(self.code.into_owned()));
+        body.insert("description".to_string(), Value::from(self.detail));
+        body.extend(self.extensions);
+
+        let mut headers = self.headers;
+        headers.insert("Content-Type", HeaderValue::from_static(PROBLEM_JSON));
+        headers = add_cors_headers(headers);
+        (self.status, headers, Value::Object(body).to_string()).into_response()
+    }
+}
+
+fn default_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "BadRequest",
+        StatusCode::UNAUTHORIZED => "Unauthorized",
+        StatusCode::FORBIDDEN => "Forbidden",
+        StatusCode::NOT_FOUND => "NotFound",
+        StatusCode::METHOD_NOT_ALLOWED => "MethodNotAllowed",
+        StatusCode::CONFLICT => "Conflict",
+        StatusCode::PRECONDITION_FAILED => "PreconditionFailed",
+        StatusCode::PAYLOAD_TOO_LARGE => "PayloadTooLarge",
+        StatusCode::PRECONDITION_REQUIRED => "PreconditionRequired",
+        StatusCode::TOO_MANY_REQUESTS => "TooManyRequests",
+        StatusCode::BAD_GATEWAY => "BadGateway",
+        _ => "InternalServerError",
+    }
+}
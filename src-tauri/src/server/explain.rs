@@ -0,0 +1,148 @@
+use crate::database::repository::SqlCondition;
+use crate::models::search::{SearchBody, SearchQuery};
+use crate::server::auth::{require_admin, InternalRequest};
+use crate::server::handlers::explain_items;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// A step of a search and the rows it produced
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanStage {
+    pub stage: &'static str,
+    pub rows: usize,
+    pub ms: f64,
+}
+
+/// How a search ran: where each filter was evaluated, the rows every stage
+/// produced and how long it took
+#[derive(Debug, Default, Serialize)]
+pub struct SearchPlan {
+    pub collections: Vec<String>,
+    /// Conditions evaluated by SQLite while scanning
+    pub sql_filters: Vec<SqlCondition>,
+    /// SQLite's query plan for the filtered scan
+    pub sqlite_plan: Vec<String>,
+    /// Filters applied to each scanned item after it is read
+    pub memory_filters: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sortby: Option<String>,
+    pub stages: Vec<PlanStage>,
+    pub returned: usize,
+    pub total_ms: f64,
+    pub suggestions: Vec<String>,
+}
+
+impl SearchPlan {
+    pub fn stage(&mut self, stage: &'static str, rows: usize, started: Instant) {
+        self.stages.push(PlanStage {
+            stage,
+            rows,
+            ms: elapsed_ms(started),
+        });
+    }
+}
+
+pub fn elapsed_ms(started: Instant) -> f64 {
+    duration_ms(started.elapsed())
+}
+
+/// Milliseconds, to the microsecond
+pub fn duration_ms(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+/// Handler running a search and reporting how it was executed instead of its
+/// results (GET /search/explain). Requires the admin key.
+pub async fn search_explain_get(
+    Query(query): Query<SearchQuery>,
+    State(state): State<AppState>,
+    internal: Option<Extension<InternalRequest>>,
+    headers: HeaderMap,
+) -> Response {
+    search_explain(query, state, internal, headers).await
+}
+
+/// POST form of [`search_explain_get`], taking a search body
+pub async fn search_explain_post(
+    State(state): State<AppState>,
+    internal: Option<Extension<InternalRequest>>,
+    headers: HeaderMap,
+    Json(body): Json<SearchBody>,
+) -> Response {
+    search_explain(SearchQuery::from(body), state, internal, headers).await
+}
+
+async fn search_explain(
+    query: SearchQuery,
+    state: AppState,
+    internal: Option<Extension<InternalRequest>>,
+    request_headers: HeaderMap,
+) -> Response {
+    if let Err(response) =
+        require_admin(&state, &request_headers, internal.map(|Extension(i)| i)).await
+    {
+        return response;
+    }
+
+    let started = Instant::now();
+    let mut plan = match explain_items(&query, &state).await {
+        Ok(plan) => plan,
+        Err(response) => return response,
+    };
+    plan.total_ms = elapsed_ms(started);
+    add_suggestions(&mut plan);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (headers, serde_json::to_string(&plan).unwrap()).into_response()
+}
+
+/// Hints for the slow parts of a plan
+fn add_suggestions(plan: &mut SearchPlan) {
+    for condition in plan.sql_filters.iter().filter(|c| !c.indexed) {
+        plan.suggestions.push(format!(
+            "{} is read with json_extract on every scanned item. Indexing the property \
+             on the searched collections (PUT /collections/{{collection_id}}/indexed-properties) \
+             lets SQLite use an index.",
+            condition.filter
+        ));
+    }
+
+    let scanned = plan
+        .stages
+        .iter()
+        .find(|s| s.stage == "sql_scan")
+        .map_or(0, |s| s.rows);
+    let kept = plan
+        .stages
+        .iter()
+        .find(|s| s.stage == "memory_filter")
+        .map_or(0, |s| s.rows);
+    if !plan.memory_filters.is_empty() && scanned > 0 && kept * 10 < scanned {
+        plan.suggestions.push(format!(
+            "{} of {} scanned items were discarded by {}, which run after items are read. \
+             Narrowing the search with collections or query reduces the scan.",
+            scanned - kept,
+            scanned,
+            plan.memory_filters.join(" and ")
+        ));
+    }
+    if plan.sortby.is_some() && scanned > plan.returned {
+        plan.suggestions.push(
+            "sortby reads every match before sorting; without it results stream in pages."
+                .to_string(),
+        );
+    }
+}
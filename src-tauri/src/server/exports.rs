@@ -0,0 +1,364 @@
+use crate::models::search::{SearchBody, SearchQuery};
+use crate::server::error::ApiError;
+use crate::server::handlers::find_all_items;
+use crate::server::jobs::{registry, JobLimits, JobOutput, JobStatus};
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::Write;
+use std::sync::Arc;
+
+/// File formats search results can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    GeoJson,
+    Csv,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "geojson" | "json" => Some(ExportFormat::GeoJson),
+            "csv" => Some(ExportFormat::Csv),
+            "parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::GeoJson => "geojson",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::GeoJson => "application/geo+json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    const ALL: [ExportFormat; 3] = [ExportFormat::GeoJson, ExportFormat::Csv, ExportFormat::Parquet];
+}
+
+/// Body of POST /search/export: a search body plus the output format
+#[derive(Debug, Deserialize)]
+pub struct ExportRequest {
+    #[serde(flatten)]
+    pub search: SearchBody,
+    /// "geojson" (default), "csv" or "parquet"
+    pub format: Option<String>,
+}
+
+fn json_response(status: StatusCode, headers: HeaderMap, body: Value) -> Response {
+    let mut headers = add_cors_headers(headers);
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Handler starting a search export job (POST /search/export)
+pub async fn search_export(State(state): State<AppState>, Json(request): Json<ExportRequest>) -> Response {
+    let format_name = request.format.unwrap_or_else(|| "geojson".to_string());
+    let Some(format) = ExportFormat::parse(&format_name) else {
+        return ApiError::invalid_parameter(format!(
+            "Unknown format '{}'. Use geojson, csv or parquet",
+            format_name
+        ))
+        .into_response();
+    };
+
+    // Exports of a single collection share that collection's slots
+    let collection = match request.search.collections.as_deref() {
+        Some([collection]) => Some(collection.clone()),
+        _ => None,
+    };
+    let job = registry().create("search_export", "export", collection.as_deref());
+    let job_id = job.id.clone();
+    let query = SearchQuery::from(request.search);
+    let task_state = state.clone();
+    let limits = JobLimits::load(&state.db_service.settings, &job.queue).await;
+    registry().spawn(&job, limits, async move {
+        run_export(&task_state, &job_id, query, format).await.map(Some)
+    });
+
+    let server_config = ServerConfig::from_config(&state.config);
+    let href = server_config.href(&format!("/exports/{}", job.id));
+    let mut headers = HeaderMap::new();
+    if let Ok(location) = HeaderValue::from_str(&href) {
+        headers.insert("Location", location);
+    }
+    let mut body = serde_json::to_value(&job).unwrap();
+    body["links"] = serde_json::json!([{ "href": href, "rel": "status", "type": "application/json" }]);
+    json_response(StatusCode::ACCEPTED, headers, body)
+}
+
+/// Handler for an export: the file once the job has completed, its status otherwise
+/// (GET /exports/:job_id)
+pub async fn export_file(Path(job_id): Path<String>, State(state): State<AppState>) -> Response {
+    let not_found = || ApiError::not_found(format!("Export '{}' not found", job_id)).into_response();
+
+    let output = match registry().get(&job_id) {
+        Some(job) if job.status == JobStatus::Completed => job.output,
+        Some(job) if job.status == JobStatus::Failed => {
+            return json_response(StatusCode::INTERNAL_SERVER_ERROR, HeaderMap::new(), serde_json::to_value(&job).unwrap())
+        }
+        Some(job) => {
+            return json_response(StatusCode::ACCEPTED, HeaderMap::new(), serde_json::to_value(&job).unwrap())
+        }
+        // Exports from before a restart are still on disk
+        None => find_export_on_disk(&state, &job_id),
+    };
+    let Some(output) = output else {
+        return not_found();
+    };
+
+    let data = match tokio::fs::read(&output.path).await {
+        Ok(data) => data,
+        Err(_) => return not_found(),
+    };
+    let mut headers = add_cors_headers(HeaderMap::new());
+    if let Ok(value) = HeaderValue::from_str(&output.content_type) {
+        headers.insert("Content-Type", value);
+    }
+    if let Ok(value) =
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", output.file_name))
+    {
+        headers.insert("Content-Disposition", value);
+    }
+    (headers, data).into_response()
+}
+
+fn find_export_on_disk(state: &AppState, job_id: &str) -> Option<JobOutput> {
+    // Job ids are UUIDs; anything else cannot name an export file
+    uuid::Uuid::parse_str(job_id).ok()?;
+    ExportFormat::ALL.iter().find_map(|format| {
+        let file_name = format!("search-{}.{}", job_id, format.extension());
+        let path = std::path::Path::new(&state.config.exports_dir()).join(&file_name);
+        let size = std::fs::metadata(&path).ok()?.len();
+        Some(JobOutput {
+            path: path.to_string_lossy().into_owned(),
+            file_name,
+            content_type: format.content_type().to_string(),
+            size,
+            count: 0,
+        })
+    })
+}
+
+async fn run_export(
+    state: &AppState,
+    job_id: &str,
+    query: SearchQuery,
+    format: ExportFormat,
+) -> Result<JobOutput, String> {
+    let items = find_all_items(&query, state)
+        .await
+        .map_err(|_| "Search failed".to_string())?
+        .items;
+    let items: Vec<Value> = items
+        .iter()
+        .map(|item| serde_json::to_value(item).unwrap_or(Value::Null))
+        .collect();
+
+    let exports_dir = state.config.exports_dir();
+    tokio::fs::create_dir_all(&exports_dir)
+        .await
+        .map_err(|e| e.to_string())?;
+    let file_name = format!("search-{}.{}", job_id, format.extension());
+    let path = std::path::Path::new(&exports_dir).join(&file_name);
+    let count = items.len();
+
+    let write_path = path.clone();
+    tokio::task::spawn_blocking(move || -> Result<(), String> {
+        let file = std::fs::File::create(&write_path).map_err(|e| e.to_string())?;
+        match format {
+            ExportFormat::GeoJson => write_geojson(file, &items),
+            ExportFormat::Csv => write_csv(file, &items),
+            ExportFormat::Parquet => write_parquet(file, &items),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let size = tokio::fs::metadata(&path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    Ok(JobOutput {
+        path: path.to_string_lossy().into_owned(),
+        file_name,
+        content_type: format.content_type().to_string(),
+        size,
+        count,
+    })
+}
+
+fn write_geojson(file: std::fs::File, items: &[Value]) -> Result<(), String> {
+    let mut writer = std::io::BufWriter::new(file);
+    let collection = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": items
+    });
+    serde_json::to_writer(&mut writer, &collection).map_err(|e| e.to_string())?;
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// One row per item: id, collection, datetime, bbox, GeoJSON geometry, then a
+/// column for every scalar property found in the results
+fn write_csv(file: std::fs::File, items: &[Value]) -> Result<(), String> {
+    let fixed = ["id", "collection", "datetime", "min_lon", "min_lat", "max_lon", "max_lat", "geometry"];
+    let mut property_keys: Vec<String> = Vec::new();
+    for item in items {
+        if let Some(properties) = item.get("properties").and_then(|p| p.as_object()) {
+            for (key, value) in properties {
+                if key != "datetime" && !value.is_object() && !value.is_array() && !property_keys.contains(key) {
+                    property_keys.push(key.clone());
+                }
+            }
+        }
+    }
+    property_keys.sort();
+
+    let mut writer = std::io::BufWriter::new(file);
+    let header: Vec<String> = fixed
+        .iter()
+        .map(|s| s.to_string())
+        .chain(property_keys.iter().cloned())
+        .collect();
+    write_csv_row(&mut writer, &header)?;
+
+    for item in items {
+        let bbox: Vec<String> = match item.get("bbox").and_then(|b| b.as_array()) {
+            Some(bbox) if bbox.len() >= 4 => {
+                // 3D bboxes are min_x, min_y, min_z, max_x, max_y, max_z
+                let (max_x, max_y) = if bbox.len() == 6 { (3, 4) } else { (2, 3) };
+                [0, 1, max_x, max_y].iter().map(|&i| bbox[i].to_string()).collect()
+            }
+            _ => vec![String::new(); 4],
+        };
+        let properties = item.get("properties");
+        let mut row = vec![
+            csv_value(item.get("id")),
+            csv_value(item.get("collection")),
+            csv_value(properties.and_then(|p| p.get("datetime"))),
+        ];
+        row.extend(bbox);
+        row.push(
+            item.get("geometry")
+                .filter(|g| !g.is_null())
+                .map(|g| g.to_string())
+                .unwrap_or_default(),
+        );
+        for key in &property_keys {
+            row.push(csv_value(properties.and_then(|p| p.get(key))));
+        }
+        write_csv_row(&mut writer, &row)?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+fn csv_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn write_csv_row(writer: &mut impl Write, fields: &[String]) -> Result<(), String> {
+    let line: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+    writeln!(writer, "{}", line.join(",")).map_err(|e| e.to_string())
+}
+
+/// Items as a Parquet table with the geometry, bbox, properties and assets
+/// stored as JSON text columns
+fn write_parquet(file: std::fs::File, items: &[Value]) -> Result<(), String> {
+    use parquet::data_type::{ByteArray, ByteArrayType};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    let schema = parse_message_type(
+        "message item {
+            REQUIRED BYTE_ARRAY id (UTF8);
+            OPTIONAL BYTE_ARRAY collection (UTF8);
+            OPTIONAL BYTE_ARRAY datetime (UTF8);
+            OPTIONAL BYTE_ARRAY geometry (JSON);
+            OPTIONAL BYTE_ARRAY bbox (JSON);
+            OPTIONAL BYTE_ARRAY properties (JSON);
+            OPTIONAL BYTE_ARRAY assets (JSON);
+        }",
+    )
+    .map_err(|e| e.to_string())?;
+
+    let text = |v: Option<&Value>| match v {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(other) => Some(other.to_string()),
+    };
+    let columns: Vec<Vec<Option<String>>> = vec![
+        items.iter().map(|i| text(i.get("id")).or(Some(String::new()))).collect(),
+        items.iter().map(|i| text(i.get("collection"))).collect(),
+        items
+            .iter()
+            .map(|i| text(i.get("properties").and_then(|p| p.get("datetime"))))
+            .collect(),
+        items.iter().map(|i| text(i.get("geometry"))).collect(),
+        items.iter().map(|i| text(i.get("bbox"))).collect(),
+        items.iter().map(|i| text(i.get("properties"))).collect(),
+        items.iter().map(|i| text(i.get("assets"))).collect(),
+    ];
+
+    let mut writer = SerializedFileWriter::new(
+        file,
+        Arc::new(schema),
+        Arc::new(WriterProperties::builder().build()),
+    )
+    .map_err(|e| e.to_string())?;
+    let mut row_group = writer.next_row_group().map_err(|e| e.to_string())?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column().map_err(|e| e.to_string())? {
+        let values = &columns[index];
+        let present: Vec<ByteArray> = values
+            .iter()
+            .flatten()
+            .map(|v| ByteArray::from(v.as_str()))
+            .collect();
+        // The id column is required and has no definition levels
+        let levels: Vec<i16> = values.iter().map(|v| i16::from(v.is_some())).collect();
+        let levels = if index == 0 { None } else { Some(levels.as_slice()) };
+        column
+            .typed::<ByteArrayType>()
+            .write_batch(&present, levels, None)
+            .map_err(|e| e.to_string())?;
+        column.close().map_err(|e| e.to_string())?;
+        index += 1;
+    }
+    row_group.close().map_err(|e| e.to_string())?;
+    writer.close().map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,461 @@
+use crate::database::settings::{FederatedCatalogs, FederationTimeoutSecs, Setting};
+use crate::models::search::SearchQuery;
+use crate::server::access_log::add_item_access_stats;
+use crate::server::compact::compact_feature_collection;
+use crate::server::error::ApiError;
+use crate::server::handlers::{add_item_counts, find_items, page_search_href};
+use crate::server::helpers::calculate_bbox_for_features;
+use crate::server::middleware::add_cors_headers;
+use crate::server::outbound::http_client;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    http::{HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Header marking searches sent by a federating server. Such searches are
+/// answered from the local catalog only, so two servers federating each
+/// other do not loop.
+const FEDERATED_HEADER: &str = "x-zenstac-federated";
+
+/// Item property naming the catalog a federated result came from: `local`
+/// or the remote's name in `federated_catalogs`
+pub const ORIGIN_FIELD: &str = "zenstac:origin";
+
+/// Name the local catalog's results are tagged with
+const LOCAL_ORIGIN: &str = "local";
+
+/// How long the state behind a federated page token is kept
+const CURSOR_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// What is left to fetch from a remote catalog
+#[derive(Debug, Clone)]
+enum RemoteStep {
+    /// The first page: a POST of the search to the catalog's `/search`
+    Search,
+    /// The page behind a `next` link of the previous one
+    Follow(Value),
+}
+
+/// Where each source of a federated search continues. Sources that are
+/// exhausted or failed are absent.
+#[derive(Debug, Clone)]
+struct FederatedCursor {
+    /// Local page token; `Some(None)` for the first page
+    local: Option<Option<String>>,
+    remotes: BTreeMap<String, RemoteStep>,
+}
+
+fn cursors() -> &'static Mutex<HashMap<String, (Instant, FederatedCursor)>> {
+    static CURSORS: OnceLock<Mutex<HashMap<String, (Instant, FederatedCursor)>>> = OnceLock::new();
+    CURSORS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Keeps a cursor and returns the page token for it. Tokens start with `f`,
+/// which local tokens never do.
+fn store_cursor(cursor: FederatedCursor) -> String {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let mut cursors = cursors().lock().unwrap();
+    cursors.retain(|_, (stored, _)| stored.elapsed() < CURSOR_TTL);
+    cursors.insert(id.clone(), (Instant::now(), cursor));
+    format!("f{}", id)
+}
+
+fn load_cursor(token: &str) -> Option<FederatedCursor> {
+    let id = token.strip_prefix('f')?;
+    let cursors = cursors().lock().unwrap();
+    cursors
+        .get(id)
+        .filter(|(stored, _)| stored.elapsed() < CURSOR_TTL)
+        .map(|(_, cursor)| cursor.clone())
+}
+
+/// The remote catalogs a search fans out to, with secrets resolved, or
+/// `None` when it is answered locally: no catalogs are configured, the
+/// request opted out with `federated=false`, or it came from a federating
+/// server
+pub async fn catalogs_for(
+    state: &AppState,
+    query: &SearchQuery,
+    request_headers: &HeaderMap,
+) -> Option<BTreeMap<String, String>> {
+    if query.federated == Some(false) || request_headers.contains_key(FEDERATED_HEADER) {
+        return None;
+    }
+    let configured = state
+        .db_service
+        .settings
+        .get::<FederatedCatalogs>()
+        .await
+        .unwrap_or_else(|_| FederatedCatalogs::default_value());
+    if configured.is_empty() {
+        return None;
+    }
+    let mut catalogs = BTreeMap::new();
+    for (name, url) in configured {
+        match state.db_service.secrets.expand(&url).await {
+            Ok(url) => {
+                catalogs.insert(name, url.trim_end_matches('/').to_string());
+            }
+            Err(e) => eprintln!("Federation: catalog {} skipped: {}", name, e),
+        }
+    }
+    Some(catalogs)
+}
+
+/// The search as a STAC POST body for remote catalogs. Paging and output
+/// options stay local.
+fn remote_body(query: &SearchQuery) -> Value {
+    let list = |value: &Option<String>| {
+        value.as_ref().map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|part| !part.is_empty())
+                .map(Value::from)
+                .collect::<Vec<_>>()
+        })
+    };
+    let mut body = serde_json::Map::new();
+    if let Some(limit) = query.limit {
+        body.insert("limit".to_string(), json!(limit));
+    }
+    if let Some(bbox) = &query.bbox {
+        let numbers: Vec<f64> = bbox
+            .split(',')
+            .filter_map(|n| n.trim().parse().ok())
+            .collect();
+        body.insert("bbox".to_string(), json!(numbers));
+    }
+    if let Some(datetime) = &query.datetime {
+        body.insert("datetime".to_string(), json!(datetime));
+    }
+    if let Some(intersects) = &query.intersects {
+        if let Ok(geometry) = serde_json::from_str::<Value>(intersects) {
+            body.insert("intersects".to_string(), geometry);
+        }
+    }
+    if let Some(ids) = list(&query.ids) {
+        body.insert("ids".to_string(), json!(ids));
+    }
+    if let Some(collections) = list(&query.collections) {
+        body.insert("collections".to_string(), json!(collections));
+    }
+    if let Some(sortby) = &query.sortby {
+        let fields: Vec<Value> = sortby
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(|field| {
+                let (field, direction) = match field.split_once(':') {
+                    Some((field, direction)) => (field, direction),
+                    None => match field.strip_prefix('-') {
+                        Some(field) => (field, "desc"),
+                        None => (field.trim_start_matches('+'), "asc"),
+                    },
+                };
+                json!({ "field": field, "direction": direction })
+            })
+            .collect();
+        body.insert("sortby".to_string(), json!(fields));
+    }
+    if let Some(Ok(predicates)) = query.query.as_deref().map(serde_json::from_str::<Value>) {
+        body.insert("query".to_string(), predicates);
+    }
+    if let Some(q) = &query.q {
+        body.insert("q".to_string(), json!(q));
+    }
+    if let Some(filter) = &query.filter {
+        let json_filter = query.filter_lang.as_deref() == Some("cql2-json");
+        match serde_json::from_str::<Value>(filter) {
+            Ok(expr) if json_filter => {
+                body.insert("filter".to_string(), expr);
+            }
+            _ => {
+                body.insert("filter".to_string(), json!(filter));
+            }
+        }
+        body.insert(
+            "filter-lang".to_string(),
+            json!(if json_filter {
+                "cql2-json"
+            } else {
+                "cql2-text"
+            }),
+        );
+    }
+    Value::Object(body)
+}
+
+/// One page from a remote catalog
+struct RemotePage {
+    features: Vec<Value>,
+    next: Option<Value>,
+    matched: Option<usize>,
+}
+
+async fn fetch_remote(
+    client: &reqwest::Client,
+    root: &str,
+    step: &RemoteStep,
+    body: &Value,
+    timeout: Duration,
+) -> Result<RemotePage, String> {
+    let request = match step {
+        RemoteStep::Search => client
+            .post(format!("{}/search", root))
+            .header("Content-Type", "application/json")
+            .body(body.to_string()),
+        RemoteStep::Follow(link) => {
+            let href = link.get("href").and_then(Value::as_str).unwrap_or_default();
+            let method = link.get("method").and_then(Value::as_str).unwrap_or("GET");
+            if method.eq_ignore_ascii_case("POST") {
+                let mut next_body = link.get("body").cloned().unwrap_or(json!({}));
+                if link.get("merge").and_then(Value::as_bool) == Some(true) {
+                    if let (Some(merged), Some(original)) =
+                        (next_body.as_object(), body.as_object())
+                    {
+                        let mut full = original.clone();
+                        full.extend(merged.clone());
+                        next_body = Value::Object(full);
+                    }
+                }
+                client
+                    .post(href)
+                    .header("Content-Type", "application/json")
+                    .body(next_body.to_string())
+            } else {
+                client.get(href)
+            }
+        }
+    };
+    let response = request
+        .header(FEDERATED_HEADER, "1")
+        .header("Accept", "application/geo+json, application/json")
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_timeout() {
+                format!("no answer within {} seconds", timeout.as_secs())
+            } else {
+                format!("not reachable: {}", e)
+            }
+        })?;
+    let status = response.status();
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("answered {}", status));
+    }
+    let page: Value = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("returned an invalid response: {}", e))?;
+    let features = match page.get("features") {
+        Some(Value::Array(features)) => features.clone(),
+        _ => return Err("returned no features".to_string()),
+    };
+    let next = page
+        .get("links")
+        .and_then(Value::as_array)
+        .and_then(|links| {
+            links
+                .iter()
+                .find(|link| link.get("rel").and_then(Value::as_str) == Some("next"))
+        })
+        .filter(|link| link.get("href").and_then(Value::as_str).is_some())
+        .cloned();
+    let matched = page
+        .get("numberMatched")
+        .or_else(|| page.get("context").and_then(|c| c.get("matched")))
+        .and_then(Value::as_u64)
+        .map(|n| n as usize);
+    Ok(RemotePage {
+        features,
+        next,
+        matched,
+    })
+}
+
+fn tag_origin(feature: &mut Value, origin: &str) {
+    if !feature.get("properties").is_some_and(Value::is_object) {
+        feature["properties"] = json!({});
+    }
+    feature["properties"][ORIGIN_FIELD] = json!(origin);
+}
+
+/// Answers a search from the local catalog and the remote ones at once.
+///
+/// Every source is asked for one page of `limit` results in parallel; the
+/// merged page holds the local results followed by each catalog's in name
+/// order, each tagged with its origin. Catalogs that fail or time out are
+/// left out and reported in the `federation` member. The `next` link
+/// continues every source that has more; its token is kept in memory for
+/// half an hour.
+pub async fn federated_search(
+    query: SearchQuery,
+    state: &AppState,
+    catalogs: BTreeMap<String, String>,
+) -> Response {
+    let server_config = ServerConfig::from_config(&state.config);
+    let cursor = match query.token.as_deref() {
+        None => FederatedCursor {
+            local: Some(None),
+            remotes: catalogs
+                .keys()
+                .map(|name| (name.clone(), RemoteStep::Search))
+                .collect(),
+        },
+        Some(token) if token.starts_with('f') => match load_cursor(token) {
+            Some(cursor) => cursor,
+            None => {
+                return ApiError::bad_request(
+                    "This federated pagination token has expired; repeat the search",
+                )
+                .into_response()
+            }
+        },
+        // A local token pages the local catalog alone
+        Some(token) => FederatedCursor {
+            local: Some(Some(token.to_string())),
+            remotes: BTreeMap::new(),
+        },
+    };
+    let body = remote_body(&query);
+
+    let local = async {
+        match &cursor.local {
+            Some(token) => {
+                let mut local_query = query.clone();
+                local_query.token = token.clone();
+                Some(find_items(&local_query, state).await)
+            }
+            None => None,
+        }
+    };
+    let client = http_client(&state.db_service).await;
+    let timeout = Duration::from_secs(
+        state
+            .db_service
+            .settings
+            .get::<FederationTimeoutSecs>()
+            .await
+            .unwrap_or_else(|_| FederationTimeoutSecs::default_value()),
+    );
+    let remotes = futures_util::future::join_all(cursor.remotes.iter().map(|(name, step)| {
+        let client = client.clone();
+        let root = catalogs.get(name).cloned();
+        let body = &body;
+        async move {
+            let result = match (client, root) {
+                (Ok(client), Some(root)) => fetch_remote(&client, &root, step, body, timeout).await,
+                (Err(e), _) => Err(e),
+                (_, None) => Err("no longer configured".to_string()),
+            };
+            (name.clone(), result)
+        }
+    }));
+    let (local, remotes) = futures_util::future::join(local, remotes).await;
+
+    let mut features = Vec::new();
+    let mut sources = Vec::new();
+    let mut next = FederatedCursor {
+        local: None,
+        remotes: BTreeMap::new(),
+    };
+    let mut matched = Some(0usize);
+    let mut limit = None;
+    if let Some(local) = local {
+        let results = match local {
+            Ok(results) => results,
+            Err(response) => return response,
+        };
+        let mut items = results.items;
+        add_item_access_stats(&state.db_service, &server_config, &mut items).await;
+        sources.push(json!({
+            "name": LOCAL_ORIGIN,
+            "status": "ok",
+            "returned": items.len()
+        }));
+        features.extend(items.into_iter().map(|item| {
+            let mut feature = json!(item);
+            tag_origin(&mut feature, LOCAL_ORIGIN);
+            feature
+        }));
+        matched = matched.zip(results.number_matched).map(|(a, b)| a + b);
+        limit = results.limit;
+        if let Some(token) = results.next_token {
+            next.local = Some(Some(token));
+        }
+    }
+    for (name, result) in remotes {
+        match result {
+            Ok(page) => {
+                sources.push(json!({
+                    "name": name,
+                    "status": "ok",
+                    "returned": page.features.len()
+                }));
+                features.extend(page.features.into_iter().map(|mut feature| {
+                    tag_origin(&mut feature, &name);
+                    feature
+                }));
+                matched = matched.zip(page.matched).map(|(a, b)| a + b);
+                if let Some(link) = page.next {
+                    next.remotes.insert(name, RemoteStep::Follow(link));
+                }
+            }
+            Err(e) => {
+                eprintln!("Federation: catalog {}: {}", name, e);
+                matched = None;
+                sources.push(json!({ "name": name, "status": "error", "error": e }));
+            }
+        }
+    }
+
+    let mut links = vec![json!({
+        "href": server_config.search_href(),
+        "rel": "self",
+        "type": "application/geo+json"
+    })];
+    if next.local.is_some() || !next.remotes.is_empty() {
+        links.push(json!({
+            "href": page_search_href(&server_config, &query, &store_cursor(next)),
+            "rel": "next",
+            "type": "application/geo+json",
+            "method": "GET"
+        }));
+    }
+
+    let number_returned = features.len();
+    let bbox = calculate_bbox_for_features(&features);
+    let mut response = json!({
+        "type": "FeatureCollection",
+        "features": features,
+        "links": links,
+        "federation": sources
+    });
+    add_item_counts(
+        &mut response,
+        number_returned,
+        limit.or(query.limit.map(|l| l.max(0) as usize)),
+        matched,
+    );
+    if let Some(bbox) = bbox {
+        response["bbox"] = json!(bbox);
+    }
+    if query.compact == Some(true) {
+        compact_feature_collection(&mut response);
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/geo+json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (headers, serde_json::to_string(&response).unwrap()).into_response()
+}
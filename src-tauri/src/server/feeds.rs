@@ -0,0 +1,237 @@
+use crate::database::DbItem;
+use crate::server::conformance::{ConformanceRegistry, FeatureGroup, LandingLink};
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::thumbnails::thumbnail_href;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+const DEFAULT_FEED_ENTRIES: i64 = 50;
+const MAX_FEED_ENTRIES: i64 = 500;
+
+/// Links the catalog feed from the landing page
+pub fn register_conformance(registry: &mut ConformanceRegistry) {
+    registry.register_link(
+        FeatureGroup::Core,
+        LandingLink {
+            rel: "alternate",
+            path: "/feed.xml",
+            media_type: "application/atom+xml",
+            title: "Recently added items",
+        },
+    );
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeedQuery {
+    /// Number of entries (default 50, at most 500)
+    pub limit: Option<i64>,
+}
+
+/// Handler for the Atom feed of recently created or updated items across the
+/// catalog (GET /feed.xml)
+pub async fn catalog_feed(
+    Query(query): Query<FeedQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let server_config = ServerConfig::from_config(&state.config);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_FEED_ENTRIES)
+        .clamp(1, MAX_FEED_ENTRIES);
+
+    let items = state
+        .db_service
+        .items
+        .recent(None, limit)
+        .await
+        .map_err(recent_items_error)?;
+    Ok(feed_response(
+        &server_config,
+        &state.config.catalog.title,
+        &server_config.feed_href(None),
+        &items,
+    ))
+}
+
+/// Handler for the Atom feed of recently created or updated items in one
+/// collection (GET /collections/:collection_id/feed.xml)
+pub async fn collection_feed(
+    Path(collection_id): Path<String>,
+    Query(query): Query<FeedQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let server_config = ServerConfig::from_config(&state.config);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_FEED_ENTRIES)
+        .clamp(1, MAX_FEED_ENTRIES);
+
+    let collection = state
+        .db_service
+        .collections
+        .get_by_id(&collection_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to get collection: {}", e)))?
+        .ok_or_else(|| ApiError::not_found(format!("Collection '{}' not found", collection_id)))?;
+
+    let items = state
+        .db_service
+        .items
+        .recent(Some(&collection_id), limit)
+        .await
+        .map_err(recent_items_error)?;
+    Ok(feed_response(
+        &server_config,
+        collection.title.as_deref().unwrap_or(&collection.id),
+        &server_config.feed_href(Some(&collection_id)),
+        &items,
+    ))
+}
+
+fn recent_items_error(e: rusqlite::Error) -> ApiError {
+    ApiError::internal(format!("Failed to read recent items: {}", e))
+}
+
+fn feed_response(
+    server_config: &ServerConfig,
+    title: &str,
+    self_href: &str,
+    items: &[DbItem],
+) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/atom+xml; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (headers, atom_feed(server_config, title, self_href, items)).into_response()
+}
+
+/// Atom document with one entry per item, newest first. Footprints use the
+/// GeoRSS Simple encoding so feed readers and GIS tools can map entries.
+fn atom_feed(
+    server_config: &ServerConfig,
+    title: &str,
+    self_href: &str,
+    items: &[DbItem],
+) -> String {
+    let updated = items
+        .first()
+        .map(|item| item.updated_at.clone())
+        .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str(
+        "<feed xmlns=\"http://www.w3.org/2005/Atom\" xmlns:georss=\"http://www.georss.org/georss\">\n",
+    );
+    xml.push_str(&format!("  <id>{}</id>\n", escape(self_href)));
+    xml.push_str(&format!("  <title>{}</title>\n", escape(title)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", escape(&updated)));
+    xml.push_str(&format!(
+        "  <link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n",
+        escape(self_href)
+    ));
+    xml.push_str(&format!(
+        "  <link rel=\"alternate\" type=\"application/json\" href=\"{}\"/>\n",
+        escape(&server_config.root_href())
+    ));
+    xml.push_str("  <generator>ZenSTAC</generator>\n");
+
+    for item in items {
+        xml.push_str(&atom_entry(server_config, item));
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn atom_entry(server_config: &ServerConfig, item: &DbItem) -> String {
+    let href = server_config.item_href(&item.collection_id, &item.id);
+    let property = |name: &str| item.properties.get(name).and_then(|v| v.as_str());
+    let title = property("title").unwrap_or(&item.id);
+
+    let mut entry = String::from("  <entry>\n");
+    entry.push_str(&format!("    <id>{}</id>\n", escape(&href)));
+    entry.push_str(&format!("    <title>{}</title>\n", escape(title)));
+    entry.push_str(&format!(
+        "    <published>{}</published>\n",
+        escape(&item.created_at)
+    ));
+    entry.push_str(&format!(
+        "    <updated>{}</updated>\n",
+        escape(&item.updated_at)
+    ));
+    entry.push_str(&format!(
+        "    <link rel=\"alternate\" type=\"application/geo+json\" href=\"{}\"/>\n",
+        escape(&href)
+    ));
+    entry.push_str(&format!(
+        "    <category term=\"{}\"/>\n",
+        escape(&item.collection_id)
+    ));
+
+    let mut summary = Vec::new();
+    if let Some(description) = property("description") {
+        summary.push(description.to_string());
+    }
+    if let Some(datetime) = property("datetime") {
+        summary.push(format!("Acquired {}", datetime));
+    } else if let (Some(start), Some(end)) = (property("start_datetime"), property("end_datetime"))
+    {
+        summary.push(format!("Acquired {} to {}", start, end));
+    }
+    if !summary.is_empty() {
+        entry.push_str(&format!(
+            "    <summary>{}</summary>\n",
+            escape(&summary.join(". "))
+        ));
+    }
+
+    if let Some(thumbnail) = thumbnail_href(server_config, item) {
+        entry.push_str(&format!(
+            "    <link rel=\"enclosure\" href=\"{}\"/>\n",
+            escape(&thumbnail)
+        ));
+    }
+
+    // GeoRSS boxes are "south west north east"
+    let bbox: Option<Vec<f64>> = item
+        .bbox
+        .as_ref()
+        .and_then(|b| serde_json::from_value(b.clone()).ok());
+    match bbox.as_deref() {
+        Some([west, south, east, north]) | Some([west, south, _, east, north, _]) => {
+            if west == east && south == north {
+                entry.push_str(&format!(
+                    "    <georss:point>{} {}</georss:point>\n",
+                    south, west
+                ));
+            } else {
+                entry.push_str(&format!(
+                    "    <georss:box>{} {} {} {}</georss:box>\n",
+                    south, west, north, east
+                ));
+            }
+        }
+        _ => {}
+    }
+
+    entry.push_str("  </entry>\n");
+    entry
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,83 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Asset and file operations for async contexts. Single-file operations use
+// tokio::fs directly; directory walks touch many files, so they run on the
+// blocking pool instead of stalling the runtime.
+
+/// A file found by a directory walk
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+/// Whether a file or directory exists
+pub async fn exists(path: impl AsRef<Path>) -> bool {
+    tokio::fs::try_exists(path).await.unwrap_or(false)
+}
+
+/// Every file under a directory, recursively; empty when it does not exist
+pub async fn list_files(dir: impl Into<PathBuf>) -> io::Result<Vec<FileEntry>> {
+    let dir = dir.into();
+    tokio::task::spawn_blocking(move || {
+        let mut files = Vec::new();
+        walk(&dir, &mut files);
+        files
+    })
+    .await
+    .map_err(io::Error::other)
+}
+
+/// Total size of the files under a directory; 0 when it does not exist
+pub async fn dir_size(dir: impl Into<PathBuf>) -> io::Result<u64> {
+    Ok(list_files(dir).await?.iter().map(|f| f.bytes).sum())
+}
+
+/// Removes the empty directories directly inside `dir`, returning how many
+/// were removed
+pub async fn remove_empty_subdirs(dir: impl AsRef<Path>) -> io::Result<usize> {
+    let mut removed = 0;
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        if tokio::fs::read_dir(&path)
+            .await?
+            .next_entry()
+            .await?
+            .is_none()
+        {
+            match tokio::fs::remove_dir(&path).await {
+                Ok(()) => removed += 1,
+                Err(e) => eprintln!("Failed to remove empty directory {:?}: {}", path, e),
+            }
+        }
+    }
+    Ok(removed)
+}
+
+fn walk(dir: &Path, files: &mut Vec<FileEntry>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            walk(&entry.path(), files);
+        } else {
+            files.push(FileEntry {
+                path: entry.path(),
+                bytes: metadata.len(),
+            });
+        }
+    }
+}
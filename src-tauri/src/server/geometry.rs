@@ -0,0 +1,422 @@
+use crate::models::item::Geometry;
+use crate::server::error::ApiError;
+use crate::server::helpers::calculate_bbox_for_geometry;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use wkt::TryFromWkt;
+
+/// Mean Earth radius in meters, used for buffering on the sphere
+const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// Request body for the geometry tools (POST /geometry/tools)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum GeometryToolRequest {
+    /// Convert a WKT string to a GeoJSON geometry
+    WktToGeojson { wkt: String },
+    /// Buffer a point by a distance in meters into a polygon
+    Buffer {
+        geometry: Geometry,
+        distance: f64,
+        /// Number of vertices on the circle (defaults to 32)
+        segments: Option<usize>,
+    },
+    /// Compute the bounding box of a geometry
+    Bbox { geometry: Geometry },
+}
+
+/// Result of a geometry tool: the resulting geometry and its bounding box
+#[derive(Debug, Clone, Serialize)]
+pub struct GeometryToolResponse {
+    pub geometry: Geometry,
+    pub bbox: Vec<f64>,
+}
+
+/// Runs a geometry tool. Shared by the HTTP endpoint and the Tauri command.
+pub fn run_geometry_tool(request: GeometryToolRequest) -> Result<GeometryToolResponse, String> {
+    let geometry = match request {
+        GeometryToolRequest::WktToGeojson { wkt } => wkt_to_geometry(&wkt)?,
+        GeometryToolRequest::Buffer {
+            geometry,
+            distance,
+            segments,
+        } => buffer_point(&geometry, distance, segments.unwrap_or(32))?,
+        GeometryToolRequest::Bbox { geometry } => geometry,
+    };
+
+    let bbox = calculate_bbox_for_geometry(&geometry);
+    Ok(GeometryToolResponse { geometry, bbox })
+}
+
+/// Parses WKT into a GeoJSON geometry
+pub fn wkt_to_geometry(wkt: &str) -> Result<Geometry, String> {
+    let parsed = geo_types::Geometry::<f64>::try_from_wkt_str(wkt.trim())
+        .map_err(|e| format!("Invalid WKT: {}", e))?;
+    let value = geojson::Value::from(&parsed);
+    let json = serde_json::to_value(geojson::Geometry::new(value)).map_err(|e| e.to_string())?;
+    serde_json::from_value(json).map_err(|e| format!("Unsupported geometry: {}", e))
+}
+
+/// Buffers a point into a circular polygon of `distance` meters radius
+pub fn buffer_point(geometry: &Geometry, distance: f64, segments: usize) -> Result<Geometry, String> {
+    let (lon, lat) = match geometry {
+        Geometry::Point { coordinates } if coordinates.len() >= 2 => (coordinates[0], coordinates[1]),
+        _ => return Err("Only Point geometries can be buffered".to_string()),
+    };
+    if distance.is_nan() || distance <= 0.0 {
+        return Err("Buffer distance must be a positive number of meters".to_string());
+    }
+    if !(3..=360).contains(&segments) {
+        return Err("Segments must be between 3 and 360".to_string());
+    }
+
+    let angular = distance / EARTH_RADIUS_METERS;
+    let lat1 = lat.to_radians();
+    let lon1 = lon.to_radians();
+
+    let mut ring = Vec::with_capacity(segments + 1);
+    for i in 0..segments {
+        // Counter-clockwise, as required by RFC 7946 for exterior rings
+        let bearing = -2.0 * std::f64::consts::PI * i as f64 / segments as f64;
+        let lat2 = (lat1.sin() * angular.cos() + lat1.cos() * angular.sin() * bearing.cos()).asin();
+        let lon2 = lon1
+            + (bearing.sin() * angular.sin() * lat1.cos())
+                .atan2(angular.cos() - lat1.sin() * lat2.sin());
+        ring.push(vec![lon2.to_degrees(), lat2.to_degrees()]);
+    }
+    ring.push(ring[0].clone());
+
+    Ok(Geometry::Polygon {
+        coordinates: vec![ring],
+    })
+}
+
+/// Request body for reprojection (POST /geometry/reproject)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReprojectRequest {
+    /// GeoJSON geometry in the source CRS
+    pub geometry: Geometry,
+    /// EPSG code of the source CRS (e.g. 32633 for UTM zone 33N)
+    pub source_epsg: u16,
+}
+
+/// Reprojects a geometry from the given EPSG code to WGS84 longitude/latitude
+pub fn reproject_to_wgs84(geometry: &Geometry, source_epsg: u16) -> Result<Geometry, String> {
+    let src = proj4rs::Proj::from_epsg_code(source_epsg)
+        .map_err(|e| format!("Unsupported source CRS EPSG:{}: {}", source_epsg, e))?;
+    let dst = proj4rs::Proj::from_epsg_code(4326).map_err(|e| e.to_string())?;
+
+    map_positions(geometry, &mut |position| {
+        if position.len() < 2 {
+            return Err("Positions must have at least two coordinates".to_string());
+        }
+        // proj4rs works in radians for geographic coordinates
+        let mut point = if src.is_latlong() {
+            (position[0].to_radians(), position[1].to_radians(), 0.0)
+        } else {
+            (position[0], position[1], 0.0)
+        };
+        proj4rs::transform::transform(&src, &dst, &mut point)
+            .map_err(|e| format!("Reprojection failed: {}", e))?;
+
+        let mut result = vec![point.0.to_degrees(), point.1.to_degrees()];
+        // Heights are passed through unchanged
+        result.extend_from_slice(&position[2..]);
+        Ok(result)
+    })
+}
+
+/// Radius of the spherical Mercator projection (EPSG:3857)
+const WEB_MERCATOR_RADIUS: f64 = 6_378_137.0;
+/// Latitude limit of EPSG:3857, where the map becomes square
+const WEB_MERCATOR_MAX_LAT: f64 = 85.051_128_779_806_59;
+
+/// Projects a WGS84 geometry to web mercator (EPSG:3857). Latitudes beyond
+/// the projection's ±85.05° are clamped.
+pub fn to_web_mercator(geometry: &Geometry) -> Result<Geometry, String> {
+    map_positions(geometry, &mut |position| {
+        if position.len() < 2 {
+            return Err("Positions must have at least two coordinates".to_string());
+        }
+        let lat = position[1].clamp(-WEB_MERCATOR_MAX_LAT, WEB_MERCATOR_MAX_LAT);
+        let x = WEB_MERCATOR_RADIUS * position[0].to_radians();
+        let y = WEB_MERCATOR_RADIUS
+            * (std::f64::consts::FRAC_PI_4 + lat.to_radians() / 2.0).tan().ln();
+        let mut result = vec![x, y];
+        result.extend_from_slice(&position[2..]);
+        Ok(result)
+    })
+}
+
+/// Simplifies every line and ring of a geometry with Douglas-Peucker, dropping
+/// vertices closer than `tolerance` (in coordinate units) to the simplified
+/// outline. Lines keep their two ends and rings at least four positions, so
+/// the result stays valid GeoJSON; points are unchanged.
+pub fn simplify_geometry(geometry: &Geometry, tolerance: f64) -> Geometry {
+    let line = |positions: &[Vec<f64>]| simplify_line(positions, tolerance, 2);
+    let rings = |rings: &[Vec<Vec<f64>>]| -> Vec<Vec<Vec<f64>>> {
+        rings
+            .iter()
+            .map(|ring| simplify_line(ring, tolerance, 4))
+            .collect()
+    };
+    match geometry {
+        Geometry::Point { .. } | Geometry::MultiPoint { .. } => geometry.clone(),
+        Geometry::LineString { coordinates } => Geometry::LineString {
+            coordinates: line(coordinates),
+        },
+        Geometry::Polygon { coordinates } => Geometry::Polygon {
+            coordinates: rings(coordinates),
+        },
+        Geometry::MultiLineString { coordinates } => Geometry::MultiLineString {
+            coordinates: coordinates.iter().map(|l| line(l)).collect(),
+        },
+        Geometry::MultiPolygon { coordinates } => Geometry::MultiPolygon {
+            coordinates: coordinates.iter().map(|polygon| rings(polygon)).collect(),
+        },
+        Geometry::GeometryCollection { geometries } => Geometry::GeometryCollection {
+            geometries: geometries
+                .iter()
+                .map(|g| simplify_geometry(g, tolerance))
+                .collect(),
+        },
+    }
+}
+
+/// Douglas-Peucker over one line; lines that would drop below `min_len`
+/// positions are returned unchanged
+fn simplify_line(positions: &[Vec<f64>], tolerance: f64, min_len: usize) -> Vec<Vec<f64>> {
+    if positions.len() <= min_len || tolerance <= 0.0 {
+        return positions.to_vec();
+    }
+    let mut keep = vec![false; positions.len()];
+    keep[0] = true;
+    keep[positions.len() - 1] = true;
+    let mut stack = vec![(0, positions.len() - 1)];
+    while let Some((start, end)) = stack.pop() {
+        let farthest = (start + 1..end)
+            .map(|i| (i, segment_distance(&positions[i], &positions[start], &positions[end])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                keep[i] = true;
+                stack.push((start, i));
+                stack.push((i, end));
+            }
+        }
+    }
+    let simplified: Vec<Vec<f64>> = positions
+        .iter()
+        .zip(&keep)
+        .filter(|(_, keep)| **keep)
+        .map(|(p, _)| p.clone())
+        .collect();
+    if simplified.len() < min_len {
+        positions.to_vec()
+    } else {
+        simplified
+    }
+}
+
+/// Planar distance from `p` to the segment `a`-`b`
+fn segment_distance(p: &[f64], a: &[f64], b: &[f64]) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    let (x, y) = (a[0] + t * dx, a[1] + t * dy);
+    ((p[0] - x).powi(2) + (p[1] - y).powi(2)).sqrt()
+}
+
+/// Applies `f` to every position of a geometry
+fn map_positions<F>(geometry: &Geometry, f: &mut F) -> Result<Geometry, String>
+where
+    F: FnMut(&[f64]) -> Result<Vec<f64>, String>,
+{
+    fn line<F>(positions: &[Vec<f64>], f: &mut F) -> Result<Vec<Vec<f64>>, String>
+    where
+        F: FnMut(&[f64]) -> Result<Vec<f64>, String>,
+    {
+        positions.iter().map(|p| f(p)).collect()
+    }
+
+    fn rings<F>(lines: &[Vec<Vec<f64>>], f: &mut F) -> Result<Vec<Vec<Vec<f64>>>, String>
+    where
+        F: FnMut(&[f64]) -> Result<Vec<f64>, String>,
+    {
+        lines.iter().map(|l| line(l, f)).collect()
+    }
+
+    Ok(match geometry {
+        Geometry::Point { coordinates } => Geometry::Point {
+            coordinates: f(coordinates)?,
+        },
+        Geometry::LineString { coordinates } => Geometry::LineString {
+            coordinates: line(coordinates, f)?,
+        },
+        Geometry::Polygon { coordinates } => Geometry::Polygon {
+            coordinates: rings(coordinates, f)?,
+        },
+        Geometry::MultiPoint { coordinates } => Geometry::MultiPoint {
+            coordinates: line(coordinates, f)?,
+        },
+        Geometry::MultiLineString { coordinates } => Geometry::MultiLineString {
+            coordinates: rings(coordinates, f)?,
+        },
+        Geometry::MultiPolygon { coordinates } => Geometry::MultiPolygon {
+            coordinates: coordinates
+                .iter()
+                .map(|polygon| rings(polygon, f))
+                .collect::<Result<_, _>>()?,
+        },
+        Geometry::GeometryCollection { geometries } => Geometry::GeometryCollection {
+            geometries: geometries
+                .iter()
+                .map(|g| map_positions(g, f))
+                .collect::<Result<_, _>>()?,
+        },
+    })
+}
+
+/// Handler for geometry utilities (POST /geometry/tools)
+pub async fn geometry_tools(
+    Json(request): Json<GeometryToolRequest>,
+) -> Result<Response, ApiError> {
+    let result = run_geometry_tool(request).map_err(ApiError::bad_request)?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+
+    Ok((headers, serde_json::to_string(&result).unwrap()).into_response())
+}
+
+/// Handler for reprojecting client geometries to WGS84 (POST /geometry/reproject)
+pub async fn geometry_reproject(
+    Json(request): Json<ReprojectRequest>,
+) -> Result<Response, ApiError> {
+    let geometry = reproject_to_wgs84(&request.geometry, request.source_epsg)
+        .map_err(ApiError::bad_request)?;
+    let result = GeometryToolResponse {
+        bbox: calculate_bbox_for_geometry(&geometry),
+        geometry,
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+
+    Ok((headers, serde_json::to_string(&result).unwrap()).into_response())
+}
+
+/// Query of an item footprint (GET /collections/:collection_id/items/:item_id/footprint)
+#[derive(Debug, Clone, Deserialize)]
+pub struct FootprintQuery {
+    /// Simplification tolerance in degrees; 0 keeps every vertex. Defaults to
+    /// a thousandth of the larger side of the geometry's bbox.
+    pub tolerance: Option<f64>,
+    /// Output CRS, EPSG:4326 (default) or EPSG:3857, as `EPSG:<code>`, a bare
+    /// code or an OGC CRS URI
+    pub crs: Option<String>,
+}
+
+/// OGC URIs of the footprint output CRSs
+const CRS84_URI: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
+const WEB_MERCATOR_URI: &str = "http://www.opengis.net/def/crs/EPSG/0/3857";
+
+/// Whether a `crs` parameter asks for web mercator; `Err` for unsupported CRSs
+fn wants_web_mercator(crs: &str) -> Result<bool, String> {
+    let code = crs
+        .trim()
+        .trim_start_matches("http://www.opengis.net/def/crs/EPSG/0/")
+        .trim_start_matches("EPSG:")
+        .trim_start_matches("epsg:");
+    match code {
+        "3857" | "900913" => Ok(true),
+        "4326" | CRS84_URI | "CRS84" => Ok(false),
+        _ => Err(format!("Unsupported crs '{}'; use EPSG:4326 or EPSG:3857", crs)),
+    }
+}
+
+/// Handler for an item's outline only, simplified and optionally in web
+/// mercator, for map overlays (GET /collections/:collection_id/items/:item_id/footprint)
+pub async fn item_footprint(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    Query(query): Query<FootprintQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let mercator = query
+        .crs
+        .as_deref()
+        .map(wants_web_mercator)
+        .transpose()
+        .map_err(ApiError::bad_request)?
+        .unwrap_or(false);
+    if query.tolerance.is_some_and(|t| !t.is_finite() || t < 0.0) {
+        return Err(ApiError::bad_request(
+            "tolerance must be a non-negative number of degrees",
+        ));
+    }
+
+    let db_item = state
+        .db_service
+        .items
+        .get_by_id(&collection_id, &item_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to retrieve item"))?
+        .ok_or_else(|| {
+            ApiError::not_found(format!(
+                "Item '{}' not found in collection '{}'",
+                item_id, collection_id
+            ))
+        })?;
+    let geometry: Geometry = db_item
+        .geometry
+        .filter(|g| !g.is_null())
+        .ok_or_else(|| ApiError::not_found(format!("Item '{}' has no geometry", item_id)))
+        .and_then(|geometry| {
+            serde_json::from_value(geometry)
+                .map_err(|_| ApiError::internal("The item's geometry is not valid GeoJSON"))
+        })?;
+
+    let tolerance = query.tolerance.unwrap_or_else(|| {
+        match calculate_bbox_for_geometry(&geometry)[..] {
+            [min_x, min_y, max_x, max_y] => (max_x - min_x).max(max_y - min_y) / 1000.0,
+            _ => 0.0,
+        }
+    });
+    let mut footprint = simplify_geometry(&geometry, tolerance);
+    if mercator {
+        footprint = to_web_mercator(&footprint).map_err(ApiError::internal)?;
+    }
+
+    let mut body = serde_json::to_value(&footprint).unwrap_or_default();
+    body["bbox"] = serde_json::json!(calculate_bbox_for_geometry(&footprint));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/geo+json; charset=utf-8"),
+    );
+    let crs_uri = if mercator { WEB_MERCATOR_URI } else { CRS84_URI };
+    if let Ok(value) = HeaderValue::from_str(&format!("<{}>", crs_uri)) {
+        headers.insert("Content-Crs", value);
+    }
+    headers = add_cors_headers(headers);
+
+    Ok((headers, serde_json::to_string(&body).unwrap()).into_response())
+}
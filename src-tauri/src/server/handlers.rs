@@ -1,18 +1,67 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::{HeaderMap, HeaderValue},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, Uri},
     response::{IntoResponse, Response},
-    Json,
+    Extension, Json,
 };
 
-use crate::models::search::{OGCFeaturesQuery, SearchBody, SearchQuery};
+use crate::database::computed_properties;
+use crate::database::full_text;
+use crate::database::settings::{
+    AssetBandwidthLimit, LandingChildLinkLimit, SearchMaxResults, Setting,
+};
+use crate::database::{is_unique_violation, CollectionBounds, ItemBounds, ItemChange, ItemCursor};
+use crate::models::search::{CollectionsQuery, OGCFeaturesQuery, SearchBody, SearchQuery};
 use crate::models::{catalog::Catalog, link::Link};
-use crate::server::helpers::{filter_items_by_bbox, filter_items_by_datetime, parse_sortby, sort_items};
+use crate::server::access_log::{add_item_access_stats, client_id};
+use crate::server::bandwidth;
+use crate::server::bulk_update;
+use crate::server::checksums;
+use crate::server::asset_cleanup::{cleanup_report, confirmation_required_response, DeleteQuery};
+use crate::server::asset_sizes;
+use crate::server::auth::{caller_config, InternalRequest};
+use crate::server::branding::{branding_links, find_branding_file, BrandingKind};
+use crate::server::compact::{compact_feature_collection, compact_item, CompactQuery};
+use crate::server::conditional::{http_date, ListValidators, RecordValidators};
+use crate::server::conformance::{
+    conformance_body, ConformanceQuery, ConformanceRegistry, FeatureGroup, LandingLink,
+};
+use crate::server::cql2;
+use crate::server::error::ApiError;
+use crate::server::explain::{duration_ms, PlanStage, SearchPlan};
+use crate::server::federation;
+use crate::server::helpers::{
+    calculate_bbox_for_features, calculate_bbox_for_geometry, calculate_bbox_for_items,
+    geometries_intersect,
+    parse_datetime_interval, parse_intersects, parse_query, parse_sortby, sort_items,
+};
+use crate::server::i18n::{add_language_headers, LanguagePreference};
+use crate::server::ids::{resolve_id, IdScope};
+use crate::server::ingest::prepare_item;
+use crate::server::ingest_hooks::IngestEvent;
+use crate::server::ingest_mode::with_warnings;
+use crate::server::landing::landing_redirect;
+use crate::server::media_types::{asset_roles, MediaTypeRegistry};
 use crate::server::middleware::add_cors_headers;
+use crate::server::plugins::{apply_properties, extract_file_properties};
+use crate::server::ranges::{requested_range, ByteRange};
 use crate::server::openapi::OpenApiSpec;
 use crate::server::server::AppState;
+use crate::server::stac_validation::{
+    stac_violation_response, validate_stac_collection, ValidateQuery,
+};
+use crate::server::thumbnails::{add_collection_thumbnail, gallery_html};
+use crate::server::trash::{self, trashed_response};
+use crate::server::upload_scan::{
+    quarantine, scan_upload, ScanVerdict, SCAN_REASON_FIELD, SCAN_STATUS_FIELD,
+};
 use crate::server::utils::ServerConfig;
-use chrono::Utc;
+use crate::server::virtual_collections;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncSeekExt;
 
 use serde_json::json;
 
@@ -24,16 +73,78 @@ pub async fn health_check() -> Response {
         HeaderValue::from_static("application/json; charset=utf-8"),
     );
     headers = add_cors_headers(headers);
-    (
-        headers,
-        r#"{"status":"ok","timestamp":"2024-01-01T00:00:00Z"}"#,
-    )
-        .into_response()
+    let health = json!({
+        "status": "ok",
+        "timestamp": "2024-01-01T00:00:00Z",
+        "instance": crate::server::network::instance_id()
+    });
+    (headers, health.to_string()).into_response()
+}
+
+/// Conformance classes of the landing page, collections, items, search and
+/// transactions served here
+pub fn register_conformance(registry: &mut ConformanceRegistry) {
+    registry.register(
+        FeatureGroup::Core,
+        &[
+            "https://api.stacspec.org/v1.0.0/core",
+            "https://api.stacspec.org/v1.0.0/collections",
+            "https://api.stacspec.org/v1.0.0/ogcapi-features",
+            "https://api.stacspec.org/v1.0.0-rc.1/collection-search",
+            "https://api.stacspec.org/v1.0.0-rc.1/collection-search#free-text",
+            "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/core",
+            "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/oas30",
+            "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/geojson",
+        ],
+    );
+    registry.register(
+        FeatureGroup::Search,
+        &[
+            "https://api.stacspec.org/v1.0.0/item-search",
+            "https://api.stacspec.org/v1.0.0-rc.3/item-search#query",
+            "https://api.stacspec.org/v1.0.0/item-search#sort",
+            "https://api.stacspec.org/v1.0.0-rc.1/item-search#free-text",
+            "https://api.stacspec.org/v1.0.0-rc.2/item-search#context",
+        ],
+    );
+    registry.register(
+        FeatureGroup::Transactions,
+        &[
+            "https://api.stacspec.org/v1.0.0/collections/extensions/transaction",
+            "https://api.stacspec.org/v1.0.0/ogcapi-features/extensions/transaction",
+            "http://www.opengis.net/spec/ogcapi-features-4/1.0/conf/create-replace-delete",
+            "http://www.opengis.net/spec/ogcapi-features-4/1.0/conf/update",
+            "http://www.opengis.net/spec/ogcapi-features-4/1.0/conf/optimistic-locking-etags",
+            "http://www.opengis.net/spec/ogcapi-features-4/1.0/conf/features",
+        ],
+    );
+    registry.register_link(
+        FeatureGroup::Search,
+        LandingLink {
+            rel: "search",
+            path: "/search",
+            media_type: "application/geo+json",
+            title: "Item Search",
+        },
+    );
 }
 
-pub async fn hello_world(State(state): State<AppState>) -> Response {
+pub async fn hello_world(
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    internal: Option<Extension<InternalRequest>>,
+    uri: Uri,
+) -> Response {
+    // Browsers may be sent to a STAC browser or the HTML documentation
+    if let Some(response) = landing_redirect(&state, &request_headers, &uri).await {
+        return response;
+    }
+
     let server_config = ServerConfig::from_config(&state.config);
-    let catalog = Catalog {
+    let registry = ConformanceRegistry::standard();
+    let caller_config =
+        caller_config(&state, &request_headers, internal.map(|Extension(i)| i)).await;
+    let mut catalog = Catalog {
         r#type: "Catalog".to_string(),
         stac_version: state.config.catalog.stac_version.clone(),
         stac_extensions: Some(state.config.catalog.stac_extensions.clone()),
@@ -59,15 +170,6 @@ pub async fn hello_world(State(state): State<AppState>) -> Response {
                 headers: None,
                 body: None,
             },
-            Link {
-                href: server_config.collections_href(),
-                rel: "child".to_string(),
-                r#type: Some("application/json".to_string()),
-                title: Some("Collections".to_string()),
-                method: None,
-                headers: None,
-                body: None,
-            },
             Link {
                 href: server_config.conformance_href(),
                 rel: "conformance".to_string(),
@@ -86,15 +188,6 @@ pub async fn hello_world(State(state): State<AppState>) -> Response {
                 headers: None,
                 body: None,
             },
-            Link {
-                href: server_config.search_href(),
-                rel: "search".to_string(),
-                r#type: Some("application/geo+json".to_string()),
-                title: Some("Item Search".to_string()),
-                method: None,
-                headers: None,
-                body: None,
-            },
             Link {
                 href: server_config.api_href(),
                 rel: "service-desc".to_string(),
@@ -114,32 +207,72 @@ pub async fn hello_world(State(state): State<AppState>) -> Response {
                 body: None,
             },
         ],
-        conforms_to: state.config.catalog.conforms_to.clone(),
+        conforms_to: registry.classes(&caller_config.server),
     };
+
+    // Links of the enabled features, then the catalog's branding
+    catalog
+        .links
+        .extend(registry.landing_links(&caller_config.server, &server_config));
+    catalog
+        .links
+        .extend(branding_links(&state.config, &server_config));
+
+    // Link each collection as a child so static STAC crawlers can walk the catalog.
+    // Large catalogs are capped and left to the /collections endpoint.
+    let child_limit = state
+        .db_service
+        .settings
+        .get::<LandingChildLinkLimit>()
+        .await
+        .unwrap_or_else(|_| LandingChildLinkLimit::default_value());
+    if let Ok(collections) = state.db_service.collections.get_all().await {
+        for collection in collections.iter().take(child_limit) {
+            catalog.links.push(Link {
+                href: server_config.collection_href(&collection.id),
+                rel: "child".to_string(),
+                r#type: Some("application/json".to_string()),
+                title: Some(collection.title.clone().unwrap_or_else(|| collection.id.clone())),
+                method: None,
+                headers: None,
+                body: None,
+            });
+        }
+    }
+
     let mut headers = HeaderMap::new();
     headers.insert(
         "Content-Type",
         HeaderValue::from_static("application/json; charset=utf-8"),
     );
+    headers.insert("Vary", HeaderValue::from_static("Accept, Authorization, X-API-Key"));
     headers = add_cors_headers(headers);
     (headers, serde_json::to_string(&catalog).unwrap()).into_response()
 }
 
-pub async fn api_spec() -> Response {
+pub async fn api_spec(
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    internal: Option<Extension<InternalRequest>>,
+) -> Response {
     let mut headers = HeaderMap::new();
     headers.insert(
         "Content-Type",
         HeaderValue::from_static("application/vnd.oai.openapi+json;version=3.0"),
     );
+    headers.insert("Vary", HeaderValue::from_static("Authorization, X-API-Key"));
     headers = add_cors_headers(headers);
 
-    let spec = OpenApiSpec::create_stac_core_spec();
+    // Callers without write permission get the read-only API
+    let config = caller_config(&state, &request_headers, internal.map(|Extension(i)| i)).await;
+    let mut spec = OpenApiSpec::create_stac_core_spec();
+    spec.retain_enabled(&config.server);
     let json = serde_json::to_string(&spec).unwrap();
 
     (headers, json).into_response()
 }
 
-pub async fn api_html() -> Response {
+pub async fn api_html(State(state): State<AppState>) -> Response {
     let mut headers = HeaderMap::new();
     headers.insert(
         "Content-Type",
@@ -147,55 +280,252 @@ pub async fn api_html() -> Response {
     );
     headers = add_cors_headers(headers);
 
-    let html = crate::server::utils::read_static_html("api.html")
+    let mut html = crate::server::utils::read_static_html("api.html")
         .unwrap_or_else(|| "<h1>API Documentation Not Found</h1>".to_string());
 
+    // Advertise the catalog favicon and about document on the HTML page
+    let server_config = ServerConfig::from_config(&state.config);
+    let mut head_links = String::new();
+    for (kind, rel) in [(BrandingKind::Favicon, "icon"), (BrandingKind::About, "about")] {
+        if find_branding_file(&state.config, kind).is_some() {
+            head_links.push_str(&format!(
+                "<link rel=\"{}\" href=\"{}\">\n",
+                rel,
+                server_config.branding_href(kind.name())
+            ));
+        }
+    }
+    if !head_links.is_empty() {
+        html = match html.find("</head>") {
+            Some(pos) => format!("{}{}{}", &html[..pos], head_links, &html[pos..]),
+            None => format!("{}{}", head_links, html),
+        };
+    }
+
+    // Thumbnails of the collections, ahead of the reference sections
+    let gallery = gallery_html(&state.db_service, &server_config).await;
+    if !gallery.is_empty() {
+        if let Some(pos) = html.find("<h2>Conformance</h2>") {
+            html.insert_str(pos, &format!("{}\n    ", gallery.trim_start()));
+        }
+    }
+
     (headers, html).into_response()
 }
 
-pub async fn conformance(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let conformance_classes = serde_json::json!({
-        "conformsTo": state.config.catalog.conforms_to.clone()
-    });
-    Json(conformance_classes)
+pub async fn conformance(
+    State(state): State<AppState>,
+    Query(query): Query<ConformanceQuery>,
+    request_headers: HeaderMap,
+    internal: Option<Extension<InternalRequest>>,
+) -> Json<serde_json::Value> {
+    let config = caller_config(&state, &request_headers, internal.map(|Extension(i)| i)).await;
+    Json(conformance_body(&config, query.verbose == Some(true)))
 }
 
-pub async fn collections(State(state): State<AppState>) -> Response {
-    let server_config = ServerConfig::from_config(&state.config);
+/// Where a page of /collections starts, carried by the `token` of `next`
+/// and `prev` links: after or before a collection id
+enum CollectionPageToken {
+    After(String),
+    Before(String),
+}
 
-    // Get collections from database
-    let db_collections = match state.db_service.collections.get_all().await {
-        Ok(collections) => collections,
-        Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
+impl CollectionPageToken {
+    fn encode(&self) -> String {
+        let (prefix, id) = match self {
+            CollectionPageToken::After(id) => ("n", id),
+            CollectionPageToken::Before(id) => ("p", id),
+        };
+        let hex: String = id.bytes().map(|b| format!("{:02x}", b)).collect();
+        format!("{}{}", prefix, hex)
+    }
 
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to retrieve collections"
-            });
+    fn decode(token: &str) -> Option<Self> {
+        let (after, hex) = match token.split_at_checked(1)? {
+            ("n", hex) => (true, hex),
+            ("p", hex) => (false, hex),
+            _ => return None,
+        };
+        if !hex.len().is_multiple_of(2) {
+            return None;
+        }
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect::<Option<_>>()?;
+        let id = String::from_utf8(bytes).ok()?;
+        Some(if after {
+            CollectionPageToken::After(id)
+        } else {
+            CollectionPageToken::Before(id)
+        })
+    }
+}
 
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+/// Collections page continuing at `token`, with the query's other parameters
+fn collections_page_href(
+    server_config: &ServerConfig,
+    query: &CollectionsQuery,
+    token: &str,
+) -> String {
+    let mut params = Vec::new();
+    if let Some(limit) = query.limit {
+        params.push(format!("limit={}", limit));
+    }
+    for (name, value) in [("bbox", &query.bbox), ("datetime", &query.datetime), ("q", &query.q)] {
+        if let Some(value) = value {
+            params.push(format!("{}={}", name, urlencoding::encode(value)));
+        }
+    }
+    params.push(format!("token={}", token));
+    format!("{}?{}", server_config.collections_href(), params.join("&"))
+}
+
+pub async fn collections(
+    Query(query): Query<CollectionsQuery>,
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+) -> Response {
+    let server_config = ServerConfig::from_config(&state.config);
+
+    // Bbox, datetime and q are matched in SQL against the stored extents and
+    // the full-text index
+    let mut bounds = CollectionBounds::default();
+    if let Some(bbox_str) = &query.bbox {
+        match parse_bbox(bbox_str) {
+            Ok(bbox) => bounds.bbox = Some(bbox),
+            Err(e) => return e.into_response(),
+        }
+    }
+    if let Some(datetime_str) = &query.datetime {
+        match parse_datetime_interval(datetime_str) {
+            Ok(interval) => bounds.datetime = Some(interval),
+            Err(e) => {
+                return ApiError::bad_request(format!("Invalid datetime: {}", e)).into_response()
+            }
+        }
+    }
+    if let Some(q) = &query.q {
+        bounds.text = full_text::match_expression(q);
+    }
+    let token = match query.token.as_deref().map(CollectionPageToken::decode) {
+        Some(Some(token)) => Some(token),
+        Some(None) => return ApiError::bad_request("Invalid pagination token").into_response(),
+        None => None,
+    };
+
+    // One collection past the limit tells whether another page follows;
+    // SQLite reads a negative limit as no limit
+    let limit = query.limit.map(|l| l.max(0) as usize);
+    let fetch = limit.map_or(-1, |l| l as i64 + 1);
+    let (after, backward) = match &token {
+        Some(CollectionPageToken::After(id)) => (Some(id.as_str()), false),
+        Some(CollectionPageToken::Before(id)) => (Some(id.as_str()), true),
+        None => (None, false),
+    };
+    let page = state
+        .db_service
+        .collections
+        .page(&bounds, after, backward, fetch)
+        .await;
+    let number_matched = state.db_service.collections.count_matching(&bounds).await;
+    let (mut db_collections, mut number_matched) = match (page, number_matched) {
+        (Ok(collections), Ok(number_matched)) => (collections, number_matched as usize),
+        _ => {
+            return ApiError::internal("Failed to retrieve collections").into_response();
         }
     };
+    let more = limit.is_some_and(|limit| db_collections.len() > limit);
+    if let Some(limit) = limit {
+        db_collections.truncate(limit);
+    }
+    if backward {
+        db_collections.reverse();
+    }
+    let first_id = db_collections.first().map(|c| c.id.clone());
+    let last_id = db_collections.last().map(|c| c.id.clone());
+    let (next_token, prev_token) = if backward {
+        (
+            last_id.map(CollectionPageToken::After),
+            first_id.filter(|_| more).map(CollectionPageToken::Before),
+        )
+    } else {
+        (
+            last_id.filter(|_| more).map(CollectionPageToken::After),
+            first_id
+                .filter(|_| after.is_some())
+                .map(CollectionPageToken::Before),
+        )
+    };
 
-    // Convert database collections to STAC collections
-    let collections: Vec<_> = db_collections
+    // Convert database collections to STAC collections, in the client's language
+    let preference = LanguagePreference::from_headers(&state.db_service, &request_headers).await;
+    let translations = state
+        .db_service
+        .translations
+        .for_collections()
+        .await
+        .unwrap_or_default();
+    let mut collections: Vec<serde_json::Value> = db_collections
         .iter()
-        .map(|db_col| db_col.to_stac_collection(&server_config))
+        .map(|db_col| {
+            let mut collection = json!(db_col.to_stac_collection(&server_config));
+            if let Some(translations) = translations.get(&db_col.id) {
+                preference.localize_collection(&mut collection, translations);
+            }
+            collection
+        })
         .collect();
+    for collection in collections.iter_mut() {
+        add_collection_thumbnail(&state.db_service, &server_config, collection).await;
+    }
+
+    // Virtual collections follow the stored ones on the last page of an
+    // unfiltered listing; they have no stored extent and are not in the
+    // full-text index
+    if bounds.is_empty() {
+        if let Ok(virtual_collections) = state.db_service.virtual_collections.get_all().await {
+            number_matched += virtual_collections.len();
+            if next_token.is_none() {
+                collections.extend(virtual_collections.iter().map(|vc| {
+                    virtual_collections::to_stac_collection(
+                        vc,
+                        &server_config,
+                        &state.config.catalog.stac_version,
+                    )
+                }));
+            }
+        }
+    }
+
+    let mut links = vec![
+        json!({
+            "href": server_config.collections_href(),
+            "rel": "self",
+            "type": "application/json"
+        }),
+        json!({
+            "href": server_config.root_href(),
+            "rel": "root",
+            "type": "application/json"
+        }),
+    ];
+    for (rel, token) in [("next", &next_token), ("prev", &prev_token)] {
+        if let Some(token) = token {
+            links.push(json!({
+                "href": collections_page_href(&server_config, &query, &token.encode()),
+                "rel": rel,
+                "type": "application/json",
+                "method": "GET"
+            }));
+        }
+    }
 
     let collections_response = serde_json::json!({
-        "collections": collections
+        "numberMatched": number_matched,
+        "numberReturned": collections.len(),
+        "collections": collections,
+        "links": links
     });
 
     let mut headers = HeaderMap::new();
@@ -203,6 +533,7 @@ pub async fn collections(State(state): State<AppState>) -> Response {
         "Content-Type",
         HeaderValue::from_static("application/json; charset=utf-8"),
     );
+    add_language_headers(&mut headers, None);
     headers = add_cors_headers(headers);
 
     (
@@ -215,6 +546,7 @@ pub async fn collections(State(state): State<AppState>) -> Response {
 pub async fn collection(
     Path(collection_id): Path<String>,
     State(state): State<AppState>,
+    request_headers: HeaderMap,
 ) -> Response {
     let server_config = ServerConfig::from_config(&state.config);
 
@@ -222,230 +554,422 @@ pub async fn collection(
     let db_collection = match state.db_service.collections.get_by_id(&collection_id).await {
         Ok(Some(collection)) => collection,
         Ok(None) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Collection '{}' not found", collection_id)
-            });
+            if let Ok(Some(vc)) = state.db_service.virtual_collections.get_by_id(&collection_id).await {
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    "Content-Type",
+                    HeaderValue::from_static("application/json; charset=utf-8"),
+                );
+                headers = add_cors_headers(headers);
+                let stac_collection = virtual_collections::to_stac_collection(
+                    &vc,
+                    &server_config,
+                    &state.config.catalog.stac_version,
+                );
+                return (headers, serde_json::to_string(&stac_collection).unwrap()).into_response();
+            }
 
-            return (
-                axum::http::StatusCode::NOT_FOUND,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
+            return ApiError::not_found(format!("Collection '{}' not found", collection_id))
                 .into_response();
         }
         Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to retrieve collection"
-            });
-
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::internal("Failed to retrieve collection").into_response();
         }
     };
 
-    // Convert to STAC collection
-    let stac_collection = db_collection.to_stac_collection(&server_config);
+    let validators = collection_validators(&state, &db_collection, &request_headers).await;
+    if validators.matches(&request_headers) {
+        return validators.not_modified();
+    }
+
+    // Convert to STAC collection, in the client's language
+    let mut stac_collection = json!(db_collection.to_stac_collection(&server_config));
+    let translations = state
+        .db_service
+        .translations
+        .get(&collection_id, None)
+        .await
+        .unwrap_or_default();
+    let language = LanguagePreference::from_headers(&state.db_service, &request_headers)
+        .await
+        .localize_collection(&mut stac_collection, &translations);
+    add_collection_thumbnail(&state.db_service, &server_config, &mut stac_collection).await;
 
     let mut headers = HeaderMap::new();
     headers.insert(
         "Content-Type",
         HeaderValue::from_static("application/json; charset=utf-8"),
     );
+    add_language_headers(&mut headers, language.as_deref());
     headers = add_cors_headers(headers);
+    validators.add_headers(&mut headers);
 
     (headers, serde_json::to_string(&stac_collection).unwrap()).into_response()
 }
 
+/// Validators of a stored collection in the language negotiated for the
+/// request, for conditional GET and PUT
+async fn collection_validators(
+    state: &AppState,
+    collection: &crate::database::models::DbCollection,
+    request_headers: &HeaderMap,
+) -> RecordValidators {
+    let translations = state
+        .db_service
+        .translations
+        .get(&collection.id, None)
+        .await
+        .unwrap_or_default();
+    let language = LanguagePreference::from_headers(&state.db_service, request_headers)
+        .await
+        .choose(&translations);
+    RecordValidators::new(collection, &translations, &collection.updated_at, &language)
+}
+
+/// Validators of a stored item in the language negotiated for the request,
+/// for conditional GET and PUT
+async fn item_validators(
+    state: &AppState,
+    item: &crate::database::models::DbItem,
+    request_headers: &HeaderMap,
+) -> RecordValidators {
+    let translations = state
+        .db_service
+        .translations
+        .get(&item.collection_id, Some(&item.id))
+        .await
+        .unwrap_or_default();
+    let language = LanguagePreference::from_headers(&state.db_service, request_headers)
+        .await
+        .choose(&translations);
+    RecordValidators::new(item, &translations, &item.updated_at, &language)
+}
+
 pub async fn collection_items(
     Path(collection_id): Path<String>,
     Query(query): Query<OGCFeaturesQuery>,
     State(state): State<AppState>,
+    uri: Uri,
+    request_headers: HeaderMap,
 ) -> Response {
     let server_config = ServerConfig::from_config(&state.config);
 
     // Get collection from database to verify it exists
     let db_collection = match state.db_service.collections.get_by_id(&collection_id).await {
-        Ok(Some(_)) => {}
+        Ok(Some(collection)) => collection,
         Ok(None) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Collection '{}' not found", collection_id)
-            });
+            // Virtual collections run their stored search
+            if let Ok(Some(vc)) = state.db_service.virtual_collections.get_by_id(&collection_id).await {
+                return virtual_collections::items_response(&vc, &query, &state).await;
+            }
 
-            return (
-                axum::http::StatusCode::NOT_FOUND,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
+            return ApiError::not_found(format!("Collection '{}' not found", collection_id))
                 .into_response();
         }
         Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to retrieve collection"
-            });
-
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::internal("Failed to retrieve collection").into_response();
         }
     };
 
-    // Get items from database
-    let limit = query.limit.map(|l| l as i64);
-    let offset = query.offset.map(|o| o as i64);
-    let db_items = match state
+    let computed = state
         .db_service
-        .items
-        .get_by_collection(&collection_id, limit, offset)
+        .computed_properties
+        .for_collection(&collection_id)
         .await
-    {
-        Ok(items) => items,
-        Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to retrieve items"
-            });
-
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+        .unwrap_or_default();
+
+    // Polling clients holding a current page get 304 without the items
+    // being read; a collection without item changes dates from its own
+    // update. Items with time-relative computed properties change without
+    // one, so their pages get no validators.
+    let change = if computed_properties::any_time_relative(&computed) {
+        None
+    } else {
+        match state.db_service.items.last_change(&collection_id).await {
+            Ok(Some(change)) => Some(change),
+            Ok(None) => chrono::DateTime::parse_from_rfc3339(&db_collection.updated_at)
+                .ok()
+                .map(|updated| ItemChange {
+                    last_modified: updated.with_timezone(&Utc),
+                    revision: 0,
+                }),
+            Err(_) => None,
         }
     };
+    let validators = change.map(|change| ListValidators::new(&change, &uri, &request_headers));
+    if let Some(validators) = &validators {
+        if validators.matches(&request_headers) {
+            return validators.not_modified();
+        }
+    }
 
-    // Convert database items to STAC items
-    let items: Vec<_> = db_items
-        .iter()
-        .map(|db_item| db_item.to_stac_item(&server_config))
-        .collect();
-
-    let items_response = serde_json::json!({
-        "type": "FeatureCollection",
-        "features": items,
-        "links": [
-            {
-                "href": server_config.collection_items_href(&collection_id),
-                "rel": "self",
-                "type": "application/geo+json"
-            },
-            {
-                "href": server_config.collection_href(&collection_id),
-                "rel": "parent",
-                "type": "application/json"
+    let bounds = match query_bounds(query.bbox.as_deref(), query.datetime.as_deref()) {
+        Ok(bounds) => bounds,
+        Err(response) => return response,
+    };
+    let sortby = match query.sortby.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(sortby_str) => match parse_sortby(sortby_str) {
+            Ok(sortby) => sortby,
+            Err(e) => {
+                return ApiError::bad_request(format!("Invalid sortby: {}", e)).into_response()
             }
-        ]
-    });
-
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "Content-Type",
-        HeaderValue::from_static("application/geo+json; charset=utf-8"),
-    );
-    headers = add_cors_headers(headers);
-
-    (headers, serde_json::to_string(&items_response).unwrap()).into_response()
-}
+        },
+        None => Vec::new(),
+    };
+    let filter = match &query.filter {
+        Some(filter) => match cql2::parse_filter(filter, query.filter_lang.as_deref()) {
+            Ok(expr) => Some(expr),
+            Err(e) => {
+                return ApiError::bad_request(format!("Invalid filter: {}", e)).into_response()
+            }
+        },
+        None => None,
+    };
 
-pub async fn item(
-    Path((collection_id, item_id)): Path<(String, String)>,
-    State(state): State<AppState>,
-) -> Response {
-    let server_config = ServerConfig::from_config(&state.config);
+    // Bbox, datetime and sortby are applied in SQL; pages are no larger than
+    // the search_max_results setting
+    let max_results = state
+        .db_service
+        .settings
+        .get::<SearchMaxResults>()
+        .await
+        .unwrap_or_else(|_| SearchMaxResults::default_value()) as usize;
+    let page_size = query.limit.map_or(10, |l| l.max(0) as usize).min(max_results);
+    let page_offset = query.offset.unwrap_or(0).max(0) as usize;
+    let collection_ids = std::slice::from_ref(&collection_id);
 
-    // Get item from database
-    let db_item = match state
+    // Database items become STAC items with their related item links
+    let relations = state
         .db_service
-        .items
-        .get_by_id(&collection_id, &item_id)
+        .item_links
+        .outgoing_for_collection(&collection_id)
         .await
-    {
-        Ok(Some(item)) => item,
-        Ok(None) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
+        .unwrap_or_default();
+    let now = Utc::now();
+    let to_json = |mut db_item: crate::database::models::DbItem| {
+        computed_properties::apply(&computed, &mut db_item, now);
+        let related = relations.get(&db_item.id).map(Vec::as_slice).unwrap_or(&[]);
+        db_item.into_stac_item_json(&server_config, related)
+    };
 
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Item '{}' not found in collection '{}'", item_id, collection_id)
-            });
+    let items_repo = &state.db_service.items;
+    let (mut items, number_matched, has_next) = match &filter {
+        // Unfiltered pages are read by SQL in item id order, or sortby
+        None => {
+            let db_items = match items_repo
+                .page_sorted(
+                    collection_ids,
+                    &[],
+                    &bounds,
+                    &sortby,
+                    page_offset as i64,
+                    page_size as i64,
+                )
+                .await
+            {
+                Ok(items) => items,
+                Err(_) => {
+                    return ApiError::internal("Failed to retrieve items").into_response();
+                }
+            };
+            let items: Vec<serde_json::Value> = db_items.into_iter().map(to_json).collect();
+            let number_matched = items_repo
+                .count_matching(collection_ids, &[], &bounds)
+                .await
+                .ok()
+                .map(|count| count.max(0) as usize);
+            // Without a count, a full page is taken to have a successor
+            let has_next = match number_matched {
+                Some(total) => page_offset + items.len() < total,
+                None => page_size > 0 && items.len() == page_size,
+            };
+            (items, number_matched, has_next)
+        }
+        // A filter is tested in memory on batches of the SQL matches, read
+        // in keyset (datetime, id) order until one item past the page is
+        // found. Sorted matches must all be read, so sortby is refused when
+        // more than search_max_results items could match.
+        Some(expr) => {
+            if !sortby.is_empty() {
+                let estimate = match items_repo.count_matching(collection_ids, &[], &bounds).await {
+                    Ok(count) => count.max(0) as usize,
+                    Err(_) => {
+                        return ApiError::internal("Failed to count items").into_response();
+                    }
+                };
+                if estimate > max_results {
+                    return ApiError::bad_request(format!(
+                        "The filter is tested on up to {} items, more than the {} that can be \
+                         sorted at once. Narrow it with bbox or datetime, or drop sortby.",
+                        estimate, max_results
+                    ))
+                    .into_response();
+                }
+            }
 
-            return (
-                axum::http::StatusCode::NOT_FOUND,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            let mut matched = Vec::new();
+            let mut skipped = 0;
+            let mut cursor: Option<ItemCursor> = None;
+            let mut scanned = 0;
+            let exhausted = loop {
+                let batch = if sortby.is_empty() {
+                    items_repo
+                        .page_after(
+                            collection_ids,
+                            &[],
+                            &bounds,
+                            cursor.as_ref(),
+                            SEARCH_SCAN_BATCH,
+                        )
+                        .await
+                        .map(|batch| {
+                            cursor = batch.last().map(|(last, _)| last.clone());
+                            batch.into_iter().map(|(_, item)| item).collect::<Vec<_>>()
+                        })
+                } else {
+                    items_repo
+                        .page_sorted(
+                            collection_ids,
+                            &[],
+                            &bounds,
+                            &sortby,
+                            scanned as i64,
+                            SEARCH_SCAN_BATCH,
+                        )
+                        .await
+                };
+                let batch = match batch {
+                    Ok(batch) => batch,
+                    Err(_) => {
+                        return ApiError::internal("Failed to retrieve items").into_response();
+                    }
+                };
+                let exhausted = (batch.len() as i64) < SEARCH_SCAN_BATCH;
+                scanned += batch.len();
+                for item in batch.into_iter().map(to_json) {
+                    if !expr.matches(&item) {
+                        continue;
+                    }
+                    if skipped < page_offset {
+                        skipped += 1;
+                    } else {
+                        matched.push(item);
+                    }
+                }
+                if exhausted || matched.len() > page_size {
+                    break exhausted;
+                }
+            };
+            // Only a scan that reached the end knows how many items match
+            let number_matched = exhausted.then(|| skipped + matched.len());
+            let has_next = matched.len() > page_size;
+            matched.truncate(page_size);
+            (matched, number_matched, has_next)
         }
-        Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
+    };
+    add_item_access_stats(&state.db_service, &server_config, &mut items).await;
 
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to retrieve item"
-            });
+    // Titles and descriptions in the client's language
+    let preference = LanguagePreference::from_headers(&state.db_service, &request_headers).await;
+    let translations = state
+        .db_service
+        .translations
+        .for_items(&collection_id)
+        .await
+        .unwrap_or_default();
+    for feature in items.iter_mut() {
+        let item_translations = feature
+            .get("id")
+            .and_then(|id| id.as_str())
+            .and_then(|id| translations.get(id));
+        if let Some(item_translations) = item_translations {
+            preference.localize_item(feature, item_translations);
+        }
+    }
 
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+    let mut links = vec![
+        json!({
+            "href": server_config.collection_items_href(&collection_id),
+            "rel": "self",
+            "type": "application/geo+json"
+        }),
+        json!({
+            "href": server_config.collection_href(&collection_id),
+            "rel": "parent",
+            "type": "application/json"
+        }),
+    ];
+    // Offset pages
+    if has_next {
+        links.push(json!({
+            "href": items_page_href(&server_config, &collection_id, &query, page_offset + items.len()),
+            "rel": "next",
+            "type": "application/geo+json"
+        }));
+    }
+    if page_offset > 0 {
+        links.push(json!({
+            "href": items_page_href(&server_config, &collection_id, &query, page_offset.saturating_sub(page_size)),
+            "rel": "prev",
+            "type": "application/geo+json"
+        }));
+    }
+
+    // Union of the returned items' extents, used by map clients to zoom to results
+    let bbox = calculate_bbox_for_features(&items);
+    let number_returned = items.len();
+    let mut items_response = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": items,
+        "links": links
+    });
+    add_item_counts(&mut items_response, number_returned, Some(page_size), number_matched);
+    if let Some(bbox) = bbox {
+        items_response["bbox"] = serde_json::json!(bbox);
+    }
+    if query.compact == Some(true) {
+        compact_feature_collection(&mut items_response);
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/geo+json; charset=utf-8"),
+    );
+    add_language_headers(&mut headers, None);
+    headers = add_cors_headers(headers);
+    if let Some(validators) = &validators {
+        validators.add_headers(&mut headers);
+    }
+
+    (headers, serde_json::to_string(&items_response).unwrap()).into_response()
+}
+
+pub async fn item(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    Query(compact): Query<CompactQuery>,
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+) -> Response {
+    let server_config = ServerConfig::from_config(&state.config);
+
+    // Get item from database
+    let db_item = match state
+        .db_service
+        .items
+        .get_by_id(&collection_id, &item_id)
+        .await
+    {
+        Ok(Some(item)) => item,
+        Ok(None) => {
+            return ApiError::not_found(format!(
+                "Item '{}' not found in collection '{}'",
+                item_id, collection_id
+            ))
+            .into_response();
+        }
+        Err(_) => {
+            return ApiError::internal("Failed to retrieve item").into_response();
         }
     };
 
@@ -457,32 +981,103 @@ pub async fn item(
     
     }
 
-    // Convert to STAC item
-    let stac_item = db_item.to_stac_item(&server_config);
+    // Computed properties are evaluated again, as time-relative ones age;
+    // the stored version does not say whether those changed, so items with
+    // them get no validators
+    let definitions = state
+        .db_service
+        .computed_properties
+        .for_collection(&collection_id)
+        .await
+        .unwrap_or_default();
+    let validators = if computed_properties::any_time_relative(&definitions) {
+        None
+    } else {
+        Some(item_validators(&state, &db_item, &request_headers).await)
+    };
+    if let Some(validators) = &validators {
+        if validators.matches(&request_headers) {
+            return validators.not_modified();
+        }
+    }
+    let mut db_item = db_item;
+    computed_properties::apply(&definitions, &mut db_item, Utc::now());
+
+    // Convert to STAC item, with links to related items
+    let relations = state
+        .db_service
+        .item_links
+        .outgoing(&collection_id, &item_id)
+        .await
+        .unwrap_or_default();
+    let mut stac_item = db_item.into_stac_item_json(&server_config, &relations);
+    add_item_access_stats(
+        &state.db_service,
+        &server_config,
+        std::slice::from_mut(&mut stac_item),
+    )
+    .await;
+
+    // Title and description in the client's language
+    let translations = state
+        .db_service
+        .translations
+        .get(&collection_id, Some(&item_id))
+        .await
+        .unwrap_or_default();
+    let language = LanguagePreference::from_headers(&state.db_service, &request_headers)
+        .await
+        .localize_item(&mut stac_item, &translations);
+    if compact.compact == Some(true) {
+        compact_item(&mut stac_item);
+    }
 
     let mut headers = HeaderMap::new();
     headers.insert(
         "Content-Type",
         HeaderValue::from_static("application/geo+json; charset=utf-8"),
     );
+    add_language_headers(&mut headers, language.as_deref());
     headers = add_cors_headers(headers);
+    if let Some(validators) = &validators {
+        validators.add_headers(&mut headers);
+    }
 
     (headers, serde_json::to_string(&stac_item).unwrap()).into_response()
 }
 
-pub async fn sortables() -> Response {
-    let sortables_response = serde_json::json!({
-        "sortables": [
-            {
-                "field": "datetime",
-                "direction": ["asc", "desc"]
-            },
-            {
-                "field": "id",
-                "direction": ["asc", "desc"]
-            }
-        ]
-    });
+/// Fields searches can sort on: `id`, `collection`, `datetime` and every
+/// property with scalar values stored by the items of `collection_ids`
+async fn sortables_response(state: &AppState, collection_ids: &[String]) -> Response {
+    let stored = match state.db_service.items.property_types(collection_ids).await {
+        Ok(stored) => stored,
+        Err(_) => return ApiError::internal("Failed to derive sortables").into_response(),
+    };
+    let mut sortables = vec![
+        json!({ "field": "id", "type": "string", "direction": ["asc", "desc"] }),
+        json!({ "field": "collection", "type": "string", "direction": ["asc", "desc"] }),
+        json!({ "field": "datetime", "type": "string", "direction": ["asc", "desc"] }),
+    ];
+    for (property, sqlite_types) in &stored {
+        if property == "datetime" {
+            continue;
+        }
+        let json_type = if sqlite_types.iter().all(|t| t == "integer" || t == "real") {
+            "number"
+        } else if sqlite_types.iter().all(|t| t == "true" || t == "false") {
+            "boolean"
+        } else if sqlite_types.iter().all(|t| t == "text") {
+            "string"
+        } else {
+            // Arrays, objects and mixed values have no meaningful order
+            continue;
+        };
+        sortables.push(json!({
+            "field": format!("properties.{}", property),
+            "type": json_type,
+            "direction": ["asc", "desc"]
+        }));
+    }
 
     let mut headers = HeaderMap::new();
     headers.insert(
@@ -491,31 +1086,28 @@ pub async fn sortables() -> Response {
     );
     headers = add_cors_headers(headers);
 
-    (headers, serde_json::to_string(&sortables_response).unwrap()).into_response()
+    (headers, json!({ "sortables": sortables }).to_string()).into_response()
 }
 
-pub async fn collection_sortables(Path(_collection_id): Path<String>) -> Response {
-    let sortables_response = serde_json::json!({
-        "sortables": [
-            {
-                "field": "datetime",
-                "direction": ["asc", "desc"]
-            },
-            {
-                "field": "id",
-                "direction": ["asc", "desc"]
-            }
-        ]
-    });
-
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "Content-Type",
-        HeaderValue::from_static("application/json; charset=utf-8"),
-    );
-    headers = add_cors_headers(headers);
+pub async fn sortables(State(state): State<AppState>) -> Response {
+    let collection_ids: Vec<String> = match state.db_service.collections.get_all().await {
+        Ok(collections) => collections.into_iter().map(|c| c.id).collect(),
+        Err(_) => return ApiError::internal("Failed to retrieve collections").into_response(),
+    };
+    sortables_response(&state, &collection_ids).await
+}
 
-    (headers, serde_json::to_string(&sortables_response).unwrap()).into_response()
+pub async fn collection_sortables(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    match state.db_service.collections.get_by_id(&collection_id).await {
+        Ok(Some(_)) => sortables_response(&state, std::slice::from_ref(&collection_id)).await,
+        Ok(None) => {
+            ApiError::not_found(format!("Collection '{}' not found", collection_id)).into_response()
+        }
+        Err(_) => ApiError::internal("Failed to retrieve collection").into_response(),
+    }
 }
 
 pub async fn collections_sortables() -> Response {
@@ -536,34 +1128,137 @@ pub async fn collections_sortables() -> Response {
 pub async fn search_get(
     Query(query): Query<SearchQuery>,
     State(state): State<AppState>,
+    request_headers: HeaderMap,
 ) -> Response {
-    search_items(query, state).await
+    search_items(query, state, &request_headers).await
 }
 
-pub async fn search_post(State(state): State<AppState>, Json(body): Json<SearchBody>) -> Response {
-    let query = SearchQuery {
-        limit: body.limit,
-        bbox: body
-            .bbox
-            .map(|bbox| format!("{},{},{},{}", bbox[0], bbox[1], bbox[2], bbox[3])),
-        datetime: body.datetime,
-        intersects: body
-            .intersects
-            .map(|geom| serde_json::to_string(&geom).unwrap_or_default()),
-        ids: body.ids.map(|ids| ids.join(",")),
-        collections: body.collections.map(|cols| cols.join(",")),
-        sortby: body.sortby.map(|sortby| {
-            sortby
-                .iter()
-                .map(|s| format!("{}:{}", s.field, s.direction))
-                .collect::<Vec<_>>()
-                .join(",")
-        }),
-    };
-    search_items(query, state).await
+pub async fn search_post(
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    Json(body): Json<SearchBody>,
+) -> Response {
+    search_items(SearchQuery::from(body), state, &request_headers).await
 }
 
-async fn search_items(query: SearchQuery, state: AppState) -> Response {
+/// Items read from the database per keyset scan
+const SEARCH_SCAN_BATCH: i64 = 500;
+
+/// One page of search results
+pub(crate) struct SearchResults {
+    pub items: Vec<crate::models::Item>,
+    /// Token for the following page, when there is one
+    pub next_token: Option<String>,
+    /// Token for the preceding page, when there is one
+    pub prev_token: Option<String>,
+    /// Number of items matching the search, when known without reading them
+    /// all; CQL2 filters run in memory, so unsorted filtered searches lack it
+    pub number_matched: Option<usize>,
+    /// Page size the search was served with, after the `search_max_results`
+    /// cap; `None` when every match is returned
+    pub limit: Option<usize>,
+}
+
+/// Sets the item counts of a FeatureCollection page: `numberReturned` and
+/// `numberMatched` of OGC API Features, and the same counts with the page
+/// size in the `context` object of the STAC context extension. `matched` is
+/// left out when it isn't known.
+pub(crate) fn add_item_counts(
+    page: &mut serde_json::Value,
+    returned: usize,
+    limit: Option<usize>,
+    matched: Option<usize>,
+) {
+    page["numberReturned"] = json!(returned);
+    let mut context = json!({ "returned": returned });
+    if let Some(limit) = limit {
+        context["limit"] = json!(limit);
+    }
+    if let Some(matched) = matched {
+        page["numberMatched"] = json!(matched);
+        context["matched"] = json!(matched);
+    }
+    page["context"] = context;
+}
+
+/// Where a search page starts, carried by the `token` of `next` and `prev`
+/// links: after or before an item in keyset order, or at an offset into
+/// sorted results
+enum PageToken {
+    After(ItemCursor),
+    Before(ItemCursor),
+    Offset(usize),
+}
+
+impl PageToken {
+    fn encode(&self) -> String {
+        match self {
+            PageToken::After(cursor) => cursor.encode(),
+            PageToken::Before(cursor) => format!("p{}", cursor.encode()),
+            PageToken::Offset(offset) => format!("o{}", offset),
+        }
+    }
+
+    fn decode(token: &str) -> Option<Self> {
+        if let Some(cursor) = token.strip_prefix('p') {
+            return ItemCursor::decode(cursor).map(PageToken::Before);
+        }
+        if let Some(offset) = token.strip_prefix('o') {
+            return offset.parse().ok().map(PageToken::Offset);
+        }
+        ItemCursor::decode(token).map(PageToken::After)
+    }
+}
+
+/// Runs an item search and returns the matching items, filtered, sorted and
+/// limited. Shared by /search and the items of virtual collections. Searches
+/// that could match more than the `search_max_results` setting are served in
+/// pages of that size when unsorted and refused when sorted.
+pub(crate) async fn find_items(
+    query: &SearchQuery,
+    state: &AppState,
+) -> Result<SearchResults, Response> {
+    let max_results = state
+        .db_service
+        .settings
+        .get::<SearchMaxResults>()
+        .await
+        .unwrap_or_else(|_| SearchMaxResults::default_value());
+    run_search(query, state, Some(max_results as usize), None).await
+}
+
+/// Like [`find_items`] without the result guardrail, for background jobs that
+/// need every match
+pub(crate) async fn find_all_items(
+    query: &SearchQuery,
+    state: &AppState,
+) -> Result<SearchResults, Response> {
+    run_search(query, state, None, None).await
+}
+
+/// Runs a search like [`find_items`] and reports how it was executed
+pub(crate) async fn explain_items(
+    query: &SearchQuery,
+    state: &AppState,
+) -> Result<SearchPlan, Response> {
+    let max_results = state
+        .db_service
+        .settings
+        .get::<SearchMaxResults>()
+        .await
+        .unwrap_or_else(|_| SearchMaxResults::default_value());
+    let mut plan = SearchPlan::default();
+    let results = run_search(query, state, Some(max_results as usize), Some(&mut plan)).await?;
+    plan.returned = results.items.len();
+    Ok(plan)
+}
+
+async fn run_search(
+    query: &SearchQuery,
+    state: &AppState,
+    max_results: Option<usize>,
+    mut plan: Option<&mut SearchPlan>,
+) -> Result<SearchResults, Response> {
     let server_config = ServerConfig::from_config(&state.config);
 
 
@@ -584,24 +1279,7 @@ async fn search_items(query: SearchQuery, state: AppState) -> Response {
                     all_ids
                 }
                 Err(e) => {
-                    let mut headers = HeaderMap::new();
-                    headers.insert(
-                        "Content-Type",
-                        HeaderValue::from_static("application/json; charset=utf-8"),
-                    );
-                    headers = add_cors_headers(headers);
-
-                    let error_response = serde_json::json!({
-                        "code": "InternalServerError",
-                        "description": "Failed to retrieve collections"
-                    });
-
-                    return (
-                        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                        headers,
-                        serde_json::to_string(&error_response).unwrap(),
-                    )
-                        .into_response();
+                    return Err(ApiError::internal("Failed to retrieve collections").into_response());
                 }
             }
         } else {
@@ -616,110 +1294,554 @@ async fn search_items(query: SearchQuery, state: AppState) -> Response {
                 ids
             }
             Err(e) => {
-                let mut headers = HeaderMap::new();
-                headers.insert(
-                    "Content-Type",
-                    HeaderValue::from_static("application/json; charset=utf-8"),
-                );
-                headers = add_cors_headers(headers);
+                return Err(ApiError::internal("Failed to retrieve collections").into_response());
+            }
+        }
+    };
+
+    // Bbox and datetime are matched in SQL against the stored item extents
+    let mut bounds = query_bounds(query.bbox.as_deref(), query.datetime.as_deref())?;
+    if let Some(ids_str) = &query.ids {
+        let ids: Vec<String> = ids_str
+            .split(',')
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect();
+        if !ids.is_empty() {
+            bounds.ids = Some(ids);
+        }
+    }
 
-                let error_response = serde_json::json!({
-                    "code": "InternalServerError",
-                    "description": "Failed to retrieve collections"
-                });
+    if let Some(q) = &query.q {
+        bounds.text = full_text::match_expression(q);
+    }
 
-                return (
-                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    headers,
-                    serde_json::to_string(&error_response).unwrap(),
+    // Items are narrowed in SQL by the geometry's bbox, then tested exactly
+    let intersects = match &query.intersects {
+        Some(_) if query.bbox.is_some() => {
+            return Err(
+                ApiError::bad_request("Only one of bbox and intersects may be given")
+                    .into_response(),
+            )
+        }
+        Some(geometry) => match parse_intersects(geometry) {
+            Ok(geometry) => {
+                if let [min_x, min_y, max_x, max_y] = calculate_bbox_for_geometry(&geometry)[..] {
+                    bounds.bbox = Some([min_x, min_y, max_x, max_y]);
+                }
+                Some(geometry)
+            }
+            Err(e) => {
+                return Err(
+                    ApiError::bad_request(format!("Invalid intersects: {}", e)).into_response()
                 )
-                    .into_response();
             }
-        }
+        },
+        None => None,
+    };
+
+    let predicates = match &query.query {
+        Some(query_str) => match serde_json::from_str(query_str)
+            .map_err(|e| format!("query is not JSON: {}", e))
+            .and_then(|value| parse_query(&value))
+        {
+            Ok(predicates) => predicates,
+            Err(e) => {
+                return Err(ApiError::bad_request(format!("Invalid query: {}", e)).into_response())
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let filter = match &query.filter {
+        Some(filter) => match cql2::parse_filter(filter, query.filter_lang.as_deref()) {
+            Ok(expr) => Some(expr),
+            Err(e) => {
+                return Err(ApiError::bad_request(format!("Invalid filter: {}", e)).into_response())
+            }
+        },
+        None => None,
     };
 
-    // Get items from all specified collections
-    // Don't apply limit here - we want all items to apply filters properly
-    let mut all_items = Vec::new();
-    for collection_id in &collection_ids {
+    // Keyset paging follows the (datetime, collection, id) order; sorted
+    // searches page by offset instead
+    let sortby = match query.sortby.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(sortby_str) => match parse_sortby(sortby_str) {
+            Ok(sortby) => Some(sortby),
+            Err(e) => {
+                return Err(ApiError::bad_request(format!("Invalid sortby: {}", e)).into_response())
+            }
+        },
+        None => None,
+    };
+    let token = match query.token.as_deref().map(PageToken::decode) {
+        Some(Some(PageToken::Offset(_))) if sortby.is_none() => {
+            return Err(ApiError::bad_request(
+                "This pagination token belongs to a sorted search; repeat the sortby",
+            )
+            .into_response())
+        }
+        Some(Some(PageToken::After(_) | PageToken::Before(_))) if sortby.is_some() => {
+            return Err(ApiError::bad_request(
+                "Pagination tokens of unsorted searches cannot be combined with sortby",
+            )
+            .into_response())
+        }
+        Some(Some(token)) => Some(token),
+        Some(None) => return Err(ApiError::bad_request("Invalid pagination token").into_response()),
+        None => None,
+    };
+    let (cursor, backward, offset) = match token {
+        Some(PageToken::After(cursor)) => (Some(cursor), false, 0),
+        Some(PageToken::Before(cursor)) => (Some(cursor), true, 0),
+        Some(PageToken::Offset(offset)) => (None, false, offset),
+        None => (None, false, 0),
+    };
+    let paged = cursor.is_some();
 
+    if let Some(plan) = plan.as_deref_mut() {
+        plan.collections = collection_ids.clone();
         match state
             .db_service
             .items
-            .get_by_collection(collection_id, None, Some(0))
+            .filter_plan(&collection_ids, &predicates, &bounds)
             .await
         {
-            Ok(items) => {
+            Ok((sql_filters, sqlite_plan)) => {
+                plan.sql_filters = sql_filters;
+                plan.sqlite_plan = sqlite_plan;
+            }
+            Err(e) => plan.sqlite_plan = vec![format!("Unavailable: {}", e)],
+        }
+        if filter.is_some() {
+            plan.memory_filters.push("filter".to_string());
+        }
+        if intersects.is_some() {
+            plan.memory_filters.push("intersects".to_string());
+        }
+        plan.sortby = query.sortby.clone();
+    }
+
+    let relations = state
+        .db_service
+        .item_links
+        .outgoing_for_collections(&collection_ids)
+        .await
+        .unwrap_or_default();
+    let computed = state
+        .db_service
+        .computed_properties
+        .by_collection()
+        .await
+        .unwrap_or_default();
+    let now = Utc::now();
+
+    let matches_filters = |item: &crate::models::Item| {
+        intersects.as_ref().is_none_or(|geometry| {
+            item.geometry
+                .as_ref()
+                .is_some_and(|item_geometry| geometries_intersect(item_geometry, geometry))
+        }) && filter
+            .as_ref()
+            .is_none_or(|expr| expr.matches(&json!(item)))
+    };
+
+    // Without in-memory filters SQLite sorts and pages the matches, so only
+    // the page is read
+    if let Some(sortby) = sortby.as_ref().filter(|_| filter.is_none() && intersects.is_none()) {
+        let started = Instant::now();
+        let items = &state.db_service.items;
+        let total = match items.count_matching(&collection_ids, &predicates, &bounds).await {
+            Ok(count) => count.max(0) as usize,
+            Err(_) => return Err(ApiError::internal("Failed to count items").into_response()),
+        };
+        if let Some(plan) = plan.as_deref_mut() {
+            plan.stage("count_estimate", total, started);
+        }
+        let page_size = query.limit.map_or(total, |l| l.max(0) as usize);
+        let page_size = max_results.map_or(page_size, |max| page_size.min(max));
+
+        let started = Instant::now();
+        let db_items = match items
+            .page_sorted(
+                &collection_ids,
+                &predicates,
+                &bounds,
+                sortby,
+                offset as i64,
+                page_size as i64,
+            )
+            .await
+        {
+            Ok(db_items) => db_items,
+            Err(_) => return Err(ApiError::internal("Failed to retrieve items").into_response()),
+        };
+        if let Some(plan) = plan {
+            plan.stage("sql_sort", db_items.len(), started);
+        }
+        let items: Vec<_> = db_items
+            .into_iter()
+            .map(|mut db_item| {
+                if let Some(definitions) = computed.get(&db_item.collection_id) {
+                    computed_properties::apply(definitions, &mut db_item, now);
+                }
+                let related = relations
+                    .get(&db_item.collection_id)
+                    .and_then(|r| r.get(&db_item.id))
+                    .map(Vec::as_slice)
+                    .unwrap_or(&[]);
+                db_item.to_stac_item_with_relations(&server_config, related)
+            })
+            .collect();
+        let next_token = (offset + items.len() < total)
+            .then(|| PageToken::Offset(offset + items.len()).encode());
+        let prev_token =
+            (offset > 0).then(|| PageToken::Offset(offset.saturating_sub(page_size)).encode());
+        return Ok(SearchResults {
+            items,
+            next_token,
+            prev_token,
+            number_matched: Some(total),
+            limit: Some(page_size),
+        });
+    }
 
-                let stac_items: Vec<_> = items
-                    .iter()
-                    .map(|db_item| db_item.to_stac_item(&server_config))
-                    .collect();
-                all_items.extend(stac_items);
+    // Estimate the result size from the SQL filters before materializing
+    // anything, so a broad search can't freeze the app
+    let mut limit = query.limit.map(|l| l.max(0) as usize);
+    let mut number_matched = None;
+    if let Some(max_results) = max_results {
+        if limit.is_none_or(|l| l > max_results) {
+            let started = Instant::now();
+            let estimate = match state
+                .db_service
+                .items
+                .count_matching(&collection_ids, &predicates, &bounds)
+                .await
+            {
+                Ok(count) => count.max(0) as usize,
+                Err(_) => return Err(ApiError::internal("Failed to count items").into_response()),
+            };
+            if let Some(plan) = plan.as_deref_mut() {
+                plan.stage("count_estimate", estimate, started);
             }
-            Err(e) => {
-                // Continue with other collections if one fails
-                continue;
+            if filter.is_none() && intersects.is_none() {
+                number_matched = Some(estimate);
+            }
+            if estimate > max_results {
+                if sortby.is_some() {
+                    return Err(ApiError::bad_request(format!(
+                        "The search matches up to {} items, more than the {} that can be sorted at once. \
+                         Narrow it with collections, bbox, datetime or query, drop sortby to page \
+                         through the results, or export them with POST /search/export.",
+                        estimate, max_results
+                    ))
+                    .into_response());
+                }
+                // Unsorted results are streamed in keyset pages instead
+                limit = Some(max_results);
             }
         }
     }
-    
 
+    // Scan in keyset order until one item past the page is found; a sorted
+    // search needs every match before it can be sorted
+    let page_limit = if sortby.is_none() { limit } else { None };
+    let mut matched: Vec<(ItemCursor, crate::models::Item)> = Vec::new();
+    let mut cursor = cursor;
+    let (mut scanned, mut scan_time, mut filter_time) = (0, Duration::ZERO, Duration::ZERO);
+    loop {
+        let started = Instant::now();
+        let items = &state.db_service.items;
+        let scan = match cursor.as_ref().filter(|_| backward) {
+            Some(before) => {
+                items
+                    .page_before(
+                        &collection_ids,
+                        &predicates,
+                        &bounds,
+                        before,
+                        SEARCH_SCAN_BATCH,
+                    )
+                    .await
+            }
+            None => {
+                items
+                    .page_after(
+                        &collection_ids,
+                        &predicates,
+                        &bounds,
+                        cursor.as_ref(),
+                        SEARCH_SCAN_BATCH,
+                    )
+                    .await
+            }
+        };
+        let batch = match scan {
+            Ok(batch) => batch,
+            Err(_) => return Err(ApiError::internal("Failed to retrieve items").into_response()),
+        };
+        let exhausted = (batch.len() as i64) < SEARCH_SCAN_BATCH;
+        if let Some((last, _)) = batch.last() {
+            cursor = Some(last.clone());
+        }
+        scanned += batch.len();
+        scan_time += started.elapsed();
 
-    // Apply filters
-    let filtered_items = if let Some(bbox_str) = &query.bbox {
-        let filtered = filter_items_by_bbox(&all_items, bbox_str);
-        filtered
-    } else {
-
-        all_items
-    };
+        let started = Instant::now();
+        for (item_cursor, mut db_item) in batch {
+            if let Some(definitions) = computed.get(&db_item.collection_id) {
+                computed_properties::apply(definitions, &mut db_item, now);
+            }
+            let related = relations
+                .get(&db_item.collection_id)
+                .and_then(|r| r.get(&db_item.id))
+                .map(Vec::as_slice)
+                .unwrap_or(&[]);
+            let item = db_item.to_stac_item_with_relations(&server_config, related);
+            if matches_filters(&item) {
+                matched.push((item_cursor, item));
+            }
+        }
+        filter_time += started.elapsed();
 
-    let filtered_items = if let Some(datetime_str) = &query.datetime {
-        let filtered = filter_items_by_datetime(&filtered_items, datetime_str);
-        filtered
-    } else {
+        if exhausted || page_limit.is_some_and(|l| matched.len() > l) {
+            break;
+        }
+    }
 
-        filtered_items
-    };
+    if let Some(plan) = plan.as_deref_mut() {
+        plan.stages.push(PlanStage {
+            stage: "sql_scan",
+            rows: scanned,
+            ms: duration_ms(scan_time),
+        });
+        plan.stages.push(PlanStage {
+            stage: "memory_filter",
+            rows: matched.len(),
+            ms: duration_ms(filter_time),
+        });
+    }
 
-    // Apply sorting
-    let sorted_items = if let Some(sortby_str) = &query.sortby {
-        match parse_sortby(sortby_str) {
-            Ok(sortby) => sort_items(filtered_items, &sortby),
-            Err(_) => filtered_items,
+    if let Some(sortby) = sortby {
+        let started = Instant::now();
+        let items: Vec<_> = matched.into_iter().map(|(_, item)| item).collect();
+        let items = sort_items(items, &sortby);
+        if let Some(plan) = plan {
+            plan.stage("sort", items.len(), started);
         }
+        let total = items.len();
+        let page_size = limit.unwrap_or(total);
+        let items: Vec<_> = items.into_iter().skip(offset).take(page_size).collect();
+        let next_token = (offset + items.len() < total)
+            .then(|| PageToken::Offset(offset + items.len()).encode());
+        let prev_token =
+            (offset > 0).then(|| PageToken::Offset(offset.saturating_sub(page_size)).encode());
+        return Ok(SearchResults {
+            items,
+            next_token,
+            prev_token,
+            number_matched: Some(total),
+            limit,
+        });
+    }
+
+    // Pages start after the last or before the first returned item, so items
+    // ingested meanwhile never shift or repeat pages
+    let more = limit.is_some_and(|limit| matched.len() > limit);
+    if let Some(limit) = limit {
+        matched.truncate(limit);
+    }
+    let (next_token, prev_token) = if backward {
+        // Scanned backwards, closest item first
+        matched.reverse();
+        (
+            matched.last().map(|(c, _)| PageToken::After(c.clone()).encode()),
+            more.then(|| matched.first())
+                .flatten()
+                .map(|(c, _)| PageToken::Before(c.clone()).encode()),
+        )
     } else {
-        filtered_items
+        (
+            more.then(|| matched.last())
+                .flatten()
+                .map(|(c, _)| PageToken::After(c.clone()).encode()),
+            paged
+                .then(|| matched.first())
+                .flatten()
+                .map(|(c, _)| PageToken::Before(c.clone()).encode()),
+        )
     };
 
-    // Apply limit to final results
-    let final_items = if let Some(limit) = query.limit {
-        let limit = limit as usize;
-        if limit < sorted_items.len() {
-            let limited = sorted_items[..limit].to_vec();
-            limited
-        } else {
-            sorted_items
+    if number_matched.is_none() && filter.is_none() && intersects.is_none() {
+        number_matched = state
+            .db_service
+            .items
+            .count_matching(&collection_ids, &predicates, &bounds)
+            .await
+            .ok()
+            .map(|count| count.max(0) as usize);
+    }
+
+    Ok(SearchResults {
+        items: matched.into_iter().map(|(_, item)| item).collect(),
+        next_token,
+        prev_token,
+        number_matched,
+        limit,
+    })
+}
+
+/// Bounds of the `bbox` and `datetime` parameters, matched in SQL against
+/// the stored item extents. A bbox without four numbers is ignored.
+fn query_bounds(bbox: Option<&str>, datetime: Option<&str>) -> Result<ItemBounds, Response> {
+    let mut bounds = ItemBounds::default();
+    if let Some(datetime_str) = datetime {
+        match parse_datetime_interval(datetime_str) {
+            Ok(interval) => bounds.datetime = Some(interval),
+            Err(e) => {
+                return Err(
+                    ApiError::bad_request(format!("Invalid datetime: {}", e)).into_response()
+                )
+            }
         }
-    } else {
+    }
+    if let Some(bbox_str) = bbox {
+        bounds.bbox = Some(parse_bbox(bbox_str).map_err(IntoResponse::into_response)?);
+    }
+    Ok(bounds)
+}
 
-        sorted_items
-    };
-    
+/// Two-dimensional extent of a `bbox` parameter of 4 or 6 comma-separated
+/// numbers; the heights of a 3D box are dropped
+fn parse_bbox(bbox: &str) -> Result<[f64; 4], ApiError> {
+    let numbers: Result<Vec<f64>, _> = bbox.split(',').map(|s| s.trim().parse::<f64>()).collect();
+    match numbers.as_deref() {
+        Ok(&[min_x, min_y, max_x, max_y]) | Ok(&[min_x, min_y, _, max_x, max_y, _]) => {
+            Ok([min_x, min_y, max_x, max_y])
+        }
+        _ => Err(ApiError::invalid_parameter(
+            "Invalid bbox: expected 4 or 6 comma-separated numbers",
+        )),
+    }
+}
 
+/// Items page of a collection starting at `offset`, with the query's other
+/// parameters
+fn items_page_href(
+    server_config: &ServerConfig,
+    collection_id: &str,
+    query: &OGCFeaturesQuery,
+    offset: usize,
+) -> String {
+    let mut params = vec![format!("offset={}", offset)];
+    if let Some(limit) = query.limit {
+        params.push(format!("limit={}", limit));
+    }
+    let filters = [
+        ("bbox", &query.bbox),
+        ("datetime", &query.datetime),
+        ("sortby", &query.sortby),
+        ("filter", &query.filter),
+        ("filter-lang", &query.filter_lang),
+    ];
+    for (name, value) in filters {
+        if let Some(value) = value {
+            params.push(format!("{}={}", name, urlencoding::encode(value)));
+        }
+    }
+    if query.compact == Some(true) {
+        params.push("compact=true".to_string());
+    }
+    format!(
+        "{}?{}",
+        server_config.collection_items_href(collection_id),
+        params.join("&")
+    )
+}
 
-    let response = serde_json::json!({
+/// GET form of the search continuing at `token`, for `next` and `prev` links
+pub(crate) fn page_search_href(
+    server_config: &ServerConfig,
+    query: &SearchQuery,
+    token: &str,
+) -> String {
+    let mut params = Vec::new();
+    if let Some(limit) = query.limit {
+        params.push(format!("limit={}", limit));
+    }
+    let filters = [
+        ("bbox", &query.bbox),
+        ("datetime", &query.datetime),
+        ("intersects", &query.intersects),
+        ("ids", &query.ids),
+        ("collections", &query.collections),
+        ("query", &query.query),
+        ("q", &query.q),
+        ("filter", &query.filter),
+        ("filter-lang", &query.filter_lang),
+        ("sortby", &query.sortby),
+    ];
+    for (name, value) in filters {
+        if let Some(value) = value {
+            params.push(format!("{}={}", name, urlencoding::encode(value)));
+        }
+    }
+    if query.compact == Some(true) {
+        params.push("compact=true".to_string());
+    }
+    if query.federated == Some(false) {
+        params.push("federated=false".to_string());
+    }
+    params.push(format!("token={}", token));
+    format!("{}?{}", server_config.search_href(), params.join("&"))
+}
+
+async fn search_items(query: SearchQuery, state: AppState, request_headers: &HeaderMap) -> Response {
+    // Remote catalogs configured for federation are searched alongside
+    if let Some(catalogs) = federation::catalogs_for(&state, &query, request_headers).await {
+        return federation::federated_search(query, &state, catalogs).await;
+    }
+    let server_config = ServerConfig::from_config(&state.config);
+
+    let results = match find_items(&query, &state).await {
+        Ok(results) => results,
+        Err(response) => return response,
+    };
+    let mut final_items = results.items;
+    add_item_access_stats(&state.db_service, &server_config, &mut final_items).await;
+
+    let mut links = vec![serde_json::json!({
+        "href": server_config.search_href(),
+        "rel": "self",
+        "type": "application/geo+json"
+    })];
+    for (rel, token) in [("next", &results.next_token), ("prev", &results.prev_token)] {
+        if let Some(token) = token {
+            links.push(serde_json::json!({
+                "href": page_search_href(&server_config, &query, token),
+                "rel": rel,
+                "type": "application/geo+json",
+                "method": "GET"
+            }));
+        }
+    }
+
+    let number_returned = final_items.len();
+    let mut response = serde_json::json!({
         "type": "FeatureCollection",
         "features": final_items,
-        "links": [
-            {
-                "href": server_config.search_href(),
-                "rel": "self",
-                "type": "application/geo+json"
-            }
-        ]
+        "links": links
     });
+    add_item_counts(&mut response, number_returned, results.limit, results.number_matched);
+
+    // Union of the returned items' extents, used by map clients to zoom to results
+    if let Some(bbox) = calculate_bbox_for_items(&final_items) {
+        response["bbox"] = serde_json::json!(bbox);
+    }
+    if query.compact == Some(true) {
+        compact_feature_collection(&mut response);
+    }
 
     let mut headers = HeaderMap::new();
     headers.insert(
@@ -733,188 +1855,54 @@ async fn search_items(query: SearchQuery, state: AppState) -> Response {
 
 pub async fn delete_collection(
     Path(collection_id): Path<String>,
+    Query(delete_query): Query<DeleteQuery>,
     State(state): State<AppState>,
+    request_headers: HeaderMap,
 ) -> Response {
+    // Virtual collections are read-only
+    if virtual_collections::is_virtual(&state.db_service, &collection_id).await {
+        return virtual_collections::read_only_response(&collection_id);
+    }
+
     // Check if collection exists first
-    let _db_collection = match state.db_service.collections.get_by_id(&collection_id).await {
-        Ok(Some(_)) => (), // Collection exists, proceed with deletion
+    let existing_collection = match state.db_service.collections.get_by_id(&collection_id).await {
+        Ok(Some(collection)) => collection,
         Ok(None) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Collection '{}' not found", collection_id)
-            });
-
-            return (
-                axum::http::StatusCode::NOT_FOUND,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
-        }
-        Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to check collection existence"
-            });
-
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
+            return ApiError::not_found(format!("Collection '{}' not found", collection_id))
                 .into_response();
         }
-    };
-
-    // Store collection_id for cleanup after database deletion
-    let collection_id_for_cleanup = collection_id.clone();
-
-    // Delete the collection
-    match state.db_service.collections.delete(&collection_id).await {
-        Ok(_) => {
-            // Now trigger async cleanup AFTER the database deletion is complete
-            tokio::spawn(async move {
-                // Wait a bit to ensure database operations are fully complete
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-                let collection_assets_dir = format!("{}/{}", state.config.assets_dir(), collection_id_for_cleanup);
-                let collection_assets_path = std::path::Path::new(&collection_assets_dir);
-
-                if collection_assets_path.exists() {
-                
-
-                
-
-
-                    // Try to remove the directory with retries and delays
-                    let mut attempts = 0;
-                    const MAX_ATTEMPTS: u32 = 5;
-
-                    while attempts < MAX_ATTEMPTS {
-                        attempts += 1;
-
-
-                        match std::fs::remove_dir_all(&collection_assets_dir) {
-                            Ok(_) => {
-                            
-
-                                // Verify it's actually gone
-                                if collection_assets_path.exists() {
-                                
-                                    if attempts < MAX_ATTEMPTS {
-
-                                        tokio::time::sleep(tokio::time::Duration::from_millis(500))
-                                            .await;
-                                        continue;
-                                    }
-                                } else {
-
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Delete collection handler: Attempt {} failed to remove collection assets directory {}: {}", attempts, collection_assets_dir, e);
-
-                                if attempts < MAX_ATTEMPTS {
-
-                                    tokio::time::sleep(tokio::time::Duration::from_millis(1000))
-                                        .await;
-
-                                    // Try alternative approach - remove contents first, then directory
-
-                                    if let Ok(entries) = std::fs::read_dir(&collection_assets_dir) {
-                                        for entry in entries {
-                                            if let Ok(entry) = entry {
-                                                let path = entry.path();
-                                                if path.is_dir() {
-                                                    if let Err(e) = std::fs::remove_dir_all(&path) {
-                                                        eprintln!("Delete collection handler: Failed to remove subdirectory {:?}: {}", path, e);
-                                                    } else {
-
-                                                    }
-                                                } else {
-                                                    if let Err(e) = std::fs::remove_file(&path) {
-                                                        eprintln!("Delete collection handler: Failed to remove file {:?}: {}", path, e);
-                                                    } else {
-
-                                                    }
-                                                }
-                                            }
-                                        }
-
-                                        // Wait a bit more before trying to remove the empty directory
-                                        tokio::time::sleep(tokio::time::Duration::from_millis(500))
-                                            .await;
-                                    }
-                                } else {
-                                
-                                }
-                            }
-                        }
-                    }
-                } else {
-
-                }
-            });
-
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let success_response = serde_json::json!({
-                "message": format!("Collection '{}' deleted successfully. Asset cleanup started in background.", collection_id)
-            });
-
-            (
-                axum::http::StatusCode::OK,
-                headers,
-                serde_json::to_string(&success_response).unwrap(),
-            )
-                .into_response()
-        }
-        Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
+        Err(_) => {
+            return ApiError::internal("Failed to check collection existence").into_response();
+        }
+    };
 
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to delete collection"
-            });
+    // An If-Match, when sent, must name the current version
+    let validators = collection_validators(&state, &existing_collection, &request_headers).await;
+    if let Err(response) = validators.check_if_match(&request_headers, false) {
+        return response;
+    }
 
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+    // Large asset purges need an explicit confirm flag
+    if delete_query.confirm != Some(true) {
+        let report = cleanup_report(&state.db_service, &state.config, &collection_id, None).await;
+        if report.requires_confirmation {
+            return confirmation_required_response(&report);
         }
     }
+
+    // Move the collection and its items to the trash; their assets stay in
+    // place until it is purged
+    match state.db_service.collections.delete(&collection_id).await {
+        Ok(_) => trashed_response(&ServerConfig::from_config(&state.config), &collection_id, None),
+        Err(_) => ApiError::internal("Failed to delete collection").into_response(),
+    }
 }
 
 pub async fn delete_item(
     Path((collection_id, item_id)): Path<(String, String)>,
+    Query(delete_query): Query<DeleteQuery>,
     State(state): State<AppState>,
+    request_headers: HeaderMap,
 ) -> Response {
     // Check if item exists first
     let db_item = match state
@@ -923,138 +1911,61 @@ pub async fn delete_item(
         .get_by_id(&collection_id, &item_id)
         .await
     {
-        Ok(Some(_)) => (), // Item exists, proceed with deletion
+        Ok(Some(item)) => item,
         Ok(None) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Item '{}' not found in collection '{}'", item_id, collection_id)
-            });
-
-            return (
-                axum::http::StatusCode::NOT_FOUND,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::not_found(format!(
+                "Item '{}' not found in collection '{}'",
+                item_id, collection_id
+            ))
+            .into_response();
         }
         Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
+            return ApiError::internal("Failed to check item existence").into_response();
+        }
+    };
 
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to check item existence"
-            });
+    // An If-Match, when sent, must name the current version
+    let validators = item_validators(&state, &db_item, &request_headers).await;
+    if let Err(response) = validators.check_if_match(&request_headers, false) {
+        return response;
+    }
 
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+    // Large asset purges need an explicit confirm flag
+    if delete_query.confirm != Some(true) {
+        let report = cleanup_report(
+            &state.db_service,
+            &state.config,
+            &collection_id,
+            Some(&item_id),
+        )
+        .await;
+        if report.requires_confirmation {
+            return confirmation_required_response(&report);
         }
-    };
+    }
 
-    // Delete the item from database
+    // Move the item to the trash; its assets stay in place until it is purged
     match state
         .db_service
         .items
         .delete(&collection_id, &item_id)
         .await
     {
-        Ok(_) => {
-            // Trigger async cleanup of asset files
-            let collection_id_clone = collection_id.clone();
-            let item_id_clone = item_id.clone();
-            tokio::spawn(async move {
-                let config = crate::config::Config::default();
-                let assets_dir = format!("{}/{}/{}", config.assets_dir(), collection_id_clone, item_id_clone);
-                let assets_path = std::path::Path::new(&assets_dir);
-
-                if assets_path.exists() {
-
-
-                    // Remove all files in the assets directory
-                    if let Err(e) = std::fs::remove_dir_all(&assets_dir) {
-                        eprintln!(
-                            "Delete handler: Failed to remove assets directory {}: {}",
-                            assets_dir, e
-                        );
-                    } else {
-
-                    }
-
-                    // Try to remove the parent directory if it's empty
-                    let parent_dir = format!("{}/{}", config.assets_dir(), collection_id_clone);
-                    if let Ok(entries) = std::fs::read_dir(&parent_dir) {
-                        if entries.count() == 0 {
-                            if let Err(e) = std::fs::remove_dir(&parent_dir) {
-                                eprintln!("Delete handler: Failed to remove empty parent directory {}: {}", parent_dir, e);
-                            } else {
-
-                            }
-                        }
-                    }
-                } else {
-
-                }
-            });
-
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let success_response = serde_json::json!({
-                "message": format!("Item '{}' deleted successfully from collection '{}'. Asset cleanup started in background.", item_id, collection_id)
-            });
-
-            (
-                axum::http::StatusCode::OK,
-                headers,
-                serde_json::to_string(&success_response).unwrap(),
-            )
-                .into_response()
-        }
-        Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to delete item"
-            });
-
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
-        }
+        Ok(_) => trashed_response(
+            &ServerConfig::from_config(&state.config),
+            &collection_id,
+            Some(&item_id),
+        ),
+        Err(_) => ApiError::internal("Failed to delete item").into_response(),
     }
 }
 
 pub async fn put_item(
     Path((collection_id, item_id)): Path<(String, String)>,
+    Query(validate_query): Query<ValidateQuery>,
     State(state): State<AppState>,
+    request_headers: HeaderMap,
+    internal: Option<Extension<InternalRequest>>,
     Json(item_data): Json<serde_json::Value>,
 ) -> Response {
     let server_config = ServerConfig::from_config(&state.config);
@@ -1063,44 +1974,11 @@ pub async fn put_item(
     let _db_collection = match state.db_service.collections.get_by_id(&collection_id).await {
         Ok(Some(_)) => (), // Collection exists, proceed with update
         Ok(None) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Collection '{}' not found", collection_id)
-            });
-
-            return (
-                axum::http::StatusCode::NOT_FOUND,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
+            return ApiError::not_found(format!("Collection '{}' not found", collection_id))
                 .into_response();
         }
         Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to check collection existence"
-            });
-
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::internal("Failed to check collection existence").into_response();
         }
     };
 
@@ -1111,47 +1989,38 @@ pub async fn put_item(
         .get_by_id(&collection_id, &item_id)
         .await
     {
-        Ok(Some(_)) => (), // Item exists, proceed with update
+        Ok(Some(item)) => item,
         Ok(None) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Item '{}' not found in collection '{}'", item_id, collection_id)
-            });
-
-            return (
-                axum::http::StatusCode::NOT_FOUND,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::not_found(format!(
+                "Item '{}' not found in collection '{}'",
+                item_id, collection_id
+            ))
+            .into_response();
         }
         Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
+            return ApiError::internal("Failed to check item existence").into_response();
+        }
+    };
 
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to check item existence"
-            });
+    // Network clients must name the version they replace; the desktop UI
+    // may, but is not required to
+    let validators = item_validators(&state, &existing_item, &request_headers).await;
+    if let Err(response) = validators.check_if_match(&request_headers, internal.is_none()) {
+        return response;
+    }
 
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
-        }
+    // Ingest hook, ingest mode, STAC checks and the collection's item schema
+    let (item_data, warnings) = match prepare_item(
+        &state.db_service,
+        &collection_id,
+        IngestEvent::Update,
+        item_data,
+        validate_query.enabled(),
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(rejection) => return rejection.into_response(),
     };
 
     // Parse the item data and create a DbItem
@@ -1159,45 +2028,17 @@ pub async fn put_item(
         Ok(stac_item) => {
             // Validate that the item ID and collection match the path parameters
             if stac_item.id != item_id {
-                let mut headers = HeaderMap::new();
-                headers.insert(
-                    "Content-Type",
-                    HeaderValue::from_static("application/json; charset=utf-8"),
-                );
-                headers = add_cors_headers(headers);
-
-                let error_response = serde_json::json!({
-                    "code": "BadRequest",
-                    "description": "Item ID in request body does not match path parameter"
-                });
-
-                return (
-                    axum::http::StatusCode::BAD_REQUEST,
-                    headers,
-                    serde_json::to_string(&error_response).unwrap(),
+                return ApiError::bad_request(
+                    "Item ID in request body does not match path parameter",
                 )
-                    .into_response();
+                .into_response();
             }
 
             if stac_item.collection != Some(collection_id.clone()) {
-                let mut headers = HeaderMap::new();
-                headers.insert(
-                    "Content-Type",
-                    HeaderValue::from_static("application/json; charset=utf-8"),
-                );
-                headers = add_cors_headers(headers);
-
-                let error_response = serde_json::json!({
-                    "code": "BadRequest",
-                    "description": "Collection ID in request body does not match path parameter"
-                });
-
-                return (
-                    axum::http::StatusCode::BAD_REQUEST,
-                    headers,
-                    serde_json::to_string(&error_response).unwrap(),
+                return ApiError::bad_request(
+                    "Collection ID in request body does not match path parameter",
                 )
-                    .into_response();
+                .into_response();
             }
 
             // Compute bbox from geometry if possible
@@ -1273,24 +2114,7 @@ pub async fn put_item(
             db_item
         }
         Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "BadRequest",
-                "description": "Invalid item data format"
-            });
-
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::bad_request("Invalid item data format").into_response();
         }
     };
 
@@ -1303,89 +2127,67 @@ pub async fn put_item(
                 HeaderValue::from_static("application/geo+json; charset=utf-8"),
             );
             headers = add_cors_headers(headers);
+            // The new version's ETag, for the client's next conditional write
+            let updated = state.db_service.items.get_by_id(&collection_id, &item_id).await;
+            if let Ok(Some(updated)) = updated {
+                item_validators(&state, &updated, &request_headers).await.add_headers(&mut headers);
+            }
 
             // Return the updated item
             (
                 axum::http::StatusCode::OK,
                 headers,
-                serde_json::to_string(&item_data).unwrap(),
+                serde_json::to_string(&with_warnings(item_data, &warnings)).unwrap(),
             )
                 .into_response()
         }
         Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to update item"
-            });
-
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::internal("Failed to update item").into_response();
         }
     }
 }
 
 pub async fn put_collection(
     Path(collection_id): Path<String>,
+    Query(validate_query): Query<ValidateQuery>,
     State(state): State<AppState>,
+    request_headers: HeaderMap,
+    internal: Option<Extension<InternalRequest>>,
     Json(collection_data): Json<serde_json::Value>,
 ) -> Response {
+    // Virtual collections are read-only
+    if virtual_collections::is_virtual(&state.db_service, &collection_id).await {
+        return virtual_collections::read_only_response(&collection_id);
+    }
+
     let server_config = ServerConfig::from_config(&state.config);
 
     // Check if collection exists first
-    let _db_collection = match state.db_service.collections.get_by_id(&collection_id).await {
-        Ok(Some(_)) => (), // Collection exists, proceed with update
+    let existing_collection = match state.db_service.collections.get_by_id(&collection_id).await {
+        Ok(Some(collection)) => collection,
         Ok(None) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Collection '{}' not found", collection_id)
-            });
-
-            return (
-                axum::http::StatusCode::NOT_FOUND,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
+            return ApiError::not_found(format!("Collection '{}' not found", collection_id))
                 .into_response();
         }
         Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
+            return ApiError::internal("Failed to check collection existence").into_response();
+        }
+    };
 
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to check collection existence"
-            });
+    // Network clients must name the version they replace; the desktop UI
+    // may, but is not required to
+    let validators = collection_validators(&state, &existing_collection, &request_headers).await;
+    if let Err(response) = validators.check_if_match(&request_headers, internal.is_none()) {
+        return response;
+    }
 
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+    // Check the STAC rules unless the client opted out
+    if validate_query.enabled() {
+        let violations = validate_stac_collection(&collection_data);
+        if !violations.is_empty() {
+            return stac_violation_response("Collection", &violations);
         }
-    };
+    }
 
     // Parse the collection data and create a DbCollection
     let db_collection = match serde_json::from_value::<crate::models::collection::Collection>(
@@ -1394,24 +2196,10 @@ pub async fn put_collection(
         Ok(stac_collection) => {
             // Validate that the collection ID matches the path parameter
             if stac_collection.id != collection_id {
-                let mut headers = HeaderMap::new();
-                headers.insert(
-                    "Content-Type",
-                    HeaderValue::from_static("application/json; charset=utf-8"),
-                );
-                headers = add_cors_headers(headers);
-
-                let error_response = serde_json::json!({
-                    "code": "BadRequest",
-                    "description": "Collection ID in request body does not match path parameter"
-                });
-
-                return (
-                    axum::http::StatusCode::BAD_REQUEST,
-                    headers,
-                    serde_json::to_string(&error_response).unwrap(),
+                return ApiError::bad_request(
+                    "Collection ID in request body does not match path parameter",
                 )
-                    .into_response();
+                .into_response();
             }
 
             // Convert STAC collection to DbCollection
@@ -1449,24 +2237,7 @@ pub async fn put_collection(
             db_collection
         }
         Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "BadRequest",
-                "description": "Invalid collection data format"
-            });
-
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::bad_request("Invalid collection data format").into_response();
         }
     };
 
@@ -1479,41 +2250,121 @@ pub async fn put_collection(
                 HeaderValue::from_static("application/json; charset=utf-8"),
             );
             headers = add_cors_headers(headers);
+            // The new version's ETag, for the client's next conditional write
+            let updated = state.db_service.collections.get_by_id(&collection_id).await;
+            if let Ok(Some(updated)) = updated {
+                collection_validators(&state, &updated, &request_headers).await.add_headers(&mut headers);
+            }
+
+            // Return the updated collection
+            (
+                axum::http::StatusCode::OK,
+                headers,
+                serde_json::to_string(&collection_data).unwrap(),
+            )
+                .into_response()
+        }
+        Err(_) => {
+            return ApiError::internal("Failed to update collection").into_response();
+        }
+    }
+}
+
+/// Handler to update an item with a JSON Merge Patch
+/// (PATCH /collections/:collection_id/items/:item_id). The patch is applied
+/// to the stored item and the result goes through the same checks as a PUT.
+pub async fn patch_item(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    validate_query: Query<ValidateQuery>,
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    internal: Option<Extension<InternalRequest>>,
+    Json(patch): Json<serde_json::Value>,
+) -> Response {
+    if !patch.is_object() {
+        return ApiError::bad_request("The patch must be a JSON object").into_response();
+    }
+
+    let existing = match state.db_service.items.get_by_id(&collection_id, &item_id).await {
+        Ok(Some(item)) => item,
+        Ok(None) => {
+            return ApiError::not_found(format!(
+                "Item '{}' not found in collection '{}'",
+                item_id, collection_id
+            ))
+            .into_response()
+        }
+        Err(_) => return ApiError::internal("Failed to load item").into_response(),
+    };
 
-            // Return the updated collection
-            (
-                axum::http::StatusCode::OK,
-                headers,
-                serde_json::to_string(&collection_data).unwrap(),
-            )
-                .into_response()
+    // Patch the stored document, not the served one, so generated links are
+    // not written back
+    let server_config = ServerConfig::from_config(&state.config);
+    let stored_links = existing.links.clone().unwrap_or_else(|| json!([]));
+    let mut document = existing.into_stac_item_json(&server_config, &[]);
+    document["links"] = stored_links;
+    if patch.get("geometry").is_some() && patch.get("bbox").is_none() {
+        if let Some(document) = document.as_object_mut() {
+            document.remove("bbox");
         }
-        Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
+    }
+    bulk_update::merge_patch(&mut document, &patch);
+
+    put_item(
+        Path((collection_id, item_id)),
+        validate_query,
+        State(state),
+        request_headers,
+        internal,
+        Json(document),
+    )
+    .await
+}
 
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to update collection"
-            });
+/// Handler to update a collection with a JSON Merge Patch
+/// (PATCH /collections/:collection_id)
+pub async fn patch_collection(
+    Path(collection_id): Path<String>,
+    validate_query: Query<ValidateQuery>,
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    internal: Option<Extension<InternalRequest>>,
+    Json(patch): Json<serde_json::Value>,
+) -> Response {
+    if !patch.is_object() {
+        return ApiError::bad_request("The patch must be a JSON object").into_response();
+    }
 
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+    let existing = match state.db_service.collections.get_by_id(&collection_id).await {
+        Ok(Some(collection)) => collection,
+        Ok(None) => {
+            return ApiError::not_found(format!("Collection '{}' not found", collection_id))
+                .into_response()
         }
-    }
+        Err(_) => return ApiError::internal("Failed to load collection").into_response(),
+    };
+
+    let server_config = ServerConfig::from_config(&state.config);
+    let mut document = json!(existing.to_stac_collection(&server_config));
+    document["links"] = json!([]);
+    bulk_update::merge_patch(&mut document, &patch);
+
+    put_collection(
+        Path(collection_id),
+        validate_query,
+        State(state),
+        request_headers,
+        internal,
+        Json(document),
+    )
+    .await
 }
 
 /// Handler to create a new collection (POST /collections)
 pub async fn create_collection(
+    Query(validate_query): Query<ValidateQuery>,
     State(state): State<AppState>,
+    request_headers: HeaderMap,
     Json(payload): Json<serde_json::Value>,
 ) -> Response {
     let mut headers = HeaderMap::new();
@@ -1523,22 +2374,35 @@ pub async fn create_collection(
     );
     headers = add_cors_headers(headers);
 
-    // Extract required fields
-    let id = match payload.get("id").and_then(|v| v.as_str()) {
-        Some(s) => s.to_string(),
-        None => {
-            let error_response = json!({
-                "code": "BadRequest",
-                "description": "Missing required field: id"
-            });
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+    // Use the client's id or generate one with the configured strategy
+    let id = match resolve_id(&state.db_service, &payload, IdScope::Collection).await {
+        Ok(id) => id,
+        Err(e) => {
+            return ApiError::bad_request(e).into_response();
         }
     };
+
+    // Virtual collections share the id space of stored ones
+    if virtual_collections::is_virtual(&state.db_service, &id).await {
+        return ApiError::new(
+            StatusCode::CONFLICT,
+            "Conflict",
+            format!("A virtual collection with id '{}' already exists", id),
+        )
+        .into_response();
+    }
+
+    // Check the STAC rules unless the client opted out
+    if validate_query.enabled() {
+        let mut document = payload.clone();
+        if let Some(object) = document.as_object_mut() {
+            object.insert("id".to_string(), serde_json::Value::String(id.clone()));
+        }
+        let violations = validate_stac_collection(&document);
+        if !violations.is_empty() {
+            return stac_violation_response("Collection", &violations);
+        }
+    }
     let title = payload
         .get("title")
         .and_then(|v| v.as_str())
@@ -1546,31 +2410,13 @@ pub async fn create_collection(
     let description = match payload.get("description").and_then(|v| v.as_str()) {
         Some(s) => s.to_string(),
         None => {
-            let error_response = json!({
-                "code": "BadRequest",
-                "description": "Missing required field: description"
-            });
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::bad_request("Missing required field: description").into_response();
         }
     };
     let license = match payload.get("license").and_then(|v| v.as_str()) {
         Some(s) => s.to_string(),
         None => {
-            let error_response = json!({
-                "code": "BadRequest",
-                "description": "Missing required field: license"
-            });
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::bad_request("Missing required field: license").into_response();
         }
     };
 
@@ -1602,33 +2448,46 @@ pub async fn create_collection(
 
     // Insert into database
     match state.db_service.collections.create(&db_collection).await {
-        Ok(_) => (
-            axum::http::StatusCode::CREATED,
-            headers,
-            serde_json::to_string(&db_collection).unwrap(),
-        )
-            .into_response(),
-        Err(e) => {
-            let error_response = json!({
-                "code": "InternalServerError",
-                "description": format!("Failed to create collection: {}", e)
-            });
+        Ok(_) => {
+            let server_config = ServerConfig::from_config(&state.config);
+            let location = server_config.collection_href(&id);
+            if let Ok(value) = HeaderValue::from_str(&location) {
+                headers.insert("Location", value);
+            }
+            collection_validators(&state, &db_collection, &request_headers)
+                .await
+                .add_headers(&mut headers);
+            // Answer with the collection as GET serves it, not the stored record
             (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                axum::http::StatusCode::CREATED,
                 headers,
-                serde_json::to_string(&error_response).unwrap(),
+                serde_json::to_string(&db_collection.to_stac_collection(&server_config)).unwrap(),
             )
                 .into_response()
         }
+        Err(e) if is_unique_violation(&e) => {
+            let server_config = ServerConfig::from_config(&state.config);
+            trash::id_taken_response(&state.db_service, &server_config, &id, None).await
+        }
+        Err(e) => {
+            ApiError::internal(format!("Failed to create collection: {}", e)).into_response()
+        }
     }
 }
 
 /// Handler to create a new item (POST /collections/:collection_id/items)
 pub async fn create_item(
     Path(collection_id): Path<String>,
+    Query(validate_query): Query<ValidateQuery>,
     State(state): State<AppState>,
+    request_headers: HeaderMap,
     Json(payload): Json<serde_json::Value>,
 ) -> Response {
+    // Virtual collections are read-only
+    if virtual_collections::is_virtual(&state.db_service, &collection_id).await {
+        return virtual_collections::read_only_response(&collection_id);
+    }
+
     let mut headers = HeaderMap::new();
     headers.insert(
         "Content-Type",
@@ -1636,65 +2495,55 @@ pub async fn create_item(
     );
     headers = add_cors_headers(headers);
 
-    // Extract required fields
-    let id = match payload.get("id").and_then(|v| v.as_str()) {
-        Some(s) => s.to_string(),
-        None => {
-            let error_response = serde_json::json!({
-                "code": "BadRequest",
-                "description": "Missing required field: id"
-            });
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+    // Use the client's id or generate one with the configured strategy
+    let id = match resolve_id(
+        &state.db_service,
+        &payload,
+        IdScope::Item {
+            collection_id: &collection_id,
+        },
+    )
+    .await
+    {
+        Ok(id) => id,
+        Err(e) => {
+            return ApiError::bad_request(e).into_response();
         }
     };
+    let mut payload = payload;
+    if let Some(object) = payload.as_object_mut() {
+        object.insert("id".to_string(), serde_json::Value::String(id.clone()));
+    }
+    // Ingest hook, ingest mode, STAC checks and the collection's item schema
+    let (payload, warnings) = match prepare_item(
+        &state.db_service,
+        &collection_id,
+        IngestEvent::Create,
+        payload,
+        validate_query.enabled(),
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(rejection) => return rejection.into_response(),
+    };
+
     let geometry = match payload.get("geometry") {
         Some(g) => g.clone(),
         None => {
-            let error_response = serde_json::json!({
-                "code": "BadRequest",
-                "description": "Missing required field: geometry"
-            });
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::bad_request("Missing required field: geometry").into_response();
         }
     };
     let properties = match payload.get("properties") {
         Some(p) => p.clone(),
         None => {
-            let error_response = serde_json::json!({
-                "code": "BadRequest",
-                "description": "Missing required field: properties"
-            });
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::bad_request("Missing required field: properties").into_response();
         }
     };
     let assets = match payload.get("assets") {
         Some(a) => a.clone(),
         None => {
-            let error_response = serde_json::json!({
-                "code": "BadRequest",
-                "description": "Missing required field: assets"
-            });
-            return (
-                axum::http::StatusCode::BAD_REQUEST,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::bad_request("Missing required field: assets").into_response();
         }
     };
 
@@ -1771,24 +2620,36 @@ pub async fn create_item(
 
     // Insert into database
     match state.db_service.items.create(&db_item).await {
-        Ok(_) => (
-            axum::http::StatusCode::CREATED,
-            headers,
-            serde_json::to_string(&db_item).unwrap(),
-        )
-            .into_response(),
-        Err(e) => {
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": format!("Failed to create item: {}", e)
-            });
+        Ok(_) => {
+            let server_config = ServerConfig::from_config(&state.config);
+            let location = server_config.item_href(&collection_id, &id);
+            if let Ok(value) = HeaderValue::from_str(&location) {
+                headers.insert("Location", value);
+            }
+            headers.insert(
+                "Content-Type",
+                HeaderValue::from_static("application/geo+json; charset=utf-8"),
+            );
+            item_validators(&state, &db_item, &request_headers)
+                .await
+                .add_headers(&mut headers);
+            // Answer with the STAC item as GET serves it, not the stored record
+            let stac_item = db_item.into_stac_item_json(&server_config, &[]);
             (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                axum::http::StatusCode::CREATED,
                 headers,
-                serde_json::to_string(&error_response).unwrap(),
+                serde_json::to_string(&with_warnings(stac_item, &warnings)).unwrap(),
             )
                 .into_response()
         }
+        Err(e) if is_unique_violation(&e) => {
+            let server_config = ServerConfig::from_config(&state.config);
+            trash::id_taken_response(&state.db_service, &server_config, &collection_id, Some(&id))
+                .await
+        }
+        Err(e) => {
+            ApiError::internal(format!("Failed to create item: {}", e)).into_response()
+        }
     }
 }
 
@@ -1798,14 +2659,6 @@ pub async fn upload_asset(
     State(state): State<AppState>,
     mut multipart: axum::extract::Multipart,
 ) -> Response {
-
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "Content-Type",
-        HeaderValue::from_static("application/json; charset=utf-8"),
-    );
-    headers = add_cors_headers(headers);
-
     // Verify the item exists
     let db_item = match state
         .db_service
@@ -1815,251 +2668,316 @@ pub async fn upload_asset(
     {
         Ok(Some(_)) => {}
         Ok(None) => {
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Item '{}' not found in collection '{}'", item_id, collection_id)
-            });
-            return (
-                axum::http::StatusCode::NOT_FOUND,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::not_found(format!(
+                "Item '{}' not found in collection '{}'",
+                item_id, collection_id
+            ))
+            .into_response();
         }
         Err(_) => {
-            let error_response = serde_json::json!({
-                "code": "InternalServerError",
-                "description": "Failed to verify item exists"
-            });
-            return (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                headers,
-                serde_json::to_string(&error_response).unwrap(),
-            )
-                .into_response();
+            return ApiError::internal("Failed to verify item exists").into_response();
         }
     };
 
     // Create assets directory if it doesn't exist
             let assets_dir = format!("{}/{}/{}", state.config.assets_dir(), collection_id, item_id);
 
-    if let Err(e) = std::fs::create_dir_all(&assets_dir) {
+    if let Err(e) = tokio::fs::create_dir_all(&assets_dir).await {
     
-        let error_response = serde_json::json!({
-            "code": "InternalServerError",
-            "description": format!("Failed to create assets directory: {}", e)
-        });
-        return (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            headers,
-            serde_json::to_string(&error_response).unwrap(),
-        )
+        return ApiError::internal(format!("Failed to create assets directory: {}", e))
             .into_response();
     }
 
 
     // Process the multipart form data
-    while let Some(field) = multipart.next_field().await.unwrap() {
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                return ApiError::new(
+                    e.status(),
+                    "BadRequest",
+                    format!("Failed to read multipart form data: {}", e.body_text()),
+                )
+                .into_response();
+            }
+        };
         let name = field.name().unwrap_or_default().to_string();
 
         if name == "file" {
             let filename = field.file_name().unwrap_or_default().to_string();
-            let content_type = field
-                .content_type()
-                .unwrap_or("application/octet-stream")
-                .to_string();
+            let content_type = MediaTypeRegistry::load(&state.db_service)
+                .await
+                .for_upload(&filename, field.content_type());
 
             // Read the file data
             let data = match field.bytes().await {
                 Ok(data) => data,
-                Err(_) => {
-                    let error_response = serde_json::json!({
-                        "code": "BadRequest",
-                        "description": "Failed to read uploaded file data"
-                    });
-                    return (
-                        axum::http::StatusCode::BAD_REQUEST,
-                        headers,
-                        serde_json::to_string(&error_response).unwrap(),
-                    )
+                Err(e) => {
+                    return ApiError::new(e.status(), "BadRequest", "Failed to read uploaded file data")
                         .into_response();
                 }
             };
 
-            // Save the file
-            let file_path = format!("{}/{}", assets_dir, asset_key);
-            if let Err(e) = std::fs::write(&file_path, &data) {
-                let error_response = serde_json::json!({
-                    "code": "InternalServerError",
-                    "description": format!("Failed to save uploaded file: {}", e)
-                });
-                return (
-                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    headers,
-                    serde_json::to_string(&error_response).unwrap(),
-                )
+            // Save the file next to its final place, where the upload scanner
+            // checks it before it can be served
+            let upload_path = pending_upload_path(&state.config, &collection_id, &item_id, &asset_key);
+            if let Err(e) = tokio::fs::write(&upload_path, &data).await {
+                return ApiError::internal(format!("Failed to save uploaded file: {}", e))
                     .into_response();
             }
+            return store_upload(&state, &collection_id, &item_id, &asset_key, &filename, &content_type)
+                .await;
+        }
+    }
 
-            // Update the item's assets in the database
-            let server_config = ServerConfig::from_config(&state.config);
-            let asset_href = server_config.asset_href(&collection_id, &item_id, &asset_key);
+    ApiError::bad_request("No file found in upload request").into_response()
+}
 
-            // Get current item
-            let mut db_item = match state
-                .db_service
-                .items
-                .get_by_id(&collection_id, &item_id)
-                .await
-            {
-                Ok(Some(item)) => item,
-                Ok(None) => {
-                    let error_response = serde_json::json!({
-                        "code": "NotFound",
-                        "description": "Item not found after upload"
-                    });
-                    return (
-                        axum::http::StatusCode::NOT_FOUND,
-                        headers,
-                        serde_json::to_string(&error_response).unwrap(),
-                    )
-                        .into_response();
-                }
-                Err(_) => {
-                    let error_response = serde_json::json!({
-                        "code": "InternalServerError",
-                        "description": "Failed to retrieve item for asset update"
-                    });
-                    return (
-                        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                        headers,
-                        serde_json::to_string(&error_response).unwrap(),
-                    )
-                        .into_response();
-                }
-            };
+/// Path an upload is written to before the scanner has checked it: next to
+/// the asset's final place, but never served
+pub(crate) fn pending_upload_path(
+    config: &crate::config::Config,
+    collection_id: &str,
+    item_id: &str,
+    asset_key: &str,
+) -> String {
+    format!(
+        "{}/{}/{}/.{}.upload",
+        config.assets_dir(),
+        collection_id,
+        item_id,
+        asset_key
+    )
+}
 
-            // Update assets
-            let mut assets = if let Some(assets_json) = &db_item.assets {
-                serde_json::from_value::<std::collections::HashMap<String, serde_json::Value>>(
-                    assets_json.clone(),
-                )
-                .unwrap_or_default()
-            } else {
-                std::collections::HashMap::new()
-            };
+/// Scans a file written to [`pending_upload_path`], moves it into place or
+/// to quarantine, and records the asset on the item. Shared by single-request
+/// and chunked uploads.
+pub(crate) async fn store_upload(
+    state: &AppState,
+    collection_id: &str,
+    item_id: &str,
+    asset_key: &str,
+    filename: &str,
+    content_type: &str,
+) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
 
-            // Determine asset roles based on key and content type
-            let roles = if asset_key == "thumbnail" {
-                vec!["thumbnail"]
-            } else if content_type.starts_with("image/") {
-                vec!["overview"]
-            } else if content_type.contains("geotiff") || content_type.contains("tiff") {
-                vec!["data"]
-            } else {
-                vec!["data"]
-            };
+    let file_path = format!(
+        "{}/{}/{}/{}",
+        state.config.assets_dir(),
+        collection_id,
+        item_id,
+        asset_key
+    );
+    let upload_path = pending_upload_path(&state.config, collection_id, item_id, asset_key);
+    let verdict = scan_upload(
+        &state.db_service,
+        std::path::Path::new(&upload_path),
+        collection_id,
+        item_id,
+        asset_key,
+    )
+    .await;
+    let stored = match &verdict {
+        ScanVerdict::Rejected(_) => {
+            // The rejected upload still replaces the asset, so the
+            // previous file is not served under its metadata
+            match tokio::fs::remove_file(&file_path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => eprintln!("Failed to remove replaced asset {}: {}", file_path, e),
+            }
+            quarantine(
+                &state.config,
+                std::path::Path::new(&upload_path),
+                collection_id,
+                item_id,
+                asset_key,
+            )
+            .await
+            .map(|_| ())
+        }
+        _ => tokio::fs::rename(&upload_path, &file_path)
+            .await
+            .map_err(|e| format!("Failed to save uploaded file: {}", e)),
+    };
+    if let Err(e) = stored {
+        let _ = tokio::fs::remove_file(&upload_path).await;
+        return ApiError::internal(e).into_response();
+    }
+    if let Err(e) =
+        asset_sizes::refresh(&state.db_service, &state.config, collection_id, Some(item_id)).await
+    {
+        eprintln!("Failed to update asset size of {}/{}: {}", collection_id, item_id, e);
+    }
 
-            // Add or update the asset with proper STAC structure
-            let asset_data = serde_json::json!({
-                "href": asset_href,
-                "type": content_type,
-                "title": filename,
-                "description": format!("Uploaded asset: {}", filename),
-                "roles": roles
-            });
+    // Update the item's assets in the database
+    let server_config = ServerConfig::from_config(&state.config);
+    let asset_href = server_config.asset_href(collection_id, item_id, asset_key);
+
+    // Get current item
+    let mut db_item = match state
+        .db_service
+        .items
+        .get_by_id(collection_id, item_id)
+        .await
+    {
+        Ok(Some(item)) => item,
+        Ok(None) => {
+            return ApiError::not_found("Item not found after upload").into_response();
+        }
+        Err(_) => {
+            return ApiError::internal("Failed to retrieve item for asset update")
+                .into_response();
+        }
+    };
 
-            assets.insert(asset_key.clone(), asset_data.clone());
+    // Update assets
+    let mut assets = if let Some(assets_json) = &db_item.assets {
+        serde_json::from_value::<std::collections::HashMap<String, serde_json::Value>>(
+            assets_json.clone(),
+        )
+        .unwrap_or_default()
+    } else {
+        std::collections::HashMap::new()
+    };
 
-        
+    let roles = asset_roles(asset_key, content_type);
 
-        
+    // Add or update the asset with proper STAC structure
+    let mut asset_data = serde_json::json!({
+        "href": asset_href,
+        "type": content_type,
+        "title": filename,
+        "description": format!("Uploaded asset: {}", filename),
+        "roles": roles
+    });
+    if let Some(status) = verdict.status() {
+        asset_data[SCAN_STATUS_FIELD] = serde_json::json!(status);
+    }
+    if let ScanVerdict::Rejected(reason) = &verdict {
+        asset_data[SCAN_REASON_FIELD] = serde_json::json!(reason);
+    } else {
+        match checksums::file_info(&file_path).await {
+            Ok(info) => {
+                checksums::set_file_info(&mut asset_data, &info);
+                checksums::add_file_extension(&mut db_item);
+            }
+            Err(e) => eprintln!("Failed to hash {}: {}", file_path, e),
+        }
+    }
 
+    assets.insert(asset_key.to_string(), asset_data.clone());
 
-            db_item.assets = Some(serde_json::to_value(assets).unwrap());
-            db_item.updated_at = chrono::Utc::now().to_rfc3339();
+    // Plugins may read sensor metadata from the file into properties;
+    // rejected files are never handed to them
+    if !matches!(verdict, ScanVerdict::Rejected(_)) {
+        let extracted = extract_file_properties(
+            std::path::PathBuf::from(state.config.plugins_dir()),
+            filename.to_string(),
+            std::path::Path::new(&file_path),
+        )
+        .await;
+        apply_properties(&mut db_item.properties, extracted);
+    }
 
-            // Save updated item
-            if let Err(_) = state.db_service.items.update(&db_item).await {
-                let error_response = serde_json::json!({
-                    "code": "InternalServerError",
-                    "description": "Failed to update item with new asset"
-                });
-                return (
-                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    headers,
-                    serde_json::to_string(&error_response).unwrap(),
-                )
-                    .into_response();
-            }
+    db_item.assets = Some(serde_json::to_value(assets).unwrap());
+    db_item.updated_at = chrono::Utc::now().to_rfc3339();
 
-            let success_response = serde_json::json!({
-                "success": true,
-                "message": "Asset uploaded successfully",
-                "asset": {
-                    "href": asset_href,
-                    "type": content_type,
-                    "title": filename,
-                    "key": asset_key
-                }
-            });
+    // Save updated item
+    if let Err(_) = state.db_service.items.update(&db_item).await {
+        return ApiError::internal("Failed to update item with new asset").into_response();
+    }
 
-            return (
-                axum::http::StatusCode::CREATED,
-                headers,
-                serde_json::to_string(&success_response).unwrap(),
-            )
-                .into_response();
-        }
+    if let ScanVerdict::Rejected(reason) = verdict {
+        return ApiError::new(
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            "UploadRejected",
+            format!("Upload rejected by the scanner and quarantined: {}", reason),
+        )
+        .with_extension("asset", asset_key)
+        .into_response();
     }
 
-    let error_response = serde_json::json!({
-        "code": "BadRequest",
-        "description": "No file found in upload request"
+    let success_response = serde_json::json!({
+        "success": true,
+        "message": "Asset uploaded successfully",
+        "asset": {
+            "href": asset_href,
+            "type": content_type,
+            "title": filename,
+            "key": asset_key
+        }
     });
+
     (
-        axum::http::StatusCode::BAD_REQUEST,
+        axum::http::StatusCode::CREATED,
         headers,
-        serde_json::to_string(&error_response).unwrap(),
+        serde_json::to_string(&success_response).unwrap(),
     )
         .into_response()
 }
 
-/// Handler to serve asset files
+/// Handler to serve asset files. Network clients share the configured
+/// asset bandwidth limit per client; the desktop UI is never throttled.
 pub async fn serve_asset(
     Path((collection_id, item_id, asset_key)): Path<(String, String, String)>,
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request_headers: HeaderMap,
 ) -> Response {
             let config = crate::config::Config::default();
         let file_path = format!("{}/{}/{}/{}", config.assets_dir(), collection_id, item_id, asset_key);
 
-    match std::fs::read(&file_path) {
-        Ok(data) => {
-            // Determine content type based on file extension
-            let content_type = if let Some(ext) = std::path::Path::new(&asset_key).extension() {
-                match ext.to_str().unwrap_or("").to_lowercase().as_str() {
-                    "jpg" | "jpeg" => "image/jpeg",
-                    "png" => "image/png",
-                    "gif" => "image/gif",
-                    "webp" => "image/webp",
-                    "svg" => "image/svg+xml",
-                    "tif" | "tiff" => "image/tiff",
-                    "pdf" => "application/pdf",
-                    "json" => "application/json",
-                    "xml" => "application/xml",
-                    "txt" => "text/plain",
-                    "csv" => "text/csv",
-                    _ => "application/octet-stream",
-                }
-            } else {
-                "application/octet-stream"
-            };
+    // Files of items in the trash stay on disk until purged but are not served
+    match state.db_service.items.exists(&collection_id, &item_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiError::not_found(format!(
+                "Asset '{}' not found for item '{}' in collection '{}'",
+                asset_key, item_id, collection_id
+            ))
+            .into_response()
+        }
+        Err(_) => return ApiError::internal("Failed to retrieve item").into_response(),
+    }
+
+    let opened = match tokio::fs::File::open(&file_path).await {
+        Ok(file) => file.metadata().await.map(|metadata| (file, metadata)),
+        Err(e) => Err(e),
+    };
+    match opened {
+        Ok((mut file, metadata)) if metadata.is_file() => {
+            let media_types = MediaTypeRegistry::load(&state.db_service).await;
+            let content_type = media_types.for_path(&asset_key);
+            let rate = state
+                .db_service
+                .settings
+                .get::<AssetBandwidthLimit>()
+                .await
+                .unwrap_or_else(|_| AssetBandwidthLimit::default_value());
+            let client = connect_info.map(|ConnectInfo(addr)| client_id(&request_headers, &addr));
+
+            let len = metadata.len();
+            let modified: DateTime<Utc> = metadata
+                .modified()
+                .map(DateTime::from)
+                .unwrap_or_else(|_| Utc::now());
 
             let mut headers = HeaderMap::new();
             headers.insert("Content-Type", HeaderValue::from_str(content_type).unwrap());
+            headers.insert("Accept-Ranges", HeaderValue::from_static("bytes"));
             headers.insert(
-                "Content-Length",
-                HeaderValue::from_str(&data.len().to_string()).unwrap(),
+                "Last-Modified",
+                HeaderValue::from_str(&http_date(&modified)).unwrap(),
             );
             headers.insert(
                 "Cache-Control",
@@ -2067,27 +2985,48 @@ pub async fn serve_asset(
             ); // Cache for 1 year
             headers = add_cors_headers(headers);
 
-            (headers, data).into_response()
-        }
-        Err(_) => {
-            let mut headers = HeaderMap::new();
-            headers.insert(
-                "Content-Type",
-                HeaderValue::from_static("application/json; charset=utf-8"),
-            );
-            headers = add_cors_headers(headers);
-
-            let error_response = serde_json::json!({
-                "code": "NotFound",
-                "description": format!("Asset '{}' not found for item '{}' in collection '{}'", asset_key, item_id, collection_id)
-            });
+            // Byte ranges let COG readers fetch tiles and players seek
+            // without downloading the whole file
+            let range = requested_range(&request_headers, len, &modified);
+            let (status, start, length) = match range {
+                ByteRange::Full => (StatusCode::OK, 0, len),
+                ByteRange::Partial { start, end } => {
+                    headers.insert(
+                        "Content-Range",
+                        HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, len))
+                            .unwrap(),
+                    );
+                    (StatusCode::PARTIAL_CONTENT, start, end - start + 1)
+                }
+                ByteRange::Unsatisfiable => {
+                    headers.insert(
+                        "Content-Range",
+                        HeaderValue::from_str(&format!("bytes */{}", len)).unwrap(),
+                    );
+                    return (StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response();
+                }
+            };
+            if start > 0 {
+                if let Err(e) = file.seek(std::io::SeekFrom::Start(start)).await {
+                    return ApiError::internal(format!("Failed to read asset: {}", e))
+                        .into_response();
+                }
+            }
+            headers.insert("Content-Length", HeaderValue::from(length));
 
             (
-                axum::http::StatusCode::NOT_FOUND,
+                status,
                 headers,
-                serde_json::to_string(&error_response).unwrap(),
+                bandwidth::file_body(file, length, client.as_deref(), rate),
             )
                 .into_response()
         }
+        _ => {
+            ApiError::not_found(format!(
+                "Asset '{}' not found for item '{}' in collection '{}'",
+                asset_key, item_id, collection_id
+            ))
+            .into_response()
+        }
     }
 }
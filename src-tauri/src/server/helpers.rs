@@ -1,3 +1,5 @@
+use crate::database::indexed_properties::check_property_name;
+use crate::database::{PredicateOp, PropertyPredicate, SortBy, SortKey};
 use crate::models::{self, Item};
 
 /// Calculates bounding box for different geometry types
@@ -150,8 +152,252 @@ pub fn calculate_bbox_for_geometry(geometry: &models::item::Geometry) -> Vec<f64
 
 
 
-/// Parses sortby parameter string into field/direction pairs
-pub fn parse_sortby(sortby_str: &str) -> Result<Vec<(String, String)>, String> {
+/// Computes the union of the bounding boxes of the given items.
+/// Items without a bbox fall back to their geometry; 3D boxes contribute their 2D extent.
+/// Returns None when no item has spatial information.
+pub fn calculate_bbox_for_items(items: &[Item]) -> Option<Vec<f64>> {
+    union_bboxes(items.iter().filter_map(|item| match (&item.bbox, &item.geometry) {
+        (Some(bbox), _) => Some(bbox.clone()),
+        (None, Some(geometry)) => Some(calculate_bbox_for_geometry(geometry)),
+        (None, None) => None,
+    }))
+}
+
+/// `calculate_bbox_for_items` for items already converted to JSON
+pub fn calculate_bbox_for_features(features: &[serde_json::Value]) -> Option<Vec<f64>> {
+    union_bboxes(features.iter().filter_map(|feature| {
+        match feature.get("bbox").and_then(|bbox| serde_json::from_value(bbox.clone()).ok()) {
+            Some(bbox) => Some(bbox),
+            None => feature
+                .get("geometry")
+                .and_then(|geometry| serde_json::from_value(geometry.clone()).ok())
+                .map(|geometry| calculate_bbox_for_geometry(&geometry)),
+        }
+    }))
+}
+
+fn union_bboxes(bboxes: impl Iterator<Item = Vec<f64>>) -> Option<Vec<f64>> {
+    let mut min_lon = f64::MAX;
+    let mut min_lat = f64::MAX;
+    let mut max_lon = f64::MIN;
+    let mut max_lat = f64::MIN;
+
+    for bbox in bboxes {
+        let (west, south, east, north) = match bbox.len() {
+            4 => (bbox[0], bbox[1], bbox[2], bbox[3]),
+            6 => (bbox[0], bbox[1], bbox[3], bbox[4]),
+            _ => continue,
+        };
+
+        min_lon = min_lon.min(west);
+        min_lat = min_lat.min(south);
+        max_lon = max_lon.max(east);
+        max_lat = max_lat.max(north);
+    }
+
+    if min_lon == f64::MAX {
+        None
+    } else {
+        Some(vec![min_lon, min_lat, max_lon, max_lat])
+    }
+}
+
+/// Parses the `intersects` search parameter: a GeoJSON geometry with at
+/// least one position, each of two or more numbers
+pub fn parse_intersects(value: &str) -> Result<models::item::Geometry, String> {
+    let geometry: models::item::Geometry =
+        serde_json::from_str(value).map_err(|e| format!("not a GeoJSON geometry: {}", e))?;
+    let parts = geometry_parts(&geometry);
+    if parts.is_empty() {
+        return Err("the geometry has no coordinates".to_string());
+    }
+    let well_formed = parts.iter().all(|part| match part {
+        GeometryPart::Point(_) => true,
+        GeometryPart::Line(line) => !line.is_empty(),
+        GeometryPart::Polygon(rings) => rings.first().is_some_and(|ring| ring.len() >= 3),
+    });
+    if !well_formed || !all_positions_valid(&geometry) {
+        return Err("every position needs a longitude and a latitude".to_string());
+    }
+    Ok(geometry)
+}
+
+fn all_positions_valid(geometry: &models::item::Geometry) -> bool {
+    use models::item::Geometry;
+    let valid = |p: &Vec<f64>| p.len() >= 2 && p.iter().all(|c| c.is_finite());
+    match geometry {
+        Geometry::Point { coordinates } => valid(coordinates),
+        Geometry::LineString { coordinates } | Geometry::MultiPoint { coordinates } => {
+            coordinates.iter().all(valid)
+        }
+        Geometry::Polygon { coordinates } | Geometry::MultiLineString { coordinates } => {
+            coordinates.iter().flatten().all(valid)
+        }
+        Geometry::MultiPolygon { coordinates } => coordinates.iter().flatten().flatten().all(valid),
+        Geometry::GeometryCollection { geometries } => geometries.iter().all(all_positions_valid),
+    }
+}
+
+type Position = [f64; 2];
+
+/// A geometry flattened into its simple parts
+enum GeometryPart {
+    Point(Position),
+    Line(Vec<Position>),
+    /// Exterior ring followed by holes
+    Polygon(Vec<Vec<Position>>),
+}
+
+fn geometry_parts(geometry: &models::item::Geometry) -> Vec<GeometryPart> {
+    use models::item::Geometry;
+    fn position(p: &[f64]) -> Option<Position> {
+        match p {
+            [x, y, ..] => Some([*x, *y]),
+            _ => None,
+        }
+    }
+    fn line(coordinates: &[Vec<f64>]) -> Vec<Position> {
+        coordinates.iter().filter_map(|p| position(p)).collect()
+    }
+    fn polygon(rings: &[Vec<Vec<f64>>]) -> Vec<Vec<Position>> {
+        rings.iter().map(|ring| line(ring)).collect()
+    }
+
+    match geometry {
+        Geometry::Point { coordinates } => {
+            position(coordinates).map(GeometryPart::Point).into_iter().collect()
+        }
+        Geometry::MultiPoint { coordinates } => coordinates
+            .iter()
+            .filter_map(|p| position(p).map(GeometryPart::Point))
+            .collect(),
+        Geometry::LineString { coordinates } => vec![GeometryPart::Line(line(coordinates))],
+        Geometry::MultiLineString { coordinates } => coordinates
+            .iter()
+            .map(|l| GeometryPart::Line(line(l)))
+            .collect(),
+        Geometry::Polygon { coordinates } => vec![GeometryPart::Polygon(polygon(coordinates))],
+        Geometry::MultiPolygon { coordinates } => coordinates
+            .iter()
+            .map(|p| GeometryPart::Polygon(polygon(p)))
+            .collect(),
+        Geometry::GeometryCollection { geometries } => {
+            geometries.iter().flat_map(geometry_parts).collect()
+        }
+    }
+}
+
+impl GeometryPart {
+    /// Edges of the part; a point or a one-position line is a zero-length edge
+    fn segments(&self) -> Vec<(Position, Position)> {
+        let edges = |positions: &[Position]| -> Vec<(Position, Position)> {
+            match positions {
+                [] => Vec::new(),
+                [single] => vec![(*single, *single)],
+                _ => positions.windows(2).map(|w| (w[0], w[1])).collect(),
+            }
+        };
+        match self {
+            GeometryPart::Point(p) => vec![(*p, *p)],
+            GeometryPart::Line(line) => edges(line),
+            GeometryPart::Polygon(rings) => rings.iter().flat_map(|ring| edges(ring)).collect(),
+        }
+    }
+
+    /// Any position of the part
+    fn first_position(&self) -> Option<Position> {
+        match self {
+            GeometryPart::Point(p) => Some(*p),
+            GeometryPart::Line(line) => line.first().copied(),
+            GeometryPart::Polygon(rings) => rings.first().and_then(|ring| ring.first().copied()),
+        }
+    }
+
+    /// Whether `p` lies inside the polygon's exterior ring and outside its holes
+    fn contains(&self, p: Position) -> bool {
+        let GeometryPart::Polygon(rings) = self else {
+            return false;
+        };
+        match rings.split_first() {
+            Some((exterior, holes)) => {
+                ring_contains(exterior, p) && !holes.iter().any(|hole| ring_contains(hole, p))
+            }
+            None => false,
+        }
+    }
+}
+
+/// Even-odd ray casting; points on the ring are settled by the edge tests
+fn ring_contains(ring: &[Position], p: Position) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for i in 0..ring.len() {
+        let (a, b) = (ring[i], ring[j]);
+        if (a[1] > p[1]) != (b[1] > p[1])
+            && p[0] < (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1]) + a[0]
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn orientation(a: Position, b: Position, c: Position) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Whether `p`, collinear with the segment, lies within it
+fn within_segment(a: Position, b: Position, p: Position) -> bool {
+    p[0] >= a[0].min(b[0]) && p[0] <= a[0].max(b[0]) && p[1] >= a[1].min(b[1]) && p[1] <= a[1].max(b[1])
+}
+
+fn segments_intersect((p1, p2): (Position, Position), (p3, p4): (Position, Position)) -> bool {
+    let d1 = orientation(p3, p4, p1);
+    let d2 = orientation(p3, p4, p2);
+    let d3 = orientation(p1, p2, p3);
+    let d4 = orientation(p1, p2, p4);
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return true;
+    }
+    (d1 == 0.0 && within_segment(p3, p4, p1))
+        || (d2 == 0.0 && within_segment(p3, p4, p2))
+        || (d3 == 0.0 && within_segment(p1, p2, p3))
+        || (d4 == 0.0 && within_segment(p1, p2, p4))
+}
+
+/// Whether two GeoJSON geometries share at least one point: their edges
+/// cross or touch, or one lies inside a polygon of the other. Coordinates are
+/// treated as planar, as in the bbox filter.
+pub fn geometries_intersect(a: &models::item::Geometry, b: &models::item::Geometry) -> bool {
+    let a_parts = geometry_parts(a);
+    let b_parts = geometry_parts(b);
+    let b_segments: Vec<_> = b_parts.iter().flat_map(GeometryPart::segments).collect();
+    let edges_meet = a_parts
+        .iter()
+        .flat_map(GeometryPart::segments)
+        .any(|sa| b_segments.iter().any(|&sb| segments_intersect(sa, sb)));
+    if edges_meet {
+        return true;
+    }
+    // Without crossing edges a part is either wholly inside a polygon or
+    // wholly outside it, so one position decides
+    let inside = |parts: &[GeometryPart], polygons: &[GeometryPart]| {
+        parts.iter().any(|part| {
+            part.first_position()
+                .is_some_and(|p| polygons.iter().any(|polygon| polygon.contains(p)))
+        })
+    };
+    inside(&a_parts, &b_parts) || inside(&b_parts, &a_parts)
+}
+
+/// Parses a sortby parameter: comma-separated fields, each `field`,
+/// `field:asc`, `field:desc`, `+field` or `-field`. Fields are `id`,
+/// `collection` or a property, bare or as `properties.<name>`; property names
+/// may contain colons (`eo:cloud_cover:desc`).
+pub fn parse_sortby(sortby_str: &str) -> Result<Vec<SortBy>, String> {
     let mut sortby = Vec::new();
 
     for part in sortby_str.split(',') {
@@ -160,17 +406,22 @@ pub fn parse_sortby(sortby_str: &str) -> Result<Vec<(String, String)>, String> {
             continue;
         }
 
-        if part.contains(':') {
-            let mut split = part.split(':');
-            let field = split.next().unwrap_or("").trim().to_string();
-            let direction = split.next().unwrap_or("asc").trim().to_string();
-
-            if !field.is_empty() {
-                sortby.push((field, direction));
-            }
+        let (field, descending) = if let Some(field) = part.strip_prefix('-') {
+            (field, true)
+        } else if let Some(field) = part.strip_prefix('+') {
+            (field, false)
         } else {
-            sortby.push((part.to_string(), "asc".to_string()));
+            match part.rsplit_once(':') {
+                Some((field, direction)) if direction.eq_ignore_ascii_case("desc") => (field, true),
+                Some((field, direction)) if direction.eq_ignore_ascii_case("asc") => (field, false),
+                _ => (part, false),
+            }
+        };
+        let key = SortKey::from_field(field.trim());
+        if let SortKey::Property(property) = &key {
+            check_property_name(property)?;
         }
+        sortby.push(SortBy { key, descending });
     }
 
     if sortby.is_empty() {
@@ -180,128 +431,211 @@ pub fn parse_sortby(sortby_str: &str) -> Result<Vec<(String, String)>, String> {
     }
 }
 
-/// Sorts items based on sortby parameters
-pub fn sort_items(mut items: Vec<Item>, sortby: &[(String, String)]) -> Vec<Item> {
-    for (field, direction) in sortby.iter().rev() {
-        items.sort_by(|a, b| {
-            let comparison = match field.as_str() {
-                "datetime" => {
-                    let a_datetime = a.properties.datetime.as_deref().unwrap_or("");
-                    let b_datetime = b.properties.datetime.as_deref().unwrap_or("");
-                    a_datetime.cmp(b_datetime)
-                }
-                "id" => a.id.cmp(&b.id),
-                _ => std::cmp::Ordering::Equal,
+/// Parses a STAC Query extension object such as
+/// `{"eo:cloud_cover": {"lt": 10}, "platform": {"in": ["a", "b"]}}`
+pub fn parse_query(query: &serde_json::Value) -> Result<Vec<PropertyPredicate>, String> {
+    let properties = query
+        .as_object()
+        .ok_or("query must be an object of property names to operators")?;
+
+    let mut predicates = Vec::new();
+    for (property, operators) in properties {
+        check_property_name(property)?;
+        let operators = operators
+            .as_object()
+            .ok_or_else(|| format!("query for '{}' must be an object of operators", property))?;
+
+        for (operator, value) in operators {
+            let op = match operator.as_str() {
+                "eq" => PredicateOp::Eq,
+                "neq" => PredicateOp::Neq,
+                "lt" => PredicateOp::Lt,
+                "lte" => PredicateOp::Lte,
+                "gt" => PredicateOp::Gt,
+                "gte" => PredicateOp::Gte,
+                "startsWith" => PredicateOp::StartsWith,
+                "endsWith" => PredicateOp::EndsWith,
+                "contains" => PredicateOp::Contains,
+                "in" => PredicateOp::In,
+                other => return Err(format!("Unknown query operator '{}'", other)),
             };
 
-            if direction == "desc" {
-                comparison.reverse()
-            } else {
-                comparison
+            let values = match (op, value) {
+                (PredicateOp::In, serde_json::Value::Array(values)) if !values.is_empty() => {
+                    values.clone()
+                }
+                (PredicateOp::In, _) => {
+                    return Err(format!("'in' for '{}' needs a non-empty array", property))
+                }
+                (
+                    PredicateOp::StartsWith | PredicateOp::EndsWith | PredicateOp::Contains,
+                    serde_json::Value::String(_),
+                ) => vec![value.clone()],
+                (PredicateOp::StartsWith | PredicateOp::EndsWith | PredicateOp::Contains, _) => {
+                    return Err(format!("'{}' for '{}' needs a string", operator, property))
+                }
+                _ => vec![value.clone()],
+            };
+            if let Some(bad) = values
+                .iter()
+                .find(|v| !(v.is_number() || v.is_string() || v.is_boolean()))
+            {
+                return Err(format!(
+                    "Unsupported value {} for '{}'; use numbers, strings or booleans",
+                    bad, property
+                ));
             }
-        });
-    }
 
-    items
+            predicates.push(PropertyPredicate {
+                property: property.clone(),
+                op,
+                values,
+            });
+        }
+    }
+    Ok(predicates)
 }
 
-/// Filters items by bounding box
-pub fn filter_items_by_bbox(items: &[Item], bbox_str: &str) -> Vec<Item> {
-    let bbox_parts: Vec<f64> = bbox_str
-        .split(',')
-        .filter_map(|s| s.trim().parse::<f64>().ok())
+/// Sorts items by `sortby`, comparing values the way SQLite orders them so
+/// in-memory and SQL sorts agree: missing and null first, then numbers
+/// (booleans as 0 and 1) numerically, then strings; arrays and objects
+/// compare as their JSON text
+pub fn sort_items(items: Vec<Item>, sortby: &[SortBy]) -> Vec<Item> {
+    let mut keyed: Vec<(Vec<serde_json::Value>, Item)> = items
+        .into_iter()
+        .map(|item| {
+            let properties = serde_json::to_value(&item.properties).unwrap_or_default();
+            let values = sortby
+                .iter()
+                .map(|s| match &s.key {
+                    SortKey::Id => serde_json::Value::String(item.id.clone()),
+                    SortKey::Collection => item
+                        .collection
+                        .clone()
+                        .map_or(serde_json::Value::Null, serde_json::Value::String),
+                    SortKey::Property(property) => {
+                        properties.get(property).cloned().unwrap_or_default()
+                    }
+                })
+                .collect();
+            (values, item)
+        })
         .collect();
 
-    if bbox_parts.len() != 4 {
-        return items.to_vec();
+    keyed.sort_by(|(a, _), (b, _)| {
+        sortby
+            .iter()
+            .zip(a.iter().zip(b))
+            .map(|(s, (a, b))| {
+                let comparison = compare_sort_values(a, b);
+                if s.descending {
+                    comparison.reverse()
+                } else {
+                    comparison
+                }
+            })
+            .find(|comparison| comparison.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    keyed.into_iter().map(|(_, item)| item).collect()
+}
+
+/// SQLite's order of JSON values read with `json_extract`
+fn compare_sort_values(a: &serde_json::Value, b: &serde_json::Value) -> std::cmp::Ordering {
+    use serde_json::Value;
+
+    enum SortValue {
+        Null,
+        Number(f64),
+        Text(String),
+    }
+    let sort_value = |value: &Value| match value {
+        Value::Null => SortValue::Null,
+        Value::Bool(b) => SortValue::Number(*b as u8 as f64),
+        Value::Number(n) => SortValue::Number(n.as_f64().unwrap_or_default()),
+        Value::String(s) => SortValue::Text(s.clone()),
+        other => SortValue::Text(other.to_string()),
+    };
+    match (sort_value(a), sort_value(b)) {
+        (SortValue::Null, SortValue::Null) => std::cmp::Ordering::Equal,
+        (SortValue::Null, _) => std::cmp::Ordering::Less,
+        (_, SortValue::Null) => std::cmp::Ordering::Greater,
+        (SortValue::Number(a), SortValue::Number(b)) => a.total_cmp(&b),
+        (SortValue::Number(_), SortValue::Text(_)) => std::cmp::Ordering::Less,
+        (SortValue::Text(_), SortValue::Number(_)) => std::cmp::Ordering::Greater,
+        (SortValue::Text(a), SortValue::Text(b)) => a.cmp(&b),
     }
+}
 
-    let [min_lon, min_lat, max_lon, max_lat] =
-        [bbox_parts[0], bbox_parts[1], bbox_parts[2], bbox_parts[3]];
+type UtcDateTime = chrono::DateTime<chrono::Utc>;
 
-    items
-        .iter()
-        .filter(|item| {
-            if let Some(bbox) = &item.bbox {
-                if bbox.len() >= 4 {
-                    let item_min_lon = bbox[0];
-                    let item_min_lat = bbox[1];
-                    let item_max_lon = bbox[2];
-                    let item_max_lat = bbox[3];
-
-                    // Check if the item's bbox intersects with the query bbox
-                    return item_min_lon <= max_lon
-                        && item_max_lon >= min_lon
-                        && item_min_lat <= max_lat
-                        && item_max_lat >= min_lat;
-                }
-            }
-            false
-        })
-        .cloned()
-        .collect()
+/// Parses one end of a datetime query. Besides RFC 3339 this accepts plain
+/// dates, which cover the whole day, and date-times without seconds or without
+/// an offset, which are taken as UTC. `..` and an empty string are open ends.
+fn parse_datetime_bound(value: &str, end_of_range: bool) -> Result<Option<UtcDateTime>, String> {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+    let value = value.trim();
+    if value.is_empty() || value == ".." {
+        return Ok(None);
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(Some(dt.with_timezone(&Utc)));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let time = if end_of_range {
+            NaiveTime::from_hms_nano_opt(23, 59, 59, 999_999_999).expect("valid time")
+        } else {
+            NaiveTime::MIN
+        };
+        return Ok(Some(date.and_time(time).and_utc()));
+    }
+
+    let naive = value
+        .strip_suffix(['Z', 'z'])
+        .unwrap_or(value)
+        .replacen([' ', 't'], "T", 1);
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(&naive, format) {
+            return Ok(Some(dt.and_utc()));
+        }
+    }
+    Err(format!(
+        "'{}' is not an RFC 3339 date-time or a YYYY-MM-DD date",
+        value
+    ))
 }
 
-/// Filters items by datetime range according to STAC specification
-/// datetime format: "start/end", "start/..", "../end", or "start"
-pub fn filter_items_by_datetime(items: &[Item], datetime_str: &str) -> Vec<Item> {
-    use chrono::{DateTime, Utc};
-    
-    // Parse datetime range
+/// Parses a datetime query: an instant, a date (the whole day) or an interval
+/// "start/end" where either end may be open (".."). Returns the inclusive
+/// bounds, or a message suitable for a 400 response.
+pub fn parse_datetime_interval(
+    datetime_str: &str,
+) -> Result<(Option<UtcDateTime>, Option<UtcDateTime>), String> {
     let parts: Vec<&str> = datetime_str.split('/').collect();
-    if parts.is_empty() {
-        return items.to_vec();
-    }
-    
-    let start_datetime = if parts[0] == ".." {
-        None
-    } else {
-        DateTime::parse_from_rfc3339(parts[0]).ok().map(|dt| dt.with_timezone(&Utc))
-    };
-    
-    let end_datetime = if parts.len() > 1 && parts[1] == ".." {
-        None
-    } else if parts.len() > 1 {
-        DateTime::parse_from_rfc3339(parts[1]).ok().map(|dt| dt.with_timezone(&Utc))
-    } else {
-        // Single datetime - exact match
-        start_datetime.clone()
+    let (start, end) = match parts.as_slice() {
+        [single] => (
+            parse_datetime_bound(single, false)?,
+            parse_datetime_bound(single, true)?,
+        ),
+        [start, end] => (
+            parse_datetime_bound(start, false)?,
+            parse_datetime_bound(end, true)?,
+        ),
+        _ => {
+            return Err(
+                "datetime must be a date-time, a date or an interval 'start/end'".to_string(),
+            )
+        }
     };
-    
-    items
-        .iter()
-        .filter(|item| {
-            if let Some(item_datetime_str) = &item.properties.datetime {
-                if let Ok(item_datetime) = DateTime::parse_from_rfc3339(item_datetime_str) {
-                    let item_dt = item_datetime.with_timezone(&Utc);
-                    
-                    match (start_datetime.as_ref(), end_datetime.as_ref()) {
-                        (Some(start), Some(end)) => {
-                            // Range: start <= item <= end
-                            item_dt >= *start && item_dt <= *end
-                        }
-                        (Some(start), None) => {
-                            // Start only: item >= start
-                            item_dt >= *start
-                        }
-                        (None, Some(end)) => {
-                            // End only: item <= end
-                            item_dt <= *end
-                        }
-                        (None, None) => {
-                            // No datetime filter
-                            true
-                        }
-                    }
-                } else {
-                    // Invalid datetime format in item
-                    false
-                }
-            } else {
-                // Item has no datetime - only include if no datetime filter is applied
-                start_datetime.is_none() && end_datetime.is_none()
-            }
-        })
-        .cloned()
-        .collect()
+
+    match (start, end) {
+        (None, None) => Err("datetime needs at least one bounded end".to_string()),
+        (Some(start), Some(end)) if start > end => {
+            Err("datetime interval starts after it ends".to_string())
+        }
+        bounds => Ok(bounds),
+    }
 }
+
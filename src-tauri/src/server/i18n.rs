@@ -0,0 +1,342 @@
+use crate::database::settings::{MetadataLanguage, Setting};
+use crate::database::translations::{is_language_tag, Translations};
+use crate::database::DatabaseService;
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+/// Schema of the STAC language extension, declared by translated resources
+pub const LANGUAGE_EXTENSION: &str =
+    "https://stac-extensions.github.io/language/v1.0.0/schema.json";
+
+/// The languages a client accepts, from its `Accept-Language` header, and the
+/// language stored metadata is written in
+#[derive(Debug, Clone)]
+pub struct LanguagePreference {
+    accepted: Vec<String>,
+    default: String,
+}
+
+impl LanguagePreference {
+    /// Reads the preference of a request
+    pub async fn from_headers(db_service: &DatabaseService, headers: &HeaderMap) -> Self {
+        let default = db_service
+            .settings
+            .get::<MetadataLanguage>()
+            .await
+            .unwrap_or_else(|_| MetadataLanguage::default_value());
+        let accept = headers
+            .get("Accept-Language")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        Self {
+            accepted: parse_accept_language(accept),
+            default,
+        }
+    }
+
+    /// Best available language: the first accepted one that matches exactly,
+    /// then by primary language (`fr-CA` accepts `fr` and `fr` accepts
+    /// `fr-CH`), otherwise the metadata language
+    pub fn choose(&self, translations: &Translations) -> String {
+        let available: Vec<&str> = std::iter::once(self.default.as_str())
+            .chain(translations.keys().map(String::as_str))
+            .collect();
+        for accepted in &self.accepted {
+            if accepted == "*" {
+                break;
+            }
+            if let Some(exact) = available.iter().find(|a| a.eq_ignore_ascii_case(accepted)) {
+                return exact.to_string();
+            }
+            let primary = primary_language(accepted);
+            if let Some(related) = available
+                .iter()
+                .find(|a| primary_language(a).eq_ignore_ascii_case(primary))
+            {
+                return related.to_string();
+            }
+        }
+        self.default.clone()
+    }
+
+    /// Serves a collection in the preferred language and lists the others
+    /// with the language extension. Returns the language served, `None` when
+    /// the collection has no translations and is left as stored.
+    pub fn localize_collection(
+        &self,
+        collection: &mut Value,
+        translations: &Translations,
+    ) -> Option<String> {
+        let language = self.localize(collection, translations)?;
+        add_language_extension(collection);
+        Some(language)
+    }
+
+    /// Serves an item in the preferred language; its title, description and
+    /// language fields are properties
+    pub fn localize_item(&self, item: &mut Value, translations: &Translations) -> Option<String> {
+        let language = self.localize(item.get_mut("properties")?, translations)?;
+        add_language_extension(item);
+        Some(language)
+    }
+
+    fn localize(&self, target: &mut Value, translations: &Translations) -> Option<String> {
+        if translations.is_empty() {
+            return None;
+        }
+        let object = target.as_object_mut()?;
+        let language = self.choose(translations);
+        if let Some(translation) = translations.get(&language) {
+            if let Some(title) = &translation.title {
+                object.insert("title".to_string(), Value::from(title.as_str()));
+            }
+            if let Some(description) = &translation.description {
+                object.insert("description".to_string(), Value::from(description.as_str()));
+            }
+        }
+        let others: Vec<Value> = std::iter::once(&self.default)
+            .chain(translations.keys())
+            .filter(|code| **code != language)
+            .map(|code| json!({ "code": code }))
+            .collect();
+        object.insert("language".to_string(), json!({ "code": language }));
+        object.insert("languages".to_string(), Value::Array(others));
+        Some(language)
+    }
+}
+
+/// Accepted language tags by descending quality; `q=0` tags are dropped
+fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut accepted: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';');
+            let tag = pieces.next()?.trim();
+            if tag.is_empty() {
+                return None;
+            }
+            let quality = pieces
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (quality > 0.0).then(|| (tag.to_string(), quality))
+        })
+        .collect();
+    accepted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    accepted.into_iter().map(|(tag, _)| tag).collect()
+}
+
+fn primary_language(tag: &str) -> &str {
+    tag.split('-').next().unwrap_or(tag)
+}
+
+fn add_language_extension(resource: &mut Value) {
+    let Some(object) = resource.as_object_mut() else {
+        return;
+    };
+    let extensions = object
+        .entry("stac_extensions")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if extensions.is_null() {
+        *extensions = Value::Array(Vec::new());
+    }
+    if let Some(list) = extensions.as_array_mut() {
+        if !list.iter().any(|e| e == LANGUAGE_EXTENSION) {
+            list.push(Value::from(LANGUAGE_EXTENSION));
+        }
+    }
+}
+
+/// Marks a response as negotiated on `Accept-Language`, naming the language
+/// served when there is one
+pub fn add_language_headers(headers: &mut HeaderMap, language: Option<&str>) {
+    headers.insert("Vary", HeaderValue::from_static("Accept-Language"));
+    if let Some(value) = language.and_then(|l| HeaderValue::from_str(l).ok()) {
+        headers.insert("Content-Language", value);
+    }
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Handler listing the translations of a collection
+/// (GET /collections/:collection_id/translations)
+pub async fn get_collection_translations(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    translations_response(get_translations(&state.db_service, &collection_id, None).await)
+}
+
+/// Handler replacing the translations of a collection
+/// (PUT /collections/:collection_id/translations)
+pub async fn put_collection_translations(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+    Json(translations): Json<Translations>,
+) -> Result<Response, ApiError> {
+    translations_response(
+        set_translations(&state.db_service, &collection_id, None, translations).await,
+    )
+}
+
+/// Handler listing the translations of an item
+/// (GET /collections/:collection_id/items/:item_id/translations)
+pub async fn get_item_translations(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    translations_response(get_translations(&state.db_service, &collection_id, Some(&item_id)).await)
+}
+
+/// Handler replacing the translations of an item
+/// (PUT /collections/:collection_id/items/:item_id/translations)
+pub async fn put_item_translations(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    Json(translations): Json<Translations>,
+) -> Result<Response, ApiError> {
+    translations_response(
+        set_translations(
+            &state.db_service,
+            &collection_id,
+            Some(&item_id),
+            translations,
+        )
+        .await,
+    )
+}
+
+fn translations_response(
+    result: Result<Translations, (StatusCode, String)>,
+) -> Result<Response, ApiError> {
+    let translations =
+        result.map_err(|(status, description)| ApiError::from_status(status, description))?;
+    Ok(json_response(StatusCode::OK, json!(translations)))
+}
+
+/// Translations of a collection, or of one of its items. Shared by the HTTP
+/// endpoints and the Tauri command.
+pub async fn get_translations(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> Result<Translations, (StatusCode, String)> {
+    check_exists(db_service, collection_id, item_id).await?;
+    db_service
+        .translations
+        .get(collection_id, item_id)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read translations: {}", e),
+            )
+        })
+}
+
+/// Replaces the translations of a collection or item. Keys must be language
+/// tags other than the metadata language, which the stored document holds.
+pub async fn set_translations(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    item_id: Option<&str>,
+    translations: Translations,
+) -> Result<Translations, (StatusCode, String)> {
+    let default = db_service
+        .settings
+        .get::<MetadataLanguage>()
+        .await
+        .unwrap_or_else(|_| MetadataLanguage::default_value());
+    for (language, translation) in &translations {
+        if !is_language_tag(language) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("'{}' is not a language tag such as fr or pt-BR", language),
+            ));
+        }
+        if language.eq_ignore_ascii_case(&default) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "'{}' is the metadata language; edit the title and description of the {} instead",
+                    language,
+                    if item_id.is_some() { "item" } else { "collection" }
+                ),
+            ));
+        }
+        if translation.title.is_none() && translation.description.is_none() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Translation '{}' has neither a title nor a description",
+                    language
+                ),
+            ));
+        }
+    }
+
+    check_exists(db_service, collection_id, item_id).await?;
+    db_service
+        .translations
+        .set(collection_id, item_id, &translations)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to save translations: {}", e),
+            )
+        })?;
+    Ok(translations)
+}
+
+async fn check_exists(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    let found = match item_id {
+        Some(item_id) => db_service
+            .items
+            .get_by_id(collection_id, item_id)
+            .await
+            .map(|item| item.is_some()),
+        None => db_service
+            .collections
+            .get_by_id(collection_id)
+            .await
+            .map(|collection| collection.is_some()),
+    };
+    match found {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            match item_id {
+                Some(item_id) => format!(
+                    "Item '{}' not found in collection '{}'",
+                    item_id, collection_id
+                ),
+                None => format!("Collection '{}' not found", collection_id),
+            },
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to look up the translated resource: {}", e),
+        )),
+    }
+}
@@ -0,0 +1,116 @@
+use crate::database::settings::{IdGeneration, IdStrategy, Setting};
+use crate::database::DatabaseService;
+
+/// Crockford base32 alphabet used by ULIDs
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// What a new id is generated for; slugs are checked for collisions in scope
+pub enum IdScope<'a> {
+    Collection,
+    Item { collection_id: &'a str },
+}
+
+/// Returns the id given by the client or, when it is missing or empty,
+/// generates one with the configured strategy.
+///
+/// `Err` carries the client-facing message when the strategy is `reject`.
+pub async fn resolve_id(
+    db_service: &DatabaseService,
+    payload: &serde_json::Value,
+    scope: IdScope<'_>,
+) -> Result<String, String> {
+    if let Some(id) = payload.get("id").and_then(|v| v.as_str()) {
+        if !id.trim().is_empty() {
+            return Ok(id.to_string());
+        }
+    }
+
+    let strategy = db_service
+        .settings
+        .get::<IdGeneration>()
+        .await
+        .unwrap_or_else(|_| IdGeneration::default_value());
+
+    match strategy {
+        IdStrategy::Reject => Err("Missing required field: id".to_string()),
+        IdStrategy::UuidV7 => Ok(uuid::Uuid::now_v7().to_string()),
+        IdStrategy::Ulid => Ok(ulid()),
+        IdStrategy::Slug => {
+            let base = slug_id(payload, &scope);
+            let mut candidate = base.clone();
+            let mut suffix = 2;
+            while id_exists(db_service, &scope, &candidate).await {
+                candidate = format!("{}-{}", base, suffix);
+                suffix += 1;
+            }
+            Ok(candidate)
+        }
+    }
+}
+
+async fn id_exists(db_service: &DatabaseService, scope: &IdScope<'_>, id: &str) -> bool {
+    match scope {
+        IdScope::Collection => matches!(db_service.collections.get_by_id(id).await, Ok(Some(_))),
+        IdScope::Item { collection_id } => {
+            matches!(db_service.items.get_by_id(collection_id, id).await, Ok(Some(_)))
+        }
+    }
+}
+
+/// ULID: 48-bit millisecond timestamp and 80 random bits in Crockford base32
+fn ulid() -> String {
+    let millis = chrono::Utc::now().timestamp_millis().max(0) as u128;
+    let random = u128::from_be_bytes(*uuid::Uuid::new_v4().as_bytes()) & ((1u128 << 80) - 1);
+    let value = (millis << 80) | random;
+
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Slug of the title plus a compact UTC datetime.
+/// Items use `properties.title` and `properties.datetime`; collections the
+/// top-level `title` and the current time.
+fn slug_id(payload: &serde_json::Value, scope: &IdScope<'_>) -> String {
+    let properties = payload.get("properties");
+    let (title, datetime) = match scope {
+        IdScope::Collection => (payload.get("title"), None),
+        IdScope::Item { .. } => (
+            properties.and_then(|p| p.get("title")),
+            properties.and_then(|p| p.get("datetime").or_else(|| p.get("start_datetime"))),
+        ),
+    };
+
+    let datetime = datetime
+        .and_then(|v| v.as_str())
+        .and_then(|v| chrono::DateTime::parse_from_rfc3339(v).ok())
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+        .unwrap_or_else(chrono::Utc::now);
+    let stamp = datetime.format("%Y%m%dt%H%M%Sz").to_string();
+
+    let slug = slugify(title.and_then(|v| v.as_str()).unwrap_or(""));
+    if slug.is_empty() {
+        let prefix = match scope {
+            IdScope::Collection => "collection",
+            IdScope::Item { .. } => "item",
+        };
+        format!("{}-{}", prefix, stamp)
+    } else {
+        format!("{}-{}", slug, stamp)
+    }
+}
+
+/// Lowercase ASCII words joined by dashes, capped at 60 characters
+fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(60);
+    slug.trim_end_matches('-').to_string()
+}
@@ -0,0 +1,109 @@
+use crate::database::indexed_properties::{check_property_name, IndexedProperty};
+use crate::database::DatabaseService;
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Handler listing the indexed properties of a collection
+/// (GET /collections/:collection_id/indexed-properties)
+pub async fn get_indexed_properties(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    state
+        .db_service
+        .collections
+        .get_by_id(&collection_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to get collection: {}", e)))?
+        .ok_or_else(|| ApiError::not_found(format!("Collection '{}' not found", collection_id)))?;
+
+    let properties = state
+        .db_service
+        .indexed_properties
+        .for_collection(&collection_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read indexed properties: {}", e)))?;
+    Ok(json_response(StatusCode::OK, serde_json::json!(properties)))
+}
+
+/// Handler replacing the indexed properties of a collection
+/// (PUT /collections/:collection_id/indexed-properties)
+pub async fn put_indexed_properties(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+    Json(properties): Json<Vec<IndexedProperty>>,
+) -> Result<Response, ApiError> {
+    let properties = set_indexed_properties(&state.db_service, &collection_id, properties)
+        .await
+        .map_err(|(status, description)| ApiError::from_status(status, description))?;
+    Ok(json_response(StatusCode::OK, serde_json::json!(properties)))
+}
+
+/// Replaces the properties a collection indexes. Each gets a generated column
+/// and index on the items table, which query extension predicates on that
+/// property use instead of scanning item JSON. Shared by the HTTP endpoint and
+/// the Tauri command.
+pub async fn set_indexed_properties(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    properties: Vec<IndexedProperty>,
+) -> Result<Vec<IndexedProperty>, (StatusCode, String)> {
+    for property in &properties {
+        check_property_name(&property.property).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    match db_service.collections.get_by_id(collection_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Collection '{}' not found", collection_id),
+            ))
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to get collection: {}", e),
+            ))
+        }
+    }
+
+    db_service
+        .indexed_properties
+        .set_for_collection(collection_id, &properties)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to save indexed properties: {}", e),
+            )
+        })?;
+
+    db_service
+        .indexed_properties
+        .for_collection(collection_id)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read indexed properties: {}", e),
+            )
+        })
+}
@@ -0,0 +1,95 @@
+use crate::database::DatabaseService;
+use crate::server::ingest_hooks::{run_ingest_hook, IngestEvent};
+use crate::server::ingest_mode::{apply_ingest_mode, ingest_rejected_response};
+use crate::server::item_schema::{
+    describe_violations, schema_violation_response, validate_item, SchemaViolation,
+};
+use crate::server::stac_validation::{stac_violation_response, validate_stac_item};
+use axum::response::Response;
+use serde_json::Value;
+
+/// Why an incoming item was turned away
+#[derive(Debug)]
+pub enum IngestRejection {
+    /// Problems found in strict ingest mode
+    Strict(Vec<String>),
+    /// STAC rules the item breaks
+    Stac(Vec<SchemaViolation>),
+    /// Violations of the item schema attached to its collection
+    Schema {
+        collection_id: String,
+        violations: Vec<SchemaViolation>,
+    },
+}
+
+impl IngestRejection {
+    /// 400 problem response for API clients
+    pub fn into_response(self) -> Response {
+        match self {
+            IngestRejection::Strict(problems) => ingest_rejected_response(&problems),
+            IngestRejection::Stac(violations) => stac_violation_response("Item", &violations),
+            IngestRejection::Schema {
+                collection_id,
+                violations,
+            } => schema_violation_response(&collection_id, &violations),
+        }
+    }
+}
+
+impl std::fmt::Display for IngestRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IngestRejection::Strict(problems) => {
+                write!(f, "rejected in strict ingest mode: {}", problems.join("; "))
+            }
+            IngestRejection::Stac(violations) => {
+                write!(f, "is not valid STAC: {}", describe_violations(violations))
+            }
+            IngestRejection::Schema {
+                collection_id,
+                violations,
+            } => write!(
+                f,
+                "does not satisfy the schema of collection '{}': {}",
+                collection_id,
+                describe_violations(violations)
+            ),
+        }
+    }
+}
+
+/// Takes an item created or updated in `collection_id` through the steps
+/// every way into the catalog shares: the user's ingest hook, the ingest
+/// mode, the STAC checks unless `validate` is off, and the collection's item
+/// schema. Returns the item to store and the fix-ups made in lenient mode.
+pub async fn prepare_item(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    event: IngestEvent,
+    item: Value,
+    validate: bool,
+) -> Result<(Value, Vec<String>), IngestRejection> {
+    // The user's ingest hook may amend the properties before anything is stored
+    let item = run_ingest_hook(db_service, collection_id, event, item).await;
+
+    // Repair or reject non-conformant items according to the ingest mode
+    let (item, warnings) = apply_ingest_mode(db_service, item)
+        .await
+        .map_err(IngestRejection::Strict)?;
+
+    if validate {
+        let violations = validate_stac_item(&item);
+        if !violations.is_empty() {
+            return Err(IngestRejection::Stac(violations));
+        }
+    }
+
+    // Enforce the collection's item schema, if one is attached
+    validate_item(db_service, collection_id, &item)
+        .await
+        .map_err(|violations| IngestRejection::Schema {
+            collection_id: collection_id.to_string(),
+            violations,
+        })?;
+    Ok((item, warnings))
+}
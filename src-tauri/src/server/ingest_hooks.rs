@@ -0,0 +1,130 @@
+use crate::database::settings::{IngestHookCommand, IngestHookTimeoutSecs, Setting};
+use crate::database::DatabaseService;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// Largest hook output that is considered, in bytes
+const MAX_HOOK_OUTPUT: usize = 4 * 1024 * 1024;
+
+/// Why the ingest hook is run
+#[derive(Debug, Clone, Copy)]
+pub enum IngestEvent {
+    Create,
+    Update,
+}
+
+impl IngestEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            IngestEvent::Create => "create",
+            IngestEvent::Update => "update",
+        }
+    }
+}
+
+/// Runs the user's ingest hook (the `ingest_hook_command` setting) with the
+/// item JSON on stdin and returns the item with its properties amended by the
+/// hook.
+///
+/// The hook may print a JSON object to stdout; its `properties` member, or the
+/// object itself when it has none, is merged into the item's properties. Hooks
+/// that are not configured, fail, time out or print anything else leave the
+/// item unchanged so a broken script never blocks ingest.
+pub async fn run_ingest_hook(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    event: IngestEvent,
+    mut item: serde_json::Value,
+) -> serde_json::Value {
+    let command = db_service
+        .settings
+        .get::<IngestHookCommand>()
+        .await
+        .unwrap_or_else(|_| IngestHookCommand::default_value());
+    if command.trim().is_empty() {
+        return item;
+    }
+    let timeout = db_service
+        .settings
+        .get::<IngestHookTimeoutSecs>()
+        .await
+        .unwrap_or_else(|_| IngestHookTimeoutSecs::default_value());
+
+    match execute(&command, collection_id, event, &item, timeout).await {
+        Ok(Some(amendments)) => {
+            if let Some(properties) = item
+                .get_mut("properties")
+                .and_then(|p| p.as_object_mut())
+            {
+                for (key, value) in amendments {
+                    properties.insert(key, value);
+                }
+            }
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Ingest hook '{}' failed, item stored unchanged: {}", command, e),
+    }
+    item
+}
+
+/// Spawns the hook and returns the properties it wants to set, if any
+async fn execute(
+    command: &str,
+    collection_id: &str,
+    event: IngestEvent,
+    item: &serde_json::Value,
+    timeout: u64,
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>, String> {
+    // The command is run directly, not through a shell
+    let mut child = tokio::process::Command::new(command)
+        .env("ZENSTAC_EVENT", event.name())
+        .env("ZENSTAC_COLLECTION", collection_id)
+        .env(
+            "ZENSTAC_ITEM",
+            item.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+        )
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("could not start: {}", e))?;
+
+    let input = serde_json::to_vec(item).map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().ok_or("stdin unavailable")?;
+
+    let run = async move {
+        // Hooks that don't read their input close the pipe early; that is fine
+        let _ = stdin.write_all(&input).await;
+        drop(stdin);
+        child.wait_with_output().await
+    };
+    let output = tokio::time::timeout(Duration::from_secs(timeout), run)
+        .await
+        .map_err(|_| format!("timed out after {} s", timeout))?
+        .map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("exited with {}: {}", output.status, stderr.trim()));
+    }
+    if output.stdout.len() > MAX_HOOK_OUTPUT {
+        return Err(format!("output larger than {} bytes", MAX_HOOK_OUTPUT));
+    }
+    if output.stdout.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(None);
+    }
+
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("output is not JSON: {}", e))?;
+    let amendments = match value {
+        serde_json::Value::Object(mut object) => match object.remove("properties") {
+            Some(serde_json::Value::Object(properties)) => properties,
+            Some(_) => return Err("'properties' in the output is not an object".to_string()),
+            None => object,
+        },
+        _ => return Err("output is not a JSON object".to_string()),
+    };
+    Ok(Some(amendments))
+}
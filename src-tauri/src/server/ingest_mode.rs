@@ -0,0 +1,290 @@
+use crate::database::settings::{IngestMode, ItemIngestMode, PreserveOriginalDatetimes, Setting};
+use crate::database::DatabaseService;
+use crate::server::error::ApiError;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value};
+
+/// STAC version filled in for items that omit it
+const DEFAULT_STAC_VERSION: &str = "1.0.0";
+
+/// Properties defined as numbers by STAC common metadata and the common
+/// extensions; producers sometimes write them as strings
+const NUMERIC_PROPERTIES: &[&str] = &[
+    "gsd",
+    "eo:cloud_cover",
+    "eo:snow_cover",
+    "view:off_nadir",
+    "view:incidence_angle",
+    "view:azimuth",
+    "view:sun_azimuth",
+    "view:sun_elevation",
+    "sat:relative_orbit",
+    "sat:absolute_orbit",
+    "sar:resolution_range",
+    "sar:resolution_azimuth",
+    "sar:looks_range",
+    "sar:looks_azimuth",
+    "proj:epsg",
+];
+
+/// Properties holding RFC 3339 date-times
+const DATETIME_PROPERTIES: &[&str] = &[
+    "datetime",
+    "start_datetime",
+    "end_datetime",
+    "created",
+    "updated",
+];
+
+/// Property keeping the values of date-times rewritten to UTC, by property
+pub const ORIGINAL_DATETIMES_PROPERTY: &str = "zenstac:original_datetimes";
+
+/// Applies the configured ingest mode to an incoming item. Returns the item to
+/// store and the fix-ups made to it, or the problems found in strict mode.
+/// Date-times given with an offset are then stored in UTC in either mode.
+pub async fn apply_ingest_mode(
+    db_service: &DatabaseService,
+    item: Value,
+) -> Result<(Value, Vec<String>), Vec<String>> {
+    let mode = db_service
+        .settings
+        .get::<ItemIngestMode>()
+        .await
+        .unwrap_or_else(|_| ItemIngestMode::default_value());
+    let (mut item, fixes) = normalize_item(mode, item)?;
+    if let Some(Value::Object(properties)) = item.get_mut("properties") {
+        normalize_datetimes_to_utc(properties, preserve_original_datetimes(db_service).await);
+    }
+    Ok((item, fixes))
+}
+
+/// Whether date-times rewritten to UTC keep their original values
+pub async fn preserve_original_datetimes(db_service: &DatabaseService) -> bool {
+    db_service
+        .settings
+        .get::<PreserveOriginalDatetimes>()
+        .await
+        .unwrap_or_else(|_| PreserveOriginalDatetimes::default_value())
+}
+
+/// Rewrites the date-time properties written with an offset (`+09:00`,
+/// `-05:00`, `+00:00`) as UTC with a `Z`, so items sort and filter the same
+/// whichever timezone their producer used. This is not a repair: offsets are
+/// valid RFC 3339 and are accepted in strict mode. With `preserve` the values
+/// as sent are kept in `zenstac:original_datetimes`; a kept value that no
+/// longer denotes the stored instant is dropped.
+pub fn normalize_datetimes_to_utc(properties: &mut Map<String, Value>, preserve: bool) {
+    let mut originals = match properties.remove(ORIGINAL_DATETIMES_PROPERTY) {
+        Some(Value::Object(originals)) if preserve => originals,
+        _ => Map::new(),
+    };
+    for key in DATETIME_PROPERTIES {
+        let Some(Value::String(text)) = properties.get_mut(*key) else {
+            originals.remove(*key);
+            continue;
+        };
+        let Ok(datetime) = DateTime::parse_from_rfc3339(text) else {
+            originals.remove(*key);
+            continue;
+        };
+        if text.ends_with('Z') {
+            let same_instant = originals
+                .get(*key)
+                .and_then(|original| original.as_str())
+                .and_then(|original| DateTime::parse_from_rfc3339(original).ok())
+                .is_some_and(|original| original == datetime);
+            if !same_instant {
+                originals.remove(*key);
+            }
+            continue;
+        }
+        let utc = datetime
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        let original = std::mem::replace(text, utc);
+        originals.insert(key.to_string(), Value::String(original));
+    }
+    if preserve && !originals.is_empty() {
+        properties.insert(
+            ORIGINAL_DATETIMES_PROPERTY.to_string(),
+            Value::Object(originals),
+        );
+    }
+}
+
+/// Repairs the common ways items deviate from the spec: a missing `type` or
+/// `stac_version`, numbers and bboxes written as strings, date-times that
+/// are not RFC 3339 and a missing `assets` object. Each repair is described
+/// by one message; lenient mode returns them as warnings with the repaired
+/// item, strict mode rejects the item with them.
+pub fn normalize_item(
+    mode: IngestMode,
+    mut item: Value,
+) -> Result<(Value, Vec<String>), Vec<String>> {
+    let Some(object) = item.as_object_mut() else {
+        return Err(vec!["Item must be a JSON object".to_string()]);
+    };
+    let mut fixes = Vec::new();
+
+    match object.get("type").and_then(|t| t.as_str()) {
+        Some("Feature") => {}
+        Some(other) => {
+            fixes.push(format!("type was '{}', set to 'Feature'", other));
+            object.insert("type".to_string(), Value::from("Feature"));
+        }
+        None => {
+            fixes.push("type was missing, set to 'Feature'".to_string());
+            object.insert("type".to_string(), Value::from("Feature"));
+        }
+    }
+
+    if !object.get("stac_version").is_some_and(|v| v.is_string()) {
+        fixes.push(format!(
+            "stac_version was missing, set to '{}'",
+            DEFAULT_STAC_VERSION
+        ));
+        object.insert(
+            "stac_version".to_string(),
+            Value::from(DEFAULT_STAC_VERSION),
+        );
+    }
+
+    if let Some(Value::String(extension)) = object.get("stac_extensions") {
+        let extension = extension.clone();
+        fixes.push("stac_extensions was a string, wrapped in an array".to_string());
+        object.insert(
+            "stac_extensions".to_string(),
+            Value::Array(vec![Value::String(extension)]),
+        );
+    }
+
+    if let Some(bbox) = object.get_mut("bbox") {
+        normalize_bbox(bbox, &mut fixes);
+    }
+
+    if matches!(object.get("assets"), None | Some(Value::Null)) {
+        fixes.push("assets was missing, set to an empty object".to_string());
+        object.insert("assets".to_string(), Value::Object(Map::new()));
+    }
+
+    if let Some(Value::Object(properties)) = object.get_mut("properties") {
+        normalize_properties(properties, &mut fixes);
+    }
+
+    match mode {
+        IngestMode::Lenient => Ok((item, fixes)),
+        IngestMode::Strict if fixes.is_empty() => Ok((item, fixes)),
+        IngestMode::Strict => Err(fixes),
+    }
+}
+
+fn normalize_bbox(bbox: &mut Value, fixes: &mut Vec<String>) {
+    if let Value::String(text) = bbox {
+        let numbers: Option<Vec<f64>> = text
+            .split(',')
+            .map(|part| part.trim().parse::<f64>().ok())
+            .collect();
+        if let Some(numbers) = numbers.filter(|n| n.len() == 4 || n.len() == 6) {
+            fixes.push("bbox was a string, converted to an array of numbers".to_string());
+            *bbox = Value::from(numbers);
+        }
+        return;
+    }
+    if let Value::Array(values) = bbox {
+        let mut converted = false;
+        for value in values.iter_mut() {
+            if let Some(number) = numeric_string(value) {
+                *value = number;
+                converted = true;
+            }
+        }
+        if converted {
+            fixes.push("bbox contained numbers as strings, converted to numbers".to_string());
+        }
+    }
+}
+
+fn normalize_properties(properties: &mut Map<String, Value>, fixes: &mut Vec<String>) {
+    for key in NUMERIC_PROPERTIES {
+        if let Some(value) = properties.get_mut(*key) {
+            if let Some(number) = numeric_string(value) {
+                fixes.push(format!(
+                    "properties.{} was the string {}, converted to a number",
+                    key, value
+                ));
+                *value = number;
+            }
+        }
+    }
+
+    for key in DATETIME_PROPERTIES {
+        if let Some(Value::String(text)) = properties.get_mut(*key) {
+            if chrono::DateTime::parse_from_rfc3339(text).is_ok() {
+                continue;
+            }
+            if let Some(normalized) = normalize_datetime(text) {
+                fixes.push(format!(
+                    "properties.{} '{}' is not RFC 3339, rewritten as '{}'",
+                    key, text, normalized
+                ));
+                *text = normalized;
+            }
+        }
+    }
+}
+
+/// The number a string holds, keeping integers integral
+fn numeric_string(value: &Value) -> Option<Value> {
+    let text = value.as_str()?.trim();
+    if let Ok(integer) = text.parse::<i64>() {
+        return Some(Value::from(integer));
+    }
+    text.parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .map(Value::from)
+}
+
+/// Rewrites dates and date-times without an offset, taken as UTC, in RFC 3339
+fn normalize_datetime(text: &str) -> Option<String> {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    let text = text.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(
+            date.and_time(chrono::NaiveTime::MIN)
+                .and_utc()
+                .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        );
+    }
+    let naive = text
+        .strip_suffix(['Z', 'z'])
+        .unwrap_or(text)
+        .replacen([' ', 't'], "T", 1);
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(&naive, format).ok())
+        .map(|dt| dt.and_utc().to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+/// Adds the fix-ups made in lenient mode to a response body. They are not
+/// stored with the item.
+pub fn with_warnings(mut body: Value, warnings: &[String]) -> Value {
+    if !warnings.is_empty() {
+        if let Some(object) = body.as_object_mut() {
+            object.insert("warnings".to_string(), Value::from(warnings.to_vec()));
+        }
+    }
+    body
+}
+
+/// 400 response listing why strict mode rejected an item
+pub fn ingest_rejected_response(problems: &[String]) -> Response {
+    ApiError::bad_request(format!(
+        "Item is not conformant and the server is in strict ingest mode: {}",
+        problems.join("; ")
+    ))
+    .with_extension("problems", problems)
+    .into_response()
+}
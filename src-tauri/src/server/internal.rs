@@ -0,0 +1,163 @@
+use crate::config::Config;
+use crate::database::DatabaseService;
+use crate::server::auth::InternalRequest;
+use crate::server::explain::duration_ms;
+use crate::server::server::create_stac_router;
+use axum::body::Body;
+use axum::http::{HeaderName, HeaderValue, Method, Request, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+use tower::ServiceExt;
+
+/// A response as the in-app API console shows it
+#[derive(Debug, Serialize)]
+pub struct ApiExchange {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    /// JSON bodies parsed, anything else as text; `null` when empty
+    pub body: Value,
+    pub duration_ms: f64,
+}
+
+/// Runs a request through the STAC router in-process.
+///
+/// Used by the desktop UI so catalog access keeps working when the public
+/// HTTP server is stopped, rebound or otherwise unreachable. `path` is
+/// relative to the API version prefix (e.g. "/collections").
+pub async fn dispatch(
+    db_service: DatabaseService,
+    config: Config,
+    method: Method,
+    path: &str,
+    body: Option<Value>,
+) -> Result<Value, String> {
+    let uri = format!("{}{}", config.server.api_version, path);
+    let (status, _, bytes) = send(
+        db_service,
+        config,
+        method,
+        &uri,
+        &HashMap::new(),
+        body.map(|body| body.to_string()),
+    )
+    .await?;
+    let value = body_value(&bytes);
+
+    if !status.is_success() {
+        let description = value
+            .get("description")
+            .and_then(|d| d.as_str())
+            .map(|d| d.to_string())
+            .unwrap_or_else(|| status.to_string());
+        return Err(format!("API Error {}: {}", status.as_u16(), description));
+    }
+
+    Ok(value)
+}
+
+/// Runs a request typed into the in-app API console through the STAC router
+/// and returns the whole response, errors included.
+///
+/// `path` is relative to the API version prefix and may carry a query (e.g.
+/// "/search?limit=5"). The body is sent as given, as JSON unless the headers
+/// name another `Content-Type`, so malformed requests can be tried too.
+pub async fn execute(
+    db_service: DatabaseService,
+    config: Config,
+    method: &str,
+    path: &str,
+    headers: &HashMap<String, String>,
+    body: Option<String>,
+) -> Result<ApiExchange, String> {
+    let method = Method::from_bytes(method.trim().to_uppercase().as_bytes())
+        .map_err(|_| format!("Invalid method '{}'", method))?;
+    let uri = format!(
+        "{}/{}",
+        config.server.api_version,
+        path.trim_start_matches('/')
+    );
+
+    let started = Instant::now();
+    let (status, response_headers, bytes) =
+        send(db_service, config, method, &uri, headers, body).await?;
+    let duration = started.elapsed();
+
+    let mut headers = BTreeMap::new();
+    for (name, value) in response_headers.iter() {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        headers
+            .entry(name.as_str().to_string())
+            .and_modify(|joined: &mut String| {
+                joined.push_str(", ");
+                joined.push_str(&value);
+            })
+            .or_insert(value);
+    }
+
+    Ok(ApiExchange {
+        status: status.as_u16(),
+        headers,
+        body: body_value(&bytes),
+        duration_ms: duration_ms(duration),
+    })
+}
+
+/// Sends a request to a fresh router marked as coming from the desktop UI
+async fn send(
+    db_service: DatabaseService,
+    config: Config,
+    method: Method,
+    uri: &str,
+    headers: &HashMap<String, String>,
+    body: Option<String>,
+) -> Result<(StatusCode, axum::http::HeaderMap, axum::body::Bytes), String> {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .extension(InternalRequest);
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("Invalid header name '{}'", name))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("Invalid value for header '{}'", name))?;
+        builder = builder.header(name, value);
+    }
+    let body = match body {
+        Some(body) => {
+            let has_content_type = builder
+                .headers_ref()
+                .is_some_and(|headers| headers.contains_key("Content-Type"));
+            if !has_content_type {
+                builder = builder.header("Content-Type", "application/json");
+            }
+            Body::from(body)
+        }
+        None => Body::empty(),
+    };
+    let request = builder
+        .body(body)
+        .map_err(|e| format!("Invalid request: {}", e))?;
+
+    let response = create_stac_router(db_service, config.with_all_features())
+        .oneshot(request)
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    Ok((status, headers, bytes))
+}
+
+/// A response body as JSON, or as text when it is not JSON
+fn body_value(bytes: &[u8]) -> Value {
+    if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(bytes).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).to_string()))
+    }
+}
@@ -0,0 +1,159 @@
+use crate::database::DatabaseService;
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// A single schema violation: JSON pointer into the item and the reason
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SchemaViolation {
+    pub path: String,
+    pub message: String,
+}
+
+/// Violations as one line, e.g. `/properties/gsd: "x" is not of type "number"`
+pub fn describe_violations(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(|v| if v.path.is_empty() { v.message.clone() } else { format!("{}: {}", v.path, v.message) })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Checks that `schema` is a usable JSON Schema
+pub fn check_item_schema(schema: &serde_json::Value) -> Result<(), String> {
+    jsonschema::validator_for(schema)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid JSON Schema: {}", e))
+}
+
+/// Validates a full item document against the schema attached to its
+/// collection. Collections without a schema accept every item.
+pub async fn validate_item(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    item: &serde_json::Value,
+) -> Result<(), Vec<SchemaViolation>> {
+    let schema = match db_service.collections.get_item_schema(collection_id).await {
+        Ok(Some(schema)) => schema,
+        _ => return Ok(()),
+    };
+    let validator = match jsonschema::validator_for(&schema) {
+        Ok(validator) => validator,
+        Err(e) => {
+            return Err(vec![SchemaViolation {
+                path: String::new(),
+                message: format!("Collection schema is invalid: {}", e),
+            }])
+        }
+    };
+
+    let violations: Vec<SchemaViolation> = validator
+        .iter_errors(item)
+        .map(|error| SchemaViolation {
+            path: error.instance_path.to_string(),
+            message: error.to_string(),
+        })
+        .collect();
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// 400 response listing every schema violation
+pub fn schema_violation_response(collection_id: &str, violations: &[SchemaViolation]) -> Response {
+    ApiError::bad_request(format!(
+        "Item does not satisfy the schema of collection '{}': {}",
+        collection_id,
+        describe_violations(violations)
+    ))
+    .with_extension("violations", serde_json::json!(violations))
+    .into_response()
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Handler returning the item schema of a collection (GET /collections/:collection_id/schema)
+pub async fn get_collection_schema(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    match state.db_service.collections.get_by_id(&collection_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return ApiError::not_found(format!("Collection '{}' not found", collection_id))
+                .into_response()
+        }
+        Err(e) => {
+            return ApiError::internal(format!("Failed to get collection: {}", e)).into_response()
+        }
+    }
+
+    match state.db_service.collections.get_item_schema(&collection_id).await {
+        Ok(Some(schema)) => json_response(StatusCode::OK, schema),
+        Ok(None) => {
+            ApiError::not_found(format!("Collection '{}' has no item schema", collection_id))
+                .into_response()
+        }
+        Err(e) => ApiError::internal(format!("Failed to read item schema: {}", e)).into_response(),
+    }
+}
+
+/// Handler attaching an item schema to a collection (PUT /collections/:collection_id/schema).
+/// A `null` body removes the schema.
+pub async fn put_collection_schema(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+    Json(schema): Json<serde_json::Value>,
+) -> Response {
+    match set_collection_schema(&state.db_service, &collection_id, schema).await {
+        Ok(Some(schema)) => json_response(StatusCode::OK, schema),
+        Ok(None) => (StatusCode::NO_CONTENT, add_cors_headers(HeaderMap::new())).into_response(),
+        Err((status, description)) => ApiError::from_status(status, description).into_response(),
+    }
+}
+
+/// Stores or clears a collection's item schema after checking it compiles.
+/// Shared by the HTTP endpoint and the Tauri command.
+pub async fn set_collection_schema(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    schema: serde_json::Value,
+) -> Result<Option<serde_json::Value>, (StatusCode, String)> {
+    let schema = if schema.is_null() { None } else { Some(schema) };
+    if let Some(schema) = &schema {
+        check_item_schema(schema).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    match db_service
+        .collections
+        .set_item_schema(collection_id, schema.as_ref())
+        .await
+    {
+        Ok(true) => Ok(schema),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            format!("Collection '{}' not found", collection_id),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to save item schema: {}", e),
+        )),
+    }
+}
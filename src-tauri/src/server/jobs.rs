@@ -0,0 +1,327 @@
+use crate::database::settings::{JobCollectionConcurrency, JobConcurrency, Setting};
+use crate::database::SettingsRepository;
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use axum::{
+    extract::Path,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::oneshot;
+
+/// Jobs a queue runs at once when `job_concurrency` does not name it
+const DEFAULT_QUEUE_CONCURRENCY: usize = 2;
+
+/// Lifecycle of a background job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// File produced by a completed job
+#[derive(Debug, Clone, Serialize)]
+pub struct JobOutput {
+    #[serde(skip)]
+    pub path: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: u64,
+    /// Number of records written
+    pub count: usize,
+}
+
+/// A background job such as a search export
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    /// Queue the job waits in for a slot
+    pub queue: String,
+    /// Collection the job works on, which it shares its queue's slots with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    pub status: JobStatus,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<JobOutput>,
+    /// What a job without an output file did, e.g. the assets a re-hosting
+    /// job downloaded and those it could not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<serde_json::Value>,
+}
+
+/// In-process registry of background jobs. It outlives server restarts but
+/// not the application; job outputs stay on disk.
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<String, Job>>>,
+    queues: Arc<Mutex<HashMap<String, QueueState>>>,
+}
+
+/// How many jobs of a queue run at once
+#[derive(Debug, Clone, Copy)]
+pub struct JobLimits {
+    pub queue: usize,
+    pub per_collection: usize,
+}
+
+impl JobLimits {
+    /// Limits of `queue` from the `job_concurrency` and
+    /// `job_collection_concurrency` settings
+    pub async fn load(settings: &SettingsRepository, queue: &str) -> Self {
+        let queues = settings
+            .get::<JobConcurrency>()
+            .await
+            .unwrap_or_else(|_| JobConcurrency::default_value());
+        let per_collection = settings
+            .get::<JobCollectionConcurrency>()
+            .await
+            .unwrap_or_else(|_| JobCollectionConcurrency::default_value());
+        Self {
+            queue: queues.get(queue).copied().unwrap_or(DEFAULT_QUEUE_CONCURRENCY),
+            per_collection,
+        }
+    }
+}
+
+/// A job waiting for a slot in its queue
+struct Waiter {
+    collection: Option<String>,
+    wake: oneshot::Sender<()>,
+}
+
+/// Running and waiting jobs of a queue
+#[derive(Default)]
+struct QueueState {
+    limits: Option<JobLimits>,
+    running: usize,
+    running_by_collection: HashMap<String, usize>,
+    waiting: VecDeque<Waiter>,
+}
+
+impl QueueState {
+    fn limits(&self) -> JobLimits {
+        self.limits.unwrap_or(JobLimits {
+            queue: DEFAULT_QUEUE_CONCURRENCY,
+            per_collection: JobCollectionConcurrency::default_value(),
+        })
+    }
+
+    fn has_room(&self, collection: Option<&str>) -> bool {
+        let limits = self.limits();
+        self.running < limits.queue
+            && collection.is_none_or(|c| {
+                self.running_by_collection.get(c).copied().unwrap_or(0) < limits.per_collection
+            })
+    }
+
+    fn occupy(&mut self, collection: Option<&str>) {
+        self.running += 1;
+        if let Some(c) = collection {
+            *self.running_by_collection.entry(c.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    fn release(&mut self, collection: Option<&str>) {
+        self.running = self.running.saturating_sub(1);
+        if let Some(c) = collection {
+            if let Some(count) = self.running_by_collection.get_mut(c) {
+                *count -= 1;
+                if *count == 0 {
+                    self.running_by_collection.remove(c);
+                }
+            }
+        }
+    }
+
+    /// Starts waiting jobs while there is room, each time picking the one
+    /// whose collection has the fewest running jobs (oldest first among
+    /// equals), so one collection's backlog cannot starve the others
+    fn dispatch(&mut self) {
+        loop {
+            let next = self
+                .waiting
+                .iter()
+                .enumerate()
+                .filter(|(_, w)| self.has_room(w.collection.as_deref()))
+                .min_by_key(|(i, w)| {
+                    let running = w
+                        .collection
+                        .as_deref()
+                        .and_then(|c| self.running_by_collection.get(c).copied())
+                        .unwrap_or(0);
+                    (running, *i)
+                })
+                .map(|(i, _)| i);
+            let Some(index) = next else {
+                return;
+            };
+            let waiter = self.waiting.remove(index).unwrap();
+            self.occupy(waiter.collection.as_deref());
+            // A waiter whose task is gone gives its slot straight back
+            if waiter.wake.send(()).is_err() {
+                self.release(waiter.collection.as_deref());
+            }
+        }
+    }
+}
+
+/// A job's slot in its queue, freed on drop
+struct QueueSlot {
+    registry: JobRegistry,
+    queue: String,
+    collection: Option<String>,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        let mut queues = self.registry.queues.lock().unwrap();
+        if let Some(state) = queues.get_mut(&self.queue) {
+            state.release(self.collection.as_deref());
+            state.dispatch();
+        }
+    }
+}
+
+impl JobRegistry {
+    /// Registers a new queued job of `kind`
+    pub fn create(&self, kind: &str, queue: &str, collection: Option<&str>) -> Job {
+        let job = Job {
+            id: uuid::Uuid::now_v7().to_string(),
+            kind: kind.to_string(),
+            queue: queue.to_string(),
+            collection: collection.map(str::to_string),
+            status: JobStatus::Queued,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+            error: None,
+            output: None,
+            report: None,
+        };
+        self.jobs.lock().unwrap().insert(job.id.clone(), job.clone());
+        job
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn start(&self, id: &str) {
+        self.update(id, |job| job.status = JobStatus::Running);
+    }
+
+    pub fn complete(&self, id: &str, output: Option<JobOutput>) {
+        self.update(id, |job| {
+            job.status = JobStatus::Completed;
+            job.finished_at = Some(chrono::Utc::now().to_rfc3339());
+            job.output = output;
+        });
+    }
+
+    /// Replaces the report of a job, e.g. with its progress so far
+    pub fn report(&self, id: &str, report: serde_json::Value) {
+        self.update(id, |job| job.report = Some(report));
+    }
+
+    pub fn fail(&self, id: &str, error: String) {
+        self.update(id, |job| {
+            job.status = JobStatus::Failed;
+            job.finished_at = Some(chrono::Utc::now().to_rfc3339());
+            job.error = Some(error);
+        });
+    }
+
+    /// Runs `task` for `job` in the background once its queue has a free
+    /// slot within `limits`
+    pub fn spawn<F>(&self, job: &Job, limits: JobLimits, task: F)
+    where
+        F: std::future::Future<Output = Result<Option<JobOutput>, String>> + Send + 'static,
+    {
+        let registry = self.clone();
+        let id = job.id.clone();
+        let queue = job.queue.clone();
+        let collection = job.collection.clone();
+        tokio::spawn(async move {
+            let _slot = registry.acquire(&queue, collection, limits).await;
+            registry.start(&id);
+            match task.await {
+                Ok(output) => registry.complete(&id, output),
+                Err(e) => registry.fail(&id, e),
+            }
+        });
+    }
+
+    /// Waits for a slot in `queue`
+    async fn acquire(&self, queue: &str, collection: Option<String>, limits: JobLimits) -> QueueSlot {
+        let wait = {
+            let mut queues = self.queues.lock().unwrap();
+            let state = queues.entry(queue.to_string()).or_default();
+            // Settings changes apply from the next job on
+            state.limits = Some(limits);
+            if state.waiting.is_empty() && state.has_room(collection.as_deref()) {
+                state.occupy(collection.as_deref());
+                None
+            } else {
+                let (wake, woken) = oneshot::channel();
+                state.waiting.push_back(Waiter {
+                    collection: collection.clone(),
+                    wake,
+                });
+                // A raised limit may let waiting jobs start
+                state.dispatch();
+                Some(woken)
+            }
+        };
+        if let Some(woken) = wait {
+            // The sender is only dropped after sending
+            let _ = woken.await;
+        }
+        QueueSlot {
+            registry: self.clone(),
+            queue: queue.to_string(),
+            collection,
+        }
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(id) {
+            f(job);
+        }
+    }
+}
+
+/// The application's job registry
+pub fn registry() -> &'static JobRegistry {
+    static REGISTRY: OnceLock<JobRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(JobRegistry::default)
+}
+
+/// Handler for the status of a background job, e.g. a trash purge: `202`
+/// while it is queued or running, `200` once it has finished
+/// (GET /jobs/:job_id)
+pub async fn job_status(Path(job_id): Path<String>) -> Response {
+    let Some(job) = registry().get(&job_id) else {
+        return ApiError::not_found(format!("Job '{}' not found", job_id)).into_response();
+    };
+    let status = match job.status {
+        JobStatus::Queued | JobStatus::Running => StatusCode::ACCEPTED,
+        JobStatus::Completed | JobStatus::Failed => StatusCode::OK,
+    };
+    let mut headers = add_cors_headers(HeaderMap::new());
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    (status, headers, serde_json::to_string(&job).unwrap()).into_response()
+}
@@ -0,0 +1,84 @@
+use crate::database::settings::{BrowserUrl, RootRedirect, RootRedirectTarget, Setting};
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+
+/// Whether a request comes from a browser: its `Accept` header names
+/// `text/html` ahead of any JSON type and it did not ask for a format with `f`
+pub fn wants_html(headers: &HeaderMap, uri: &Uri) -> bool {
+    let explicit_format = uri
+        .query()
+        .is_some_and(|q| q.split('&').any(|pair| pair.starts_with("f=")));
+    if explicit_format {
+        return false;
+    }
+    let accept = headers
+        .get("Accept")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match (accept.find("text/html"), accept.find("json")) {
+        (Some(html), Some(json)) => html < json,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Redirect for a browser requesting the catalog root, as configured by the
+/// `root_redirect` setting; `None` serves the catalog JSON
+pub async fn landing_redirect(
+    state: &AppState,
+    headers: &HeaderMap,
+    uri: &Uri,
+) -> Option<Response> {
+    if !wants_html(headers, uri) {
+        return None;
+    }
+    let target = state
+        .db_service
+        .settings
+        .get::<RootRedirect>()
+        .await
+        .unwrap_or_else(|_| RootRedirect::default_value());
+    let location = match target {
+        RootRedirectTarget::Catalog => return None,
+        RootRedirectTarget::Browser => state
+            .db_service
+            .settings
+            .get::<BrowserUrl>()
+            .await
+            .unwrap_or_else(|_| BrowserUrl::default_value()),
+        RootRedirectTarget::Landing => ServerConfig::from_config(&state.config).api_html_href(),
+    };
+    Some(redirect(&location))
+}
+
+/// Handler for the domain root (GET /) when the API is mounted under a
+/// version path: browsers go where `root_redirect` says, everything else to
+/// the catalog
+pub async fn root_redirect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    if let Some(response) = landing_redirect(&state, &headers, &uri).await {
+        return response;
+    }
+    redirect(&ServerConfig::from_config(&state.config).root_href())
+}
+
+/// Temporary redirect, since the target is configurable
+fn redirect(location: &str) -> Response {
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(location) {
+        headers.insert("Location", value);
+    }
+    headers.insert("Vary", HeaderValue::from_static("Accept"));
+    headers = add_cors_headers(headers);
+    (StatusCode::FOUND, headers).into_response()
+}
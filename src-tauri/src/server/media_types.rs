@@ -0,0 +1,135 @@
+use crate::database::settings::{MediaTypeOverrides, Setting};
+use crate::database::DatabaseService;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Media type of files whose extension is unknown
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Built-in media types by extension, following the STAC best practices.
+/// Multi-part extensions take precedence over their last suffix.
+const BUILT_IN: &[(&str, &str)] = &[
+    // Raster
+    (
+        "cog.tif",
+        "image/tiff; application=geotiff; profile=cloud-optimized",
+    ),
+    (
+        "cog.tiff",
+        "image/tiff; application=geotiff; profile=cloud-optimized",
+    ),
+    ("tif", "image/tiff; application=geotiff"),
+    ("tiff", "image/tiff; application=geotiff"),
+    ("jp2", "image/jp2"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    // Multidimensional
+    ("zarr", "application/vnd+zarr"),
+    ("nc", "application/netcdf"),
+    ("h5", "application/x-hdf5"),
+    ("hdf5", "application/x-hdf5"),
+    ("hdf", "application/x-hdf"),
+    // Vector
+    ("geojson", "application/geo+json"),
+    ("gpkg", "application/geopackage+sqlite3"),
+    ("fgb", "application/vnd.flatgeobuf"),
+    ("parquet", "application/vnd.apache.parquet"),
+    ("kml", "application/vnd.google-earth.kml+xml"),
+    ("kmz", "application/vnd.google-earth.kmz"),
+    ("pmtiles", "application/vnd.pmtiles"),
+    // Point clouds
+    ("copc.laz", "application/vnd.laszip+copc"),
+    ("laz", "application/vnd.laszip"),
+    ("las", "application/vnd.las"),
+    // Documents and metadata
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("pdf", "application/pdf"),
+    ("html", "text/html"),
+    ("txt", "text/plain"),
+    ("csv", "text/csv"),
+    ("zip", "application/zip"),
+];
+
+/// Maps asset file names to media types: the built-in STAC types plus the
+/// `media_types` setting, which adds extensions or replaces built-in ones
+#[derive(Debug, Clone)]
+pub struct MediaTypeRegistry {
+    types: BTreeMap<String, String>,
+}
+
+impl Default for MediaTypeRegistry {
+    fn default() -> Self {
+        Self::with_overrides(BTreeMap::new())
+    }
+}
+
+impl MediaTypeRegistry {
+    /// The built-in types with `overrides` applied on top
+    pub fn with_overrides(overrides: BTreeMap<String, String>) -> Self {
+        let mut types: BTreeMap<String, String> = BUILT_IN
+            .iter()
+            .map(|(extension, media_type)| (extension.to_string(), media_type.to_string()))
+            .collect();
+        types.extend(overrides);
+        Self { types }
+    }
+
+    /// Registry including the user's `media_types` setting
+    pub async fn load(db_service: &DatabaseService) -> Self {
+        let overrides = db_service
+            .settings
+            .get::<MediaTypeOverrides>()
+            .await
+            .unwrap_or_else(|_| MediaTypeOverrides::default_value());
+        Self::with_overrides(overrides)
+    }
+
+    /// Media type of a file, matched on its longest known extension
+    /// (`scene.cog.tif` before `scene.tif`); octet-stream when unknown
+    pub fn for_path(&self, path: impl AsRef<Path>) -> &str {
+        let name = path
+            .as_ref()
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        name.match_indices('.')
+            .map(|(i, _)| &name[i + 1..])
+            .find_map(|extension| self.types.get(extension))
+            .map_or(OCTET_STREAM, |media_type| media_type.as_str())
+    }
+
+    /// Media type to record for an uploaded file: the one the client sent,
+    /// unless it is missing or generic, in which case it is looked up by name
+    pub fn for_upload(&self, file_name: &str, declared: Option<&str>) -> String {
+        match declared.map(str::trim) {
+            Some(declared) if !declared.is_empty() && declared != OCTET_STREAM => {
+                declared.to_string()
+            }
+            _ => self.for_path(file_name).to_string(),
+        }
+    }
+
+    /// Every known extension and its media type
+    pub fn entries(&self) -> &BTreeMap<String, String> {
+        &self.types
+    }
+}
+
+/// Roles given to a new asset: `thumbnail` for the thumbnail key, `data` for
+/// GeoTIFFs and non-image files and `overview` for other images
+pub fn asset_roles(asset_key: &str, media_type: &str) -> Vec<&'static str> {
+    if asset_key == "thumbnail" {
+        vec!["thumbnail"]
+    } else if media_type.contains("application=geotiff") {
+        vec!["data"]
+    } else if media_type.starts_with("image/") {
+        vec!["overview"]
+    } else {
+        vec!["data"]
+    }
+}
@@ -1,3 +1,4 @@
+use crate::server::error::ApiError;
 use axum::{
     http::{HeaderMap, HeaderValue, Method, StatusCode, Uri},
     response::{IntoResponse, Response},
@@ -8,11 +9,19 @@ pub fn add_cors_headers(mut headers: HeaderMap) -> HeaderMap {
     headers.insert("Access-Control-Allow-Origin", HeaderValue::from_static("*"));
     headers.insert(
         "Access-Control-Allow-Methods",
-        HeaderValue::from_static("GET, POST, OPTIONS"),
+        HeaderValue::from_static("GET, POST, PUT, PATCH, DELETE, OPTIONS"),
     );
     headers.insert(
         "Access-Control-Allow-Headers",
-        HeaderValue::from_static("Content-Type, Authorization"),
+        HeaderValue::from_static(
+            "Content-Type, Authorization, X-API-Key, If-Match, If-None-Match, If-Modified-Since, Range, If-Range, Upload-Offset, Upload-Checksum",
+        ),
+    );
+    headers.insert(
+        "Access-Control-Expose-Headers",
+        HeaderValue::from_static(
+            "ETag, Last-Modified, Location, Upload-Offset, Accept-Ranges, Content-Range, Content-Length",
+        ),
     );
     headers.insert(
         "Cache-Control",
@@ -63,3 +72,244 @@ pub async fn trailing_slash_redirect(uri: Uri, method: Method) -> Result<Respons
 
     Ok(Response::new(axum::body::Body::empty()))
 }
+
+/// Middleware handling the OGC API `f` format parameter.
+///
+/// `f=json` / `f=geojson` are accepted on every route (clients such as owslib
+/// always send one); `f=json` also switches GeoJSON responses to plain
+/// `application/json`. `/api` and `/api.html` redirect to each other when the
+/// other format is requested. Anything else is rejected with 400.
+pub async fn format_negotiation(
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let format = req.uri().query().and_then(|query| {
+        query.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            if key != "f" {
+                return None;
+            }
+            urlencoding::decode(value).ok().map(|v| v.to_lowercase())
+        })
+    });
+
+    let Some(format) = format else {
+        return next.run(req).await;
+    };
+
+    let path = req.uri().path().to_string();
+    let is_html = HTML_FORMATS.contains(&format.as_str());
+    let is_json = JSON_FORMATS.contains(&format.as_str());
+
+    // The redirect target names the format, so `f` is dropped and the
+    // other parameters are kept
+    let other_params = req.uri().query().map(without_format).unwrap_or_default();
+    if path.ends_with("/api") && is_html {
+        return format_redirect(&format!("{}.html{}", path, other_params));
+    }
+    if path.ends_with("/api.html") && is_json {
+        return format_redirect(&format!(
+            "{}{}",
+            path.trim_end_matches(".html"),
+            other_params
+        ));
+    }
+
+    let is_api_html = is_html && path.ends_with("/api.html");
+    if !is_json && !is_api_html {
+        return ApiError::invalid_parameter(format!(
+            "Unsupported format '{}'. Supported values for f are: json, geojson",
+            format
+        ))
+        .into_response();
+    }
+
+    let mut response = next.run(req).await;
+
+    // f=json asks for plain JSON rather than GeoJSON
+    if format == "json" || format == "application/json" {
+        let is_geojson = response
+            .headers()
+            .get("Content-Type")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.starts_with("application/geo+json"))
+            .unwrap_or(false);
+        if is_geojson {
+            response.headers_mut().insert(
+                "Content-Type",
+                HeaderValue::from_static("application/json; charset=utf-8"),
+            );
+        }
+    }
+
+    response
+}
+
+/// Values of `f` asking for JSON or GeoJSON
+const JSON_FORMATS: &[&str] = &[
+    "json",
+    "geojson",
+    "application/json",
+    "application/geo+json",
+];
+/// Values of `f` asking for HTML
+const HTML_FORMATS: &[&str] = &["html", "text/html"];
+
+/// Query string without its `f` parameters, with its leading `?`; empty
+/// when nothing else remains
+fn without_format(query: &str) -> String {
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some("f"))
+        .collect();
+    if kept.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", kept.join("&"))
+    }
+}
+
+fn format_redirect(location: &str) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert("Location", HeaderValue::from_str(location).unwrap());
+    headers = add_cors_headers(headers);
+    (StatusCode::SEE_OTHER, headers).into_response()
+}
+
+/// Body size limits applied by the router, reported back to clients on 413
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimits {
+    pub json: u64,
+    pub upload: u64,
+}
+
+/// Middleware replacing bare 413 responses with a JSON error that states the
+/// limit that was hit and the setting controlling it
+pub async fn payload_too_large(
+    axum::extract::State(limits): axum::extract::State<BodyLimits>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let is_upload = req.uri().path().contains("/upload/");
+    let response = next.run(req).await;
+
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+
+    let (limit, kind, setting) = if is_upload {
+        (limits.upload, "asset uploads", "max_upload_body_bytes")
+    } else {
+        (limits.json, "JSON requests", "max_json_body_bytes")
+    };
+
+    ApiError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "PayloadTooLarge",
+        format!(
+            "Request body exceeds the {} byte limit for {}. Increase the '{}' setting to accept larger bodies.",
+            limit, kind, setting
+        ),
+    )
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    /// Routes answering like the catalog: GeoJSON for items, JSON elsewhere
+    fn router() -> Router {
+        let geojson = || async {
+            (
+                [("Content-Type", "application/geo+json")],
+                r#"{"type":"FeatureCollection","features":[]}"#,
+            )
+        };
+        let json = || async { ([("Content-Type", "application/json")], "{}") };
+        Router::new()
+            .route("/v1/collections", get(json))
+            .route("/v1/collections/:id/items", get(geojson))
+            .route("/v1/api", get(json))
+            .route("/v1/api.html", get(|| async { "<html></html>" }))
+            .layer(axum::middleware::from_fn(format_negotiation))
+    }
+
+    async fn send(uri: &str) -> Response {
+        router()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn header<'a>(response: &'a Response, name: &str) -> &'a str {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    }
+
+    // Requests as sent by OWSLib, QGIS and pystac-client
+    #[tokio::test]
+    async fn accepts_json_formats_sent_by_clients() {
+        for uri in [
+            "/v1/collections?f=json",
+            "/v1/collections?f=application%2Fjson",
+            "/v1/collections/c/items?f=json&limit=10",
+            "/v1/collections/c/items?limit=100&f=geojson",
+            "/v1/collections/c/items?bbox=-10,40,10,50&f=application/geo+json",
+        ] {
+            assert_eq!(send(uri).await.status(), StatusCode::OK, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn f_json_serves_geojson_as_plain_json() {
+        let plain = send("/v1/collections/c/items?f=json").await;
+        assert!(header(&plain, "Content-Type").starts_with("application/json"));
+        let geojson = send("/v1/collections/c/items?f=geojson").await;
+        assert_eq!(header(&geojson, "Content-Type"), "application/geo+json");
+        let unset = send("/v1/collections/c/items?limit=10").await;
+        assert_eq!(header(&unset, "Content-Type"), "application/geo+json");
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_formats_with_a_problem_document() {
+        for uri in [
+            "/v1/collections?f=notjson",
+            "/v1/collections?f=xml",
+            "/v1/collections?f=html",
+        ] {
+            let response = send(uri).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            assert_eq!(
+                header(&response, "Content-Type"),
+                crate::server::error::PROBLEM_JSON
+            );
+            let body = axum::body::to_bytes(response.into_body(), 4096)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["code"], "InvalidParameterValue");
+        }
+    }
+
+    #[tokio::test]
+    async fn api_document_redirects_keep_other_parameters() {
+        let to_html = send("/v1/api?f=html").await;
+        assert_eq!(to_html.status(), StatusCode::SEE_OTHER);
+        assert_eq!(header(&to_html, "Location"), "/v1/api.html");
+
+        let to_html = send("/v1/api?lang=de&f=text%2Fhtml&x=1").await;
+        assert_eq!(header(&to_html, "Location"), "/v1/api.html?lang=de&x=1");
+
+        let to_json = send("/v1/api.html?f=json&lang=de").await;
+        assert_eq!(to_json.status(), StatusCode::SEE_OTHER);
+        assert_eq!(header(&to_json, "Location"), "/v1/api?lang=de");
+
+        assert_eq!(send("/v1/api.html?f=html").await.status(), StatusCode::OK);
+    }
+}
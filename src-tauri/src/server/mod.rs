@@ -1,6 +1,63 @@
+pub mod access_log;
+pub mod acme;
+pub mod aggregations;
+pub mod asset_cleanup;
+pub mod asset_sizes;
+pub mod assets;
+pub mod auth;
+pub mod bandwidth;
+pub mod basemap;
+pub mod branding;
+pub mod bulk_update;
+pub mod capabilities;
+pub mod catalog_checks;
+pub mod checksums;
+pub mod compact;
+pub mod computed_properties;
+pub mod conditional;
+pub mod conformance;
+pub mod cql2;
+pub mod demo;
+pub mod error;
+pub mod exports;
+pub mod explain;
+pub mod federation;
+pub mod feeds;
+pub mod files;
+pub mod geometry;
 pub mod handlers;
 pub mod helpers;
+pub mod i18n;
+pub mod ids;
+pub mod indexed_properties;
+pub mod ingest;
+pub mod ingest_hooks;
+pub mod ingest_mode;
+pub mod internal;
+pub mod jobs;
+pub mod landing;
+pub mod item_schema;
+pub mod media_types;
 pub mod middleware;
+pub mod network;
 pub mod openapi;
+pub mod outbound;
+pub mod plugins;
+pub mod processing;
+pub mod queryables;
+pub mod ranges;
+pub mod rate_limit;
+pub mod rehost;
+pub mod relations;
+pub mod response_cache;
+pub mod retention;
 pub mod server;
+pub mod stac_validation;
+pub mod statistics;
+pub mod sync;
+pub mod thumbnails;
+pub mod trash;
+pub mod upload_scan;
+pub mod upload_sessions;
 pub mod utils;
+pub mod virtual_collections;
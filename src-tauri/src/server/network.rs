@@ -0,0 +1,185 @@
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+const STUN_BINDING_RESPONSE: u16 = 0x0101;
+const STUN_ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const STUN_ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+/// A candidate value for the `server_external_address` setting
+#[derive(Debug, Clone, Serialize)]
+pub struct AddressSuggestion {
+    pub address: String,
+    /// "lan" for local interfaces, "public" for the STUN-reported address
+    pub source: String,
+    pub interface: Option<String>,
+}
+
+/// Random identifier of this process, returned by /health so the self-check
+/// can tell this server apart from anything else answering on the URL
+pub fn instance_id() -> &'static str {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Non-loopback addresses of the local network interfaces, IPv4 first
+pub fn lan_addresses() -> Vec<(String, IpAddr)> {
+    let mut addresses: Vec<(String, IpAddr)> = if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|iface| !iface.is_loopback())
+        .map(|iface| (iface.name.clone(), iface.ip()))
+        .filter(|(_, ip)| match ip {
+            IpAddr::V4(v4) => !v4.is_link_local(),
+            // fe80::/10 addresses need a scope id and are useless in URLs
+            IpAddr::V6(v6) => (v6.segments()[0] & 0xffc0) != 0xfe80,
+        })
+        .collect();
+    addresses.sort_by_key(|(_, ip)| ip.is_ipv6());
+    addresses
+}
+
+/// Asks a STUN server (`host:port`) for the public address of this machine
+pub async fn stun_public_ip(server: &str) -> Result<IpAddr, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    socket
+        .connect(server)
+        .await
+        .map_err(|e| format!("Failed to reach STUN server {}: {}", server, e))?;
+
+    let mut transaction_id = [0u8; 12];
+    transaction_id.copy_from_slice(&uuid::Uuid::new_v4().as_bytes()[..12]);
+
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&STUN_BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(&transaction_id);
+
+    let mut buf = [0u8; 576];
+    // UDP may drop packets, so retry a few times before giving up
+    for _ in 0..3 {
+        socket
+            .send(&request)
+            .await
+            .map_err(|e| format!("Failed to send STUN request: {}", e))?;
+        if let Ok(Ok(len)) = tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut buf)).await {
+            return parse_binding_response(&buf[..len], &transaction_id);
+        }
+    }
+    Err(format!("No response from STUN server {}", server))
+}
+
+fn parse_binding_response(data: &[u8], transaction_id: &[u8; 12]) -> Result<IpAddr, String> {
+    if data.len() < 20
+        || u16::from_be_bytes([data[0], data[1]]) != STUN_BINDING_RESPONSE
+        || data[8..20] != transaction_id[..]
+    {
+        return Err("Invalid STUN response".to_string());
+    }
+
+    let mut mapped = None;
+    let mut offset = 20;
+    while offset + 4 <= data.len() {
+        let attr_type = u16::from_be_bytes([data[offset], data[offset + 1]]);
+        let attr_len = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        let value = match data.get(offset + 4..offset + 4 + attr_len) {
+            Some(value) => value,
+            None => break,
+        };
+        match attr_type {
+            STUN_ATTR_XOR_MAPPED_ADDRESS => {
+                if let Some(ip) = decode_address(value, Some(transaction_id)) {
+                    return Ok(ip);
+                }
+            }
+            STUN_ATTR_MAPPED_ADDRESS => mapped = decode_address(value, None),
+            _ => {}
+        }
+        // Attributes are padded to a multiple of 4 bytes
+        offset += 4 + attr_len.div_ceil(4) * 4;
+    }
+
+    mapped.ok_or_else(|| "STUN response did not contain a mapped address".to_string())
+}
+
+/// Decodes a (XOR-)MAPPED-ADDRESS value; `xor` carries the transaction id
+fn decode_address(value: &[u8], xor: Option<&[u8; 12]>) -> Option<IpAddr> {
+    let cookie = STUN_MAGIC_COOKIE.to_be_bytes();
+    match value.get(1)? {
+        0x01 if value.len() >= 8 => {
+            let mut octets = [value[4], value[5], value[6], value[7]];
+            if xor.is_some() {
+                for (byte, mask) in octets.iter_mut().zip(cookie) {
+                    *byte ^= mask;
+                }
+            }
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        0x02 if value.len() >= 20 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&value[4..20]);
+            if let Some(transaction_id) = xor {
+                let mask: Vec<u8> = cookie.iter().chain(transaction_id.iter()).copied().collect();
+                for (byte, mask) in octets.iter_mut().zip(mask) {
+                    *byte ^= mask;
+                }
+            }
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+/// LAN addresses plus, when a STUN server is configured, the public address
+pub async fn suggest_external_addresses(stun_server: &str) -> Vec<AddressSuggestion> {
+    let mut suggestions: Vec<AddressSuggestion> = lan_addresses()
+        .into_iter()
+        .map(|(name, ip)| AddressSuggestion {
+            address: ip.to_string(),
+            source: "lan".to_string(),
+            interface: Some(name),
+        })
+        .collect();
+
+    if !stun_server.trim().is_empty() {
+        if let Ok(ip) = stun_public_ip(stun_server.trim()).await {
+            suggestions.push(AddressSuggestion {
+                address: ip.to_string(),
+                source: "public".to_string(),
+                interface: None,
+            });
+        }
+    }
+
+    suggestions
+}
+
+/// Requests `{api_url}/health` and checks that the answer comes from this process
+pub async fn self_check(api_url: &str) -> Result<(), String> {
+    let url = format!("{}/health", api_url.trim_end_matches('/'));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("{} is not reachable: {}", url, e))?;
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    let body: serde_json::Value = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("{} did not return a ZenSTAC health response: {}", url, e))?;
+
+    if body.get("instance").and_then(|v| v.as_str()) != Some(instance_id()) {
+        return Err(format!("{} is served by a different server", url));
+    }
+    Ok(())
+}
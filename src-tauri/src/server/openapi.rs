@@ -1,3 +1,4 @@
+use crate::config::ServerConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -44,6 +45,7 @@ pub struct PathItem {
     pub get: Option<Operation>,
     pub post: Option<Operation>,
     pub put: Option<Operation>,
+    pub patch: Option<Operation>,
     pub delete: Option<Operation>,
 }
 
@@ -132,6 +134,7 @@ impl OpenApiSpec {
             }),
             post: None,
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -147,6 +150,7 @@ impl OpenApiSpec {
             }),
             post: None,
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -162,6 +166,7 @@ impl OpenApiSpec {
             }),
             post: None,
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -170,9 +175,55 @@ impl OpenApiSpec {
             get: Some(Operation {
                 tags: vec!["Collections".to_string()],
                 summary: "List Collections".to_string(),
-                description: "Returns a list of all collections in the STAC catalog.".to_string(),
+                description: "Returns the collections in the STAC catalog, optionally filtered by extent and free text and paged with next/prev links.".to_string(),
                 operation_id: "getCollections".to_string(),
-                parameters: None,
+                parameters: Some(vec![
+                    Parameter {
+                        name: "limit".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "integer".to_string(),
+                        },
+                        description: "Maximum number of collections to return; all when omitted".to_string(),
+                    },
+                    Parameter {
+                        name: "token".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Pagination token from a next or prev link".to_string(),
+                    },
+                    Parameter {
+                        name: "bbox".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Bounding box (min_lon,min_lat,max_lon,max_lat) the collection's spatial extent must intersect".to_string(),
+                    },
+                    Parameter {
+                        name: "datetime".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Date-time or interval the collection's temporal extent must overlap".to_string(),
+                    },
+                    Parameter {
+                        name: "q".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Free-text search over collection titles, descriptions and keywords".to_string(),
+                    }
+                ]),
                 request_body: None,
                 responses: create_standard_responses("collections"),
             }),
@@ -181,7 +232,7 @@ impl OpenApiSpec {
                 summary: "Create Collection".to_string(),
                 description: "Creates a new collection in the STAC catalog.".to_string(),
                 operation_id: "createCollection".to_string(),
-                parameters: None,
+                parameters: Some(vec![validate_parameter()]),
                 request_body: Some(RequestBody {
                     required: true,
                     content: Content {
@@ -199,9 +250,10 @@ impl OpenApiSpec {
                         }),
                     },
                 }),
-                responses: create_standard_responses("collection"),
+                responses: create_insert_responses("collection"),
             }),
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -240,7 +292,9 @@ impl OpenApiSpec {
                             param_type: "string".to_string(),
                         },
                         description: "The collection identifier".to_string(),
-                    }
+                    },
+                    if_match_parameter(),
+                    validate_parameter(),
                 ]),
                 request_body: Some(RequestBody {
                     required: true,
@@ -259,7 +313,40 @@ impl OpenApiSpec {
                         }),
                     },
                 }),
-                responses: create_standard_responses("collection"),
+                responses: create_conditional_write_responses("collection"),
+            }),
+            patch: Some(Operation {
+                tags: vec!["Collections".to_string()],
+                summary: "Patch Collection".to_string(),
+                description: "Updates members of an existing collection with a JSON Merge Patch (RFC 7396).".to_string(),
+                operation_id: "patchCollection".to_string(),
+                parameters: Some(vec![
+                    Parameter {
+                        name: "collection_id".to_string(),
+                        location: "path".to_string(),
+                        required: true,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "The collection identifier".to_string(),
+                    },
+                    if_match_parameter(),
+                    validate_parameter(),
+                ]),
+                request_body: Some(RequestBody {
+                    required: true,
+                    content: Content {
+                        application_json: Some(JsonContent {
+                            schema: Schema {
+                                ref_path: "#/components/schemas/collection".to_string(),
+                            },
+                            example: serde_json::json!({
+                                "title": "Renamed Example Collection"
+                            }),
+                        }),
+                    },
+                }),
+                responses: create_conditional_write_responses("collection"),
             }),
             delete: Some(Operation {
                 tags: vec!["Collections".to_string()],
@@ -335,7 +422,8 @@ impl OpenApiSpec {
                             param_type: "string".to_string(),
                         },
                         description: "The collection identifier".to_string(),
-                    }
+                    },
+                    validate_parameter(),
                 ]),
                 request_body: Some(RequestBody {
                     required: true,
@@ -360,9 +448,54 @@ impl OpenApiSpec {
                         }),
                     },
                 }),
-                responses: create_standard_responses("item"),
+                responses: create_insert_responses("item"),
+            }),
+            put: None,
+            patch: None,
+            delete: None,
+        });
+
+        paths.insert("/collections/{collection_id}/items/update".to_string(), PathItem {
+            get: None,
+            post: Some(Operation {
+                tags: vec!["Items".to_string()],
+                summary: "Update Matching Items".to_string(),
+                description: "Applies a JSON Merge Patch to every item of the collection matching a CQL2 filter, in one transaction. With dry_run the matching items are counted and checked without being changed.".to_string(),
+                operation_id: "updateItems".to_string(),
+                parameters: Some(vec![
+                    Parameter {
+                        name: "collection_id".to_string(),
+                        location: "path".to_string(),
+                        required: true,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "The collection identifier".to_string(),
+                    }
+                ]),
+                request_body: Some(RequestBody {
+                    required: true,
+                    content: Content {
+                        application_json: Some(JsonContent {
+                            schema: Schema {
+                                ref_path: "#/components/schemas/bulkUpdate".to_string(),
+                            },
+                            example: serde_json::json!({
+                                "filter": "platform = 'sentinel2a'",
+                                "patch": {
+                                    "properties": {
+                                        "platform": "sentinel-2a"
+                                    }
+                                },
+                                "dry_run": true
+                            }),
+                        }),
+                    },
+                }),
+                responses: create_standard_responses("bulkUpdateReport"),
             }),
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -419,7 +552,9 @@ impl OpenApiSpec {
                             param_type: "string".to_string(),
                         },
                         description: "The item identifier".to_string(),
-                    }
+                    },
+                    if_match_parameter(),
+                    validate_parameter(),
                 ]),
                 request_body: Some(RequestBody {
                     required: true,
@@ -444,7 +579,51 @@ impl OpenApiSpec {
                         }),
                     },
                 }),
-                responses: create_standard_responses("item"),
+                responses: create_conditional_write_responses("item"),
+            }),
+            patch: Some(Operation {
+                tags: vec!["Items".to_string()],
+                summary: "Patch Item".to_string(),
+                description: "Updates members of an existing item with a JSON Merge Patch (RFC 7396).".to_string(),
+                operation_id: "patchItem".to_string(),
+                parameters: Some(vec![
+                    Parameter {
+                        name: "collection_id".to_string(),
+                        location: "path".to_string(),
+                        required: true,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "The collection identifier".to_string(),
+                    },
+                    Parameter {
+                        name: "item_id".to_string(),
+                        location: "path".to_string(),
+                        required: true,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "The item identifier".to_string(),
+                    },
+                    if_match_parameter(),
+                    validate_parameter(),
+                ]),
+                request_body: Some(RequestBody {
+                    required: true,
+                    content: Content {
+                        application_json: Some(JsonContent {
+                            schema: Schema {
+                                ref_path: "#/components/schemas/item".to_string(),
+                            },
+                            example: serde_json::json!({
+                                "properties": {
+                                    "eo:cloud_cover": 12.5
+                                }
+                            }),
+                        }),
+                    },
+                }),
+                responses: create_conditional_write_responses("item"),
             }),
             delete: Some(Operation {
                 tags: vec!["Items".to_string()],
@@ -519,6 +698,15 @@ impl OpenApiSpec {
                             param_type: "integer".to_string(),
                         },
                         description: "The maximum number of results to return".to_string(),
+                    },
+                    Parameter {
+                        name: "q".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Free-text search over titles, descriptions, keywords and properties; comma-separated terms match if any does".to_string(),
                     }
                 ]),
                 request_body: None,
@@ -549,6 +737,7 @@ impl OpenApiSpec {
                 responses: create_standard_responses("itemCollection"),
             }),
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -565,6 +754,7 @@ impl OpenApiSpec {
             }),
             post: None,
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -580,6 +770,7 @@ impl OpenApiSpec {
             }),
             post: None,
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -605,6 +796,283 @@ impl OpenApiSpec {
             }),
             post: None,
             put: None,
+            patch: None,
+            delete: None,
+        });
+
+        // Queryables endpoints
+        paths.insert("/queryables".to_string(), PathItem {
+            get: Some(Operation {
+                tags: vec!["Queryables".to_string()],
+                summary: "Get Queryables".to_string(),
+                description: "Returns a JSON Schema of the properties searches can filter on, across all collections.".to_string(),
+                operation_id: "getQueryables".to_string(),
+                parameters: None,
+                request_body: None,
+                responses: create_standard_responses("queryables"),
+            }),
+            post: None,
+            put: None,
+            patch: None,
+            delete: None,
+        });
+
+        paths.insert("/collections/{collection_id}/queryables".to_string(), PathItem {
+            get: Some(Operation {
+                tags: vec!["Queryables".to_string()],
+                summary: "Get Collection Queryables".to_string(),
+                description: "Returns a JSON Schema of the properties the collection's items can be filtered on.".to_string(),
+                operation_id: "getCollectionQueryables".to_string(),
+                parameters: Some(vec![
+                    Parameter {
+                        name: "collection_id".to_string(),
+                        location: "path".to_string(),
+                        required: true,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "The collection identifier".to_string(),
+                    }
+                ]),
+                request_body: None,
+                responses: create_standard_responses("queryables"),
+            }),
+            post: None,
+            put: None,
+            patch: None,
+            delete: None,
+        });
+
+        // Aggregations endpoints
+        paths.insert("/aggregations".to_string(), PathItem {
+            get: Some(Operation {
+                tags: vec!["Aggregations".to_string()],
+                summary: "Get Aggregations".to_string(),
+                description: "Returns counts, datetime histograms and property term frequencies of the matching items of all or the listed collections.".to_string(),
+                operation_id: "getAggregations".to_string(),
+                parameters: Some(vec![
+                    Parameter {
+                        name: "collections".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Comma-separated collection IDs".to_string(),
+                    },
+                    Parameter {
+                        name: "aggregations".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Comma-separated aggregations: total_count, datetime_min, datetime_max, datetime_frequency, collection_frequency, cloud_cover_frequency or <property>_frequency".to_string(),
+                    },
+                    Parameter {
+                        name: "bbox".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Bounding box in format min_lon,min_lat,max_lon,max_lat".to_string(),
+                    },
+                    Parameter {
+                        name: "datetime".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Date/time range in RFC 3339 format".to_string(),
+                    },
+                    Parameter {
+                        name: "query".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Query extension object as JSON".to_string(),
+                    },
+                    Parameter {
+                        name: "q".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Free-text search".to_string(),
+                    },
+                    Parameter {
+                        name: "datetime_frequency_interval".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Bucket size of datetime_frequency: year, month, day or hour".to_string(),
+                    },
+                    Parameter {
+                        name: "cloud_cover_frequency_interval".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "number".to_string(),
+                        },
+                        description: "Bucket width of cloud_cover_frequency in percent".to_string(),
+                    }
+                ]),
+                request_body: None,
+                responses: create_standard_responses("aggregations"),
+            }),
+            post: None,
+            put: None,
+            patch: None,
+            delete: None,
+        });
+
+        paths.insert("/collections/{collection_id}/aggregations".to_string(), PathItem {
+            get: Some(Operation {
+                tags: vec!["Aggregations".to_string()],
+                summary: "Get Collection Aggregations".to_string(),
+                description: "Returns counts, datetime histograms and property term frequencies of the collection's matching items.".to_string(),
+                operation_id: "getCollectionAggregations".to_string(),
+                parameters: Some(vec![
+                    Parameter {
+                        name: "collection_id".to_string(),
+                        location: "path".to_string(),
+                        required: true,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "The collection identifier".to_string(),
+                    },
+                    Parameter {
+                        name: "aggregations".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Comma-separated aggregations: total_count, datetime_min, datetime_max, datetime_frequency, collection_frequency, cloud_cover_frequency or <property>_frequency".to_string(),
+                    },
+                    Parameter {
+                        name: "bbox".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Bounding box in format min_lon,min_lat,max_lon,max_lat".to_string(),
+                    },
+                    Parameter {
+                        name: "datetime".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Date/time range in RFC 3339 format".to_string(),
+                    },
+                    Parameter {
+                        name: "query".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Query extension object as JSON".to_string(),
+                    },
+                    Parameter {
+                        name: "q".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Free-text search".to_string(),
+                    },
+                    Parameter {
+                        name: "datetime_frequency_interval".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Bucket size of datetime_frequency: year, month, day or hour".to_string(),
+                    },
+                    Parameter {
+                        name: "cloud_cover_frequency_interval".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "number".to_string(),
+                        },
+                        description: "Bucket width of cloud_cover_frequency in percent".to_string(),
+                    }
+                ]),
+                request_body: None,
+                responses: create_standard_responses("aggregations"),
+            }),
+            post: None,
+            put: None,
+            patch: None,
+            delete: None,
+        });
+
+        // Item footprint endpoint
+        paths.insert("/collections/{collection_id}/items/{item_id}/footprint".to_string(), PathItem {
+            get: Some(Operation {
+                tags: vec!["Items".to_string()],
+                summary: "Get Item Footprint".to_string(),
+                description: "Returns the item's geometry alone, simplified for map overlays and optionally in web mercator.".to_string(),
+                operation_id: "getItemFootprint".to_string(),
+                parameters: Some(vec![
+                    Parameter {
+                        name: "collection_id".to_string(),
+                        location: "path".to_string(),
+                        required: true,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "The collection identifier".to_string(),
+                    },
+                    Parameter {
+                        name: "item_id".to_string(),
+                        location: "path".to_string(),
+                        required: true,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "The item identifier".to_string(),
+                    },
+                    Parameter {
+                        name: "tolerance".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "number".to_string(),
+                        },
+                        description: "Simplification tolerance in degrees; 0 keeps every vertex".to_string(),
+                    },
+                    Parameter {
+                        name: "crs".to_string(),
+                        location: "query".to_string(),
+                        required: false,
+                        schema: ParameterSchema {
+                            param_type: "string".to_string(),
+                        },
+                        description: "Output CRS: EPSG:4326 (default) or EPSG:3857".to_string(),
+                    }
+                ]),
+                request_body: None,
+                responses: create_standard_responses("footprint"),
+            }),
+            post: None,
+            put: None,
+            patch: None,
             delete: None,
         });
 
@@ -663,6 +1131,7 @@ impl OpenApiSpec {
                 responses: create_standard_responses("asset"),
             }),
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -706,6 +1175,7 @@ impl OpenApiSpec {
             }),
             post: None,
             put: None,
+            patch: None,
             delete: None,
         });
 
@@ -823,6 +1293,99 @@ impl OpenApiSpec {
             },
         }
     }
+
+    /// Removes the paths and operations of feature groups disabled in the server config
+    pub fn retain_enabled(&mut self, server: &ServerConfig) {
+        if !server.enable_transactions {
+            for path in self.paths.values_mut() {
+                path.put = None;
+                path.patch = None;
+                path.delete = None;
+            }
+            for path in [
+                "/collections",
+                "/collections/{collection_id}/items",
+                "/collections/{collection_id}/items/update",
+            ] {
+                if let Some(item) = self.paths.get_mut(path) {
+                    item.post = None;
+                }
+            }
+        }
+        if !server.enable_uploads {
+            self.paths.remove("/upload/{collection_id}/{item_id}/{asset_key}");
+        }
+        if !server.enable_search {
+            for path in [
+                "/search",
+                "/sortables",
+                "/collections/sortables",
+                "/collections/{collection_id}/sortables",
+                "/queryables",
+                "/aggregations",
+                "/collections/{collection_id}/aggregations",
+            ] {
+                self.paths.remove(path);
+            }
+        }
+        self.paths.retain(|_, item| {
+            item.get.is_some()
+                || item.post.is_some()
+                || item.put.is_some()
+                || item.patch.is_some()
+                || item.delete.is_some()
+        });
+    }
+}
+
+/// `validate` flag of collection and item creates and updates
+fn validate_parameter() -> Parameter {
+    Parameter {
+        name: "validate".to_string(),
+        location: "query".to_string(),
+        required: false,
+        schema: ParameterSchema {
+            param_type: "boolean".to_string(),
+        },
+        description: "false skips the STAC checks; violations are otherwise answered with 400 listing each one".to_string(),
+    }
+}
+
+/// `If-Match` header of collection and item replacements and patches
+fn if_match_parameter() -> Parameter {
+    Parameter {
+        name: "If-Match".to_string(),
+        location: "header".to_string(),
+        required: false,
+        schema: ParameterSchema {
+            param_type: "string".to_string(),
+        },
+        description: "ETag of the version being replaced, as returned by GET; required of network clients".to_string(),
+    }
+}
+
+/// Standard responses plus the conflict of an id that is already taken
+fn create_insert_responses(schema_name: &str) -> HashMap<String, Response> {
+    let mut responses = create_standard_responses(schema_name);
+    responses.insert("409".to_string(), Response {
+        description: "A resource with this id already exists".to_string(),
+        content: None,
+    });
+    responses
+}
+
+/// Standard responses plus the precondition failures of a conditional write
+fn create_conditional_write_responses(schema_name: &str) -> HashMap<String, Response> {
+    let mut responses = create_standard_responses(schema_name);
+    responses.insert("412".to_string(), Response {
+        description: "The resource changed since the If-Match version was read".to_string(),
+        content: None,
+    });
+    responses.insert("428".to_string(), Response {
+        description: "If-Match is missing".to_string(),
+        content: None,
+    });
+    responses
 }
 
 fn create_standard_responses(schema_name: &str) -> HashMap<String, Response> {
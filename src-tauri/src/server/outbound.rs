@@ -0,0 +1,89 @@
+use crate::database::settings::{OutboundNoProxy, OutboundProxy, Setting};
+use crate::database::DatabaseService;
+use reqwest::{NoProxy, Proxy};
+use std::sync::Mutex;
+
+/// Proxy configuration an outbound client was built with
+#[derive(Clone, PartialEq, Eq)]
+struct ProxySettings {
+    /// Empty for the system proxy, `none`, or a proxy URL with secrets resolved
+    proxy: String,
+    no_proxy: String,
+}
+
+/// HTTP client for requests leaving this server (basemap tiles, peer sync,
+/// federated search, catalog checks). It goes through the configured proxy and is rebuilt
+/// when the proxy settings change; callers set their own timeouts per request.
+pub async fn http_client(db_service: &DatabaseService) -> Result<reqwest::Client, String> {
+    static CLIENT: Mutex<Option<(ProxySettings, reqwest::Client)>> = Mutex::new(None);
+
+    let proxy = db_service
+        .settings
+        .get::<OutboundProxy>()
+        .await
+        .unwrap_or_else(|_| OutboundProxy::default_value());
+    let settings = ProxySettings {
+        proxy: db_service.secrets.expand(&proxy).await?,
+        no_proxy: db_service
+            .settings
+            .get::<OutboundNoProxy>()
+            .await
+            .unwrap_or_else(|_| OutboundNoProxy::default_value()),
+    };
+
+    let mut cached = CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((built_with, client)) = cached.as_ref() {
+        if *built_with == settings {
+            return Ok(client.clone());
+        }
+    }
+    let client = build_client(&settings)?;
+    *cached = Some((settings, client.clone()));
+    Ok(client)
+}
+
+fn build_client(settings: &ProxySettings) -> Result<reqwest::Client, String> {
+    // Proxies are always set explicitly so the no_proxy list applies to the
+    // system proxy as well
+    let mut builder = reqwest::Client::builder()
+        // Tile providers such as OpenStreetMap require an identifying user agent
+        .user_agent(concat!("ZenSTAC/", env!("CARGO_PKG_VERSION")))
+        .no_proxy();
+
+    let mut no_proxy = settings.no_proxy.clone();
+    match settings.proxy.as_str() {
+        "none" => {}
+        "" => {
+            if let Some(env_list) = env_value(&["NO_PROXY", "no_proxy"]) {
+                no_proxy = format!("{},{}", no_proxy, env_list);
+            }
+            if let Some(url) = env_value(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"])
+            {
+                let proxy = Proxy::https(&url)
+                    .map_err(|e| format!("Invalid system proxy {}: {}", url, e))?;
+                builder = builder.proxy(proxy.no_proxy(NoProxy::from_string(&no_proxy)));
+            }
+            if let Some(url) = env_value(&["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]) {
+                let proxy = Proxy::http(&url)
+                    .map_err(|e| format!("Invalid system proxy {}: {}", url, e))?;
+                builder = builder.proxy(proxy.no_proxy(NoProxy::from_string(&no_proxy)));
+            }
+        }
+        url => {
+            let proxy = Proxy::all(url).map_err(|e| format!("Invalid outbound proxy: {}", e))?;
+            builder = builder.proxy(proxy.no_proxy(NoProxy::from_string(&no_proxy)));
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+fn env_value(names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| {
+        std::env::var(name)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+    })
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use wasmtime::{Caller, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/// Largest asset passed to plugins; bigger files are skipped
+const MAX_INPUT_BYTES: usize = 64 * 1024 * 1024;
+/// Linear memory a plugin may grow to
+const MAX_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+/// Instructions budget per extraction
+const FUEL_PER_CALL: u64 = 2_000_000_000;
+
+struct HostState {
+    plugin: String,
+    limits: StoreLimits,
+}
+
+fn engine() -> Result<&'static Engine, String> {
+    static ENGINE: OnceLock<Result<Engine, String>> = OnceLock::new();
+    ENGINE
+        .get_or_init(|| {
+            let mut config = wasmtime::Config::new();
+            config.consume_fuel(true);
+            Engine::new(&config).map_err(|e| e.to_string())
+        })
+        .as_ref()
+        .map_err(|e| e.clone())
+}
+
+/// Compiled modules, recompiled when the file changes
+fn load_module(path: &Path) -> Result<Module, String> {
+    static MODULES: OnceLock<Mutex<HashMap<PathBuf, (SystemTime, Module)>>> = OnceLock::new();
+    let modified = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| e.to_string())?;
+
+    let cache = MODULES.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some((cached_at, module)) = cache.lock().unwrap().get(path) {
+        if *cached_at == modified {
+            return Ok(module.clone());
+        }
+    }
+
+    let module = Module::from_file(engine()?, path).map_err(|e| e.to_string())?;
+    cache
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), (modified, module.clone()));
+    Ok(module)
+}
+
+/// Plugins in the directory, in file name order
+pub fn list_plugins(plugins_dir: &Path) -> Vec<PathBuf> {
+    let mut plugins: Vec<PathBuf> = std::fs::read_dir(plugins_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "wasm"))
+                .collect()
+        })
+        .unwrap_or_default();
+    plugins.sort();
+    plugins
+}
+
+/// Runs every plugin on an asset file and merges the properties they emit;
+/// later plugins win on conflicts. Plugin failures are logged and skipped.
+///
+/// A plugin exports `memory`, `alloc(len) -> ptr` and
+/// `extract(name_ptr, name_len, data_ptr, data_len) -> i64`, which returns
+/// `(ptr << 32) | len` of a JSON object of properties or 0 to pass. The only
+/// host import is `zenstac.log(ptr, len)`; there is no filesystem, network or
+/// clock access, and memory and instructions are capped per call.
+pub async fn extract_properties(
+    plugins_dir: PathBuf,
+    file_name: String,
+    data: Vec<u8>,
+) -> serde_json::Map<String, serde_json::Value> {
+    if data.len() > MAX_INPUT_BYTES {
+        return serde_json::Map::new();
+    }
+    let plugins = list_plugins(&plugins_dir);
+    if plugins.is_empty() {
+        return serde_json::Map::new();
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let mut properties = serde_json::Map::new();
+        for plugin in plugins {
+            match run_plugin(&plugin, &file_name, &data) {
+                Ok(Some(extracted)) => properties.extend(extracted),
+                Ok(None) => {}
+                Err(e) => eprintln!("Plugin {} failed on {}: {}", plugin.display(), file_name, e),
+            }
+        }
+        properties
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Runs the plugins on an asset file on disk. The file is only read when a
+/// plugin is installed and it is small enough to hand to them.
+pub async fn extract_file_properties(
+    plugins_dir: PathBuf,
+    file_name: String,
+    path: &Path,
+) -> serde_json::Map<String, serde_json::Value> {
+    if list_plugins(&plugins_dir).is_empty() {
+        return serde_json::Map::new();
+    }
+    match tokio::fs::metadata(path).await {
+        Ok(metadata) if metadata.len() <= MAX_INPUT_BYTES as u64 => {}
+        _ => return serde_json::Map::new(),
+    }
+    match tokio::fs::read(path).await {
+        Ok(data) => extract_properties(plugins_dir, file_name, data).await,
+        Err(_) => serde_json::Map::new(),
+    }
+}
+
+/// Merges extracted properties into an item's properties
+pub fn apply_properties(
+    item_properties: &mut serde_json::Value,
+    extracted: serde_json::Map<String, serde_json::Value>,
+) {
+    if let Some(properties) = item_properties.as_object_mut() {
+        properties.extend(extracted);
+    }
+}
+
+fn run_plugin(
+    path: &Path,
+    file_name: &str,
+    data: &[u8],
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>, String> {
+    let engine = engine()?;
+    let module = load_module(path)?;
+    let plugin = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut store = Store::new(
+        engine,
+        HostState {
+            plugin,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY_BYTES)
+                .instances(1)
+                .build(),
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(FUEL_PER_CALL).map_err(|e| e.to_string())?;
+
+    let mut linker: Linker<HostState> = Linker::new(engine);
+    linker
+        .func_wrap(
+            "zenstac",
+            "log",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                let Some(memory) = caller.get_export("memory").and_then(|e| e.into_memory()) else {
+                    return;
+                };
+                let bytes = memory
+                    .data(&caller)
+                    .get(ptr as u32 as usize..(ptr as u32 as usize).saturating_add(len as u32 as usize))
+                    .map(|b| String::from_utf8_lossy(b).into_owned());
+                if let Some(message) = bytes {
+                    eprintln!("[plugin {}] {}", caller.data().plugin, message);
+                }
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .map_err(|e| e.to_string())?;
+    let (name_ptr, name_len) = write_input(&instance, &mut store, file_name.as_bytes())?;
+    let (data_ptr, data_len) = write_input(&instance, &mut store, data)?;
+
+    let extract = instance
+        .get_typed_func::<(i32, i32, i32, i32), i64>(&mut store, "extract")
+        .map_err(|e| e.to_string())?;
+    let packed = extract
+        .call(&mut store, (name_ptr, name_len, data_ptr, data_len))
+        .map_err(|e| e.to_string())?;
+    if packed == 0 {
+        return Ok(None);
+    }
+
+    let out_ptr = (packed as u64 >> 32) as usize;
+    let out_len = (packed as u64 & 0xffff_ffff) as usize;
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or("plugin does not export memory")?;
+    let output = memory
+        .data(&store)
+        .get(out_ptr..out_ptr.saturating_add(out_len))
+        .ok_or("plugin returned an output outside its memory")?;
+
+    match serde_json::from_slice::<serde_json::Value>(output) {
+        Ok(serde_json::Value::Object(properties)) => Ok(Some(properties)),
+        Ok(_) => Err("output is not a JSON object".to_string()),
+        Err(e) => Err(format!("output is not JSON: {}", e)),
+    }
+}
+
+/// Copies bytes into plugin memory through its `alloc` export
+fn write_input(
+    instance: &Instance,
+    store: &mut Store<HostState>,
+    bytes: &[u8],
+) -> Result<(i32, i32), String> {
+    let len = i32::try_from(bytes.len()).map_err(|_| "input too large".to_string())?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&mut *store, "alloc")
+        .map_err(|e| e.to_string())?;
+    let ptr = alloc.call(&mut *store, len).map_err(|e| e.to_string())?;
+    let memory = instance
+        .get_memory(&mut *store, "memory")
+        .ok_or("plugin does not export memory")?;
+    memory
+        .write(&mut *store, ptr as u32 as usize, bytes)
+        .map_err(|e| e.to_string())?;
+    Ok((ptr, len))
+}
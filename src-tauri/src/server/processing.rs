@@ -0,0 +1,94 @@
+use crate::database::item_links::{ItemLink, ItemRelation};
+use crate::database::{DatabaseService, DbItem};
+use serde_json::{json, Value};
+
+/// STAC processing extension schema
+pub const PROCESSING_EXTENSION: &str =
+    "https://stac-extensions.github.io/processing/v1.2.0/schema.json";
+
+/// How an item was produced by an internal job (import, COG conversion,
+/// thumbnailing, harvest, ...)
+#[derive(Debug, Clone, Default)]
+pub struct ProcessingRecord {
+    /// Job that produced the item, recorded in `processing:software`
+    pub job: String,
+    /// Free-text description of the processing steps (`processing:lineage`)
+    pub lineage: Option<String>,
+    /// Catalog items the new item was derived from, as (collection, item)
+    pub derived_from: Vec<(String, String)>,
+    /// Sources outside the catalog, e.g. the original URL of a harvested item
+    pub derived_from_hrefs: Vec<String>,
+}
+
+/// Stamps the processing extension fields on an item: `processing:software`,
+/// `processing:datetime` and `processing:lineage`, plus `derived_from` links
+/// for sources outside the catalog. Catalog sources are recorded with
+/// [`record_lineage`] once the item is stored.
+pub fn stamp_processing(item: &mut DbItem, record: &ProcessingRecord) {
+    if let Some(properties) = item.properties.as_object_mut() {
+        properties.insert(
+            "processing:software".to_string(),
+            json!({
+                "zenstac": env!("CARGO_PKG_VERSION"),
+                format!("zenstac-{}", record.job): env!("CARGO_PKG_VERSION")
+            }),
+        );
+        properties.insert(
+            "processing:datetime".to_string(),
+            json!(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+        );
+        if let Some(lineage) = &record.lineage {
+            properties.insert("processing:lineage".to_string(), json!(lineage));
+        }
+    }
+
+    let mut extensions: Vec<Value> = item
+        .stac_extensions
+        .as_ref()
+        .and_then(|e| e.as_array().cloned())
+        .unwrap_or_default();
+    if !extensions.iter().any(|e| e.as_str() == Some(PROCESSING_EXTENSION)) {
+        extensions.push(json!(PROCESSING_EXTENSION));
+    }
+    item.stac_extensions = Some(Value::Array(extensions));
+
+    if !record.derived_from_hrefs.is_empty() {
+        let mut links: Vec<Value> = item
+            .links
+            .as_ref()
+            .and_then(|l| l.as_array().cloned())
+            .unwrap_or_default();
+        for href in &record.derived_from_hrefs {
+            let exists = links.iter().any(|l| {
+                l.get("rel").and_then(|r| r.as_str()) == Some("derived_from")
+                    && l.get("href").and_then(|h| h.as_str()) == Some(href)
+            });
+            if !exists {
+                links.push(json!({ "href": href, "rel": "derived_from" }));
+            }
+        }
+        item.links = Some(Value::Array(links));
+    }
+}
+
+/// Records `derived_from` relationships from a stored item to the catalog
+/// items it was produced from
+pub async fn record_lineage(
+    db_service: &DatabaseService,
+    item: &DbItem,
+    record: &ProcessingRecord,
+) -> rusqlite::Result<()> {
+    for (collection_id, item_id) in &record.derived_from {
+        db_service
+            .item_links
+            .add(&ItemLink {
+                source_collection: item.collection_id.clone(),
+                source_item: item.id.clone(),
+                rel: ItemRelation::DerivedFrom,
+                target_collection: collection_id.clone(),
+                target_item: item_id.clone(),
+            })
+            .await?;
+    }
+    Ok(())
+}
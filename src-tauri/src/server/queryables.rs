@@ -0,0 +1,185 @@
+use crate::server::conformance::{ConformanceRegistry, FeatureGroup, LandingLink};
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Collection queryables are always served; the catalog-wide ones, linked
+/// from the landing page, come with search
+pub fn register_conformance(registry: &mut ConformanceRegistry) {
+    registry.register(
+        FeatureGroup::Core,
+        &["http://www.opengis.net/spec/ogcapi-features-3/1.0/conf/queryables"],
+    );
+    registry.register_link(
+        FeatureGroup::Search,
+        LandingLink {
+            rel: "http://www.opengis.net/def/rel/ogc/1.0/queryables",
+            path: "/queryables",
+            media_type: "application/schema+json",
+            title: "Queryables",
+        },
+    );
+}
+
+/// Properties every item can be filtered on, whatever its collection stores,
+/// including the asset fields answered from the asset index
+fn core_queryables() -> Map<String, Value> {
+    let mut properties = Map::new();
+    properties.insert(
+        "id".to_string(),
+        json!({ "title": "Item ID", "type": "string" }),
+    );
+    properties.insert(
+        "collection".to_string(),
+        json!({ "title": "Collection ID", "type": "string" }),
+    );
+    properties.insert(
+        "geometry".to_string(),
+        json!({ "title": "Geometry", "$ref": "https://geojson.org/schema/Geometry.json" }),
+    );
+    properties.insert(
+        "datetime".to_string(),
+        json!({ "title": "Datetime", "type": "string", "format": "date-time" }),
+    );
+    properties.insert(
+        "assets.key".to_string(),
+        json!({ "title": "Key of an asset", "type": "string" }),
+    );
+    properties.insert(
+        "assets.type".to_string(),
+        json!({ "title": "Media type of an asset", "type": "string" }),
+    );
+    properties.insert(
+        "assets.roles".to_string(),
+        json!({ "title": "Role of an asset", "type": "string" }),
+    );
+    properties.insert(
+        "assets.file:size".to_string(),
+        json!({ "title": "File size of an asset in bytes", "type": "integer" }),
+    );
+    properties
+}
+
+/// JSON Schema of a property from the SQLite JSON types its values take
+fn property_schema(name: &str, sqlite_types: &BTreeSet<String>) -> Value {
+    let mut types = BTreeSet::new();
+    for sqlite_type in sqlite_types {
+        types.insert(match sqlite_type.as_str() {
+            "integer" if !sqlite_types.contains("real") => "integer",
+            "integer" | "real" => "number",
+            "true" | "false" => "boolean",
+            "text" => "string",
+            "array" => "array",
+            _ => "object",
+        });
+    }
+    let mut schema = match types.len() {
+        1 => json!({ "type": types.first() }),
+        _ => json!({ "type": types }),
+    };
+    let timestamp = name.ends_with("datetime") || name == "created" || name == "updated";
+    if timestamp && types.len() == 1 && types.contains("string") {
+        schema["format"] = json!("date-time");
+    }
+    schema
+}
+
+/// Queryables document for the items of `collection_ids`: the core
+/// properties and every property their items store
+async fn queryables_schema(
+    state: &AppState,
+    collection_ids: &[String],
+    id: String,
+    title: String,
+) -> Result<Value, String> {
+    let stored: BTreeMap<String, BTreeSet<String>> = state
+        .db_service
+        .items
+        .property_types(collection_ids)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut properties = core_queryables();
+    for (name, sqlite_types) in &stored {
+        if !properties.contains_key(name) {
+            properties.insert(name.clone(), property_schema(name, sqlite_types));
+        }
+    }
+    Ok(json!({
+        "$schema": "https://json-schema.org/draft/2019-09/schema",
+        "$id": id,
+        "type": "object",
+        "title": title,
+        "properties": properties,
+        "additionalProperties": true
+    }))
+}
+
+fn schema_response(schema: Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/schema+json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (headers, serde_json::to_string(&schema).unwrap()).into_response()
+}
+
+fn queryables_error(e: impl std::fmt::Display) -> ApiError {
+    eprintln!("Failed to derive queryables: {}", e);
+    ApiError::internal("Failed to derive queryables")
+}
+
+/// Handler for the properties searches can filter on, across all collections
+/// (GET /queryables)
+pub async fn queryables(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let collections = state
+        .db_service
+        .collections
+        .get_all()
+        .await
+        .map_err(queryables_error)?;
+    let collection_ids: Vec<String> = collections.into_iter().map(|c| c.id).collect();
+    let server_config = ServerConfig::from_config(&state.config);
+    let schema = queryables_schema(
+        &state,
+        &collection_ids,
+        server_config.href("/queryables"),
+        "Queryables for all collections".to_string(),
+    )
+    .await
+    .map_err(queryables_error)?;
+    Ok(schema_response(schema))
+}
+
+/// Handler for the properties a collection's items can be filtered on
+/// (GET /collections/:collection_id/queryables)
+pub async fn collection_queryables(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    state
+        .db_service
+        .collections
+        .get_by_id(&collection_id)
+        .await
+        .map_err(queryables_error)?
+        .ok_or_else(|| ApiError::not_found(format!("Collection '{}' not found", collection_id)))?;
+    let server_config = ServerConfig::from_config(&state.config);
+    let schema = queryables_schema(
+        &state,
+        std::slice::from_ref(&collection_id),
+        format!("{}/queryables", server_config.collection_href(&collection_id)),
+        format!("Queryables for {}", collection_id),
+    )
+    .await
+    .map_err(queryables_error)?;
+    Ok(schema_response(schema))
+}
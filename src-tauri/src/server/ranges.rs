@@ -0,0 +1,73 @@
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+
+/// Part of a file a GET asks for with its `Range` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// No usable range: the whole file is sent with 200
+    Full,
+    /// Bytes `start..=end`, sent with 206 Partial Content
+    Partial { start: u64, end: u64 },
+    /// A range outside the file, answered with 416
+    Unsatisfiable,
+}
+
+/// Range requested of a file of `len` bytes last modified at `modified`.
+/// Only a single `bytes` range is honored; several ranges, other units and
+/// malformed headers get the whole file, as RFC 9110 allows. An `If-Range`
+/// date other than the file's `Last-Modified` also gets the whole file, so a
+/// client resuming a download never mixes two versions of it. Assets carry
+/// no entity tag, so an `If-Range` tag never matches.
+pub fn requested_range(headers: &HeaderMap, len: u64, modified: &DateTime<Utc>) -> ByteRange {
+    let Some(range) = headers.get("Range").and_then(|v| v.to_str().ok()) else {
+        return ByteRange::Full;
+    };
+    if let Some(if_range) = headers.get("If-Range").and_then(|v| v.to_str().ok()) {
+        let unchanged = DateTime::parse_from_rfc2822(if_range)
+            .is_ok_and(|date| date.timestamp() == modified.timestamp());
+        if !unchanged {
+            return ByteRange::Full;
+        }
+    }
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (first, last) = (first.trim(), last.trim());
+
+    if first.is_empty() {
+        // `bytes=-N`: the last N bytes
+        return match last.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial {
+                start: len.saturating_sub(suffix),
+                end: len - 1,
+            },
+            Err(_) => ByteRange::Full,
+        };
+    }
+    let Ok(start) = first.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    let end = if last.is_empty() {
+        None
+    } else {
+        match last.parse::<u64>() {
+            Ok(end) if end >= start => Some(end),
+            _ => return ByteRange::Full,
+        }
+    };
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial {
+        start,
+        end: end.map_or(len - 1, |end| end.min(len - 1)),
+    }
+}
@@ -0,0 +1,103 @@
+use crate::database::settings::{RequestRateLimit, Setting};
+use crate::server::access_log::client_id;
+use crate::server::auth::InternalRequest;
+use crate::server::error::ApiError;
+use crate::server::server::AppState;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Buckets idle this long are dropped by the next request
+const IDLE_BUCKET: Duration = Duration::from_secs(300);
+
+/// Requests of one client: refills at the per-minute limit up to ten
+/// seconds' worth, so a page fetching a few resources at once passes while
+/// a scraper settles at the limit
+#[derive(Debug)]
+struct RequestBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Request buckets by client
+#[derive(Debug, Default)]
+pub struct RequestLimiter {
+    buckets: Mutex<HashMap<String, RequestBucket>>,
+}
+
+impl RequestLimiter {
+    /// Admits one request of `client`, or returns how long until the next
+    /// one would be admitted
+    fn admit(&self, client: &str, per_minute: u32) -> Result<(), Duration> {
+        let rate = per_minute as f64 / 60.0;
+        let burst = (per_minute as f64 / 6.0).max(1.0);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.retain(|_, bucket| now.duration_since(bucket.updated) < IDLE_BUCKET);
+        let bucket = buckets
+            .entry(client.to_string())
+            .or_insert(RequestBucket {
+                tokens: burst,
+                updated: now,
+            });
+        bucket.tokens =
+            (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+/// The application's request limiter
+pub fn limiter() -> &'static RequestLimiter {
+    static LIMITER: OnceLock<RequestLimiter> = OnceLock::new();
+    LIMITER.get_or_init(RequestLimiter::default)
+}
+
+/// Middleware answering `429 Too Many Requests` to network clients over the
+/// `request_rate_limit` setting. Desktop UI requests are not limited.
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let client = match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) if request.extensions().get::<InternalRequest>().is_none() => {
+            client_id(request.headers(), addr)
+        }
+        _ => return next.run(request).await,
+    };
+    let per_minute = state
+        .db_service
+        .settings
+        .get::<RequestRateLimit>()
+        .await
+        .unwrap_or_else(|_| RequestRateLimit::default_value());
+    if per_minute > 0 {
+        if let Err(wait) = limiter().admit(&client, per_minute) {
+            return too_many_requests(per_minute, wait);
+        }
+    }
+    next.run(request).await
+}
+
+fn too_many_requests(per_minute: u32, wait: Duration) -> Response {
+    let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+    ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "TooManyRequests",
+        format!(
+            "More than {} requests per minute; retry in {} s",
+            per_minute, retry_after
+        ),
+    )
+    .with_header(header::RETRY_AFTER, HeaderValue::from(retry_after))
+    .into_response()
+}
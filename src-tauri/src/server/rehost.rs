@@ -0,0 +1,444 @@
+use crate::config::Config;
+use crate::database::DatabaseService;
+use crate::server::asset_sizes;
+use crate::server::checksums::{add_file_extension, sha2_256_multihash, SHA2_256_MULTIHASH};
+use crate::server::error::ApiError;
+use crate::server::handlers::pending_upload_path;
+use crate::server::jobs::{registry, JobLimits};
+use crate::server::middleware::add_cors_headers;
+use crate::server::outbound;
+use crate::server::server::AppState;
+use crate::server::upload_scan::{quarantine, scan_upload, ScanVerdict, SCAN_STATUS_FIELD};
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+/// Items read from the database at a time
+const PAGE_SIZE: i64 = 200;
+
+/// What a re-hosting job did, kept as the job's report
+#[derive(Debug, Default, Serialize)]
+pub struct RehostReport {
+    /// Assets now stored locally and pointing at this server
+    pub rehosted: usize,
+    /// Bytes downloaded for them
+    pub bytes: u64,
+    /// Assets left pointing at their remote href
+    pub failed: Vec<RehostFailure>,
+}
+
+/// An asset that could not be re-hosted
+#[derive(Debug, Serialize)]
+pub struct RehostFailure {
+    pub item_id: String,
+    pub asset_key: String,
+    pub href: String,
+    pub error: String,
+}
+
+/// A downloaded file waiting at the item's pending upload path
+struct Download {
+    size: u64,
+    /// SHA2-256 multihash in hex, as in `file:checksum`
+    checksum: String,
+    /// Scanner status for the asset, if a scanner is configured
+    scan_status: Option<&'static str>,
+}
+
+fn json_response(status: StatusCode, headers: HeaderMap, body: Value) -> Response {
+    let mut headers = add_cors_headers(headers);
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Handler starting a job that downloads the remote assets of a
+/// collection's items into local storage and points them at this server
+/// (POST /collections/:collection_id/rehost)
+pub async fn rehost_collection(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    match state.db_service.collections.get_by_id(&collection_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return ApiError::not_found(format!("Collection '{}' not found", collection_id))
+                .into_response()
+        }
+        Err(e) => {
+            return ApiError::internal(format!("Failed to get collection: {}", e)).into_response()
+        }
+    }
+
+    let job = registry().create("asset_rehost", "asset_rehost", Some(&collection_id));
+    let job_id = job.id.clone();
+    let limits = JobLimits::load(&state.db_service.settings, &job.queue).await;
+    let db_service = state.db_service.clone();
+    let config = state.config.clone();
+    let task_collection = collection_id.clone();
+    registry().spawn(&job, limits, async move {
+        let report = rehost_assets(&db_service, &config, &task_collection, &job_id).await?;
+        if report.failed.is_empty() {
+            Ok(None)
+        } else {
+            Err(format!(
+                "{} of {} remote assets could not be re-hosted",
+                report.failed.len(),
+                report.failed.len() + report.rehosted
+            ))
+        }
+    });
+
+    let href = ServerConfig::from_config(&state.config).href(&format!("/jobs/{}", job.id));
+    let mut headers = HeaderMap::new();
+    if let Ok(location) = HeaderValue::from_str(&href) {
+        headers.insert("Location", location);
+    }
+    let body = json!({
+        "message": format!("Re-hosting the remote assets of collection '{}'", collection_id),
+        "job": job,
+        "links": [{ "href": href, "rel": "status", "type": "application/json" }]
+    });
+    json_response(StatusCode::ACCEPTED, headers, body)
+}
+
+/// Downloads every remote asset of the collection's items, one item at a
+/// time, and rewrites their hrefs. The job's report is updated after each
+/// item.
+async fn rehost_assets(
+    db_service: &DatabaseService,
+    config: &Config,
+    collection_id: &str,
+    job_id: &str,
+) -> Result<RehostReport, String> {
+    let server_config = ServerConfig::from_config(config);
+    let own_prefix = format!("{}/", server_config.base_url.trim_end_matches('/'));
+    let client = outbound::http_client(db_service).await?;
+    let mut report = RehostReport::default();
+
+    let mut offset = 0;
+    loop {
+        let items = db_service
+            .items
+            .get_by_collection(collection_id, Some(PAGE_SIZE), Some(offset))
+            .await
+            .map_err(|e| e.to_string())?;
+        for item in &items {
+            let remote = remote_assets(item.assets.as_ref(), &own_prefix);
+            if remote.is_empty() {
+                continue;
+            }
+            let mut downloaded = Vec::new();
+            for (key, href, asset) in remote {
+                let result = if usable_key(&key) {
+                    download(
+                        &client,
+                        db_service,
+                        config,
+                        collection_id,
+                        &item.id,
+                        &key,
+                        &href,
+                        &asset,
+                    )
+                    .await
+                } else {
+                    Err("Asset key cannot be used as a file name".to_string())
+                };
+                match result {
+                    Ok(file) => downloaded.push((key, href, file)),
+                    Err(error) => report.failed.push(RehostFailure {
+                        item_id: item.id.clone(),
+                        asset_key: key,
+                        href,
+                        error,
+                    }),
+                }
+            }
+            if !downloaded.is_empty() {
+                if let Err(error) = store_item(
+                    db_service,
+                    config,
+                    &server_config,
+                    collection_id,
+                    &item.id,
+                    downloaded,
+                    &mut report,
+                )
+                .await
+                {
+                    report.failed.push(RehostFailure {
+                        item_id: item.id.clone(),
+                        asset_key: String::new(),
+                        href: String::new(),
+                        error,
+                    });
+                }
+            }
+            registry().report(job_id, json!(report));
+        }
+        if (items.len() as i64) < PAGE_SIZE {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    Ok(report)
+}
+
+/// Key, href and member object of each asset with an absolute http(s) href
+/// outside this server
+fn remote_assets(assets: Option<&Value>, own_prefix: &str) -> Vec<(String, String, Value)> {
+    assets
+        .and_then(|assets| assets.as_object())
+        .map(|assets| {
+            assets
+                .iter()
+                .filter_map(|(key, asset)| {
+                    let href = asset.get("href")?.as_str()?;
+                    let remote = (href.starts_with("http://") || href.starts_with("https://"))
+                        && !href.starts_with(own_prefix);
+                    remote.then(|| (key.clone(), href.to_string(), asset.clone()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether an asset key can name its file in the item's asset folder, as the
+/// upload endpoint stores it
+fn usable_key(key: &str) -> bool {
+    !key.is_empty() && !key.starts_with('.') && !key.contains(['/', '\\'])
+}
+
+/// Fetches an asset to the item's pending upload path, checks its size and
+/// checksum against the response and the asset's `file:size` and
+/// `file:checksum`, and runs the upload scanner on it. A file that fails is
+/// removed, or quarantined when the scanner rejects it.
+#[allow(clippy::too_many_arguments)]
+async fn download(
+    client: &reqwest::Client,
+    db_service: &DatabaseService,
+    config: &Config,
+    collection_id: &str,
+    item_id: &str,
+    key: &str,
+    href: &str,
+    asset: &Value,
+) -> Result<Download, String> {
+    let dir = format!("{}/{}/{}", config.assets_dir(), collection_id, item_id);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create asset directory: {}", e))?;
+    let path = pending_upload_path(config, collection_id, item_id, key);
+
+    let fetched = fetch(client, href, &path).await;
+    let verified = fetched.and_then(|(size, expected_size, checksum)| {
+        if let Some(expected) = expected_size.filter(|expected| *expected != size) {
+            return Err(format!("Received {} of {} bytes", size, expected));
+        }
+        if let Some(expected) = asset.get("file:size").and_then(|v| v.as_u64()) {
+            if expected != size {
+                return Err(format!(
+                    "File has {} bytes, file:size says {}",
+                    size, expected
+                ));
+            }
+        }
+        // Checksums in other algorithms than SHA2-256 are kept unverified
+        if let Some(expected) = asset.get("file:checksum").and_then(|v| v.as_str()) {
+            let expected = expected.to_ascii_lowercase();
+            if expected.starts_with(SHA2_256_MULTIHASH) && expected != checksum {
+                return Err(format!(
+                    "Checksum {} does not match file:checksum",
+                    checksum
+                ));
+            }
+        }
+        Ok((size, checksum))
+    });
+    let (size, checksum) = match verified {
+        Ok(verified) => verified,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(e);
+        }
+    };
+
+    let verdict = scan_upload(
+        db_service,
+        std::path::Path::new(&path),
+        collection_id,
+        item_id,
+        key,
+    )
+    .await;
+    if let ScanVerdict::Rejected(reason) = verdict {
+        if let Err(e) = quarantine(
+            config,
+            std::path::Path::new(&path),
+            collection_id,
+            item_id,
+            key,
+        )
+        .await
+        {
+            let _ = tokio::fs::remove_file(&path).await;
+            eprintln!("Failed to quarantine {}: {}", path, e);
+        }
+        return Err(format!("Rejected by the upload scanner: {}", reason));
+    }
+    Ok(Download {
+        size,
+        checksum,
+        scan_status: verdict.status(),
+    })
+}
+
+/// Streams a response body to `path`, returning its size, the size the
+/// server announced and the SHA2-256 multihash of the bytes
+async fn fetch(
+    client: &reqwest::Client,
+    href: &str,
+    path: &str,
+) -> Result<(u64, Option<u64>, String), String> {
+    let mut response = client
+        .get(href)
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Server answered {}", response.status()));
+    }
+    let expected_size = response.content_length();
+
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Download interrupted: {}", e))?
+    {
+        hasher.update(&chunk);
+        size += chunk.len() as u64;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+    Ok((size, expected_size, sha2_256_multihash(&hasher.finalize())))
+}
+
+/// Moves an item's downloaded files into place and points their assets at
+/// this server. The item is read again first; an asset whose href changed
+/// while its file was downloading keeps the new href and its download is
+/// dropped.
+async fn store_item(
+    db_service: &DatabaseService,
+    config: &Config,
+    server_config: &ServerConfig,
+    collection_id: &str,
+    item_id: &str,
+    downloaded: Vec<(String, String, Download)>,
+    report: &mut RehostReport,
+) -> Result<(), String> {
+    let pending = |key: &str| pending_upload_path(config, collection_id, item_id, key);
+    let item = db_service
+        .items
+        .get_by_id(collection_id, item_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(mut item) = item else {
+        for (key, _, _) in &downloaded {
+            let _ = tokio::fs::remove_file(pending(key)).await;
+        }
+        return Err("Item was deleted while its assets were downloading".to_string());
+    };
+
+    let mut assets = item
+        .assets
+        .as_ref()
+        .and_then(|assets| assets.as_object().cloned())
+        .unwrap_or_default();
+    let mut stored = Vec::new();
+    for (key, href, file) in downloaded {
+        let asset = assets
+            .get_mut(&key)
+            .and_then(|asset| asset.as_object_mut())
+            .filter(|asset| asset.get("href").and_then(|h| h.as_str()) == Some(href.as_str()));
+        let Some(asset) = asset else {
+            let _ = tokio::fs::remove_file(pending(&key)).await;
+            continue;
+        };
+        let final_path = format!(
+            "{}/{}/{}/{}",
+            config.assets_dir(),
+            collection_id,
+            item_id,
+            key
+        );
+        if let Err(e) = tokio::fs::rename(pending(&key), &final_path).await {
+            let _ = tokio::fs::remove_file(pending(&key)).await;
+            report.failed.push(RehostFailure {
+                item_id: item_id.to_string(),
+                asset_key: key,
+                href,
+                error: format!("Failed to store the file: {}", e),
+            });
+            continue;
+        }
+
+        asset.insert(
+            "href".to_string(),
+            json!(server_config.asset_href(collection_id, item_id, &key)),
+        );
+        asset.insert("file:size".to_string(), json!(file.size));
+        let keeps_checksum = asset
+            .get("file:checksum")
+            .and_then(|v| v.as_str())
+            .is_some_and(|c| !c.to_ascii_lowercase().starts_with(SHA2_256_MULTIHASH));
+        if !keeps_checksum {
+            asset.insert("file:checksum".to_string(), json!(file.checksum));
+        }
+        if let Some(status) = file.scan_status {
+            asset.insert(SCAN_STATUS_FIELD.to_string(), json!(status));
+        }
+        stored.push(file.size);
+    }
+    if stored.is_empty() {
+        return Ok(());
+    }
+
+    add_file_extension(&mut item);
+    item.assets = Some(Value::Object(assets));
+    item.updated_at = chrono::Utc::now().to_rfc3339();
+    db_service
+        .items
+        .update(&item)
+        .await
+        .map_err(|e| format!("Failed to update item: {}", e))?;
+    report.rehosted += stored.len();
+    report.bytes += stored.iter().sum::<u64>();
+
+    if let Err(e) = asset_sizes::refresh(db_service, config, collection_id, Some(item_id)).await {
+        eprintln!(
+            "Failed to update asset size of {}/{}: {}",
+            collection_id, item_id, e
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use crate::database::item_links::{ItemLink, ItemRelation};
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+/// Body of POST/DELETE /collections/:collection_id/items/:item_id/related
+#[derive(Debug, Deserialize)]
+pub struct RelationBody {
+    /// "derived_from", "source" or "sibling"
+    pub rel: String,
+    /// Collection of the related item; defaults to the item's own collection
+    pub collection: Option<String>,
+    pub item: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelatedQuery {
+    /// Only follow relationships of this type
+    pub rel: Option<String>,
+    /// "outgoing" (declared by the item), "incoming" (pointing at it) or "both" (default)
+    pub direction: Option<String>,
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+async fn item_exists(
+    state: &AppState,
+    collection_id: &str,
+    item_id: &str,
+) -> Result<bool, ApiError> {
+    match state.db_service.items.get_by_id(collection_id, item_id).await {
+        Ok(found) => Ok(found.is_some()),
+        Err(e) => Err(ApiError::internal(format!("Failed to retrieve item: {}", e))),
+    }
+}
+
+/// Handler listing the items related to an item (GET /collections/:collection_id/items/:item_id/related)
+pub async fn related_items(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    Query(query): Query<RelatedQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let server_config = ServerConfig::from_config(&state.config);
+
+    if !item_exists(&state, &collection_id, &item_id).await? {
+        return Err(ApiError::not_found(format!(
+            "Item '{}' not found in collection '{}'",
+            item_id, collection_id
+        )));
+    }
+
+    let rel_filter = match query.rel.as_deref() {
+        Some(rel) => Some(ItemRelation::parse(rel).ok_or_else(|| {
+            ApiError::invalid_parameter(format!(
+                "Unknown rel '{}'. Use derived_from, source or sibling",
+                rel
+            ))
+        })?),
+        None => None,
+    };
+    let direction = query.direction.as_deref().unwrap_or("both");
+    if !matches!(direction, "outgoing" | "incoming" | "both") {
+        return Err(ApiError::invalid_parameter(format!(
+            "Unknown direction '{}'. Use outgoing, incoming or both",
+            direction
+        )));
+    }
+
+    // (direction, relation seen from the related item)
+    let links_error =
+        |e: rusqlite::Error| ApiError::internal(format!("Failed to read item links: {}", e));
+    let mut related: Vec<(&str, ItemLink)> = Vec::new();
+    if direction != "incoming" {
+        let links = state
+            .db_service
+            .item_links
+            .outgoing(&collection_id, &item_id)
+            .await
+            .map_err(links_error)?;
+        related.extend(links.into_iter().map(|l| ("outgoing", l)));
+    }
+    if direction != "outgoing" {
+        let links = state
+            .db_service
+            .item_links
+            .incoming(&collection_id, &item_id)
+            .await
+            .map_err(links_error)?;
+        related.extend(links.into_iter().map(|l| ("incoming", l)));
+    }
+    if let Some(rel) = rel_filter {
+        related.retain(|(_, link)| link.rel == rel);
+    }
+
+    let mut features = Vec::new();
+    let mut relations = Vec::new();
+    for (direction, link) in related {
+        let (other_collection, other_item) = if direction == "outgoing" {
+            (link.target_collection.clone(), link.target_item.clone())
+        } else {
+            (link.source_collection.clone(), link.source_item.clone())
+        };
+        // Relationships may outlive items removed outside the API
+        if let Ok(Some(db_item)) = state
+            .db_service
+            .items
+            .get_by_id(&other_collection, &other_item)
+            .await
+        {
+            features.push(db_item.to_stac_item(&server_config));
+            relations.push(serde_json::json!({
+                "rel": link.rel,
+                "direction": direction,
+                "collection": other_collection,
+                "item": other_item
+            }));
+        }
+    }
+
+    let related_href = format!("{}/related", server_config.item_href(&collection_id, &item_id));
+    let response = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+        "relations": relations,
+        "links": [
+            {
+                "href": related_href,
+                "rel": "self",
+                "type": "application/geo+json"
+            },
+            {
+                "href": server_config.item_href(&collection_id, &item_id),
+                "rel": "parent",
+                "type": "application/geo+json"
+            }
+        ]
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/geo+json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+
+    Ok((headers, serde_json::to_string(&response).unwrap()).into_response())
+}
+
+/// Resolves a request body into a stored relationship, checking both items exist
+async fn resolve_link(
+    state: &AppState,
+    collection_id: String,
+    item_id: String,
+    body: RelationBody,
+) -> Result<ItemLink, ApiError> {
+    let rel = parse_rel(&body.rel)?;
+    let target_collection = body.collection.unwrap_or_else(|| collection_id.clone());
+    if target_collection == collection_id && body.item == item_id {
+        return Err(ApiError::bad_request("An item cannot be related to itself"));
+    }
+
+    for (c, i) in [(&collection_id, &item_id), (&target_collection, &body.item)] {
+        if !item_exists(state, c, i).await? {
+            return Err(ApiError::not_found(format!(
+                "Item '{}' not found in collection '{}'",
+                i, c
+            )));
+        }
+    }
+
+    Ok(ItemLink {
+        source_collection: collection_id,
+        source_item: item_id,
+        rel,
+        target_collection,
+        target_item: body.item,
+    })
+}
+
+fn parse_rel(rel: &str) -> Result<ItemRelation, ApiError> {
+    ItemRelation::parse(rel).ok_or_else(|| {
+        ApiError::bad_request(format!(
+            "Unknown rel '{}'. Use derived_from, source or sibling",
+            rel
+        ))
+    })
+}
+
+/// Handler declaring a relationship (POST /collections/:collection_id/items/:item_id/related)
+pub async fn add_item_relation(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    Json(body): Json<RelationBody>,
+) -> Result<Response, ApiError> {
+    let link = resolve_link(&state, collection_id, item_id, body).await?;
+    state
+        .db_service
+        .item_links
+        .add(&link)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to save item link: {}", e)))?;
+    Ok(json_response(StatusCode::CREATED, serde_json::json!(link)))
+}
+
+/// Handler removing a relationship (DELETE /collections/:collection_id/items/:item_id/related)
+pub async fn remove_item_relation(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    Json(body): Json<RelationBody>,
+) -> Result<Response, ApiError> {
+    let link = ItemLink {
+        rel: parse_rel(&body.rel)?,
+        target_collection: body.collection.unwrap_or_else(|| collection_id.clone()),
+        source_collection: collection_id,
+        source_item: item_id,
+        target_item: body.item,
+    };
+
+    let removed = state
+        .db_service
+        .item_links
+        .remove(&link)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to remove item link: {}", e)))?;
+    if !removed {
+        return Err(ApiError::not_found("Relationship not found"));
+    }
+    Ok((StatusCode::NO_CONTENT, add_cors_headers(HeaderMap::new())).into_response())
+}
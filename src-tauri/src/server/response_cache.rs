@@ -0,0 +1,165 @@
+use crate::database::settings::{ResponseCacheSeconds, Setting};
+use crate::server::auth::InternalRequest;
+use crate::server::server::AppState;
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Largest response body kept; bigger and streamed responses, such as
+/// asset downloads, always reach the handler
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Most responses kept; the oldest is evicted beyond this
+const MAX_ENTRIES: usize = 512;
+
+/// Request headers that change the response of the same URL
+const VARY_HEADERS: [header::HeaderName; 4] = [
+    header::ACCEPT,
+    header::ACCEPT_LANGUAGE,
+    header::AUTHORIZATION,
+    header::HeaderName::from_static("x-api-key"),
+];
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored: Instant,
+}
+
+/// GET responses by request, kept in memory for `response_cache_seconds`
+#[derive(Debug, Default)]
+pub struct ResponseCache {
+    entries: Mutex<HashMap<u64, CachedResponse>>,
+}
+
+impl ResponseCache {
+    fn get(&self, key: u64, ttl: Duration) -> Option<CachedResponse> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&key)
+            .filter(|entry| entry.stored.elapsed() < ttl)
+            .cloned()
+    }
+
+    fn insert(&self, key: u64, entry: CachedResponse, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.stored.elapsed() < ttl);
+        if entries.len() >= MAX_ENTRIES {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, entry);
+    }
+
+    /// Drops every kept response; called after each successful write
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// The application's response cache
+pub fn cache() -> &'static ResponseCache {
+    static CACHE: OnceLock<ResponseCache> = OnceLock::new();
+    CACHE.get_or_init(ResponseCache::default)
+}
+
+/// Middleware serving repeated GETs of network clients from memory while
+/// the `response_cache_seconds` setting is non-zero. Conditional and range
+/// requests and the desktop UI bypass the cache, and any successful write
+/// empties it.
+pub async fn response_cache(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    if method != Method::GET {
+        let response = next.run(request).await;
+        if response.status().is_success() && method != Method::HEAD && method != Method::OPTIONS {
+            cache().clear();
+        }
+        return response;
+    }
+
+    let headers = request.headers();
+    if request.extensions().get::<InternalRequest>().is_some()
+        || headers.contains_key(header::IF_NONE_MATCH)
+        || headers.contains_key(header::IF_MODIFIED_SINCE)
+        || headers.contains_key(header::RANGE)
+    {
+        return next.run(request).await;
+    }
+    let seconds = state
+        .db_service
+        .settings
+        .get::<ResponseCacheSeconds>()
+        .await
+        .unwrap_or_else(|_| ResponseCacheSeconds::default_value());
+    if seconds == 0 {
+        return next.run(request).await;
+    }
+    let ttl = Duration::from_secs(seconds);
+
+    let key = cache_key(&request);
+    if let Some(entry) = cache().get(key, ttl) {
+        let mut response = (entry.status, entry.headers, Body::from(entry.body)).into_response();
+        response
+            .headers_mut()
+            .insert(header::AGE, HeaderValue::from(entry.stored.elapsed().as_secs()));
+        return response;
+    }
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK
+        || !response
+            .body()
+            .size_hint()
+            .exact()
+            .is_some_and(|size| size as usize <= MAX_BODY_BYTES)
+    {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    cache().insert(
+        key,
+        CachedResponse {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            stored: Instant::now(),
+        },
+        ttl,
+    );
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Hash of the URL and the request headers the response varies by, so keys
+/// do not hold the credentials themselves
+fn cache_key(request: &Request) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    request.uri().hash(&mut hasher);
+    for name in &VARY_HEADERS {
+        request
+            .headers()
+            .get(name)
+            .map(|value| value.as_bytes())
+            .hash(&mut hasher);
+    }
+    hasher.finish()
+}
@@ -0,0 +1,390 @@
+use crate::config::Config;
+use crate::database::indexed_properties::check_property_name;
+use crate::database::retention::{RetentionAge, RetentionPolicy, RetentionRule};
+use crate::database::settings::{RetentionIntervalMinutes, Setting, TrashRetentionDays};
+use crate::database::{DatabaseService, DbItem};
+use crate::server::asset_cleanup::AssetCleanup;
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::trash::purge_expired;
+use crate::server::upload_sessions::discard_expired;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// An item a retention policy removes, and the rule that expired it
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiredItem {
+    pub collection_id: String,
+    pub item_id: String,
+    pub reason: String,
+}
+
+/// Outcome of evaluating a collection's retention policy
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionReport {
+    pub collection_id: String,
+    /// True when nothing was deleted because only a preview was asked for
+    pub preview: bool,
+    pub evaluated: usize,
+    pub expired: Vec<ExpiredItem>,
+    pub deleted: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Checks that a policy's rules can be evaluated
+pub fn check_policy(policy: &RetentionPolicy) -> Result<(), String> {
+    for rule in &policy.rules {
+        match rule {
+            RetentionRule::MaxAge { days, .. } if *days == 0 => {
+                return Err("max_age rules need at least 1 day".to_string())
+            }
+            RetentionRule::KeepLatest { count, .. } if *count == 0 => {
+                return Err("keep_latest rules need a count of at least 1".to_string())
+            }
+            RetentionRule::KeepLatest {
+                group_by: Some(property),
+                ..
+            } => check_property_name(property)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn parse_time(value: Option<&serde_json::Value>) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value?.as_str()?)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Acquisition time of an item: `datetime`, or the end of its interval
+fn acquired_at(item: &DbItem) -> Option<DateTime<Utc>> {
+    parse_time(item.properties.get("datetime"))
+        .or_else(|| parse_time(item.properties.get("end_datetime")))
+        .or_else(|| parse_time(item.properties.get("start_datetime")))
+}
+
+fn created_at(item: &DbItem) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&item.created_at)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Items of a collection that the policy expires at `now`. Items without the
+/// timestamp a rule needs never expire by age and sort oldest for
+/// `keep_latest`.
+pub fn expired_items(
+    policy: &RetentionPolicy,
+    items: &[DbItem],
+    now: DateTime<Utc>,
+) -> Vec<ExpiredItem> {
+    let mut expired: BTreeMap<String, String> = BTreeMap::new();
+
+    for rule in &policy.rules {
+        match rule {
+            RetentionRule::MaxAge { days, age } => {
+                let cutoff = now - chrono::Duration::days(i64::from(*days));
+                for item in items {
+                    let time = match age {
+                        RetentionAge::Acquired => acquired_at(item),
+                        RetentionAge::Created => created_at(item),
+                    };
+                    if time.is_some_and(|t| t < cutoff) {
+                        expired
+                            .entry(item.id.clone())
+                            .or_insert_with(|| format!("Older than {} days", days));
+                    }
+                }
+            }
+            RetentionRule::KeepLatest { count, group_by } => {
+                let mut groups: BTreeMap<String, Vec<&DbItem>> = BTreeMap::new();
+                for item in items {
+                    let group = group_by
+                        .as_ref()
+                        .and_then(|property| item.properties.get(property))
+                        .map(|value| match value.as_str() {
+                            Some(text) => text.to_string(),
+                            None => value.to_string(),
+                        })
+                        .unwrap_or_default();
+                    groups.entry(group).or_default().push(item);
+                }
+                for (group, mut members) in groups {
+                    members.sort_by(|a, b| {
+                        let a_time = acquired_at(a).or_else(|| created_at(a));
+                        let b_time = acquired_at(b).or_else(|| created_at(b));
+                        b_time.cmp(&a_time).then_with(|| b.id.cmp(&a.id))
+                    });
+                    for item in members.into_iter().skip(*count as usize) {
+                        expired
+                            .entry(item.id.clone())
+                            .or_insert_with(|| match group_by {
+                                Some(property) => format!(
+                                    "Not among the latest {} with {} = {}",
+                                    count, property, group
+                                ),
+                                None => format!("Not among the latest {}", count),
+                            });
+                    }
+                }
+            }
+        }
+    }
+
+    expired
+        .into_iter()
+        .map(|(item_id, reason)| ExpiredItem {
+            collection_id: policy.collection_id.clone(),
+            item_id,
+            reason,
+        })
+        .collect()
+}
+
+/// Evaluates a policy and, unless `preview` is set, moves the expired items
+/// to the trash
+pub async fn run_policy(
+    db_service: &DatabaseService,
+    policy: &RetentionPolicy,
+    preview: bool,
+) -> Result<RetentionReport, String> {
+    let items = db_service
+        .items
+        .get_by_collection(&policy.collection_id, Some(i64::MAX), None)
+        .await
+        .map_err(|e| format!("Failed to read items: {}", e))?;
+    let expired = expired_items(policy, &items, Utc::now());
+
+    let mut report = RetentionReport {
+        collection_id: policy.collection_id.clone(),
+        preview,
+        evaluated: items.len(),
+        expired,
+        deleted: 0,
+        errors: Vec::new(),
+    };
+    if preview {
+        return Ok(report);
+    }
+
+    for item in &report.expired {
+        match db_service
+            .items
+            .delete(&item.collection_id, &item.item_id)
+            .await
+        {
+            Ok(()) => report.deleted += 1,
+            Err(e) => report
+                .errors
+                .push(format!("{}/{}: {}", item.collection_id, item.item_id, e)),
+        }
+    }
+    Ok(report)
+}
+
+/// Applies every enabled retention policy and purges expired trash, then
+/// waits for the configured interval. Runs for the lifetime of the
+/// application.
+pub async fn run_retention_scheduler(db_service: DatabaseService, config: Config) {
+    loop {
+        let minutes = db_service
+            .settings
+            .get::<RetentionIntervalMinutes>()
+            .await
+            .unwrap_or_else(|_| RetentionIntervalMinutes::default_value());
+        tokio::time::sleep(std::time::Duration::from_secs(minutes * 60)).await;
+
+        let policies = match db_service.retention_policies.get_all().await {
+            Ok(policies) => policies,
+            Err(e) => {
+                eprintln!("Retention: failed to read policies: {}", e);
+                continue;
+            }
+        };
+        for policy in policies.iter().filter(|p| p.enabled && !p.rules.is_empty()) {
+            match run_policy(&db_service, policy, false).await {
+                Ok(report) => {
+                    for error in report.errors {
+                        eprintln!("Retention: {}", error);
+                    }
+                }
+                Err(e) => eprintln!("Retention: collection {}: {}", policy.collection_id, e),
+            }
+        }
+
+        let trash_days = db_service
+            .settings
+            .get::<TrashRetentionDays>()
+            .await
+            .unwrap_or_else(|_| TrashRetentionDays::default_value());
+        if let Err(e) = purge_expired(&db_service, &config, trash_days).await {
+            eprintln!("Retention: {}", e);
+        }
+        // Asset folders cleaned up on their own
+        if let Err(e) = AssetCleanup::new(&config).purge(trash_days).await {
+            eprintln!("Retention: {}", e);
+        }
+        // Chunked uploads abandoned by their clients
+        if let Err(e) = discard_expired(&db_service).await {
+            eprintln!("Retention: {}", e);
+        }
+    }
+}
+
+/// Replaces the retention policy of a collection; no rules removes it. Shared
+/// by the HTTP endpoint and the Tauri command.
+pub async fn save_policy(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    rules: Vec<RetentionRule>,
+    enabled: bool,
+) -> Result<Option<RetentionPolicy>, (StatusCode, String)> {
+    match db_service.collections.get_by_id(collection_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Collection '{}' not found", collection_id),
+            ))
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to get collection: {}", e),
+            ))
+        }
+    }
+
+    if rules.is_empty() {
+        db_service
+            .retention_policies
+            .delete(collection_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Ok(None);
+    }
+
+    let policy = RetentionPolicy {
+        collection_id: collection_id.to_string(),
+        rules,
+        enabled,
+        updated_at: Utc::now().to_rfc3339(),
+    };
+    check_policy(&policy).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    db_service
+        .retention_policies
+        .upsert(&policy)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to save retention policy: {}", e),
+            )
+        })?;
+    Ok(Some(policy))
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+async fn policy_or_404(state: &AppState, collection_id: &str) -> Result<RetentionPolicy, ApiError> {
+    state
+        .db_service
+        .retention_policies
+        .get(collection_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read retention policy: {}", e)))?
+        .ok_or_else(|| {
+            ApiError::not_found(format!(
+                "Collection '{}' has no retention policy",
+                collection_id
+            ))
+        })
+}
+
+/// Handler returning the retention policy of a collection
+/// (GET /collections/:collection_id/retention)
+pub async fn get_retention_policy(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let policy = policy_or_404(&state, &collection_id).await?;
+    Ok(json_response(StatusCode::OK, serde_json::json!(policy)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetentionPolicyBody {
+    pub rules: Vec<RetentionRule>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Handler replacing the retention policy of a collection
+/// (PUT /collections/:collection_id/retention)
+pub async fn put_retention_policy(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+    Json(body): Json<RetentionPolicyBody>,
+) -> Result<Response, ApiError> {
+    let saved = save_policy(&state.db_service, &collection_id, body.rules, body.enabled)
+        .await
+        .map_err(|(status, description)| ApiError::from_status(status, description))?;
+    Ok(match saved {
+        Some(policy) => json_response(StatusCode::OK, serde_json::json!(policy)),
+        None => (StatusCode::NO_CONTENT, add_cors_headers(HeaderMap::new())).into_response(),
+    })
+}
+
+/// Handler removing the retention policy of a collection
+/// (DELETE /collections/:collection_id/retention)
+pub async fn delete_retention_policy(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let deleted = state
+        .db_service
+        .retention_policies
+        .delete(&collection_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to delete retention policy: {}", e)))?;
+    if !deleted {
+        return Err(ApiError::not_found(format!(
+            "Collection '{}' has no retention policy",
+            collection_id
+        )));
+    }
+    Ok((StatusCode::NO_CONTENT, add_cors_headers(HeaderMap::new())).into_response())
+}
+
+/// Handler listing the items the policy would remove now, without deleting
+/// anything (GET /collections/:collection_id/retention/preview)
+pub async fn preview_retention_policy(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let policy = policy_or_404(&state, &collection_id).await?;
+    let report = run_policy(&state.db_service, &policy, true)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(json_response(StatusCode::OK, serde_json::json!(report)))
+}
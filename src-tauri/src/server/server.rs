@@ -1,16 +1,59 @@
 use crate::config::Config;
 use crate::database::DatabaseService;
+use crate::server::access_log::{access_log, access_stats, item_access_stats};
+use crate::server::acme::{serve_https, AcmeSettings};
+use crate::server::aggregations::{aggregations, collection_aggregations};
+use crate::server::asset_cleanup::{collection_cleanup_preview, item_cleanup_preview};
+use crate::server::auth::write_access;
+use crate::server::feeds::{catalog_feed, collection_feed};
+use crate::server::basemap::basemap_tile;
+use crate::server::branding::branding_file;
+use crate::server::bulk_update::update_items;
+use crate::server::computed_properties::{get_computed_properties, put_computed_properties};
+use crate::server::explain::{search_explain_get, search_explain_post};
+use crate::server::exports::{export_file, search_export};
+use crate::server::geometry::{geometry_reproject, geometry_tools, item_footprint};
 use crate::server::handlers::{
     api_html, api_spec, collection, collection_items, collection_sortables, collections,
     collections_sortables, conformance, create_collection, create_item, delete_collection,
-    delete_item, health_check, hello_world, item, put_collection, put_item, search_get,
-    search_post, serve_asset, sortables, upload_asset,
+    delete_item, health_check, hello_world, item, patch_collection, patch_item, put_collection,
+    put_item, search_get, search_post, serve_asset, sortables, upload_asset,
 };
-use crate::server::middleware::{options_handler, trailing_slash_redirect};
+use crate::server::i18n::{
+    get_collection_translations, get_item_translations, put_collection_translations,
+    put_item_translations,
+};
+use crate::server::indexed_properties::{get_indexed_properties, put_indexed_properties};
+use crate::server::item_schema::{get_collection_schema, put_collection_schema};
+use crate::server::jobs::job_status;
+use crate::server::landing::root_redirect;
+use crate::server::middleware::{
+    format_negotiation, options_handler, payload_too_large, trailing_slash_redirect, BodyLimits,
+};
+use crate::server::rehost::rehost_collection;
+use crate::server::relations::{add_item_relation, related_items, remove_item_relation};
+use crate::server::queryables::{collection_queryables, queryables};
+use crate::server::rate_limit::rate_limit;
+use crate::server::response_cache::response_cache;
+use crate::server::retention::{
+    delete_retention_policy, get_retention_policy, preview_retention_policy, put_retention_policy,
+};
+use crate::server::statistics::catalog_statistics;
+use crate::server::sync::{sync_push, sync_records, sync_revisions};
+use crate::server::upload_sessions::{
+    append_upload_chunk, create_upload_session, delete_upload_session, get_upload_session,
+    list_upload_sessions,
+};
+use crate::server::trash::{
+    empty_trash, list_trash, purge_collection, purge_item, restore_collection, restore_item,
+};
+use crate::server::virtual_collections::{delete_virtual_collection, put_virtual_collection};
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{delete, get, post, put},
     Router,
 };
+use std::future::IntoFuture;
 use tokio::net::TcpListener;
 use tower_http::cors::{Any, CorsLayer};
 
@@ -27,72 +70,363 @@ pub fn create_stac_router(db_service: DatabaseService, config: Config) -> Router
     // Get the API version path (e.g., "/v1")
     let api_path = &state.config.server.api_version;
 
-    Router::new()
+    // Uploads may be gigabytes while JSON bodies stay small
+    let body_limits = BodyLimits {
+        json: state.config.server.max_json_body_bytes,
+        upload: state.config.server.max_upload_body_bytes,
+    };
+    let json_limit = usize::try_from(body_limits.json).unwrap_or(usize::MAX);
+    let upload_limit = usize::try_from(body_limits.upload).unwrap_or(usize::MAX);
+
+    let features = &state.config.server;
+
+    // Disabled transactions drop the write methods (405); disabled search and
+    // uploads drop their routes entirely (404)
+    let mut collections_route = get(collections).options(options_handler);
+    let mut collection_route = get(collection).options(options_handler);
+    let mut items_route = get(collection_items).options(options_handler);
+    let mut item_route = get(item).options(options_handler);
+    let mut schema_route = get(get_collection_schema).options(options_handler);
+    let mut indexed_properties_route = get(get_indexed_properties).options(options_handler);
+    let mut computed_properties_route = get(get_computed_properties).options(options_handler);
+    let mut related_route = get(related_items).options(options_handler);
+    let mut collection_translations_route =
+        get(get_collection_translations).options(options_handler);
+    let mut item_translations_route = get(get_item_translations).options(options_handler);
+    if features.enable_transactions {
+        collections_route = collections_route.post(create_collection);
+        collection_route = collection_route
+            .put(put_collection)
+            .patch(patch_collection)
+            .delete(delete_collection);
+        items_route = items_route.post(create_item);
+        item_route = item_route.put(put_item).patch(patch_item).delete(delete_item);
+        schema_route = schema_route.put(put_collection_schema);
+        indexed_properties_route = indexed_properties_route.put(put_indexed_properties);
+        computed_properties_route = computed_properties_route.put(put_computed_properties);
+        related_route = related_route.post(add_item_relation).delete(remove_item_relation);
+        collection_translations_route =
+            collection_translations_route.put(put_collection_translations);
+        item_translations_route = item_translations_route.put(put_item_translations);
+    }
+
+    let mut router = Router::new()
         .route(api_path, get(hello_world))
         .route(&format!("{}/health", api_path), get(health_check))
         .route(&format!("{}/api", api_path), get(api_spec))
         .route(&format!("{}/api.html", api_path), get(api_html))
         .route(&format!("{}/conformance", api_path), get(conformance))
-        .route(
-            &format!("{}/collections", api_path),
-            get(collections)
-                .post(create_collection)
-                .options(options_handler),
-        )
+        .route(&format!("{}/collections", api_path), collections_route)
         .route(
             &format!("{}/collections/:collection_id", api_path),
-            get(collection)
-                .put(put_collection)
-                .delete(delete_collection)
-                .options(options_handler),
+            collection_route,
         )
         .route(
             &format!("{}/collections/:collection_id/items", api_path),
-            get(collection_items)
-                .post(create_item)
-                .options(options_handler),
+            items_route,
+        )
+        // Properties the collection's items can be filtered on
+        .route(
+            &format!("{}/collections/:collection_id/queryables", api_path),
+            get(collection_queryables).options(options_handler),
+        )
+        // JSON Schema enforced on the collection's items
+        .route(
+            &format!("{}/collections/:collection_id/schema", api_path),
+            schema_route,
+        )
+        // Item properties backed by generated columns for fast query predicates
+        .route(
+            &format!("{}/collections/:collection_id/indexed-properties", api_path),
+            indexed_properties_route,
+        )
+        // Item properties derived from formulas, e.g. area_km2 from the geometry
+        .route(
+            &format!("{}/collections/:collection_id/computed-properties", api_path),
+            computed_properties_route,
+        )
+        // Titles and descriptions in other languages, served by Accept-Language
+        .route(
+            &format!("{}/collections/:collection_id/translations", api_path),
+            collection_translations_route,
         )
         .route(
             &format!("{}/collections/:collection_id/items/:item_id", api_path),
-            get(item)
-                .put(put_item)
-                .delete(delete_item)
-                .options(options_handler),
+            item_route,
         )
         .route(
-            &format!("{}/collections/:collection_id/sortables", api_path),
-            get(collection_sortables).options(options_handler),
+            &format!(
+                "{}/collections/:collection_id/items/:item_id/translations",
+                api_path
+            ),
+            item_translations_route,
         )
+        // Relationships between items, e.g. provenance from raw to processed products
         .route(
-            &format!("{}/collections/sortables", api_path),
-            get(collections_sortables).options(options_handler),
+            &format!(
+                "{}/collections/:collection_id/items/:item_id/related",
+                api_path
+            ),
+            related_route,
         )
+        // Simplified outline of an item, optionally in web mercator
         .route(
-            &format!("{}/sortables", api_path),
-            get(sortables).options(options_handler),
+            &format!(
+                "{}/collections/:collection_id/items/:item_id/footprint",
+                api_path
+            ),
+            get(item_footprint).options(options_handler),
         )
+        // Access statistics of an item, when item_access_stats is enabled
         .route(
-            &format!("{}/search", api_path),
-            get(search_get).post(search_post).options(options_handler),
+            &format!(
+                "{}/collections/:collection_id/items/:item_id/stats",
+                api_path
+            ),
+            get(item_access_stats).options(options_handler),
         )
-        // File upload and serving routes
         .route(
-            &format!("{}/upload/:collection_id/:item_id/:asset_key", api_path),
-            post(upload_asset).options(options_handler),
+            &format!("{}/geometry/tools", api_path),
+            post(geometry_tools).options(options_handler),
+        )
+        .route(
+            &format!("{}/geometry/reproject", api_path),
+            post(geometry_reproject).options(options_handler),
+        )
+        // Cached basemap tiles for offline maps
+        .route(
+            &format!("{}/basemap/:z/:x/:y", api_path),
+            get(basemap_tile).options(options_handler),
+        )
+        // Atom feeds of recently created or updated items
+        .route(
+            &format!("{}/feed.xml", api_path),
+            get(catalog_feed).options(options_handler),
         )
+        .route(
+            &format!("{}/collections/:collection_id/feed.xml", api_path),
+            get(collection_feed).options(options_handler),
+        )
+        // Item counts, extents and asset storage per collection
+        .route(
+            &format!("{}/stats", api_path),
+            get(catalog_statistics).options(options_handler),
+        )
+        // Most requested collections, items and assets
+        .route(
+            &format!("{}/stats/access", api_path),
+            get(access_stats).options(options_handler),
+        )
+        // Catalog logo, favicon and about document
+        .route(
+            &format!("{}/branding/:kind", api_path),
+            get(branding_file).options(options_handler),
+        )
+        // Asset serving route
         .route(
             &format!(
                 "{}/collections/:collection_id/items/:item_id/:asset_key",
                 api_path
             ),
             get(serve_asset).options(options_handler),
+        );
+
+    if features.enable_search {
+        router = router
+            .route(
+                &format!("{}/collections/:collection_id/sortables", api_path),
+                get(collection_sortables).options(options_handler),
+            )
+            .route(
+                &format!("{}/collections/sortables", api_path),
+                get(collections_sortables).options(options_handler),
+            )
+            .route(
+                &format!("{}/sortables", api_path),
+                get(sortables).options(options_handler),
+            )
+            .route(
+                &format!("{}/queryables", api_path),
+                get(queryables).options(options_handler),
+            )
+            // Counts, datetime histograms and term frequencies of matching items
+            .route(
+                &format!("{}/aggregations", api_path),
+                get(aggregations).options(options_handler),
+            )
+            .route(
+                &format!("{}/collections/:collection_id/aggregations", api_path),
+                get(collection_aggregations).options(options_handler),
+            )
+            .route(
+                &format!("{}/search", api_path),
+                get(search_get).post(search_post).options(options_handler),
+            )
+            // How a search runs, for diagnosing slow queries (admin key)
+            .route(
+                &format!("{}/search/explain", api_path),
+                get(search_explain_get)
+                    .post(search_explain_post)
+                    .options(options_handler),
+            )
+            // Search results written to a file by a background job
+            .route(
+                &format!("{}/search/export", api_path),
+                post(search_export).options(options_handler),
+            )
+            .route(
+                &format!("{}/exports/:job_id", api_path),
+                get(export_file).options(options_handler),
+            );
+    }
+
+    // Stored searches published as read-only collections
+    if features.enable_transactions {
+        router = router
+            .route(
+                &format!("{}/virtual-collections/:collection_id", api_path),
+                put(put_virtual_collection)
+                    .delete(delete_virtual_collection)
+                    .options(options_handler),
+            )
+            // Asset files a collection or item delete would remove
+            .route(
+                &format!("{}/collections/:collection_id/cleanup-preview", api_path),
+                get(collection_cleanup_preview).options(options_handler),
+            )
+            .route(
+                &format!(
+                    "{}/collections/:collection_id/items/:item_id/cleanup-preview",
+                    api_path
+                ),
+                get(item_cleanup_preview).options(options_handler),
+            )
+            // Merge patch applied to every item matching a filter
+            .route(
+                &format!("{}/collections/:collection_id/items/update", api_path),
+                post(update_items).options(options_handler),
+            )
+            // Download remote asset hrefs of a collection into local storage
+            .route(
+                &format!("{}/collections/:collection_id/rehost", api_path),
+                post(rehost_collection).options(options_handler),
+            )
+            // Status of a trash purge or re-hosting left running in the background
+            .route(
+                &format!("{}/jobs/:job_id", api_path),
+                get(job_status).options(options_handler),
+            )
+            // Deleted collections and items, restoring and purging them
+            .route(
+                &format!("{}/trash", api_path),
+                get(list_trash).delete(empty_trash).options(options_handler),
+            )
+            .route(
+                &format!("{}/trash/collections/:collection_id", api_path),
+                delete(purge_collection).options(options_handler),
+            )
+            .route(
+                &format!("{}/trash/collections/:collection_id/restore", api_path),
+                post(restore_collection).options(options_handler),
+            )
+            .route(
+                &format!("{}/trash/collections/:collection_id/items/:item_id", api_path),
+                delete(purge_item).options(options_handler),
+            )
+            .route(
+                &format!(
+                    "{}/trash/collections/:collection_id/items/:item_id/restore",
+                    api_path
+                ),
+                post(restore_item).options(options_handler),
+            )
+            // Retention rules expiring old items, and a preview of what they remove
+            .route(
+                &format!("{}/collections/:collection_id/retention", api_path),
+                get(get_retention_policy)
+                    .put(put_retention_policy)
+                    .delete(delete_retention_policy)
+                    .options(options_handler),
+            )
+            .route(
+                &format!("{}/collections/:collection_id/retention/preview", api_path),
+                get(preview_retention_policy).options(options_handler),
+            )
+            // Peer sync: revision vectors, record exchange and pushed changes
+            .route(
+                &format!("{}/sync", api_path),
+                get(sync_revisions).post(sync_push).options(options_handler),
+            )
+            .route(
+                &format!("{}/sync/records", api_path),
+                post(sync_records).options(options_handler),
+            );
+    }
+
+    if features.enable_uploads {
+        router = router.route(
+            &format!("{}/upload/:collection_id/:item_id/:asset_key", api_path),
+            post(upload_asset)
+                .layer(DefaultBodyLimit::max(upload_limit))
+                .options(options_handler),
+        )
+        // Chunked uploads that resume where they stopped, even after a
+        // restart; each chunk is held to the upload body limit rather than
+        // the JSON one
+        .route(
+            &format!("{}/upload/:collection_id/:item_id/:asset_key/sessions", api_path),
+            get(list_upload_sessions)
+                .post(create_upload_session)
+                .options(options_handler),
         )
+        .route(
+            &format!(
+                "{}/upload/:collection_id/:item_id/:asset_key/sessions/:session_id",
+                api_path
+            ),
+            get(get_upload_session)
+                .patch(append_upload_chunk)
+                .delete(delete_upload_session)
+                .layer(DefaultBodyLimit::max(upload_limit))
+                .options(options_handler),
+        );
+    }
+
+    // Under a version path, the bare domain sends browsers where the
+    // `root_redirect` setting says and other clients to the catalog
+    if api_path != "/" {
+        router = router.route("/", get(root_redirect).options(options_handler));
+    }
+
+    router
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            response_cache,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            write_access,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            access_log,
+        ))
+        .layer(DefaultBodyLimit::max(json_limit))
+        .layer(axum::middleware::from_fn_with_state(
+            body_limits,
+            payload_too_large,
+        ))
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
                 .allow_methods(Any)
                 .allow_headers(Any),
         )
+        .layer(axum::middleware::from_fn(format_negotiation))
         .layer(axum::middleware::from_fn_with_state(
             (),
             |req: axum::extract::Request, next: axum::middleware::Next| async move {
@@ -115,10 +449,23 @@ pub async fn start_stac_server(
     db_service: DatabaseService,
     config: Config,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let app = create_stac_router(db_service, config);
+    let acme = AcmeSettings::load(&db_service).await;
+    let app = create_stac_router(db_service, config.clone());
     let listener = TcpListener::bind(format!("{}:{}", host, port)).await?;
 
+    // Peer addresses feed the anonymised client counts of the access statistics
+    let http = axum::serve(
+        listener,
+        app.clone().into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    );
 
-    axum::serve(listener, app).await?;
+    // With an ACME domain the same router is also served over HTTPS
+    match acme {
+        Some(acme) => tokio::select! {
+            result = http.into_future() => result?,
+            result = serve_https(host, app, acme, &config) => result?,
+        },
+        None => http.await?,
+    }
     Ok(())
 }
@@ -0,0 +1,336 @@
+use crate::models::item::Geometry;
+use crate::models::provider::Provider;
+use crate::models::{Properties, SpatialExtent, TemporalExtent};
+use crate::server::error::ApiError;
+use crate::server::item_schema::{describe_violations, SchemaViolation};
+use axum::response::{IntoResponse, Response};
+use chrono::DateTime;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// `validate` flag accepted by collection and item creates and updates;
+/// `false` skips the STAC checks
+#[derive(Debug, Default, Deserialize)]
+pub struct ValidateQuery {
+    pub validate: Option<bool>,
+}
+
+impl ValidateQuery {
+    pub fn enabled(&self) -> bool {
+        self.validate != Some(false)
+    }
+}
+
+/// Checks an item document against the STAC item rules the server relies
+/// on. Every broken rule is reported, each with the JSON pointer it applies
+/// to. Links and the bbox are not required: the server generates the first
+/// and computes the second from the geometry.
+pub fn validate_stac_item(item: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    let Some(object) = item.as_object() else {
+        violation(&mut violations, "", "Item must be a JSON object");
+        return violations;
+    };
+
+    if object.get("type").and_then(|t| t.as_str()) != Some("Feature") {
+        violation(&mut violations, "/type", "type must be 'Feature'");
+    }
+    require_string(&mut violations, item, "stac_version");
+    require_string(&mut violations, item, "id");
+
+    match object.get("geometry") {
+        None => violation(&mut violations, "/geometry", "geometry is required (it may be null)"),
+        Some(Value::Null) => {}
+        Some(geometry) => match serde_json::from_value::<Geometry>(geometry.clone()) {
+            Ok(geometry) => check_geometry(&mut violations, "/geometry", &geometry),
+            Err(e) => violation(
+                &mut violations,
+                "/geometry",
+                &format!("geometry is not a GeoJSON geometry: {}", e),
+            ),
+        },
+    }
+
+    if let Some(bbox) = object.get("bbox") {
+        check_bbox(&mut violations, "/bbox", bbox);
+    }
+
+    match object.get("properties") {
+        Some(properties @ Value::Object(_)) => check_properties(&mut violations, properties),
+        _ => violation(&mut violations, "/properties", "properties must be an object"),
+    }
+
+    match object.get("assets") {
+        None => {}
+        Some(Value::Object(assets)) => {
+            for (key, asset) in assets {
+                if !asset.get("href").is_some_and(|href| href.is_string()) {
+                    violation(
+                        &mut violations,
+                        &format!("/assets/{}/href", key),
+                        "asset href must be a string",
+                    );
+                }
+            }
+        }
+        Some(_) => violation(&mut violations, "/assets", "assets must be an object"),
+    }
+
+    check_links(&mut violations, object.get("links"));
+    violations
+}
+
+/// Checks a collection document against the STAC collection rules. Members
+/// the server fills in on create (`type`, `stac_version`, `extent`, `links`)
+/// are checked only when given.
+pub fn validate_stac_collection(collection: &Value) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    let Some(object) = collection.as_object() else {
+        violation(&mut violations, "", "Collection must be a JSON object");
+        return violations;
+    };
+
+    if object
+        .get("type")
+        .is_some_and(|t| t.as_str() != Some("Collection"))
+    {
+        violation(&mut violations, "/type", "type must be 'Collection'");
+    }
+    if object.contains_key("stac_version") {
+        require_string(&mut violations, collection, "stac_version");
+    }
+    require_string(&mut violations, collection, "id");
+    require_string(&mut violations, collection, "description");
+    require_string(&mut violations, collection, "license");
+
+    if let Some(extent) = object.get("extent") {
+        match extent.get("spatial").cloned().map(serde_json::from_value::<SpatialExtent>) {
+            Some(Ok(spatial)) if spatial.is_valid() => {
+                for (index, bbox) in spatial.bbox.iter().enumerate() {
+                    check_bbox(
+                        &mut violations,
+                        &format!("/extent/spatial/bbox/{}", index),
+                        &Value::from(bbox.clone()),
+                    );
+                }
+            }
+            Some(Ok(_)) => violation(
+                &mut violations,
+                "/extent/spatial/bbox",
+                "spatial extent needs at least one bbox, all of 4 or all of 6 numbers",
+            ),
+            Some(Err(e)) => violation(&mut violations, "/extent/spatial", &e.to_string()),
+            None => violation(&mut violations, "/extent/spatial", "spatial extent is required"),
+        }
+        match extent.get("temporal").cloned().map(serde_json::from_value::<TemporalExtent>) {
+            Some(Ok(temporal)) if !temporal.is_valid() => violation(
+                &mut violations,
+                "/extent/temporal/interval",
+                "temporal extent needs at least one interval of two date-times or nulls",
+            ),
+            Some(Ok(temporal)) if !temporal.has_valid_timestamps() => violation(
+                &mut violations,
+                "/extent/temporal/interval",
+                "temporal extent date-times must be RFC 3339",
+            ),
+            Some(Ok(_)) => {}
+            Some(Err(e)) => violation(&mut violations, "/extent/temporal", &e.to_string()),
+            None => violation(&mut violations, "/extent/temporal", "temporal extent is required"),
+        }
+    }
+
+    if let Some(providers) = object.get("providers") {
+        match serde_json::from_value::<Vec<Provider>>(providers.clone()) {
+            Ok(providers) => {
+                if providers.iter().any(|provider| !provider.is_valid()) {
+                    violation(
+                        &mut violations,
+                        "/providers",
+                        "a provider may have the 'host' role at most once",
+                    );
+                }
+            }
+            Err(e) => violation(&mut violations, "/providers", &e.to_string()),
+        }
+    }
+
+    check_links(&mut violations, object.get("links"));
+    violations
+}
+
+/// 400 response listing every STAC rule a document breaks
+pub fn stac_violation_response(kind: &str, violations: &[SchemaViolation]) -> Response {
+    ApiError::bad_request(format!(
+        "{} is not valid STAC: {}",
+        kind,
+        describe_violations(violations)
+    ))
+    .with_extension("violations", serde_json::json!(violations))
+    .into_response()
+}
+
+fn violation(violations: &mut Vec<SchemaViolation>, path: &str, message: &str) {
+    violations.push(SchemaViolation {
+        path: path.to_string(),
+        message: message.to_string(),
+    });
+}
+
+fn require_string(violations: &mut Vec<SchemaViolation>, document: &Value, key: &str) {
+    if !document
+        .get(key)
+        .and_then(|value| value.as_str())
+        .is_some_and(|value| !value.is_empty())
+    {
+        violation(
+            violations,
+            &format!("/{}", key),
+            &format!("{} must be a non-empty string", key),
+        );
+    }
+}
+
+/// Datetime rules of common metadata, on top of the typed `Properties` checks
+fn check_properties(violations: &mut Vec<SchemaViolation>, properties: &Value) {
+    let typed = match serde_json::from_value::<Properties>(properties.clone()) {
+        Ok(typed) => typed,
+        Err(e) => {
+            violation(violations, "/properties", &e.to_string());
+            return;
+        }
+    };
+    if !typed.has_valid_datetimes() {
+        for (key, value) in [
+            ("datetime", &typed.datetime),
+            ("start_datetime", &typed.start_datetime),
+            ("end_datetime", &typed.end_datetime),
+        ] {
+            if value
+                .as_ref()
+                .is_some_and(|text| DateTime::parse_from_rfc3339(text).is_err())
+            {
+                violation(
+                    violations,
+                    &format!("/properties/{}", key),
+                    &format!("{} must be an RFC 3339 date-time", key),
+                );
+            }
+        }
+    }
+    if typed.datetime.is_none() && !typed.is_valid() {
+        violation(
+            violations,
+            "/properties/datetime",
+            "datetime is null, so start_datetime and end_datetime are required",
+        );
+    }
+    if let (Some(start), Some(end)) = (typed.start_datetime_as_utc(), typed.end_datetime_as_utc()) {
+        if start > end {
+            violation(
+                violations,
+                "/properties/end_datetime",
+                "end_datetime is before start_datetime",
+            );
+        }
+    }
+}
+
+/// Positions within WGS 84 bounds and closed polygon rings of at least four
+/// positions
+fn check_geometry(violations: &mut Vec<SchemaViolation>, path: &str, geometry: &Geometry) {
+    match geometry {
+        Geometry::Point { coordinates } => check_position(violations, path, coordinates),
+        Geometry::LineString { coordinates } | Geometry::MultiPoint { coordinates } => {
+            if matches!(geometry, Geometry::LineString { .. }) && coordinates.len() < 2 {
+                violation(violations, path, "a LineString needs at least two positions");
+            }
+            for position in coordinates {
+                check_position(violations, path, position);
+            }
+        }
+        Geometry::Polygon { coordinates } | Geometry::MultiLineString { coordinates } => {
+            let polygon = matches!(geometry, Geometry::Polygon { .. });
+            for ring in coordinates {
+                if polygon {
+                    check_ring(violations, path, ring);
+                }
+                for position in ring {
+                    check_position(violations, path, position);
+                }
+            }
+        }
+        Geometry::MultiPolygon { coordinates } => {
+            for ring in coordinates.iter().flatten() {
+                check_ring(violations, path, ring);
+                for position in ring {
+                    check_position(violations, path, position);
+                }
+            }
+        }
+        Geometry::GeometryCollection { geometries } => {
+            for (index, geometry) in geometries.iter().enumerate() {
+                check_geometry(violations, &format!("{}/geometries/{}", path, index), geometry);
+            }
+        }
+    }
+}
+
+fn check_position(violations: &mut Vec<SchemaViolation>, path: &str, position: &[f64]) {
+    let message = match position {
+        [] | [_] => "a position needs at least two coordinates",
+        [x, ..] if !(-180.0..=180.0).contains(x) => "longitude must be within -180 to 180",
+        [_, y, ..] if !(-90.0..=90.0).contains(y) => "latitude must be within -90 to 90",
+        _ => return,
+    };
+    if !violations.iter().any(|v| v.path == path && v.message == message) {
+        violation(violations, path, message);
+    }
+}
+
+fn check_ring(violations: &mut Vec<SchemaViolation>, path: &str, ring: &[Vec<f64>]) {
+    if ring.len() < 4 {
+        violation(violations, path, "a polygon ring needs at least four positions");
+    } else if ring.first() != ring.last() {
+        violation(violations, path, "a polygon ring must end at its first position");
+    }
+}
+
+/// Four or six numbers, with each minimum not above its maximum (longitude
+/// may wrap across the antimeridian)
+fn check_bbox(violations: &mut Vec<SchemaViolation>, path: &str, bbox: &Value) {
+    let numbers: Option<Vec<f64>> = bbox
+        .as_array()
+        .map(|values| values.iter().map(|value| value.as_f64()).collect())
+        .unwrap_or(None);
+    let Some(numbers) = numbers.filter(|n| n.len() == 4 || n.len() == 6) else {
+        violation(violations, path, "bbox must be an array of 4 or 6 numbers");
+        return;
+    };
+    let half = numbers.len() / 2;
+    for axis in 1..half {
+        if numbers[axis] > numbers[half + axis] {
+            violation(violations, path, "bbox minimum is above its maximum");
+            return;
+        }
+    }
+}
+
+fn check_links(violations: &mut Vec<SchemaViolation>, links: Option<&Value>) {
+    match links {
+        None => {}
+        Some(Value::Array(links)) => {
+            for (index, link) in links.iter().enumerate() {
+                for key in ["href", "rel"] {
+                    if !link.get(key).is_some_and(|value| value.is_string()) {
+                        violation(
+                            violations,
+                            &format!("/links/{}/{}", index, key),
+                            &format!("link {} must be a string", key),
+                        );
+                    }
+                }
+            }
+        }
+        Some(_) => violation(violations, "/links", "links must be an array"),
+    }
+}
@@ -0,0 +1,26 @@
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+
+/// Handler for the catalog's item counts, extents and asset storage, per
+/// collection and in total (GET /stats)
+pub async fn catalog_statistics(State(state): State<AppState>) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+
+    match state.db_service.get_statistics().await {
+        Ok(statistics) => (headers, serde_json::to_string(&statistics).unwrap()).into_response(),
+        Err(e) => {
+            ApiError::internal(format!("Failed to read catalog statistics: {}", e)).into_response()
+        }
+    }
+}
@@ -0,0 +1,602 @@
+use crate::database::settings::{Setting, SyncReplicaId};
+use crate::database::sync_revisions::{
+    compare_vectors, content_hash, is_content_hash, merge_vectors, Causality, RecordKey, Revision,
+};
+use crate::database::{DatabaseService, DbCollection, DbItem};
+use crate::server::error::ApiError;
+use crate::server::middleware::add_cors_headers;
+use crate::server::outbound::http_client;
+use crate::server::server::AppState;
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// A record exchanged between peers: its revision and, unless deleted, the
+/// stored collection or item document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncRecord {
+    #[serde(flatten)]
+    pub revision: Revision,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document: Option<Value>,
+}
+
+/// Which side wins when both changed a record since the last sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Local,
+    Remote,
+}
+
+/// Result of applying remote records
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ApplyOutcome {
+    pub applied: usize,
+    /// Records both sides changed with different content; left untouched
+    pub conflicts: Vec<RecordKey>,
+    pub errors: Vec<String>,
+}
+
+/// Result of a sync with a peer
+#[derive(Debug, Default, Serialize)]
+pub struct SyncReport {
+    pub pulled: usize,
+    pub pushed: usize,
+    pub conflicts: Vec<RecordKey>,
+    pub errors: Vec<String>,
+}
+
+/// Serializes revision bookkeeping so a refresh never interleaves with applying
+/// records from a peer
+fn sync_lock() -> &'static tokio::sync::Mutex<()> {
+    static LOCK: OnceLock<tokio::sync::Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| tokio::sync::Mutex::new(()))
+}
+
+/// This installation's replica id, generated and stored on first use
+pub async fn replica_id(db_service: &DatabaseService) -> Result<String, String> {
+    let id = db_service
+        .settings
+        .get::<SyncReplicaId>()
+        .await
+        .unwrap_or_else(|_| SyncReplicaId::default_value());
+    if !id.is_empty() {
+        return Ok(id);
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    db_service
+        .settings
+        .set::<SyncReplicaId>(&id)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+/// Content hashes of every collection and item in the catalog, as stored
+/// with them
+async fn current_hashes(db_service: &DatabaseService) -> Result<BTreeMap<RecordKey, String>, String> {
+    let mut hashes = BTreeMap::new();
+    let collections = db_service
+        .collections
+        .content_hashes()
+        .await
+        .map_err(|e| e.to_string())?;
+    for (collection_id, hash) in collections {
+        let items = db_service
+            .items
+            .content_hashes(&collection_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        for (item_id, hash) in items {
+            hashes.insert(RecordKey::item(&collection_id, &item_id), hash);
+        }
+        hashes.insert(RecordKey::collection(&collection_id), hash);
+    }
+    Ok(hashes)
+}
+
+/// Bumps this replica's counter on every record created, changed or deleted
+/// since the last refresh. Changes are found by content hash, so every write
+/// path (API, UI, CLI) is covered without hooks.
+pub async fn refresh_revisions(db_service: &DatabaseService) -> Result<Vec<Revision>, String> {
+    let _guard = sync_lock().lock().await;
+    refresh_locked(db_service).await
+}
+
+async fn refresh_locked(db_service: &DatabaseService) -> Result<Vec<Revision>, String> {
+    let me = replica_id(db_service).await?;
+    let current = current_hashes(db_service).await?;
+    let mut revisions: BTreeMap<RecordKey, Revision> = db_service
+        .sync_revisions
+        .get_all()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|r| (r.key.clone(), r))
+        .collect();
+
+    let mut changed = Vec::new();
+    for (key, hash) in &current {
+        match revisions.get(key) {
+            Some(revision) if !revision.deleted && &revision.hash == hash => {}
+            existing => {
+                let mut vector = existing.map(|r| r.vector.clone()).unwrap_or_default();
+                *vector.entry(me.clone()).or_insert(0) += 1;
+                changed.push(Revision {
+                    key: key.clone(),
+                    vector,
+                    hash: hash.clone(),
+                    deleted: false,
+                });
+            }
+        }
+    }
+    for (key, revision) in &revisions {
+        if !revision.deleted && !current.contains_key(key) {
+            let mut vector = revision.vector.clone();
+            *vector.entry(me.clone()).or_insert(0) += 1;
+            changed.push(Revision {
+                key: key.clone(),
+                vector,
+                hash: String::new(),
+                deleted: true,
+            });
+        }
+    }
+
+    for revision in changed {
+        db_service
+            .sync_revisions
+            .upsert(&revision)
+            .await
+            .map_err(|e| e.to_string())?;
+        revisions.insert(revision.key.clone(), revision);
+    }
+    Ok(revisions.into_values().collect())
+}
+
+/// Revisions of the requested records with their documents
+pub async fn record_documents(
+    db_service: &DatabaseService,
+    keys: &[RecordKey],
+) -> Result<Vec<SyncRecord>, String> {
+    let mut records = Vec::new();
+    for key in keys {
+        let Some(revision) = db_service
+            .sync_revisions
+            .get(key)
+            .await
+            .map_err(|e| e.to_string())?
+        else {
+            continue;
+        };
+        let document = if revision.deleted {
+            None
+        } else if key.is_collection() {
+            db_service
+                .collections
+                .get_by_id(&key.collection_id)
+                .await
+                .map_err(|e| e.to_string())?
+                .and_then(|c| serde_json::to_value(c).ok())
+        } else {
+            db_service
+                .items
+                .get_by_id(&key.collection_id, &key.item_id)
+                .await
+                .map_err(|e| e.to_string())?
+                .and_then(|i| serde_json::to_value(i).ok())
+        };
+        if document.is_none() && !revision.deleted {
+            continue;
+        }
+        records.push(SyncRecord { revision, document });
+    }
+    Ok(records)
+}
+
+/// Refuses revisions from a peer whose content hashes cannot be compared
+/// with this build's: every record both sides changed would be reported as a
+/// conflict, and identical ones as changed
+fn check_peer_hashes<'a>(revisions: impl IntoIterator<Item = &'a Revision>) -> Result<(), String> {
+    let legacy = revisions
+        .into_iter()
+        .any(|revision| !revision.deleted && !is_content_hash(&revision.hash));
+    if legacy {
+        return Err(
+            "The peer runs a ZenSTAC release with unstable content hashes; upgrade it to sync"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Applies records received from a peer. A record is written when its vector
+/// has seen every local change; concurrent changes with different content are
+/// conflicts unless `resolution` is `Remote`.
+pub async fn apply_records(
+    db_service: &DatabaseService,
+    mut records: Vec<SyncRecord>,
+    resolution: Option<Resolution>,
+) -> Result<ApplyOutcome, String> {
+    check_peer_hashes(records.iter().map(|record| &record.revision))?;
+    let _guard = sync_lock().lock().await;
+    // Unsynced local edits must count before comparing vectors
+    let local: BTreeMap<RecordKey, Revision> = refresh_locked(db_service)
+        .await?
+        .into_iter()
+        .map(|r| (r.key.clone(), r))
+        .collect();
+
+    // Collections before their items; deletions of items before collections
+    records.sort_by_key(|r| match (r.revision.key.is_collection(), r.revision.deleted) {
+        (true, false) => 0,
+        (false, false) => 1,
+        (false, true) => 2,
+        (true, true) => 3,
+    });
+
+    let mut outcome = ApplyOutcome::default();
+    for record in records {
+        let remote = &record.revision;
+        let local_revision = local.get(&remote.key);
+        let local_vector = local_revision.map(|r| r.vector.clone()).unwrap_or_default();
+
+        let write = match compare_vectors(&remote.vector, &local_vector) {
+            Causality::Equal | Causality::Older => continue,
+            Causality::Newer => true,
+            Causality::Concurrent => {
+                let same_content = local_revision.is_some_and(|l| {
+                    l.deleted == remote.deleted && (l.deleted || l.hash == remote.hash)
+                });
+                if same_content {
+                    false
+                } else if resolution == Some(Resolution::Remote) {
+                    true
+                } else {
+                    outcome.conflicts.push(remote.key.clone());
+                    continue;
+                }
+            }
+        };
+
+        let hash = if write {
+            match write_record(db_service, &record).await {
+                Ok(hash) => hash,
+                Err(e) => {
+                    outcome.errors.push(e);
+                    continue;
+                }
+            }
+        } else {
+            local_revision.map(|l| l.hash.clone()).unwrap_or_default()
+        };
+
+        let revision = Revision {
+            key: remote.key.clone(),
+            vector: merge_vectors(&local_vector, &remote.vector),
+            hash,
+            deleted: remote.deleted,
+        };
+        db_service
+            .sync_revisions
+            .upsert(&revision)
+            .await
+            .map_err(|e| e.to_string())?;
+        outcome.applied += 1;
+    }
+    Ok(outcome)
+}
+
+/// Stores or deletes a remote record locally and returns its content hash
+async fn write_record(db_service: &DatabaseService, record: &SyncRecord) -> Result<String, String> {
+    let key = &record.revision.key;
+    let label = if key.is_collection() {
+        format!("Collection {}", key.collection_id)
+    } else {
+        format!("Item {}/{}", key.collection_id, key.item_id)
+    };
+    let fail = |e: String| format!("{}: {}", label, e);
+
+    if record.revision.deleted {
+        if key.is_collection() {
+            db_service.collections.delete(&key.collection_id).await
+        } else {
+            db_service.items.delete(&key.collection_id, &key.item_id).await
+        }
+        .map_err(|e| fail(e.to_string()))?;
+        return Ok(String::new());
+    }
+
+    let document = record
+        .document
+        .clone()
+        .ok_or_else(|| fail("missing document".to_string()))?;
+    if key.is_collection() {
+        let mut collection: DbCollection =
+            serde_json::from_value(document).map_err(|e| fail(e.to_string()))?;
+        collection.id = key.collection_id.clone();
+        let exists = db_service
+            .collections
+            .get_by_id(&collection.id)
+            .await
+            .map_err(|e| fail(e.to_string()))?
+            .is_some();
+        // A collection in the local trash comes back with the remote content
+        let exists = exists
+            || db_service
+                .collections
+                .restore(&collection.id)
+                .await
+                .map_err(|e| fail(e.to_string()))?;
+        if exists {
+            db_service.collections.update(&collection).await
+        } else {
+            db_service.collections.create(&collection).await
+        }
+        .map_err(|e| fail(e.to_string()))?;
+        Ok(content_hash(&collection))
+    } else {
+        let mut item: DbItem = serde_json::from_value(document).map_err(|e| fail(e.to_string()))?;
+        item.collection_id = key.collection_id.clone();
+        item.id = key.item_id.clone();
+        if db_service
+            .collections
+            .get_by_id(&item.collection_id)
+            .await
+            .map_err(|e| fail(e.to_string()))?
+            .is_none()
+        {
+            return Err(fail("collection does not exist".to_string()));
+        }
+        let exists = db_service
+            .items
+            .get_by_id(&item.collection_id, &item.id)
+            .await
+            .map_err(|e| fail(e.to_string()))?
+            .is_some();
+        let exists = exists
+            || db_service
+                .items
+                .restore(&item.collection_id, &item.id)
+                .await
+                .map_err(|e| fail(e.to_string()))?;
+        if exists {
+            db_service.items.update(&item).await
+        } else {
+            db_service.items.create(&item).await
+        }
+        .map_err(|e| fail(e.to_string()))?;
+        Ok(content_hash(&item))
+    }
+}
+
+async fn peer_request<T: serde::de::DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+    url: &str,
+    token: Option<&str>,
+) -> Result<T, String> {
+    let request = match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    }
+    .timeout(Duration::from_secs(60));
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("{} is not reachable: {}", url, e))?;
+    let status = response.status();
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!(
+            "{} answered {}: {}",
+            url,
+            status,
+            String::from_utf8_lossy(&bytes)
+        ));
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("{} returned an invalid response: {}", url, e))
+}
+
+fn post_json<B: Serialize>(
+    client: &reqwest::Client,
+    url: &str,
+    body: &B,
+) -> Result<reqwest::RequestBuilder, String> {
+    Ok(client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(body).map_err(|e| e.to_string())?))
+}
+
+#[derive(Deserialize)]
+struct PeerRevisions {
+    revisions: Vec<Revision>,
+}
+
+#[derive(Deserialize)]
+struct PeerRecords {
+    records: Vec<SyncRecord>,
+}
+
+/// Exchanges changes with another ZenSTAC instance at `peer_url` (its API
+/// root, e.g. `http://192.168.1.20:3000/v1`). Records only one side changed
+/// are copied to the other; records both changed differently are reported as
+/// conflicts, or resolved in favour of `resolution`. `credential` names a
+/// stored secret sent to the peer as a bearer token.
+pub async fn sync_with_peer(
+    db_service: &DatabaseService,
+    peer_url: &str,
+    resolution: Option<Resolution>,
+    credential: Option<&str>,
+) -> Result<SyncReport, String> {
+    let token = match credential {
+        Some(id) => Some(
+            db_service
+                .secrets
+                .get(id)
+                .await?
+                .ok_or_else(|| format!("Secret '{}' is not set", id))?,
+        ),
+        None => None,
+    };
+    let token = token.as_deref();
+    let client = http_client(db_service).await?;
+    let peer_url = peer_url.trim_end_matches('/');
+    let sync_url = format!("{}/sync", peer_url);
+    let records_url = format!("{}/sync/records", peer_url);
+
+    let local: BTreeMap<RecordKey, Revision> = refresh_revisions(db_service)
+        .await?
+        .into_iter()
+        .map(|r| (r.key.clone(), r))
+        .collect();
+    let remote: BTreeMap<RecordKey, Revision> =
+        peer_request::<PeerRevisions>(client.get(&sync_url), &sync_url, token)
+            .await?
+            .revisions
+            .into_iter()
+            .map(|r| (r.key.clone(), r))
+            .collect();
+    check_peer_hashes(remote.values())?;
+
+    let mut pull = Vec::new();
+    let mut push = Vec::new();
+    let mut report = SyncReport::default();
+    let keys: BTreeSet<&RecordKey> = local.keys().chain(remote.keys()).collect();
+    for key in keys {
+        match (local.get(key), remote.get(key)) {
+            (Some(_), None) => push.push(key.clone()),
+            (None, Some(_)) => pull.push(key.clone()),
+            (Some(l), Some(r)) => match compare_vectors(&l.vector, &r.vector) {
+                Causality::Equal => {}
+                Causality::Newer => push.push(key.clone()),
+                Causality::Older => pull.push(key.clone()),
+                Causality::Concurrent => {
+                    let same_content = l.deleted == r.deleted && (l.deleted || l.hash == r.hash);
+                    if same_content || resolution == Some(Resolution::Remote) {
+                        pull.push(key.clone());
+                    } else if resolution == Some(Resolution::Local) {
+                        // A local revision that has seen both histories wins on the peer
+                        let mut vector = merge_vectors(&l.vector, &r.vector);
+                        *vector.entry(replica_id(db_service).await?).or_insert(0) += 1;
+                        db_service
+                            .sync_revisions
+                            .upsert(&Revision { vector, ..l.clone() })
+                            .await
+                            .map_err(|e| e.to_string())?;
+                        push.push(key.clone());
+                    } else {
+                        report.conflicts.push(key.clone());
+                    }
+                }
+            },
+            (None, None) => {}
+        }
+    }
+
+    if !pull.is_empty() {
+        let records = peer_request::<PeerRecords>(
+            post_json(&client, &records_url, &serde_json::json!({ "keys": pull }))?,
+            &records_url,
+            token,
+        )
+        .await?
+        .records;
+        let outcome = apply_records(db_service, records, resolution).await?;
+        report.pulled = outcome.applied;
+        report.conflicts.extend(outcome.conflicts);
+        report.errors.extend(outcome.errors);
+    }
+
+    if !push.is_empty() {
+        let records = record_documents(db_service, &push).await?;
+        let outcome = peer_request::<ApplyOutcome>(
+            post_json(&client, &sync_url, &serde_json::json!({ "records": records }))?,
+            &sync_url,
+            token,
+        )
+        .await?;
+        report.pushed = outcome.applied;
+        report.conflicts.extend(outcome.conflicts);
+        report
+            .errors
+            .extend(outcome.errors.into_iter().map(|e| format!("Peer: {}", e)));
+    }
+
+    report.conflicts.sort();
+    report.conflicts.dedup();
+    Ok(report)
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Handler listing the revision of every record (GET /sync)
+pub async fn sync_revisions(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let replica = replica_id(&state.db_service)
+        .await
+        .map_err(ApiError::internal)?;
+    let revisions = refresh_revisions(&state.db_service)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(json_response(
+        StatusCode::OK,
+        serde_json::json!({
+            "replica": replica,
+            "revisions": revisions
+        }),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct RecordsRequest {
+    keys: Vec<RecordKey>,
+}
+
+/// Handler returning records with their documents (POST /sync/records)
+pub async fn sync_records(
+    State(state): State<AppState>,
+    Json(request): Json<RecordsRequest>,
+) -> Result<Response, ApiError> {
+    let records = record_documents(&state.db_service, &request.keys)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(json_response(
+        StatusCode::OK,
+        serde_json::json!({ "records": records }),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct PushRequest {
+    records: Vec<SyncRecord>,
+}
+
+/// Handler applying records pushed by a peer (POST /sync). Conflicting records
+/// are never overwritten here; the pushing side resolves them.
+pub async fn sync_push(
+    State(state): State<AppState>,
+    Json(request): Json<PushRequest>,
+) -> Result<Response, ApiError> {
+    let outcome = apply_records(&state.db_service, request.records, None)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(json_response(StatusCode::OK, serde_json::json!(outcome)))
+}
@@ -0,0 +1,186 @@
+use crate::database::{DatabaseService, DbItem};
+use crate::server::utils::ServerConfig;
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+
+/// Key and asset of the item's thumbnail: the `thumbnail` key, else the first
+/// asset with the `thumbnail` role
+fn thumbnail_asset(item: &DbItem) -> Option<(&String, &Value)> {
+    let assets = item.assets.as_ref()?.as_object()?;
+    assets.iter().find(|(key, asset)| key.as_str() == "thumbnail" || has_thumbnail_role(asset))
+}
+
+fn has_thumbnail_role(asset: &Value) -> bool {
+    asset
+        .get("roles")
+        .and_then(|r| r.as_array())
+        .is_some_and(|roles| roles.iter().any(|r| r.as_str() == Some("thumbnail")))
+}
+
+/// Absolute URL of the item's thumbnail asset, if it has one
+pub fn thumbnail_href(server_config: &ServerConfig, item: &DbItem) -> Option<String> {
+    let (key, asset) = thumbnail_asset(item)?;
+    let href = asset.get("href")?.as_str()?;
+    if href.starts_with("http://") || href.starts_with("https://") {
+        Some(href.to_string())
+    } else {
+        Some(server_config.asset_href(&item.collection_id, &item.id, key))
+    }
+}
+
+/// Item whose thumbnail represents a collection: the pinned item while it
+/// still has a thumbnail, else the most recently updated item with one
+pub async fn thumbnail_item(db_service: &DatabaseService, collection_id: &str) -> Option<DbItem> {
+    let pinned = db_service
+        .collections
+        .get_thumbnail_item(collection_id)
+        .await
+        .ok()
+        .flatten();
+    if let Some(item_id) = pinned {
+        if let Ok(Some(item)) = db_service.items.get_by_id(collection_id, &item_id).await {
+            if thumbnail_asset(&item).is_some() {
+                return Some(item);
+            }
+        }
+    }
+    db_service
+        .items
+        .latest_with_thumbnail(collection_id)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Collection asset pointing at the representative item's thumbnail
+pub async fn collection_thumbnail(
+    db_service: &DatabaseService,
+    server_config: &ServerConfig,
+    collection_id: &str,
+) -> Option<Value> {
+    let item = thumbnail_item(db_service, collection_id).await?;
+    let href = thumbnail_href(server_config, &item)?;
+    let mut asset = json!({
+        "href": href,
+        "title": format!("Thumbnail of item {}", item.id),
+        "roles": ["thumbnail"]
+    });
+    if let Some(media_type) = thumbnail_asset(&item).and_then(|(_, a)| a.get("type")) {
+        asset["type"] = media_type.clone();
+    }
+    Some(asset)
+}
+
+/// Adds the `thumbnail` asset to a STAC collection unless it has its own
+/// thumbnail
+pub async fn add_collection_thumbnail(
+    db_service: &DatabaseService,
+    server_config: &ServerConfig,
+    collection: &mut Value,
+) {
+    let Some(collection_id) = collection.get("id").and_then(|id| id.as_str()).map(str::to_string)
+    else {
+        return;
+    };
+    let has_own = collection
+        .get("assets")
+        .and_then(|a| a.as_object())
+        .is_some_and(|assets| {
+            assets.iter().any(|(key, asset)| key == "thumbnail" || has_thumbnail_role(asset))
+        });
+    if has_own {
+        return;
+    }
+    let Some(thumbnail) = collection_thumbnail(db_service, server_config, &collection_id).await
+    else {
+        return;
+    };
+    if !collection.get("assets").is_some_and(Value::is_object) {
+        collection["assets"] = json!({});
+    }
+    collection["assets"]["thumbnail"] = thumbnail;
+}
+
+/// Pins an item's thumbnail as the collection's, or clears the pin (`None`)
+/// to fall back to the most recent item with a thumbnail
+pub async fn pin_collection_thumbnail(
+    db_service: &DatabaseService,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> Result<Option<String>, (StatusCode, String)> {
+    if let Some(item_id) = item_id {
+        let item = db_service
+            .items
+            .get_by_id(collection_id, item_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read item: {}", e)))?
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("Item '{}' not found in collection '{}'", item_id, collection_id),
+                )
+            })?;
+        if thumbnail_asset(&item).is_none() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Item '{}' has no thumbnail asset", item_id),
+            ));
+        }
+    }
+
+    match db_service
+        .collections
+        .set_thumbnail_item(collection_id, item_id)
+        .await
+    {
+        Ok(true) => Ok(item_id.map(str::to_string)),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            format!("Collection '{}' not found", collection_id),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to pin thumbnail: {}", e),
+        )),
+    }
+}
+
+/// Gallery of the collections' thumbnails for the HTML landing page; empty
+/// when no collection has one
+pub async fn gallery_html(db_service: &DatabaseService, server_config: &ServerConfig) -> String {
+    let Ok(collections) = db_service.collections.get_all().await else {
+        return String::new();
+    };
+    let mut figures = String::new();
+    for collection in &collections {
+        let Some(thumbnail) = collection_thumbnail(db_service, server_config, &collection.id).await
+        else {
+            continue;
+        };
+        let href = thumbnail.get("href").and_then(|h| h.as_str()).unwrap_or_default();
+        let title = collection.title.as_deref().unwrap_or(&collection.id);
+        figures.push_str(&format!(
+            "        <figure><a href=\"{}\"><img src=\"{}\" alt=\"{}\" loading=\"lazy\"></a><figcaption>{}</figcaption></figure>\n",
+            escape(&server_config.collection_href(&collection.id)),
+            escape(href),
+            escape(title),
+            escape(title)
+        ));
+    }
+    if figures.is_empty() {
+        return String::new();
+    }
+    format!(
+        "    <h2>Collections</h2>\n    <div class=\"gallery\">\n{}    </div>\n",
+        figures
+    )
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
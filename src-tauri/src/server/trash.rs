@@ -0,0 +1,473 @@
+use crate::config::Config;
+use crate::database::settings::{Setting, TrashRetentionDays};
+use crate::database::DatabaseService;
+use crate::server::asset_cleanup::AssetCleanup;
+use crate::server::error::ApiError;
+use crate::server::jobs::{self, Job, JobLimits};
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+
+fn json_response(status: StatusCode, headers: HeaderMap, body: serde_json::Value) -> Response {
+    let mut headers = add_cors_headers(headers);
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Path of the restore endpoint of a trashed collection or item
+fn restore_path(collection_id: &str, item_id: Option<&str>) -> String {
+    match item_id {
+        Some(item_id) => format!(
+            "/trash/collections/{}/items/{}/restore",
+            collection_id, item_id
+        ),
+        None => format!("/trash/collections/{}/restore", collection_id),
+    }
+}
+
+/// `409 Conflict` answer of a create whose id is taken. When the record
+/// holding it is in the trash, the problem says so and links to where it can
+/// be restored or purged, after which the id is free again.
+pub async fn id_taken_response(
+    db_service: &DatabaseService,
+    server_config: &ServerConfig,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> Response {
+    let in_trash = db_service
+        .collections
+        .in_trash(collection_id, item_id)
+        .await
+        .unwrap_or(false);
+    let record = match item_id {
+        Some(item_id) => format!(
+            "An item with id '{}' in collection '{}'",
+            item_id, collection_id
+        ),
+        None => format!("A collection with id '{}'", collection_id),
+    };
+    if !in_trash {
+        return ApiError::new(
+            StatusCode::CONFLICT,
+            "Conflict",
+            format!("{} already exists", record),
+        )
+        .into_response();
+    }
+
+    let restore_path = restore_path(collection_id, item_id);
+    let restore = server_config.href(&restore_path);
+    let purge = server_config.href(restore_path.trim_end_matches("/restore"));
+    ApiError::new(
+        StatusCode::CONFLICT,
+        "Conflict",
+        format!(
+            "{} is in the trash. Restore it with POST {}, or purge it with DELETE {} to create a new one.",
+            record, restore, purge
+        ),
+    )
+    .with_extension(
+        "links",
+        serde_json::json!([
+            { "href": restore, "rel": "restore", "method": "POST", "type": "application/json" },
+            { "href": purge, "rel": "purge", "method": "DELETE", "type": "application/json" }
+        ]),
+    )
+    .into_response()
+}
+
+/// Answer of a delete that moved a collection or item to the trash, linking
+/// to where it can be restored
+pub fn trashed_response(
+    server_config: &ServerConfig,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> Response {
+    let message = match item_id {
+        Some(item_id) => format!(
+            "Item '{}' of collection '{}' moved to the trash",
+            item_id, collection_id
+        ),
+        None => format!("Collection '{}' moved to the trash", collection_id),
+    };
+    let body = serde_json::json!({
+        "message": message,
+        "links": [{
+            "href": server_config.href(&restore_path(collection_id, item_id)),
+            "rel": "restore",
+            "method": "POST",
+            "type": "application/json"
+        }]
+    });
+    json_response(StatusCode::OK, HeaderMap::new(), body)
+}
+
+/// `202 Accepted` answer of a purge left running in a background job, with
+/// a `Location` header pointing at the job's status
+fn purge_accepted_response(server_config: &ServerConfig, message: String, job: &Job) -> Response {
+    let href = server_config.href(&format!("/jobs/{}", job.id));
+    let mut headers = HeaderMap::new();
+    if let Ok(location) = HeaderValue::from_str(&href) {
+        headers.insert("Location", location);
+    }
+    let body = serde_json::json!({
+        "message": message,
+        "job": job,
+        "links": [{ "href": href, "rel": "status", "type": "application/json" }]
+    });
+    json_response(StatusCode::ACCEPTED, headers, body)
+}
+
+/// Permanently deletes a collection or item in the trash: its record, the
+/// data kept beside it and its asset folder. Returns false when it is not in
+/// the trash, e.g. because it was restored meanwhile.
+pub async fn purge_record(
+    db_service: &DatabaseService,
+    config: &Config,
+    collection_id: &str,
+    item_id: Option<&str>,
+) -> Result<bool, String> {
+    let in_trash = db_service
+        .collections
+        .in_trash(collection_id, item_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !in_trash {
+        return Ok(false);
+    }
+
+    // The assets leave first: once the record is gone its id may be taken
+    // again, and the new record's uploads must not be caught by this purge
+    let cleanup = AssetCleanup::new(config);
+    let detached = cleanup.detach(collection_id, item_id).await?;
+    let purged = match item_id {
+        Some(item_id) => db_service.items.purge(collection_id, item_id).await,
+        None => db_service.collections.purge(collection_id).await,
+    };
+    if !matches!(purged, Ok(true)) {
+        if let Some(detached) = &detached {
+            cleanup.reattach(detached, collection_id, item_id).await?;
+        }
+        return purged.map_err(|e| e.to_string());
+    }
+
+    let label = match item_id {
+        Some(item_id) => format!("{}/{}", collection_id, item_id),
+        None => collection_id.to_string(),
+    };
+    let side_data = match item_id {
+        Some(item_id) => vec![
+            (
+                "item links",
+                db_service
+                    .item_links
+                    .delete_for_item(collection_id, item_id)
+                    .await,
+            ),
+            (
+                "asset sizes",
+                db_service
+                    .asset_sizes
+                    .delete_for_item(collection_id, item_id)
+                    .await,
+            ),
+            (
+                "translations",
+                db_service
+                    .translations
+                    .delete_for_item(collection_id, item_id)
+                    .await,
+            ),
+        ],
+        None => vec![
+            (
+                "item links",
+                db_service
+                    .item_links
+                    .delete_for_collection(collection_id)
+                    .await,
+            ),
+            (
+                "indexed properties",
+                db_service
+                    .indexed_properties
+                    .delete_for_collection(collection_id)
+                    .await,
+            ),
+            (
+                "computed properties",
+                db_service
+                    .computed_properties
+                    .delete_for_collection(collection_id)
+                    .await,
+            ),
+            (
+                "retention policy",
+                db_service
+                    .retention_policies
+                    .delete(collection_id)
+                    .await
+                    .map(|_| ()),
+            ),
+            (
+                "asset sizes",
+                db_service
+                    .asset_sizes
+                    .delete_for_collection(collection_id)
+                    .await,
+            ),
+            (
+                "translations",
+                db_service
+                    .translations
+                    .delete_for_collection(collection_id)
+                    .await,
+            ),
+        ],
+    };
+    for (what, result) in side_data {
+        if let Err(e) = result {
+            eprintln!("Failed to remove {} of {}: {}", what, label, e);
+        }
+    }
+
+    if let Some(detached) = &detached {
+        cleanup.remove(detached).await?;
+    }
+    Ok(true)
+}
+
+/// Purges every collection and item that has been in the trash for more
+/// than `days` days, returning how many were purged
+pub async fn purge_expired(
+    db_service: &DatabaseService,
+    config: &Config,
+    days: u32,
+) -> Result<usize, String> {
+    let cutoff = Utc::now() - chrono::Duration::days(days as i64);
+    let records = db_service
+        .collections
+        .trashed()
+        .await
+        .map_err(|e| e.to_string())?;
+    let expired: Vec<RecordKey> = records
+        .into_iter()
+        .filter(|record| {
+            DateTime::parse_from_rfc3339(&record.deleted_at)
+                .map(|deleted_at| deleted_at < cutoff)
+                .unwrap_or(false)
+        })
+        .map(|record| (record.collection_id, record.item_id))
+        .collect();
+    purge_records(db_service, config, &expired).await
+}
+
+/// Collection id and, for an item, item id of a trashed record
+type RecordKey = (String, Option<String>);
+
+/// Purges the given records, returning how many were still in the trash
+async fn purge_records(
+    db_service: &DatabaseService,
+    config: &Config,
+    records: &[RecordKey],
+) -> Result<usize, String> {
+    let mut purged = 0;
+    for (collection_id, item_id) in records {
+        if purge_record(db_service, config, collection_id, item_id.as_deref()).await? {
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+/// Runs a purge in the background and answers `202 Accepted` with its job
+async fn spawn_purge(
+    state: &AppState,
+    message: String,
+    collection_id: Option<&str>,
+    records: Vec<RecordKey>,
+) -> Response {
+    let job = jobs::registry().create("trash_purge", "trash_purge", collection_id);
+    let limits = JobLimits::load(&state.db_service.settings, &job.queue).await;
+    let db_service = state.db_service.clone();
+    let config = state.config.clone();
+    jobs::registry().spawn(&job, limits, async move {
+        purge_records(&db_service, &config, &records)
+            .await
+            .map_err(|e| {
+                eprintln!("Failed to purge the trash: {}", e);
+                e
+            })?;
+        Ok(None)
+    });
+    purge_accepted_response(&ServerConfig::from_config(&state.config), message, &job)
+}
+
+/// Handler listing the collections and items in the trash (GET /trash)
+pub async fn list_trash(State(state): State<AppState>) -> Response {
+    let retention_days = state
+        .db_service
+        .settings
+        .get::<TrashRetentionDays>()
+        .await
+        .unwrap_or_else(|_| TrashRetentionDays::default_value());
+    match state.db_service.collections.trashed().await {
+        Ok(records) => json_response(
+            StatusCode::OK,
+            HeaderMap::new(),
+            serde_json::json!({ "entries": records, "retention_days": retention_days }),
+        ),
+        Err(e) => ApiError::internal(format!("Failed to list the trash: {}", e)).into_response(),
+    }
+}
+
+/// Handler taking a collection and the items deleted with it out of the
+/// trash (POST /trash/collections/:collection_id/restore)
+pub async fn restore_collection(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    match state.db_service.collections.restore(&collection_id).await {
+        Ok(true) => json_response(
+            StatusCode::OK,
+            HeaderMap::new(),
+            serde_json::json!({
+                "message": format!("Collection '{}' restored", collection_id),
+                "links": [{
+                    "href": ServerConfig::from_config(&state.config).collection_href(&collection_id),
+                    "rel": "collection",
+                    "type": "application/json"
+                }]
+            }),
+        ),
+        Ok(false) => ApiError::not_found(format!(
+            "Collection '{}' is not in the trash",
+            collection_id
+        ))
+        .into_response(),
+        Err(e) => {
+            ApiError::internal(format!("Failed to restore collection: {}", e)).into_response()
+        }
+    }
+}
+
+/// Handler taking an item out of the trash
+/// (POST /trash/collections/:collection_id/items/:item_id/restore)
+pub async fn restore_item(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Response {
+    // An item cannot come back into a collection that is itself trashed
+    if let Ok(true) = state
+        .db_service
+        .collections
+        .in_trash(&collection_id, None)
+        .await
+    {
+        return ApiError::new(
+            StatusCode::CONFLICT,
+            "Conflict",
+            format!(
+                "Collection '{}' is in the trash; restore it before its items",
+                collection_id
+            ),
+        )
+        .into_response();
+    }
+    match state
+        .db_service
+        .items
+        .restore(&collection_id, &item_id)
+        .await
+    {
+        Ok(true) => json_response(
+            StatusCode::OK,
+            HeaderMap::new(),
+            serde_json::json!({
+                "message": format!("Item '{}' of collection '{}' restored", item_id, collection_id),
+                "links": [{
+                    "href": ServerConfig::from_config(&state.config).item_href(&collection_id, &item_id),
+                    "rel": "item",
+                    "type": "application/geo+json"
+                }]
+            }),
+        ),
+        Ok(false) => ApiError::not_found(format!(
+            "Item '{}' of collection '{}' is not in the trash",
+            item_id, collection_id
+        ))
+        .into_response(),
+        Err(e) => ApiError::internal(format!("Failed to restore item: {}", e)).into_response(),
+    }
+}
+
+/// Handler permanently deleting everything in the trash (DELETE /trash)
+pub async fn empty_trash(State(state): State<AppState>) -> Response {
+    match state.db_service.collections.trashed().await {
+        Ok(records) => {
+            let message = format!("Purging {} records from the trash", records.len());
+            let records = records
+                .into_iter()
+                .map(|record| (record.collection_id, record.item_id))
+                .collect();
+            spawn_purge(&state, message, None, records).await
+        }
+        Err(e) => ApiError::internal(format!("Failed to list the trash: {}", e)).into_response(),
+    }
+}
+
+/// Handler permanently deleting a trashed collection with all of its items
+/// (DELETE /trash/collections/:collection_id)
+pub async fn purge_collection(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    purge_one(&state, &collection_id, None).await
+}
+
+/// Handler permanently deleting a trashed item
+/// (DELETE /trash/collections/:collection_id/items/:item_id)
+pub async fn purge_item(
+    Path((collection_id, item_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+) -> Response {
+    purge_one(&state, &collection_id, Some(&item_id)).await
+}
+
+async fn purge_one(state: &AppState, collection_id: &str, item_id: Option<&str>) -> Response {
+    let label = match item_id {
+        Some(item_id) => format!("Item '{}' of collection '{}'", item_id, collection_id),
+        None => format!("Collection '{}'", collection_id),
+    };
+    match state
+        .db_service
+        .collections
+        .in_trash(collection_id, item_id)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiError::not_found(format!("{} is not in the trash", label)).into_response()
+        }
+        Err(e) => {
+            return ApiError::internal(format!("Failed to check the trash: {}", e)).into_response()
+        }
+    }
+    let record = (collection_id.to_string(), item_id.map(str::to_string));
+    spawn_purge(
+        state,
+        format!("{} is being purged from the trash", label),
+        Some(collection_id),
+        vec![record],
+    )
+    .await
+}
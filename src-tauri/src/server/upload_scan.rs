@@ -0,0 +1,143 @@
+use crate::config::Config;
+use crate::database::settings::{Setting, UploadScanCommand, UploadScanTimeoutSecs};
+use crate::database::DatabaseService;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+/// Asset member recording the outcome of the upload scan
+pub const SCAN_STATUS_FIELD: &str = "zenstac:scan_status";
+
+/// Asset member with the scanner's reason for rejecting an upload
+pub const SCAN_REASON_FIELD: &str = "zenstac:scan_reason";
+
+/// Outcome of scanning an uploaded file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    /// No scanner is configured
+    Unscanned,
+    Clean,
+    /// The scanner flagged the file or could not vouch for it
+    Rejected(String),
+}
+
+impl ScanVerdict {
+    /// Value of the asset's scan status member, if it gets one
+    pub fn status(&self) -> Option<&'static str> {
+        match self {
+            ScanVerdict::Unscanned => None,
+            ScanVerdict::Clean => Some("clean"),
+            ScanVerdict::Rejected(_) => Some("rejected"),
+        }
+    }
+}
+
+/// Runs the user's upload scanner (the `upload_scan_command` setting, e.g.
+/// `clamscan`) with the path of an uploaded file as its only argument.
+///
+/// Exit status 0 passes the file. Any other status, a scanner that cannot be
+/// started and one that runs longer than `upload_scan_timeout_secs` reject it:
+/// unlike the ingest hook, a broken scanner must not let files through.
+pub async fn scan_upload(
+    db_service: &DatabaseService,
+    file: &Path,
+    collection_id: &str,
+    item_id: &str,
+    asset_key: &str,
+) -> ScanVerdict {
+    let command = db_service
+        .settings
+        .get::<UploadScanCommand>()
+        .await
+        .unwrap_or_else(|_| UploadScanCommand::default_value());
+    if command.trim().is_empty() {
+        return ScanVerdict::Unscanned;
+    }
+    let timeout = db_service
+        .settings
+        .get::<UploadScanTimeoutSecs>()
+        .await
+        .unwrap_or_else(|_| UploadScanTimeoutSecs::default_value());
+
+    match execute(&command, file, collection_id, item_id, asset_key, timeout).await {
+        Ok(()) => ScanVerdict::Clean,
+        Err(reason) => {
+            eprintln!(
+                "Upload scanner '{}' rejected {}/{}/{}: {}",
+                command, collection_id, item_id, asset_key, reason
+            );
+            ScanVerdict::Rejected(reason)
+        }
+    }
+}
+
+/// Spawns the scanner and returns why it rejected the file, if it did
+async fn execute(
+    command: &str,
+    file: &Path,
+    collection_id: &str,
+    item_id: &str,
+    asset_key: &str,
+    timeout: u64,
+) -> Result<(), String> {
+    // The command is run directly, not through a shell
+    let child = tokio::process::Command::new(command)
+        .arg(file)
+        .env("ZENSTAC_COLLECTION", collection_id)
+        .env("ZENSTAC_ITEM", item_id)
+        .env("ZENSTAC_ASSET", asset_key)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("scanner could not start: {}", e))?;
+
+    let output = tokio::time::timeout(Duration::from_secs(timeout), child.wait_with_output())
+        .await
+        .map_err(|_| format!("scan timed out after {} s", timeout))?
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    // Scanners such as clamscan name the finding on stdout, errors go to stderr
+    let reason = [&output.stdout, &output.stderr]
+        .iter()
+        .map(|stream| String::from_utf8_lossy(stream))
+        .find_map(|text| {
+            text.lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| format!("scanner exited with {}", output.status));
+    Err(reason)
+}
+
+/// Moves a rejected upload to `quarantine/<collection>/<item>/` in the
+/// application data directory, prefixed with the time it was rejected so
+/// repeated uploads never overwrite each other
+pub async fn quarantine(
+    config: &Config,
+    file: &Path,
+    collection_id: &str,
+    item_id: &str,
+    asset_key: &str,
+) -> Result<PathBuf, String> {
+    let dir = Path::new(&config.quarantine_dir())
+        .join(collection_id)
+        .join(item_id);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create quarantine directory: {}", e))?;
+    let target = dir.join(format!(
+        "{}-{}",
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        asset_key
+    ));
+    tokio::fs::rename(file, &target)
+        .await
+        .map_err(|e| format!("Failed to quarantine upload: {}", e))?;
+    Ok(target)
+}
@@ -0,0 +1,685 @@
+use crate::database::settings::{Setting, UploadSessionExpiryHours};
+use crate::database::{DatabaseService, UploadSession};
+use crate::server::error::ApiError;
+use crate::server::handlers::{pending_upload_path, store_upload};
+use crate::server::media_types::MediaTypeRegistry;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// Request header carrying the offset a chunk starts at, and response header
+/// carrying the offset the next chunk must start at
+const UPLOAD_OFFSET: &str = "upload-offset";
+/// Optional request header with the chunk's checksum, `crc32 <8 hex digits>`
+const UPLOAD_CHECKSUM: &str = "upload-checksum";
+
+/// Body of a request opening an upload session
+#[derive(Debug, Deserialize)]
+pub struct NewUploadSession {
+    pub file_name: String,
+    /// Size of the whole file in bytes
+    pub size: u64,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// CRC-32 of the whole file as 8 hex digits, checked once it is complete
+    #[serde(default)]
+    pub crc32: Option<String>,
+}
+
+/// Parses a CRC-32 given as 8 hex digits
+fn parse_crc(value: &str) -> Option<u32> {
+    let value = value.trim();
+    if value.len() != 8 {
+        return None;
+    }
+    u32::from_str_radix(value, 16).ok()
+}
+
+/// Sessions a chunk is being written to; a second writer is turned away
+/// rather than interleaving bytes in the same file
+fn busy_sessions() -> &'static Mutex<HashSet<String>> {
+    static BUSY: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    BUSY.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Marks a session busy until dropped
+struct SessionGuard(String);
+
+impl SessionGuard {
+    fn acquire(id: &str) -> Option<Self> {
+        busy_sessions()
+            .lock()
+            .unwrap()
+            .insert(id.to_string())
+            .then(|| SessionGuard(id.to_string()))
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        busy_sessions().lock().unwrap().remove(&self.0);
+    }
+}
+
+fn session_path(session: &UploadSession) -> String {
+    format!(
+        "/upload/{}/{}/{}/sessions/{}",
+        session.collection_id, session.item_id, session.asset_key, session.id
+    )
+}
+
+fn session_json(server_config: &ServerConfig, session: &UploadSession) -> serde_json::Value {
+    let mut body = serde_json::json!(session);
+    body["links"] = serde_json::json!([{
+        "href": server_config.href(&session_path(session)),
+        "rel": "self",
+        "type": "application/json"
+    }]);
+    body
+}
+
+fn offset_header(offset: u64) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        HeaderName::from_static(UPLOAD_OFFSET),
+        HeaderValue::from(offset),
+    );
+    headers
+}
+
+fn json_response(status: StatusCode, headers: HeaderMap, body: serde_json::Value) -> Response {
+    let mut headers = add_cors_headers(headers);
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+fn conflict(detail: impl Into<String>, offset: u64) -> Response {
+    ApiError::new(StatusCode::CONFLICT, "Conflict", detail)
+        .with_extension("offset", offset)
+        .with_headers(offset_header(offset))
+        .into_response()
+}
+
+/// Loads a session of the asset in the path; sessions of other assets are
+/// not found
+async fn load_session(
+    state: &AppState,
+    collection_id: &str,
+    item_id: &str,
+    asset_key: &str,
+    session_id: &str,
+) -> Result<UploadSession, Response> {
+    match state.db_service.upload_sessions.get(session_id).await {
+        Ok(Some(session))
+            if session.collection_id == collection_id
+                && session.item_id == item_id
+                && session.asset_key == asset_key =>
+        {
+            Ok(session)
+        }
+        Ok(_) => Err(
+            ApiError::not_found(format!("Upload session '{}' not found", session_id))
+                .into_response(),
+        ),
+        Err(e) => {
+            Err(ApiError::internal(format!("Failed to read upload session: {}", e)).into_response())
+        }
+    }
+}
+
+/// Brings the recorded offset in line with the temporary file. A file cut
+/// short, e.g. by a crash before its data reached the disk, sends the
+/// session back to what the file holds; bytes past the offset were never
+/// acknowledged and are overwritten by the next chunk.
+async fn recover(db_service: &DatabaseService, session: &mut UploadSession) -> Result<(), String> {
+    let on_disk = match tokio::fs::metadata(&session.temp_path).await {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(format!("Failed to read upload file: {}", e)),
+    };
+    if on_disk >= session.offset {
+        return Ok(());
+    }
+
+    let mut crc = crc32fast::Hasher::new();
+    if on_disk > 0 {
+        let mut file = tokio::fs::File::open(&session.temp_path)
+            .await
+            .map_err(|e| format!("Failed to read upload file: {}", e))?;
+        let mut buffer = vec![0u8; 1024 * 1024];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .await
+                .map_err(|e| format!("Failed to read upload file: {}", e))?;
+            if read == 0 {
+                break;
+            }
+            crc.update(&buffer[..read]);
+        }
+    }
+    let crc = crc.finalize();
+    eprintln!(
+        "Upload session {} resumes at byte {} of {}, not {}",
+        session.id, on_disk, session.size, session.offset
+    );
+    db_service
+        .upload_sessions
+        .advance(&session.id, session.offset, on_disk, crc)
+        .await
+        .map_err(|e| format!("Failed to update upload session: {}", e))?;
+    session.offset = on_disk;
+    session.crc32 = crc;
+    Ok(())
+}
+
+/// Removes a session and its temporary file
+pub async fn discard(db_service: &DatabaseService, session: &UploadSession) -> Result<(), String> {
+    match tokio::fs::remove_file(&session.temp_path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to remove {}: {}", session.temp_path, e)),
+    }
+    db_service
+        .upload_sessions
+        .delete(&session.id)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Failed to delete upload session {}: {}", session.id, e))
+}
+
+/// Discards sessions that received nothing for longer than the
+/// `upload_session_expiry_hours` setting, returning how many
+pub async fn discard_expired(db_service: &DatabaseService) -> Result<usize, String> {
+    let hours = db_service
+        .settings
+        .get::<UploadSessionExpiryHours>()
+        .await
+        .unwrap_or_else(|_| UploadSessionExpiryHours::default_value());
+    let cutoff = chrono::Utc::now() - chrono::Duration::hours(hours as i64);
+    let expired = db_service
+        .upload_sessions
+        .expired(&cutoff.to_rfc3339())
+        .await
+        .map_err(|e| format!("Failed to list upload sessions: {}", e))?;
+    let mut discarded = 0;
+    for session in expired {
+        // A session receiving a chunk right now is not idle
+        let Some(_guard) = SessionGuard::acquire(&session.id) else {
+            continue;
+        };
+        discard(db_service, &session).await?;
+        discarded += 1;
+    }
+    Ok(discarded)
+}
+
+/// Handler opening a chunked upload of an asset
+/// (POST /upload/:collection_id/:item_id/:asset_key/sessions)
+pub async fn create_upload_session(
+    Path((collection_id, item_id, asset_key)): Path<(String, String, String)>,
+    State(state): State<AppState>,
+    Json(body): Json<NewUploadSession>,
+) -> Response {
+    match state
+        .db_service
+        .items
+        .get_by_id(&collection_id, &item_id)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return ApiError::not_found(format!(
+                "Item '{}' not found in collection '{}'",
+                item_id, collection_id
+            ))
+            .into_response();
+        }
+        Err(_) => return ApiError::internal("Failed to verify item exists").into_response(),
+    }
+    if body.size == 0 {
+        return ApiError::bad_request("An upload session needs a size of at least 1 byte")
+            .into_response();
+    }
+    let checksum = match body.crc32.as_deref().map(parse_crc) {
+        None => None,
+        Some(Some(crc)) => Some(format!("{:08x}", crc)),
+        Some(None) => {
+            return ApiError::bad_request("crc32 must be 8 hex digits").into_response();
+        }
+    };
+
+    let uploads_dir = state.config.uploads_dir();
+    if let Err(e) = tokio::fs::create_dir_all(&uploads_dir).await {
+        return ApiError::internal(format!("Failed to create uploads directory: {}", e))
+            .into_response();
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    let temp_path = std::path::Path::new(&uploads_dir)
+        .join(format!("{}.part", id))
+        .to_string_lossy()
+        .to_string();
+    if let Err(e) = tokio::fs::File::create(&temp_path).await {
+        return ApiError::internal(format!("Failed to create upload file: {}", e)).into_response();
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let session = UploadSession {
+        id,
+        content_type: MediaTypeRegistry::load(&state.db_service)
+            .await
+            .for_upload(&body.file_name, body.content_type.as_deref()),
+        collection_id,
+        item_id,
+        asset_key,
+        file_name: body.file_name,
+        size: body.size,
+        offset: 0,
+        crc32: 0,
+        checksum,
+        temp_path,
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    if let Err(e) = state.db_service.upload_sessions.create(&session).await {
+        let _ = tokio::fs::remove_file(&session.temp_path).await;
+        return ApiError::internal(format!("Failed to create upload session: {}", e))
+            .into_response();
+    }
+
+    let server_config = ServerConfig::from_config(&state.config);
+    let mut headers = offset_header(0);
+    if let Ok(location) = HeaderValue::from_str(&server_config.href(&session_path(&session))) {
+        headers.insert("Location", location);
+    }
+    json_response(
+        StatusCode::CREATED,
+        headers,
+        session_json(&server_config, &session),
+    )
+}
+
+/// Handler listing the unfinished uploads of an asset
+/// (GET /upload/:collection_id/:item_id/:asset_key/sessions)
+pub async fn list_upload_sessions(
+    Path((collection_id, item_id, asset_key)): Path<(String, String, String)>,
+    State(state): State<AppState>,
+) -> Response {
+    match state
+        .db_service
+        .upload_sessions
+        .list_for_asset(&collection_id, &item_id, &asset_key)
+        .await
+    {
+        Ok(sessions) => {
+            let server_config = ServerConfig::from_config(&state.config);
+            let sessions: Vec<serde_json::Value> = sessions
+                .iter()
+                .map(|session| session_json(&server_config, session))
+                .collect();
+            json_response(
+                StatusCode::OK,
+                HeaderMap::new(),
+                serde_json::json!({ "sessions": sessions }),
+            )
+        }
+        Err(e) => {
+            ApiError::internal(format!("Failed to list upload sessions: {}", e)).into_response()
+        }
+    }
+}
+
+/// Handler reporting how much of an upload the server holds, so a client
+/// knows where to resume
+/// (GET /upload/:collection_id/:item_id/:asset_key/sessions/:session_id)
+pub async fn get_upload_session(
+    Path((collection_id, item_id, asset_key, session_id)): Path<(String, String, String, String)>,
+    State(state): State<AppState>,
+) -> Response {
+    let mut session =
+        match load_session(&state, &collection_id, &item_id, &asset_key, &session_id).await {
+            Ok(session) => session,
+            Err(response) => return response,
+        };
+    if let Some(_guard) = SessionGuard::acquire(&session.id) {
+        if let Err(e) = recover(&state.db_service, &mut session).await {
+            return ApiError::internal(e).into_response();
+        }
+    }
+    json_response(
+        StatusCode::OK,
+        offset_header(session.offset),
+        session_json(&ServerConfig::from_config(&state.config), &session),
+    )
+}
+
+/// Handler abandoning an upload (DELETE
+/// /upload/:collection_id/:item_id/:asset_key/sessions/:session_id)
+pub async fn delete_upload_session(
+    Path((collection_id, item_id, asset_key, session_id)): Path<(String, String, String, String)>,
+    State(state): State<AppState>,
+) -> Response {
+    let session =
+        match load_session(&state, &collection_id, &item_id, &asset_key, &session_id).await {
+            Ok(session) => session,
+            Err(response) => return response,
+        };
+    let Some(_guard) = SessionGuard::acquire(&session.id) else {
+        return conflict("A chunk of this upload is being written", session.offset);
+    };
+    match discard(&state.db_service, &session).await {
+        Ok(()) => (StatusCode::NO_CONTENT, add_cors_headers(HeaderMap::new())).into_response(),
+        Err(e) => ApiError::internal(e).into_response(),
+    }
+}
+
+/// Handler appending a chunk to an upload
+/// (PATCH /upload/:collection_id/:item_id/:asset_key/sessions/:session_id).
+///
+/// The `Upload-Offset` header must equal the session's offset. A chunk with
+/// an `Upload-Checksum` header is kept only when it matches; a chunk without
+/// one keeps what arrived before a dropped connection. The chunk that
+/// completes the file stores the asset like a single-request upload.
+pub async fn append_upload_chunk(
+    Path((collection_id, item_id, asset_key, session_id)): Path<(String, String, String, String)>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let mut session =
+        match load_session(&state, &collection_id, &item_id, &asset_key, &session_id).await {
+            Ok(session) => session,
+            Err(response) => return response,
+        };
+    let Some(_guard) = SessionGuard::acquire(&session.id) else {
+        return conflict("A chunk of this upload is being written", session.offset);
+    };
+    if let Err(e) = recover(&state.db_service, &mut session).await {
+        return ApiError::internal(e).into_response();
+    }
+
+    let offset = match headers
+        .get(UPLOAD_OFFSET)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+    {
+        Some(offset) => offset,
+        None => {
+            return ApiError::bad_request("The Upload-Offset header is required").into_response()
+        }
+    };
+    if offset != session.offset {
+        return conflict(
+            format!(
+                "Chunk starts at byte {} but the upload continues at byte {}",
+                offset, session.offset
+            ),
+            session.offset,
+        );
+    }
+    let expected_chunk_crc = match headers.get(UPLOAD_CHECKSUM) {
+        None => None,
+        Some(value) => {
+            match value
+                .to_str()
+                .ok()
+                .and_then(|v| v.trim().strip_prefix("crc32 "))
+                .and_then(parse_crc)
+            {
+                Some(crc) => Some(crc),
+                None => {
+                    return ApiError::bad_request("Upload-Checksum must be 'crc32 <8 hex digits>'")
+                        .into_response()
+                }
+            }
+        }
+    };
+
+    let mut file = match tokio::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(&session.temp_path)
+        .await
+    {
+        Ok(file) => file,
+        Err(e) => {
+            return ApiError::internal(format!("Failed to open upload file: {}", e)).into_response()
+        }
+    };
+    // Bytes past the offset were never acknowledged
+    let prepared = match file.set_len(session.offset).await {
+        Ok(()) => file
+            .seek(std::io::SeekFrom::Start(session.offset))
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = prepared {
+        return ApiError::internal(format!("Failed to prepare upload file: {}", e)).into_response();
+    }
+
+    let remaining = session.size - session.offset;
+    let max_chunk = state.config.server.max_upload_body_bytes;
+    let mut written: u64 = 0;
+    // The file's CRC-32 continues from the bytes received before
+    let mut crc = crc32fast::Hasher::new_with_initial(session.crc32);
+    let mut chunk_crc = crc32fast::Hasher::new();
+    let mut failure: Option<ApiError> = None;
+    let mut dropped = false;
+    let mut stream = body.into_data_stream();
+    while let Some(data) = stream.next().await {
+        let data = match data {
+            Ok(data) => data,
+            Err(e) => {
+                failure = Some(ApiError::bad_request(format!(
+                    "Failed to read chunk: {}",
+                    e
+                )));
+                dropped = true;
+                break;
+            }
+        };
+        if written + data.len() as u64 > remaining {
+            failure = Some(ApiError::bad_request(format!(
+                "Chunk runs past the declared size of {} bytes",
+                session.size
+            )));
+            break;
+        }
+        if written + data.len() as u64 > max_chunk {
+            failure = Some(ApiError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "PayloadTooLarge",
+                format!(
+                    "Chunk exceeds the {} byte limit for asset uploads. Send smaller chunks or increase the 'max_upload_body_bytes' setting.",
+                    max_chunk
+                ),
+            ));
+            break;
+        }
+        if let Err(e) = file.write_all(&data).await {
+            failure = Some(ApiError::internal(format!(
+                "Failed to write upload file: {}",
+                e
+            )));
+            break;
+        }
+        crc.update(&data);
+        chunk_crc.update(&data);
+        written += data.len() as u64;
+    }
+    let crc = crc.finalize();
+    let chunk_crc = chunk_crc.finalize();
+
+    // Without a checksum, what arrived before a dropped connection is kept;
+    // with one, only a whole verified chunk is
+    let mismatch = failure.is_none() && expected_chunk_crc.is_some_and(|crc| crc != chunk_crc);
+    let keep = match &failure {
+        None => !mismatch,
+        Some(_) => dropped && expected_chunk_crc.is_none() && written > 0,
+    };
+    let synced = if keep {
+        match file.flush().await {
+            Ok(()) => file.sync_data().await,
+            Err(e) => Err(e),
+        }
+    } else {
+        file.set_len(session.offset).await
+    };
+    drop(file);
+    if let Err(e) = synced {
+        return ApiError::internal(format!("Failed to write upload file: {}", e)).into_response();
+    }
+    if mismatch {
+        return ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "ChecksumMismatch",
+            format!(
+                "Chunk checksum is {:08x}, not the {:08x} sent in Upload-Checksum",
+                chunk_crc,
+                expected_chunk_crc.unwrap_or_default()
+            ),
+        )
+        .with_headers(offset_header(session.offset))
+        .into_response();
+    }
+
+    let new_offset = if keep {
+        session.offset + written
+    } else {
+        session.offset
+    };
+    if keep {
+        match state
+            .db_service
+            .upload_sessions
+            .advance(&session.id, session.offset, new_offset, crc)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                return conflict(
+                    "The upload session changed while the chunk was written",
+                    session.offset,
+                )
+            }
+            Err(e) => {
+                return ApiError::internal(format!("Failed to update upload session: {}", e))
+                    .into_response()
+            }
+        }
+    }
+    if let Some(error) = failure {
+        return error
+            .with_headers(offset_header(new_offset))
+            .into_response();
+    }
+
+    session.offset = new_offset;
+    session.crc32 = crc;
+    if session.offset < session.size {
+        return (
+            StatusCode::NO_CONTENT,
+            add_cors_headers(offset_header(session.offset)),
+        )
+            .into_response();
+    }
+    complete(&state, &session).await
+}
+
+/// Moves a complete upload into place and stores the asset. The session is
+/// gone afterwards whatever the outcome, as its file has been used up.
+async fn complete(state: &AppState, session: &UploadSession) -> Response {
+    if let Some(expected) = &session.checksum {
+        let actual = format!("{:08x}", session.crc32);
+        if &actual != expected {
+            if let Err(e) = discard(&state.db_service, session).await {
+                eprintln!("{}", e);
+            }
+            return ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "ChecksumMismatch",
+                format!(
+                    "Uploaded file has CRC-32 {}, not the {} the session was opened with",
+                    actual, expected
+                ),
+            )
+            .into_response();
+        }
+    }
+
+    let assets_dir = format!(
+        "{}/{}/{}",
+        state.config.assets_dir(),
+        session.collection_id,
+        session.item_id
+    );
+    let upload_path = pending_upload_path(
+        &state.config,
+        &session.collection_id,
+        &session.item_id,
+        &session.asset_key,
+    );
+    let moved = match tokio::fs::create_dir_all(&assets_dir).await {
+        Ok(()) => move_file(&session.temp_path, &upload_path).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = moved {
+        return ApiError::internal(format!("Failed to save uploaded file: {}", e)).into_response();
+    }
+    if let Err(e) = state.db_service.upload_sessions.delete(&session.id).await {
+        eprintln!("Failed to delete upload session {}: {}", session.id, e);
+    }
+
+    store_upload(
+        state,
+        &session.collection_id,
+        &session.item_id,
+        &session.asset_key,
+        &session.file_name,
+        &session.content_type,
+    )
+    .await
+}
+
+/// Renames a file, copying it when the uploads and assets directories are on
+/// different filesystems
+async fn move_file(from: &str, to: &str) -> std::io::Result<()> {
+    if tokio::fs::rename(from, to).await.is_ok() {
+        return Ok(());
+    }
+    tokio::fs::copy(from, to).await?;
+    tokio::fs::remove_file(from).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_crc_continues_across_chunks() {
+        // CRC-32 check value of "123456789"
+        let expected = parse_crc("cbf43926").unwrap();
+        assert_eq!(crc32fast::hash(b"123456789"), expected);
+
+        let first = crc32fast::hash(b"1234");
+        let mut resumed = crc32fast::Hasher::new_with_initial(first);
+        resumed.update(b"56789");
+        assert_eq!(resumed.finalize(), expected);
+    }
+}
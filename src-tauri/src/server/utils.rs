@@ -93,6 +93,20 @@ impl ServerConfig {
         self.href("search")
     }
 
+    /// Generates a full URL for the Atom feed of recent items, catalog-wide or
+    /// for one collection
+    pub fn feed_href(&self, collection_id: Option<&str>) -> String {
+        match collection_id {
+            Some(collection_id) => self.href(&format!("collections/{}/feed.xml", collection_id)),
+            None => self.href("feed.xml"),
+        }
+    }
+
+    /// Generates a full URL for a catalog branding file (logo, favicon, about)
+    pub fn branding_href(&self, kind: &str) -> String {
+        self.href(&format!("branding/{}", kind))
+    }
+
 
 }
 
@@ -0,0 +1,295 @@
+use crate::database::virtual_collections::VirtualCollection;
+use crate::database::DatabaseService;
+use crate::models::search::{OGCFeaturesQuery, SearchQuery};
+use crate::server::access_log::add_item_access_stats;
+use crate::server::compact::compact_feature_collection;
+use crate::server::error::ApiError;
+use crate::server::handlers::{add_item_counts, find_items};
+use crate::server::helpers::calculate_bbox_for_items;
+use crate::server::middleware::add_cors_headers;
+use crate::server::server::AppState;
+use crate::server::utils::ServerConfig;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// STAC Collection document for a virtual collection. Links point back to the
+/// source collections with `rel=derived_from`.
+pub fn to_stac_collection(
+    virtual_collection: &VirtualCollection,
+    server_config: &ServerConfig,
+    stac_version: &str,
+) -> serde_json::Value {
+    let id = &virtual_collection.id;
+    let mut links = vec![
+        serde_json::json!({
+            "href": server_config.collection_href(id),
+            "rel": "self",
+            "type": "application/json",
+            "title": "This Collection"
+        }),
+        serde_json::json!({
+            "href": server_config.collection_items_href(id),
+            "rel": "items",
+            "type": "application/geo+json",
+            "title": "Items in this Collection"
+        }),
+        serde_json::json!({
+            "href": server_config.root_href(),
+            "rel": "root",
+            "type": "application/json",
+            "title": "Root Catalog"
+        }),
+        serde_json::json!({
+            "href": server_config.root_href(),
+            "rel": "parent",
+            "type": "application/json",
+            "title": "Parent Catalog"
+        }),
+    ];
+    for source in virtual_collection.search.collections.iter().flatten() {
+        links.push(serde_json::json!({
+            "href": server_config.collection_href(source),
+            "rel": "derived_from",
+            "type": "application/json"
+        }));
+    }
+
+    let bbox = match &virtual_collection.search.bbox {
+        Some(bbox) if bbox.len() == 4 => bbox.clone(),
+        _ => vec![-180.0, -90.0, 180.0, 90.0],
+    };
+    let interval = match virtual_collection.search.datetime.as_deref() {
+        Some(datetime) if datetime.contains('/') => {
+            let mut parts = datetime.splitn(2, '/');
+            let bound = |v: Option<&str>| match v {
+                Some(v) if !v.is_empty() && v != ".." => serde_json::Value::String(v.to_string()),
+                _ => serde_json::Value::Null,
+            };
+            let start = bound(parts.next());
+            let end = bound(parts.next());
+            serde_json::json!([start, end])
+        }
+        Some(datetime) => serde_json::json!([datetime, datetime]),
+        None => serde_json::json!([null, null]),
+    };
+
+    serde_json::json!({
+        "type": "Collection",
+        "stac_version": stac_version,
+        "id": id,
+        "title": virtual_collection.title,
+        "description": virtual_collection.description,
+        "keywords": virtual_collection.keywords.clone().unwrap_or_default(),
+        "license": virtual_collection.license.clone().unwrap_or_else(|| "various".to_string()),
+        "extent": {
+            "spatial": { "bbox": [bbox] },
+            "temporal": { "interval": [interval] }
+        },
+        "links": links,
+        "virtual": true,
+        "search": virtual_collection.search
+    })
+}
+
+/// Items of a virtual collection: the stored search, paged with the request's
+/// `limit` and `offset`. The stored `limit` caps the total number of items.
+pub async fn items_response(
+    virtual_collection: &VirtualCollection,
+    query: &OGCFeaturesQuery,
+    state: &AppState,
+) -> Response {
+    let server_config = ServerConfig::from_config(&state.config);
+    let search = SearchQuery::from(virtual_collection.search.clone());
+
+    let (items, complete) = match find_items(&search, state).await {
+        Ok(results) => (results.items, results.next_token.is_none()),
+        Err(response) => return response,
+    };
+    // Every match is known unless the search was cut to its first page
+    let number_matched = complete.then_some(items.len());
+    let offset = query.offset.unwrap_or(0).max(0) as usize;
+    let limit = query.limit.map(|l| l.max(0) as usize).unwrap_or(items.len());
+    let mut page: Vec<_> = items.into_iter().skip(offset).take(limit).collect();
+    add_item_access_stats(&state.db_service, &server_config, &mut page).await;
+
+    let id = &virtual_collection.id;
+    let mut items_response = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": page,
+        "links": [
+            {
+                "href": server_config.collection_items_href(id),
+                "rel": "self",
+                "type": "application/geo+json"
+            },
+            {
+                "href": server_config.collection_href(id),
+                "rel": "parent",
+                "type": "application/json"
+            }
+        ]
+    });
+
+    add_item_counts(&mut items_response, page.len(), Some(limit), number_matched);
+    if let Some(bbox) = calculate_bbox_for_items(&page) {
+        items_response["bbox"] = serde_json::json!(bbox);
+    }
+    if query.compact == Some(true) {
+        compact_feature_collection(&mut items_response);
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/geo+json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+
+    (headers, serde_json::to_string(&items_response).unwrap()).into_response()
+}
+
+/// Whether `collection_id` names a virtual collection
+pub async fn is_virtual(db_service: &DatabaseService, collection_id: &str) -> bool {
+    matches!(
+        db_service.virtual_collections.get_by_id(collection_id).await,
+        Ok(Some(_))
+    )
+}
+
+/// 405 returned for writes against a virtual collection
+pub fn read_only_response(collection_id: &str) -> Response {
+    ApiError::new(
+        StatusCode::METHOD_NOT_ALLOWED,
+        "MethodNotAllowed",
+        format!("Virtual collection '{}' is read-only", collection_id),
+    )
+    .into_response()
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/json; charset=utf-8"),
+    );
+    headers = add_cors_headers(headers);
+    (status, headers, serde_json::to_string(&body).unwrap()).into_response()
+}
+
+/// Handler creating or replacing a virtual collection (PUT /virtual-collections/:collection_id)
+pub async fn put_virtual_collection(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+    Json(mut virtual_collection): Json<VirtualCollection>,
+) -> Result<Response, ApiError> {
+    virtual_collection.id = collection_id;
+    let saved = save_virtual_collection(&state.db_service, virtual_collection)
+        .await
+        .map_err(|(status, description)| ApiError::from_status(status, description))?;
+    let server_config = ServerConfig::from_config(&state.config);
+    Ok(json_response(
+        StatusCode::OK,
+        to_stac_collection(&saved, &server_config, &state.config.catalog.stac_version),
+    ))
+}
+
+/// Handler removing a virtual collection (DELETE /virtual-collections/:collection_id)
+pub async fn delete_virtual_collection(
+    Path(collection_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let deleted = state
+        .db_service
+        .virtual_collections
+        .delete(&collection_id)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to delete virtual collection: {}", e)))?;
+    if !deleted {
+        return Err(ApiError::not_found(format!(
+            "Virtual collection '{}' not found",
+            collection_id
+        )));
+    }
+    Ok((StatusCode::NO_CONTENT, add_cors_headers(HeaderMap::new())).into_response())
+}
+
+/// Validates and stores a virtual collection. The id must not clash with a
+/// real collection and every source collection must exist. Shared by the HTTP
+/// endpoint and the Tauri command.
+pub async fn save_virtual_collection(
+    db_service: &DatabaseService,
+    mut virtual_collection: VirtualCollection,
+) -> Result<VirtualCollection, (StatusCode, String)> {
+    let internal = |e: rusqlite::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to save virtual collection: {}", e),
+        )
+    };
+
+    if virtual_collection.id.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Missing required field: id".to_string()));
+    }
+    if db_service
+        .collections
+        .get_by_id(&virtual_collection.id)
+        .await
+        .map_err(internal)?
+        .is_some()
+    {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("A collection with id '{}' already exists", virtual_collection.id),
+        ));
+    }
+
+    let sources = virtual_collection.search.collections.clone().unwrap_or_default();
+    if sources.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "The search must name at least one source collection".to_string(),
+        ));
+    }
+    for source in &sources {
+        if db_service
+            .collections
+            .get_by_id(source)
+            .await
+            .map_err(internal)?
+            .is_none()
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Source collection '{}' not found", source),
+            ));
+        }
+    }
+    if let Some(bbox) = &virtual_collection.search.bbox {
+        if bbox.len() != 4 {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "bbox must have four values: min_lon, min_lat, max_lon, max_lat".to_string(),
+            ));
+        }
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let existing = db_service
+        .virtual_collections
+        .get_by_id(&virtual_collection.id)
+        .await
+        .map_err(internal)?;
+    virtual_collection.created_at = existing.map(|e| e.created_at).unwrap_or_else(|| now.clone());
+    virtual_collection.updated_at = now;
+
+    db_service
+        .virtual_collections
+        .upsert(&virtual_collection)
+        .await
+        .map_err(internal)?;
+    Ok(virtual_collection)
+}
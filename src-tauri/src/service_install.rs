@@ -0,0 +1,183 @@
+use crate::config::{database_location, DatabaseLocation};
+
+/// Name of the systemd unit and of the Windows scheduled task
+pub const SERVICE_NAME: &str = "zenstac";
+
+/// Registers `zenstac serve` for the selected catalog to start with the
+/// user's session and starts it. Replaces an earlier registration, so it
+/// also moves the service to another catalog. Returns what was installed.
+pub fn install(database_path: &str) -> Result<String, String> {
+    if let DatabaseLocation::Memory { .. } = database_location() {
+        return Err("An in-memory catalog cannot be served by a service".to_string());
+    }
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate the zenstac executable: {}", e))?;
+    let database = std::fs::canonicalize(database_path)
+        .map_err(|e| format!("Failed to resolve {}: {}", database_path, e))?;
+    platform::install(&exe, &database)
+}
+
+/// Stops and removes the service registered by `install`
+pub fn uninstall() -> Result<String, String> {
+    platform::uninstall()
+}
+
+/// Runs a service manager command, failing with its error output
+#[cfg(any(target_os = "linux", windows))]
+fn run(program: &str, args: &[&str]) -> Result<(), String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{run, SERVICE_NAME};
+    use std::path::{Path, PathBuf};
+
+    /// `~/.config/systemd/user/zenstac.service`
+    fn unit_path() -> Result<PathBuf, String> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".config"))
+                .ok_or("HOME is not set")?,
+        };
+        Ok(config_dir
+            .join("systemd")
+            .join("user")
+            .join(format!("{}.service", SERVICE_NAME)))
+    }
+
+    /// Quotes an `ExecStart` argument; `%` starts a unit specifier
+    fn quote(value: &Path) -> String {
+        let value = value
+            .to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('%', "%%");
+        format!("\"{}\"", value)
+    }
+
+    pub fn install(exe: &Path, database: &Path) -> Result<String, String> {
+        let path = unit_path()?;
+        let unit = format!(
+            "[Unit]\n\
+             Description=ZenSTAC STAC API server\n\
+             After=network-online.target\n\
+             \n\
+             [Service]\n\
+             ExecStart={} serve --database {}\n\
+             Restart=on-failure\n\
+             RestartSec=5\n\
+             \n\
+             [Install]\n\
+             WantedBy=default.target\n",
+            quote(exe),
+            quote(database)
+        );
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        std::fs::write(&path, unit)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        let unit_name = format!("{}.service", SERVICE_NAME);
+        run("systemctl", &["--user", "daemon-reload"])?;
+        run("systemctl", &["--user", "enable", &unit_name])?;
+        // Restart rather than start, so a reinstall serves the new catalog
+        run("systemctl", &["--user", "restart", &unit_name])?;
+        Ok(format!(
+            "Installed systemd user unit {} serving {}. Run `loginctl enable-linger` to keep it running while logged out.",
+            path.display(),
+            database.display()
+        ))
+    }
+
+    pub fn uninstall() -> Result<String, String> {
+        let path = unit_path()?;
+        if !path.exists() {
+            return Err("The ZenSTAC service is not installed".to_string());
+        }
+        let unit_name = format!("{}.service", SERVICE_NAME);
+        run("systemctl", &["--user", "disable", "--now", &unit_name])?;
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        run("systemctl", &["--user", "daemon-reload"])?;
+        Ok(format!("Removed systemd user unit {}", path.display()))
+    }
+}
+
+/// ZenSTAC does not implement the Windows service control protocol, so it
+/// runs as a scheduled task started at logon, in the user's session like
+/// the systemd user unit, where it can reach the user's keyring
+#[cfg(windows)]
+mod platform {
+    use super::{run, SERVICE_NAME};
+    use std::path::Path;
+
+    pub fn install(exe: &Path, database: &Path) -> Result<String, String> {
+        let command = format!(
+            "\"{}\" serve --database \"{}\"",
+            exe.display(),
+            database.display()
+        );
+        // Ending an earlier registration's server frees its port
+        let _ = run("schtasks", &["/End", "/TN", SERVICE_NAME]);
+        run(
+            "schtasks",
+            &[
+                "/Create",
+                "/TN",
+                SERVICE_NAME,
+                "/TR",
+                &command,
+                "/SC",
+                "ONLOGON",
+                "/RL",
+                "LIMITED",
+                "/F",
+            ],
+        )?;
+        run("schtasks", &["/Run", "/TN", SERVICE_NAME])?;
+        Ok(format!(
+            "Installed scheduled task {} serving {} at logon",
+            SERVICE_NAME,
+            database.display()
+        ))
+    }
+
+    pub fn uninstall() -> Result<String, String> {
+        let _ = run("schtasks", &["/End", "/TN", SERVICE_NAME]);
+        run("schtasks", &["/Delete", "/TN", SERVICE_NAME, "/F"])
+            .map_err(|_| "The ZenSTAC service is not installed".to_string())?;
+        Ok(format!("Removed scheduled task {}", SERVICE_NAME))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use std::path::Path;
+
+    const UNSUPPORTED: &str = "Installing ZenSTAC as a service is supported on Linux and Windows";
+
+    pub fn install(_exe: &Path, _database: &Path) -> Result<String, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn uninstall() -> Result<String, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+}
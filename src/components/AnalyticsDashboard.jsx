@@ -21,11 +21,13 @@ export const AnalyticsDashboard = (props) => {
     assets: 0,
     total: 0
   });
+  const [topAssets, setTopAssets] = createSignal([]);
 
   // Health monitoring
   onMount(() => {
     checkServerStatus();
     getDataVolume();
+    getTopAssets();
     const interval = setInterval(checkServerStatus, 60000);
     
     // Listen for server configuration changes
@@ -54,6 +56,11 @@ export const AnalyticsDashboard = (props) => {
     }
   }
 
+  async function getTopAssets() {
+    const result = await stacApi.getAccessStats('asset', 7, 5);
+    setTopAssets(result.stats || []);
+  }
+
   async function checkServerStatus() {
     try {
       const status = await stacApi.statusCheck();
@@ -258,6 +265,37 @@ export const AnalyticsDashboard = (props) => {
             })()}
           </div>
 
+          {/* Top Downloads */}
+          <div class="bg-white dark:bg-slate-800 rounded-xl p-5 shadow transition-all duration-300 h-full flex flex-col border border-gray-200 dark:border-gray-700">
+            <div class="flex items-center gap-3 mb-6">
+              <sl-icon name="download" style="color: var(--sl-color-primary-600); font-size: 20px;" />
+              <h3 class="m-0 text-lg font-semibold text-slate-800 dark:text-slate-100">Top Downloads This Week</h3>
+            </div>
+            {topAssets().length > 0 ? (
+              <div class="flex flex-col gap-3">
+                {topAssets().map(stat => (
+                  <sl-tooltip
+                    content={`${stat.collection_id} / ${stat.item_id} · ${stat.unique_clients} unique clients`}
+                    placement="top"
+                    trigger="hover"
+                  >
+                    <div class="flex justify-between items-center gap-2">
+                      <span class="text-sm font-medium text-slate-700 dark:text-slate-200 flex-1 truncate">{stat.asset_key}</span>
+                      <span class="text-xs text-slate-500 dark:text-slate-300">
+                        <sl-format-bytes value={stat.bytes_served.toString()} display="narrow" />
+                      </span>
+                      <sl-badge variant="primary" pill style="font-size: 12px; font-weight: 600;">
+                        {stat.hits}
+                      </sl-badge>
+                    </div>
+                  </sl-tooltip>
+                ))}
+              </div>
+            ) : (
+              <EmptyState icon="download" title="No Downloads" description="No assets have been requested this week." />
+            )}
+          </div>
+
           {/* Server Status */}
           <div class="bg-white dark:bg-slate-800 rounded-xl p-5 shadow transition-all duration-300 h-full flex flex-col border border-gray-200 dark:border-gray-700">
             <div class="flex items-center gap-3 mb-6">
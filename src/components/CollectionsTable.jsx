@@ -9,7 +9,7 @@ import '@shoelace-style/shoelace/dist/components/option/option.js';
 import { EmptyState } from "./EmptyState.jsx";
 import { DataTable } from "./DataTable.jsx";
 import { useSharedState } from "../pages/sharedState.jsx";
-import { stacApi } from "../services/api.js";
+import { stacApi, findThumbnailAsset } from "../services/api.js";
 import { AddCollectionDialog } from "./AddCollectionDialog.jsx";
 
 export const CollectionsTable = ({ collections, isLoading, searchQuery, onRefresh }) => {
@@ -143,6 +143,30 @@ export const CollectionsTable = ({ collections, isLoading, searchQuery, onRefres
 
   // DataTable columns definition
   const columns = [
+    {
+      key: 'thumbnail',
+      label: '',
+      width: '80px',
+      thStyle: 'text-align: center;',
+      render: (collection) => {
+        const thumbnailAsset = findThumbnailAsset(collection);
+        return (
+          <div class="flex justify-center">
+            {thumbnailAsset ? (
+              <img
+                src={thumbnailAsset.href}
+                alt="Thumbnail"
+                class="w-12 h-12 object-cover rounded border border-gray-200 dark:border-gray-600"
+              />
+            ) : (
+              <div class="w-12 h-12 bg-gray-100 dark:bg-slate-700 rounded border border-gray-200 dark:border-gray-600 flex items-center justify-center">
+                <sl-icon name="collection" class="text-gray-400"></sl-icon>
+              </div>
+            )}
+          </div>
+        );
+      }
+    },
     {
       key: 'title',
       label: 'Title',
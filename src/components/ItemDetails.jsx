@@ -11,7 +11,7 @@ import { LeafletMap } from './LeafletMap.jsx';
 
 import { ShoelaceDialog } from './ShoelaceDialog.jsx';
 import { UploadAssetDialog } from './UploadAssetDialog.jsx';
-import { stacApi } from '../services/api.js';
+import { stacApi, findThumbnailAsset } from '../services/api.js';
 
 export const ItemDetails = (props) => {
   const { item, onBack, onUpdate } = props;
@@ -36,6 +36,18 @@ export const ItemDetails = (props) => {
   const [showPropertiesEditor, setShowPropertiesEditor] = createSignal(false);
   const [showUploadAssetDialog, setShowUploadAssetDialog] = createSignal(false);
   
+  // Simplified geometry for the map preview; the full geometry stays in the item
+  const [footprint, setFootprint] = createSignal(null);
+
+  createEffect(() => {
+    const current = currentItem();
+    setFootprint(null);
+    if (!current?.geometry) return;
+    stacApi.getItemFootprint(current.collection, current.id)
+      .then(setFootprint)
+      .catch((e) => console.error('Failed to load item footprint:', e));
+  });
+
   // Temporary state for dialog editing
   const [tempBboxData, setTempBboxData] = createSignal('');
   const [tempGeometryData, setTempGeometryData] = createSignal('');
@@ -131,6 +143,16 @@ export const ItemDetails = (props) => {
   };
 
 
+  // Use this item's preview as its collection's thumbnail
+  const handlePinThumbnail = async () => {
+    try {
+      await stacApi.pinCollectionThumbnail(currentItem().collection, currentItem().id);
+      if (onUpdate) await onUpdate();
+    } catch (e) {
+      console.error('Failed to pin thumbnail:', e);
+    }
+  };
+
   const handleExploreCollection = () => {
     window.history.pushState({}, '', `/collections/${item.collection}`);
     window.dispatchEvent(new PopStateEvent('popstate'));
@@ -360,7 +382,7 @@ export const ItemDetails = (props) => {
       {/* Map Preview Section */}
       {(ei().geometry || ei().bbox) && (
         <div class="details-section mb-6 p-6 bg-white dark:bg-slate-800 rounded-lg shadow border border-gray-200 dark:border-gray-700">
-          <LeafletMap geometry={ei().geometry} bbox={ei().bbox} id={`item-details-map-${ei().id}`} />
+          <LeafletMap geometry={isEditing() ? ei().geometry : (footprint() || ei().geometry)} bbox={ei().bbox} id={`item-details-map-${ei().id}`} />
         </div>
       )}
 
@@ -497,14 +519,26 @@ export const ItemDetails = (props) => {
         <div class="flex justify-between items-center mb-4">
           <h3 class="text-lg font-semibold dark:text-white">Assets</h3>
           {!isEditing() && (
-            <sl-button
-              variant="default"
-              size="small"
-              onClick={() => setShowUploadAssetDialog(true)}
-            >
-              <sl-icon slot="prefix" name="image"></sl-icon>
-              Upload Asset
-            </sl-button>
+            <div class="flex gap-2">
+              {findThumbnailAsset(currentItem()) && (
+                <sl-button
+                  variant="default"
+                  size="small"
+                  onClick={handlePinThumbnail}
+                >
+                  <sl-icon slot="prefix" name="pin-angle"></sl-icon>
+                  Use as Collection Thumbnail
+                </sl-button>
+              )}
+              <sl-button
+                variant="default"
+                size="small"
+                onClick={() => setShowUploadAssetDialog(true)}
+              >
+                <sl-icon slot="prefix" name="image"></sl-icon>
+                Upload Asset
+              </sl-button>
+            </div>
           )}
         </div>
         
@@ -1,4 +1,4 @@
-import { onMount, onCleanup, createSignal } from 'solid-js';
+import { onMount, onCleanup, createSignal, createEffect, on } from 'solid-js';
 import L from 'leaflet';
 import 'leaflet/dist/leaflet.css';
 import { TerraDraw } from 'terra-draw';
@@ -9,7 +9,7 @@ import {
   TerraDrawPolygonMode,
   TerraDrawSelectMode
 } from 'terra-draw';
-import { getCurrentBasemap, getBasemapByValue } from '../services/basemapService.js';
+import { getCurrentBasemap, getResolvedBasemapByValue } from '../services/basemapService.js';
 
 const ALL_MODES = [
   { key: 'edit', icon: 'hand-index', label: 'Edit' },
@@ -70,9 +70,9 @@ export const LeafletMap = (props) => {
   const [basemap, setBasemap] = createSignal(null);
 
   // Handle basemap change events
-  const handleBasemapChange = (event) => {
+  const handleBasemapChange = async (event) => {
     const newBasemapValue = event.detail.basemap;
-    const newBasemap = getBasemapByValue(newBasemapValue);
+    const newBasemap = await getResolvedBasemapByValue(newBasemapValue);
     setBasemap(newBasemap);
     
     if (map && tileLayer) {
@@ -117,6 +117,11 @@ export const LeafletMap = (props) => {
     }
   };
 
+  // Redraw viewing maps when the geometry they show changes
+  createEffect(on(() => props.geometry, () => {
+    if (!isDrawingMap) displayExistingGeometry();
+  }, { defer: true }));
+
   onMount(async () => {
     
     // Both viewing and drawing maps now need both containers
@@ -4,6 +4,8 @@ import '@shoelace-style/shoelace/dist/components/button/button.js';
 import '@shoelace-style/shoelace/dist/components/input/input.js';
 import '@shoelace-style/shoelace/dist/components/select/select.js';
 import '@shoelace-style/shoelace/dist/components/option/option.js';
+import '@shoelace-style/shoelace/dist/components/tag/tag.js';
+import '@shoelace-style/shoelace/dist/components/checkbox/checkbox.js';
 import { ShoelaceDialog } from "./ShoelaceDialog.jsx";
 import { createSignal, createEffect, onMount } from 'solid-js';
 import { invoke } from '@tauri-apps/api/core';
@@ -14,6 +16,7 @@ import { refreshApiConfiguration } from '../services/api.js';
 export const SettingsDialog = ({ openState }) => {
   const [isOpen, setIsOpen] = openState;
   const [selectedBasemap, setSelectedBasemap] = createSignal('openstreetmap');
+  const [tileCacheEnabled, setTileCacheEnabled] = createSignal(false);
   const [darkMode] = darkModeSignal;
   
   // Server configuration state
@@ -21,9 +24,12 @@ export const SettingsDialog = ({ openState }) => {
   const [externalAddress, setExternalAddress] = createSignal('127.0.0.1');
   const [port, setPort] = createSignal(3000);
   const [isRestarting, setIsRestarting] = createSignal(false);
+  const [addressSuggestions, setAddressSuggestions] = createSignal([]);
+  const [isDetectingAddress, setIsDetectingAddress] = createSignal(false);
+  const [verifyExternalUrl, setVerifyExternalUrl] = createSignal(false);
   const [showAdvancedSettings, setShowAdvancedSettings] = createSignal(false);
   
-  let basemapSelect, darkModeSwitch;
+  let basemapSelect, darkModeSwitch, tileCacheSwitch;
 
   // Add handler placeholders at the top of the component
   const handleStopServer = async () => {
@@ -65,6 +71,7 @@ export const SettingsDialog = ({ openState }) => {
         }
       };
       await loadSetting('basemap', setSelectedBasemap);
+      await loadSetting('basemap_cache_enabled', setTileCacheEnabled, v => v === 'true');
       
       // Load server configuration
       await loadSetting('server_internal_address', setInternalAddress);
@@ -78,6 +85,7 @@ export const SettingsDialog = ({ openState }) => {
         if (serverConfig.internal_address) setInternalAddress(serverConfig.internal_address);
         if (serverConfig.external_address) setExternalAddress(serverConfig.external_address);
         if (serverConfig.port) setPort(serverConfig.port);
+        setAddressSuggestions(serverConfig.suggested_external_addresses || []);
       } catch (error) {
         console.error('Failed to load server config from backend:', error);
       }
@@ -99,11 +107,29 @@ export const SettingsDialog = ({ openState }) => {
         }));
     
       }
+      if (key === 'basemap_cache_enabled') {
+        window.dispatchEvent(new CustomEvent('basemap-changed', {
+          detail: { basemap: selectedBasemap() }
+        }));
+      }
     } catch (error) {
       console.error(`Failed to save setting ${key}:`, error);
     }
   };
 
+  // Query the STUN server for the public address in addition to LAN addresses
+  const detectPublicAddress = async () => {
+    setIsDetectingAddress(true);
+    try {
+      const serverConfig = await invoke('get_server_config', { detectPublicAddress: true });
+      setAddressSuggestions(serverConfig.suggested_external_addresses || []);
+    } catch (error) {
+      console.error('Failed to detect public address:', error);
+    } finally {
+      setIsDetectingAddress(false);
+    }
+  };
+
   // Save server configuration and restart server
   const saveServerConfig = async () => {
     try {
@@ -122,18 +148,13 @@ export const SettingsDialog = ({ openState }) => {
       setIsRestarting(true);
   
       
-      // Save all server settings
-      await Promise.all([
-        saveSetting('server_internal_address', internalAddress()),
-        saveSetting('server_external_address', externalAddress()),
-        saveSetting('server_port', port())
-      ]);
-      
-      // Update server configuration and restart
+      // Update server configuration and restart; the backend persists the
+      // settings (after the external URL check when enabled)
       const result = await invoke('update_server_config', {
         internalAddress: internalAddress(),
         externalAddress: externalAddress(),
-        port: port()
+        port: port(),
+        verifyExternalUrl: verifyExternalUrl()
       });
       
   
@@ -154,7 +175,7 @@ export const SettingsDialog = ({ openState }) => {
 
   // Set up event listeners for switches and select
   onMount(() => {
-    let handleDarkModeChange, handleBasemapChange;
+    let handleDarkModeChange, handleBasemapChange, handleTileCacheChange;
     
     // Set up event listener for dark mode switch
     if (darkModeSwitch) {
@@ -174,6 +195,15 @@ export const SettingsDialog = ({ openState }) => {
       basemapSelect.addEventListener('sl-change', handleBasemapChange);
     }
     
+    // Set up event listener for tile cache switch
+    if (tileCacheSwitch) {
+      handleTileCacheChange = (e) => {
+        setTileCacheEnabled(e.target.checked);
+        saveSetting('basemap_cache_enabled', e.target.checked);
+      };
+      tileCacheSwitch.addEventListener('sl-change', handleTileCacheChange);
+    }
+    
     // Clean up function
     return () => {
       if (darkModeSwitch && handleDarkModeChange) {
@@ -182,6 +212,9 @@ export const SettingsDialog = ({ openState }) => {
       if (basemapSelect && handleBasemapChange) {
         basemapSelect.removeEventListener('sl-change', handleBasemapChange);
       }
+      if (tileCacheSwitch && handleTileCacheChange) {
+        tileCacheSwitch.removeEventListener('sl-change', handleTileCacheChange);
+      }
     };
   });
 
@@ -225,6 +258,17 @@ export const SettingsDialog = ({ openState }) => {
                 ))}
               </sl-select>
             </div>
+            <div class="flex items-center justify-between">
+              <div>
+                <div class="font-medium text-gray-900 dark:text-white">Offline Tile Cache</div>
+                <div class="text-sm text-gray-600 dark:text-gray-300">Keep viewed map tiles on disk so maps work without a connection</div>
+              </div>
+              <sl-switch
+                ref={el => (tileCacheSwitch = el)}
+                size="large"
+                checked={tileCacheEnabled()}
+              />
+            </div>
           </div>
         </div>
 
@@ -291,6 +335,26 @@ export const SettingsDialog = ({ openState }) => {
                   help-text="External server address for production access"
                   onInput={(e) => setExternalAddress(e.target.value)}
                 />
+                <div class="flex flex-wrap items-center gap-2 mt-2">
+                  {addressSuggestions().map(suggestion => (
+                    <sl-tag
+                      size="small"
+                      variant={suggestion.source === 'public' ? 'primary' : 'neutral'}
+                      class="cursor-pointer"
+                      title={suggestion.interface || 'Public address (STUN)'}
+                      onClick={() => setExternalAddress(suggestion.address)}
+                    >
+                      {suggestion.address}
+                    </sl-tag>
+                  ))}
+                  <sl-button
+                    size="small"
+                    loading={isDetectingAddress()}
+                    onClick={detectPublicAddress}
+                  >
+                    Detect Public Address
+                  </sl-button>
+                </div>
               </div>
               <div>
                 <label class="block text-sm font-medium text-gray-700 dark:text-gray-200 mb-2">Port</label>
@@ -305,6 +369,14 @@ export const SettingsDialog = ({ openState }) => {
                   onInput={(e) => setPort(parseInt(e.target.value, 10) || 3000)}
                 />
               </div>
+              <div>
+                <sl-checkbox
+                  checked={verifyExternalUrl()}
+                  on:sl-change={(e) => setVerifyExternalUrl(e.target.checked)}
+                >
+                  Check that the external URL reaches this server before saving
+                </sl-checkbox>
+              </div>
               <div class="pt-2">
                 <sl-button
                   variant="primary"
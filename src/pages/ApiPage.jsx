@@ -305,7 +305,11 @@ export const ApiPage = () => {
       return JSON.stringify({ error: response.message }, null, 2);
     }
     
-    return JSON.stringify(response.data, null, 2);
+    const headers = Object.entries(response.headers || {})
+      .map(([name, value]) => `${name}: ${value}`)
+      .join('\n');
+    const data = typeof response.data === 'string' ? response.data : JSON.stringify(response.data, null, 2);
+    return headers ? `${headers}\n\n${data}` : data;
   };
 
   const getParameterInput = (param, endpointKey) => {
@@ -522,10 +526,13 @@ export const ApiPage = () => {
                               </sl-button>
                               
                               {response && (
-                                <sl-badge variant={response.error ? 'danger' : 'success'}>
+                                <sl-badge variant={response.error || response.status >= 400 ? 'danger' : 'success'}>
                                   {response.error ? 'Error' : `Status: ${response.status}`}
                                 </sl-badge>
                               )}
+                              {response && !response.error && (
+                                <span class="text-xs text-gray-500">{response.durationMs} ms</span>
+                              )}
                             </div>
 
                             {/* Response */}
@@ -112,6 +112,7 @@ const createSharedState = () => {
           temporal: { interval: [["1900-01-01T00:00:00Z", null]] }
         },
         links: collection.links || [],
+        assets: collection.assets || {},
         summaries: collection.summaries || {},
         itemCount: 0, // Will be calculated when items are loaded
         spatialCoverage: collection.spatialCoverage || "Global",
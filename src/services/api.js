@@ -397,6 +397,38 @@ class STACApiService {
     }
   }
 
+  // Catalog request through the internal Tauri IPC layer, so the desktop UI
+  // keeps working when the public server is stopped or restricted
+  async invokeCatalog(command, args, endpoint, method = 'GET') {
+    const requestType = this.getRequestType(endpoint, method);
+    const shouldTrack = requestType !== null;
+    const activityMessage = shouldTrack ? this.getActivityMessage(endpoint, method, requestType) : null;
+
+    try {
+      const data = await invoke(command, args);
+
+      if (shouldTrack) {
+        activityTracker.addActivity(requestType, activityMessage, endpoint, 'success');
+      }
+
+      if (method === 'DELETE' && !data) {
+        return { success: true, message: 'Resource deleted successfully' };
+      }
+      return data;
+    } catch (error) {
+      if (shouldTrack) {
+        activityTracker.addActivity(
+          requestType,
+          activityMessage || `Failed ${method} request`,
+          endpoint,
+          'error'
+        );
+      }
+
+      throw error instanceof Error ? error : new Error(error);
+    }
+  }
+
   // Helper method to generate activity messages
   getActivityMessage(endpoint, method, type) {
     const parts = endpoint.split('/');
@@ -550,59 +582,51 @@ class STACApiService {
   }
 
   // Execute API request for the interactive API page
+  // API playground request, run through the backend router in-process so it
+  // works even where loopback connections are blocked
   async executeApiRequest(path, method = 'GET', body = null) {
-    if (!this.baseUrl) {
-      await this.initializeBaseUrl();
-    }
-    const url = `${this.baseUrl}${path}`;
-
-
-    const requestOptions = {
-      method,
-      headers: {
-        'Content-Type': 'application/json',
-      },
+    const response = await invoke('execute_api_request', { method, path, body });
+    return {
+      status: response.status,
+      headers: response.headers,
+      data: response.body,
+      durationMs: response.duration_ms
     };
-
-    if (body) {
-      requestOptions.body = JSON.stringify(body);
-    }
-
-    try {
-      const response = await fetch(url, requestOptions);
-      const data = await response.json();
-      return { status: response.status, data };
-    } catch (error) {
-      throw error;
-    }
   }
 
   // Collections
   async getCollections() {
-    const response = await this.makeRequest('/collections');
+    const response = await this.invokeCatalog('list_collections', {}, '/collections');
     return response.collections || [];
   }
 
   async getCollection(collectionId) {
-    return this.makeRequest(`/collections/${collectionId}`);
+    return this.invokeCatalog('get_collection', { collectionId }, `/collections/${collectionId}`);
   }
 
   // Items
   async getItems(collectionId, filters = {}) {
-    const queryParams = new URLSearchParams();
-    
-    if (filters.limit) queryParams.append('limit', filters.limit);
-    if (filters.offset) queryParams.append('offset', filters.offset);
-    if (filters.datetime) queryParams.append('datetime', filters.datetime);
-    if (filters.bbox) queryParams.append('bbox', filters.bbox.join(','));
-    
-    const endpoint = `/collections/${collectionId}/items${queryParams.toString() ? `?${queryParams.toString()}` : ''}`;
-    const response = await this.makeRequest(endpoint);
+    const response = await this.invokeCatalog('list_items', {
+      collectionId,
+      limit: filters.limit || null,
+      offset: filters.offset || null,
+      bbox: filters.bbox || null,
+      datetime: filters.datetime || null
+    }, `/collections/${collectionId}/items`);
     return response.features || [];
   }
 
   async getItem(collectionId, itemId) {
-    return this.makeRequest(`/collections/${collectionId}/items/${itemId}`);
+    return this.invokeCatalog('get_item', { collectionId, itemId }, `/collections/${collectionId}/items/${itemId}`);
+  }
+
+  async getItemFootprint(collectionId, itemId, options = {}) {
+    return this.invokeCatalog('get_item_footprint', {
+      collectionId,
+      itemId,
+      tolerance: options.tolerance ?? null,
+      crs: options.crs || null
+    }, `/collections/${collectionId}/items/${itemId}/footprint`);
   }
 
   // Search
@@ -615,6 +639,7 @@ class STACApiService {
       ids: searchParams.ids || null,
       limit: searchParams.limit || 10,
       query: searchParams.query || {},
+      q: searchParams.q || null,
       filter: searchParams.filter || null,
       sortby: searchParams.sortby || null,
       fields: searchParams.fields || null,
@@ -628,10 +653,7 @@ class STACApiService {
       }
     });
 
-    const response = await this.makeRequest('/search', {
-      method: 'POST',
-      body: JSON.stringify(body)
-    });
+    const response = await this.invokeCatalog('search_items', { search: body }, '/search', 'POST');
 
     return response;
   }
@@ -690,6 +712,19 @@ class STACApiService {
     return Object.keys(query).length > 0 ? query : null;
   }
 
+  // Geometry editor helpers (run in the backend, no JS geometry stack needed)
+  async wktToGeojson(wkt) {
+    return invoke('geometry_tool', { request: { operation: 'wkt_to_geojson', wkt } });
+  }
+
+  async bufferPoint(geometry, distance, segments = 32) {
+    return invoke('geometry_tool', { request: { operation: 'buffer', geometry, distance, segments } });
+  }
+
+  async geometryBbox(geometry) {
+    return invoke('geometry_tool', { request: { operation: 'bbox', geometry } });
+  }
+
   // Health and status checks
   async healthCheck() {
     if (!this.baseUrl) {
@@ -783,6 +818,20 @@ class STACApiService {
     }
   }
 
+  // Most requested collections, items or assets over the last `days` days
+  async getAccessStats(kind = 'asset', days = 7, limit = 10) {
+    try {
+      return await invoke('get_access_stats', { kind, days, limit });
+    } catch (error) {
+      return { since: null, days, stats: [] };
+    }
+  }
+
+  // Pin an item's thumbnail as its collection's; a null itemId unpins
+  async pinCollectionThumbnail(collectionId, itemId) {
+    return invoke('pin_collection_thumbnail', { collectionId, itemId });
+  }
+
   async uploadRasterTiff(collectionId, itemId, bandName, tiffBytes) {
     try {
       return await invoke('upload_raster_tiff', {
@@ -827,15 +876,18 @@ class STACApiService {
     }
   }
 
-  // Manual cleanup for orphaned collection directories
-  async cleanupOrphanedCollectionDirectories() {
+  // Reports asset files no collection or item refers to; with confirm
+  // removes them
+  async cleanupOrphanedCollectionDirectories(confirm = false) {
     try {
-      const result = await invoke('cleanup_orphaned_collection_directories');
+      const result = await invoke('cleanup_orphaned_collection_directories', { confirm });
       
       // Track cleanup activity
       activityTracker.addActivity(
         'collection_cleanup', 
-        'Started cleanup of orphaned collection directories', 
+        result.dry_run
+          ? `Found ${result.orphaned_count} orphaned asset files`
+          : `Removed ${result.removed_files} orphaned asset files`, 
         '/cleanup/orphaned-collections', 
         'success'
       );
@@ -857,23 +909,15 @@ class STACApiService {
 
   // Collection management
   async deleteCollection(collectionId) {
-    return this.makeRequest(`/collections/${collectionId}`, {
-      method: 'DELETE'
-    });
+    return this.invokeCatalog('delete_collection', { collectionId }, `/collections/${collectionId}`, 'DELETE');
   }
 
   async updateCollection(collectionId, updates) {
-    return this.makeRequest(`/collections/${collectionId}`, {
-      method: 'PUT',
-      body: JSON.stringify(updates)
-    });
+    return this.invokeCatalog('update_collection', { collectionId, collection: updates }, `/collections/${collectionId}`, 'PUT');
   }
 
   async putCollection(collectionId, collectionData) {
-    return this.makeRequest(`/collections/${collectionId}`, {
-      method: 'PUT',
-      body: JSON.stringify(collectionData)
-    });
+    return this.invokeCatalog('update_collection', { collectionId, collection: collectionData }, `/collections/${collectionId}`, 'PUT');
   }
 
   async createCollection(collectionData) {
@@ -884,17 +928,12 @@ class STACApiService {
       description: collectionData.description,
       license: collectionData.license
     };
-    return this.makeRequest('/collections', {
-      method: 'POST',
-      body: JSON.stringify(minimal)
-    });
+    return this.invokeCatalog('create_collection', { collection: minimal }, '/collections', 'POST');
   }
 
   // Item management
   async deleteItem(collectionId, itemId, triggerCleanup = true) {
-    const response = await this.makeRequest(`/collections/${collectionId}/items/${itemId}`, {
-      method: 'DELETE'
-    });
+    const response = await this.invokeCatalog('delete_item', { collectionId, itemId }, `/collections/${collectionId}/items/${itemId}`, 'DELETE');
     
     // The backend now automatically triggers cleanup, but we can also manually trigger it if needed
     if (triggerCleanup) {
@@ -906,24 +945,15 @@ class STACApiService {
   }
 
   async updateItem(collectionId, itemId, updates) {
-    return this.makeRequest(`/collections/${collectionId}/items/${itemId}`, {
-      method: 'PUT',
-      body: JSON.stringify(updates)
-    });
+    return this.invokeCatalog('update_item', { collectionId, itemId, item: updates }, `/collections/${collectionId}/items/${itemId}`, 'PUT');
   }
 
   async putItem(collectionId, itemId, itemData) {
-    return this.makeRequest(`/collections/${collectionId}/items/${itemId}`, {
-      method: 'PUT',
-      body: JSON.stringify(itemData)
-    });
+    return this.invokeCatalog('update_item', { collectionId, itemId, item: itemData }, `/collections/${collectionId}/items/${itemId}`, 'PUT');
   }
 
   async createItem(collectionId, itemData) {
-    return this.makeRequest(`/collections/${collectionId}/items`, {
-      method: 'POST',
-      body: JSON.stringify(itemData)
-    });
+    return this.invokeCatalog('create_item', { collectionId, item: itemData }, `/collections/${collectionId}/items`, 'POST');
   }
 
   // File upload methods
@@ -44,13 +44,40 @@ export async function getCurrentBasemap() {
   try {
     const basemapValue = await invoke('get_user_pref', { key: 'basemap' });
     const selectedBasemap = basemapValue || 'openstreetmap';
-    return basemapOptions.find(option => option.value === selectedBasemap) || basemapOptions[0];
+    const basemap = basemapOptions.find(option => option.value === selectedBasemap) || basemapOptions[0];
+    return await withTileCache(basemap);
   } catch (error) {
     console.error('Failed to load basemap setting:', error);
     return basemapOptions[0]; // Default to OpenStreetMap
   }
 }
 
+// Get basemap configuration by value, routed through the tile cache when enabled
+export async function getResolvedBasemapByValue(value) {
+  return withTileCache(getBasemapByValue(value));
+}
+
+// When the offline tile cache is enabled, point the basemap at the server's
+// /basemap proxy and make the proxy fetch from this basemap's source
+async function withTileCache(basemap) {
+  try {
+    const enabled = await invoke('get_user_pref', { key: 'basemap_cache_enabled' });
+    if (enabled !== 'true') return basemap;
+
+    const upstream = basemap.url.replace('{s}', 'a').replace('{r}', '');
+    const current = await invoke('get_user_pref', { key: 'basemap_upstream_url' });
+    if (current !== upstream) {
+      await invoke('set_user_pref', { key: 'basemap_upstream_url', value: upstream });
+    }
+
+    const { internal_address, port } = await invoke('get_server_config');
+    return { ...basemap, url: `http://${internal_address}:${port}/v1/basemap/{z}/{x}/{y}` };
+  } catch (error) {
+    console.error('Failed to enable basemap tile cache:', error);
+    return basemap;
+  }
+}
+
 // Get basemap configuration by value
 export function getBasemapByValue(value) {
   return basemapOptions.find(option => option.value === value) || basemapOptions[0];